@@ -0,0 +1,80 @@
+/*
+ * C API for embedding Kalix in other applications.
+ *
+ * Build the shared library (kalix.dll / libkalix.so / libkalix.dylib) with:
+ *
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * A model is an opaque handle from kalix_load_model, released with kalix_free.
+ * Other functions return KALIX_OK (0) on success or one of the KALIX_ERR_* codes,
+ * with a description from kalix_last_error. Strings are null-terminated UTF-8.
+ * Series values are copied into buffers owned by the caller. A handle must not be
+ * used from two threads at once.
+ */
+#ifndef KALIX_H
+#define KALIX_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define KALIX_OK 0
+#define KALIX_ERR_NULL_ARGUMENT 1     /* A required pointer argument was null */
+#define KALIX_ERR_INVALID_UTF8 2      /* A string argument was not valid UTF-8 */
+#define KALIX_ERR_LOAD 3              /* The model could not be read or configured */
+#define KALIX_ERR_RUN 4               /* The simulation failed */
+#define KALIX_ERR_SERIES_NOT_FOUND 5  /* No series has the given name */
+#define KALIX_ERR_BUFFER_TOO_SMALL 6  /* The buffer is too small; the required length is still written */
+#define KALIX_ERR_PARAMETER 7         /* The parameter target is invalid or the value was rejected */
+#define KALIX_ERR_WRITE 8             /* Writing outputs to a file failed */
+#define KALIX_ERR_PANIC 99            /* Kalix panicked; do not use the handle again */
+
+typedef struct KalixModel KalixModel;
+
+/* The time grid of a series: len values from start_timestamp (signed Unix seconds),
+   step_size seconds apart. */
+typedef struct KalixSeriesInfo {
+    int64_t start_timestamp;
+    uint64_t step_size;
+    size_t len;
+} KalixSeriesInfo;
+
+/* Load and configure a model from an INI file, or from the contents of one. */
+int32_t kalix_load_model(const char *path, KalixModel **out_model);
+int32_t kalix_load_model_string(const char *ini, KalixModel **out_model);
+
+/* Run the simulation. A model may be run any number of times. */
+int32_t kalix_run(KalixModel *model);
+
+/* The time grid of a result series, e.g. "node.gauge.dsflow". */
+int32_t kalix_get_series_info(const KalixModel *model, const char *name, KalixSeriesInfo *out_info);
+
+/* Copy a result series into buffer (buffer_len doubles). The number of values is written to
+   out_len (if not NULL) even when the buffer is too small. Missing values are NaN. */
+int32_t kalix_get_series(const KalixModel *model, const char *name,
+                         double *buffer, size_t buffer_len, size_t *out_len);
+
+/* Parameters by target address, e.g. "node.my_sac.uzfwm" or "c.demand_factor". */
+int32_t kalix_set_parameter(KalixModel *model, const char *target, double value);
+int32_t kalix_get_parameter(const KalixModel *model, const char *target, double *out_value);
+
+/* Write the model's outputs to a .csv or .pxb file. */
+int32_t kalix_write_outputs(const KalixModel *model, const char *path);
+
+/* Release a model handle. NULL is ignored. */
+void kalix_free(KalixModel *model);
+
+/* The last error on the calling thread ("" after a successful call). Valid until the next call. */
+const char *kalix_last_error(void);
+
+/* The Kalix version, e.g. "0.3.3". */
+const char *kalix_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KALIX_H */
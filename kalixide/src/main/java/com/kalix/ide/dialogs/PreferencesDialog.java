@@ -865,12 +865,12 @@ public class PreferencesDialog extends JDialog {
             });
             formPanel.add(logScaleMinField, gbc);
 
-            // STDIO data format setting (pixie vs csv for get_result responses)
+            // STDIO data format setting (pixie, f64 or csv for get_result responses)
             gbc.gridx = 0; gbc.gridy = 4; gbc.gridwidth = 1; gbc.weightx = 0; gbc.fill = GridBagConstraints.NONE;
             formPanel.add(new JLabel("STDIO data format:"), gbc);
 
             gbc.gridx = 1; gbc.fill = GridBagConstraints.HORIZONTAL; gbc.weightx = 1.0;
-            stdioFormatComboBox = new JComboBox<>(new String[]{"pixie", "csv"});
+            stdioFormatComboBox = new JComboBox<>(new String[]{"pixie", "f64", "csv"});
             stdioFormatComboBox.setRenderer(new DefaultListCellRenderer() {
                 @Override
                 public Component getListCellRendererComponent(JList<?> list, Object value, int index,
@@ -878,6 +878,8 @@ public class PreferencesDialog extends JDialog {
                     super.getListCellRendererComponent(list, value, index, isSelected, cellHasFocus);
                     if ("pixie".equals(value)) {
                         setText("Compressed (pixie, recommended)");
+                    } else if ("f64".equals(value)) {
+                        setText("Uncompressed binary (f64, fastest on local sessions)");
                     } else if ("csv".equals(value)) {
                         setText("Plain text (csv, debug)");
                     }
@@ -886,7 +888,8 @@ public class PreferencesDialog extends JDialog {
             });
             stdioFormatComboBox.setToolTipText(
                 "Wire format for timeseries results from kalixcli. 'pixie' uses Gorilla compression "
-                + "(smaller, faster); 'csv' is human-readable plain text (larger, slower).");
+                + "(smaller, faster); 'f64' sends raw binary values (larger, least CPU); "
+                + "'csv' is human-readable plain text (larger, slower).");
             stdioFormatComboBox.setSelectedItem(
                 PreferenceManager.getFileString(PreferenceKeys.STDIO_DATA_FORMAT, "pixie"));
             stdioFormatComboBox.addActionListener(e -> {
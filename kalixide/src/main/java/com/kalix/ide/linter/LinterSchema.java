@@ -181,6 +181,14 @@ public class LinterSchema {
                 }
             }
 
+            // Parse optional parameter patterns (parameters whose names vary, e.g. by date)
+            JsonNode patternsNode = typeNode.path("optional_param_patterns");
+            if (patternsNode.isArray()) {
+                for (JsonNode pattern : patternsNode) {
+                    nodeType.optionalParamPatterns.add(Pattern.compile(pattern.asText()));
+                }
+            }
+
             // Parse downstream node parameters
             JsonNode dsnodeNode = typeNode.path("dsnode_params");
             if (dsnodeNode.isArray()) {
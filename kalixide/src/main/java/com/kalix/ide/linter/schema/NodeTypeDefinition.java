@@ -1,9 +1,12 @@
 package com.kalix.ide.linter.schema;
 
+import java.util.ArrayList;
 import java.util.HashMap;
 import java.util.HashSet;
+import java.util.List;
 import java.util.Map;
 import java.util.Set;
+import java.util.regex.Pattern;
 
 /**
  * Defines a node type in the linter schema including its parameters and validation rules.
@@ -13,6 +16,7 @@ public class NodeTypeDefinition {
     public String description;
     public Set<String> requiredParams = new HashSet<>();
     public Set<String> optionalParams = new HashSet<>();
+    public List<Pattern> optionalParamPatterns = new ArrayList<>();
     public Set<String> dsnodeParams = new HashSet<>();
     public Set<String> allowedOutputs = new HashSet<>();
     public Map<String, ParameterDefinition> parameterDefinitions = new HashMap<>();
@@ -25,6 +29,17 @@ public class NodeTypeDefinition {
         return all;
     }
 
+    /**
+     * True if the parameter is one of the allowed parameters or matches an optional parameter
+     * pattern, e.g. a dated storage dimensions table.
+     */
+    public boolean isAllowedParam(String paramName) {
+        if (getAllowedParams().contains(paramName)) {
+            return true;
+        }
+        return optionalParamPatterns.stream().anyMatch(p -> p.matcher(paramName).matches());
+    }
+
     public ParameterDefinition getParameterDefinition(String paramName) {
         return parameterDefinitions.get(paramName);
     }
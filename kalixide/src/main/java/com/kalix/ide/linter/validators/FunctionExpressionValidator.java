@@ -16,7 +16,8 @@ import java.util.concurrent.ConcurrentHashMap;
  * - Constant references: "c.pi", "c.node_1_demand_levels.high"
  * - Node output references: "node.node13_inflow.ds_1"
  * - This references: "this.dsflow", "this.volume" (shorthand for current node outputs)
- * - Sim references: "sim.year", "sim.month", "sim.day", "sim.day_of_year", "sim.step"
+ * - Table references: "tables.rating_curve" (as the first argument of lookup)
+ * - Sim references: "sim.year", "sim.month", "sim.day", "sim.day_of_year", "sim.days_in_month", "sim.is_leap_year", "sim.step"
  * - Constant expressions: "5.0", "2 + 3"
  * - Complex functions: "if(data.temp > 20, 10.0, 5.0) * 1.2"
  *
@@ -29,12 +30,12 @@ public class FunctionExpressionValidator {
         new ConcurrentHashMap<>(100);
     private static final int MAX_CACHE_SIZE = 500;
 
-    // Known functions with their argument counts (-1 = variable args, must be >= 2)
+    // Known functions with their argument counts (-1 = variable args, must be >= 2; -2 = 1 or 2 args)
     private static final Map<String, Integer> KNOWN_FUNCTIONS = createFunctionMap();
 
     // Known simulation variables
     private static final Set<String> KNOWN_SIM_VARIABLES = Set.of(
-        "sim.year", "sim.month", "sim.day", "sim.day_of_year", "sim.step"
+        "sim.year", "sim.month", "sim.day", "sim.day_of_year", "sim.days_in_month", "sim.is_leap_year", "sim.step"
     );
 
     private static Map<String, Integer> createFunctionMap() {
@@ -73,6 +74,28 @@ public class FunctionExpressionValidator {
         map.put("pow", 2);
         map.put("atan2", 2);
 
+        // Table lookup: (table name, x)
+        map.put("lookup", 2);
+
+        // Rolling windows over the previous timesteps: (series, number of timesteps)
+        map.put("mavg_last", 2);
+        map.put("sum_last", 2);
+        map.put("min_last", 2);
+        map.put("max_last", 2);
+
+        // Running values kept between timesteps: (value, optional reset condition)
+        map.put("accumulate", -2);
+        map.put("counter", -2);
+
+        // Simulation time (same as the sim.* variables)
+        map.put("year", 0);
+        map.put("month", 0);
+        map.put("day", 0);
+        map.put("day_of_year", 0);
+        map.put("days_in_month", 0);
+        map.put("is_leap_year", 0);
+        map.put("step", 0);
+
         return Collections.unmodifiableMap(map);
     }
 
@@ -287,7 +310,7 @@ public class FunctionExpressionValidator {
     // ==================== Tokenizer ====================
 
     enum TokenType {
-        NUMBER, IDENT, DATA_REF, CONST_REF, NODE_REF, THIS_REF, SIM_REF, OPERATOR, LPAREN, RPAREN, COMMA, EOF
+        NUMBER, IDENT, DATA_REF, CONST_REF, NODE_REF, THIS_REF, SIM_REF, TABLE_REF, OPERATOR, LPAREN, RPAREN, COMMA, EOF
     }
 
     static class Token {
@@ -353,6 +376,17 @@ public class FunctionExpressionValidator {
                 return new Token(TokenType.COMMA, ",", pos - 1);
             }
 
+            // Quoted table names, e.g. lookup("tables.rating_curve", x)
+            if (ch == '"') {
+                int start = pos;
+                int end = input.indexOf('"', pos + 1);
+                if (end < 0) {
+                    throw new ParseException("Unterminated string at position " + pos);
+                }
+                pos = end + 1;
+                return new Token(TokenType.TABLE_REF, input.substring(start + 1, end), start);
+            }
+
             throw new ParseException("Unexpected character at position " + pos + ": '" + ch + "'");
         }
 
@@ -426,6 +460,11 @@ public class FunctionExpressionValidator {
                 return readDottedReference(start, firstSegment, TokenType.NODE_REF);
             }
 
+            // Check if this is a table reference (starts with "tables.")
+            if (firstSegment.equals("tables") && pos < input.length() && input.charAt(pos) == '.') {
+                return readDottedReference(start, firstSegment, TokenType.TABLE_REF);
+            }
+
             // Check if this is a sim reference (starts with "sim.")
             if (firstSegment.equals("sim") && pos < input.length() && input.charAt(pos) == '.') {
                 return readSimReference(start);
@@ -716,6 +755,11 @@ public class FunctionExpressionValidator {
             } else if (current.type == TokenType.THIS_REF) {
                 validateThisRef(current.value, errors);
                 advance();
+            } else if (current.type == TokenType.TABLE_REF) {
+                if (!current.value.toLowerCase().startsWith("tables.")) {
+                    errors.add("Table name '" + current.value + "' must start with 'tables.'");
+                }
+                advance();
             } else if (current.type == TokenType.IDENT) {
                 // Function call
                 parseFunctionCall(errors);
@@ -755,6 +799,8 @@ public class FunctionExpressionValidator {
                                " argument" + (expectedCount == 1 ? "" : "s") + ", but got " + argCount);
                 } else if (expectedCount == -1 && argCount < 2) {
                     errors.add("Function '" + funcName + "' expects at least 2 arguments, but got " + argCount);
+                } else if (expectedCount == -2 && (argCount < 1 || argCount > 2)) {
+                    errors.add("Function '" + funcName + "' expects 1 or 2 arguments, but got " + argCount);
                 }
             }
         }
@@ -832,7 +878,7 @@ public class FunctionExpressionValidator {
         private void validateSimReference(String simRef, List<String> errors) {
             // Check if the sim reference is one of the known variables
             if (!KNOWN_SIM_VARIABLES.contains(simRef)) {
-                errors.add("Unknown sim variable: '" + simRef + "'. Valid options are: sim.year, sim.month, sim.day, sim.day_of_year, sim.step");
+                errors.add("Unknown sim variable: '" + simRef + "'. Valid options are: sim.year, sim.month, sim.day, sim.day_of_year, sim.days_in_month, sim.is_leap_year, sim.step");
             }
         }
 
@@ -74,7 +74,7 @@ public class NodeValidator implements ValidationStrategy {
         String paramName = prop.getKey();
 
         // Check if parameter is allowed for this node type
-        if (!typeDef.getAllowedParams().contains(paramName)) {
+        if (!typeDef.isAllowedParam(paramName)) {
             result.addIssue(prop.getLineNumber(),
                           "Unknown parameter '" + paramName + "' for node type '" + typeDef.name + "'",
                           ValidationRule.Severity.WARNING, "unknown_parameter");
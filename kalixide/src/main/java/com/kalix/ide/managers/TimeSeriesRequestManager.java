@@ -12,9 +12,12 @@ import com.fasterxml.jackson.databind.ObjectMapper;
 import org.slf4j.Logger;
 import org.slf4j.LoggerFactory;
 
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
 import java.time.LocalDateTime;
 import java.time.ZoneOffset;
 import java.time.format.DateTimeFormatter;
+import java.util.Base64;
 import java.util.List;
 import java.util.Map;
 import java.util.concurrent.*;
@@ -351,6 +354,9 @@ public class TimeSeriesRequestManager {
             case "pixie":
                 timeSeriesData = decodePixiePayload(seriesName, dataString);
                 break;
+            case "f64":
+                timeSeriesData = decodeF64Payload(seriesName, dataString);
+                break;
             case "csv":
                 timeSeriesData = decodeCsvPayload(seriesName, dataString);
                 break;
@@ -402,6 +408,39 @@ public class TimeSeriesRequestManager {
         return new TimeSeriesData(dateTimes, values).densified();
     }
 
+    /**
+     * Decode a base64-encoded uncompressed f64 frame into TimeSeriesData.
+     * Frame layout (all little-endian): start timestamp (u64, Kalix offset-binary seconds),
+     * timestep seconds (u64), value count (u64), then the values as f64.
+     *
+     * <p>The {@code seriesName} argument is retained for diagnostic context only.</p>
+     */
+    static TimeSeriesData decodeF64Payload(String seriesName, String base64Data) {
+        ByteBuffer frame = ByteBuffer.wrap(Base64.getDecoder().decode(base64Data))
+            .order(ByteOrder.LITTLE_ENDIAN);
+        if (frame.remaining() < 24) {
+            throw new IllegalArgumentException("Invalid f64 frame for '" + seriesName + "': missing header");
+        }
+        // Kalix stores timestamps in offset-binary u64 (see decodePixiePayload).
+        long startSeconds = frame.getLong() ^ Long.MIN_VALUE;
+        long timestepSeconds = frame.getLong();
+        long count = frame.getLong();
+        if (count < 0 || frame.remaining() != count * 8) {
+            throw new IllegalArgumentException("Invalid f64 frame for '" + seriesName + "': expected "
+                + count + " values but found " + frame.remaining() + " bytes");
+        }
+
+        int n = (int) count;
+        LocalDateTime startTime = LocalDateTime.ofEpochSecond(startSeconds, 0, ZoneOffset.UTC);
+        LocalDateTime[] dateTimes = new LocalDateTime[n];
+        double[] values = new double[n];
+        for (int i = 0; i < n; i++) {
+            dateTimes[i] = startTime.plusSeconds(i * timestepSeconds);
+            values[i] = frame.getDouble();
+        }
+        return new TimeSeriesData(dateTimes, values);
+    }
+
     /**
      * Decode a CSV-format timeseries payload into TimeSeriesData.
      * Format: "start_timestamp,timestep_seconds,value1,value2,..."
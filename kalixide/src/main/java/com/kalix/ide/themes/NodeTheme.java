@@ -216,6 +216,7 @@ public class NodeTheme {
                     map.put("loss", "DAA520");           // Goldenrod - same as routing
                     map.put("splitter", "8B4513");       // Saddle Brown (index 6)
                     map.put("confluence", "8B4513");     // Saddle Brown - same as splitter
+                    map.put("transfer", "8B4513");       // Saddle Brown - same as splitter
                     map.put("gauge", "228B22");          // Forest Green (index 0)
                     map.put("order_control", "CCCC00"); // Yellow-lime (index 7)
                     // Unused colors from botanical palette
@@ -238,6 +239,7 @@ public class NodeTheme {
                     map.put("loss", "6495ED");           // Cornflower Blue - same as routing
                     map.put("splitter", "00CED1");       // Dark Turquoise (index 4)
                     map.put("confluence", "00CED1");     // Dark Turquoise - same as splitter
+                    map.put("transfer", "00CED1");       // Dark Turquoise - same as splitter
                     map.put("gauge", "87CEEB");          // Sky Blue (index 3)
                     map.put("order_control", "4682B4"); // Steel Blue (index 9)
                     break;
@@ -254,6 +256,7 @@ public class NodeTheme {
                     map.put("loss", "2F4F4F");           // Dark Slate Gray - same as routing
                     map.put("splitter", "FF6B35");       // Orange-Red (from palette index 0)
                     map.put("confluence", "FF6B35");     // Orange-Red - same as splitter
+                    map.put("transfer", "FF6B35");       // Orange-Red - same as splitter
                     map.put("gauge", "F7931E");          // Orange (from palette index 1)
                     map.put("order_control", "FFD23F"); // Yellow (from palette index 2)
                     break;
@@ -270,6 +273,7 @@ public class NodeTheme {
                     map.put("loss", "F9C74F");           // Yellow - same as routing
                     map.put("splitter", "F8961E");       // Orange (index 2)
                     map.put("confluence", "F8961E");     // Orange - same as splitter
+                    map.put("transfer", "F8961E");       // Orange - same as splitter
                     map.put("gauge", "F94144");          // Red (index 0)
                     map.put("order_control", "43AA8B"); // Teal (index 6)
                     break;
@@ -311,6 +315,7 @@ public class NodeTheme {
         Map.entry("loss", new ShapeTextMapping(NodeShape.SQUARE, "Lo")),
         Map.entry("gauge", new ShapeTextMapping(NodeShape.CIRCLE, "Ga")),
         Map.entry("splitter", new ShapeTextMapping(NodeShape.CIRCLE, "Sp")),
+        Map.entry("transfer", new ShapeTextMapping(NodeShape.SQUARE, "Tr")),
         Map.entry("order_control", new ShapeTextMapping(NodeShape.HEXAGON, "OC"))
     );
 
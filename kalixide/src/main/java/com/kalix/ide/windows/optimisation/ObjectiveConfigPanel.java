@@ -26,6 +26,8 @@ public class ObjectiveConfigPanel extends JPanel {
         "ONE_MINUS_NSE",
         "ONE_MINUS_LNSE",
         "ONE_MINUS_KGE",
+        "ONE_MINUS_KGE_PRIME",
+        "ONE_MINUS_KGE_NP",
         "ONE_MINUS_PEARS_R",
         "RMSE",
         "MAE",
@@ -0,0 +1,60 @@
+package com.kalix.ide.managers;
+
+import com.kalix.ide.flowviz.data.TimeSeriesData;
+import org.junit.jupiter.api.Test;
+
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+import java.util.Base64;
+
+import static org.junit.jupiter.api.Assertions.assertEquals;
+import static org.junit.jupiter.api.Assertions.assertThrows;
+import static org.junit.jupiter.api.Assertions.assertTrue;
+
+/**
+ * Tests for the uncompressed f64 wire format used by get_result responses.
+ * Builds a frame the way the CLI does (little-endian header then values) and decodes it.
+ */
+class TimeSeriesRequestManagerF64Test {
+
+    private static final long TIMESTEP_SECONDS = 86400L; // daily
+    private static final long START_EPOCH_SEC = 1577836800L; // 2020-01-01T00:00:00Z
+
+    @Test
+    void decode_preservesValuesAndTimestamps() {
+        double[] expected = {1.0, Double.NaN, -2.5, 1e9};
+        TimeSeriesData decoded = TimeSeriesRequestManager.decodeF64Payload("test.series", encode(expected, expected.length));
+
+        double[] actual = decoded.getValues();
+        assertEquals(expected.length, actual.length);
+        assertEquals(1.0, actual[0], 0.0);
+        assertTrue(Double.isNaN(actual[1]));
+        assertEquals(-2.5, actual[2], 0.0);
+        assertEquals(1e9, actual[3], 0.0);
+
+        long[] timestamps = decoded.getTimestamps();
+        assertEquals(expected.length, timestamps.length);
+        for (int i = 0; i < timestamps.length; i++) {
+            long expectedMs = (START_EPOCH_SEC + i * TIMESTEP_SECONDS) * 1000L;
+            assertEquals(expectedMs, timestamps[i]);
+        }
+    }
+
+    @Test
+    void decode_rejectsTruncatedFrame() {
+        String b64 = encode(new double[]{1.0, 2.0}, 3);
+        assertThrows(IllegalArgumentException.class,
+            () -> TimeSeriesRequestManager.decodeF64Payload("bad.series", b64));
+    }
+
+    private static String encode(double[] values, long declaredCount) {
+        ByteBuffer frame = ByteBuffer.allocate(24 + 8 * values.length).order(ByteOrder.LITTLE_ENDIAN);
+        frame.putLong(START_EPOCH_SEC ^ Long.MIN_VALUE);
+        frame.putLong(TIMESTEP_SECONDS);
+        frame.putLong(declaredCount);
+        for (double v : values) {
+            frame.putDouble(v);
+        }
+        return Base64.getEncoder().encodeToString(frame.array());
+    }
+}
@@ -23,3 +23,20 @@ def _optimise_from_file(
     save_model_path: Optional[str] = None,
     progress: Optional[Callable[[Dict[str, Any]], Any]] = None,
 ) -> Dict[str, Any]: ...
+
+class _Model:
+    @staticmethod
+    def from_file(path: str) -> _Model: ...
+    @staticmethod
+    def from_string(ini: str, working_directory: Optional[str] = None) -> _Model: ...
+    def run(self) -> None: ...
+    def outputs(self) -> List[str]: ...
+    def get_series(self, name: str) -> Tuple[NDArray[np.int64], NDArray[np.float64]]: ...
+    def set_parameter(self, target: str, value: float) -> None: ...
+    def get_parameter(self, target: str) -> float: ...
+    def calibrate(
+        self,
+        config_ini: str,
+        progress: Optional[Callable[[Dict[str, Any]], Any]] = None,
+    ) -> Dict[str, Any]: ...
+    def to_ini(self) -> str: ...
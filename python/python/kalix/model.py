@@ -0,0 +1,109 @@
+"""A stateful Kalix model held in memory between calls.
+
+Where `kalix.simulate()` and `kalix.optimise()` go file-to-result, a `Model`
+is loaded once and then driven from Python: set parameters, run, read results
+as numpy arrays or pandas Series, calibrate in place, and save it back out.
+"""
+from __future__ import annotations
+
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Union
+
+import numpy as np
+import pandas as pd
+
+from kalix._native import _Model
+from kalix.opt import ProgressCallback, _resolve_progress
+
+__all__ = ["Model", "load_model"]
+
+PathLike = Union[str, Path]
+
+
+def _is_ini_string(source: PathLike) -> bool:
+    """An INI string has more than one line; anything else is taken as a path."""
+    return isinstance(source, str) and "\n" in source
+
+
+class Model:
+    """A loaded Kalix model. Create one with `load_model()`."""
+
+    def __init__(self, native: _Model) -> None:
+        self._native = native
+
+    @property
+    def outputs(self) -> List[str]:
+        """Names of the series in the model's ``[outputs]`` section."""
+        return self._native.outputs()
+
+    def run(self) -> None:
+        """Run the simulation. The model is configured on its first run."""
+        self._native.run()
+
+    def get_series(self, name: str) -> np.ndarray:
+        """Values of a result series (e.g. ``"node.gauge.dsflow"``) after a run.
+
+        Raises ``KeyError`` if the model has no series with that name.
+        """
+        return self._native.get_series(name)[1]
+
+    def get_timeseries(self, name: str) -> pd.Series:
+        """A result series as a pandas Series with a UTC ``DatetimeIndex``."""
+        timestamps, values = self._native.get_series(name)
+        index = pd.to_datetime(timestamps, unit="s", utc=True).as_unit("s")
+        index.name = "time"
+        return pd.Series(values, index=index, name=name)
+
+    def set_parameter(self, target: str, value: float) -> None:
+        """Set a parameter by its target address.
+
+        Targets look like ``"node.<name>.<param>"`` or ``"c.<constant>"``, as
+        in the ``[parameters]`` section of an optimisation config.
+        """
+        self._native.set_parameter(target, float(value))
+
+    def get_parameter(self, target: str) -> float:
+        """Get a parameter by its target address. See `set_parameter`."""
+        return self._native.get_parameter(target)
+
+    def calibrate(
+        self,
+        config: PathLike,
+        *,
+        progress: Union[None, bool, ProgressCallback] = None,
+    ) -> Dict[str, Any]:
+        """Calibrate this model in place and return the result.
+
+        ``config`` is an optimisation config, as a path or an INI string. Its
+        ``model_file`` entry is ignored — this model is calibrated instead, and
+        is left with the best parameters found. ``progress`` and the returned
+        dict are as for `kalix.optimise()`. Observed data files in the config
+        are resolved relative to the current working directory.
+        """
+        config_ini = str(config) if _is_ini_string(config) else Path(config).read_text()
+        native_progress, finalize = _resolve_progress(progress)
+        result = self._native.calibrate(config_ini, native_progress)
+        if finalize is not None:
+            finalize()
+        return result
+
+    def to_ini(self) -> str:
+        """The model, including any parameter changes, as an INI string."""
+        return self._native.to_ini()
+
+    def save(self, path: PathLike) -> None:
+        """Write the model, including any parameter changes, to an INI file."""
+        Path(path).write_text(self.to_ini())
+
+
+def load_model(source: PathLike, *, working_directory: Optional[PathLike] = None) -> Model:
+    """Load a model from an INI file path or an INI string.
+
+    A string containing a newline is parsed as the model itself; anything else
+    is a path. Relative input paths in a model string are resolved from
+    ``working_directory`` (default: the current directory).
+    """
+    if _is_ini_string(source):
+        wd = str(working_directory) if working_directory is not None else None
+        return Model(_Model.from_string(str(source), wd))
+    return Model(_Model.from_file(str(source)))
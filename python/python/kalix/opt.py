@@ -9,7 +9,7 @@ from __future__ import annotations
 import sys
 import time
 from pathlib import Path
-from typing import Any, Callable, Dict, Optional, Union
+from typing import Any, Callable, Dict, Optional, Tuple, Union
 
 from kalix._native import _optimise_from_file
 
@@ -87,6 +87,29 @@ def _throttle(callback: ProgressCallback, min_interval: float = 0.2) -> Progress
     return throttled
 
 
+def _resolve_progress(
+    progress: Union[None, bool, ProgressCallback],
+) -> Tuple[Optional[ProgressCallback], Optional[Callable[[], None]]]:
+    """Resolve a ``progress`` argument into the native callback (or None).
+
+    Also returns an optional cleanup to call after the run (e.g. a terminal
+    newline). Shared by `optimise()` and `Model.calibrate()`.
+    """
+    finalize = None
+    if progress is False:
+        native_progress = None
+    elif callable(progress):
+        native_progress = progress  # user-supplied: passed through verbatim
+    else:  # None / True / anything else → built-in default reporter
+        reporter = _default_reporter()
+        native_progress = _throttle(reporter) if reporter is not None else None
+        if reporter is not None and not _in_notebook():
+            # Close off the rewritten stderr line with a newline.
+            def finalize() -> None:
+                print(file=sys.stderr)
+    return native_progress, finalize
+
+
 def optimise(
     config_file: PathLike,
     *,
@@ -150,20 +173,7 @@ def optimise(
     does. If the config specifies an ``output_file``, a results summary is
     written there as well.
     """
-    # Resolve the `progress` argument into the native callback (or None).
-    finalize = None  # optional cleanup after the run (e.g. terminal newline)
-    if progress is False:
-        native_progress = None
-    elif callable(progress):
-        native_progress = progress  # user-supplied: passed through verbatim
-    else:  # None / True / anything else → built-in default reporter
-        reporter = _default_reporter()
-        native_progress = _throttle(reporter) if reporter is not None else None
-        if reporter is not None and not _in_notebook():
-            # Close off the rewritten stderr line with a newline.
-            def finalize() -> None:
-                print(file=sys.stderr)
-
+    native_progress, finalize = _resolve_progress(progress)
     result = _optimise_from_file(
         str(config_file),
         str(model_file) if model_file is not None else None,
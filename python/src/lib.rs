@@ -1,14 +1,18 @@
 //! PyO3 bindings for kalix.
 //!
-//! v0.1: just Pixie (.pxt/.pxb) I/O. Functions are prefixed with `_` and re-exported
-//! through the Python `kalix` package, which adds pandas/numpy ergonomics.
+//! Pixie (.pxt/.pxb) I/O, the stateless `simulate`/`optimise` entry points, and the
+//! stateful `_Model` class. Names are prefixed with `_` and re-exported through the
+//! Python `kalix` package, which adds pandas/numpy ergonomics.
 
+use kalix::io::ini_model_io::IniModelIO;
 use kalix::io::pixie_io;
-use kalix::run;
-use kalix::tid::utils::{wrap_to_i64, wrap_to_u64};
+use kalix::model::Model;
+use kalix::numerical::opt::{OptimisationConfig, OptimizationProgress, ProgressCallback};
+use kalix::run::{self, OptimisationOutcome};
+use kalix::tid::utils::{u64_to_unix_seconds, wrap_to_i64, wrap_to_u64};
 use kalix::timeseries::Timeseries;
 use numpy::{IntoPyArray, PyArray1, PyReadonlyArray1};
-use pyo3::exceptions::{PyIOError, PyRuntimeError, PyValueError};
+use pyo3::exceptions::{PyIOError, PyKeyError, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 
@@ -180,6 +184,8 @@ fn _simulate_from_file(
 /// Returns a dict with: `best_objective`, `n_evaluations`, `success`,
 /// `message`, `parameters` ({target: physical_value}), and
 /// `optimised_model_ini` (the optimised model serialised back to an INI string).
+/// When the config sets a `calibration_period` or `validation_period`, it also has
+/// `calibration_metrics` / `validation_metrics` dicts of `{period, objective, terms}`.
 ///
 /// `progress`, if given, must be a Python callable. It is invoked once per
 /// generation with a dict of `{n_evaluations, best_objective, elapsed_seconds}`.
@@ -194,33 +200,38 @@ fn _optimise_from_file<'py>(
     save_model_path: Option<&str>,
     progress: Option<PyObject>,
 ) -> PyResult<Bound<'py, PyDict>> {
-    use kalix::numerical::opt::OptimizationProgress;
-
-    // Adapt the optional Python callable into a Rust progress callback. The
-    // optimiser calls this once per generation from a single thread while the
-    // GIL is released, so we re-acquire the GIL to call into Python.
-    let progress_callback: Option<Box<dyn Fn(&OptimizationProgress) + Send + Sync>> =
-        progress.map(|callable| {
-            let cb: Box<dyn Fn(&OptimizationProgress) + Send + Sync> =
-                Box::new(move |p: &OptimizationProgress| {
-                    Python::with_gil(|py| {
-                        let d = PyDict::new_bound(py);
-                        let _ = d.set_item("n_evaluations", p.n_evaluations);
-                        let _ = d.set_item("best_objective", p.best_objective);
-                        let _ = d.set_item("elapsed_seconds", p.elapsed.as_secs_f64());
-                        // Ignore any exception raised by the user's callback.
-                        let _ = callable.call1(py, (d,));
-                    });
-                });
-            cb
-        });
-
+    let progress_callback = python_progress_callback(progress);
     let outcome = py
         .allow_threads(|| {
             run::optimise_from_file(config_path, model_path, save_model_path, progress_callback)
         })
         .map_err(PyRuntimeError::new_err)?;
+    outcome_to_dict(py, &outcome)
+}
+
+/// Adapt an optional Python callable into a Rust optimisation progress callback.
+///
+/// The optimiser calls this once per generation from a single thread while the
+/// GIL is released, so we re-acquire the GIL to call into Python.
+fn python_progress_callback(progress: Option<PyObject>) -> Option<ProgressCallback> {
+    progress.map(|callable| {
+        let cb: ProgressCallback =
+            Box::new(move |p: &OptimizationProgress| {
+                Python::with_gil(|py| {
+                    let d = PyDict::new_bound(py);
+                    let _ = d.set_item("n_evaluations", p.n_evaluations);
+                    let _ = d.set_item("best_objective", p.best_objective);
+                    let _ = d.set_item("elapsed_seconds", p.elapsed.as_secs_f64());
+                    // Ignore any exception raised by the user's callback.
+                    let _ = callable.call1(py, (d,));
+                });
+            });
+        cb
+    })
+}
 
+/// Package an optimisation outcome as the result dict described on `_optimise_from_file`.
+fn outcome_to_dict<'py>(py: Python<'py>, outcome: &OptimisationOutcome) -> PyResult<Bound<'py, PyDict>> {
     let params = PyDict::new_bound(py);
     for (target, value) in &outcome.parameters {
         params.set_item(target, value)?;
@@ -230,17 +241,135 @@ fn _optimise_from_file<'py>(
     result.set_item("best_objective", outcome.best_objective)?;
     result.set_item("n_evaluations", outcome.n_evaluations)?;
     result.set_item("success", outcome.success)?;
-    result.set_item("message", outcome.message)?;
+    result.set_item("message", &outcome.message)?;
     result.set_item("parameters", params)?;
-    result.set_item("optimised_model_ini", outcome.optimised_model_ini)?;
+    result.set_item("optimised_model_ini", &outcome.optimised_model_ini)?;
+    for (key, metrics) in [("calibration_metrics", &outcome.calibration_metrics),
+                           ("validation_metrics", &outcome.validation_metrics)] {
+        if let Some(metrics) = metrics {
+            let terms = PyDict::new_bound(py);
+            for (term, value) in &metrics.terms {
+                terms.set_item(term, value)?;
+            }
+            let d = PyDict::new_bound(py);
+            d.set_item("period", metrics.period)?;
+            d.set_item("objective", metrics.objective)?;
+            d.set_item("terms", terms)?;
+            result.set_item(key, d)?;
+        }
+    }
     Ok(result)
 }
 
+/// A loaded model held in memory between calls: load → set parameters → run → read
+/// results, or calibrate in place.
+///
+/// Wrapped by `kalix.Model`, which adds path/string detection and pandas output.
+#[pyclass(name = "_Model")]
+struct PyModel {
+    model: Model,
+}
+
+impl PyModel {
+    /// Configure the model the first time it is run or calibrated.
+    fn ensure_configured(&mut self) -> Result<(), String> {
+        if self.model.execution_order.is_empty() {
+            self.model.configure()?;
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PyModel {
+    /// Load a model from an INI file.
+    #[staticmethod]
+    fn from_file(path: &str) -> PyResult<Self> {
+        let model = IniModelIO::new().read_model_file(path).map_err(PyValueError::new_err)?;
+        Ok(PyModel { model })
+    }
+
+    /// Load a model from an INI string. Relative input paths are resolved from
+    /// `working_directory`, or the current directory if it is not given.
+    #[staticmethod]
+    #[pyo3(signature = (ini, working_directory=None))]
+    fn from_string(ini: &str, working_directory: Option<std::path::PathBuf>) -> PyResult<Self> {
+        let model = IniModelIO::new()
+            .read_model_string_with_working_directory(ini, working_directory)
+            .map_err(PyValueError::new_err)?;
+        Ok(PyModel { model })
+    }
+
+    /// Run the simulation. The GIL is released during the run.
+    fn run(&mut self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| {
+            self.ensure_configured()?;
+            self.model.run()
+        })
+        .map_err(PyRuntimeError::new_err)
+    }
+
+    /// Names of the series listed in the model's `[outputs]` section.
+    fn outputs(&self) -> Vec<String> {
+        self.model.outputs.clone()
+    }
+
+    /// Timestamps (signed Unix seconds) and values of a result series after a run.
+    fn get_series<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+    ) -> PyResult<(Bound<'py, PyArray1<i64>>, Bound<'py, PyArray1<f64>>)> {
+        let idx = self.model.data_cache.get_existing_series_idx(name)
+            .ok_or_else(|| PyKeyError::new_err(format!("Series not found: {}", name)))?;
+        let series = self.model.data_cache.get_series(idx);
+        let timestamps: Vec<i64> = series.timestamps.iter().map(|&t| u64_to_unix_seconds(t)).collect();
+        Ok((timestamps.into_pyarray_bound(py), series.values.clone().into_pyarray_bound(py)))
+    }
+
+    /// Set a parameter by target address, e.g. `node.my_sac.uzfwm` or `c.demand_factor`.
+    fn set_parameter(&mut self, target: &str, value: f64) -> PyResult<()> {
+        self.model.set_parameter(target, value).map_err(PyValueError::new_err)
+    }
+
+    /// Get a parameter by target address. See `set_parameter`.
+    fn get_parameter(&self, target: &str) -> PyResult<f64> {
+        self.model.get_parameter(target).map_err(PyValueError::new_err)
+    }
+
+    /// Calibrate the model in place against an optimisation config given as an INI
+    /// string, and return the same result dict as `_optimise_from_file`. The config's
+    /// `model_file` is not used. The GIL is released during the run.
+    #[pyo3(signature = (config_ini, progress=None))]
+    fn calibrate<'py>(
+        &mut self,
+        py: Python<'py>,
+        config_ini: &str,
+        progress: Option<PyObject>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let config = OptimisationConfig::from_ini(config_ini).map_err(PyValueError::new_err)?;
+        let progress_callback = python_progress_callback(progress);
+        let outcome = py
+            .allow_threads(|| {
+                self.ensure_configured()?;
+                run::calibrate_model(&mut self.model, &config, progress_callback)
+            })
+            .map_err(PyRuntimeError::new_err)?;
+        outcome_to_dict(py, &outcome)
+    }
+
+    /// Serialise the model (including any parameter changes) back to an INI string.
+    fn to_ini(&self) -> String {
+        IniModelIO::new().model_to_string(&self.model)
+    }
+}
+
 #[pymodule]
 fn _native(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(_read_pixie_raw, m)?)?;
     m.add_function(wrap_pyfunction!(_write_pixie_raw, m)?)?;
     m.add_function(wrap_pyfunction!(_simulate_from_file, m)?)?;
     m.add_function(wrap_pyfunction!(_optimise_from_file, m)?)?;
+    m.add_class::<PyModel>()?;
     Ok(())
 }
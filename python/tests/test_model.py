@@ -0,0 +1,115 @@
+"""Tests for kalix.Model (the stateful load → mutate → run → inspect entry)."""
+from __future__ import annotations
+
+import textwrap
+
+import numpy as np
+import pandas as pd
+import pytest
+
+import kalix
+
+# A single inflow node whose inflow is the Beale function of two constants,
+# minimised at (x, y) = (3, 0.5) with value 0. No input data is required.
+_BEALE_MODEL = textwrap.dedent("""\
+    [kalix]
+    start = 2000-01-01T00:00:00
+    end = 2000-01-10T00:00:00
+
+    [constants]
+    c.x = 0.46
+    c.y = 0.34
+
+    [node.my_node]
+    loc = 0,0
+    type = inflow
+    inflow = (1.5 - c.x + c.x*c.y)^2 + (2.25 - c.x + c.x * c.y^2)^2 + (2.625 - c.x + c.x * c.y^3)^2
+
+    [outputs]
+    node.my_node.ds_1
+""")
+
+
+def _beale(x: float, y: float) -> float:
+    return (1.5 - x + x * y) ** 2 + (2.25 - x + x * y ** 2) ** 2 + (2.625 - x + x * y ** 3) ** 2
+
+
+def test_run_and_get_series():
+    model = kalix.load_model(_BEALE_MODEL)
+    assert model.outputs == ["node.my_node.ds_1"]
+    model.run()
+
+    values = model.get_series("node.my_node.ds_1")
+    assert isinstance(values, np.ndarray) and values.dtype == np.float64
+    assert len(values) == 10
+    np.testing.assert_allclose(values, _beale(0.46, 0.34))
+
+    series = model.get_timeseries("node.my_node.ds_1")
+    assert isinstance(series, pd.Series)
+    assert series.index[0] == pd.Timestamp("2000-01-01", tz="UTC")
+    assert series.index[-1] == pd.Timestamp("2000-01-10", tz="UTC")
+
+    with pytest.raises(KeyError):
+        model.get_series("node.nowhere.ds_1")
+
+
+def test_set_parameter_then_rerun():
+    model = kalix.load_model(_BEALE_MODEL)
+    model.run()
+    model.set_parameter("c.x", 3.0)
+    model.set_parameter("c.y", 0.5)
+    assert model.get_parameter("c.x") == 3.0
+    model.run()
+    np.testing.assert_allclose(model.get_series("node.my_node.ds_1"), 0.0, atol=1e-12)
+
+    with pytest.raises(ValueError):
+        model.set_parameter("node.my_node.nothing", 1.0)
+
+
+def test_load_from_file_and_save(tmp_path):
+    path = tmp_path / "beale_model.ini"
+    path.write_text(_BEALE_MODEL)
+    model = kalix.load_model(path)
+    model.set_parameter("c.x", 1.25)
+
+    saved = tmp_path / "saved.ini"
+    model.save(saved)
+    assert kalix.load_model(saved).get_parameter("c.x") == 1.25
+    assert saved.read_text() == model.to_ini()
+
+
+def test_calibrate_in_place(tmp_path):
+    zeros = tmp_path / "zeros.csv"
+    lines = ["Datetime,obs"] + [f"2000-01-{d:02d},0.0" for d in range(1, 11)]
+    zeros.write_text("\n".join(lines) + "\n")
+    config = textwrap.dedent(f"""\
+        [optimisation]
+        objective_expression = term1
+        algorithm = SCE
+        complexes = 4
+        termination_evaluations = 2000
+        n_threads = 1
+        random_seed = 12345
+
+        [term.term1]
+        simulated = node.my_node.ds_1
+        observed_file = {zeros}
+        observed_series = 1
+        statistic = MAE
+
+        [parameters]
+        c.x = lin_range(g(1),-4.5,4.5)
+        c.y = lin_range(g(2),-4.5,4.5)
+    """)
+
+    model = kalix.load_model(_BEALE_MODEL)
+    res = model.calibrate(config, progress=False)
+    assert res["success"] is True
+    assert res["best_objective"] == pytest.approx(0.0, abs=1e-3)
+
+    # The model keeps the best parameters, and a config file works the same way
+    assert model.get_parameter("c.x") == pytest.approx(3.0, abs=1e-2)
+    assert model.get_parameter("c.y") == pytest.approx(0.5, abs=1e-2)
+    config_file = tmp_path / "opt.ini"
+    config_file.write_text(config)
+    assert kalix.load_model(_BEALE_MODEL).calibrate(config_file, progress=False)["success"] is True
@@ -0,0 +1,286 @@
+//! C-compatible API for embedding Kalix in other applications (Delphi, C#, Fortran, ...).
+//!
+//! Built with the `ffi` feature as a shared library:
+//!
+//! ```text
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+//!
+//! The declarations for C callers are in `include/kalix.h`. A model is an opaque handle from
+//! `kalix_load_model` that the caller releases with `kalix_free`. Every other function
+//! returns a status code: `KALIX_OK` (0) on success, otherwise one of the `KALIX_ERR_*`
+//! codes, with a description of the last error on the calling thread available from
+//! `kalix_last_error`. Strings passed in are null-terminated UTF-8. Series values are copied
+//! into buffers owned by the caller, so no memory allocated by Kalix is handed out other
+//! than the model handle. Panics are caught and reported as `KALIX_ERR_PANIC`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tid::utils::u64_to_unix_seconds;
+
+pub const KALIX_OK: i32 = 0;
+/// A required pointer argument was null
+pub const KALIX_ERR_NULL_ARGUMENT: i32 = 1;
+/// A string argument was not valid UTF-8
+pub const KALIX_ERR_INVALID_UTF8: i32 = 2;
+/// The model could not be read or configured
+pub const KALIX_ERR_LOAD: i32 = 3;
+/// The simulation failed
+pub const KALIX_ERR_RUN: i32 = 4;
+/// No series has the given name
+pub const KALIX_ERR_SERIES_NOT_FOUND: i32 = 5;
+/// The caller's buffer is too small; the required length is still written
+pub const KALIX_ERR_BUFFER_TOO_SMALL: i32 = 6;
+/// The parameter target is invalid or the value was rejected
+pub const KALIX_ERR_PARAMETER: i32 = 7;
+/// Writing outputs to a file failed
+pub const KALIX_ERR_WRITE: i32 = 8;
+/// Kalix panicked; the model handle should not be used again
+pub const KALIX_ERR_PANIC: i32 = 99;
+
+/// Opaque model handle. C callers only ever hold a pointer to it.
+pub struct KalixModel {
+    model: Model,
+}
+
+/// The time grid of a series: `len` values from `start_timestamp` (signed Unix seconds),
+/// `step_size` seconds apart
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KalixSeriesInfo {
+    pub start_timestamp: i64,
+    pub step_size: u64,
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+/// Runs `f`, recording any error for `kalix_last_error` and turning panics into `KALIX_ERR_PANIC`
+fn guard<F: FnOnce() -> Result<(), (i32, String)>>(f: F) -> i32 {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            set_last_error("");
+            KALIX_OK
+        }
+        Ok(Err((code, message))) => {
+            set_last_error(&message);
+            code
+        }
+        Err(payload) => {
+            let message = payload.downcast_ref::<String>().cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "Unknown panic".to_string());
+            set_last_error(&format!("Kalix panicked: {}", message));
+            KALIX_ERR_PANIC
+        }
+    }
+}
+
+/// Reads a null-terminated UTF-8 string argument
+unsafe fn read_str<'a>(ptr: *const c_char, argument: &str) -> Result<&'a str, (i32, String)> {
+    if ptr.is_null() {
+        return Err((KALIX_ERR_NULL_ARGUMENT, format!("Argument '{}' is null", argument)));
+    }
+    CStr::from_ptr(ptr).to_str()
+        .map_err(|_| (KALIX_ERR_INVALID_UTF8, format!("Argument '{}' is not valid UTF-8", argument)))
+}
+
+unsafe fn model_ref<'a>(model: *const KalixModel) -> Result<&'a KalixModel, (i32, String)> {
+    model.as_ref().ok_or_else(|| (KALIX_ERR_NULL_ARGUMENT, "Argument 'model' is null".to_string()))
+}
+
+unsafe fn model_mut<'a>(model: *mut KalixModel) -> Result<&'a mut KalixModel, (i32, String)> {
+    model.as_mut().ok_or_else(|| (KALIX_ERR_NULL_ARGUMENT, "Argument 'model' is null".to_string()))
+}
+
+fn configure_and_box(mut model: Model, out_model: *mut *mut KalixModel) -> Result<(), (i32, String)> {
+    model.configure().map_err(|e| (KALIX_ERR_LOAD, e))?;
+    let handle = Box::new(KalixModel { model });
+    unsafe { *out_model = Box::into_raw(handle) };
+    Ok(())
+}
+
+/// Loads and configures a model from an INI file, writing a new handle to `out_model`.
+///
+/// # Safety
+/// `path` must be a null-terminated string and `out_model` a valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_load_model(path: *const c_char, out_model: *mut *mut KalixModel) -> i32 {
+    guard(|| {
+        let path = read_str(path, "path")?;
+        if out_model.is_null() {
+            return Err((KALIX_ERR_NULL_ARGUMENT, "Argument 'out_model' is null".to_string()));
+        }
+        let model = IniModelIO::new().read_model_file(path).map_err(|e| (KALIX_ERR_LOAD, e))?;
+        configure_and_box(model, out_model)
+    })
+}
+
+/// Loads and configures a model from the contents of an INI file. Relative input paths are
+/// resolved from the current working directory.
+///
+/// # Safety
+/// `ini` must be a null-terminated string and `out_model` a valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_load_model_string(ini: *const c_char, out_model: *mut *mut KalixModel) -> i32 {
+    guard(|| {
+        let ini = read_str(ini, "ini")?;
+        if out_model.is_null() {
+            return Err((KALIX_ERR_NULL_ARGUMENT, "Argument 'out_model' is null".to_string()));
+        }
+        let model = IniModelIO::new().read_model_string(ini).map_err(|e| (KALIX_ERR_LOAD, e))?;
+        configure_and_box(model, out_model)
+    })
+}
+
+/// Runs the simulation. A model may be run any number of times, e.g. after changing parameters.
+///
+/// # Safety
+/// `model` must be a handle from `kalix_load_model` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_run(model: *mut KalixModel) -> i32 {
+    guard(|| {
+        let handle = model_mut(model)?;
+        handle.model.run().map_err(|e| (KALIX_ERR_RUN, e))
+    })
+}
+
+/// Writes the time grid of a result series (e.g. `node.gauge.dsflow`) to `out_info`.
+///
+/// # Safety
+/// `model` must be a live handle, `name` a null-terminated string and `out_info` a valid
+/// pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_get_series_info(model: *const KalixModel, name: *const c_char,
+                                               out_info: *mut KalixSeriesInfo) -> i32 {
+    guard(|| {
+        let handle = model_ref(model)?;
+        let name = read_str(name, "name")?;
+        let out_info = out_info.as_mut()
+            .ok_or_else(|| (KALIX_ERR_NULL_ARGUMENT, "Argument 'out_info' is null".to_string()))?;
+        let cache = &handle.model.data_cache;
+        let idx = cache.get_existing_series_idx(name)
+            .ok_or_else(|| (KALIX_ERR_SERIES_NOT_FOUND, format!("Series not found: {}", name)))?;
+        let series = &cache.series[idx];
+        *out_info = KalixSeriesInfo {
+            start_timestamp: u64_to_unix_seconds(series.start_timestamp),
+            step_size: series.step_size,
+            len: cache.series_len(idx),
+        };
+        Ok(())
+    })
+}
+
+/// Copies the values of a result series into `buffer`, which holds `buffer_len` doubles.
+/// The number of values is written to `out_len` (if not null), including when the buffer
+/// is too small, so callers can pass a null buffer with length 0 to ask for the size.
+/// Missing values are NaN.
+///
+/// # Safety
+/// `model` must be a live handle, `name` a null-terminated string, and `buffer` valid for
+/// `buffer_len` doubles.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_get_series(model: *const KalixModel, name: *const c_char,
+                                          buffer: *mut f64, buffer_len: usize, out_len: *mut usize) -> i32 {
+    guard(|| {
+        let handle = model_ref(model)?;
+        let name = read_str(name, "name")?;
+        let cache = &handle.model.data_cache;
+        let idx = cache.get_existing_series_idx(name)
+            .ok_or_else(|| (KALIX_ERR_SERIES_NOT_FOUND, format!("Series not found: {}", name)))?;
+        let series = cache.get_series(idx);
+        if let Some(out_len) = out_len.as_mut() {
+            *out_len = series.values.len();
+        }
+        if series.values.len() > buffer_len {
+            return Err((KALIX_ERR_BUFFER_TOO_SMALL, format!(
+                "Series '{}' has {} values but the buffer holds {}", name, series.values.len(), buffer_len)));
+        }
+        if !series.values.is_empty() {
+            if buffer.is_null() {
+                return Err((KALIX_ERR_NULL_ARGUMENT, "Argument 'buffer' is null".to_string()));
+            }
+            std::slice::from_raw_parts_mut(buffer, series.values.len()).copy_from_slice(&series.values);
+        }
+        Ok(())
+    })
+}
+
+/// Sets a parameter by target address, e.g. `node.my_sac.uzfwm` or `c.demand_factor`.
+///
+/// # Safety
+/// `model` must be a live handle and `target` a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_set_parameter(model: *mut KalixModel, target: *const c_char, value: f64) -> i32 {
+    guard(|| {
+        let handle = model_mut(model)?;
+        let target = read_str(target, "target")?;
+        handle.model.set_parameter(target, value).map_err(|e| (KALIX_ERR_PARAMETER, e))
+    })
+}
+
+/// Gets a parameter by target address, writing it to `out_value`.
+///
+/// # Safety
+/// `model` must be a live handle, `target` a null-terminated string and `out_value` a valid
+/// pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_get_parameter(model: *const KalixModel, target: *const c_char,
+                                             out_value: *mut f64) -> i32 {
+    guard(|| {
+        let handle = model_ref(model)?;
+        let target = read_str(target, "target")?;
+        let out_value = out_value.as_mut()
+            .ok_or_else(|| (KALIX_ERR_NULL_ARGUMENT, "Argument 'out_value' is null".to_string()))?;
+        *out_value = handle.model.get_parameter(target).map_err(|e| (KALIX_ERR_PARAMETER, e))?;
+        Ok(())
+    })
+}
+
+/// Writes the model's outputs to a file, in the format given by its extension (`.csv` or `.pxb`).
+///
+/// # Safety
+/// `model` must be a live handle and `path` a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_write_outputs(model: *const KalixModel, path: *const c_char) -> i32 {
+    guard(|| {
+        let handle = model_ref(model)?;
+        let path = read_str(path, "path")?;
+        handle.model.write_outputs(path).map_err(|e| (KALIX_ERR_WRITE, e))
+    })
+}
+
+/// Releases a model handle. Passing null does nothing.
+///
+/// # Safety
+/// `model` must be null or a handle from `kalix_load_model` that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn kalix_free(model: *mut KalixModel) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
+
+/// Description of the last error on the calling thread, or an empty string if the last call
+/// succeeded. The string is owned by Kalix and is valid until the next call on the thread.
+#[no_mangle]
+pub extern "C" fn kalix_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// The Kalix version, e.g. `0.3.3`, as a static null-terminated string
+#[no_mangle]
+pub extern "C" fn kalix_version() -> *const c_char {
+    concat!(env!("KALIX_VERSION"), "\0").as_ptr() as *const c_char
+}
@@ -1 +1,5 @@
-pub mod stdio;
\ No newline at end of file
+pub mod stdio;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
\ No newline at end of file
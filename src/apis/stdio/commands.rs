@@ -1,13 +1,17 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use crate::apis::stdio::messages::{CommandSpec, ParameterSpec, ProgressInfo};
-use crate::apis::stdio::session::Session;
+use crate::apis::stdio::messages::{CommandSpec, ParameterSpec, ProgressInfo, RollingThroughput, THROUGHPUT_WINDOW};
+use crate::apis::stdio::session::{Session, DEFAULT_MODEL_ID};
 use crate::io::ini_model_io::IniModelIO;
 use crate::io::csv_io;
 use crate::io::pixie_io;
+use crate::timeseries::Timeseries;
 use chrono;
 use crate::tid;
 use crate::numerical::opt::Optimisable;
+use crate::misc::node_timing::NodeTimer;
+use crate::data_management::events::EventKind;
+use crate::data_management::run_log::LogLevel;
 
 pub trait Command: Send + Sync {
     fn name(&self) -> &str;
@@ -72,9 +76,29 @@ impl CommandRegistry {
         registry.register(Arc::new(LoadModelFileCommand));
         registry.register(Arc::new(LoadModelStringCommand));
         registry.register(Arc::new(RunSimulationCommand));
+        registry.register(Arc::new(StepCommand));
+        registry.register(Arc::new(RunToCommand));
+        registry.register(Arc::new(InspectCommand));
         registry.register(Arc::new(RunOptimisationCommand));
+        registry.register(Arc::new(GetOptimisationResultCommand));
+        registry.register(Arc::new(ApplyCalibrationCommand));
+        registry.register(Arc::new(RunBatchCommand));
+        registry.register(Arc::new(RunSensitivityCommand));
+        registry.register(Arc::new(RunGlueCommand));
+        registry.register(Arc::new(RunForecastCommand));
         registry.register(Arc::new(GetOptimisableParamsCommand));
+        registry.register(Arc::new(GetParameterCommand));
+        registry.register(Arc::new(SetParameterCommand));
+        registry.register(Arc::new(ValidateModelCommand));
+        registry.register(Arc::new(GetModelStructureCommand));
+        registry.register(Arc::new(ExportGraphCommand));
+        registry.register(Arc::new(GenerateReportCommand));
+        registry.register(Arc::new(GetProvenanceCommand));
         registry.register(Arc::new(GetResultCommand));
+        registry.register(Arc::new(CompareResultsCommand));
+        registry.register(Arc::new(GetLogCommand));
+        registry.register(Arc::new(GetEventsCommand));
+        registry.register(Arc::new(GetProfileCommand));
         registry.register(Arc::new(SaveResultsCommand));
         registry.register(Arc::new(EchoCommand));
         
@@ -98,6 +122,29 @@ impl CommandRegistry {
     }
 }
 
+/// The model a command addresses: its `model_id` parameter, or the session's default model
+fn model_id(params: &serde_json::Value) -> &str {
+    params.get("model_id").and_then(|v| v.as_str()).unwrap_or(DEFAULT_MODEL_ID)
+}
+
+/// Reads an optional date parameter such as `"2024-01-01"` as a timestamp
+fn date_param(params: &serde_json::Value, key: &str) -> Result<Option<u64>, CommandError> {
+    params.get(key).and_then(|v| v.as_str())
+        .map(|s| tid::utils::date_string_to_u64_flexible(s)
+            .map(|(timestamp, _)| timestamp)
+            .map_err(|e| CommandError::InvalidParameters(format!("Invalid {} date: {}", key, e))))
+        .transpose()
+}
+
+fn model_id_spec() -> ParameterSpec {
+    ParameterSpec {
+        name: "model_id".to_string(),
+        param_type: "string".to_string(),
+        required: false,
+        default: Some(serde_json::Value::String(DEFAULT_MODEL_ID.to_string())),
+    }
+}
+
 // Built-in commands
 
 pub struct GetVersionCommand;
@@ -223,6 +270,7 @@ impl Command for TestProgressCommand {
                 current: None,
                 total: None,
                 task_type: None,
+                timing: None,
             };
             
             progress_sender(progress);
@@ -258,7 +306,14 @@ impl Command for LoadModelFileCommand {
                 param_type: "string".to_string(),
                 required: true,
                 default: None,
-            }
+            },
+            ParameterSpec {
+                name: "overlays".to_string(),
+                param_type: "array".to_string(),
+                required: false,
+                default: Some(serde_json::json!([])),
+            },
+            model_id_spec(),
         ]
     }
     
@@ -276,16 +331,24 @@ impl Command for LoadModelFileCommand {
         let model_path = params.get("model_path")
             .and_then(|v| v.as_str())
             .ok_or_else(|| CommandError::InvalidParameters("model_path is required".to_string()))?;
+        let overlays: Vec<String> = match params.get("overlays") {
+            None | Some(serde_json::Value::Null) => Vec::new(),
+            Some(serde_json::Value::Array(paths)) => paths.iter()
+                .map(|p| p.as_str().map(|s| s.to_string())
+                    .ok_or_else(|| CommandError::InvalidParameters("overlays must be an array of file paths".to_string())))
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(CommandError::InvalidParameters("overlays must be an array of file paths".to_string())),
+        };
 
-        // Load the model
+        // Load the model, applying any scenario overlays in order
         let ini_reader = IniModelIO::new();
-        let model = ini_reader.read_model_file(model_path)
+        let model = ini_reader.read_model_file_with_overlays(model_path, &overlays)
             .map_err(|e| CommandError::ExecutionError(format!("Failed to load model: {}", e)))?;
 
         // Store the model in the session
-        session.set_model(model);
+        session.set_model_by_id(model_id(&params), model);
         
-        let model_info = session.get_model()
+        let model_info = session.get_model_by_id(model_id(&params))
             .map(|m| serde_json::json!({
                 "nodes_count": m.nodes.len(),
                 "inputs_count": m.inputs.len(),
@@ -296,6 +359,8 @@ impl Command for LoadModelFileCommand {
         Ok(serde_json::json!({
             "success": true,
             "model_path": model_path,
+            "overlays": overlays,
+            "model_id": model_id(&params),
             "model_info": model_info
         }))
     }
@@ -319,7 +384,8 @@ impl Command for LoadModelStringCommand {
                 param_type: "string".to_string(),
                 required: true,
                 default: None,
-            }
+            },
+            model_id_spec(),
         ]
     }
     
@@ -344,9 +410,9 @@ impl Command for LoadModelStringCommand {
             .map_err(|e| CommandError::ExecutionError(format!("Failed to parse model: {}", e)))?;
 
         // Store the model in the session
-        session.set_model(model);
+        session.set_model_by_id(model_id(&params), model);
         
-        let model_info = session.get_model()
+        let model_info = session.get_model_by_id(model_id(&params))
             .map(|m| serde_json::json!({
                 "nodes_count": m.nodes.len(),
                 "inputs_count": m.inputs.len(),
@@ -357,6 +423,7 @@ impl Command for LoadModelStringCommand {
         Ok(serde_json::json!({
             "success": true,
             "ini_length": model_ini.len(),
+            "model_id": model_id(&params),
             "model_info": model_info
         }))
     }
@@ -431,6 +498,7 @@ impl Command for GetResultCommand {
                 required: true,
                 default: Some(serde_json::Value::String("csv".to_string())),
             },
+            model_id_spec(),
         ]
     }
 
@@ -454,14 +522,18 @@ impl Command for GetResultCommand {
             .unwrap_or("pixie");
 
         // Get model and check if it exists
-        let model = session.get_model()
+        let model = session.get_model_by_id(model_id(&params))
             .ok_or(CommandError::ModelNotLoaded)?;
 
         // Find the series in the data cache
         let series_idx = model.data_cache.get_existing_series_idx(series_name)
             .ok_or_else(|| CommandError::ResultNotFound(format!("Timeseries '{}' not found in model results", series_name)))?;
+        if !model.data_cache.is_recorded_in_full(series_idx) {
+            return Err(CommandError::ResultNotFound(format!(
+                "Timeseries '{}' is only used within the model and is not recorded. Add it to [outputs] to record it", series_name)));
+        }
 
-        let timeseries = &model.data_cache.series[series_idx];
+        let timeseries = model.data_cache.get_series(series_idx);
         let start_timestamp = tid::utils::u64_to_iso_datetime_string(timeseries.start_timestamp);
 
         let metadata = serde_json::json!({
@@ -509,8 +581,29 @@ impl Command for GetResultCommand {
                     "data": encoded
                 }))
             }
+            "f64" => {
+                use base64::{Engine, engine::general_purpose::STANDARD};
+
+                // Uncompressed frame: start timestamp, step and count (u64), then the values,
+                // all little-endian. No per-value formatting or parsing on either side.
+                let mut frame = Vec::with_capacity(24 + 8 * timeseries.values.len());
+                frame.extend_from_slice(&timeseries.start_timestamp.to_le_bytes());
+                frame.extend_from_slice(&timeseries.step_size.to_le_bytes());
+                frame.extend_from_slice(&(timeseries.values.len() as u64).to_le_bytes());
+                for value in &timeseries.values {
+                    frame.extend_from_slice(&value.to_le_bytes());
+                }
+
+                Ok(serde_json::json!({
+                    "series_name": series_name,
+                    "format": "f64",
+                    "codec": "f64_le",
+                    "metadata": metadata,
+                    "data": STANDARD.encode(&frame)
+                }))
+            }
             other => Err(CommandError::InvalidParameters(
-                format!("Unsupported format '{}'; expected 'pixie' or 'csv'", other)
+                format!("Unsupported format '{}'; expected 'pixie', 'f64' or 'csv'", other)
             )),
         }
     }
@@ -528,7 +621,51 @@ impl Command for RunSimulationCommand {
     }
     
     fn parameters(&self) -> Vec<ParameterSpec> {
-        vec![] // No parameters for now
+        vec![
+            ParameterSpec {
+                name: "start".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "end".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "inputs".to_string(),
+                param_type: "object".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "node_timing".to_string(),
+                param_type: "boolean".to_string(),
+                required: false,
+                default: Some(serde_json::json!(false)),
+            },
+            ParameterSpec {
+                name: "record_events".to_string(),
+                param_type: "boolean".to_string(),
+                required: false,
+                default: Some(serde_json::json!(false)),
+            },
+            ParameterSpec {
+                name: "step_mode".to_string(),
+                param_type: "boolean".to_string(),
+                required: false,
+                default: Some(serde_json::json!(false)),
+            },
+            ParameterSpec {
+                name: "incremental".to_string(),
+                param_type: "boolean".to_string(),
+                required: false,
+                default: Some(serde_json::json!(false)),
+            },
+            model_id_spec(),
+        ]
     }
     
     fn interruptible(&self) -> bool {
@@ -538,18 +675,30 @@ impl Command for RunSimulationCommand {
     fn execute(
         &self,
         session: &mut Session,
-        _params: serde_json::Value,
+        params: serde_json::Value,
         progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
     ) -> Result<serde_json::Value, CommandError> {
-        use std::time::Instant;
         use std::sync::Arc;
-        use std::sync::atomic::{AtomicU64, Ordering};
-        
+
         // Get interrupt flag before getting mutable model reference
         let interrupt_flag = Arc::clone(&session.interrupt_flag);
 
+        let start = date_param(&params, "start")?;
+        let end = date_param(&params, "end")?;
+        let time_nodes = params.get("node_timing").and_then(|v| v.as_bool()).unwrap_or(false);
+        let record_events = params.get("record_events").and_then(|v| v.as_bool()).unwrap_or(false);
+        let step_mode = params.get("step_mode").and_then(|v| v.as_bool()).unwrap_or(false);
+        let incremental = params.get("incremental").and_then(|v| v.as_bool()).unwrap_or(false);
+        let input_overrides = match params.get("inputs") {
+            None | Some(serde_json::Value::Null) => serde_json::Map::new(),
+            Some(serde_json::Value::Object(map)) => map.clone(),
+            Some(_) => return Err(CommandError::InvalidParameters(
+                "inputs must be an object mapping input series names to values".to_string()
+            )),
+        };
+
         // Check if model is loaded
-        let model = session.get_model_mut()
+        let model = session.get_model_by_id_mut(model_id(&params))
             .ok_or(CommandError::ModelNotLoaded)?;
 
         // Check if model has input data
@@ -557,108 +706,71 @@ impl Command for RunSimulationCommand {
         //     return Err(CommandError::DataNotLoaded);
         // }
 
-        // Try to configure the model simulation period
-        match model.configure() {
-            Ok(_) => (),
-            Err(e) => {
-                return Err(CommandError::ExecutionError(format!("Configuration failed: {}", e)));
-            }
+        // The period and input overrides only apply to this run. The model's own inputs and
+        // period are put back afterwards, whether or not the run succeeds.
+        let specified_period = (model.configuration.specified_sim_start_timestamp,
+                                model.configuration.specified_sim_end_timestamp);
+        let mut replaced_inputs: Vec<(String, crate::timeseries::Timeseries)> = Vec::new();
+        if time_nodes {
+            model.node_timer = Some(Arc::new(NodeTimer::new(model.nodes.len())));
         }
-        
+        model.data_cache.events.enabled = record_events;
+        model.enable_incremental_runs(incremental);
+        let outcome = apply_input_overrides(model, &input_overrides, start, &mut replaced_inputs)
+            .and_then(|_| {
+                if start.is_some() {
+                    model.configuration.specified_sim_start_timestamp = start;
+                }
+                if end.is_some() {
+                    model.configuration.specified_sim_end_timestamp = end;
+                }
+                if step_mode {
+                    // Paused before the first timestep, for the step, run_to and inspect commands
+                    return model.configure().and_then(|_| model.start_stepping())
+                        .map(|_| std::time::Duration::ZERO)
+                        .map_err(|e| CommandError::ExecutionError(format!("Configuration failed: {}", e)));
+                }
+                simulate_with_progress(model, interrupt_flag, progress_sender)
+            });
+        for (name, original) in replaced_inputs.into_iter().rev() {
+            let _ = model.replace_input_series(&name, original);
+        }
+        (model.configuration.specified_sim_start_timestamp,
+         model.configuration.specified_sim_end_timestamp) = specified_period;
+        // Timing is only for this run, and must not slow down later runs or clones of the model
+        let node_timing = model.node_timer.take().map(|timer| node_timing_json(model, &timer));
+        if !step_mode {
+            model.data_cache.events.enabled = false;
+        }
+        let simulation_duration = outcome?;
+        if step_mode {
+            return Ok(stepped_run_json(model, 0));
+        }
+
         // Get simulation info for result
         let start_timestamp = model.configuration.sim_start_timestamp;
         let end_timestamp = model.configuration.sim_end_timestamp;
         let stepsize = model.configuration.sim_stepsize;
         let total_timesteps = ((end_timestamp - start_timestamp) / stepsize) + 1;
-        
-        // Track progress timing for rate limiting (max 1 update per 200ms, 1 per percentage)
-        let last_progress_time = Arc::new(std::sync::Mutex::new(Instant::now()));
-        let last_progress_percent = Arc::new(AtomicU64::new(0));
-        
-        // Create progress callback for the model
-        let progress_sender_clone = Arc::new(progress_sender);
-        let progress_callback = {
-            let last_time = Arc::clone(&last_progress_time);
-            let last_percent = Arc::clone(&last_progress_percent);
-            let sender = Arc::clone(&progress_sender_clone);
-            
-            Box::new(move |current_step: u64, total_steps: u64| {
-                // Calculate percentage (0% to 100% range for simulation)
-                let sim_progress = (current_step as f64 / total_steps as f64) * 100.0;
-                let overall_progress = sim_progress;
-                let overall_percent = overall_progress as u64;
-                
-                // Check rate limiting conditions
-                let now = Instant::now();
-                let should_update = {
-                    let mut last_time_guard = last_time.lock().unwrap();
-                    let time_elapsed = now.duration_since(*last_time_guard).as_millis() >= 200;
-                    let percent_changed = overall_percent > last_percent.load(Ordering::Relaxed);
-                    
-                    if time_elapsed && percent_changed {
-                        *last_time_guard = now;
-                        last_percent.store(overall_percent, Ordering::Relaxed);
-                        true
-                    } else {
-                        false
-                    }
-                };
-                
-                if should_update {
-                    sender(ProgressInfo {
-                        percent_complete: overall_progress,
-                        current_step: format!("Running simulation - Processing timestep {} of {}", current_step + 1, total_steps),
-                        estimated_remaining: None,
-                        data: None,
-                        current: None,
-                        total: None,
-                        task_type: None,
-                    });
-                }
-            })
-        };
-        
-        // Send initial progress message
-        let progress_sender_clone = Arc::clone(&progress_sender_clone);
-        progress_sender_clone(ProgressInfo {
-            percent_complete: 0.0,
-            current_step: format!("Running simulation - Processing timestep 1 of {}", total_timesteps),
-            estimated_remaining: None,
-            data: None,
-            current: None,
-            total: None,
-            task_type: None,
-        });
-
-        // Simulation phase - 20% to 90%
-        let simulation_start = Instant::now();
-
-        // Run the simulation with interrupt checking
-        let completed = model.run_with_interrupt(
-            move || interrupt_flag.load(Ordering::Relaxed),
-            Some(progress_callback)
-        ).map_err(|e| CommandError::ExecutionError(format!("Simulation failed: {}", e)))?;
-        
-        let simulation_duration = simulation_start.elapsed();
-        
-        if !completed {
-            // Simulation was interrupted
-            return Err(CommandError::Interrupted);
-        }
-
-        // Send final progress message for 100% completion
-        progress_sender_clone(ProgressInfo {
-            percent_complete: 100.0,
-            current_step: format!("Running simulation - Processing timestep {} of {}", total_timesteps, total_timesteps),
-            estimated_remaining: None,
-            data: None,
-            current: None,
-            total: None,
-            task_type: None,
-        });
 
         // Collect output information
         let outputs_generated: Vec<String> = model.outputs.clone();
+        let log_entries = model.run_log().entries().len();
+        let missing_inputs = serde_json::json!({
+            "substituted": model.run_log().count("input_nan_substituted"),
+            "propagated": model.run_log().count("input_nan"),
+        });
+        let window = &model.simulation_window;
+        let period_set_by = serde_json::json!({
+            "start": window.start_set_by,
+            "end": window.end_set_by,
+            "clipped": window.clipped,
+        });
+        let events_recorded = record_events.then(|| model.events().len());
+        let rerun_nodes = model.rerun_nodes().map(|nodes| {
+            use crate::nodes::Node;
+            nodes.iter().map(|&i| model.nodes[i].get_name().to_string()).collect::<Vec<_>>()
+        });
         
         // Store simulation metadata in session results
         let simulation_metadata = serde_json::json!({
@@ -677,222 +789,85 @@ impl Command for RunSimulationCommand {
                 crate::tid::utils::u64_to_date_string_for_step_size(start_timestamp, stepsize),
                 crate::tid::utils::u64_to_date_string_for_step_size(end_timestamp, stepsize)
             ),
+            "simulation_period_set_by": period_set_by,
+            "inputs_overridden": input_overrides.keys().collect::<Vec<_>>(),
+            "node_timing": node_timing,
+            "log_entries": log_entries,
+            "missing_input_values": missing_inputs,
+            "events_recorded": events_recorded,
+            "rerun_nodes": rerun_nodes,
             "execution_time_seconds": simulation_duration.as_secs(),
             "available_results": ["timeseries_data", "summary_statistics"]
         }))
     }
 }
 
-pub struct RunOptimisationCommand;
+pub struct StepCommand;
 
-impl Command for RunOptimisationCommand {
+impl Command for StepCommand {
     fn name(&self) -> &str {
-        "run_optimisation"
+        "step"
     }
 
     fn description(&self) -> &str {
-        "Run parameter optimisation with specified configuration"
+        "Run the next timesteps of a stepped run started by run_simulation with step_mode, then pause again"
     }
 
     fn parameters(&self) -> Vec<ParameterSpec> {
         vec![
             ParameterSpec {
-                name: "config".to_string(),
-                param_type: "string".to_string(),
-                required: true,
-                default: None,
-            },
-            ParameterSpec {
-                name: "model_ini".to_string(),
-                param_type: "string".to_string(),
+                name: "n".to_string(),
+                param_type: "integer".to_string(),
                 required: false,
-                default: None,
-            }
+                default: Some(serde_json::json!(1)),
+            },
+            model_id_spec(),
         ]
     }
 
     fn interruptible(&self) -> bool {
-        true
+        false
     }
 
     fn execute(
         &self,
         session: &mut Session,
         params: serde_json::Value,
-        progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
     ) -> Result<serde_json::Value, CommandError> {
-        use crate::numerical::opt::{
-            OptimisationConfig, OptimisationProblem,
-            Optimizer, OptimizationProgress, create_optimizer_with_callback
-        };
-        use crate::io::optimisation_config_io::load_observed_for_term;
-        use crate::numerical::opt::optimisation::ComparisonPair;
-        use crate::functions::parse_function;
-
-        // Extract config string parameter
-        let config_str = params.get("config")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| CommandError::InvalidParameters("config is required".to_string()))?;
-
-        // Parse optimisation configuration from INI format
-        let config = OptimisationConfig::from_ini(config_str)
-            .map_err(|e| CommandError::InvalidParameters(format!("Failed to parse optimisation config: {}", e)))?;
-
-        // Load model with priority: inline model_ini > config model_file > session model
-        let model = if let Some(model_ini) = params.get("model_ini").and_then(|v| v.as_str()) {
-            // Priority 1: Use inline model parameter
-            IniModelIO::new().read_model_string(model_ini)
-                .map_err(|e| CommandError::ExecutionError(format!("Failed to parse inline model: {}", e)))?
-        } else if let Some(model_file) = &config.model_file {
-            // Priority 2: Use model_file from config
-            IniModelIO::new().read_model_file(model_file)
-                .map_err(|e| CommandError::ExecutionError(format!("Failed to load model from '{}': {}", model_file, e)))?
-        } else if let Some(session_model) = session.get_model() {
-            // Priority 3: Use session's loaded model
-            session_model.clone()
-        } else {
-            return Err(CommandError::ModelNotLoaded);
+        let n = match params.get("n") {
+            None | Some(serde_json::Value::Null) => 1,
+            Some(v) => v.as_u64().ok_or(CommandError::InvalidParameters(
+                format!("n must be a non-negative integer, not {}", v)))? as usize,
         };
-
-        // Build comparison pairs from terms (load each observed series)
-        let mut comparisons: Vec<ComparisonPair> = Vec::with_capacity(config.terms.len());
-        for term in &config.terms {
-            let observed = load_observed_for_term(&term.observed_file, &term.observed_series)
-                .map_err(|e| CommandError::ExecutionError(
-                    format!("Failed to load observed data for term '{}': {}", term.name, e)
-                ))?;
-            comparisons.push(ComparisonPair {
-                name: term.name.clone(),
-                observed: observed.timeseries,
-                simulated_series_name: term.simulated_series.clone(),
-                statistic: term.statistic.clone(),
-            });
-        }
-
-        // Parse the composite objective expression once
-        let expression = parse_function(&config.objective_expression)
-            .map_err(|e| CommandError::ExecutionError(
-                format!("Failed to parse objective_expression '{}': {}", config.objective_expression, e)
-            ))?;
-
-        let mut problem = OptimisationProblem::new(
-            model,
-            config.parameter_config.clone(),
-            comparisons,
-            expression,
-        );
-
-        // Get interrupt flag
-        let interrupt_flag = std::sync::Arc::clone(&session.interrupt_flag);
-
-        // Create progress callback that sends STDIO progress messages
-        let termination_evals = config.termination_evaluations;
-        let progress_callback = Box::new(move |progress: &OptimizationProgress| {
-            // Check for interrupt
-            if interrupt_flag.load(std::sync::atomic::Ordering::Relaxed) {
-                return;
-            }
-
-            // Build diversity sample: [best, ...up to 10 random samples from population]
-            let mut data_values = vec![progress.best_objective];
-
-            if let Some(ref pop_objectives) = progress.population_objectives {
-                use rand::seq::SliceRandom;
-
-                // Find index of best objective to exclude it from sampling
-                let best_idx = pop_objectives.iter()
-                    .enumerate()
-                    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                    .map(|(idx, _)| idx);
-
-                // Create indices excluding the best
-                let mut available_indices: Vec<usize> = (0..pop_objectives.len())
-                    .filter(|&i| Some(i) != best_idx)
-                    .collect();
-
-                // Sample up to 10 random indices (without replacement)
-                let sample_size = 10.min(available_indices.len());
-                available_indices.shuffle(&mut rand::thread_rng());
-
-                // Collect sampled values and sort them (low to high, best to worst)
-                let mut sampled: Vec<f64> = available_indices.iter()
-                    .take(sample_size)
-                    .map(|&i| pop_objectives[i])
-                    .collect();
-                sampled.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-                data_values.extend(sampled);
-            }
-
-            progress_sender(ProgressInfo {
-                percent_complete: (progress.n_evaluations as f64 / termination_evals as f64) * 100.0,
-                current_step: format!("{} evaluations, best objective = {:.6}",
-                    progress.n_evaluations, progress.best_objective),
-                estimated_remaining: None,
-                data: Some(data_values),
-                current: Some(progress.n_evaluations as i64),
-                total: Some(termination_evals as i64),
-                task_type: Some("opt".to_string()),
-            });
-        });
-
-        // Create optimizer with progress callback configured
-        let optimiser: Box<dyn Optimizer> = create_optimizer_with_callback(&config, Some(progress_callback))
-            .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
-
-        // Run optimisation (callback already configured in optimizer)
-        let result = optimiser.optimize(&mut problem, None);
-
-        // Check if interrupted
-        if session.check_interrupt() {
-            return Err(CommandError::Interrupted);
-        }
-
-        // Get physical parameter values
-        let params_physical = problem.config.evaluate(&result.best_params);
-
-        // Apply best parameters to the model to get the optimized model
-        problem.set_params(&result.best_params)
-            .map_err(|e| CommandError::ExecutionError(format!("Failed to apply best parameters: {}", e)))?;
-
-        // Serialize the optimized model to INI string
-        let optimised_model_ini = IniModelIO::new().model_to_string(&problem.model);
-
-        // Build result JSON
-        let mut result_json = serde_json::json!({
-            "best_objective": result.best_objective,
-            "evaluations": result.n_evaluations,
-            "params_normalized": result.best_params,
-            "params_physical": params_physical.into_iter().collect::<std::collections::HashMap<_, _>>(),
-            "optimised_model_ini": optimised_model_ini,
-            "success": result.success,
-            "message": result.message
-        });
-
-        // Add algorithm-specific data (e.g., generations for DE)
-        if let Some(obj) = result_json.as_object_mut() {
-            for (key, value) in result.algorithm_data {
-                obj.insert(key, value);
-            }
-        }
-
-        Ok(result_json)
+        let model = session.get_model_by_id_mut(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+        let steps_run = model.step(n).map_err(CommandError::ExecutionError)?;
+        Ok(stepped_run_json(model, steps_run))
     }
 }
 
-pub struct GetOptimisableParamsCommand;
+pub struct RunToCommand;
 
-impl Command for GetOptimisableParamsCommand {
+impl Command for RunToCommand {
     fn name(&self) -> &str {
-        "get_optimisable_params"
+        "run_to"
     }
 
     fn description(&self) -> &str {
-        "Get list of all optimisable parameters from the loaded model"
+        "Run a stepped run started by run_simulation with step_mode up to a date, and pause before its timestep"
     }
 
     fn parameters(&self) -> Vec<ParameterSpec> {
-        vec![] // No parameters required
+        vec![
+            ParameterSpec {
+                name: "date".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            model_id_spec(),
+        ]
     }
 
     fn interruptible(&self) -> bool {
@@ -902,49 +877,2224 @@ impl Command for GetOptimisableParamsCommand {
     fn execute(
         &self,
         session: &mut Session,
-        _params: serde_json::Value,
+        params: serde_json::Value,
         _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
     ) -> Result<serde_json::Value, CommandError> {
-        use crate::numerical::opt::OptimisableComponent;
-        use crate::nodes::NodeEnum;
-
-        // Get model and check if it exists
-        let model = session.get_model()
+        let date = date_param(&params, "date")?
+            .ok_or(CommandError::InvalidParameters("Missing required parameter: date".to_string()))?;
+        let model = session.get_model_by_id_mut(model_id(&params))
             .ok_or(CommandError::ModelNotLoaded)?;
+        let steps_run = model.run_to(date).map_err(CommandError::ExecutionError)?;
+        Ok(stepped_run_json(model, steps_run))
+    }
+}
 
-        // Collect optimisable parameters from all components
-        let mut params = Vec::new();
+pub struct InspectCommand;
 
-        // Add constants (already prefixed with "c.")
-        for constant_name in model.data_cache.list_params() {
-            params.push(constant_name);
+impl Command for InspectCommand {
+    fn name(&self) -> &str {
+        "inspect"
+    }
+
+    fn description(&self) -> &str {
+        "Get the stores, flows and recorded results of nodes at the end of the last timestep of a stepped run"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "nodes".to_string(),
+                param_type: "array".to_string(),
+                required: false,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::hydrology::constituents::NodeWater;
+        use crate::nodes::Node;
+
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+        let names: Vec<String> = match params.get("nodes") {
+            None | Some(serde_json::Value::Null) => model.nodes.iter().map(|n| n.get_name().to_string()).collect(),
+            Some(serde_json::Value::Array(names)) => names.iter()
+                .map(|v| v.as_str().map(str::to_string)
+                    .ok_or(CommandError::InvalidParameters(format!("Node names must be strings, not {}", v))))
+                .collect::<Result<_, _>>()?,
+            Some(v) => return Err(CommandError::InvalidParameters(format!("nodes must be an array of node names, not {}", v))),
+        };
+        let mut nodes = Vec::new();
+        for name in &names {
+            let node = model.inspect_node(name).map_err(CommandError::InvalidParameters)?;
+            let water = match node.water {
+                NodeWater::PassThrough { lateral_inflow } => serde_json::json!({"lateral_inflow": lateral_inflow}),
+                NodeWater::Runoff { quickflow, baseflow } => serde_json::json!({"quickflow": quickflow, "baseflow": baseflow}),
+                NodeWater::Store { volume, outflow, area_km2 } => serde_json::json!({"volume": volume, "outflow": outflow, "area_km2": area_km2}),
+            };
+            let results: serde_json::Map<String, serde_json::Value> = node.results.into_iter()
+                .map(|(name, value)| (name, serde_json::json!(value)))
+                .collect();
+            nodes.push(serde_json::json!({
+                "name": node.name,
+                "type": node.node_type,
+                "state": node.state,
+                "water": water,
+                "mass_balance": node.mass_balance,
+                "results": results,
+            }));
+        }
+        let mut result = stepped_run_json(model, 0);
+        if let Some(map) = result.as_object_mut() {
+            map.remove("steps_run");
+            map.insert("nodes".to_string(), serde_json::json!(nodes));
+        }
+        Ok(result)
+    }
+}
+
+/// Where a stepped run is paused, after `steps_run` timesteps were run by the last command
+fn stepped_run_json(model: &crate::model::Model, steps_run: usize) -> serde_json::Value {
+    let date = |t: Option<u64>| t.map(|t| tid::utils::u64_to_date_string_for_step_size(t, model.configuration.sim_stepsize));
+    serde_json::json!({
+        "paused": model.is_stepping(),
+        "steps_run": steps_run,
+        "last_step": date(model.last_step_timestamp()),
+        "next_step": date(model.next_step_timestamp()),
+        "simulation_period": format!("{} to {}",
+            date(Some(model.configuration.sim_start_timestamp)).unwrap(),
+            date(Some(model.configuration.sim_end_timestamp)).unwrap()),
+    })
+}
+
+/// Replaces the model's input series with the `inputs` overrides given to run_simulation,
+/// recording the replaced data in `replaced` so it can be restored. Each override is either
+/// an array of values on the input's own timestep (starting at `start` if given, otherwise
+/// where the original series starts), or CSV text with a date column and one value column.
+fn apply_input_overrides(
+    model: &mut crate::model::Model,
+    overrides: &serde_json::Map<String, serde_json::Value>,
+    start: Option<u64>,
+    replaced: &mut Vec<(String, crate::timeseries::Timeseries)>,
+) -> Result<(), CommandError> {
+    use crate::timeseries::Timeseries;
+
+    for (name, value) in overrides {
+        let original = model.get_input_series(name)
+            .ok_or_else(|| CommandError::InvalidParameters(format!("Input series not found: {}", name)))?;
+        let step_size = original.step_size;
+
+        let timeseries = match value {
+            serde_json::Value::Array(values) => {
+                let mut ts = Timeseries::new(step_size);
+                ts.start_timestamp = start.unwrap_or(original.start_timestamp);
+                for v in values {
+                    // Nulls are missing values
+                    ts.push_value(if v.is_null() { f64::NAN } else {
+                        v.as_f64().ok_or_else(|| CommandError::InvalidParameters(
+                            format!("Override for '{}' contains a non-numeric value: {}", name, v)
+                        ))?
+                    });
+                }
+                ts
+            }
+            serde_json::Value::String(csv_text) => {
+                let source = format!("override for '{}'", name);
+                let mut columns = csv_io::read_ts_from_str(csv_text, &source)
+                    .map_err(CommandError::InvalidParameters)?;
+                if columns.len() != 1 {
+                    return Err(CommandError::InvalidParameters(format!(
+                        "CSV {} must have one value column but has {}", source, columns.len()
+                    )));
+                }
+                let mut ts = columns.remove(0);
+                if ts.values.len() < 2 {
+                    ts.step_size = step_size; // A single row has no step of its own
+                }
+                ts
+            }
+            _ => return Err(CommandError::InvalidParameters(format!(
+                "Override for '{}' must be an array of values or CSV text", name
+            ))),
+        };
+        if timeseries.step_size != step_size {
+            return Err(CommandError::InvalidParameters(format!(
+                "Override for '{}' has step_size {} but the input has step_size {}",
+                name, timeseries.step_size, step_size
+            )));
+        }
+
+        let original = model.replace_input_series(name, timeseries)
+            .map_err(CommandError::InvalidParameters)?;
+        replaced.push((name.clone(), original));
+    }
+    Ok(())
+}
+
+/// Number of slowest individual nodes listed in a node timing breakdown
+const SLOWEST_NODES_REPORTED: usize = 10;
+
+/// Events listed by `get_events` unless the client asks for more
+const DEFAULT_MAX_EVENTS: usize = 10000;
+
+/// JSON breakdown of the time spent so far in each node type and in the slowest nodes
+fn node_timing_json(model: &crate::model::Model, timer: &NodeTimer) -> serde_json::Value {
+    use crate::nodes::Node;
+
+    let names: Vec<String> = model.nodes.iter().map(|n| n.get_name().to_string()).collect();
+    let types: Vec<String> = model.nodes.iter().map(|n| n.get_type_as_string()).collect();
+    node_timing_report_json(&timer.report(&names, &types, SLOWEST_NODES_REPORTED))
+}
+
+fn node_timing_report_json(report: &crate::misc::node_timing::NodeTimingReport) -> serde_json::Value {
+    let ms = |d: &std::time::Duration| d.as_secs_f64() * 1000.0;
+    serde_json::json!({
+        "total_ms": ms(&report.total),
+        "by_type": report.by_type.iter()
+            .map(|(node_type, time)| serde_json::json!({"type": node_type, "ms": ms(time)}))
+            .collect::<Vec<_>>(),
+        "slowest": report.slowest.iter()
+            .map(|(node, time)| serde_json::json!({"node": node, "ms": ms(time)}))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Configures and runs the model, reporting progress. Returns the time spent simulating.
+fn simulate_with_progress(
+    model: &mut crate::model::Model,
+    interrupt_flag: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+) -> Result<std::time::Duration, CommandError> {
+    use std::time::Instant;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // Try to configure the model simulation period
+    match model.configure() {
+        Ok(_) => (),
+        Err(e) => {
+            return Err(CommandError::ExecutionError(format!("Configuration failed: {}", e)));
+        }
+    }
+    
+    // Get simulation info for progress reporting
+    let start_timestamp = model.configuration.sim_start_timestamp;
+    let end_timestamp = model.configuration.sim_end_timestamp;
+    let stepsize = model.configuration.sim_stepsize;
+    let total_timesteps = ((end_timestamp - start_timestamp) / stepsize) + 1;
+    
+    // Track progress timing for rate limiting (max 1 update per 200ms, 1 per percentage)
+    let last_progress_time = Arc::new(std::sync::Mutex::new(Instant::now()));
+    let last_progress_percent = Arc::new(AtomicU64::new(0));
+    
+    // With a node timer attached, every progress update carries the timing breakdown so far
+    let timing_snapshot: Arc<dyn Fn() -> Option<serde_json::Value> + Send + Sync> = match &model.node_timer {
+        Some(timer) => {
+            use crate::nodes::Node;
+            let timer = Arc::clone(timer);
+            let names: Vec<String> = model.nodes.iter().map(|n| n.get_name().to_string()).collect();
+            let types: Vec<String> = model.nodes.iter().map(|n| n.get_type_as_string()).collect();
+            Arc::new(move || Some(node_timing_report_json(&timer.report(&names, &types, SLOWEST_NODES_REPORTED))))
+        }
+        None => Arc::new(|| None),
+    };
+
+    // Create progress callback for the model
+    let progress_sender_clone = Arc::new(progress_sender);
+    let progress_callback = {
+        let last_time = Arc::clone(&last_progress_time);
+        let last_percent = Arc::clone(&last_progress_percent);
+        let sender = Arc::clone(&progress_sender_clone);
+        let timing_snapshot = Arc::clone(&timing_snapshot);
+        let mut throughput = RollingThroughput::new(THROUGHPUT_WINDOW);
+        throughput.record(Instant::now(), 0);
+        
+        Box::new(move |current_step: u64, total_steps: u64| {
+            // Calculate percentage (0% to 100% range for simulation)
+            let sim_progress = (current_step as f64 / total_steps as f64) * 100.0;
+            let overall_progress = sim_progress;
+            let overall_percent = overall_progress as u64;
+            
+            // Check rate limiting conditions
+            let now = Instant::now();
+            let should_update = {
+                let mut last_time_guard = last_time.lock().unwrap();
+                let time_elapsed = now.duration_since(*last_time_guard).as_millis() >= 200;
+                let percent_changed = overall_percent > last_percent.load(Ordering::Relaxed);
+                
+                if time_elapsed && percent_changed {
+                    *last_time_guard = now;
+                    last_percent.store(overall_percent, Ordering::Relaxed);
+                    true
+                } else {
+                    false
+                }
+            };
+            
+            if should_update {
+                throughput.record(now, current_step + 1);
+                sender(ProgressInfo {
+                    percent_complete: overall_progress,
+                    current_step: format!("Running simulation - Processing timestep {} of {}", current_step + 1, total_steps),
+                    estimated_remaining: throughput.estimated_remaining(total_steps),
+                    data: None,
+                    current: None,
+                    total: None,
+                    task_type: None,
+                    timing: timing_snapshot(),
+                });
+            }
+        })
+    };
+    
+    // Send initial progress message
+    let progress_sender_clone = Arc::clone(&progress_sender_clone);
+    progress_sender_clone(ProgressInfo {
+        percent_complete: 0.0,
+        current_step: format!("Running simulation - Processing timestep 1 of {}", total_timesteps),
+        estimated_remaining: None,
+        data: None,
+        current: None,
+        total: None,
+        task_type: None,
+        timing: None,
+    });
+
+    // Simulation phase - 20% to 90%
+    let simulation_start = Instant::now();
+
+    // Run the simulation with interrupt checking
+    let completed = model.run_with_interrupt(
+        move || interrupt_flag.load(Ordering::Relaxed),
+        Some(progress_callback)
+    ).map_err(|e| CommandError::ExecutionError(format!("Simulation failed: {}", e)))?;
+    
+    let simulation_duration = simulation_start.elapsed();
+    
+    if !completed {
+        // Simulation was interrupted
+        return Err(CommandError::Interrupted);
+    }
+
+    // Send final progress message for 100% completion
+    progress_sender_clone(ProgressInfo {
+        percent_complete: 100.0,
+        current_step: format!("Running simulation - Processing timestep {} of {}", total_timesteps, total_timesteps),
+        estimated_remaining: None,
+        data: None,
+        current: None,
+        total: None,
+        task_type: None,
+        timing: timing_snapshot(),
+    });
+
+    Ok(simulation_duration)
+}
+
+/// Builds the optimisation problem described by a calibration config.
+///
+/// The model is taken, in priority order, from the inline `model_ini` parameter, the
+/// config's `model_file`, or the session model with id `model_id`.
+fn build_optimisation_problem(
+    session: &Session,
+    model_id: &str,
+    params: &serde_json::Value,
+    config: &crate::numerical::opt::OptimisationConfig,
+) -> Result<crate::numerical::opt::OptimisationProblem, CommandError> {
+    use crate::numerical::opt::OptimisationProblem;
+    use crate::io::optimisation_config_io::load_observed_for_term;
+    use crate::numerical::opt::optimisation::ComparisonPair;
+    use crate::functions::parse_function;
+
+    if !config.catchments.is_empty() {
+        return Err(CommandError::InvalidParameters(
+            "Regional calibrations ([catchment.NAME] sections) are run with 'kalix optimise'".to_string()));
+    }
+
+    // Load model with priority: inline model_ini > config model_file > session model
+    let model = if let Some(model_ini) = params.get("model_ini").and_then(|v| v.as_str()) {
+        // Priority 1: Use inline model parameter
+        IniModelIO::new().read_model_string(model_ini)
+            .map_err(|e| CommandError::ExecutionError(format!("Failed to parse inline model: {}", e)))?
+    } else if let Some(model_file) = &config.model_file {
+        // Priority 2: Use model_file from config
+        IniModelIO::new().read_model_file(model_file)
+            .map_err(|e| CommandError::ExecutionError(format!("Failed to load model from '{}': {}", model_file, e)))?
+    } else if let Some(session_model) = session.get_model_by_id(model_id) {
+        // Priority 3: Use session's loaded model
+        session_model.clone()
+    } else {
+        return Err(CommandError::ModelNotLoaded);
+    };
+
+    // Build comparison pairs from terms (load each observed series)
+    let mut comparisons: Vec<ComparisonPair> = Vec::with_capacity(config.terms.len());
+    for term in &config.terms {
+        let observed = load_observed_for_term(&term.observed_file, &term.observed_series)
+            .map_err(|e| CommandError::ExecutionError(
+                format!("Failed to load observed data for term '{}': {}", term.name, e)
+            ))?;
+        comparisons.push(ComparisonPair {
+            name: term.name.clone(),
+            observed: Arc::unwrap_or_clone(observed.timeseries),
+            simulated_series_name: term.simulated_series.clone(),
+            statistic: term.statistic.clone(),
+            transforms: term.transforms.clone(),
+        });
+    }
+
+    // Parse the composite objective expression once
+    let expression = parse_function(&config.objective_expression)
+        .map_err(|e| CommandError::ExecutionError(
+            format!("Failed to parse objective_expression '{}': {}", config.objective_expression, e)
+        ))?;
+
+    let objectives = config.parse_objective_expressions()
+        .map_err(CommandError::ExecutionError)?;
+
+    Ok(OptimisationProblem::new(
+        model,
+        config.parameter_config.clone(),
+        comparisons,
+        expression,
+    ).with_objectives(objectives).with_period(config.calibration_period))
+}
+
+/// JSON for the objective and per-term losses of a parameter set over one period
+fn period_metrics_json(metrics: &crate::numerical::opt::PeriodMetrics) -> serde_json::Value {
+    let terms: serde_json::Map<String, serde_json::Value> = metrics.terms.iter()
+        .map(|(name, value)| (name.clone(), serde_json::json!(value)))
+        .collect();
+    serde_json::json!({
+        "start": metrics.period.map(|(start, _)| tid::utils::u64_to_iso_datetime_string(start)),
+        "end": metrics.period.map(|(_, end)| tid::utils::u64_to_iso_datetime_string(end)),
+        "objective": metrics.objective,
+        "terms": terms,
+    })
+}
+
+/// Session result key under which the last `run_optimisation` result is kept
+const LAST_OPTIMISATION_KEY: &str = "last_optimisation";
+
+/// Applies the `params_physical` of an optimisation result to a session model, writing them
+/// back into its INI document, and optionally saves the updated INI to `path`
+fn apply_calibration_to_model(
+    session: &mut Session,
+    model_id: &str,
+    result: &serde_json::Value,
+    path: Option<&str>,
+) -> Result<serde_json::Value, CommandError> {
+    let mut parameters: Vec<(String, f64)> = result.get("params_physical")
+        .and_then(|v| v.as_object())
+        .ok_or_else(|| CommandError::ExecutionError("The optimisation result has no physical parameter values".to_string()))?
+        .iter()
+        .map(|(target, value)| value.as_f64()
+            .map(|v| (target.clone(), v))
+            .ok_or_else(|| CommandError::ExecutionError(format!("Invalid value for parameter '{}'", target))))
+        .collect::<Result<_, _>>()?;
+    parameters.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let model = session.get_model_by_id_mut(model_id)
+        .ok_or(CommandError::ModelNotLoaded)?;
+    model.apply_calibration(&parameters)
+        .map_err(CommandError::ExecutionError)?;
+
+    let saved_path = match path {
+        Some(path) => {
+            model.save_ini_to_file(path).map_err(CommandError::IoError)?;
+            Some(std::path::Path::new(path)
+                .canonicalize()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or(path.to_string()))
+        }
+        None => None,
+    };
+
+    let values: serde_json::Map<String, serde_json::Value> = parameters.into_iter()
+        .map(|(target, value)| (target, serde_json::json!(value)))
+        .collect();
+    Ok(serde_json::json!({
+        "model_id": model_id,
+        "parameters": values,
+        "path": saved_path
+    }))
+}
+
+pub struct RunOptimisationCommand;
+
+impl Command for RunOptimisationCommand {
+    fn name(&self) -> &str {
+        "run_optimisation"
+    }
+
+    fn description(&self) -> &str {
+        "Run parameter optimisation with specified configuration"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "config".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "model_ini".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "apply".to_string(),
+                param_type: "boolean".to_string(),
+                required: false,
+                default: Some(serde_json::Value::Bool(false)),
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        true
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::numerical::opt::{
+            OptimisationConfig, Optimizer, OptimizationProgress, StopFlag, create_optimizer_with_callback
+        };
+
+        // Extract config string parameter
+        let config_str = params.get("config")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CommandError::InvalidParameters("config is required".to_string()))?;
+
+        // Parse optimisation configuration from INI format
+        let mut config = OptimisationConfig::from_ini(config_str)
+            .map_err(|e| CommandError::InvalidParameters(format!("Failed to parse optimisation config: {}", e)))?;
+
+        // A stop request ends the run early with the best parameters found so far
+        config.early_stopping.stop_flag = Some(StopFlag::new(std::sync::Arc::clone(&session.interrupt_flag)));
+
+        let mut problem = build_optimisation_problem(session, model_id(&params), &params, &config)?;
+
+        // Get interrupt flag
+        let interrupt_flag = std::sync::Arc::clone(&session.interrupt_flag);
+
+        // Create progress callback that sends STDIO progress messages
+        let termination_evals = config.termination_evaluations;
+        let throughput = std::sync::Mutex::new(RollingThroughput::new(THROUGHPUT_WINDOW));
+        throughput.lock().unwrap().record(std::time::Instant::now(), 0);
+        let progress_callback = Box::new(move |progress: &OptimizationProgress| {
+            // Check for interrupt
+            if interrupt_flag.load(std::sync::atomic::Ordering::Relaxed) {
+                return;
+            }
+
+            let estimated_remaining = {
+                let mut throughput = throughput.lock().unwrap();
+                throughput.record(std::time::Instant::now(), progress.n_evaluations as u64);
+                throughput.estimated_remaining(termination_evals as u64)
+            };
+
+            // Build diversity sample: [best, ...up to 10 random samples from population]
+            let mut data_values = vec![progress.best_objective];
+
+            if let Some(ref pop_objectives) = progress.population_objectives {
+                use rand::seq::SliceRandom;
+
+                // Find index of best objective to exclude it from sampling
+                let best_idx = pop_objectives.iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                    .map(|(idx, _)| idx);
+
+                // Create indices excluding the best
+                let mut available_indices: Vec<usize> = (0..pop_objectives.len())
+                    .filter(|&i| Some(i) != best_idx)
+                    .collect();
+
+                // Sample up to 10 random indices (without replacement)
+                let sample_size = 10.min(available_indices.len());
+                available_indices.shuffle(&mut rand::thread_rng());
+
+                // Collect sampled values and sort them (low to high, best to worst)
+                let mut sampled: Vec<f64> = available_indices.iter()
+                    .take(sample_size)
+                    .map(|&i| pop_objectives[i])
+                    .collect();
+                sampled.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                data_values.extend(sampled);
+            }
+
+            progress_sender(ProgressInfo {
+                percent_complete: (progress.n_evaluations as f64 / termination_evals as f64) * 100.0,
+                current_step: format!("{} evaluations, best objective = {:.6}",
+                    progress.n_evaluations, progress.best_objective),
+                estimated_remaining,
+                data: Some(data_values),
+                current: Some(progress.n_evaluations as i64),
+                total: Some(termination_evals as i64),
+                task_type: Some("opt".to_string()),
+                timing: None,
+            });
+        });
+
+        // Optionally trace every evaluation to the file requested by the config
+        if let Some(trace_file) = &config.trace_file {
+            problem.start_trace(trace_file).map_err(CommandError::ExecutionError)?;
+        }
+
+        // Create optimizer with progress callback configured
+        let optimiser: Box<dyn Optimizer> = create_optimizer_with_callback(&config, problem.trace_progress(Some(progress_callback)))
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+
+        // Run optimisation (callback already configured in optimizer)
+        let result = optimiser.optimize(&mut problem, None);
+        problem.finish_trace().map_err(CommandError::ExecutionError)?;
+
+        // If interrupted, return the best so far unless nothing was evaluated successfully
+        let interrupted = session.check_interrupt();
+        if interrupted && (result.best_params.is_empty() || !result.best_objective.is_finite()) {
+            return Err(CommandError::Interrupted);
+        }
+
+        // Re-evaluate the best parameter set over the calibration and validation periods
+        let (calibration_metrics, validation_metrics) = if result.success && !interrupted
+            && (config.calibration_period.is_some() || config.validation_period.is_some()) {
+            let calibration = problem.period_metrics(&result.best_params, config.calibration_period)
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to evaluate calibration period: {}", e)))?;
+            let validation = config.validation_period
+                .map(|period| problem.period_metrics(&result.best_params, Some(period)))
+                .transpose()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to evaluate validation period: {}", e)))?;
+            (Some(calibration), validation)
+        } else {
+            (None, None)
+        };
+
+        // Get physical parameter values
+        let params_physical = problem.config.evaluate(&result.best_params);
+
+        // Apply best parameters to the model to get the optimized model
+        problem.set_params(&result.best_params)
+            .map_err(|e| CommandError::ExecutionError(format!("Failed to apply best parameters: {}", e)))?;
+
+        // Serialize the optimized model to INI string
+        let optimised_model_ini = IniModelIO::new().model_to_string(&problem.model);
+
+        // Build result JSON
+        let mut result_json = serde_json::json!({
+            "best_objective": result.best_objective,
+            "evaluations": result.n_evaluations,
+            "params_normalized": result.best_params,
+            "params_physical": params_physical.into_iter().collect::<std::collections::HashMap<_, _>>(),
+            "optimised_model_ini": optimised_model_ini,
+            "success": result.success,
+            "interrupted": interrupted,
+            "message": result.message
+        });
+        if let Some(metrics) = &calibration_metrics {
+            result_json["calibration_metrics"] = period_metrics_json(metrics);
+        }
+        if let Some(metrics) = &validation_metrics {
+            result_json["validation_metrics"] = period_metrics_json(metrics);
+        }
+
+        // Add algorithm-specific data (e.g., generations for DE)
+        if let Some(obj) = result_json.as_object_mut() {
+            for (key, value) in result.algorithm_data {
+                obj.insert(key, value);
+            }
+        }
+
+        // Label the Pareto front (multi-objective algorithms) with objective names and physical params
+        if let Some(front) = result_json.get_mut("pareto_front").and_then(|v| v.as_array_mut()) {
+            for member in front.iter_mut() {
+                let params: Vec<f64> = serde_json::from_value(member["params"].clone()).unwrap_or_default();
+                let values: Vec<f64> = serde_json::from_value(member["objectives"].clone()).unwrap_or_default();
+                let objectives: serde_json::Map<String, serde_json::Value> = config.objectives.iter()
+                    .zip(values)
+                    .map(|(o, v)| (o.name.clone(), serde_json::json!(v)))
+                    .collect();
+                *member = serde_json::json!({
+                    "objectives": objectives,
+                    "params_normalized": params,
+                    "params_physical": problem.config.evaluate(&params).into_iter().collect::<std::collections::HashMap<_, _>>(),
+                });
+            }
+        }
+
+        session.store_result(LAST_OPTIMISATION_KEY.to_string(), result_json.clone());
+
+        // Optionally write the best parameters back into the session's model
+        if params.get("apply").and_then(|v| v.as_bool()).unwrap_or(false) {
+            result_json["applied"] = apply_calibration_to_model(session, model_id(&params), &result_json, None)?;
+        }
+        Ok(result_json)
+    }
+}
+
+pub struct GetOptimisationResultCommand;
+
+impl Command for GetOptimisationResultCommand {
+    fn name(&self) -> &str {
+        "get_optimisation_result"
+    }
+
+    fn description(&self) -> &str {
+        "Get the result of the last optimisation, including one that was stopped early"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        _params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        session.get_result(LAST_OPTIMISATION_KEY)
+            .cloned()
+            .ok_or_else(|| CommandError::ExecutionError("No optimisation has been run in this session".to_string()))
+    }
+}
+
+pub struct ApplyCalibrationCommand;
+
+impl Command for ApplyCalibrationCommand {
+    fn name(&self) -> &str {
+        "apply_calibration"
+    }
+
+    fn description(&self) -> &str {
+        "Write the best parameters of the last optimisation into the loaded model, and optionally save it"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "path".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        let result = session.get_result(LAST_OPTIMISATION_KEY)
+            .cloned()
+            .ok_or_else(|| CommandError::ExecutionError("No optimisation has been run in this session".to_string()))?;
+        let path = params.get("path").and_then(|v| v.as_str());
+        apply_calibration_to_model(session, model_id(&params), &result, path)
+    }
+}
+
+pub struct RunBatchCommand;
+
+impl Command for RunBatchCommand {
+    fn name(&self) -> &str {
+        "run_batch"
+    }
+
+    fn description(&self) -> &str {
+        "Run the loaded model once per parameter set, in parallel, and return per-run summaries"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "parameter_sets".to_string(),
+                param_type: "array".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "series".to_string(),
+                param_type: "array".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "return_series".to_string(),
+                param_type: "boolean".to_string(),
+                required: false,
+                default: Some(serde_json::Value::Bool(false)),
+            },
+            ParameterSpec {
+                name: "n_threads".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: Some(serde_json::json!(0)),
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        true
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use std::sync::atomic::Ordering;
+        use crate::misc::batch_run::{BatchOptions, ParameterSet};
+
+        // Each parameter set is an object of {"target": value}, e.g. {"node.a.lztwm": 120.0}
+        let sets_json = params.get("parameter_sets")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| CommandError::InvalidParameters("parameter_sets is required".to_string()))?;
+        let mut param_sets: Vec<ParameterSet> = Vec::with_capacity(sets_json.len());
+        for (i, set_json) in sets_json.iter().enumerate() {
+            let obj = set_json.as_object()
+                .ok_or_else(|| CommandError::InvalidParameters(format!("parameter_sets[{}] must be an object", i)))?;
+            let mut set = ParameterSet::with_capacity(obj.len());
+            for (target, value) in obj {
+                let value = value.as_f64()
+                    .ok_or_else(|| CommandError::InvalidParameters(format!("parameter_sets[{}].{} must be a number", i, target)))?;
+                set.push((target.clone(), value));
+            }
+            param_sets.push(set);
+        }
+
+        let options = BatchOptions {
+            series: params.get("series")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+            return_series: params.get("return_series").and_then(|v| v.as_bool()).unwrap_or(false),
+            n_threads: params.get("n_threads").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+        };
+
+        let interrupt_flag = Arc::clone(&session.interrupt_flag);
+        let model = session.get_model_by_id_mut(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        let progress_callback = Box::new(move |completed: usize, total: usize| {
+            progress_sender(ProgressInfo {
+                percent_complete: (completed as f64 / total as f64) * 100.0,
+                current_step: format!("Completed run {} of {}", completed, total),
+                estimated_remaining: None,
+                data: None,
+                current: Some(completed as i64),
+                total: Some(total as i64),
+                task_type: Some("batch".to_string()),
+                timing: None,
+            });
+        });
+
+        let results = model.run_batch_with_interrupt(
+            &param_sets,
+            &options,
+            move || interrupt_flag.load(Ordering::Relaxed),
+            Some(progress_callback),
+        ).map_err(|e| CommandError::ExecutionError(format!("Batch run failed: {}", e)))?
+            .ok_or(CommandError::Interrupted)?;
+
+        let runs: Vec<serde_json::Value> = results.iter().map(|r| {
+            let mut series = serde_json::Map::new();
+            for s in &r.series {
+                let mut entry = serde_json::json!({
+                    "count": s.summary.count,
+                    "sum": s.summary.sum,
+                    "mean": s.summary.mean,
+                    "min": s.summary.min,
+                    "max": s.summary.max,
+                });
+                if let Some(values) = &s.values {
+                    entry["values"] = serde_json::json!(values);
+                }
+                series.insert(s.name.clone(), entry);
+            }
+            serde_json::json!({
+                "index": r.index,
+                "error": r.error,
+                "series": series,
+            })
+        }).collect();
+
+        Ok(serde_json::json!({
+            "n_runs": runs.len(),
+            "start_timestamp": tid::utils::u64_to_iso_datetime_string(model.configuration.sim_start_timestamp),
+            "timestep_seconds": model.configuration.sim_stepsize,
+            "runs": runs,
+        }))
+    }
+}
+
+pub struct RunSensitivityCommand;
+
+impl Command for RunSensitivityCommand {
+    fn name(&self) -> &str {
+        "run_sensitivity"
+    }
+
+    fn description(&self) -> &str {
+        "Run Sobol or Morris sensitivity analysis of the objective defined by a calibration configuration"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "config".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "model_ini".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "method".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::json!("sobol")),
+            },
+            ParameterSpec {
+                name: "n_samples".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "levels".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: Some(serde_json::json!(4)),
+            },
+            ParameterSpec {
+                name: "seed".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "n_threads".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        true
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use std::sync::atomic::Ordering;
+        use crate::numerical::opt::OptimisationConfig;
+        use crate::numerical::sensitivity::{
+            morris_analysis, sobol_analysis, MorrisConfig, SensitivityProgress, SobolConfig
+        };
+
+        let config_str = params.get("config")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CommandError::InvalidParameters("config is required".to_string()))?;
+
+        // Only the model, terms, objective and parameters of the config are used; algorithm settings are ignored
+        let config = OptimisationConfig::from_ini(config_str)
+            .map_err(|e| CommandError::InvalidParameters(format!("Failed to parse optimisation config: {}", e)))?;
+
+        let problem = build_optimisation_problem(session, model_id(&params), &params, &config)?;
+
+        let method = params.get("method").and_then(|v| v.as_str()).unwrap_or("sobol").to_lowercase();
+        let n_samples = params.get("n_samples").and_then(|v| v.as_u64()).map(|v| v as usize);
+        let seed = params.get("seed").and_then(|v| v.as_u64()).or(config.random_seed);
+        let n_threads = params.get("n_threads").and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(config.n_threads);
+
+        let interrupt_flag = Arc::clone(&session.interrupt_flag);
+        let interrupt_check = move || interrupt_flag.load(Ordering::Relaxed);
+
+        let progress_callback: SensitivityProgress = Box::new(move |completed: usize, total: usize| {
+            progress_sender(ProgressInfo {
+                percent_complete: (completed as f64 / total as f64) * 100.0,
+                current_step: format!("Completed evaluation {} of {}", completed, total),
+                estimated_remaining: None,
+                data: None,
+                current: Some(completed as i64),
+                total: Some(total as i64),
+                task_type: Some("sensitivity".to_string()),
+                timing: None,
+            });
+        });
+
+        match method.as_str() {
+            "sobol" => {
+                let sobol_config = SobolConfig {
+                    n_samples: n_samples.unwrap_or(SobolConfig::default().n_samples),
+                    n_threads,
+                };
+                let result = sobol_analysis(&problem, &sobol_config, interrupt_check, Some(progress_callback))
+                    .map_err(|e| CommandError::ExecutionError(format!("Sensitivity analysis failed: {}", e)))?
+                    .ok_or(CommandError::Interrupted)?;
+
+                let parameters: Vec<serde_json::Value> = result.param_names.iter().enumerate()
+                    .map(|(i, name)| serde_json::json!({
+                        "name": name,
+                        "first_order": result.first_order[i],
+                        "total_order": result.total_order[i],
+                    }))
+                    .collect();
+
+                Ok(serde_json::json!({
+                    "method": "sobol",
+                    "n_evaluations": result.n_evaluations,
+                    "variance": result.variance,
+                    "parameters": parameters,
+                }))
+            }
+            "morris" => {
+                let morris_config = MorrisConfig {
+                    n_trajectories: n_samples.unwrap_or(MorrisConfig::default().n_trajectories),
+                    levels: params.get("levels").and_then(|v| v.as_u64()).unwrap_or(4) as usize,
+                    seed,
+                    n_threads,
+                };
+                let result = morris_analysis(&problem, &morris_config, interrupt_check, Some(progress_callback))
+                    .map_err(|e| CommandError::ExecutionError(format!("Sensitivity analysis failed: {}", e)))?
+                    .ok_or(CommandError::Interrupted)?;
+
+                let parameters: Vec<serde_json::Value> = result.param_names.iter().enumerate()
+                    .map(|(i, name)| serde_json::json!({
+                        "name": name,
+                        "mu": result.mu[i],
+                        "mu_star": result.mu_star[i],
+                        "sigma": result.sigma[i],
+                    }))
+                    .collect();
+
+                Ok(serde_json::json!({
+                    "method": "morris",
+                    "n_evaluations": result.n_evaluations,
+                    "parameters": parameters,
+                }))
+            }
+            other => Err(CommandError::InvalidParameters(
+                format!("Unknown sensitivity method: '{}'. Valid options: sobol, morris", other))),
+        }
+    }
+}
+
+pub struct RunGlueCommand;
+
+impl Command for RunGlueCommand {
+    fn name(&self) -> &str {
+        "run_glue"
+    }
+
+    fn description(&self) -> &str {
+        "Run GLUE Monte Carlo uncertainty analysis and write prediction bounds to the loaded model's results"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "config".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "model_ini".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "threshold".to_string(),
+                param_type: "number".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "n_samples".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: Some(serde_json::json!(1000)),
+            },
+            ParameterSpec {
+                name: "lower_quantile".to_string(),
+                param_type: "number".to_string(),
+                required: false,
+                default: Some(serde_json::json!(0.05)),
+            },
+            ParameterSpec {
+                name: "upper_quantile".to_string(),
+                param_type: "number".to_string(),
+                required: false,
+                default: Some(serde_json::json!(0.95)),
+            },
+            ParameterSpec {
+                name: "series".to_string(),
+                param_type: "array".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "seed".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "n_threads".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        true
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use std::sync::atomic::Ordering;
+        use crate::numerical::glue::{glue_analysis, GlueConfig};
+        use crate::numerical::opt::OptimisationConfig;
+
+        let config_str = params.get("config")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CommandError::InvalidParameters("config is required".to_string()))?;
+
+        // Only the model, terms, objective and parameters of the config are used; algorithm settings are ignored
+        let config = OptimisationConfig::from_ini(config_str)
+            .map_err(|e| CommandError::InvalidParameters(format!("Failed to parse optimisation config: {}", e)))?;
+
+        let problem = build_optimisation_problem(session, model_id(&params), &params, &config)?;
+
+        let defaults = GlueConfig::default();
+        let glue_config = GlueConfig {
+            n_samples: params.get("n_samples").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(defaults.n_samples),
+            threshold: params.get("threshold").and_then(|v| v.as_f64())
+                .ok_or_else(|| CommandError::InvalidParameters("threshold is required".to_string()))?,
+            lower_quantile: params.get("lower_quantile").and_then(|v| v.as_f64()).unwrap_or(defaults.lower_quantile),
+            upper_quantile: params.get("upper_quantile").and_then(|v| v.as_f64()).unwrap_or(defaults.upper_quantile),
+            series: params.get("series")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+            seed: params.get("seed").and_then(|v| v.as_u64()).or(config.random_seed),
+            n_threads: params.get("n_threads").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(config.n_threads),
+        };
+
+        let interrupt_flag = Arc::clone(&session.interrupt_flag);
+        let progress_callback = Box::new(move |completed: usize, total: usize| {
+            progress_sender(ProgressInfo {
+                percent_complete: (completed as f64 / total as f64) * 100.0,
+                current_step: format!("Completed run {} of {}", completed, total),
+                estimated_remaining: None,
+                data: None,
+                current: Some(completed as i64),
+                total: Some(total as i64),
+                task_type: Some("glue".to_string()),
+                timing: None,
+            });
+        });
+
+        let result = glue_analysis(
+            &problem,
+            &glue_config,
+            move || interrupt_flag.load(Ordering::Relaxed),
+            Some(progress_callback),
+        ).map_err(|e| CommandError::ExecutionError(format!("GLUE analysis failed: {}", e)))?
+            .ok_or(CommandError::Interrupted)?;
+
+        // Bounds go to the loaded model so they can be fetched with get_result
+        let written_to_model = match session.get_model_by_id_mut(model_id(&params)) {
+            Some(model) => {
+                result.write_to_data_cache(&mut model.data_cache);
+                true
+            }
+            None => false,
+        };
+
+        let behavioural: Vec<serde_json::Value> = result.behavioural.iter().map(|set| {
+            let physical: serde_json::Map<String, serde_json::Value> = problem.config.evaluate(&set.params)
+                .into_iter()
+                .map(|(target, value)| (target, serde_json::json!(value)))
+                .collect();
+            serde_json::json!({
+                "sample": set.sample,
+                "objective": set.objective,
+                "weight": set.weight,
+                "params_normalized": set.params,
+                "params_physical": physical,
+            })
+        }).collect();
+
+        let bounds: Vec<serde_json::Value> = result.bounds.iter().map(|b| serde_json::json!({
+            "series": b.series_name,
+            "lower": b.lower_name,
+            "upper": b.upper_name,
+        })).collect();
+
+        Ok(serde_json::json!({
+            "n_samples": result.n_samples,
+            "n_failed": result.n_failed,
+            "n_behavioural": result.behavioural.len(),
+            "param_names": result.param_names,
+            "behavioural": behavioural,
+            "bounds": bounds,
+            "written_to_model": written_to_model,
+        }))
+    }
+}
+
+pub struct RunForecastCommand;
+
+impl Command for RunForecastCommand {
+    fn name(&self) -> &str {
+        "run_forecast"
+    }
+
+    fn description(&self) -> &str {
+        "Run a forecast ensemble from the state on the forecast date and write member and quantile series to the model's results"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "forecast_start".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "forecast_end".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "inputs".to_string(),
+                param_type: "object".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "state".to_string(),
+                param_type: "object".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "series".to_string(),
+                param_type: "array".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "quantiles".to_string(),
+                param_type: "array".to_string(),
+                required: false,
+                default: Some(serde_json::json!([0.1, 0.5, 0.9])),
+            },
+            ParameterSpec {
+                name: "n_threads".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: Some(serde_json::json!(0)),
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        true
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use std::sync::atomic::Ordering;
+        use crate::misc::forecast::{member_series_name, ForecastConfig, ForecastInput, ModelState};
+
+        let forecast_start = date_param(&params, "forecast_start")?
+            .ok_or_else(|| CommandError::InvalidParameters("forecast_start is required".to_string()))?;
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        // Each input is CSV text with a column per member, or an array of members, each an
+        // array of values on the input's own timestep starting at forecast_start
+        let inputs_json = params.get("inputs")
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| CommandError::InvalidParameters("inputs must be an object mapping input series names to forecast members".to_string()))?;
+        let mut inputs = Vec::with_capacity(inputs_json.len());
+        for (name, value) in inputs_json {
+            let input = match value {
+                serde_json::Value::String(csv_text) => ForecastInput::from_csv(name, csv_text)
+                    .map_err(CommandError::InvalidParameters)?,
+                serde_json::Value::Array(members) => {
+                    let step_size = model.get_input_series(name)
+                        .ok_or_else(|| CommandError::InvalidParameters(format!("Input series not found: {}", name)))?
+                        .step_size;
+                    let mut series = Vec::with_capacity(members.len());
+                    for (m, member) in members.iter().enumerate() {
+                        let mut ts = Timeseries::new(step_size);
+                        ts.start_timestamp = forecast_start;
+                        for v in member.as_array().into_iter().flatten() {
+                            ts.push_value(if v.is_null() { f64::NAN } else {
+                                v.as_f64().ok_or_else(|| CommandError::InvalidParameters(
+                                    format!("Member {} of the forecast for '{}' contains a non-numeric value: {}", m + 1, name, v)
+                                ))?
+                            });
+                        }
+                        series.push(ts);
+                    }
+                    ForecastInput { series: name.clone(), members: series }
+                }
+                _ => return Err(CommandError::InvalidParameters(format!(
+                    "Forecast for '{}' must be CSV text or an array of members", name
+                ))),
+            };
+            inputs.push(input);
+        }
+
+        // A saved state is {"node": [values]}, as returned by an earlier forecast
+        let initial_state = match params.get("state") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::Object(map)) => Some(map.iter()
+                .map(|(node, values)| {
+                    let values = values.as_array()
+                        .and_then(|a| a.iter().map(|v| v.as_f64()).collect::<Option<Vec<f64>>>())
+                        .ok_or_else(|| CommandError::InvalidParameters(format!("state.{} must be an array of numbers", node)))?;
+                    Ok((node.clone(), values))
+                })
+                .collect::<Result<ModelState, CommandError>>()?),
+            Some(_) => return Err(CommandError::InvalidParameters(
+                "state must be an object mapping node names to arrays of values".to_string()
+            )),
+        };
+
+        let defaults = ForecastConfig::default();
+        let config = ForecastConfig {
+            forecast_start,
+            forecast_end: date_param(&params, "forecast_end")?,
+            inputs,
+            initial_state,
+            series: params.get("series")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+            quantiles: params.get("quantiles")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_f64()).collect())
+                .unwrap_or(defaults.quantiles),
+            n_threads: params.get("n_threads").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+        };
+
+        let interrupt_flag = Arc::clone(&session.interrupt_flag);
+        let progress_callback = Box::new(move |completed: usize, total: usize| {
+            progress_sender(ProgressInfo {
+                percent_complete: (completed as f64 / total as f64) * 100.0,
+                current_step: format!("Completed member {} of {}", completed, total),
+                estimated_remaining: None,
+                data: None,
+                current: Some(completed as i64),
+                total: Some(total as i64),
+                task_type: Some("forecast".to_string()),
+                timing: None,
+            });
+        });
+
+        let result = model.run_forecast(
+            &config,
+            move || interrupt_flag.load(Ordering::Relaxed),
+            Some(progress_callback),
+        ).map_err(|e| CommandError::ExecutionError(format!("Forecast failed: {}", e)))?
+            .ok_or(CommandError::Interrupted)?;
+
+        // Members and quantiles go to the model so they can be fetched with get_result
+        let model = session.get_model_by_id_mut(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+        result.write_to_data_cache(&mut model.data_cache);
+
+        let series: Vec<serde_json::Value> = result.series.iter().map(|s| serde_json::json!({
+            "series": s.name,
+            "members": (0..result.n_members).map(|m| member_series_name(&s.name, m)).collect::<Vec<_>>(),
+            "quantiles": s.quantiles.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        })).collect();
+        let state: serde_json::Map<String, serde_json::Value> = result.initial_state.iter()
+            .map(|(node, values)| (node.clone(), serde_json::json!(values)))
+            .collect();
+
+        Ok(serde_json::json!({
+            "n_members": result.n_members,
+            "forecast_start": tid::utils::u64_to_iso_datetime_string(result.start_timestamp),
+            "forecast_end": tid::utils::u64_to_iso_datetime_string(result.end_timestamp),
+            "timestep_seconds": result.step_size,
+            "series": series,
+            "state": state,
+        }))
+    }
+}
+
+pub struct GetOptimisableParamsCommand;
+
+impl Command for GetOptimisableParamsCommand {
+    fn name(&self) -> &str {
+        "get_optimisable_params"
+    }
+
+    fn description(&self) -> &str {
+        "Get list of all optimisable parameters from the loaded model"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![model_id_spec()]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::numerical::opt::OptimisableComponent;
+
+        // Get model and check if it exists
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        // Collect optimisable parameters from all components
+        let mut params = Vec::new();
+
+        // Add constants (already prefixed with "c.")
+        for constant_name in model.data_cache.list_params() {
+            params.push(constant_name);
+        }
+
+        // Add node parameters (prefixed with "node.")
+        for node_name in model.node_lookup.keys() {
+            for param in model.list_node_parameters(node_name).unwrap_or_default() {
+                params.push(format!("node.{}.{}", node_name, param));
+            }
+        }
+
+        Ok(serde_json::json!({
+            "parameters": params
+        }))
+    }
+}
+
+pub struct GetParameterCommand;
+
+impl Command for GetParameterCommand {
+    fn name(&self) -> &str {
+        "get_parameter"
+    }
+
+    fn description(&self) -> &str {
+        "Get a parameter of the loaded model, addressed as 'node.name.param' or 'c.constant_name'"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "target".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        let target = params["target"].as_str()
+            .ok_or_else(|| CommandError::InvalidParameters("Missing target parameter".to_string()))?;
+
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        let value = model.get_parameter(target)
+            .map_err(CommandError::InvalidParameters)?;
+
+        Ok(serde_json::json!({
+            "target": target,
+            "value": value,
+            "node": parameter_owner_state(model, target)
+        }))
+    }
+}
+
+pub struct SetParameterCommand;
+
+impl Command for SetParameterCommand {
+    fn name(&self) -> &str {
+        "set_parameter"
+    }
+
+    fn description(&self) -> &str {
+        "Set a parameter of the loaded model in place; the change applies from the next run"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "target".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            ParameterSpec {
+                name: "value".to_string(),
+                param_type: "number".to_string(),
+                required: true,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        let target = params["target"].as_str()
+            .ok_or_else(|| CommandError::InvalidParameters("Missing target parameter".to_string()))?;
+        let value = params["value"].as_f64()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| CommandError::InvalidParameters("value must be a finite number".to_string()))?;
+
+        let model = session.get_model_by_id_mut(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        let previous = model.get_parameter(target)
+            .map_err(CommandError::InvalidParameters)?;
+        model.set_parameter(target, value)
+            .map_err(CommandError::InvalidParameters)?;
+
+        // Report what the component actually holds, which may differ for derived parameters
+        let value = model.get_parameter(target)
+            .map_err(CommandError::ExecutionError)?;
+
+        Ok(serde_json::json!({
+            "target": target,
+            "previous": previous,
+            "value": value,
+            "node": parameter_owner_state(model, target)
+        }))
+    }
+}
+
+/// Current values of every parameter on the node a "node.name.param" target belongs to,
+/// or null for constants
+fn parameter_owner_state(model: &crate::model::Model, target: &str) -> serde_json::Value {
+    use crate::nodes::Node;
+
+    // Node names are matched without regard to case, as get_parameter does
+    let node = match target.strip_prefix("node.")
+        .and_then(|rest| rest.split('.').next())
+        .and_then(|name| model.get_node_idx(name)) {
+        Some(idx) => &model.nodes[idx],
+        None => return serde_json::Value::Null,
+    };
+    let node_name = node.get_name();
+    let names = model.list_node_parameters(node_name).unwrap_or_default();
+    let mut values: serde_json::Map<String, serde_json::Value> = names.into_iter()
+        .filter_map(|param| {
+            let value = model.get_parameter(&format!("node.{}.{}", node_name, param)).ok()?;
+            Some((param, serde_json::json!(value)))
+        })
+        .collect();
+
+    // Properties that are not optimisable (e.g. a catchment area) are listed when addressed
+    if let Some(param) = target.splitn(3, '.').nth(2) {
+        if let (false, Ok(value)) = (values.contains_key(param), model.get_parameter(target)) {
+            values.insert(param.to_string(), serde_json::json!(value));
+        }
+    }
+    serde_json::json!({
+        "name": node_name,
+        "type": node.get_type_as_string(),
+        "parameters": values
+    })
+}
+
+pub struct ValidateModelCommand;
+
+impl Command for ValidateModelCommand {
+    fn name(&self) -> &str {
+        "validate_model"
+    }
+
+    fn description(&self) -> &str {
+        "Check the loaded model for structural and data problems without running it"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![model_id_spec()]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::misc::model_validation::ValidationIssue;
+
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        let report = model.validate();
+        let to_json = |issue: &ValidationIssue| serde_json::json!({
+            "code": issue.code,
+            "message": issue.message,
+            "section": issue.section,
+            "line": issue.line_number
+        });
+
+        Ok(serde_json::json!({
+            "valid": report.is_valid(),
+            "errors": report.errors().into_iter().map(to_json).collect::<Vec<_>>(),
+            "warnings": report.warnings().into_iter().map(to_json).collect::<Vec<_>>()
+        }))
+    }
+}
+
+pub struct GetModelStructureCommand;
+
+impl Command for GetModelStructureCommand {
+    fn name(&self) -> &str {
+        "get_model_structure"
+    }
+
+    fn description(&self) -> &str {
+        "Get the node/link network of the loaded model, with its configured inputs and outputs"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![model_id_spec()]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::nodes::Node;
+
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        // Nodes in model order, which is the order they appear in the model file
+        let nodes: Vec<serde_json::Value> = model.nodes.iter()
+            .map(|node| {
+                let location = node.get_location();
+                let meta: serde_json::Map<String, serde_json::Value> = node.get_metadata().iter()
+                    .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+                    .collect();
+                serde_json::json!({
+                    "name": node.get_name(),
+                    "type": node.get_type_as_string(),
+                    "loc": [location.x(), location.y()],
+                    "meta": meta
+                })
+            })
+            .collect();
+
+        // Outlets and inlets are zero-based, so outlet 0 is the node's ds_1
+        let links: Vec<serde_json::Value> = model.links.iter()
+            .map(|link| serde_json::json!({
+                "from": model.nodes[link.from_node].get_name(),
+                "to": model.nodes[link.to_node].get_name(),
+                "from_outlet": link.from_outlet,
+                "to_inlet": link.to_inlet
+            }))
+            .collect();
+
+        Ok(serde_json::json!({
+            "nodes": nodes,
+            "links": links,
+            "inputs": model.input_file_paths,
+            "outputs": model.outputs
+        }))
+    }
+}
+
+pub struct ExportGraphCommand;
+
+impl Command for ExportGraphCommand {
+    fn name(&self) -> &str {
+        "export_graph"
+    }
+
+    fn description(&self) -> &str {
+        "Export the node/link network of the loaded model as Graphviz DOT or GeoJSON"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "format".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::Value::String("dot".to_string())),
+            },
+            ParameterSpec {
+                name: "path".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::misc::graph_export::GraphFormat;
+
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+        let format = GraphFormat::from_name(params.get("format").and_then(|v| v.as_str()).unwrap_or("dot"))
+            .map_err(CommandError::InvalidParameters)?;
+        let graph = model.export_graph(format);
+
+        // Written to file if a path is given, otherwise returned in the result
+        match params.get("path").and_then(|v| v.as_str()) {
+            Some(path) => {
+                std::fs::write(path, &graph).map_err(|e| CommandError::IoError(e.to_string()))?;
+                Ok(serde_json::json!({ "format": format.as_str(), "path": path }))
+            }
+            None => Ok(serde_json::json!({ "format": format.as_str(), "graph": graph })),
         }
+    }
+}
 
-        // Add node parameters (prefixed with "node.")
-        for (node_name, &node_idx) in &model.node_lookup {
-            match &model.nodes[node_idx] {
-                NodeEnum::Gr4jNode(node) => {
-                    for param in node.list_params() {
-                        params.push(format!("node.{}.{}", node_name, param));
-                    }
-                }
-                NodeEnum::SacramentoNode(node) => {
-                    for param in node.list_params() {
-                        params.push(format!("node.{}.{}", node_name, param));
-                    }
-                }
-                _ => {
-                    // Skip non-optimisable nodes
-                }
+pub struct GenerateReportCommand;
+
+impl Command for GenerateReportCommand {
+    fn name(&self) -> &str {
+        "generate_report"
+    }
+
+    fn description(&self) -> &str {
+        "Run the loaded model and produce a standalone HTML report of the run"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "title".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::Value::String("Kalix run report".to_string())),
+            },
+            ParameterSpec {
+                name: "path".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        let title = params.get("title").and_then(|v| v.as_str()).unwrap_or("Kalix run report");
+        let model = session.get_model_by_id_mut(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        // Gauge results are only kept if recorded, so the model is run again to record them
+        model.record_gauge_series();
+        model.configure()
+            .and_then(|_| model.run())
+            .map_err(|e| CommandError::ExecutionError(format!("Simulation failed: {}", e)))?;
+        let report = model.html_report(title, None);
+        let gauges: Vec<serde_json::Value> = model.gauge_statistics().iter()
+            .map(|s| serde_json::json!({
+                "gauge": s.gauge,
+                "n_compared": s.n_compared,
+                "nse": s.nse,
+                "kge": s.kge,
+                "pbias": s.pbias,
+            }))
+            .collect();
+
+        // Written to file if a path is given, otherwise returned in the result
+        match params.get("path").and_then(|v| v.as_str()) {
+            Some(path) => {
+                std::fs::write(path, &report).map_err(|e| CommandError::IoError(e.to_string()))?;
+                Ok(serde_json::json!({ "gauges": gauges, "path": path }))
             }
+            None => Ok(serde_json::json!({ "gauges": gauges, "html": report })),
+        }
+    }
+}
+
+pub struct GetProvenanceCommand;
+
+impl Command for GetProvenanceCommand {
+    fn name(&self) -> &str {
+        "get_provenance"
+    }
+
+    fn description(&self) -> &str {
+        "Provenance and fingerprint of a run of the loaded model: version, model and input file hashes"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![model_id_spec()]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::misc::provenance::Provenance;
+
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+        let provenance = Provenance::new(model).map_err(CommandError::ExecutionError)?;
+        Ok(provenance.to_json())
+    }
+}
+
+pub struct CompareResultsCommand;
+
+impl Command for CompareResultsCommand {
+    fn name(&self) -> &str {
+        "compare_results"
+    }
+
+    fn description(&self) -> &str {
+        "Difference statistics between two result series, possibly from two loaded models"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        let optional = |name: &str, param_type: &str, default: Option<serde_json::Value>| ParameterSpec {
+            name: name.to_string(),
+            param_type: param_type.to_string(),
+            required: false,
+            default,
+        };
+        vec![
+            ParameterSpec {
+                name: "series_a".to_string(),
+                param_type: "string".to_string(),
+                required: true,
+                default: None,
+            },
+            optional("series_b", "string", None),
+            optional("model_a", "string", Some(serde_json::json!(DEFAULT_MODEL_ID))),
+            optional("model_b", "string", Some(serde_json::json!(DEFAULT_MODEL_ID))),
+            optional("tolerance", "number", Some(serde_json::json!(0.0))),
+            optional("max_points", "integer", None),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        use crate::numerical::series_comparison::{compare_series, downsample_mean};
+
+        let series_a = params.get("series_a")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CommandError::InvalidParameters("series_a is required".to_string()))?;
+        // Comparing one output across two models is the common case, so series_b defaults to series_a
+        let series_b = params.get("series_b").and_then(|v| v.as_str()).unwrap_or(series_a);
+        let model_a = params.get("model_a").and_then(|v| v.as_str()).unwrap_or(DEFAULT_MODEL_ID);
+        let model_b = params.get("model_b").and_then(|v| v.as_str()).unwrap_or(DEFAULT_MODEL_ID);
+        let tolerance = params.get("tolerance").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let max_points = match params.get("max_points") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(v) => Some(v.as_u64().filter(|&n| n > 0).ok_or_else(|| {
+                CommandError::InvalidParameters("max_points must be a positive integer".to_string())
+            })? as usize),
+        };
+
+        let find_series = |model_id: &str, series_name: &str| {
+            let model = session.get_model_by_id(model_id)
+                .ok_or_else(|| CommandError::ExecutionError(format!("Model '{}' is not loaded", model_id)))?;
+            model.data_cache.get_existing_series_idx(series_name)
+                .map(|idx| model.data_cache.get_series(idx))
+                .ok_or_else(|| CommandError::ResultNotFound(
+                    format!("Timeseries '{}' not found in model '{}'", series_name, model_id)
+                ))
+        };
+        let a = find_series(model_a, series_a)?;
+        let b = find_series(model_b, series_b)?;
+
+        let comparison = compare_series(&a, &b, tolerance)
+            .map_err(CommandError::ExecutionError)?;
+
+        let mut result = serde_json::json!({
+            "a": {"model_id": model_a, "series_name": series_a},
+            "b": {"model_id": model_b, "series_name": series_b},
+            "n_compared": comparison.n_compared,
+            "bias": comparison.bias,
+            "rmse": comparison.rmse,
+            "nse": comparison.nse,
+            "max_abs_diff": comparison.max_abs_diff,
+            "first_divergence": comparison.first_divergence.map(tid::utils::u64_to_iso_datetime_string),
+        });
+        if let Some(max_points) = max_points {
+            let difference = downsample_mean(&comparison.difference, max_points);
+            result["difference"] = serde_json::json!({
+                "start": tid::utils::u64_to_iso_datetime_string(difference.start_timestamp),
+                "step_seconds": difference.step_size,
+                "values": difference.values,
+            });
         }
+        Ok(result)
+    }
+}
+
+pub struct GetLogCommand;
+
+impl Command for GetLogCommand {
+    fn name(&self) -> &str {
+        "get_log"
+    }
+
+    fn description(&self) -> &str {
+        "Get the diagnostics raised during the last simulation, e.g. storages spilling or demands going unmet"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "level".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::json!("info")),
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        let min_level = match params.get("level").and_then(|v| v.as_str()).unwrap_or("info") {
+            "info" => LogLevel::Info,
+            "warning" => LogLevel::Warning,
+            "error" => LogLevel::Error,
+            other => return Err(CommandError::InvalidParameters(
+                format!("Unknown level '{}'; expected 'info', 'warning' or 'error'", other))),
+        };
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+
+        let timestamp = |t: Option<u64>| t.map(tid::utils::u64_to_iso_datetime_string);
+        let entries: Vec<serde_json::Value> = model.run_log().entries().iter()
+            .filter(|e| e.level >= min_level)
+            .map(|e| serde_json::json!({
+                "level": e.level.as_str(),
+                "code": e.code,
+                "source": e.source,
+                "message": e.message,
+                "count": e.count,
+                "first": timestamp(e.first_timestamp),
+                "last": timestamp(e.last_timestamp),
+            }))
+            .collect();
+        Ok(serde_json::json!({ "entries": entries }))
+    }
+}
+
+pub struct GetEventsCommand;
+
+impl Command for GetEventsCommand {
+    fn name(&self) -> &str {
+        "get_events"
+    }
+
+    fn description(&self) -> &str {
+        "Query or export the events recorded by the last simulation run with record_events"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        let optional = |name: &str, param_type: &str, default: Option<serde_json::Value>| ParameterSpec {
+            name: name.to_string(),
+            param_type: param_type.to_string(),
+            required: false,
+            default,
+        };
+        vec![
+            optional("kind", "string", None),
+            optional("node", "string", None),
+            optional("start", "string", None),
+            optional("end", "string", None),
+            optional("max_events", "integer", Some(serde_json::json!(DEFAULT_MAX_EVENTS))),
+            optional("path", "string", None),
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        let kind = match params.get("kind").and_then(|v| v.as_str()) {
+            Some(name) => Some(EventKind::from_name(name).ok_or_else(|| CommandError::InvalidParameters(format!(
+                "Unknown event kind '{}'; expected one of: {}",
+                name, EventKind::ALL.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")
+            )))?),
+            None => None,
+        };
+        let node = params.get("node").and_then(|v| v.as_str());
+        let start = date_param(&params, "start")?;
+        let end = date_param(&params, "end")?;
+        let max_events = params.get("max_events").and_then(|v| v.as_u64())
+            .map_or(DEFAULT_MAX_EVENTS, |n| n as usize);
+
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+        let table = model.events();
+        let events = table.filter(kind, node, start, end);
+
+        // Optionally export every matching event
+        let path = match params.get("path").and_then(|v| v.as_str()) {
+            Some(path) => {
+                std::fs::write(path, table.to_csv_string(&events))
+                    .map_err(|e| CommandError::IoError(format!("Failed to write events file: {}", e)))?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        let iso = tid::utils::u64_to_iso_datetime_string;
+        let summary: Vec<serde_json::Value> = table.summarise(&events).into_iter()
+            .map(|s| serde_json::json!({
+                "kind": s.kind.as_str(),
+                "node": s.source,
+                "count": s.count,
+                "total": s.total_magnitude,
+                "max": s.max_magnitude,
+                "first": iso(s.first_timestamp),
+                "last": iso(s.last_timestamp),
+            }))
+            .collect();
+        let listed: Vec<serde_json::Value> = events.iter().take(max_events)
+            .map(|e| serde_json::json!({
+                "time": iso(e.timestamp),
+                "step": e.step,
+                "kind": e.kind.as_str(),
+                "node": table.source_name(e),
+                "magnitude": e.magnitude,
+            }))
+            .collect();
 
         Ok(serde_json::json!({
-            "parameters": params
+            "n_events": events.len(),
+            "summary": summary,
+            "events": listed,
+            "truncated": events.len() > max_events,
+            "path": path,
         }))
     }
 }
 
+pub struct GetProfileCommand;
+
+impl Command for GetProfileCommand {
+    fn name(&self) -> &str {
+        "get_profile"
+    }
+
+    fn description(&self) -> &str {
+        "Get the time each node took in the last run with node_timing, slowest first, as a table or flame graph JSON"
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![
+            ParameterSpec {
+                name: "format".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::json!("json")),
+            },
+            ParameterSpec {
+                name: "max_nodes".to_string(),
+                param_type: "integer".to_string(),
+                required: false,
+                default: None,
+            },
+            model_id_spec(),
+        ]
+    }
+
+    fn interruptible(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        session: &mut Session,
+        params: serde_json::Value,
+        _progress_sender: Box<dyn Fn(ProgressInfo) + Send + Sync>,
+    ) -> Result<serde_json::Value, CommandError> {
+        let format = params.get("format").and_then(|v| v.as_str()).unwrap_or("json");
+        if format != "json" && format != "table" {
+            return Err(CommandError::InvalidParameters(
+                format!("Unknown format '{}'; expected 'json' or 'table'", format)));
+        }
+        let max_nodes = params.get("max_nodes").and_then(|v| v.as_u64()).map(|n| n as usize);
+        let model = session.get_model_by_id(model_id(&params))
+            .ok_or(CommandError::ModelNotLoaded)?;
+        let profile = model.last_profile.as_ref().ok_or_else(|| CommandError::ExecutionError(
+            "The last run was not profiled. Run the simulation with node_timing to profile it".to_string()))?;
+
+        let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+        let mut result = serde_json::json!({
+            "run_ms": ms(profile.run_time),
+            "node_ms": ms(profile.node_time()),
+        });
+        if format == "table" {
+            result["table"] = serde_json::json!(profile.to_table(max_nodes));
+        } else {
+            result["nodes"] = profile.nodes.iter().take(max_nodes.unwrap_or(usize::MAX)).enumerate()
+                .map(|(rank, node)| serde_json::json!({
+                    "rank": rank + 1,
+                    "node": node.name,
+                    "type": node.node_type,
+                    "ms": ms(node.time),
+                    "us_per_step": node.micros_per_step(),
+                    "share": profile.share(node),
+                }))
+                .collect();
+            result["flame_graph"] = profile.to_flame_graph();
+        }
+        Ok(result)
+    }
+}
+
 pub struct SaveResultsCommand;
 
 impl Command for SaveResultsCommand {
@@ -965,11 +3115,36 @@ impl Command for SaveResultsCommand {
                 default: None,
             },
             ParameterSpec {
-                name: "format".to_string(),
-                param_type: "string".to_string(),
+                name: "format".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::Value::String("csv".to_string())),
+            },
+            ParameterSpec {
+                name: "date_header".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::json!("Time")),
+            },
+            ParameterSpec {
+                name: "units".to_string(),
+                param_type: "object".to_string(),
+                required: false,
+                default: None,
+            },
+            ParameterSpec {
+                name: "missing_value".to_string(),
+                param_type: "string".to_string(),
+                required: false,
+                default: Some(serde_json::json!("NaN")),
+            },
+            ParameterSpec {
+                name: "split_by_year".to_string(),
+                param_type: "boolean".to_string(),
                 required: false,
-                default: Some(serde_json::Value::String("csv".to_string())),
+                default: Some(serde_json::json!(false)),
             },
+            model_id_spec(),
         ]
     }
 
@@ -990,13 +3165,13 @@ impl Command for SaveResultsCommand {
             .and_then(|v| v.as_str())
             .unwrap_or("csv");
 
-        if format != "csv" && format != "pixie" {
+        if !["csv", "tsv", "ssv", "source", "pixie"].contains(&format) {
             return Err(CommandError::InvalidParameters(
-                format!("Unsupported format '{}'; expected 'csv' or 'pixie'", format)));
+                format!("Unsupported format '{}'; expected 'csv', 'tsv', 'ssv', 'source' or 'pixie'", format)));
         }
 
         // Get model and check if it exists
-        let model = session.get_model()
+        let model = session.get_model_by_id(model_id(&params))
             .ok_or(CommandError::ModelNotLoaded)?;
 
         // Check if we have any outputs to save
@@ -1010,14 +3185,20 @@ impl Command for SaveResultsCommand {
         } else {
             // Generate default filename based on current timestamp
             let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-            let ext = if format == "pixie" { "pxt" } else { "csv" };
+            let ext = match format {
+                "pixie" => "pxt",
+                "source" => "res.csv",
+                "tsv" | "ssv" => format,
+                _ => "csv",
+            };
             format!("simulation_results_{}.{}", timestamp, ext)
         };
 
         // Collect the output series that are valid to export. Outputs that no component
         // populated (e.g. an invalid recorder) have a mismatched length and are omitted
         // rather than failing the whole export — see Model::collect_output_series.
-        let timeseries_refs = model.collect_output_series();
+        let output_series = model.collect_output_series();
+        let timeseries_refs: Vec<&Timeseries> = output_series.iter().map(|ts| ts.as_ref()).collect();
 
         if timeseries_refs.is_empty() {
             return Err(CommandError::ExecutionError("No timeseries data found for output series".to_string()));
@@ -1028,11 +3209,18 @@ impl Command for SaveResultsCommand {
 
         // Write the file in the requested format. The path reported back is the file
         // actually written — for pixie that is the .pxt metadata file (a .pxb sibling is
-        // written alongside it).
+        // written alongside it), and for a CSV split by year the first year's file.
+        let mut written_paths = vec![];
         let written_path = match format {
-            "csv" => {
-                csv_io::write_ts(&file_path, timeseries_refs)
+            "csv" | "tsv" | "ssv" => {
+                let options = csv_write_options(format, &params, &timeseries_refs)?;
+                written_paths = csv_io::write_ts_wide(&file_path, &timeseries_refs, &options)
                     .map_err(|e| CommandError::IoError(format!("Failed to write CSV file: {}", String::from(e))))?;
+                written_paths.first().cloned().unwrap_or(file_path.clone())
+            }
+            "source" => {
+                let units = csv_write_options(format, &params, &timeseries_refs)?.units;
+                model.write_outputs_res_csv(&file_path, units).map_err(CommandError::IoError)?;
                 file_path.clone()
             }
             "pixie" => {
@@ -1047,28 +3235,63 @@ impl Command for SaveResultsCommand {
                     .map_err(|e| CommandError::IoError(format!("Failed to write Pixie file: {}", String::from(e))))?;
                 format!("{}.pxt", base_path)
             }
-            _ => unreachable!("format already validated to be csv or pixie"),
+            _ => unreachable!("format already validated"),
         };
 
+        // Write the run's diagnostics next to the outputs, if it raised any
+        let log_path = model.write_run_log_alongside(&written_path)
+            .map_err(CommandError::IoError)?;
+
         // Get the absolute path for the response
-        let absolute_path = Path::new(&written_path)
+        let absolute = |path: &str| Path::new(path)
             .canonicalize()
             .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or(written_path.clone());
+            .unwrap_or(path.to_string());
 
         // Build response
-        Ok(serde_json::json!({
-            "path": absolute_path,
+        let mut response = serde_json::json!({
+            "path": absolute(&written_path),
+            "log_path": log_path.as_deref().map(absolute),
             "format": format,
             "n_series": series_count,
             "len": total_timesteps
-        }))
+        });
+        if written_paths.len() > 1 {
+            response["paths"] = written_paths.iter().map(|path| absolute(path)).collect();
+        }
+        Ok(response)
+    }
+}
+
+/// Delimited-file layout options of save_results. `units` maps series names to their units;
+/// series without an entry get a blank unit.
+fn csv_write_options(format: &str, params: &serde_json::Value, series: &[&Timeseries]) -> Result<csv_io::CsvWriteOptions, CommandError> {
+    let mut options = match format {
+        "tsv" => csv_io::CsvWriteOptions::tsv(),
+        "ssv" => csv_io::CsvWriteOptions::ssv(),
+        _ => csv_io::CsvWriteOptions::default(),
+    };
+    if let Some(date_header) = params.get("date_header").and_then(|v| v.as_str()) {
+        options.date_header = date_header.to_string();
+    }
+    if let Some(missing_value) = params.get("missing_value").and_then(|v| v.as_str()) {
+        options.missing_value = missing_value.to_string();
+    }
+    options.split_by_year = params.get("split_by_year").and_then(|v| v.as_bool()).unwrap_or(false);
+    if let Some(units) = params.get("units").filter(|v| !v.is_null()) {
+        let units = units.as_object()
+            .ok_or_else(|| CommandError::InvalidParameters("'units' must map series names to units".to_string()))?;
+        options.units = Some(series.iter()
+            .map(|ts| units.get(&ts.name).and_then(|u| u.as_str()).unwrap_or("").to_string())
+            .collect());
     }
+    Ok(options)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::test_helpers::TestDir;
 
     #[test]
     fn test_command_registry() {
@@ -1082,12 +3305,796 @@ mod tests {
         assert!(commands.contains(&"load_model_string"));
         assert!(commands.contains(&"run_simulation"));
         assert!(commands.contains(&"run_optimisation"));
+        assert!(commands.contains(&"get_optimisation_result"));
+        assert!(commands.contains(&"apply_calibration"));
+        assert!(commands.contains(&"run_batch"));
+        assert!(commands.contains(&"run_forecast"));
+        assert!(commands.contains(&"run_sensitivity"));
+        assert!(commands.contains(&"run_glue"));
         assert!(commands.contains(&"get_optimisable_params"));
+        assert!(commands.contains(&"get_parameter"));
+        assert!(commands.contains(&"set_parameter"));
+        assert!(commands.contains(&"validate_model"));
+        assert!(commands.contains(&"get_model_structure"));
+        assert!(commands.contains(&"export_graph"));
+        assert!(commands.contains(&"generate_report"));
+        assert!(commands.contains(&"get_provenance"));
         assert!(commands.contains(&"get_result"));
+        assert!(commands.contains(&"compare_results"));
+        assert!(commands.contains(&"get_log"));
+        assert!(commands.contains(&"get_events"));
         assert!(commands.contains(&"save_results"));
         assert!(commands.contains(&"echo"));
     }
 
+    #[test]
+    fn test_get_result_f64_frame() {
+        use base64::{Engine, engine::general_purpose::STANDARD};
+
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/1/first_model.ini").unwrap();
+        let mut model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("./src/tests/example_models/1"))).unwrap();
+        model.configure().unwrap();
+        model.run().unwrap();
+        let idx = model.data_cache.get_existing_series_idx("node.my_gr4j_node.dsflow").unwrap();
+        let expected = model.data_cache.series[idx].clone();
+        session.set_model(model);
+
+        let result = GetResultCommand.execute(
+            &mut session,
+            serde_json::json!({"series_name": "node.my_gr4j_node.dsflow", "format": "f64"}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(result["codec"], "f64_le");
+
+        let frame = STANDARD.decode(result["data"].as_str().unwrap()).unwrap();
+        let word = |i: usize| u64::from_le_bytes(frame[8 * i..8 * i + 8].try_into().unwrap());
+        assert_eq!(word(0), expected.start_timestamp);
+        assert_eq!(word(1), expected.step_size);
+        assert_eq!(word(2) as usize, expected.values.len());
+        assert_eq!(frame.len(), 24 + 8 * expected.values.len());
+        let values: Vec<f64> = (0..expected.values.len()).map(|i| f64::from_bits(word(3 + i))).collect();
+        assert_eq!(values, expected.values);
+    }
+
+    #[test]
+    fn test_get_model_structure() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap()
+            .replace("loc = 0, 50", "loc = 0, 50\nmeta_region = Upper Murray\nMETA_Gauge_ID = 401012");
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(model);
+
+        let result = GetModelStructureCommand.execute(
+            &mut session,
+            serde_json::json!({}),
+            Box::new(|_| {}),
+        ).unwrap();
+
+        assert_eq!(result["nodes"], serde_json::json!([
+            {"name": "node1", "type": "inflow", "loc": [0.0, 0.0], "meta": {}},
+            {"name": "node2", "type": "inflow", "loc": [0.0, 50.0], "meta": {"region": "Upper Murray", "gauge_id": "401012"}}
+        ]));
+        assert_eq!(result["links"], serde_json::json!([
+            {"from": "node1", "to": "node2", "from_outlet": 0, "to_inlet": 0}
+        ]));
+        assert_eq!(result["inputs"], serde_json::json!(["./src/tests/example_models/5/data.csv"]));
+        assert_eq!(result["outputs"], serde_json::json!(["node.node1.ds_1", "node.node2.ds_1"]));
+    }
+
+    #[test]
+    fn test_export_graph() {
+        let mut session = Session::new();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap(),
+            Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(model);
+
+        let result = ExportGraphCommand.execute(
+            &mut session,
+            serde_json::json!({}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(result["format"], "dot");
+        assert!(result["graph"].as_str().unwrap().contains("\"node1\" -> \"node2\";"));
+
+        let result = ExportGraphCommand.execute(
+            &mut session,
+            serde_json::json!({"format": "geojson"}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert!(result["graph"].as_str().unwrap().contains("FeatureCollection"));
+
+        assert!(ExportGraphCommand.execute(
+            &mut session,
+            serde_json::json!({"format": "svg"}),
+            Box::new(|_| {}),
+        ).is_err());
+    }
+
+    #[test]
+    fn test_generate_report() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/8/picnic_sacr_working.ini").unwrap();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("./src/tests/example_models/8"))).unwrap();
+        session.set_model(model);
+
+        let result = GenerateReportCommand.execute(
+            &mut session,
+            serde_json::json!({"title": "Picnic"}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(result["gauges"][0]["gauge"], "temp_gauge");
+        assert!(result["gauges"][0]["nse"].as_f64().unwrap() > 0.0);
+        let html = result["html"].as_str().unwrap();
+        assert!(html.contains("<h1>Picnic</h1>"));
+        assert!(html.contains("node.my_sac.lzfpm"));
+    }
+
+    #[test]
+    fn test_get_provenance() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/8/picnic_sacr_working.ini").unwrap();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("./src/tests/example_models/8"))).unwrap();
+        session.set_model(model);
+
+        let result = GetProvenanceCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(result["fingerprint"].as_str().unwrap().len(), 64);
+        assert_eq!(result["input_files"].as_array().unwrap().len(), 3);
+        assert_eq!(result["input_files"][0]["path"], "formatted_mpot.csv");
+        assert!(result["model_file"].is_null());
+    }
+
+    #[test]
+    fn test_set_and_get_parameter() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/1/first_model.ini").unwrap();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("./src/tests/example_models/1"))).unwrap();
+        session.set_model(model);
+
+        let before = GetParameterCommand.execute(
+            &mut session,
+            serde_json::json!({"target": "node.my_gr4j_node.x1"}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(before["node"]["type"], "gr4j");
+
+        let result = SetParameterCommand.execute(
+            &mut session,
+            serde_json::json!({"target": "node.my_gr4j_node.x1", "value": 123.0}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(result["previous"], before["value"]);
+        assert_eq!(result["value"], 123.0);
+        assert_eq!(result["node"]["parameters"]["x1"], 123.0);
+        assert_eq!(session.get_model().unwrap().get_parameter("node.my_gr4j_node.x1").unwrap(), 123.0);
+
+        // Node names are not case sensitive, and the node state names the node as it is in the model
+        let result = SetParameterCommand.execute(
+            &mut session,
+            serde_json::json!({"target": "node.My_GR4J_Node.x1", "value": 124.0}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!((result["node"]["name"].clone(), result["node"]["type"].clone()),
+                   (serde_json::json!("my_gr4j_node"), serde_json::json!("gr4j")));
+        assert_eq!(result["node"]["parameters"]["x1"], 124.0);
+
+        // Unknown parameters and non-numeric values are rejected without touching the model
+        assert!(SetParameterCommand.execute(
+            &mut session,
+            serde_json::json!({"target": "node.my_gr4j_node.bogus", "value": 1.0}),
+            Box::new(|_| {}),
+        ).is_err());
+        assert!(SetParameterCommand.execute(
+            &mut session,
+            serde_json::json!({"target": "node.my_gr4j_node.x1", "value": "big"}),
+            Box::new(|_| {}),
+        ).is_err());
+        assert_eq!(session.get_model().unwrap().get_parameter("node.my_gr4j_node.x1").unwrap(), 124.0);
+    }
+
+    #[test]
+    fn test_run_batch_on_a_named_model() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        let read = |ini: &str| IniModelIO::new().read_model_string_with_working_directory(
+            ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(read(&ini));
+        session.set_model_by_id("b", read(&ini.replace("node2", "nodeb")));
+
+        let result = RunBatchCommand.execute(
+            &mut session,
+            serde_json::json!({
+                "parameter_sets": [{"c.a": 5.5, "c.b": 6.5}],
+                "series": ["node.nodeb.ds_1"],
+                "model_id": "b",
+            }),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert!(result["runs"][0]["error"].is_null(), "{}", result);
+        assert!(result["runs"][0]["series"]["node.nodeb.ds_1"]["count"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_set_catchment_area_and_storage_dimensions() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/6/model_with_every_node_type.ini").unwrap();
+        session.set_model(IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("./src/tests/example_models/6"))).unwrap());
+        let mut set = |target: &str, value: f64| SetParameterCommand.execute(
+            &mut session,
+            serde_json::json!({"target": target, "value": value}),
+            Box::new(|_| {}),
+        );
+
+        // Catchment areas
+        let result = set("node.node6_gr4j.area", 120.0).unwrap();
+        assert_eq!((result["previous"].clone(), result["value"].clone()), (serde_json::json!(80.0), serde_json::json!(120.0)));
+        assert_eq!(result["node"]["parameters"]["area"], 120.0);
+        assert_eq!(set("node.node2_sacramento.area", 50.0).unwrap()["value"], 50.0);
+        assert!(set("node.node2_sacramento.area", -1.0).is_err());
+
+        // A row of a storage's dimensions table, which must stay usable by the solver
+        let result = set("node.node4_storage.dimensions.2.volume", 95.0).unwrap();
+        assert_eq!((result["previous"].clone(), result["value"].clone()), (serde_json::json!(100.0), serde_json::json!(95.0)));
+        assert_eq!(result["node"]["type"], "storage");
+        assert_eq!(result["node"]["parameters"]["dimensions.2.volume"], 95.0);
+        assert!(set("node.node4_storage.dimensions.3.volume", 50.0).is_err());
+        assert!(set("node.node4_storage.dimensions.5.volume", 200.0).is_err());
+        assert!(set("node.node4_storage.dimensions.2.depth", 1.0).is_err());
+        assert!(set("node.node4_storage.dimensions", 1.0).is_err());
+        assert!(set("node.node1_inflow.area", 1.0).is_err());
+
+        let model = session.get_model_mut().unwrap();
+        assert_eq!(model.get_parameter("node.node4_storage.dimensions.3.volume").unwrap(), 101.0);
+        assert_eq!(model.get_parameter("node.node4_storage.dimensions.2.volume").unwrap(), 95.0);
+        model.configure().unwrap();
+        model.run().unwrap();
+    }
+
+    #[test]
+    fn test_run_simulation_with_period_and_input_overrides() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(model);
+        let node1_flow = |session: &Session| {
+            let model = session.get_model().unwrap();
+            let idx = model.data_cache.get_existing_series_idx("node.node1.ds_1").unwrap();
+            model.data_cache.series[idx].values.clone()
+        };
+
+        // Array override aligned to the requested start
+        let result = RunSimulationCommand.execute(
+            &mut session,
+            serde_json::json!({
+                "start": "2000-01-01", "end": "2000-01-03",
+                "inputs": {"data.data_csv.by_name.rain": [10.0, 20.0, 30.0]}
+            }),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(result["timesteps_processed"], 3);
+        assert_eq!(node1_flow(&session), vec![10.0, 20.0, 30.0]);
+
+        // CSV override on the same sub-period
+        RunSimulationCommand.execute(
+            &mut session,
+            serde_json::json!({
+                "start": "2000-01-02", "end": "2000-01-03",
+                "inputs": {"data.data_csv.by_index.1": "Date,Rain\n2000-01-01,1\n2000-01-02,2\n2000-01-03,3\n"}
+            }),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(node1_flow(&session), vec![2.0, 3.0]);
+
+        // Overrides do not outlive their run: a plain run sees the file data over the full period
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(result["timesteps_processed"], 32064);
+        assert_eq!(node1_flow(&session)[0], 3.8);
+
+        // Unknown inputs and mismatched timesteps are rejected
+        assert!(RunSimulationCommand.execute(
+            &mut session,
+            serde_json::json!({"inputs": {"data.data_csv.by_name.snow": [1.0]}}),
+            Box::new(|_| {}),
+        ).is_err());
+        assert!(RunSimulationCommand.execute(
+            &mut session,
+            serde_json::json!({"inputs": {"data.data_csv.by_name.rain": "Date,Rain\n2000-01-01,1\n2000-01-03,3\n"}}),
+            Box::new(|_| {}),
+        ).is_err());
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(result["timesteps_processed"], 32064);
+    }
+
+    #[test]
+    fn test_commands_address_models_by_id() {
+        let mut session = Session::new();
+        let baseline = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        let scenario = baseline.replace("c.a = 2.0", "c.a = 4.0");
+        for (id, ini) in [("baseline", &baseline), ("scenario", &scenario)] {
+            let result = LoadModelStringCommand.execute(
+                &mut session,
+                serde_json::json!({"model_ini": ini, "model_id": id}),
+                Box::new(|_| {}),
+            ).unwrap();
+            assert_eq!(result["model_id"], id);
+            RunSimulationCommand.execute(&mut session, serde_json::json!({"model_id": id}), Box::new(|_| {})).unwrap();
+        }
+        assert!(session.get_model().is_none());
+        assert_eq!(session.model_ids(), vec!["baseline", "scenario"]);
+
+        let first_value = |session: &mut Session, id: &str| {
+            let result = GetResultCommand.execute(
+                session,
+                serde_json::json!({"series_name": "node.node2.ds_1", "format": "csv", "model_id": id}),
+                Box::new(|_| {}),
+            ).unwrap();
+            let data = result["data"].as_str().unwrap().to_string();
+            data.split(',').nth(2).unwrap().parse::<f64>().unwrap()
+        };
+        // node2 adds (c.a - 5.5)^2 + (c.b - 6.5)^2 to the inflow from node1
+        let difference = first_value(&mut session, "scenario") - first_value(&mut session, "baseline");
+        assert!((difference - (1.5f64.powi(2) - 3.5f64.powi(2))).abs() < 1e-9);
+
+        let comparison = CompareResultsCommand.execute(
+            &mut session,
+            serde_json::json!({"series_a": "node.node2.ds_1", "model_a": "baseline", "model_b": "scenario", "max_points": 100}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert!((comparison["bias"].as_f64().unwrap() - difference).abs() < 1e-9);
+        assert!((comparison["max_abs_diff"].as_f64().unwrap() - difference.abs()).abs() < 1e-9);
+        assert_eq!(comparison["first_divergence"], "1925-11-15T00:00:00.000Z");
+        assert!(comparison["difference"]["values"].as_array().unwrap().len() <= 100);
+
+        // Without a model_id, commands use the default model, which is not loaded here
+        assert!(matches!(
+            GetModelStructureCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})),
+            Err(CommandError::ModelNotLoaded)
+        ));
+    }
+
+    #[test]
+    fn test_run_simulation_node_timing() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(model);
+
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&updates);
+        let result = RunSimulationCommand.execute(
+            &mut session,
+            serde_json::json!({"node_timing": true}),
+            Box::new(move |p| sink.lock().unwrap().push(p)),
+        ).unwrap();
+
+        let timing = &result["node_timing"];
+        assert_eq!(timing["by_type"].as_array().unwrap().len(), 1);
+        assert_eq!(timing["by_type"][0]["type"], "inflow");
+        assert_eq!(timing["slowest"].as_array().unwrap().len(), 2);
+        assert!(timing["total_ms"].as_f64().unwrap() > 0.0);
+        // The final progress update carries the breakdown too
+        assert!(updates.lock().unwrap().last().unwrap().timing.is_some());
+
+        // The run's profile ranks the nodes, as JSON or a table
+        let profile = GetProfileCommand.execute(&mut session, serde_json::json!({"max_nodes": 1}), Box::new(|_| {})).unwrap();
+        assert_eq!(profile["nodes"].as_array().unwrap().len(), 1);
+        assert_eq!((profile["nodes"][0]["rank"].as_u64(), profile["nodes"][0]["type"].as_str()), (Some(1), Some("inflow")));
+        assert!(profile["run_ms"].as_f64().unwrap() >= profile["node_ms"].as_f64().unwrap());
+        let flame_graph = &profile["flame_graph"];
+        let children: Vec<&str> = flame_graph["children"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+        assert_eq!((flame_graph["name"].as_str(), children), (Some("run"), vec!["inflow", "other"]));
+        assert_eq!(flame_graph["children"][0]["children"].as_array().unwrap().len(), 2);
+        let profile = GetProfileCommand.execute(&mut session, serde_json::json!({"format": "table"}), Box::new(|_| {})).unwrap();
+        let table = profile["table"].as_str().unwrap();
+        assert!(table.starts_with("Rank  Node ") && table.contains("Nodes took"), "{}", table);
+        assert_eq!(table.lines().count(), 4, "{}", table);
+
+        // The timer is detached after the run, and runs without it report nothing
+        assert!(session.get_model().unwrap().node_timer.is_none());
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert!(result["node_timing"].is_null());
+        let err = GetProfileCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).err().unwrap();
+        assert!(err.to_string().contains("The last run was not profiled"), "{}", err);
+    }
+
+    #[test]
+    fn test_interrupted_optimisation_returns_best_so_far() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(model);
+
+        // node1's flow is the Rain column, so node2's RMSE against it is (a - 5.5)^2 + (b - 6.5)^2
+        let config = r#"
+[optimisation]
+objective_expression = term1
+algorithm = DE
+population_size = 8
+termination_evaluations = 100000
+random_seed = 1
+
+[term.term1]
+simulated = node.node2.ds_1
+observed_file = ./src/tests/example_models/5/data.csv
+observed_series = 1
+statistic = RMSE
+
+[parameters]
+c.a = lin_range(g(1), 0.0, 10.0)
+c.b = lin_range(g(2), 0.0, 10.0)
+"#;
+        // Request a stop as soon as the first progress update arrives
+        let interrupt_flag = Arc::clone(&session.interrupt_flag);
+        let result = RunOptimisationCommand.execute(
+            &mut session,
+            serde_json::json!({"config": config}),
+            Box::new(move |_| interrupt_flag.store(true, std::sync::atomic::Ordering::Relaxed)),
+        ).unwrap();
+
+        assert_eq!(result["interrupted"], true);
+        let evaluations = result["evaluations"].as_u64().unwrap();
+        assert!(evaluations >= 8 && evaluations < 100000, "evaluations = {}", evaluations);
+        assert!(result["best_objective"].as_f64().unwrap().is_finite());
+        assert_eq!(result["params_normalized"].as_array().unwrap().len(), 2);
+        assert!(result["optimised_model_ini"].as_str().unwrap().contains("c.a"));
+
+        // The partial result is kept in the session
+        session.interrupt_flag.store(false, std::sync::atomic::Ordering::Relaxed);
+        let stored = GetOptimisationResultCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(stored, result);
+    }
+
+    #[test]
+    fn test_run_forecast() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(model);
+
+        // Two members, running a day past the end of the data
+        let params = serde_json::json!({
+            "forecast_start": "2013-08-27",
+            "inputs": {"data.data_csv.by_index.1": [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]},
+            "series": ["node.node1.ds_1"],
+            "quantiles": [0.5],
+        });
+        let result = RunForecastCommand.execute(&mut session, params, Box::new(|_| {})).unwrap();
+        assert_eq!(result["n_members"], 2);
+        assert!(result["forecast_end"].as_str().unwrap().starts_with("2013-08-29"));
+        assert_eq!(result["series"][0]["members"], serde_json::json!(["node.node1.ds_1_m1", "node.node1.ds_1_m2"]));
+
+        let mut get = |name: &str| GetResultCommand.execute(&mut session,
+            serde_json::json!({"series_name": name, "format": "csv"}), Box::new(|_| {})).unwrap();
+        assert!(get("node.node1.ds_1_m2")["data"].as_str().unwrap().ends_with(",4,5,6"));
+        assert!(get("node.node1.ds_1_p50")["data"].as_str().unwrap().ends_with(",1,2,3"));
+
+        let params = serde_json::json!({"forecast_start": "2013-08-27", "inputs": {"data.data_csv.by_index.1": 3}});
+        assert!(RunForecastCommand.execute(&mut session, params, Box::new(|_| {})).is_err());
+    }
+
+    #[test]
+    fn test_apply_calibration() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap()
+            .replace("c.a = 2.0", "; Calibrated against the Rain column\nc.a = 2.0  ; first guess");
+        let model = IniModelIO::new().read_model_string_with_working_directory(
+            &ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(model);
+
+        // Nothing to apply before an optimisation
+        assert!(ApplyCalibrationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).is_err());
+
+        // A bad target leaves the model as it was
+        let bad = [("c.a".to_string(), 4.0), ("c.b".to_string(), 5.0), ("c.nope".to_string(), 1.0)];
+        assert!(session.get_model_by_id_mut(DEFAULT_MODEL_ID).unwrap().apply_calibration(&bad).is_err());
+        let model = session.get_model().unwrap();
+        assert_eq!((model.get_parameter("c.a").unwrap(), model.get_parameter("c.b").unwrap()), (2.0, 3.0));
+        assert!(model.get_ini_string().unwrap().contains("c.a = 2.0  ; first guess"));
+
+        let config = r#"
+[optimisation]
+objective_expression = term1
+algorithm = DE
+population_size = 8
+termination_evaluations = 80
+random_seed = 1
+
+[term.term1]
+simulated = node.node2.ds_1
+observed_file = ./src/tests/example_models/5/data.csv
+observed_series = 1
+statistic = RMSE
+
+[parameters]
+c.a = lin_range(g(1), 0.0, 10.0)
+c.b = lin_range(g(2), 0.0, 10.0)
+"#;
+        let result = RunOptimisationCommand.execute(
+            &mut session,
+            serde_json::json!({"config": config}),
+            Box::new(|_| {}),
+        ).unwrap();
+        let best_a = result["params_physical"]["c.a"].as_f64().unwrap();
+        let best_b = result["params_physical"]["c.b"].as_f64().unwrap();
+        assert!(result["applied"].is_null());
+        assert_eq!(session.get_model().unwrap().get_parameter("c.a").unwrap(), 2.0);
+
+        let dir = TestDir::new("kalix_apply_calibration");
+        let path = dir.join("calibrated.ini");
+        let applied = ApplyCalibrationCommand.execute(
+            &mut session,
+            serde_json::json!({"path": path.to_str().unwrap()}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(applied["parameters"]["c.a"], best_a);
+        assert!(applied["path"].is_string());
+
+        // The model and its INI document both hold the calibrated values, and comments are kept
+        let model = session.get_model().unwrap();
+        assert_eq!(model.get_parameter("c.a").unwrap(), best_a);
+        assert_eq!(model.get_parameter("c.b").unwrap(), best_b);
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(saved, model.get_ini_string().unwrap());
+        assert!(saved.contains("; Calibrated against the Rain column\n"), "{}", saved);
+        assert!(saved.contains("; first guess"), "{}", saved);
+        let reloaded = IniModelIO::new().read_model_string(&saved).unwrap();
+        assert_eq!(reloaded.get_parameter("c.a").unwrap(), best_a);
+        assert_eq!(reloaded.get_parameter("c.b").unwrap(), best_b);
+
+        // Or applied straight after the run
+        session.get_model_by_id_mut(DEFAULT_MODEL_ID).unwrap().set_parameter("c.a", 2.0).unwrap();
+        let result = RunOptimisationCommand.execute(
+            &mut session,
+            serde_json::json!({"config": config, "apply": true}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert_eq!(result["applied"]["parameters"]["c.a"], result["params_physical"]["c.a"]);
+        assert!(result["applied"]["path"].is_null());
+        assert_eq!(session.get_model().unwrap().get_parameter("c.a").unwrap(),
+                   result["params_physical"]["c.a"].as_f64().unwrap());
+    }
+
+    #[test]
+    fn test_run_optimisation_on_a_named_model() {
+        // Model "b" names its second node differently, so its terms can't be simulated by the
+        // default model
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        let read = |ini: &str| IniModelIO::new().read_model_string_with_working_directory(
+            ini, Some(std::path::PathBuf::from("."))).unwrap();
+        session.set_model(read(&ini));
+        session.set_model_by_id("b", read(&ini.replace("node2", "nodeb")));
+
+        let config = r#"
+[optimisation]
+objective_expression = term1
+algorithm = DE
+population_size = 8
+termination_evaluations = 80
+random_seed = 1
+
+[term.term1]
+simulated = node.nodeb.ds_1
+observed_file = ./src/tests/example_models/5/data.csv
+observed_series = 1
+statistic = RMSE
+
+[parameters]
+c.a = lin_range(g(1), 0.0, 10.0)
+c.b = lin_range(g(2), 0.0, 10.0)
+"#;
+        let result = RunOptimisationCommand.execute(
+            &mut session,
+            serde_json::json!({"config": config, "model_id": "b", "apply": true}),
+            Box::new(|_| {}),
+        ).unwrap();
+        assert!(result["optimised_model_ini"].as_str().unwrap().contains("[node.nodeb]"));
+        assert!(result["best_objective"].as_f64().unwrap().is_finite());
+        let best_a = result["params_physical"]["c.a"].as_f64().unwrap();
+        assert_eq!(session.get_model_by_id("b").unwrap().get_parameter("c.a").unwrap(), best_a);
+        assert_eq!(session.get_model().unwrap().get_parameter("c.a").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_run_log_reports_unmet_demand() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap()
+            .replace("[outputs]", "ds_1 = node3\n\n[node.node3]\ntype = unregulated_user\ndemand = 40\n\n[outputs]");
+        LoadModelStringCommand.execute(&mut session, serde_json::json!({"model_ini": ini}), Box::new(|_| {})).unwrap();
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(result["log_entries"], 1);
+
+        let log = GetLogCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        let entries = log["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["level"], "warning");
+        assert_eq!(entries[0]["code"], "demand_unmet");
+        assert_eq!(entries[0]["source"], "node3");
+        assert!(entries[0]["count"].as_u64().unwrap() > 1);
+        assert!(entries[0]["first"].as_str().unwrap().starts_with("1925-"));
+
+        let log = GetLogCommand.execute(&mut session, serde_json::json!({"level": "error"}), Box::new(|_| {})).unwrap();
+        assert!(log["entries"].as_array().unwrap().is_empty());
+
+        // The log is written next to saved results
+        let dir = TestDir::new("kalix_run_log");
+        let path = dir.join("results.csv");
+        let saved = SaveResultsCommand.execute(
+            &mut session,
+            serde_json::json!({"path": path.to_str().unwrap()}),
+            Box::new(|_| {}),
+        ).unwrap();
+        let log_path = saved["log_path"].as_str().unwrap().to_string();
+        assert!(log_path.ends_with(".log.csv"));
+        let content = std::fs::read_to_string(&log_path).unwrap();
+        assert!(content.lines().nth(1).unwrap().starts_with("warning,demand_unmet,node3,"));
+    }
+
+    #[test]
+    fn test_save_results_writes_yearly_csvs_with_units() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        LoadModelStringCommand.execute(&mut session, serde_json::json!({"model_ini": ini}), Box::new(|_| {})).unwrap();
+        RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+
+        let dir = TestDir::new("kalix_save_results");
+        let saved = SaveResultsCommand.execute(
+            &mut session,
+            serde_json::json!({
+                "path": dir.join("results.csv").to_str().unwrap(),
+                "date_header": "Date",
+                "units": {"node.node1.ds_1": "ML/d"},
+                "split_by_year": true,
+            }),
+            Box::new(|_| {}),
+        ).unwrap();
+        let paths = saved["paths"].as_array().unwrap();
+        assert_eq!(paths.len(), 2013 - 1925 + 1);
+        assert_eq!(saved["path"], paths[0]);
+        assert!(paths[0].as_str().unwrap().ends_with("results_1925.csv"));
+
+        let content = std::fs::read_to_string(paths[1].as_str().unwrap()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[0], "Date,node.node1.ds_1,node.node2.ds_1");
+        assert_eq!(lines[1], "Units,ML/d,");
+        assert!(lines[2].starts_with("1926-01-01,"));
+        assert_eq!(lines.len(), 2 + 365);
+
+        let result = SaveResultsCommand.execute(
+            &mut session,
+            serde_json::json!({"path": dir.join("results.csv").to_str().unwrap(), "units": "ML/d"}),
+            Box::new(|_| {}),
+        );
+        assert!(matches!(result, Err(CommandError::InvalidParameters(_))));
+    }
+
+    #[test]
+    fn test_stepped_run_is_paused_stepped_and_inspected() {
+        let mut session = Session::new();
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+        LoadModelStringCommand.execute(&mut session, serde_json::json!({"model_ini": ini}), Box::new(|_| {})).unwrap();
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({"step_mode": true}), Box::new(|_| {})).unwrap();
+        assert_eq!((result["paused"].clone(), result["last_step"].clone(), result["next_step"].clone()),
+                   (serde_json::json!(true), serde_json::Value::Null, serde_json::json!("1925-11-15")));
+
+        let result = StepCommand.execute(&mut session, serde_json::json!({"n": 2}), Box::new(|_| {})).unwrap();
+        assert_eq!((result["steps_run"].clone(), result["last_step"].clone(), result["next_step"].clone()),
+                   (serde_json::json!(2), serde_json::json!("1925-11-16"), serde_json::json!("1925-11-17")));
+
+        // node2 passes on node1's rain (3.2 mm on 1925-11-16) plus 24.5
+        let result = InspectCommand.execute(&mut session, serde_json::json!({"nodes": ["NODE2"]}), Box::new(|_| {})).unwrap();
+        let nodes = result["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!((nodes[0]["name"].as_str(), nodes[0]["type"].as_str()), (Some("node2"), Some("inflow")));
+        assert!((nodes[0]["results"]["node.node2.ds_1"].as_f64().unwrap() - 27.7).abs() < 1e-9, "{}", nodes[0]);
+        assert_eq!(nodes[0]["water"]["lateral_inflow"], 24.5);
+        let all = InspectCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(all["nodes"].as_array().unwrap().len(), 2);
+
+        let result = RunToCommand.execute(&mut session, serde_json::json!({"date": "1925-12-01"}), Box::new(|_| {})).unwrap();
+        assert_eq!((result["steps_run"].clone(), result["next_step"].clone()), (serde_json::json!(14), serde_json::json!("1925-12-01")));
+        let err = RunToCommand.execute(&mut session, serde_json::json!({"date": "1925-11-20"}), Box::new(|_| {})).err().unwrap();
+        assert_eq!(err.to_string(), "Command execution error: The run is already paused at 1925-12-01");
+        let err = InspectCommand.execute(&mut session, serde_json::json!({"nodes": ["node9"]}), Box::new(|_| {})).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid parameters: Node 'node9' was not found");
+
+        // The run ends after its last timestep, and has the same results as a full run
+        let result = StepCommand.execute(&mut session, serde_json::json!({"n": 1000000}), Box::new(|_| {})).unwrap();
+        assert_eq!((result["paused"].clone(), result["next_step"].clone()), (serde_json::json!(false), serde_json::Value::Null));
+        let stepped = session.get_model().unwrap().clone();
+        let err = StepCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).err().unwrap();
+        assert_eq!(err.to_string(), "Command execution error: There is no stepped run paused");
+        RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        let full = session.get_model().unwrap();
+        let values = |m: &crate::model::Model| m.data_cache.get_series(m.data_cache.get_existing_series_idx("node.node2.ds_1").unwrap()).values.clone();
+        assert_eq!(values(&stepped), values(full));
+    }
+
+    #[test]
+    fn test_incremental_run_reruns_the_nodes_a_parameter_affects() {
+        let mut session = Session::new();
+        let ini = "[kalix]\nstart = 2000-01-01\nend = 2000-01-31\n\n\
+            [node.river]\ntype = inflow\nloc = 0, 0\ninflow = 100\nds_1 = town\n\n\
+            [node.town]\ntype = loss\nloc = 0, 10\ntable = 0, 0, 100, 50\nds_1 = outfall\n\n\
+            [node.outfall]\ntype = gauge\nloc = 0, 20\n\n[outputs]\nnode.outfall.dsflow\n";
+        LoadModelStringCommand.execute(&mut session, serde_json::json!({"model_ini": ini}), Box::new(|_| {})).unwrap();
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({"incremental": true}), Box::new(|_| {})).unwrap();
+        assert_eq!(result["rerun_nodes"], serde_json::Value::Null);
+
+        SetParameterCommand.execute(&mut session, serde_json::json!({"target": "node.town.loss_scale", "value": 0.5}), Box::new(|_| {})).unwrap();
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({"incremental": true}), Box::new(|_| {})).unwrap();
+        assert_eq!(result["rerun_nodes"], serde_json::json!(["town", "outfall"]));
+        let m = session.get_model().unwrap();
+        let outfall = m.data_cache.get_series(m.data_cache.get_existing_series_idx("node.outfall.dsflow").unwrap()).values[0];
+        assert_eq!(outfall, 75.0);
+
+        // A run without the flag is a full one
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(result["rerun_nodes"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_events_are_recorded_and_queried() {
+        let mut session = Session::new();
+        // node2 passes on rain + 24.5; the user stops diverting whenever that is below 30
+        let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap()
+            .replace("[outputs]", "ds_1 = node3\n\n[node.node3]\ntype = unregulated_user\ndemand = 40\nflow_threshold = 30\n\n[outputs]");
+        LoadModelStringCommand.execute(&mut session, serde_json::json!({"model_ini": ini}), Box::new(|_| {})).unwrap();
+
+        // Without record_events only the run log is kept
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert!(result["events_recorded"].is_null());
+        assert_eq!(result["log_entries"], 2);
+        let events = GetEventsCommand.execute(&mut session, serde_json::json!({}), Box::new(|_| {})).unwrap();
+        assert_eq!(events["n_events"], 0);
+
+        let result = RunSimulationCommand.execute(&mut session, serde_json::json!({"record_events": true}), Box::new(|_| {})).unwrap();
+        let n_events = result["events_recorded"].as_u64().unwrap();
+        assert!(!session.get_model().unwrap().events().enabled);
+
+        let events = GetEventsCommand.execute(&mut session, serde_json::json!({"max_events": 5}), Box::new(|_| {})).unwrap();
+        assert_eq!(events["n_events"].as_u64().unwrap(), n_events);
+        assert_eq!(events["events"].as_array().unwrap().len(), 5);
+        assert_eq!(events["truncated"], true);
+        let summary = events["summary"].as_array().unwrap();
+        assert_eq!(summary.len(), 2);
+        let cease = summary.iter().find(|s| s["kind"] == "cease_to_flow").unwrap();
+        assert_eq!(cease["node"], "node3");
+        assert_eq!(cease["max"], 30.0);
+        let shortfall = summary.iter().find(|s| s["kind"] == "demand_shortfall").unwrap();
+        assert_eq!(shortfall["count"].as_u64().unwrap() + cease["count"].as_u64().unwrap(), n_events);
+
+        // Filtered by kind and period, and exported
+        let dir = TestDir::new("kalix_events");
+        let path = dir.join("events.csv");
+        let events = GetEventsCommand.execute(
+            &mut session,
+            serde_json::json!({"kind": "cease_to_flow", "node": "NODE3", "end": "1925-12-31", "path": path.to_str().unwrap()}),
+            Box::new(|_| {}),
+        ).unwrap();
+        let n_cease = events["n_events"].as_u64().unwrap();
+        assert!(n_cease >= 1 && n_cease < cease["count"].as_u64().unwrap());
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count() as u64, n_cease + 1);
+        assert!(content.lines().nth(1).unwrap().contains(",cease_to_flow,node3,30"));
+
+        assert!(matches!(
+            GetEventsCommand.execute(&mut session, serde_json::json!({"kind": "flood"}), Box::new(|_| {})),
+            Err(CommandError::InvalidParameters(_))
+        ));
+    }
+
     #[test]
     fn test_get_version_command() {
         let cmd = GetVersionCommand;
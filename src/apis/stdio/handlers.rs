@@ -1,26 +1,68 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::io::Write;
+use std::sync::OnceLock;
+use std::sync::mpsc::{channel, RecvTimeoutError};
 use crate::apis::stdio::session::{Session, SessionError};
 use crate::apis::stdio::transport::{Transport, TransportError};
-use crate::apis::stdio::commands::{CommandRegistry, CommandError};
+use crate::apis::stdio::commands::{Command, CommandRegistry, CommandError};
 use crate::apis::stdio::messages::*;
 
+/// How often the message loop checks for messages while a background command runs
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Worker pool that interruptible commands run on, shared by all sessions so each command
+/// reuses a worker thread rather than spawning one
+fn command_pool() -> &'static rayon::ThreadPool {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .thread_name(|i| format!("kalix-command-{}", i))
+            .build()
+            .expect("Failed to create command thread pool")
+    })
+}
+
 pub fn run_stdio_session() -> Result<(), StdioError> {
     let mut session = Session::new();
     let transport = Transport::new();
     let registry = CommandRegistry::new();
+    serve_session(&mut session, &transport, &registry)?;
+    Ok(())
+}
 
+/// How [`serve_session`] ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEnd {
+    /// The client sent a terminate message
+    Terminated,
+    /// The client's input stream closed (e.g. a dropped network connection)
+    Disconnected,
+}
+
+/// Run the protocol over one transport until the client terminates or disconnects.
+///
+/// The session (loaded model, stored results) is owned by the caller, so a network server can
+/// serve it again to a client that reconnects.
+pub fn serve_session(
+    session: &mut Session,
+    transport: &Transport,
+    registry: &CommandRegistry
+) -> Result<SessionEnd, StdioError> {
     // Send initial ready message with return code 0 (success)
     let ready_msg = create_ready_message(session.id.clone(), 0);
     transport.send_message(&ready_msg)?;
 
     loop {
-        match handle_session_loop(&mut session, &transport, &registry) {
+        match handle_session_loop(session, transport, registry) {
             Ok(should_continue) => {
                 if !should_continue {
-                    break;
+                    return Ok(SessionEnd::Terminated);
                 }
             }
+            Err(StdioError::Transport(TransportError::StdinClosed)) => {
+                let _ = session.set_ready();
+                return Ok(SessionEnd::Disconnected);
+            }
             Err(e) => {
                 // Send error message and continue
                 let error_msg = create_error_message(
@@ -39,8 +81,6 @@ pub fn run_stdio_session() -> Result<(), StdioError> {
             }
         }
     }
-
-    Ok(())
 }
 
 fn handle_session_loop(
@@ -48,58 +88,126 @@ fn handle_session_loop(
     transport: &Transport,
     registry: &CommandRegistry
 ) -> Result<bool, StdioError> {
-    if session.is_ready() {
-        // Block waiting for command when ready
-        let msg = transport.receive_message_blocking()?;
-
-        match msg.m.as_str() {
-            MSG_COMMAND => {
-                if let Some((command, params)) = extract_command_info(&msg) {
-                    handle_command_message(session, transport, registry, command, params)?;
-                } else {
-                    send_error_message(session, transport, None, "Invalid command format".to_string())?;
-                }
-            }
-            MSG_QUERY => {
-                if let Some(query_type) = extract_query_type(&msg) {
-                    handle_query_message(session, transport, query_type)?;
-                } else {
-                    send_error_message(session, transport, None, "Invalid query format".to_string())?;
+    // Block waiting for command when ready; messages sent while a command runs are handled
+    // by handle_busy_message
+    let msg = transport.receive_message_blocking()?;
+
+    match msg.m.as_str() {
+        MSG_COMMAND => {
+            if let Some((command, params)) = extract_command_info(&msg) {
+                if !handle_command_message(session, transport, registry, command, params)? {
+                    return Ok(false); // Terminated while busy
                 }
+            } else {
+                send_error_message(session, transport, None, "Invalid command format".to_string())?;
             }
-            MSG_TERMINATE => {
-                return Ok(false); // Signal to exit
+        }
+        MSG_QUERY => {
+            if let Some(query_type) = extract_query_type(&msg) {
+                handle_query_message(session, transport, query_type, &session.get_state_info())?;
+            } else {
+                send_error_message(session, transport, None, "Invalid query format".to_string())?;
             }
-            _ => {
-                send_error_message(session, transport, None, format!("Unknown message type: {}", msg.m))?;
+        }
+        MSG_TERMINATE => {
+            return Ok(false); // Signal to exit
+        }
+        _ => {
+            send_error_message(session, transport, None, format!("Unknown message type: {}", msg.m))?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Handles a message received while a command runs in the background. Returns false if the
+/// client terminated the session.
+fn handle_busy_message(
+    session: &Session,
+    transport: &Transport,
+    msg: Message,
+    state: &StateInfo
+) -> Result<bool, StdioError> {
+    match msg.m.as_str() {
+        MSG_STOPPED => {
+            session.request_interrupt()?;
+        }
+        MSG_QUERY => {
+            if let Some(query_type) = extract_query_type(&msg) {
+                handle_query_message(session, transport, query_type, state)?;
+            } else {
+                send_error_message(session, transport, None, "Invalid query format".to_string())?;
             }
         }
-    } else if session.is_busy() {
-        // Check for interrupt messages while busy
-        if let Some(msg) = transport.try_receive_message()? {
-            match msg.m.as_str() {
-                MSG_STOPPED => {
-                    session.request_interrupt()?;
-                }
-                MSG_QUERY => {
-                    if let Some(query_type) = extract_query_type(&msg) {
-                        handle_query_message(session, transport, query_type)?;
-                    }
+        MSG_TERMINATE => {
+            // Force interrupt and exit
+            let _ = session.request_interrupt();
+            return Ok(false);
+        }
+        _ => {
+            // Invalid message while busy, send error but continue
+            send_error_message(session, transport, None, format!("Cannot process '{}' message while busy", msg.m))?;
+        }
+    }
+    Ok(true)
+}
+
+/// Runs a command on the command pool while this thread keeps handling stop messages and
+/// queries. The command gets the session's models and results for the duration; queries are
+/// answered from the state at the start. Returns the command's result and false if the client
+/// terminated the session meanwhile.
+fn execute_in_background(
+    session: &mut Session,
+    transport: &Transport,
+    command: &dyn Command,
+    parameters: serde_json::Value,
+    progress_callback: Box<dyn Fn(ProgressInfo) + Send + Sync>
+) -> Result<(Result<serde_json::Value, CommandError>, bool), StdioError> {
+    let state = session.get_state_info();
+    let mut worker = session.detach();
+    let (done_tx, done_rx) = channel();
+
+    let (result, listening) = command_pool().in_place_scope(|scope| {
+        let worker = &mut worker;
+        scope.spawn(move |_| {
+            let _ = done_tx.send(command.execute(worker, parameters, progress_callback));
+        });
+
+        // Once the client terminates or disconnects the command is stopped and only waited for
+        let mut listening: Result<bool, StdioError> = Ok(true);
+        loop {
+            match done_rx.recv_timeout(BUSY_POLL_INTERVAL) {
+                Ok(result) => return (result, listening),
+                Err(RecvTimeoutError::Disconnected) => {
+                    // The command panicked; the scope resumes the panic when this returns
+                    return (Err(CommandError::Interrupted), listening);
                 }
-                MSG_TERMINATE => {
-                    // Force interrupt and exit
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if !matches!(listening, Ok(true)) {
+                continue;
+            }
+            listening = match transport.try_receive_message() {
+                Ok(Some(msg)) => handle_busy_message(session, transport, msg, &state),
+                Ok(None) => Ok(true),
+                Err(e) => Err(e.into()),
+            };
+            match listening {
+                Ok(true) => {}
+                Ok(false) | Err(StdioError::Transport(TransportError::StdinClosed)) => {
                     let _ = session.request_interrupt();
-                    return Ok(false);
                 }
-                _ => {
-                    // Invalid message while busy, send error but continue
-                    send_error_message(session, transport, None, format!("Cannot process '{}' message while busy", msg.m))?;
+                Err(ref e) => {
+                    // e.g. a malformed message; report it and keep the command running
+                    listening = send_error_message(session, transport, None, format!("Session error: {}", e))
+                        .map(|_| true);
                 }
             }
         }
-    }
+    });
 
-    Ok(true)
+    session.rejoin(worker);
+    Ok((result, listening?))
 }
 
 fn handle_command_message(
@@ -108,7 +216,7 @@ fn handle_command_message(
     registry: &CommandRegistry,
     command: String,
     parameters: serde_json::Value
-) -> Result<(), StdioError> {
+) -> Result<bool, StdioError> {
     // Find command in registry
     let command_spec = registry.get_command(&command)
         .ok_or_else(|| StdioError::UnknownCommand(command.clone()))?;
@@ -138,13 +246,19 @@ fn handle_command_message(
         let task_type = progress.task_type.unwrap_or_else(|| "sim".to_string());
 
         // Convert progress to protocol format and send
-        let progress_msg = create_progress_message(
+        let mut progress_msg = create_progress_message(
             session_id.clone(),
             current,
             total,
             task_type,
             progress.data, // Pass through any data field
         );
+        if let Some(timing) = progress.timing {
+            progress_msg.fields["nt"] = timing;
+        }
+        if let Some(eta) = progress.estimated_remaining {
+            progress_msg.fields["eta"] = serde_json::Value::String(eta);
+        }
 
         if let Ok(json) = serde_json::to_string(&progress_msg) {
             if let Ok(mut stdout) = transport_clone.lock() {
@@ -154,8 +268,12 @@ fn handle_command_message(
         }
     });
 
-    // Execute command
-    let result = command_spec.execute(session, parameters, progress_callback);
+    // Execute command, in the background if it can be stopped
+    let (result, keep_serving) = if is_interruptible {
+        execute_in_background(session, transport, command_spec.as_ref(), parameters, progress_callback)?
+    } else {
+        (command_spec.execute(session, parameters, progress_callback), true)
+    };
 
     let execution_time_ms = duration_to_ms(start_time.elapsed());
 
@@ -241,17 +359,18 @@ fn handle_command_message(
     let ready_msg = create_ready_message(session.id.clone(), return_code);
     transport.send_message(&ready_msg)?;
 
-    Ok(())
+    Ok(keep_serving)
 }
 
 fn handle_query_message(
     session: &Session,
     transport: &Transport,
-    query_type: String
+    query_type: String,
+    state: &StateInfo
 ) -> Result<(), StdioError> {
     let result: Result<serde_json::Value, String> = match query_type.as_str() {
         "get_state" => {
-            Ok(serde_json::to_value(session.get_state_info()).unwrap())
+            Ok(serde_json::to_value(state).unwrap())
         }
         "get_session_id" => {
             Ok(serde_json::json!({"session_id": session.id}))
@@ -360,4 +479,56 @@ mod tests {
         let query_type = extract_query_type(&msg).unwrap();
         assert_eq!(query_type, "get_state");
     }
+
+    #[test]
+    fn test_messages_are_handled_while_a_command_runs() {
+        use std::io::{BufRead, BufReader};
+        let (client_reader, server_writer) = std::io::pipe().unwrap();
+        let (server_reader, mut client_writer) = std::io::pipe().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut session = Session::new();
+            let transport = Transport::from_streams(server_reader, Box::new(server_writer));
+            serve_session(&mut session, &transport, &CommandRegistry::new()).unwrap()
+        });
+        let mut lines = BufReader::new(client_reader).lines();
+        let mut next_message = || -> serde_json::Value {
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap()
+        };
+        assert_eq!(next_message()["m"], "rdy");
+
+        // A command that would run for a minute
+        writeln!(client_writer, r#"{{"m":"cmd","c":"test_progress","p":{{"duration_seconds":60}}}}"#).unwrap();
+        assert_eq!(next_message()["m"], "bsy");
+
+        // Two queries are answered while it runs
+        writeln!(client_writer, r#"{{"m":"query","q":"get_state"}}"#).unwrap();
+        writeln!(client_writer, r#"{{"m":"query","q":"get_session_id"}}"#).unwrap();
+        let mut answers = vec![];
+        while answers.len() < 2 {
+            let msg = next_message();
+            if msg["m"] == "res" {
+                answers.push(msg["cmd"].as_str().unwrap().to_string());
+            } else {
+                assert_eq!(msg["m"], "prg");
+            }
+        }
+        assert_eq!(answers, vec!["query_get_state", "query_get_session_id"]);
+
+        // A stop ends it well before the minute is up
+        let started = Instant::now();
+        writeln!(client_writer, r#"{{"m":"stp"}}"#).unwrap();
+        let mut msg = next_message();
+        while msg["m"] == "prg" {
+            msg = next_message();
+        }
+        assert_eq!(msg["m"], "err");
+        assert_eq!(next_message()["m"], "stp");
+        let ready = next_message();
+        assert_eq!(ready["m"], "rdy");
+        assert_eq!(ready["rc"], 1);
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        writeln!(client_writer, r#"{{"m":"term"}}"#).unwrap();
+        assert_eq!(server.join().unwrap(), SessionEnd::Terminated);
+    }
 }
\ No newline at end of file
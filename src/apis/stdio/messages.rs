@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 // JSON Protocol - Single Message Structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,6 +56,9 @@ pub struct StateInfo {
     pub model_loaded: bool,
     pub data_loaded: bool,
     pub last_simulation: Option<String>,
+    /// Ids of all loaded models
+    #[serde(default)]
+    pub models: Vec<String>,
 }
 
 // Message creation functions
@@ -174,6 +179,50 @@ pub struct ProgressInfo {
     pub current: Option<i64>,    // Current progress value (e.g., evaluations)
     pub total: Option<i64>,      // Total value (e.g., termination_evaluations)
     pub task_type: Option<String>, // Task type (defaults to "sim")
+    pub timing: Option<serde_json::Value>, // Optional per-node timing breakdown (run_simulation)
+}
+
+/// How far back [`RollingThroughput`] looks when estimating a rate
+pub const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Rate of progress of a task (e.g. timesteps or evaluations per second) over a rolling window,
+/// so the estimated time remaining follows changes in speed rather than averaging the whole run
+pub struct RollingThroughput {
+    window: Duration,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RollingThroughput {
+    pub fn new(window: Duration) -> Self {
+        Self { window, samples: VecDeque::new() }
+    }
+
+    /// Records that `completed` units of work had been done by `now`
+    pub fn record(&mut self, now: Instant, completed: u64) {
+        self.samples.push_back((now, completed));
+        // Keep one sample from the start of the window or before it
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Units of work per second over the window
+    pub fn rate(&self) -> Option<f64> {
+        let (&(first_time, first_done), &(last_time, last_done)) = (self.samples.front()?, self.samples.back()?);
+        let seconds = last_time.duration_since(first_time).as_secs_f64();
+        if seconds <= 0.0 || last_done <= first_done {
+            return None;
+        }
+        Some((last_done - first_done) as f64 / seconds)
+    }
+
+    /// Time to finish `total` units at the current rate, as HH:MM:SS
+    pub fn estimated_remaining(&self, total: u64) -> Option<String> {
+        let rate = self.rate()?;
+        let &(_, done) = self.samples.back()?;
+        let seconds = (total.saturating_sub(done) as f64 / rate).round() as u64;
+        Some(format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60))
+    }
 }
 
 #[cfg(test)]
@@ -208,6 +257,27 @@ mod tests {
         assert_eq!(msg.fields["d"][0], 0.856);
     }
 
+    #[test]
+    fn test_rolling_throughput() {
+        let start = Instant::now();
+        let mut throughput = RollingThroughput::new(Duration::from_secs(10));
+        throughput.record(start, 0);
+        assert_eq!(throughput.rate(), None);
+        assert_eq!(throughput.estimated_remaining(1000), None);
+
+        // 10 units per second for 5 seconds
+        throughput.record(start + Duration::from_secs(5), 50);
+        assert!((throughput.rate().unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(throughput.estimated_remaining(4000).unwrap(), "00:06:35");
+
+        // Then 100 units per second: once the slow start leaves the window it no longer counts
+        for s in 6..=20 {
+            throughput.record(start + Duration::from_secs(s), 50 + 100 * (s - 5));
+        }
+        assert!((throughput.rate().unwrap() - 100.0).abs() < 1e-9);
+        assert_eq!(throughput.estimated_remaining(1550 + 360_000).unwrap(), "01:00:00");
+    }
+
     #[test]
     fn test_command_extraction() {
         let fields = serde_json::json!({
@@ -3,6 +3,8 @@ pub mod session;
 pub mod transport;
 pub mod commands;
 pub mod handlers;
+#[cfg(feature = "network")]
+pub mod network;
 
 pub use session::*;
 pub use messages::*;
@@ -0,0 +1,214 @@
+//! TCP and WebSocket transports for the JSON protocol (requires the `network` feature)
+//!
+//! The server listens on a local address and serves one client at a time with exactly the
+//! same messages as the stdio API. A TCP client exchanges line-delimited JSON; a WebSocket
+//! client sends and receives one JSON message per text frame. The session outlives its
+//! connection: when a client disconnects, the loaded model and stored results are kept and
+//! the next client to connect picks up the same session (same `uid`). The server stops when
+//! a client sends a terminate message.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::apis::stdio::commands::CommandRegistry;
+use crate::apis::stdio::handlers::{serve_session, SessionEnd, StdioError};
+use crate::apis::stdio::session::Session;
+use crate::apis::stdio::transport::{Transport, TransportError};
+
+/// How the WebSocket reader waits for a frame before releasing the socket to writers
+const WEBSOCKET_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Wire protocol for [`run_network_session`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkProtocol {
+    /// Line-delimited JSON over a plain TCP socket
+    Tcp,
+    /// One JSON message per WebSocket text frame
+    WebSocket,
+}
+
+/// Bind `address` (e.g. `127.0.0.1:7878`) and serve the protocol until a client terminates
+pub fn run_network_session<A: ToSocketAddrs>(address: A, protocol: NetworkProtocol) -> Result<(), StdioError> {
+    let listener = TcpListener::bind(address)
+        .map_err(|e| TransportError::ConnectionError(format!("Failed to bind: {}", e)))?;
+    serve_listener(listener, protocol)
+}
+
+/// Serve clients from an already-bound listener until one terminates the session
+pub fn serve_listener(listener: TcpListener, protocol: NetworkProtocol) -> Result<(), StdioError> {
+    let mut session = Session::new();
+    let registry = CommandRegistry::new();
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        let transport = match protocol {
+            NetworkProtocol::Tcp => tcp_transport(stream),
+            NetworkProtocol::WebSocket => websocket_transport(stream),
+        };
+        let transport = match transport {
+            Ok(t) => t,
+            Err(_) => continue, // Failed handshake; wait for the next client
+        };
+
+        match serve_session(&mut session, &transport, &registry) {
+            Ok(SessionEnd::Terminated) => break,
+            Ok(SessionEnd::Disconnected) => {}
+            // A client that drops mid-command surfaces as a write failure
+            Err(StdioError::Transport(_)) => {
+                let _ = session.set_ready();
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn tcp_transport(stream: TcpStream) -> io::Result<Transport> {
+    let reader = stream.try_clone()?;
+    Ok(Transport::from_streams(reader, Box::new(stream)))
+}
+
+fn websocket_transport(stream: TcpStream) -> io::Result<Transport> {
+    let websocket = tungstenite::accept(stream)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    websocket.get_ref().set_read_timeout(Some(WEBSOCKET_POLL_INTERVAL))?;
+    let websocket = Arc::new(Mutex::new(websocket));
+    let reader = WebSocketReader { websocket: Arc::clone(&websocket), buffer: Vec::new(), position: 0 };
+    let writer = WebSocketWriter { websocket, buffer: Vec::new() };
+    Ok(Transport::from_streams(reader, Box::new(writer)))
+}
+
+type SharedWebSocket = Arc<Mutex<tungstenite::WebSocket<TcpStream>>>;
+
+/// Presents incoming text frames as a stream of lines
+struct WebSocketReader {
+    websocket: SharedWebSocket,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl Read for WebSocketReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.buffer.len() {
+            // The lock is only held for one poll interval so writers are not starved
+            let message = self.websocket.lock().unwrap().read();
+            match message {
+                Ok(tungstenite::Message::Text(text)) => {
+                    self.buffer = text.replace('\n', " ").into_bytes();
+                    self.buffer.push(b'\n');
+                    self.position = 0;
+                }
+                Ok(tungstenite::Message::Close(_)) => return Ok(0),
+                Ok(_) => {} // Pings are answered by tungstenite; binary frames are ignored
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    std::thread::yield_now();
+                }
+                Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => return Ok(0),
+                Err(e) => return Err(io::Error::other(e.to_string())),
+            }
+        }
+        let n = buf.len().min(self.buffer.len() - self.position);
+        buf[..n].copy_from_slice(&self.buffer[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Sends each line written to it as one text frame
+struct WebSocketWriter {
+    websocket: SharedWebSocket,
+    buffer: Vec<u8>,
+}
+
+impl Write for WebSocketWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let text = String::from_utf8_lossy(&line[..end]).into_owned();
+            self.websocket.lock().unwrap()
+                .send(tungstenite::Message::Text(text))
+                .map_err(|e| io::Error::other(e.to_string()))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use serde_json::Value;
+
+    fn spawn_server(protocol: NetworkProtocol) -> (std::net::SocketAddr, std::thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = std::thread::spawn(move || serve_listener(listener, protocol).unwrap());
+        (address, handle)
+    }
+
+    #[test]
+    fn test_tcp_session_survives_reconnect() {
+        let (address, server) = spawn_server(NetworkProtocol::Tcp);
+        let connect = || {
+            let stream = TcpStream::connect(address).unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            (stream, reader)
+        };
+        let read_message = |reader: &mut BufReader<TcpStream>| -> Value {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap()
+        };
+
+        let (mut stream, mut reader) = connect();
+        let ready = read_message(&mut reader);
+        assert_eq!(ready["m"], "rdy");
+        let uid = ready["uid"].clone();
+        writeln!(stream, r#"{{"m":"query","q":"get_session_id"}}"#).unwrap();
+        let result = read_message(&mut reader);
+        assert_eq!(result["m"], "res");
+        assert_eq!(result["r"]["session_id"], uid);
+        drop(reader);
+        drop(stream);
+
+        // A new client is served the same session
+        let (mut stream, mut reader) = connect();
+        let ready = read_message(&mut reader);
+        assert_eq!(ready["m"], "rdy");
+        assert_eq!(ready["uid"], uid);
+        writeln!(stream, r#"{{"m":"term"}}"#).unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_websocket_session() {
+        let (address, server) = spawn_server(NetworkProtocol::WebSocket);
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut websocket, _) = tungstenite::client(format!("ws://{}/", address), stream).unwrap();
+        let read_message = |websocket: &mut tungstenite::WebSocket<TcpStream>| -> Value {
+            match websocket.read().unwrap() {
+                tungstenite::Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                other => panic!("unexpected frame {:?}", other),
+            }
+        };
+        assert_eq!(read_message(&mut websocket)["m"], "rdy");
+
+        websocket.send(tungstenite::Message::Text(r#"{"m":"query","q":"get_state"}"#.to_string())).unwrap();
+        let result = read_message(&mut websocket);
+        assert_eq!(result["m"], "res");
+        assert_eq!(result["r"]["model_loaded"], false);
+
+        websocket.send(tungstenite::Message::Text(r#"{"m":"term"}"#.to_string())).unwrap();
+        server.join().unwrap();
+    }
+}
@@ -7,6 +7,9 @@ use crate::apis::stdio::messages::StateInfo;
 use rand::RngCore;
 use base64::{Engine as _, engine::general_purpose};
 
+/// Id of the model used by commands that are not given a `model_id`
+pub const DEFAULT_MODEL_ID: &str = "default";
+
 #[derive(Debug, Clone)]
 pub enum SessionState {
     Ready,
@@ -21,7 +24,8 @@ pub struct Session {
     pub id: String,
     pub state: Arc<Mutex<SessionState>>,
     pub interrupt_flag: Arc<AtomicBool>,
-    pub model: Option<Model>,
+    /// Loaded models keyed by model id, so a baseline and a scenario can be held together
+    pub models: HashMap<String, Model>,
     pub results: HashMap<String, serde_json::Value>,
 }
 
@@ -31,7 +35,7 @@ impl Session {
             id: Self::generate_session_id(),
             state: Arc::new(Mutex::new(SessionState::Ready)),
             interrupt_flag: Arc::new(AtomicBool::new(false)),
-            model: None,
+            models: HashMap::new(),
             results: HashMap::new(),
         }
     }
@@ -46,6 +50,25 @@ impl Session {
         encoded[..12].to_string()
     }
 
+    /// Moves the loaded models and results into a session for a command to run on a background
+    /// worker. The two share the id, state and interrupt flag, so a stop reaches the worker.
+    /// Hand the worker back with [`Session::rejoin`] when its command finishes.
+    pub fn detach(&mut self) -> Session {
+        Session {
+            id: self.id.clone(),
+            state: Arc::clone(&self.state),
+            interrupt_flag: Arc::clone(&self.interrupt_flag),
+            models: std::mem::take(&mut self.models),
+            results: std::mem::take(&mut self.results),
+        }
+    }
+
+    /// Takes back the models and results of a session made by [`Session::detach`]
+    pub fn rejoin(&mut self, worker: Session) {
+        self.models = worker.models;
+        self.results = worker.results;
+    }
+
     pub fn is_ready(&self) -> bool {
         matches!(*self.state.lock().unwrap(), SessionState::Ready)
     }
@@ -111,10 +134,11 @@ impl Session {
 
     pub fn get_state_info(&self) -> StateInfo {
         StateInfo {
-            model_loaded: self.model.is_some(),
-            data_loaded: self.model.as_ref()
+            model_loaded: self.get_model().is_some(),
+            data_loaded: self.get_model()
                 .map(|m| !m.inputs.is_empty())
                 .unwrap_or(false),
+            models: self.model_ids(),
             last_simulation: self.results.get("last_simulation")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string()),
@@ -134,19 +158,43 @@ impl Session {
     }
 
     pub fn set_model(&mut self, model: Model) {
-        self.model = Some(model);
+        self.set_model_by_id(DEFAULT_MODEL_ID, model);
     }
 
     pub fn get_model_mut(&mut self) -> Option<&mut Model> {
-        self.model.as_mut()
+        self.get_model_by_id_mut(DEFAULT_MODEL_ID)
     }
 
     pub fn get_model(&self) -> Option<&Model> {
-        self.model.as_ref()
+        self.get_model_by_id(DEFAULT_MODEL_ID)
     }
 
     pub fn clear_model(&mut self) {
-        self.model = None;
+        self.models.remove(DEFAULT_MODEL_ID);
+    }
+
+    /// Stores a model under `id`, replacing any model already loaded with that id
+    pub fn set_model_by_id(&mut self, id: &str, model: Model) {
+        self.models.insert(id.to_string(), model);
+    }
+
+    pub fn get_model_by_id(&self, id: &str) -> Option<&Model> {
+        self.models.get(id)
+    }
+
+    pub fn get_model_by_id_mut(&mut self, id: &str) -> Option<&mut Model> {
+        self.models.get_mut(id)
+    }
+
+    pub fn remove_model(&mut self, id: &str) -> Option<Model> {
+        self.models.remove(id)
+    }
+
+    /// Ids of all loaded models, sorted
+    pub fn model_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.models.keys().cloned().collect();
+        ids.sort();
+        ids
     }
 }
 
@@ -212,4 +260,46 @@ mod tests {
         session.set_ready().unwrap();
         assert!(!session.check_interrupt());
     }
+
+    #[test]
+    fn test_models_are_keyed_by_id() {
+        let mut session = Session::new();
+        assert!(session.get_model().is_none());
+
+        session.set_model(Model::new());
+        session.set_model_by_id("scenario", Model::new());
+        assert!(session.get_model().is_some());
+        assert!(session.get_model_by_id(DEFAULT_MODEL_ID).is_some());
+        assert_eq!(session.model_ids(), vec!["default", "scenario"]);
+        assert_eq!(session.get_state_info().models, vec!["default", "scenario"]);
+
+        session.clear_model();
+        assert!(session.get_model().is_none());
+        assert!(!session.get_state_info().model_loaded);
+        assert!(session.remove_model("scenario").is_some());
+        assert!(session.model_ids().is_empty());
+    }
+
+    #[test]
+    fn test_detached_worker_shares_state() {
+        let mut session = Session::new();
+        session.set_model(Model::new());
+        session.store_result("last_simulation".to_string(), serde_json::json!("done"));
+
+        let mut worker = session.detach();
+        assert!(session.models.is_empty());
+        assert_eq!(worker.id, session.id);
+        assert!(worker.get_model().is_some());
+
+        // A stop sent to the session reaches the worker
+        session.set_busy("run_simulation".to_string(), true).unwrap();
+        assert!(worker.is_busy());
+        session.request_interrupt().unwrap();
+        assert!(worker.check_interrupt());
+
+        worker.set_model_by_id("scenario", Model::new());
+        session.rejoin(worker);
+        assert_eq!(session.model_ids(), vec!["default", "scenario"]);
+        assert!(session.get_result("last_simulation").is_some());
+    }
 }
\ No newline at end of file
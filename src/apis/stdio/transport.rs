@@ -1,4 +1,4 @@
-use std::io::{BufRead, BufReader, Write, BufWriter};
+use std::io::{BufRead, BufReader, Read, Write, BufWriter};
 use std::sync::mpsc::{channel, Receiver, TryRecvError};
 use std::sync::{Arc, Mutex};
 use crate::apis::stdio::messages::Message;
@@ -10,12 +10,18 @@ pub struct Transport {
 
 impl Transport {
     pub fn new() -> Self {
+        Self::from_streams(std::io::stdin(), Box::new(std::io::stdout()))
+    }
+
+    /// Build a transport over any stream carrying line-delimited JSON, e.g. a TCP socket.
+    /// Incoming lines are read on a background thread; when the reader reaches end of stream
+    /// the transport reports `StdinClosed`.
+    pub fn from_streams<R: Read + Send + 'static>(reader: R, writer: Box<dyn Write + Send>) -> Self {
         let (stdin_tx, stdin_rx) = channel();
 
-        // Spawn thread to read STDIN line by line
+        // Spawn thread to read the input line by line
         std::thread::spawn(move || {
-            let stdin = std::io::stdin();
-            let reader = BufReader::new(stdin);
+            let reader = BufReader::new(reader);
             for line in reader.lines() {
                 match line {
                     Ok(line) => {
@@ -25,7 +31,7 @@ impl Transport {
                         }
                     }
                     Err(_) => {
-                        // STDIN closed, exit thread
+                        // Input closed, exit thread
                         break;
                     }
                 }
@@ -34,7 +40,7 @@ impl Transport {
 
         Self {
             stdin_rx,
-            stdout: Arc::new(Mutex::new(BufWriter::new(Box::new(std::io::stdout())))),
+            stdout: Arc::new(Mutex::new(BufWriter::new(writer))),
         }
     }
 
@@ -93,6 +99,9 @@ pub enum TransportError {
 
     #[error("STDIN closed")]
     StdinClosed,
+
+    #[error("Connection error: {0}")]
+    ConnectionError(String),
 }
 
 #[cfg(test)]
@@ -0,0 +1,102 @@
+//! JavaScript API for running models in the browser, e.g. from teaching tools.
+//!
+//! Built with the `wasm` feature for the `wasm32-unknown-unknown` target:
+//!
+//! ```text
+//! cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/kalix.wasm
+//! ```
+//!
+//! ```js
+//! import init, { KalixModel } from "./pkg/kalix.js";
+//! await init();
+//! const model = new KalixModel(iniText);
+//! model.addInputCsv("rain.csv", csvText);  // for data.rain_csv.*, as there are no files
+//! model.run();
+//! const flow = model.getSeries("node.gauge.dsflow");  // Float64Array
+//! ```
+//!
+//! A WebAssembly build has no file system, so models that read `[inputs]` or table files
+//! fail to load; their data is supplied as CSV text with `addInputCsv` instead. Runs are
+//! single threaded.
+
+use wasm_bindgen::prelude::*;
+
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tid::utils::u64_to_unix_seconds;
+
+#[wasm_bindgen]
+pub struct KalixModel {
+    model: Model,
+    configured: bool,
+}
+
+#[wasm_bindgen]
+impl KalixModel {
+    /// Loads a model from the contents of an INI file
+    #[wasm_bindgen(constructor)]
+    pub fn new(ini: &str) -> Result<KalixModel, JsError> {
+        let model = IniModelIO::new().read_model_string(ini).map_err(|e| JsError::new(&e))?;
+        Ok(KalixModel { model, configured: false })
+    }
+
+    /// Adds input data from CSV text, addressed as `data.<file name>.*` (and
+    /// `data.<alias>.*`) as if it had been listed in the `[inputs]` section
+    #[wasm_bindgen(js_name = addInputCsv)]
+    pub fn add_input_csv(&mut self, file_name: &str, csv_text: &str, alias: Option<String>) -> Result<(), JsError> {
+        self.model.load_input_csv_str(file_name, csv_text, alias.as_deref()).map_err(|e| JsError::new(&e))?;
+        self.configured = false;
+        Ok(())
+    }
+
+    /// Runs the simulation, configuring the model first if it is new or has new input data
+    pub fn run(&mut self) -> Result<(), JsError> {
+        if !self.configured {
+            self.model.configure().map_err(|e| JsError::new(&e))?;
+            self.configured = true;
+        }
+        self.model.run().map_err(|e| JsError::new(&e))
+    }
+
+    /// Names of the series in the model's `[outputs]` section
+    pub fn outputs(&self) -> Vec<String> {
+        self.model.outputs.clone()
+    }
+
+    /// Values of a result series after a run. Missing values are NaN.
+    #[wasm_bindgen(js_name = getSeries)]
+    pub fn get_series(&self, name: &str) -> Result<Vec<f64>, JsError> {
+        let cache = &self.model.data_cache;
+        let idx = cache.get_existing_series_idx(name)
+            .ok_or_else(|| JsError::new(&format!("Series not found: {}", name)))?;
+        Ok(cache.get_series(idx).values.clone())
+    }
+
+    /// Timestamps of a result series after a run, in milliseconds since 1970 (as for `Date`)
+    #[wasm_bindgen(js_name = getTimestamps)]
+    pub fn get_timestamps(&self, name: &str) -> Result<Vec<f64>, JsError> {
+        let cache = &self.model.data_cache;
+        let idx = cache.get_existing_series_idx(name)
+            .ok_or_else(|| JsError::new(&format!("Series not found: {}", name)))?;
+        Ok(cache.get_series(idx).timestamps.iter().map(|&t| u64_to_unix_seconds(t) as f64 * 1000.0).collect())
+    }
+
+    /// Sets a parameter by target address, e.g. `node.my_sac.uzfwm` or `c.demand_factor`
+    #[wasm_bindgen(js_name = setParameter)]
+    pub fn set_parameter(&mut self, target: &str, value: f64) -> Result<(), JsError> {
+        self.model.set_parameter(target, value).map_err(|e| JsError::new(&e))
+    }
+
+    /// Gets a parameter by target address
+    #[wasm_bindgen(js_name = getParameter)]
+    pub fn get_parameter(&self, target: &str) -> Result<f64, JsError> {
+        self.model.get_parameter(target).map_err(|e| JsError::new(&e))
+    }
+
+    /// The model, including any parameter changes, as an INI string
+    #[wasm_bindgen(js_name = toIni)]
+    pub fn to_ini(&self) -> String {
+        IniModelIO::new().model_to_string(&self.model)
+    }
+}
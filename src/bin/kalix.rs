@@ -1,11 +1,24 @@
 use clap::{CommandFactory, Parser, Subcommand};
+use kalix::io::ini_formatter::format_ini;
 use kalix::io::ini_model_io::IniModelIO;
+use kalix::io::parameter_file_io::{parameters_to_csv, read_parameter_file};
 use kalix::perf::benchmarks;
 use kalix::misc::cli_helpers::describe_cli_api;
 use kalix::misc::simulation_context::install_simulation_panic_hook;
 use kalix::apis::stdio::handlers::run_stdio_session;
+use kalix::misc::model_validation::ValidationSeverity;
+use kalix::misc::graph_export::GraphFormat;
+use kalix::misc::output_statistics::{statistics_csv, statistics_table};
+use kalix::misc::provenance::Provenance;
+use kalix::misc::regionalisation::{CatchmentTable, TransferMethod};
+use kalix::numerical::opt::OptimisationConfig;
+use kalix::run::{calibrate_region, report_from_file, summarise_series_file};
+use kalix::numerical::opt::regional::catchment_parameters;
+use kalix::misc::node_timing::NodeTimer;
+use kalix::nodes::Node;
 use std::fs;
 use std::io::{self, Read, Write};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -23,6 +36,16 @@ struct Cli {
 enum Commands {
     NewSession {
 
+    },
+    /// Serve the session protocol over a local TCP socket or WebSocket
+    #[cfg(feature = "network")]
+    Serve {
+        /// Address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:7878")]
+        address: String,
+        /// Use WebSocket text frames instead of line-delimited JSON over TCP
+        #[arg(short, long)]
+        websocket: bool,
     },
     /// Run performance tests
     Test {
@@ -36,11 +59,12 @@ enum Commands {
     /// Return API spec as JSON on STDOUT
     GetAPI,
     /// Run a simulation
-    #[command(visible_alias = "sim")]
+    #[command(visible_aliases = ["sim", "run"])]
     Simulate {
         /// Path to the model file
         model_file: String,
-        /// Path to the output file
+        /// Path to the output file. The extension picks the layout: .csv, .tsv, .ssv (semicolons
+        /// and decimal commas), .res.csv (eWater Source results) or .pxt/.pxb (Pixie)
         #[arg(short, long)]
         output_file: Option<String>,
         /// Mass balance
@@ -52,15 +76,44 @@ enum Commands {
         /// Report execution time profile
         #[arg(short = 'p', long)]
         profile: bool,
+        /// Write the time each node took to this file as flame graph JSON (implies --profile)
+        #[arg(long)]
+        profile_json: Option<String>,
+        /// Comma-separated series to record, replacing the model's [outputs]
+        #[arg(long)]
+        outputs: Option<String>,
+        /// Simulation period as "start, end" (e.g. "1990-01-01, 1999-12-31")
+        #[arg(long)]
+        period: Option<String>,
+        /// Record simulation events (spills, shortfalls, etc.) and write them to this CSV file
+        #[arg(long)]
+        events: Option<String>,
+        /// Scenario overlay file applied over the model file (repeat to apply several in order)
+        #[arg(long = "overlay")]
+        overlays: Vec<String>,
+        /// Parameter file (.csv of parameter,value rows, or .ini) applied over the model
+        #[arg(long)]
+        params: Option<String>,
+        /// Also write the output statistics table to this CSV file
+        #[arg(long)]
+        stats: Option<String>,
+        /// Don't print the output statistics table after the run
+        #[arg(long)]
+        no_stats: bool,
+        /// Record the run's provenance (version, file hashes, time and command line) and
+        /// fingerprint with the output file
+        #[arg(long)]
+        provenance: bool,
     },
     /// Run parameter optimisation
-    #[command(visible_alias = "opt", alias = "optimize")]
+    #[command(visible_aliases = ["opt", "calibrate"], alias = "optimize")]
     Optimise {
         /// Path to the optimisation configuration file (.ini)
         config_file: String,
         /// Path to the model file (.ini). Overrides model_file in config if specified
         model_file: Option<String>,
-        /// Path to save the optimised model file (.ini)
+        /// Path to save the optimised model file (.ini). For a regional calibration, a directory
+        /// receiving each catchment's model
         #[arg(short = 's', long = "save-model")]
         save_model: Option<String>,
         /// Suppress terminal output and plotting
@@ -72,6 +125,106 @@ enum Commands {
         /// Report execution time profile
         #[arg(short = 'p', long)]
         profile: bool,
+        /// Number of threads for parallel evaluation. Overrides n_threads in config
+        #[arg(short = 't', long)]
+        threads: Option<usize>,
+        /// Calibration period as "start, end". Overrides calibration_period in config
+        #[arg(long)]
+        period: Option<String>,
+        /// Path to save the optimised parameter values (.csv), for use with `simulate --params`.
+        /// For a regional calibration, a directory receiving each catchment's parameters
+        #[arg(long = "save-params")]
+        save_params: Option<String>,
+    },
+    /// Check a model for problems without running it
+    Validate {
+        /// Path to the model file
+        model_file: String,
+    },
+    /// Rewrite a model file (.ini) with canonical spacing, property order and aligned tables,
+    /// keeping its comments
+    Fmt {
+        /// Path to the model file
+        model_file: String,
+        /// Only report whether the file is already formatted, exiting with an error if not
+        #[arg(long)]
+        check: bool,
+    },
+    /// Convert a model file to another format (.ini or .json, chosen by extension)
+    Convert {
+        /// Path to the model file
+        model_file: String,
+        /// Path to the converted file
+        output_file: String,
+    },
+    /// Export the node-link network for viewing in other tools
+    #[command(visible_alias = "graph")]
+    ExportGraph {
+        /// Path to the model file
+        model_file: String,
+        /// Path to the exported file (.dot or .gv for Graphviz, .geojson or .json for GeoJSON)
+        output_file: String,
+    },
+    /// Run a model and write an HTML report of the run: model summary, mass balance, gauge
+    /// statistics and figures, and parameters
+    Report {
+        /// Path to the model file
+        model_file: String,
+        /// Path to the HTML report
+        output_file: String,
+        /// Calibrate the model with this optimisation configuration (.ini) before the run
+        #[arg(long)]
+        calibrate: Option<String>,
+    },
+    /// Compare two model files node by node: added and removed nodes, changed properties and
+    /// re-routed links
+    Diff {
+        /// Path to the first (e.g. base) model file
+        model_file_a: String,
+        /// Path to the second (e.g. scenario) model file
+        model_file_b: String,
+    },
+    /// Give ungauged Gr4j and Sacramento nodes parameters transferred from calibrated (donor)
+    /// catchments with similar attributes
+    Regionalise {
+        /// Path to the model file
+        model_file: String,
+        /// Path to the regionalised model file
+        output_file: String,
+        /// CSV of catchment attributes: a node name then one column per attribute
+        #[arg(long)]
+        attributes: String,
+        /// CSV of calibrated donor parameters: a node name then one column per parameter
+        #[arg(long)]
+        donors: String,
+        /// Transfer method: nearest (mean of the nearest donors) or regression
+        #[arg(long, default_value = "nearest")]
+        method: String,
+        /// Number of donors averaged by the nearest method
+        #[arg(long, default_value_t = 1)]
+        neighbours: usize,
+    },
+    /// Estimate the residual (unattributed) inflow between two gauges from their observed
+    /// flows, and give it to the downstream gauge as its residual input
+    Residual {
+        /// Path to the model file
+        model_file: String,
+        /// Path to the model file with the residual
+        output_file: String,
+        /// Gauge whose observed flow enters the reach
+        #[arg(long)]
+        upstream: String,
+        /// Gauge at the end of the reach
+        #[arg(long)]
+        downstream: String,
+        /// Path of the CSV file the residual is written to, relative to the model file
+        #[arg(long)]
+        csv: String,
+    },
+    /// Print summary statistics for each series in an output file (.csv, .pxb or .pxt)
+    Stats {
+        /// Path to the output file
+        file: String,
     },
 }
 
@@ -86,6 +239,16 @@ fn main() {
                 std::process::exit(1);
             }
         }
+        #[cfg(feature = "network")]
+        Commands::Serve { address, websocket } => {
+            use kalix::apis::stdio::network::{run_network_session, NetworkProtocol};
+            let protocol = if websocket { NetworkProtocol::WebSocket } else { NetworkProtocol::Tcp };
+            eprintln!("Listening on {}", address);
+            if let Err(e) = run_network_session(address.as_str(), protocol) {
+                eprintln!("Session error: {}", e);
+                std::process::exit(1);
+            }
+        }
         Commands::Test { sim_duration_seconds, new_session } => {
             if let Some(_) = new_session {
                 println!("KALIX_SESSION_READY");
@@ -120,19 +283,26 @@ fn main() {
                 None => {
                     println!("Running performance tests...");
                     benchmarks::bench1();
+                    benchmarks::bench_network(5);
+                    benchmarks::bench_catchments(5);
+                    benchmarks::bench_unit_hydrographs(10_000_000);
+                    benchmarks::bench_model_clone(50);
                     println!("Performance tests completed!");
                 }
             }
         }
         Commands::Simulate { model_file, output_file,
-            mass_balance, verify_mass_balance, profile } => {
+            mass_balance, verify_mass_balance, profile, profile_json, outputs, period, events, overlays, params, stats, no_stats, provenance } => {
 
             let total_start = Instant::now();
 
             // Load + configure
             let load_start = Instant::now();
             println!("Loading model file: {}", model_file);
-            let mut m = match IniModelIO::new().read_model_file(model_file.as_str()) {
+            for overlay in &overlays {
+                println!("Applying overlay: {}", overlay);
+            }
+            let mut m = match IniModelIO::new().read_model_file_with_overlays(model_file.as_str(), &overlays) {
                 Ok(model) => model,
                 Err(s) => {
                     eprintln!("Error: {}", s);
@@ -140,32 +310,92 @@ fn main() {
                 }
             };
 
+            // Command line overrides
+            if let Some(period) = period {
+                match OptimisationConfig::parse_period(&period) {
+                    Ok((start, end)) => {
+                        m.configuration.specified_sim_start_timestamp = Some(start);
+                        m.configuration.specified_sim_end_timestamp = Some(end);
+                    }
+                    Err(e) => {
+                        eprintln!("Error: Invalid --period: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if let Some(params) = params {
+                println!("Applying parameters: {}", params);
+                if let Err(e) = read_parameter_file(&params).and_then(|values| m.apply_parameters(&values)) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            if let Some(outputs) = outputs {
+                m.outputs = outputs.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+            }
+
             println!("Running simulation...");
             if let Err(e) = m.configure() {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
+            println!("{}", m.simulation_window.describe());
             let load_time = load_start.elapsed();
 
             // Run
+            let profile = profile || profile_json.is_some();
+            if profile {
+                m.node_timer = Some(Arc::new(NodeTimer::new(m.nodes.len())));
+            }
+            m.data_cache.events.enabled = events.is_some();
             let sim_start = Instant::now();
             if let Err(e) = m.run() {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
             let sim_time = sim_start.elapsed();
+            let (substituted, propagated) = (m.run_log().count("input_nan_substituted"), m.run_log().count("input_nan"));
+            if substituted + propagated > 0 {
+                println!("Missing input values: {} replaced by 0, {} passed on (see the run log)", substituted, propagated);
+            }
 
             // Output file
             let output_start = Instant::now();
             match output_file {
                 Some(f) => {
-                    match m.write_outputs(f.as_str()) {
+                    let written = if provenance {
+                        Provenance::new(&m)
+                            .and_then(|p| p.with_model_file(&model_file))
+                            .map(|p| p.with_command_line(&std::env::args().collect::<Vec<_>>().join(" ")))
+                            .and_then(|p| {
+                                println!("Run fingerprint: {}", p.fingerprint());
+                                m.write_outputs_with_provenance(f.as_str(), &p)
+                            })
+                    } else {
+                        m.write_outputs(f.as_str())
+                    };
+                    match written {
                         Ok(_) => { }
                         Err(s) => eprintln!("{}", s)
                     }
+                    match m.write_run_log_alongside(f.as_str()) {
+                        Ok(Some(log_path)) => println!("Run log ({} entries) written to {}", m.run_log().entries().len(), log_path),
+                        Ok(None) => { }
+                        Err(s) => eprintln!("{}", s)
+                    }
                 }
                 None => {} // TODO: do we want to look at defaulting to some output here?
             }
+            if let Some(f) = events {
+                let table = m.events();
+                match fs::write(&f, table.to_csv_string(&table.filter(None, None, None, None))) {
+                    Ok(_) => println!("{} events written to {}", table.len(), f),
+                    Err(s) => eprintln!("Error: {}", s)
+                }
+            }
 
             // Mass balance reporting and verification
             let mut mb_report = String::new();
@@ -204,6 +434,18 @@ fn main() {
                 }
                 None => {}
             }
+
+            // Output statistics, as a quick check of the results
+            let output_stats = m.output_statistics();
+            if !no_stats && !output_stats.is_empty() {
+                println!("\n{}", statistics_table(&output_stats));
+            }
+            if let Some(f) = stats {
+                match fs::write(&f, statistics_csv(&output_stats)) {
+                    Ok(_) => println!("Output statistics written to {}", f),
+                    Err(s) => eprintln!("Error: {}", s)
+                }
+            }
             let output_time = output_start.elapsed();
 
             let total_time = total_start.elapsed();
@@ -219,11 +461,33 @@ fn main() {
                 println!("  Misc:            {:>10.3} ms", misc_time.as_secs_f64() * 1000.0);
                 println!("  ─────────────────────────────");
                 println!("  Total time:      {:>10.3} ms", total_time.as_secs_f64() * 1000.0);
+
+                if let Some(timer) = &m.node_timer {
+                    let names: Vec<String> = m.nodes.iter().map(|n| n.get_name().to_string()).collect();
+                    let types: Vec<String> = m.nodes.iter().map(|n| n.get_type_as_string()).collect();
+                    let report = timer.report(&names, &types, 10);
+                    println!("\n=== Node Flow-Phase Time ===");
+                    for (node_type, time) in &report.by_type {
+                        println!("  {:<20} {:>10.3} ms", node_type, time.as_secs_f64() * 1000.0);
+                    }
+                }
+                if let Some(node_profile) = &m.last_profile {
+                    println!("\n=== Slowest Nodes ===");
+                    print!("{}", node_profile.to_table(Some(10)));
+                    if let Some(path) = &profile_json {
+                        let json = serde_json::to_string_pretty(&node_profile.to_flame_graph()).unwrap_or_default();
+                        match std::fs::write(path, json) {
+                            Ok(_) => println!("Node profile written to {}", path),
+                            Err(e) => eprintln!("Error: Failed to write the node profile to '{}': {}", path, e),
+                        }
+                    }
+                }
             }
         }
-        Commands::Optimise { config_file, model_file, save_model, quiet, report_frequency, profile } => {
+        Commands::Optimise { config_file, model_file, save_model, quiet, report_frequency, profile,
+            threads, period, save_params } => {
             use kalix::numerical::opt::{
-                OptimisationConfig, OptimisationProblem,
+                OptimisationProblem,
                 create_optimizer_with_callback, OptimizationProgress, Optimisable
             };
             use kalix::io::optimisation_config_io::load_observed_for_term;
@@ -237,7 +501,7 @@ fn main() {
 
             // Load optimisation configuration
             println!("Loading optimisation configuration: {}", config_file);
-            let config = match OptimisationConfig::from_file(&config_file) {
+            let mut config = match OptimisationConfig::from_file(&config_file) {
                 Ok(cfg) => cfg,
                 Err(e) => {
                     eprintln!("Error loading optimisation config: {}", e);
@@ -245,6 +509,20 @@ fn main() {
                 }
             };
 
+            // Command line overrides
+            if let Some(n_threads) = threads {
+                config.n_threads = n_threads;
+            }
+            if let Some(period) = period {
+                match OptimisationConfig::parse_period(&period) {
+                    Ok(p) => config.calibration_period = Some(p),
+                    Err(e) => {
+                        eprintln!("Error: Invalid --period: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
             if !quiet {
                 println!("Objective expression: {}", config.objective_expression);
                 println!("Terms ({}):", config.terms.len());
@@ -259,6 +537,84 @@ fn main() {
                 println!("Number of parameters: {}", config.parameter_config.n_genes());
             }
 
+            // A config with [catchment.NAME] sections calibrates its catchment models together
+            if !config.catchments.is_empty() {
+                if model_file.is_some() {
+                    eprintln!("Error: a regional calibration takes each model from its [catchment.NAME] section, not the command line");
+                    std::process::exit(1);
+                }
+                let plot = Arc::new(Mutex::new(
+                    OptimisationPlot::new("KALIX//OPTIMISER", config.termination_evaluations, 50, 12)
+                        .with_parameter_names(config.parameter_config.gene_names())
+                ));
+                let plot_clone = Arc::clone(&plot);
+                let last_report_eval = std::sync::atomic::AtomicUsize::new(0);
+                let progress_callback = (!quiet).then(|| Box::new(move |progress: &OptimizationProgress| {
+                    let last = last_report_eval.load(std::sync::atomic::Ordering::Relaxed);
+                    if (progress.n_evaluations / report_frequency) > (last / report_frequency) {
+                        last_report_eval.store(progress.n_evaluations, std::sync::atomic::Ordering::Relaxed);
+                        let mut plot = plot_clone.lock().unwrap();
+                        plot.update_from_progress(progress);
+                        print!("{}", plot.render());
+                        io::stdout().flush().unwrap();
+                    }
+                }) as Box<dyn Fn(&OptimizationProgress) + Send + Sync>);
+
+                println!("\n=== Starting Regional Calibration ===");
+                for catchment in &config.catchments {
+                    println!("Catchment '{}': {}", catchment.name, catchment.model_file);
+                }
+                let outcome = match calibrate_region(&config, progress_callback) {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                println!("\n\n=== Optimisation Complete ===");
+                println!("Status: {}", if outcome.success { "SUCCESS" } else { "FAILED" });
+                println!("Message: {}", outcome.message);
+                println!("Function evaluations: {}", outcome.n_evaluations);
+                println!("Best objective value: {:.6}", outcome.best_objective);
+                println!("\nOptimized Parameters (physical values):");
+                for (target, value) in &outcome.parameters {
+                    println!("  {} = {:.6}", target, value);
+                }
+                for (label, metrics) in [("Calibration", &outcome.calibration_metrics), ("Validation", &outcome.validation_metrics)] {
+                    if let Some(metrics) = metrics {
+                        println!("\n{} metrics:", label);
+                        println!("  objective = {:.6}", metrics.objective);
+                        for (term, value) in &metrics.terms {
+                            println!("  {} = {:.6}", term, value);
+                        }
+                    }
+                }
+
+                // Each catchment's model and parameters are saved as NAME.ini and NAME.csv
+                let ini_io = IniModelIO::new();
+                for (dir, extension) in [(&save_model, "ini"), (&save_params, "csv")] {
+                    let Some(dir) = dir else { continue };
+                    if let Err(e) = fs::create_dir_all(dir) {
+                        eprintln!("Error creating directory '{}': {}", dir, e);
+                        std::process::exit(1);
+                    }
+                    for (name, model) in &outcome.models {
+                        let path = std::path::Path::new(dir).join(format!("{}.{}", name, extension));
+                        let content = match extension {
+                            "ini" => ini_io.model_to_string(model),
+                            _ => parameters_to_csv(&catchment_parameters(name, &outcome.parameters)),
+                        };
+                        match fs::write(&path, content) {
+                            Ok(_) => println!("Catchment '{}' written to: {}", name, path.display()),
+                            Err(e) => eprintln!("Error writing {}: {}", path.display(), e),
+                        }
+                    }
+                }
+                println!("\nDone!");
+                return;
+            }
+
             // Determine model file: CLI argument takes precedence over config file
             let model_file_path = match model_file {
                 Some(ref path) => path,
@@ -296,9 +652,10 @@ fn main() {
                 }
                 comparisons.push(ComparisonPair {
                     name: term.name.clone(),
-                    observed: observed.timeseries,
+                    observed: Arc::unwrap_or_clone(observed.timeseries),
                     simulated_series_name: term.simulated_series.clone(),
                     statistic: term.statistic.clone(),
+                    transforms: term.transforms.clone(),
                 });
             }
 
@@ -310,12 +667,20 @@ fn main() {
                 }
             };
 
-            let problem = OptimisationProblem::new(
+            let objectives = match config.parse_objective_expressions() {
+                Ok(o) => o,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let mut problem = OptimisationProblem::new(
                 model,
                 config.parameter_config.clone(),
                 comparisons,
                 expression,
-            );
+            ).with_objectives(objectives).with_period(config.calibration_period);
 
             println!("\n=== Starting Optimisation ===");
             println!("Algorithm: {}", config.algorithm.name());
@@ -324,9 +689,17 @@ fn main() {
             println!("Parameters to optimise: {}", problem.config.n_genes());
             println!("Objective: minimize ({})\n", config.objective_expression);
 
+            if let Some(trace_file) = &config.trace_file {
+                if let Err(e) = problem.start_trace(trace_file) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+
             // Create optimisation plot
             let opt_plot = Arc::new(Mutex::new(
                 OptimisationPlot::new("KALIX//OPTIMISER", config.termination_evaluations, 50, 12)
+                    .with_parameter_names(problem.config.gene_names())
             ));
 
             // Create progress callback for terminal plot
@@ -352,7 +725,7 @@ fn main() {
             };
 
             // Create optimizer with progress callback configured
-            let optimizer = match create_optimizer_with_callback(&config, progress_callback) {
+            let optimizer = match create_optimizer_with_callback(&config, problem.trace_progress(progress_callback)) {
                 Ok(opt) => opt,
                 Err(e) => {
                     eprintln!("Error creating optimizer: {}", e);
@@ -365,6 +738,9 @@ fn main() {
             let mut problem_mut = problem;  // Make mutable for optimisation
             let result = optimizer.optimize(&mut problem_mut, None);
             let opt_time = result.elapsed;
+            if let Err(e) = problem_mut.finish_trace() {
+                eprintln!("Warning: {}", e);
+            }
 
             // Render final plot
             if !quiet {
@@ -394,6 +770,26 @@ fn main() {
                 println!("  {} = {:.6}", target, value);
             }
 
+            // Re-evaluate the best parameter set over the calibration and validation periods
+            if result.success && (config.calibration_period.is_some() || config.validation_period.is_some()) {
+                let mut periods = vec![("Calibration", config.calibration_period)];
+                if let Some(period) = config.validation_period {
+                    periods.push(("Validation", Some(period)));
+                }
+                for (label, period) in periods {
+                    match problem_mut.period_metrics(&result.best_params, period) {
+                        Ok(metrics) => {
+                            println!("\n{} metrics:", label);
+                            println!("  objective = {:.6}", metrics.objective);
+                            for (term, value) in &metrics.terms {
+                                println!("  {} = {:.6}", term, value);
+                            }
+                        }
+                        Err(e) => eprintln!("Warning: Failed to evaluate {} period: {}", label.to_lowercase(), e),
+                    }
+                }
+            }
+
             // Apply best parameters to model one final time to ensure it's in the optimal state
             if let Err(e) = problem_mut.set_params(&result.best_params) {
                 eprintln!("Warning: Failed to apply final parameters: {}", e);
@@ -409,6 +805,12 @@ fn main() {
                     Err(e) => eprintln!("Error writing model: {}", e),
                 }
             }
+            if let Some(params_path) = save_params {
+                match fs::write(&params_path, parameters_to_csv(&param_values)) {
+                    Ok(_) => println!("Optimized parameters written to: {}", params_path),
+                    Err(e) => eprintln!("Error writing parameters: {}", e),
+                }
+            }
 
             // Write results to file if specified
             if let Some(output_path) = config.output_file {
@@ -455,6 +857,191 @@ fn main() {
                 println!("  Total time:        {:>10.3} ms", total_time.as_secs_f64() * 1000.0);
             }
         }
+        Commands::Validate { model_file } => {
+            let m = match IniModelIO::new().read_model_file(model_file.as_str()) {
+                Ok(model) => model,
+                Err(s) => {
+                    eprintln!("Error: {}", s);
+                    std::process::exit(1);
+                }
+            };
+            let report = m.validate();
+            for issue in &report.issues {
+                let severity = match issue.severity {
+                    ValidationSeverity::Error => "error",
+                    ValidationSeverity::Warning => "warning",
+                };
+                match issue.line_number {
+                    Some(line) => println!("{} [{}] line {}: {}", severity, issue.code, line, issue.message),
+                    None => println!("{} [{}]: {}", severity, issue.code, issue.message),
+                }
+            }
+            println!("{} error(s), {} warning(s)", report.errors().len(), report.warnings().len());
+            if !report.is_valid() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Fmt { model_file, check } => {
+            let content = match fs::read_to_string(&model_file) {
+                Ok(content) => content,
+                Err(e) => {
+                    eprintln!("Error reading {}: {}", model_file, e);
+                    std::process::exit(1);
+                }
+            };
+            let formatted = match format_ini(&content) {
+                Ok(formatted) => formatted,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if formatted == content {
+                println!("{} is already formatted", model_file);
+            } else if check {
+                println!("{} is not formatted", model_file);
+                std::process::exit(1);
+            } else if let Err(e) = fs::write(&model_file, formatted) {
+                eprintln!("Error writing {}: {}", model_file, e);
+                std::process::exit(1);
+            } else {
+                println!("Formatted {}", model_file);
+            }
+        }
+        Commands::Convert { model_file, output_file } => {
+            let ini_io = IniModelIO::new();
+            let m = match ini_io.read_model_file(model_file.as_str()) {
+                Ok(model) => model,
+                Err(s) => {
+                    eprintln!("Error: {}", s);
+                    std::process::exit(1);
+                }
+            };
+            let lower = output_file.to_ascii_lowercase();
+            let content = if lower.ends_with(".json") {
+                serde_json::to_string_pretty(&ini_io.model_to_json(&m)).unwrap()
+            } else if lower.ends_with(".ini") {
+                ini_io.model_to_string(&m)
+            } else {
+                eprintln!("Error: Unsupported output format '{}'. Use .ini or .json", output_file);
+                std::process::exit(1);
+            };
+            if let Err(e) = fs::write(&output_file, content) {
+                eprintln!("Error writing {}: {}", output_file, e);
+                std::process::exit(1);
+            }
+            println!("Model written to: {}", output_file);
+        }
+        Commands::ExportGraph { model_file, output_file } => {
+            let format = match GraphFormat::from_path(&output_file) {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let m = match IniModelIO::new().read_model_file(model_file.as_str()) {
+                Ok(model) => model,
+                Err(s) => {
+                    eprintln!("Error: {}", s);
+                    std::process::exit(1);
+                }
+            };
+            match fs::write(&output_file, m.export_graph(format)) {
+                Ok(_) => println!("{} nodes and {} links written to {}", m.nodes.len(), m.links.len(), output_file),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Report { model_file, output_file, calibrate } => {
+            if calibrate.is_some() {
+                println!("Calibrating...");
+            }
+            match report_from_file(&model_file, &output_file, calibrate.as_deref()) {
+                Ok(()) => println!("Report written to: {}", output_file),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Stats { file } => {
+            let summaries = match summarise_series_file(&file) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let width = summaries.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max(6);
+            println!("{:<width$} {:>8} {:>14} {:>14} {:>14} {:>14}", "series", "count", "mean", "min", "max", "sum");
+            for (name, s) in &summaries {
+                println!("{:<width$} {:>8} {:>14.6} {:>14.6} {:>14.6} {:>14.6}", name, s.count, s.mean, s.min, s.max, s.sum);
+            }
+        }
+        Commands::Diff { model_file_a, model_file_b } => {
+            let io = IniModelIO::new();
+            let (a, b) = match (io.read_model_file(&model_file_a), io.read_model_file(&model_file_b)) {
+                (Ok(a), Ok(b)) => (a, b),
+                (Err(s), _) | (_, Err(s)) => {
+                    eprintln!("Error: {}", s);
+                    std::process::exit(1);
+                }
+            };
+            print!("{}", a.diff(&b).to_text());
+        }
+        Commands::Regionalise { model_file, output_file, attributes, donors, method, neighbours } => {
+            let transfers = TransferMethod::from_name(&method).and_then(|method| {
+                let attributes = CatchmentTable::read(&attributes)?;
+                let donors = CatchmentTable::read(&donors)?;
+                let mut m = IniModelIO::new().read_model_file(&model_file)?;
+                let transfers = m.regionalise(&attributes, &donors, method, neighbours)?;
+                fs::write(&output_file, IniModelIO::new().model_to_string(&m))
+                    .map_err(|e| format!("Error writing model: {}", e))?;
+                Ok(transfers)
+            });
+            match transfers {
+                Ok(transfers) => {
+                    for t in &transfers {
+                        let values: Vec<String> = t.parameters.iter().map(|(p, v)| format!("{} = {:.6}", p, v)).collect();
+                        match t.donors.is_empty() {
+                            true => println!("{}: {}", t.node, values.join(", ")),
+                            false => println!("{} (from {}): {}", t.node, t.donors.join(", "), values.join(", ")),
+                        }
+                    }
+                    println!("Regionalised model written to: {}", output_file);
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Residual { model_file, output_file, upstream, downstream, csv } => {
+            let estimate = IniModelIO::new().read_model_file(&model_file).and_then(|mut m| {
+                let estimate = m.estimate_residual(&upstream, &downstream)?;
+                m.apply_residual(&estimate, &csv)?;
+                fs::write(&output_file, IniModelIO::new().model_to_string(&m))
+                    .map_err(|e| format!("Error writing model: {}", e))?;
+                Ok(estimate)
+            });
+            match estimate {
+                Ok(estimate) => {
+                    let values = &estimate.series.values;
+                    let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+                    println!("Residual at '{}': mean {:.3} over {} steps ({} without observations, set to 0)",
+                             estimate.downstream_gauge, mean, values.len(), estimate.n_missing);
+                    println!("Residual written to: {}", csv);
+                    println!("Model written to: {}", output_file);
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         Commands::GetAPI => {
             let command = Cli::command();
             let api_description = describe_cli_api(&command);
@@ -111,5 +111,25 @@ fn main() {
         print!("{}", anim_plot.render());
     }
 
+    // The same line drawn in braille, with 2×4 points per cell
+    println!("\n\nHigh resolution (braille) lines:\n");
+    let mut braille_plot = TerminalPlot::builder()
+        .title("KALIX//OPTIMISER")
+        .x_label("evals")
+        .y_label("Objective Function")
+        .width(50)
+        .height(12)
+        .x_range(0.0, 5000.0)
+        .y_range(0.5, 2.5)
+        .color_scheme(ColorScheme::electric_grid())
+        .resolution(Resolution::Braille)
+        .build();
+    braille_plot.add_line(Line {
+        points: best_line_points,
+        style: LineStyle::Dots,
+        color: Some(Color::BrightMagenta),
+    });
+    println!("{}", braille_plot.render());
+
     println!("\n✓ Demo complete!");
 }
@@ -0,0 +1,123 @@
+use std::sync::Mutex;
+use crate::io::compression::gorilla::{GorillaCompressor, TimeValueDouble};
+
+/// Number of values in each compressed block
+pub const BLOCK_SIZE: usize = 1024;
+
+/// Values of a recorded series held in Gorilla-compressed blocks, for long runs with many
+/// outputs where keeping every result as a plain `Vec<f64>` would use too much memory.
+///
+/// Values are recorded in time order. Each time another `BLOCK_SIZE` values have been recorded
+/// they are compressed into a block. Expressions don't read the series here during a run: the
+/// data cache keeps the values they read back to in a ring of their own. Values in earlier
+/// blocks are decompressed when they are read, and the last block decompressed is kept, so
+/// reading through a series decompresses each block once.
+#[derive(Default)]
+pub struct CompressedSeries {
+    blocks: Vec<Vec<u8>>,
+    current: Vec<f64>,
+    last_read: Mutex<Option<(usize, Vec<f64>)>>,
+}
+
+impl Clone for CompressedSeries {
+    fn clone(&self) -> Self {
+        Self {
+            blocks: self.blocks.clone(),
+            current: self.current.clone(),
+            last_read: Mutex::default(),
+        }
+    }
+}
+
+impl CompressedSeries {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn from_values(values: &[f64]) -> Self {
+        let mut series = Self::new();
+        for &value in values {
+            series.push(value);
+        }
+        series
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len() * BLOCK_SIZE + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate memory used by the values, in bytes
+    pub fn memory_bytes(&self) -> usize {
+        self.blocks.iter().map(|b| b.len()).sum::<usize>()
+            + self.current.capacity() * size_of::<f64>()
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.current.push(value);
+        if self.current.len() == BLOCK_SIZE {
+            let points: Vec<TimeValueDouble> = self.current.iter().enumerate()
+                .map(|(i, &v)| TimeValueDouble::new(i as u64, v))
+                .collect();
+            let block = GorillaCompressor::new(1).compress_double(&points)
+                .expect("Compressing to memory cannot fail");
+            self.blocks.push(block);
+            self.current.clear();
+        }
+    }
+
+    /// Sets the value at `step`, filling any steps before it with NaN. Setting a step in a
+    /// block that has already been compressed (e.g. when a new run starts) discards the
+    /// values from that step on.
+    pub fn set(&mut self, step: usize, value: f64) {
+        let current_start = self.blocks.len() * BLOCK_SIZE;
+        if step >= current_start && step < self.len() {
+            self.current[step - current_start] = value;
+            return;
+        }
+        if step < current_start {
+            let mut values = self.to_values();
+            values.truncate(step);
+            *self = Self::from_values(&values);
+        }
+        while self.len() < step {
+            self.push(f64::NAN);
+        }
+        self.push(value);
+    }
+
+    /// The value at `step`, which must be less than `len()`
+    pub fn get(&self, step: usize) -> f64 {
+        let block = step / BLOCK_SIZE;
+        if block == self.blocks.len() {
+            return self.current[step % BLOCK_SIZE];
+        }
+        let mut last_read = self.last_read.lock().unwrap_or_else(|e| e.into_inner());
+        if last_read.as_ref().is_none_or(|(b, _)| *b != block) {
+            *last_read = Some((block, self.decompress_block(block)));
+        }
+        last_read.as_ref().map_or(f64::NAN, |(_, values)| values[step % BLOCK_SIZE])
+    }
+
+    pub fn to_values(&self) -> Vec<f64> {
+        let mut values = Vec::with_capacity(self.len());
+        for block in 0..self.blocks.len() {
+            values.extend(self.decompress_block(block));
+        }
+        values.extend_from_slice(&self.current);
+        values
+    }
+
+    fn decompress_block(&self, block: usize) -> Vec<f64> {
+        GorillaCompressor::new(1).decompress_double(&self.blocks[block])
+            .expect("Compressed block is corrupt")
+            .into_iter()
+            .map(|p| p.value)
+            .collect()
+    }
+}
@@ -86,6 +86,14 @@ impl ConstantsCache {
         Ok(())
     }
 
+    /// List the names of all constants that have been referenced but never assigned a value.
+    pub fn list_unassigned_names(&self) -> Vec<String> {
+        (0..self.len())
+            .filter(|&i| !self.is_assigned[i])
+            .map(|i| self.names[i].clone())
+            .collect()
+    }
+
     /// Get the value of a constant by name
     pub fn get_value_by_name(&self, name: &str) -> Result<f64, String> {
         match self.name_idx_map.get(name) {
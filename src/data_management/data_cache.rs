@@ -1,6 +1,64 @@
-﻿use crate::data_management::constants_cache::ConstantsCache;
+﻿use std::borrow::Cow;
+use crate::data_management::compressed_series::CompressedSeries;
+use crate::data_management::constants_cache::ConstantsCache;
+use crate::data_management::tables_cache::TablesCache;
+use crate::data_management::precomputed_cache::PrecomputedCache;
+use crate::data_management::events::{EventKind, EventTable};
+use crate::data_management::run_log::{LogLevel, RunLog};
+use crate::misc::configuration::DEFAULT_NEGATIVE_TOLERANCE;
+use crate::model_inputs::NanPolicy;
 use crate::tid::utils::{u64_to_year_month_day_and_seconds};
+use crate::tid::water_year::WaterYear;
 use crate::timeseries::Timeseries;
+use std::sync::Arc;
+
+/// How the values of a series are recorded during a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recording {
+    /// Every value, in `series[idx].values` (or shared with clones of the cache, for inputs)
+    Full,
+    /// Every value, compressed
+    Compressed,
+    /// Every value, in single precision (calculations are still done in double precision)
+    Single,
+    /// Only the current value and this many before it
+    Recent(usize),
+}
+
+#[derive(Clone, Default)]
+enum SeriesStorage {
+    #[default]
+    Plain,
+    Compressed(CompressedSeries),
+    Single(Vec<f32>),
+    /// Only the current value and this many before it, in `series[idx].values`
+    Recent(usize),
+    /// Values that do not change during runs (e.g. input data), shared between clones of the
+    /// cache so that copies for parallel runs do not copy them. Writing copies them first.
+    Shared(Arc<Vec<f64>>),
+}
+
+/// Where in `series[idx].values` a series is read and written during a run
+#[derive(Clone, Copy)]
+struct Window {
+    /// Applied to the step to index the values: `usize::MAX` for a series recorded there in
+    /// full, one less than the length of the ring for the others
+    mask: usize,
+    /// Steps from here on are outside the data (`usize::MAX` for a series recorded in full,
+    /// as the length of its values is the limit)
+    end: usize,
+    /// One more than the step last written this run, or 0 if none has been
+    written: usize,
+    /// How far back and ahead of the current step the series is read (see `set_reads()`)
+    lookback: usize,
+    lookahead: usize,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self { mask: usize::MAX, end: usize::MAX, written: 0, lookback: 0, lookahead: 0 }
+    }
+}
 
 #[derive(Default)]
 #[derive(Clone)]
@@ -8,6 +66,10 @@ pub struct DataCache {
     pub series: Vec<Timeseries>,
     pub series_name: Vec<String>,
     pub is_critical: Vec<bool>,
+    storage: Vec<SeriesStorage>, // Where the values of each series are held (see `set_recording()`)
+    windows: Vec<Window>,
+    archived: Vec<usize>, // Series whose values are archived each timestep (see `archive()`)
+    shared: Vec<usize>, // Series whose shared values are brought in each timestep (see `bring_in()`)
     pub current_step: usize,
     pub start_timestamp: u64,
     pub current_timestamp: u64,
@@ -16,6 +78,24 @@ pub struct DataCache {
     // Constants cache
     pub constants: ConstantsCache,
 
+    // Tables for lookup() in expressions
+    pub tables: TablesCache,
+
+    // Expressions of input data evaluated for the whole run before it starts
+    pub precomputed: PrecomputedCache,
+
+    // Diagnostics and events raised by model components during the run
+    pub log: RunLog,
+    pub events: EventTable,
+
+    // What nodes do with a missing input value, unless set for the series (see `nan_policy_of()`)
+    pub nan_policy: NanPolicy,
+    series_nan_policy: Vec<Option<NanPolicy>>,
+
+    // How negative flows and volumes are guarded against (see `guard_negative()`)
+    pub strict: bool,
+    pub negative_tolerance: f64,
+
     // These vars for model components (incl nodes) to use if they need to know the date
     timestamp_year: i32,
     timestamp_month: u32,
@@ -75,6 +155,9 @@ impl DataCache {
     pub fn new() -> DataCache {
         DataCache {
             constants: ConstantsCache::new(),
+            tables: TablesCache::new(),
+            precomputed: PrecomputedCache::new(),
+            negative_tolerance: DEFAULT_NEGATIVE_TOLERANCE,
             ..Default::default()
         }
     }
@@ -88,6 +171,11 @@ impl DataCache {
         self.series = vec![];
         self.series_name = vec![];
         self.is_critical = vec![];
+        self.storage = vec![];
+        self.windows = vec![];
+        self.archived = vec![];
+        self.shared = vec![];
+        self.series_nan_policy = vec![];
 
         // Set up the timing
         self.start_timestamp = start_timestamp;
@@ -117,10 +205,19 @@ impl DataCache {
     This updates:
       - current_step which counts the model steps (from 0)
       - current_timestamp
+      - the values of series not recorded in full: those written at the step being left are
+        archived, and shared values readers need at the new step are brought in
      */
     pub fn set_current_step(&mut self, value: usize) {
+        for i in 0..self.archived.len() {
+            self.archive(self.archived[i]);
+        }
+        let advanced = value == self.current_step + 1;
         self.current_step = value;
         self.update_current_timestamp();
+        for i in 0..self.shared.len() {
+            self.bring_in(self.shared[i], advanced);
+        }
     }
 
 
@@ -163,6 +260,14 @@ impl DataCache {
     }
 
 
+    /*
+    Checks whether the current timestep is the start of a water year
+     */
+    pub fn is_start_of_water_year(&self, water_year: &WaterYear) -> bool {
+        water_year.is_start(self.timestamp_month, self.timestamp_day, self.timestamp_seconds)
+    }
+
+
     /*
     Gets the current number of seconds since midnight
      */
@@ -190,12 +295,39 @@ impl DataCache {
         day_of_year
     }
 
+    /// Gets the number of days in the current month (28-31)
+    pub fn get_days_in_month(&self) -> u32 {
+        match self.timestamp_month {
+            2 if Self::is_leap_year(self.timestamp_year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// Whether the current year is a leap year
+    pub fn is_leap_year_now(&self) -> bool {
+        Self::is_leap_year(self.timestamp_year)
+    }
+
     /// Check if a year is a leap year
     fn is_leap_year(year: i32) -> bool {
         (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
     }
 
 
+    /*
+    Evaluates the precomputed expressions for the first n_steps timesteps, and then returns
+    to step 0. This needs to be done after the input data is loaded and before the run.
+     */
+    pub fn precompute_expressions(&mut self, n_steps: usize) {
+        let mut precomputed = std::mem::take(&mut self.precomputed);
+        precomputed.evaluate_all(self, n_steps);
+        self.precomputed = precomputed;
+        self.set_current_step(0);
+    }
+
+
     /*
     Increase the current step by +1.
     This also updates the data_cache timestamp values.
@@ -205,6 +337,88 @@ impl DataCache {
     }
 
 
+    /*
+    Records a diagnostic event in the run log against the current timestamp. The message is
+    only built the first time the event is raised by this source.
+     */
+    pub fn log_event<F>(&mut self, level: LogLevel, code: &'static str, source: &str, message: F)
+    where
+        F: FnOnce() -> String,
+    {
+        self.log.record(level, code, source, Some(self.current_timestamp), message);
+    }
+
+
+    /*
+    Sets the NaN policy of series `idx`, in place of `nan_policy` (or None to use it). Input
+    series take the policy of their file.
+     */
+    pub fn set_nan_policy(&mut self, idx: usize, policy: Option<NanPolicy>) {
+        if self.series_nan_policy.len() <= idx {
+            self.series_nan_policy.resize(idx + 1, None);
+        }
+        self.series_nan_policy[idx] = policy;
+    }
+
+
+    /*
+    The NaN policy for a value read from the given series: the strictest of theirs, or
+    `nan_policy` if none is given (e.g. for a constant).
+     */
+    pub fn nan_policy_of(&self, series: impl IntoIterator<Item = usize>) -> NanPolicy {
+        series.into_iter()
+            .map(|idx| self.series_nan_policy.get(idx).copied().flatten().unwrap_or(self.nan_policy))
+            .max()
+            .unwrap_or(self.nan_policy)
+    }
+
+
+    /*
+    Guards against a negative flow or volume more than `negative_tolerance` below zero. It is
+    set to zero and reported with an event of the given kind or, in strict mode, it stops the
+    run (by panicking, which the model reports with the node and date). Other values are
+    returned as they are.
+     */
+    pub fn guard_negative(&mut self, kind: EventKind, source: usize, value: f64) -> f64 {
+        if value >= -self.negative_tolerance || value.is_nan() {
+            return value;
+        }
+        if self.strict {
+            let what = match kind {
+                EventKind::NegativeStorageClamped => "volume",
+                _ => "flow",
+            };
+            panic!("Negative {} of {} ML at '{}'. Without 'strict = true' in [kalix] it would be set to zero",
+                   what, value, self.events.source(source));
+        }
+        self.emit_event(kind, source, -value);
+        0.0
+    }
+
+
+    /*
+    Registers a source of simulation events (a node, or a link as e.g. "dam.ds_1") and returns
+    the index it emits them with. Nodes do this when they are initialised, so that raising an
+    event does not look up the name.
+     */
+    pub fn add_event_source(&mut self, name: &str) -> usize {
+        self.events.add_source(name)
+    }
+
+
+    /*
+    Emits a simulation event at the current timestep, from a source registered with
+    add_event_source(). It is recorded in the events table if that is enabled, and always
+    folded into the run log.
+     */
+    pub fn emit_event(&mut self, kind: EventKind, source: usize, magnitude: f64) {
+        if self.events.enabled {
+            self.events.push(kind, source, self.current_step, self.current_timestamp, magnitude);
+        }
+        self.log.record_event(kind, source, self.events.source(source), Some(self.current_timestamp), magnitude);
+    }
+
+
     /*
     Looks for an exact match on the series name and returns the index of the matching series.
     Returns None if no match is found.
@@ -263,6 +477,8 @@ impl DataCache {
             self.series.push(answer);
             self.series_name.push(name.to_string());
             self.is_critical.push(flag_as_critical);
+            self.storage.push(SeriesStorage::Plain);
+            self.windows.push(Window::default());
             idx
         }
     }
@@ -282,21 +498,258 @@ impl DataCache {
         self.series.push(series);
         self.series_name.push(name.to_string());
         self.is_critical.push(false);
+        self.storage.push(SeriesStorage::Plain);
+        self.windows.push(Window::default());
+    }
+
+
+    /// Sets how a series is stored for the rest of the run. Values already recorded are kept
+    /// (as far as the new storage holds them). Series read and write the same way whatever
+    /// their storage, and `get_series()` gives all the values for writing outputs.
+    ///
+    /// Only a series recorded in full is read and written in `series[idx].values` directly.
+    /// The others hold a ring of the values readers need there (see `set_reads()`), so that
+    /// reads and writes never depend on the storage; the values are archived, and shared
+    /// input values brought in, once per timestep by `set_current_step()`.
+    pub fn set_recording(&mut self, series_idx: usize, recording: Recording) {
+        if self.recording(series_idx) == recording {
+            self.archive(series_idx);
+        } else {
+            let values = self.series_values(series_idx);
+            let ts = &mut self.series[series_idx];
+            ts.values = vec![];
+            ts.timestamps = vec![];
+            self.storage[series_idx] = match recording {
+                Recording::Full => {
+                    for value in values {
+                        ts.push_value(value);
+                    }
+                    SeriesStorage::Plain
+                }
+                Recording::Compressed => SeriesStorage::Compressed(CompressedSeries::from_values(&values)),
+                Recording::Single => SeriesStorage::Single(values.iter().map(|&v| v as f32).collect()),
+                Recording::Recent(depth) => SeriesStorage::Recent(depth),
+            };
+        }
+        self.reset_window(series_idx);
+    }
+
+    pub fn recording(&self, series_idx: usize) -> Recording {
+        match &self.storage[series_idx] {
+            SeriesStorage::Plain | SeriesStorage::Shared(_) => Recording::Full,
+            SeriesStorage::Compressed(_) => Recording::Compressed,
+            SeriesStorage::Single(_) => Recording::Single,
+            SeriesStorage::Recent(depth) => Recording::Recent(*depth),
+        }
+    }
+
+    /// Whether every value of a series is kept (plain or compressed)
+    pub fn is_recorded_in_full(&self, series_idx: usize) -> bool {
+        !matches!(self.storage[series_idx], SeriesStorage::Recent(_))
+    }
+
+    /// Sets how far back and ahead of the current timestep a series is read, which is as much
+    /// as a series not held in `series[idx].values` in full keeps there. Takes effect at the
+    /// next `set_recording()`.
+    pub fn set_reads(&mut self, series_idx: usize, lookback: usize, lookahead: usize) {
+        self.windows[series_idx].lookback = lookback;
+        self.windows[series_idx].lookahead = lookahead;
+    }
+
+    /// Sets a series to values that are shared rather than copied when the cache is cloned,
+    /// starting at `start_timestamp`
+    pub fn set_shared_values(&mut self, series_idx: usize, values: Arc<Vec<f64>>, start_timestamp: u64, step_size: u64) {
+        let ts = &mut self.series[series_idx];
+        ts.start_timestamp = start_timestamp;
+        ts.step_size = step_size;
+        self.storage[series_idx] = SeriesStorage::Shared(values);
+        self.reset_window(series_idx);
+    }
+
+    /// Replaces a series' values, recorded in full
+    pub fn replace_series(&mut self, series_idx: usize, series: Timeseries) {
+        self.series[series_idx] = series;
+        self.storage[series_idx] = SeriesStorage::Plain;
+        self.reset_window(series_idx);
+    }
+
+    /// Drops the values after the first `n_steps` of each series recorded in `series[idx]`,
+    /// e.g. those left from an earlier, longer run
+    pub fn truncate(&mut self, n_steps: usize) {
+        for (ts, storage) in self.series.iter_mut().zip(&self.storage) {
+            if matches!(storage, SeriesStorage::Plain) {
+                ts.values.truncate(n_steps);
+                ts.timestamps.truncate(n_steps);
+            }
+        }
+    }
+
+    /// Sizes the values a series is read and written in for its storage, and notes whether
+    /// it is archived or brought in each timestep
+    fn reset_window(&mut self, series_idx: usize) {
+        let window = &mut self.windows[series_idx];
+        let depth = match &self.storage[series_idx] {
+            SeriesStorage::Plain => None,
+            SeriesStorage::Recent(depth) => Some(*depth),
+            _ => Some(window.lookback + window.lookahead),
+        };
+        window.written = 0;
+        match depth {
+            None => {
+                window.mask = usize::MAX;
+                window.end = usize::MAX;
+            }
+            Some(depth) => {
+                // At least the previous value is held, for inspecting a stepped run
+                let capacity = (depth + 1).next_power_of_two().max(2);
+                window.mask = capacity - 1;
+                window.end = 0;
+                let ts = &mut self.series[series_idx];
+                ts.values = vec![f64::NAN; capacity];
+                ts.timestamps = vec![];
+            }
+        }
+
+        self.archived.retain(|&idx| idx != series_idx);
+        self.shared.retain(|&idx| idx != series_idx);
+        match &self.storage[series_idx] {
+            SeriesStorage::Plain | SeriesStorage::Recent(_) => {}
+            SeriesStorage::Compressed(_) | SeriesStorage::Single(_) => self.archived.push(series_idx),
+            SeriesStorage::Shared(values) => {
+                self.windows[series_idx].end = values.len();
+                self.archived.push(series_idx);
+                self.shared.push(series_idx);
+                self.bring_in(series_idx, false);
+            }
+        }
+    }
+
+    /// Moves the value of a compressed, single precision or shared series at the current
+    /// timestep into its storage, if it was written
+    fn archive(&mut self, series_idx: usize) {
+        let step = self.current_step;
+        let window = &self.windows[series_idx];
+        if window.written != step + 1 {
+            return;
+        }
+        let value = self.series[series_idx].values[step & window.mask];
+        match &mut self.storage[series_idx] {
+            SeriesStorage::Plain | SeriesStorage::Recent(_) => {}
+            SeriesStorage::Compressed(c) => c.set(step, value),
+            SeriesStorage::Single(values) => {
+                if values.len() <= step {
+                    values.resize(step + 1, f32::NAN);
+                }
+                values[step] = value as f32;
+            }
+            SeriesStorage::Shared(values) => {
+                let values = Arc::make_mut(values);
+                if values.len() <= step {
+                    values.resize(step + 1, f64::NAN);
+                }
+                values[step] = value;
+            }
+        }
+    }
+
+    /// Brings the shared values of a series that readers need at the current timestep into
+    /// its ring: only the furthest ahead if the step has just advanced by one, otherwise all
+    fn bring_in(&mut self, series_idx: usize, advanced: bool) {
+        let SeriesStorage::Shared(values) = &self.storage[series_idx] else {
+            return;
+        };
+        let window = &self.windows[series_idx];
+        let last = self.current_step + window.lookahead;
+        let first = if advanced { last } else { (last + 1).saturating_sub(window.mask + 1) };
+        let ring = &mut self.series[series_idx].values;
+        for step in first..=last {
+            ring[step & window.mask] = values.get(step).copied().unwrap_or(f64::NAN);
+        }
+    }
+
+    /// Number of values in a series
+    pub fn series_len(&self, series_idx: usize) -> usize {
+        let written = self.windows[series_idx].written;
+        match &self.storage[series_idx] {
+            SeriesStorage::Plain => self.series[series_idx].len(),
+            SeriesStorage::Compressed(c) => c.len().max(written),
+            SeriesStorage::Single(values) => values.len().max(written),
+            SeriesStorage::Recent(_) => self.windows[series_idx].end,
+            SeriesStorage::Shared(values) => values.len().max(written),
+        }
+    }
+
+    /// Gets a series with all its values, decompressing it if it is held compressed. Steps
+    /// that a series recording only recent values no longer holds are NaN.
+    pub fn get_series(&self, series_idx: usize) -> Cow<'_, Timeseries> {
+        match &self.storage[series_idx] {
+            SeriesStorage::Plain => Cow::Borrowed(&self.series[series_idx]),
+            _ => {
+                let mut ts = self.series[series_idx].clone();
+                ts.values = vec![];
+                ts.timestamps = vec![];
+                for value in self.series_values(series_idx) {
+                    ts.push_value(value);
+                }
+                Cow::Owned(ts)
+            }
+        }
+    }
+
+    fn series_values(&self, series_idx: usize) -> Vec<f64> {
+        let window = &self.windows[series_idx];
+        let ring = &self.series[series_idx].values;
+        let mut values = match &self.storage[series_idx] {
+            SeriesStorage::Plain => return ring.clone(),
+            SeriesStorage::Recent(depth) => {
+                let held = window.end.saturating_sub(depth + 1);
+                return (0..window.end)
+                    .map(|step| if step < held { f64::NAN } else { ring[step & window.mask] })
+                    .collect();
+            }
+            SeriesStorage::Compressed(c) => c.to_values(),
+            SeriesStorage::Single(values) => values.iter().map(|&v| v as f64).collect(),
+            SeriesStorage::Shared(values) => values.to_vec(),
+        };
+
+        // The value written at the current timestep is only archived when the step moves on
+        let step = self.current_step;
+        if window.written == step + 1 {
+            if values.len() <= step {
+                values.resize(step + 1, f64::NAN);
+            }
+            values[step] = ring[step & window.mask];
+        }
+        values
     }
 
 
     /*
     Add a new result value to a given recorder (specified by index)
      */
-    pub  fn add_value_at_index(&mut self, series_idx: usize, value: f64) {
-        //Make sure the series has enough values
-        //TODO: this is dirty. We shouldn't have to check this every single time.
-        while self.series[series_idx].len() <= self.current_step {
-            self.series[series_idx].push_value(f64::NAN); //Extend the series by adding a NAN
+    #[inline]
+    pub fn add_value_at_index(&mut self, series_idx: usize, value: f64) {
+        let step = self.current_step;
+        let window = &mut self.windows[series_idx];
+        window.written = step + 1;
+        window.end = window.end.max(step + 1);
+        let slot = step & window.mask;
+        match self.series[series_idx].values.get_mut(slot) {
+            Some(slot) => *slot = value,
+            None => self.extend_series(series_idx, value),
         }
+    }
 
-        //Set the value
-        self.series[series_idx].values[self.current_step] = value;
+    /*
+    Extends a series recorded in full to the current step (filling any steps before it with
+    NaN) and sets the value there
+     */
+    fn extend_series(&mut self, series_idx: usize, value: f64) {
+        let ts = &mut self.series[series_idx];
+        while ts.len() < self.current_step {
+            ts.push_value(f64::NAN);
+        }
+        ts.push_value(value);
     }
 
 
@@ -319,8 +772,9 @@ impl DataCache {
     ///
     /// Use only indices obtained from `get_or_add_new_series()` and ensure the current
     /// timestep is valid before calling.
+    #[inline]
     pub fn get_current_value(&self, series_idx: usize) -> f64 {
-        self.series[series_idx].values[self.current_step]
+        self.series[series_idx].values[self.current_step & self.windows[series_idx].mask]
     }
 
     /// Get a value from a data series with a temporal offset.
@@ -338,13 +792,9 @@ impl DataCache {
     /// # Performance
     ///
     /// Optimised for the hot path with minimal overhead.
+    #[inline]
     pub fn get_value_with_offset(&self, series_idx: usize, offset: isize) -> f64 {
-        let target_step = self.current_step as isize + offset;
-        if target_step < 0 || target_step as usize >= self.series[series_idx].len() {
-            f64::NAN
-        } else {
-            self.series[series_idx].values[target_step as usize]
-        }
+        self.get_value_with_offset_or_default(series_idx, offset, f64::NAN)
     }
 
     /// Get a value from a data series with a temporal offset and user-specified default.
@@ -363,10 +813,11 @@ impl DataCache {
     #[inline]
     pub fn get_value_with_offset_or_default(&self, series_idx: usize, offset: isize, default_value: f64) -> f64 {
         let target_step = self.current_step as isize + offset;
-        if target_step < 0 || target_step as usize >= self.series[series_idx].len() {
+        let window = &self.windows[series_idx];
+        if target_step < 0 || target_step as usize >= window.end {
             default_value
         } else {
-            self.series[series_idx].values[target_step as usize]
+            self.series[series_idx].values.get(target_step as usize & window.mask).copied().unwrap_or(default_value)
         }
     }
 
@@ -403,7 +854,7 @@ impl DataCache {
     Moved this code to own function. This seems a bit weird and dirty.
      */
     fn get_start_date(series: &Timeseries) -> String {
-        if series.len() > 0 {
+        if !series.timestamps.is_empty() {
             series.timestamps[0].to_string()
         } else {
             String::from("-")
@@ -0,0 +1,271 @@
+//! Typed simulation events, for auditing when and why a model behaved unusually
+//!
+//! Nodes emit events through `DataCache::emit_event`, with the source index they registered
+//! when the model was initialised (see `DataCache::add_event_source`). Every event is folded
+//! into the run log (see [`crate::data_management::run_log`]), so it is reported by `get_log`
+//! regardless. The [`EventTable`] additionally keeps each occurrence with its timestep and
+//! magnitude, but only while `EventTable::enabled` is set: a long run can raise one event per
+//! node per timestep, which is not worth the cost during calibration.
+
+use std::collections::HashMap;
+use crate::data_management::run_log::LogLevel;
+use crate::tid::utils::u64_to_iso_datetime_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// A storage spilled; magnitude is the spill volume
+    StorageSpill,
+    /// A user diverted less than its demand or order; magnitude is the shortfall
+    DemandShortfall,
+    /// Flow fell below a user's flow threshold, stopping diversions; magnitude is the threshold
+    CeaseToFlow,
+    /// A negative computed flow was set to zero; magnitude is the flow removed
+    NegativeFlowClamped,
+    /// A negative storage volume was set to zero; magnitude is the volume added
+    NegativeStorageClamped,
+    /// A link was given more flow than its capacity; magnitude is the flow lost
+    LinkCapacityExceeded,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 6] = [
+        EventKind::StorageSpill,
+        EventKind::DemandShortfall,
+        EventKind::CeaseToFlow,
+        EventKind::NegativeFlowClamped,
+        EventKind::NegativeStorageClamped,
+        EventKind::LinkCapacityExceeded,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::StorageSpill => "storage_spill",
+            EventKind::DemandShortfall => "demand_shortfall",
+            EventKind::CeaseToFlow => "cease_to_flow",
+            EventKind::NegativeFlowClamped => "negative_flow_clamped",
+            EventKind::NegativeStorageClamped => "negative_storage_clamped",
+            EventKind::LinkCapacityExceeded => "link_capacity_exceeded",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EventKind> {
+        EventKind::ALL.into_iter().find(|k| k.as_str() == name.to_lowercase())
+    }
+
+    /// Level and code of the run log entry the event is folded into
+    pub fn log_level(&self) -> LogLevel {
+        match self {
+            EventKind::StorageSpill | EventKind::CeaseToFlow => LogLevel::Info,
+            EventKind::DemandShortfall | EventKind::NegativeFlowClamped | EventKind::NegativeStorageClamped
+            | EventKind::LinkCapacityExceeded => LogLevel::Warning,
+        }
+    }
+
+    pub fn log_code(&self) -> &'static str {
+        match self {
+            EventKind::StorageSpill => "storage_spilled",
+            EventKind::DemandShortfall => "demand_unmet",
+            EventKind::CeaseToFlow => "cease_to_flow",
+            EventKind::NegativeFlowClamped => "negative_flow_clamped",
+            EventKind::NegativeStorageClamped => "negative_storage_clamped",
+            EventKind::LinkCapacityExceeded => "link_capacity_exceeded",
+        }
+    }
+
+    pub fn describe(&self, magnitude: f64) -> String {
+        match self {
+            EventKind::StorageSpill => format!("Storage spilled {} ML", magnitude),
+            EventKind::DemandShortfall => format!("Demand not met; short by {} ML", magnitude),
+            EventKind::CeaseToFlow => format!("Flow fell below the flow threshold of {} ML", magnitude),
+            EventKind::NegativeFlowClamped => format!("Negative flow of -{} ML set to zero", magnitude),
+            EventKind::NegativeStorageClamped => format!("Negative volume of -{} ML set to zero", magnitude),
+            EventKind::LinkCapacityExceeded => format!("Link capacity exceeded; {} ML lost", magnitude),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationEvent {
+    pub kind: EventKind,
+    /// Index into `EventTable::sources`
+    pub source: usize,
+    pub step: usize,
+    pub timestamp: u64,
+    pub magnitude: f64,
+}
+
+/// Count and size of the events of one kind from one source
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventSummary {
+    pub kind: EventKind,
+    pub source: String,
+    pub count: usize,
+    pub total_magnitude: f64,
+    pub max_magnitude: f64,
+    pub first_timestamp: u64,
+    pub last_timestamp: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventTable {
+    /// Events are only recorded while this is set
+    pub enabled: bool,
+    /// Names of the nodes and links that raise events, registered when the model is
+    /// initialised, so events are pushed by index
+    sources: Vec<String>,
+    source_idx: HashMap<String, usize>,
+    events: Vec<SimulationEvent>,
+}
+
+impl EventTable {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Registers a source of events, returning the index it pushes them with. A name that is
+    /// already registered keeps its index.
+    pub fn add_source(&mut self, name: &str) -> usize {
+        match self.source_idx.get(name) {
+            Some(&idx) => idx,
+            None => {
+                self.sources.push(name.to_string());
+                self.source_idx.insert(name.to_string(), self.sources.len() - 1);
+                self.sources.len() - 1
+            }
+        }
+    }
+
+    /// Name of the source registered with the given index
+    pub fn source(&self, source: usize) -> &str {
+        &self.sources[source]
+    }
+
+    pub fn push(&mut self, kind: EventKind, source: usize, step: usize, timestamp: u64, magnitude: f64) {
+        self.events.push(SimulationEvent { kind, source, step, timestamp, magnitude });
+    }
+
+    pub fn events(&self) -> &[SimulationEvent] {
+        &self.events
+    }
+
+    pub fn source_name(&self, event: &SimulationEvent) -> &str {
+        &self.sources[event.source]
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Removes the events, keeping the registered sources
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Keeps only the events whose source `keep` is true for
+    pub fn retain<F: Fn(&str) -> bool>(&mut self, keep: F) {
+        let kept: Vec<bool> = self.sources.iter().map(|source| keep(source)).collect();
+        self.events.retain(|e| kept[e.source]);
+    }
+
+    /// Puts the events in timestep order, e.g. after a re-run added some after those it kept
+    pub fn sort_by_step(&mut self) {
+        self.events.sort_by_key(|e| e.step);
+    }
+
+    /// Events matching every given criterion, in the order they were raised. `start` and
+    /// `end` are inclusive timestamps; source names are matched case-insensitively.
+    pub fn filter(&self, kind: Option<EventKind>, source: Option<&str>, start: Option<u64>, end: Option<u64>) -> Vec<&SimulationEvent> {
+        let source = source.map(|s| s.to_lowercase());
+        self.events.iter()
+            .filter(|e| kind.is_none_or(|k| e.kind == k))
+            .filter(|e| source.as_ref().is_none_or(|s| self.sources[e.source].to_lowercase() == *s))
+            .filter(|e| start.is_none_or(|t| e.timestamp >= t) && end.is_none_or(|t| e.timestamp <= t))
+            .collect()
+    }
+
+    /// One summary per kind and source, in the order each pair first occurred
+    pub fn summarise(&self, events: &[&SimulationEvent]) -> Vec<EventSummary> {
+        let mut summaries: Vec<EventSummary> = Vec::new();
+        for e in events {
+            let source = self.source_name(e);
+            match summaries.iter_mut().find(|s| s.kind == e.kind && s.source == source) {
+                Some(s) => {
+                    s.count += 1;
+                    s.total_magnitude += e.magnitude;
+                    s.max_magnitude = s.max_magnitude.max(e.magnitude);
+                    s.last_timestamp = e.timestamp;
+                }
+                None => summaries.push(EventSummary {
+                    kind: e.kind,
+                    source: source.to_string(),
+                    count: 1,
+                    total_magnitude: e.magnitude,
+                    max_magnitude: e.magnitude,
+                    first_timestamp: e.timestamp,
+                    last_timestamp: e.timestamp,
+                }),
+            }
+        }
+        summaries
+    }
+
+    /// CSV with one row per event
+    pub fn to_csv_string(&self, events: &[&SimulationEvent]) -> String {
+        let mut csv = String::from("time,step,kind,source,magnitude\r\n");
+        for e in events {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\r\n",
+                u64_to_iso_datetime_string(e.timestamp), e.step, e.kind.as_str(), self.source_name(e), e.magnitude
+            ));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_and_summarise() {
+        let mut table = EventTable::new();
+        let dam = table.add_source("dam");
+        let town = table.add_source("town");
+        assert_eq!(table.add_source("dam"), dam);
+        table.push(EventKind::StorageSpill, dam, 0, 100, 5.0);
+        table.push(EventKind::DemandShortfall, town, 1, 200, 1.0);
+        table.push(EventKind::StorageSpill, dam, 2, 300, 7.0);
+        assert_eq!(table.len(), 3);
+
+        let spills = table.filter(Some(EventKind::StorageSpill), Some("DAM"), None, None);
+        assert_eq!(spills.len(), 2);
+        assert_eq!(table.filter(None, None, Some(150), Some(250)).len(), 1);
+        assert!(table.filter(None, Some("weir"), None, None).is_empty());
+
+        let all = table.filter(None, None, None, None);
+        let summary = table.summarise(&all);
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0], EventSummary {
+            kind: EventKind::StorageSpill,
+            source: "dam".to_string(),
+            count: 2,
+            total_magnitude: 12.0,
+            max_magnitude: 7.0,
+            first_timestamp: 100,
+            last_timestamp: 300,
+        });
+
+        let csv = table.to_csv_string(&spills);
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().nth(2).unwrap().ends_with(",2,storage_spill,dam,7"));
+
+        assert_eq!(EventKind::from_name("Cease_To_Flow"), Some(EventKind::CeaseToFlow));
+        assert_eq!(EventKind::from_name("flood"), None);
+    }
+}
@@ -1,2 +1,7 @@
 pub mod constants_cache;
 pub mod data_cache;
+pub mod compressed_series;
+pub mod tables_cache;
+pub mod precomputed_cache;
+pub mod run_log;
+pub mod events;
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use crate::data_management::data_cache::DataCache;
+use crate::model_inputs::compiled_expression::CompiledExpression;
+
+/// Expressions that depend only on input data, constants, tables and the simulation date.
+/// Their values can be worked out before the run starts, so they are evaluated over the whole
+/// simulation period at once and the expressions just read the result at each timestep.
+///
+/// Identical expressions (e.g. the same demand pattern used by several nodes) share one entry.
+#[derive(Clone, Default)]
+pub struct PrecomputedCache {
+
+    // Vectors that collectively define the expressions in the PrecomputedCache
+    expressions: Vec<CompiledExpression>,
+    values: Vec<Vec<f64>>,
+
+    // Dictionary to quickly look up the idx in the above vectors if you only know the expression
+    expression_idx_map: HashMap<String, usize>,
+}
+
+impl PrecomputedCache {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Adds an expression to the PrecomputedCache if it isn't already there, and then returns
+    /// the idx. Any values from an earlier run are discarded, so the expression is evaluated
+    /// directly until the cache is filled again by `evaluate_all()`.
+    pub fn add_if_needed_and_get_idx(&mut self, expression: &str, compiled: &CompiledExpression) -> usize {
+        let idx = match self.expression_idx_map.get(expression) {
+            Some(idx) => *idx,
+            None => {
+                self.expressions.push(compiled.clone());
+                self.values.push(vec![]);
+                let idx = self.expressions.len() - 1;
+                self.expression_idx_map.insert(expression.to_string(), idx);
+                idx
+            }
+        };
+        self.values[idx].clear();
+        idx
+    }
+
+    /// Gets the value of an expression at a timestep, or None if it hasn't been evaluated.
+    #[inline]
+    pub fn get_value(&self, idx: usize, step: usize) -> Option<f64> {
+        self.values[idx].get(step).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.expressions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expressions.is_empty()
+    }
+
+    /// Evaluates every expression for timesteps `0..n_steps`. This moves the data cache through
+    /// the timesteps, so callers need to set the current step again afterwards.
+    pub fn evaluate_all(&mut self, data_cache: &mut DataCache, n_steps: usize) {
+        for values in self.values.iter_mut() {
+            values.clear();
+            values.reserve(n_steps);
+        }
+        for step in 0..n_steps {
+            data_cache.set_current_step(step);
+            for (compiled, values) in self.expressions.iter().zip(self.values.iter_mut()) {
+                let value = compiled.evaluate(data_cache).unwrap_or_else(|e| {
+                    log::error!("Critical evaluation failure in precomputed expression: {}. Returning 0.0. This indicates a parser bug.", e);
+                    0.0
+                });
+                values.push(value);
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Structured diagnostics collected while a model runs
+//!
+//! Nodes record events such as a storage spilling or a demand going unmet in the
+//! [`RunLog`] held by the data cache. Repeats of the same event from the same source are
+//! folded into one entry with a count and the first and last timestamps, so a condition
+//! that holds for years of a run stays one line. The first occurrence of each entry is
+//! also forwarded to the `log` crate, for applications that install a logger.
+
+use crate::data_management::events::EventKind;
+use crate::tid::utils::u64_to_iso_datetime_string;
+
+/// Severity of an event, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+        }
+    }
+
+    fn to_log_level(self) -> log::Level {
+        match self {
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Warning => log::Level::Warn,
+            LogLevel::Error => log::Level::Error,
+        }
+    }
+}
+
+/// One kind of event from one source, e.g. `storage_spilled` at node `dam`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    /// Stable identifier of the kind of event, e.g. `demand_unmet`
+    pub code: &'static str,
+    /// Name of the node or other component that raised it
+    pub source: String,
+    /// Description of the first occurrence
+    pub message: String,
+    pub count: usize,
+    pub first_timestamp: Option<u64>,
+    pub last_timestamp: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunLog {
+    entries: Vec<LogEntry>,
+    /// The entry of each kind of simulation event from each event source, indexed by
+    /// `source * EventKind::ALL.len() + kind`, once it has been raised
+    event_entries: Vec<Option<usize>>,
+}
+
+impl RunLog {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Records an event. The message is only built for the first occurrence.
+    pub fn record<F>(&mut self, level: LogLevel, code: &'static str, source: &str, timestamp: Option<u64>, message: F)
+    where
+        F: FnOnce() -> String,
+    {
+        self.find_or_add(level, code, source, timestamp, message);
+    }
+
+    /// Records a simulation event from a source registered with the event table. Its entry is
+    /// found by name the first time only, so repeats at later timesteps don't search the log.
+    pub fn record_event(&mut self, kind: EventKind, source: usize, source_name: &str, timestamp: Option<u64>, magnitude: f64) {
+        let slot = source * EventKind::ALL.len() + kind as usize;
+        if let Some(&Some(idx)) = self.event_entries.get(slot) {
+            let entry = &mut self.entries[idx];
+            entry.count += 1;
+            entry.last_timestamp = timestamp;
+            return;
+        }
+        let idx = self.find_or_add(kind.log_level(), kind.log_code(), source_name, timestamp, || kind.describe(magnitude));
+        if self.event_entries.len() <= slot {
+            self.event_entries.resize(slot + 1, None);
+        }
+        self.event_entries[slot] = Some(idx);
+    }
+
+    /// Counts a repeat of the entry with this code and source, or adds it. Returns its index.
+    fn find_or_add<F>(&mut self, level: LogLevel, code: &'static str, source: &str, timestamp: Option<u64>, message: F) -> usize
+    where
+        F: FnOnce() -> String,
+    {
+        if let Some(idx) = self.entries.iter().position(|e| e.code == code && e.source == source) {
+            let entry = &mut self.entries[idx];
+            entry.count += 1;
+            entry.last_timestamp = timestamp;
+            return idx;
+        }
+        let message = message();
+        log::log!(level.to_log_level(), "{} [{}]: {}", source, code, message);
+        self.entries.push(LogEntry {
+            level,
+            code,
+            source: source.to_string(),
+            message,
+            count: 1,
+            first_timestamp: timestamp,
+            last_timestamp: timestamp,
+        });
+        self.entries.len() - 1
+    }
+
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// How many times events with this code were raised, from all sources
+    pub fn count(&self, code: &str) -> usize {
+        self.entries.iter().filter(|e| e.code == code).map(|e| e.count).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.event_entries.clear();
+    }
+
+    /// Keeps only the entries for which `keep` is true
+    pub fn retain<F: FnMut(&LogEntry) -> bool>(&mut self, keep: F) {
+        self.entries.retain(keep);
+        self.event_entries.clear();
+    }
+
+    /// Adds the entries of another log after these, without folding them together
+    pub fn append(&mut self, other: RunLog) {
+        self.entries.extend(other.entries);
+    }
+
+    /// CSV with one row per entry, in the order the entries were first raised
+    pub fn to_csv_string(&self) -> String {
+        let timestamp = |t: Option<u64>| t.map(u64_to_iso_datetime_string).unwrap_or_default();
+        let mut csv = String::from("level,code,source,count,first,last,message\r\n");
+        for e in &self.entries {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},\"{}\"\r\n",
+                e.level.as_str(), e.code, e.source, e.count,
+                timestamp(e.first_timestamp), timestamp(e.last_timestamp),
+                e.message.replace('"', "\"\"")
+            ));
+        }
+        csv
+    }
+
+    pub fn write_csv(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_csv_string())
+            .map_err(|e| format!("Could not write file {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tid::utils::date_string_to_u64;
+
+    #[test]
+    fn test_repeats_are_folded_into_one_entry() {
+        let day = |d: &str| date_string_to_u64(d).unwrap();
+        let mut log = RunLog::new();
+        log.record(LogLevel::Info, "storage_spilled", "dam", Some(day("2000-01-01")), || "Spilled 5".to_string());
+        log.record(LogLevel::Warning, "demand_unmet", "town", Some(day("2000-01-02")), || "Short 2".to_string());
+        log.record(LogLevel::Info, "storage_spilled", "dam", Some(day("2000-01-03")), || panic!("message built twice"));
+
+        assert_eq!(log.entries().len(), 2);
+        let spill = &log.entries()[0];
+        assert_eq!((spill.count, spill.first_timestamp, spill.last_timestamp), (2, Some(day("2000-01-01")), Some(day("2000-01-03"))));
+        assert_eq!(spill.message, "Spilled 5");
+
+        let csv = log.to_csv_string();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("level,code,source,count,first,last,message"));
+        assert_eq!(lines.next(), Some("info,storage_spilled,dam,2,2000-01-01T00:00:00.000Z,2000-01-03T00:00:00.000Z,\"Spilled 5\""));
+        assert!(lines.next().unwrap().starts_with("warning,demand_unmet,town,1,"));
+
+        log.clear();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_events_are_folded_by_source_index() {
+        let mut log = RunLog::new();
+        log.record_event(EventKind::StorageSpill, 1, "dam", Some(1), 5.0);
+        log.record_event(EventKind::DemandShortfall, 0, "town", Some(2), 2.0);
+        log.record_event(EventKind::StorageSpill, 1, "dam", Some(3), 7.0);
+        assert_eq!(log.entries().len(), 2);
+        assert_eq!((log.entries()[0].count, log.entries()[0].last_timestamp), (2, Some(3)));
+        assert_eq!(log.entries()[0].message, "Storage spilled 5 ML");
+
+        // Removing entries moves the others, which are then found by name again
+        log.retain(|e| e.source == "town");
+        log.record_event(EventKind::DemandShortfall, 0, "town", Some(4), 1.0);
+        log.record_event(EventKind::StorageSpill, 1, "dam", Some(5), 1.0);
+        assert_eq!(log.count("demand_unmet"), 2);
+        assert_eq!(log.count("storage_spilled"), 1);
+        assert_eq!(log.entries().len(), 2);
+    }
+}
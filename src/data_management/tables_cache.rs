@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use crate::numerical::table::Table;
+
+/// Prefix of table names, e.g. `tables.rating_curve`
+pub const TABLE_PREFIX: &str = "tables.";
+
+/// Tables from the `[tables]` section, for `lookup()` in expressions. Each table has two
+/// columns (x, y) with x increasing.
+#[derive(Clone, Default)]
+pub struct TablesCache {
+
+    // Vectors that collectively define the tables in the TablesCache
+    names: Vec<String>,
+    is_assigned: Vec<bool>,
+    tables: Vec<Table>,
+    definitions: Vec<String>,   // As written in the model file (inline values or a file path)
+
+    // Dictionary to quickly look up the idx in the above vectors if you only know the name
+    name_idx_map: HashMap<String, usize>,
+}
+
+impl TablesCache {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Adds a table to the TablesCache if it doesn't already exist, and then returns the idx.
+    /// Expressions use this to refer to a table that may be defined later in the model file.
+    pub fn add_if_needed_and_get_idx(&mut self, name: &str) -> usize {
+        if let Some(idx) = self.name_idx_map.get(name) {
+            *idx
+        } else {
+            self.names.push(name.to_string());
+            self.is_assigned.push(false);
+            self.tables.push(Table::new(2));
+            self.definitions.push(String::new());
+            let idx = self.names.len() - 1;
+            self.name_idx_map.insert(name.to_string(), idx);
+            idx
+        }
+    }
+
+    /// Sets a table and the definition it was read from, and returns the idx. The table must
+    /// have two columns with x increasing.
+    pub fn set_table(&mut self, name: &str, table: Table, definition: &str) -> Result<usize, String> {
+        if table.ncols() != 2 || table.nrows() < 2 {
+            return Err(format!("Table '{}' must have two columns (x, y) and at least two rows", name));
+        }
+        for row in 1..table.nrows() {
+            if table.get_value(row, 0) <= table.get_value(row - 1, 0) {
+                return Err(format!("Table '{}' x values must be strictly increasing (violation at row {})", name, row + 1));
+            }
+        }
+        let idx = self.add_if_needed_and_get_idx(name);
+        self.tables[idx] = table;
+        self.definitions[idx] = definition.to_string();
+        self.is_assigned[idx] = true;
+        Ok(idx)
+    }
+
+    /// Looks up `x` in a table, interpolating linearly between rows. Values of `x` outside the
+    /// table take the first or last y value.
+    pub fn lookup(&self, idx: usize, x: f64) -> f64 {
+        let table = &self.tables[idx];
+        if table.nrows() < 2 {
+            return f64::NAN;
+        }
+        let last = table.nrows() - 1;
+        if x <= table.get_value(0, 0) {
+            table.get_value(0, 1)
+        } else if x >= table.get_value(last, 0) {
+            table.get_value(last, 1)
+        } else {
+            table.interpolate(0, 1, x)
+        }
+    }
+
+    /// List the names of all tables that have been referenced but never defined.
+    pub fn list_unassigned_names(&self) -> Vec<String> {
+        (0..self.names.len())
+            .filter(|&i| !self.is_assigned[i])
+            .map(|i| self.names[i].clone())
+            .collect()
+    }
+
+    /// Get all defined tables as (name, definition) tuples, for saving the model to INI.
+    pub fn get_name_definition_pairs(&self) -> Vec<(String, String)> {
+        (0..self.names.len())
+            .filter(|&i| self.is_assigned[i])
+            .map(|i| (self.names[i].clone(), self.definitions[i].clone()))
+            .collect()
+    }
+}
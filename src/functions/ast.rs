@@ -32,6 +32,103 @@ impl FunctionRef {
     }
 }
 
+/// Aggregations over the previous timesteps of a series, e.g. `mavg_last(data.flow, 7)`.
+///
+/// These take a series reference rather than a value, so the parser builds them as
+/// [`ExpressionNode::WindowAggregate`] rather than as function calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFunction {
+    Mean, Sum, Min, Max,
+}
+
+impl WindowFunction {
+    pub const ALL: [WindowFunction; 4] = [WindowFunction::Mean, WindowFunction::Sum, WindowFunction::Min, WindowFunction::Max];
+
+    /// Look up a rolling-window function by name (lowercased)
+    pub fn from_name(name: &str) -> Option<WindowFunction> {
+        Some(match name {
+            "mavg_last" => WindowFunction::Mean,
+            "sum_last"  => WindowFunction::Sum,
+            "min_last"  => WindowFunction::Min,
+            "max_last"  => WindowFunction::Max,
+            _ => return None,
+        })
+    }
+
+    /// Human-readable name (lowercase) for error messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowFunction::Mean => "mavg_last",
+            WindowFunction::Sum => "sum_last",
+            WindowFunction::Min => "min_last",
+            WindowFunction::Max => "max_last",
+        }
+    }
+
+    /// Aggregates the values, skipping missing (NaN) ones. Returns NaN if none remain.
+    pub fn aggregate(&self, values: impl Iterator<Item = f64>) -> f64 {
+        let (mut count, mut sum, mut min, mut max) = (0, 0.0, f64::INFINITY, f64::NEG_INFINITY);
+        for v in values.filter(|v| !v.is_nan()) {
+            count += 1;
+            sum += v;
+            min = min.min(v);
+            max = max.max(v);
+        }
+        if count == 0 {
+            return f64::NAN;
+        }
+        match self {
+            WindowFunction::Mean => sum / count as f64,
+            WindowFunction::Sum => sum,
+            WindowFunction::Min => min,
+            WindowFunction::Max => max,
+        }
+    }
+}
+
+/// Functions that keep a running value from one timestep to the next, e.g.
+/// `accumulate(node.user.diversion, sim.month == 7 && sim.day == 1)`.
+///
+/// Both take a value and an optional reset condition. On a timestep where the reset
+/// condition is non-zero the running value restarts from zero before the value is added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatefulFunction {
+    /// Running total of the value (missing values add nothing)
+    Accumulate,
+    /// Number of timesteps on which the value (a condition) was non-zero
+    Counter,
+}
+
+impl StatefulFunction {
+    pub const ALL: [StatefulFunction; 2] = [StatefulFunction::Accumulate, StatefulFunction::Counter];
+
+    /// Look up a stateful function by name (lowercased)
+    pub fn from_name(name: &str) -> Option<StatefulFunction> {
+        match name {
+            "accumulate" => Some(StatefulFunction::Accumulate),
+            "counter" => Some(StatefulFunction::Counter),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name (lowercase) for error messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatefulFunction::Accumulate => "accumulate",
+            StatefulFunction::Counter => "counter",
+        }
+    }
+
+    /// The amount added to the running value for this timestep
+    pub fn increment(&self, value: f64) -> f64 {
+        match self {
+            StatefulFunction::Accumulate if value.is_nan() => 0.0,
+            StatefulFunction::Accumulate => value,
+            StatefulFunction::Counter => if value != 0.0 && !value.is_nan() { 1.0 } else { 0.0 },
+        }
+    }
+}
+
 /// Trait for all AST nodes that can be evaluated.
 ///
 /// This trait defines the common interface for all nodes in the Abstract Syntax Tree.
@@ -157,6 +254,39 @@ pub enum ExpressionNode {
         default_value: f64,
     },
 
+    /// An aggregation over the previous `window` timesteps of a series (not including the
+    /// current timestep).
+    ///
+    /// Examples: `mavg_last(data.flow, 7)`, `max_last(node.dam.volume, 30)`
+    WindowAggregate {
+        /// The aggregation to apply
+        func: WindowFunction,
+        /// The name of the series
+        name: String,
+        /// The number of previous timesteps
+        window: usize,
+    },
+
+    /// A lookup in a table from the `[tables]` section, interpolating between rows.
+    ///
+    /// Examples: `lookup("tables.rating_curve", data.level)`
+    TableLookup {
+        /// The name of the table
+        table: String,
+        /// The x value to look up
+        arg: Box<dyn ASTNode>,
+    },
+
+    /// A call to a function that keeps a running value between timesteps.
+    ///
+    /// Examples: `accumulate(data.rain, sim.month == 7)`, `counter(data.rain < 1)`
+    StatefulCall {
+        /// The stateful function
+        func: StatefulFunction,
+        /// The value and the optional reset condition
+        args: Vec<Box<dyn ASTNode>>,
+    },
+
     /// A constant numerical value.
     ///
     /// Examples: `42`, `3.14159`, `-2.5`
@@ -187,6 +317,27 @@ impl ASTNode for ExpressionNode {
                     })
                 }
             }
+
+            ExpressionNode::TableLookup { .. } => {
+                // Tables are only available when evaluating via DataCache
+                Err(EvaluationError::InvalidOperation {
+                    message: "Function lookup() not supported in this evaluation context".to_string(),
+                })
+            }
+
+            ExpressionNode::StatefulCall { func, .. } => {
+                // The running value is only kept when evaluating via DataCache
+                Err(EvaluationError::InvalidOperation {
+                    message: format!("Function {}() not supported in this evaluation context", func.name()),
+                })
+            }
+
+            ExpressionNode::WindowAggregate { func, .. } => {
+                // Past values are only available when evaluating via DataCache
+                Err(EvaluationError::InvalidOperation {
+                    message: format!("Function {}() not supported in this evaluation context", func.name()),
+                })
+            }
             
             ExpressionNode::BinaryOp { left, op, right } => {
                 let left_val = left.evaluate(context)?;
@@ -226,6 +377,18 @@ impl ASTNode for ExpressionNode {
                 vars
             }
 
+            ExpressionNode::WindowAggregate { name, .. } => {
+                let mut vars = HashSet::new();
+                vars.insert(name.clone());
+                vars
+            }
+
+            ExpressionNode::TableLookup { table, arg } => {
+                let mut vars = arg.get_variables();
+                vars.insert(table.clone());
+                vars
+            }
+
             ExpressionNode::BinaryOp { left, right, .. } => {
                 let mut vars = left.get_variables();
                 vars.extend(right.get_variables());
@@ -236,7 +399,7 @@ impl ASTNode for ExpressionNode {
                 operand.get_variables()
             }
 
-            ExpressionNode::FunctionCall { args, .. } => {
+            ExpressionNode::FunctionCall { args, .. } | ExpressionNode::StatefulCall { args, .. } => {
                 let mut vars = HashSet::new();
                 for arg in args {
                     vars.extend(arg.get_variables());
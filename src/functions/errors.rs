@@ -6,6 +6,19 @@
 
 use std::fmt;
 
+/// A range of character positions in an expression, from `start` up to (not including) `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
 /// Errors that can occur during parsing of mathematical expressions.
 ///
 /// These errors are returned by the parser when it encounters invalid syntax,
@@ -18,21 +31,36 @@ pub enum ParseError {
     /// This includes issues like invalid tokens, malformed numbers,
     /// or incorrect operator usage.
     SyntaxError {
-        /// The character position where the error occurred
-        position: usize,
+        /// The characters where the error occurred
+        span: Span,
         /// A descriptive message about what went wrong
         message: String,
     },
     
     /// An unknown function name was encountered.
     ///
-    /// This occurs when the parser finds a function call with a name
-    /// that is not in the list of supported built-in functions.
+    /// This occurs when a function call has a name that is not in the list
+    /// of supported functions.
     UnknownFunction {
         /// The name of the unknown function
         name: String,
-        /// The character position where the function was found
-        position: usize,
+        /// The characters where the function name was found
+        span: Span,
+        /// A supported function with a similar name, if there is one
+        suggestion: Option<String>,
+    },
+
+    /// An unknown variable name was encountered.
+    ///
+    /// This occurs when a variable is in a namespace with a fixed set of
+    /// names (e.g. `sim.*`) but is not one of them.
+    UnknownVariable {
+        /// The name of the unknown variable
+        name: String,
+        /// The characters where the variable was found
+        span: Span,
+        /// A known variable with a similar name, if there is one
+        suggestion: Option<String>,
     },
     
     /// The expression is fundamentally invalid.
@@ -54,8 +82,8 @@ pub enum ParseError {
         expected: String,
         /// What was actually found
         found: String,
-        /// The character position where the unexpected token was found
-        position: usize,
+        /// The characters of the unexpected token
+        span: Span,
     },
     
     /// Parentheses are not properly matched.
@@ -63,33 +91,107 @@ pub enum ParseError {
     /// This occurs when there are unmatched opening or closing parentheses
     /// in the expression.
     UnmatchedParentheses {
-        /// The character position where the unmatched parenthesis was detected
-        position: usize,
+        /// The opening parenthesis that was not closed
+        span: Span,
     },
 }
 
+impl ParseError {
+    /// The characters of the expression the error refers to, if it refers to any.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::SyntaxError { span, .. } |
+            ParseError::UnknownFunction { span, .. } |
+            ParseError::UnknownVariable { span, .. } |
+            ParseError::UnexpectedToken { span, .. } |
+            ParseError::UnmatchedParentheses { span } => Some(*span),
+            ParseError::InvalidExpression { .. } => None,
+        }
+    }
+
+    /// Formats the error followed by the expression with the offending characters
+    /// underlined, e.g.
+    ///
+    /// ```text
+    /// Unknown function 'mx' at position 0. Did you mean 'max'?
+    ///     mx(data.a, 1)
+    ///     ^^
+    /// ```
+    pub fn render(&self, expression: &str) -> String {
+        match self.span() {
+            Some(span) => {
+                let length = expression.chars().count();
+                let start = span.start.min(length);
+                let carets = span.end.min(length).saturating_sub(start).max(1);
+                format!("{}\n    {}\n    {}{}", self, expression, " ".repeat(start), "^".repeat(carets))
+            }
+            None => self.to_string(),
+        }
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::SyntaxError { position, message } => {
-                write!(f, "Syntax error at position {}: {}", position, message)
+            ParseError::SyntaxError { span, message } => {
+                write!(f, "Syntax error at position {}: {}", span.start, message)
             }
-            ParseError::UnknownFunction { name, position } => {
-                write!(f, "Unknown function '{}' at position {}", name, position)
+            ParseError::UnknownFunction { name, span, suggestion } => {
+                write!(f, "Unknown function '{}' at position {}", name, span.start)?;
+                write_suggestion(f, suggestion)
+            }
+            ParseError::UnknownVariable { name, span, suggestion } => {
+                write!(f, "Unknown variable '{}' at position {}", name, span.start)?;
+                write_suggestion(f, suggestion)
             }
             ParseError::InvalidExpression { message } => {
                 write!(f, "Invalid expression: {}", message)
             }
-            ParseError::UnexpectedToken { expected, found, position } => {
-                write!(f, "Expected '{}' but found '{}' at position {}", expected, found, position)
+            ParseError::UnexpectedToken { expected, found, span } => {
+                write!(f, "Expected '{}' but found '{}' at position {}", expected, found, span.start)
             }
-            ParseError::UnmatchedParentheses { position } => {
-                write!(f, "Unmatched parentheses at position {}", position)
+            ParseError::UnmatchedParentheses { span } => {
+                write!(f, "Unmatched parentheses at position {}", span.start)
             }
         }
     }
 }
 
+fn write_suggestion(f: &mut fmt::Formatter<'_>, suggestion: &Option<String>) -> fmt::Result {
+    match suggestion {
+        Some(s) => write!(f, ". Did you mean '{}'?", s),
+        None => Ok(()),
+    }
+}
+
+/// Finds the candidate closest to `name` (ignoring case), for "did you mean" suggestions.
+/// Only candidates within a few character edits of the name are considered, and an exact
+/// match is never suggested.
+pub fn closest_match<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates.into_iter()
+        .map(|c| (edit_distance(&name, &c.to_lowercase()), c))
+        .filter(|(d, _)| *d > 0 && *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Number of single-character insertions, deletions or substitutions to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
 impl std::error::Error for ParseError {}
 
 /// Errors that can occur during evaluation of mathematical expressions.
@@ -36,6 +36,18 @@ pub enum BuiltinFunction {
 }
 
 impl BuiltinFunction {
+    /// Every built-in function, e.g. for suggesting a name when an unknown one is used.
+    pub const ALL: [BuiltinFunction; 22] = [
+        BuiltinFunction::Abs, BuiltinFunction::Sqrt, BuiltinFunction::Sin, BuiltinFunction::Cos,
+        BuiltinFunction::Tan, BuiltinFunction::Asin, BuiltinFunction::Acos, BuiltinFunction::Atan,
+        BuiltinFunction::Exp, BuiltinFunction::Ln, BuiltinFunction::Log10, BuiltinFunction::Log2,
+        BuiltinFunction::Ceil, BuiltinFunction::Floor, BuiltinFunction::Round,
+        BuiltinFunction::Pow, BuiltinFunction::Atan2,
+        BuiltinFunction::Min, BuiltinFunction::Max,
+        BuiltinFunction::Sum, BuiltinFunction::Avg,
+        BuiltinFunction::If,
+    ];
+
     /// Look up a built-in function by name (lowercased). Returns `None` for unknown names,
     /// in which case the caller treats it as a context-function name to be resolved at
     /// evaluation time against the [`crate::functions::FunctionRegistry`].
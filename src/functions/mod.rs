@@ -20,7 +20,7 @@ pub mod functions;
 pub mod operators;
 pub mod parser;
 
-pub use errors::{EvaluationError, ParseError};
+pub use errors::{closest_match, EvaluationError, ParseError, Span};
 pub use evaluator::{ContextFn, EvaluationConfig, FunctionRegistry, VariableContext};
 pub use parser::{FunctionParser, ParsedFunction};
 
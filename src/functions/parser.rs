@@ -9,10 +9,26 @@
 /// according to mathematical conventions.
 
 use std::collections::HashSet;
-use crate::functions::ast::{ASTNode, ExpressionNode};
-use crate::functions::errors::ParseError;
+use crate::functions::ast::{ASTNode, ExpressionNode, StatefulFunction, WindowFunction};
+use crate::functions::errors::{ParseError, Span};
+use crate::functions::functions::BuiltinFunction;
 use crate::functions::evaluator::VariableContext;
 use crate::functions::operators::{BinaryOperator, UnaryOperator};
+use crate::data_management::tables_cache::TABLE_PREFIX;
+
+/// Zero-argument functions for the simulation date, e.g. `month()`. Each is shorthand for the
+/// `sim.*` variable of the same name.
+const SIM_TIME_FUNCTIONS: [&str; 7] = ["year", "month", "day", "day_of_year", "days_in_month", "is_leap_year", "step"];
+
+/// Names of all the functions that expressions in a model can use (built-ins, rolling
+/// windows, stateful functions, `lookup` and the time functions)
+pub fn model_function_names() -> impl Iterator<Item = &'static str> {
+    BuiltinFunction::ALL.iter().map(|f| f.name())
+        .chain(WindowFunction::ALL.iter().map(|f| f.name()))
+        .chain(StatefulFunction::ALL.iter().map(|f| f.name()))
+        .chain(std::iter::once("lookup"))
+        .chain(SIM_TIME_FUNCTIONS)
+}
 
 /// Token types produced by the tokenizer.
 ///
@@ -24,6 +40,8 @@ enum Token {
     Number(f64),
     /// An identifier for variables or function names (e.g., x, sin, temperature)
     Identifier(String),
+    /// A quoted string (e.g., "tables.rating_curve"), only used for table names
+    Str(String),
     /// An operator symbol (e.g., +, -, *, ==, &&)
     Operator(String),
     /// Left parenthesis (
@@ -45,6 +63,8 @@ struct Tokenizer {
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
+    /// Where the most recently read token starts
+    token_start: usize,
 }
 
 impl Tokenizer {
@@ -55,6 +75,7 @@ impl Tokenizer {
             input: chars,
             position: 0,
             current_char,
+            token_start: 0,
         }
     }
     
@@ -119,7 +140,7 @@ impl Tokenizer {
         }
 
         number_str.parse().map_err(|_| ParseError::SyntaxError {
-            position: start_pos,
+            span: Span::new(start_pos, self.position),
             message: format!("Invalid number: {}", number_str),
         })
     }
@@ -151,6 +172,23 @@ impl Tokenizer {
         identifier
     }
     
+    fn read_string(&mut self) -> Result<String, ParseError> {
+        let start_pos = self.position;
+        self.advance(); // skip opening quote
+        let mut s = String::new();
+        while let Some(ch) = self.current_char {
+            self.advance();
+            if ch == '"' {
+                return Ok(s);
+            }
+            s.push(ch);
+        }
+        Err(ParseError::SyntaxError {
+            span: Span::new(start_pos, self.position),
+            message: "Unterminated string".to_string(),
+        })
+    }
+
     fn read_operator(&mut self) -> String {
         let mut op = String::new();
         
@@ -201,6 +239,7 @@ impl Tokenizer {
     
     fn next_token(&mut self) -> Result<Token, ParseError> {
         self.skip_whitespace();
+        self.token_start = self.position;
 
         match self.current_char {
             None => Ok(Token::EOF),
@@ -232,6 +271,10 @@ impl Tokenizer {
                 let identifier = self.read_identifier();
                 Ok(Token::Identifier(identifier))
             }
+            Some('"') => {
+                let s = self.read_string()?;
+                Ok(Token::Str(s))
+            }
             Some(_) => {
                 let op = self.read_operator();
                 Ok(Token::Operator(op))
@@ -248,6 +291,8 @@ impl Tokenizer {
 pub struct FunctionParser {
     tokenizer: Tokenizer,
     current_token: Token,
+    /// Where the token before the current one ends
+    previous_end: usize,
 }
 
 /// A parsed mathematical function ready for evaluation.
@@ -364,6 +409,25 @@ impl ParsedFunction {
         None
     }
 
+    /// Check whether the expression keeps a running value between timesteps, e.g.
+    /// `counter(1)`. Such expressions must be evaluated every timestep even if they
+    /// have no variables.
+    pub fn is_stateful(&self) -> bool {
+        fn contains_stateful_call(node: &dyn ASTNode) -> bool {
+            match (node as &dyn std::any::Any).downcast_ref::<ExpressionNode>() {
+                Some(ExpressionNode::StatefulCall { .. }) => true,
+                Some(ExpressionNode::BinaryOp { left, right, .. }) => {
+                    contains_stateful_call(left.as_ref()) || contains_stateful_call(right.as_ref())
+                }
+                Some(ExpressionNode::UnaryOp { operand, .. }) => contains_stateful_call(operand.as_ref()),
+                Some(ExpressionNode::FunctionCall { args, .. }) => args.iter().any(|a| contains_stateful_call(a.as_ref())),
+                Some(ExpressionNode::TableLookup { arg, .. }) => contains_stateful_call(arg.as_ref()),
+                _ => false,
+            }
+        }
+        contains_stateful_call(self.ast.as_ref())
+    }
+
     /// Check if this function is a single variable with an offset.
     ///
     /// Returns `Some((variable_name, offset, default_value))` if the expression is a variable
@@ -402,6 +466,7 @@ impl FunctionParser {
         Self {
             tokenizer: Tokenizer::new(""),
             current_token: Token::EOF,
+            previous_end: 0,
         }
     }
     
@@ -430,6 +495,7 @@ impl FunctionParser {
         let mut parser = Self {
             tokenizer: Tokenizer::new(expression),
             current_token: Token::EOF,
+            previous_end: 0,
         };
         
         parser.current_token = parser.tokenizer.next_token()?;
@@ -437,7 +503,7 @@ impl FunctionParser {
         
         if parser.current_token != Token::EOF {
             return Err(ParseError::SyntaxError {
-                position: parser.tokenizer.position,
+                span: Span::new(parser.tokenizer.token_start, parser.tokenizer.input.len()),
                 message: "Unexpected tokens after expression".to_string(),
             });
         }
@@ -446,9 +512,15 @@ impl FunctionParser {
     }
     
     fn consume_token(&mut self) -> Result<(), ParseError> {
+        self.previous_end = self.tokenizer.position;
         self.current_token = self.tokenizer.next_token()?;
         Ok(())
     }
+
+    /// The characters of the current token
+    fn current_span(&self) -> Span {
+        Span::new(self.tokenizer.token_start, self.tokenizer.position)
+    }
     
     fn parse_expression(&mut self) -> Result<Box<dyn ASTNode>, ParseError> {
         self.parse_or_expression()
@@ -631,6 +703,63 @@ impl FunctionParser {
         self.parse_primary_expression()
     }
     
+    /// Builds a rolling-window function such as `mavg_last(data.flow, 7)` from its arguments,
+    /// which must be a series reference and a whole number of timesteps
+    fn build_window_aggregate(&self, func: WindowFunction, args: Vec<Box<dyn ASTNode>>, span: Span) -> Result<Box<dyn ASTNode>, ParseError> {
+        let usage = format!("{}() expects a series and a number of timesteps, e.g. {}(data.flow, 7)", func.name(), func.name());
+        let syntax_error = |message: String| ParseError::SyntaxError { span, message };
+        if args.len() != 2 {
+            return Err(syntax_error(usage));
+        }
+        let as_expression = |arg: &dyn ASTNode| (arg as &dyn std::any::Any).downcast_ref::<ExpressionNode>().cloned();
+        let name = match as_expression(args[0].as_ref()) {
+            Some(ExpressionNode::Variable { name })
+                if !name.to_lowercase().starts_with("sim.") && !name.to_lowercase().starts_with("c.") => name,
+            _ => return Err(syntax_error(format!("{}. The series must be a data, node or function reference", usage))),
+        };
+        let window = match as_expression(args[1].as_ref()) {
+            Some(ExpressionNode::Constant { value }) if value >= 1.0 && value.fract() == 0.0 => value as usize,
+            _ => return Err(syntax_error(format!("{}. The number of timesteps must be a whole number of at least 1", usage))),
+        };
+        Ok(Box::new(ExpressionNode::WindowAggregate { func, name, window }))
+    }
+
+    /// Parses the arguments of `lookup("tables.name", x)`. The quotes around the table
+    /// name are optional.
+    fn parse_table_lookup(&mut self) -> Result<Box<dyn ASTNode>, ParseError> {
+        self.consume_token()?; // consume '('
+        let table = match &self.current_token {
+            Token::Str(s) | Token::Identifier(s) if s.to_lowercase().starts_with(TABLE_PREFIX) => s.to_lowercase(),
+            _ => {
+                return Err(ParseError::UnexpectedToken {
+                    expected: format!("table name starting with '{}'", TABLE_PREFIX),
+                    found: format!("{:?}", self.current_token),
+                    span: self.current_span(),
+                });
+            }
+        };
+        self.consume_token()?; // consume the table name
+
+        if self.current_token != Token::Comma {
+            return Err(ParseError::SyntaxError {
+                span: self.current_span(),
+                message: "lookup() expects a table name and a value, e.g. lookup(\"tables.rating_curve\", data.level)".to_string(),
+            });
+        }
+        self.consume_token()?; // consume ','
+        let arg = self.parse_expression()?;
+
+        if self.current_token != Token::RightParen {
+            return Err(ParseError::UnexpectedToken {
+                expected: ")".to_string(),
+                found: format!("{:?}", self.current_token),
+                span: self.current_span(),
+            });
+        }
+        self.consume_token()?; // consume ')'
+        Ok(Box::new(ExpressionNode::TableLookup { table, arg }))
+    }
+
     fn parse_primary_expression(&mut self) -> Result<Box<dyn ASTNode>, ParseError> {
         match &self.current_token {
             Token::Number(value) => {
@@ -640,9 +769,12 @@ impl FunctionParser {
             }
             Token::Identifier(name) => {
                 let name = name.clone();
+                let start = self.tokenizer.token_start;
                 self.consume_token()?;
 
-                if self.current_token == Token::LeftParen {
+                if self.current_token == Token::LeftParen && name.to_lowercase() == "lookup" {
+                    self.parse_table_lookup()
+                } else if self.current_token == Token::LeftParen {
                     // Function call
                     self.consume_token()?; // consume '('
                     let mut args = Vec::new();
@@ -660,11 +792,28 @@ impl FunctionParser {
                         return Err(ParseError::UnexpectedToken {
                             expected: ")".to_string(),
                             found: format!("{:?}", self.current_token),
-                            position: self.tokenizer.position,
+                            span: self.current_span(),
                         });
                     }
 
                     self.consume_token()?; // consume ')'
+                    let call_span = Span::new(start, self.previous_end);
+                    let name_lower = name.to_lowercase();
+                    if let Some(func) = WindowFunction::from_name(&name_lower) {
+                        return self.build_window_aggregate(func, args, call_span);
+                    }
+                    if let Some(func) = StatefulFunction::from_name(&name_lower) {
+                        if args.is_empty() || args.len() > 2 {
+                            return Err(ParseError::SyntaxError {
+                                span: call_span,
+                                message: format!("{}() expects a value and an optional reset condition, e.g. {}(data.rain, sim.month == 7)", func.name(), func.name()),
+                            });
+                        }
+                        return Ok(Box::new(ExpressionNode::StatefulCall { func, args }));
+                    }
+                    if args.is_empty() && SIM_TIME_FUNCTIONS.contains(&name_lower.as_str()) {
+                        return Ok(Box::new(ExpressionNode::Variable { name: format!("sim.{}", name_lower) }));
+                    }
                     Ok(Box::new(ExpressionNode::FunctionCall {
                         func: crate::functions::ast::FunctionRef::from_name(&name.to_lowercase()),
                         args,
@@ -680,7 +829,7 @@ impl FunctionParser {
                             let offset_val = *n;
                             if offset_val.fract() != 0.0 {
                                 return Err(ParseError::SyntaxError {
-                                    position: self.tokenizer.position,
+                                    span: self.current_span(),
                                     message: format!("Offset must be an integer, got {}", offset_val),
                                 });
                             }
@@ -694,7 +843,7 @@ impl FunctionParser {
                                     let offset_val = *n;
                                     if offset_val.fract() != 0.0 {
                                         return Err(ParseError::SyntaxError {
-                                            position: self.tokenizer.position,
+                                            span: self.current_span(),
                                             message: format!("Offset must be an integer, got -{}", offset_val),
                                         });
                                     }
@@ -704,7 +853,7 @@ impl FunctionParser {
                                     return Err(ParseError::UnexpectedToken {
                                         expected: "integer after minus sign".to_string(),
                                         found: format!("{:?}", self.current_token),
-                                        position: self.tokenizer.position,
+                                        span: self.current_span(),
                                     });
                                 }
                             }
@@ -713,7 +862,7 @@ impl FunctionParser {
                             return Err(ParseError::UnexpectedToken {
                                 expected: "integer (negative for past, positive for future)".to_string(),
                                 found: format!("{:?}", self.current_token),
-                                position: self.tokenizer.position,
+                                span: self.current_span(),
                             });
                         }
                     };
@@ -722,7 +871,7 @@ impl FunctionParser {
                     // Expect comma
                     if self.current_token != Token::Comma {
                         return Err(ParseError::SyntaxError {
-                            position: self.tokenizer.position,
+                            span: self.current_span(),
                             message: "Offset syntax requires default value: [offset, default]. Example: data.flow[-1, 0.0] for yesterday's value".to_string(),
                         });
                     }
@@ -741,7 +890,7 @@ impl FunctionParser {
                                     return Err(ParseError::UnexpectedToken {
                                         expected: "number after minus sign".to_string(),
                                         found: format!("{:?}", self.current_token),
-                                        position: self.tokenizer.position,
+                                        span: self.current_span(),
                                     });
                                 }
                             }
@@ -750,7 +899,7 @@ impl FunctionParser {
                             return Err(ParseError::UnexpectedToken {
                                 expected: "default value (number or nan)".to_string(),
                                 found: format!("{:?}", self.current_token),
-                                position: self.tokenizer.position,
+                                span: self.current_span(),
                             });
                         }
                     };
@@ -760,7 +909,7 @@ impl FunctionParser {
                         return Err(ParseError::UnexpectedToken {
                             expected: "]".to_string(),
                             found: format!("{:?}", self.current_token),
-                            position: self.tokenizer.position,
+                            span: self.current_span(),
                         });
                     }
                     self.consume_token()?; // consume ']'
@@ -772,12 +921,13 @@ impl FunctionParser {
                 }
             }
             Token::LeftParen => {
+                let open = self.current_span();
                 self.consume_token()?; // consume '('
                 let expr = self.parse_expression()?;
                 
                 if self.current_token != Token::RightParen {
                     return Err(ParseError::UnmatchedParentheses {
-                        position: self.tokenizer.position,
+                        span: open,
                     });
                 }
                 
@@ -785,7 +935,7 @@ impl FunctionParser {
                 Ok(expr)
             }
             _ => Err(ParseError::SyntaxError {
-                position: self.tokenizer.position,
+                span: self.current_span(),
                 message: format!("Unexpected token: {:?}", self.current_token),
             }),
         }
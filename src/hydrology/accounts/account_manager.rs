@@ -3,6 +3,7 @@ use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account::Account;
 use crate::hydrology::accounts::maintenance::{MaintenanceGroup, MaintenanceType};
 use crate::hydrology::accounts::trigger::Trigger;
+use crate::tid::water_year::WaterYear;
 
 #[derive(Default, Clone)]
 pub struct AccountManager {
@@ -39,6 +40,11 @@ impl AccountManager {
 
 
     /// Add an account
+    /// Whether there are no accounts
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
     pub fn add_account(&mut self, account: Account) -> Result<usize, String> {
 
         // Check the name doesn't clash
@@ -46,6 +52,11 @@ impl AccountManager {
             return Err(format!("Tried to create account '{}' more than once.", &account.name));
         }
 
+        // A water year month of 0 means the account is never reset
+        if account.wy_month > 0 {
+            WaterYear::new(account.wy_month).map_err(|e| format!("Account '{}': {}", account.name, e))?;
+        }
+
         // Add the account to the vec & hashmap
         let idx = self.accounts.len();
         self.account_lookup.insert(account.name.clone(), idx);
@@ -85,7 +96,8 @@ impl AccountManager {
         let mut group_map: FxHashMap<(Trigger, MaintenanceType), Vec<usize>> = FxHashMap::default();
         for (account_idx, account) in self.accounts.iter().enumerate() {
             if account.is_unreg && (account.wy_month > 0) && (account.size > 0.0) {
-                let key = (Trigger::StartWaterYear(account.wy_month), MaintenanceType::SetFull);
+                let water_year = WaterYear::new(account.wy_month).expect("Checked when the account was added");
+                let key = (Trigger::StartWaterYear(water_year), MaintenanceType::SetFull);
                 group_map.entry(key).or_default().push(account_idx);
             }
         }
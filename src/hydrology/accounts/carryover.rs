@@ -0,0 +1,335 @@
+//! Allocation and carryover accounts of regulated users, from the `[allocation]` section
+//!
+//! ```ini
+//! [allocation]
+//! water_year = 7
+//! account.irrigator.storage = dam
+//! account.irrigator.allocation = min(5000, 0.1 * node.dam.volume)
+//! account.irrigator.carryover_limit = 2000
+//! ```
+//!
+//! Each account belongs to the regulated user it is named after, and its water is held in a
+//! supply storage. At the start of each water year the user is given a new allocation, and
+//! what it did not use of the last one is carried over, up to `carryover_limit` in all (0, so
+//! nothing, by default). The run starts with `initial_carryover` carried over (default 0) and
+//! `initial_allocation` allocated (by default the allocation expression, evaluated on the
+//! first timestep).
+//!
+//! Resources are assessed at the start of the water year and on the first day of each of the
+//! `assessment_months` (e.g. `assessment_months = 9, 11, 1`). At each assessment the inflow
+//! forecasts are updated (see [resource_assessment](super::resource_assessment)) and the
+//! allocation expression is evaluated again. An allocation is never reduced during the year:
+//! if the new one is larger, the increase is added to the balance. The allocation announced so
+//! far in the year is recorded as `acc.NAME.announced`.
+//!
+//! Each timestep the user's order is limited to its balance (carryover plus allocation), and
+//! what it diverts against its order is taken from carryover first. Carried-over water is
+//! stored against the user in the storage, so it bears its share of the storage's evaporation
+//! (in proportion to the carried water's part of the storage volume at the start of the
+//! timestep) and it is the first water to spill. Both losses are shared between the accounts
+//! on a storage in proportion to their carryover.
+//!
+//! The allocation expression is evaluated before the order phase, so node results are read
+//! from the previous timestep. Account results are recorded as `acc.NAME.allocation`,
+//! `acc.NAME.announced`, `acc.NAME.carryover`, `acc.NAME.balance`, `acc.NAME.evap_loss` and
+//! `acc.NAME.spill_loss`.
+
+use crate::data_management::data_cache::DataCache;
+use crate::hydrology::accounts::account_manager::make_acc_result_name;
+use crate::hydrology::accounts::resource_assessment::{InflowForecast, InflowForecastMethod, FORECAST_PREFIX};
+use crate::model_inputs::{DynamicInput, UserFunctions};
+use crate::nodes::{Node, NodeEnum};
+use crate::tid::water_year::WaterYear;
+use crate::timeseries::Timeseries;
+
+const ACCOUNT_PREFIX: &str = "account.";
+
+#[derive(Clone, Default)]
+pub struct CarryoverAccount {
+    /// Name of the regulated user the account belongs to, as written
+    pub name: String,
+    /// Name of the storage holding the account's water
+    pub storage: String,
+    /// Volume allocated for the water year, evaluated at each assessment
+    pub allocation: DynamicInput,
+    pub carryover_limit: f64,
+    pub initial_carryover: f64,
+    pub initial_allocation: Option<f64>,
+
+    // State
+    /// Allocation announced so far this water year
+    pub announced: f64,
+    pub allocation_balance: f64,
+    pub carryover: f64,
+    evap_loss: f64,
+    spill_loss: f64,
+
+    // Resolved at configure time
+    user_idx: usize,
+    storage_idx: usize,
+
+    // Recorders
+    recorder_idx_allocation: Option<usize>,
+    recorder_idx_announced: Option<usize>,
+    recorder_idx_carryover: Option<usize>,
+    recorder_idx_balance: Option<usize>,
+    recorder_idx_evap_loss: Option<usize>,
+    recorder_idx_spill_loss: Option<usize>,
+}
+
+impl CarryoverAccount {
+    /// Water the user can still take: carryover plus what is left of its allocation
+    pub fn balance(&self) -> f64 {
+        self.carryover + self.allocation_balance
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CarryoverAccounts {
+    pub water_year: WaterYear,
+    /// Months (Jan=1) of the assessments during the water year, besides its start
+    pub assessment_months: Vec<u32>,
+    pub forecasts: Vec<InflowForecast>,
+    pub accounts: Vec<CarryoverAccount>,
+    /// Volume of each account's storage at the start of the timestep, by account
+    storage_volumes: Vec<f64>,
+}
+
+impl CarryoverAccounts {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.forecasts.is_empty()
+    }
+
+    /// Adds a property of the `[allocation]` section: `water_year`, `assessment_months`,
+    /// `forecast.NAME.PROPERTY` or `account.NAME.PROPERTY`
+    pub fn add(&mut self, name: &str, value: &str, data_cache: &mut DataCache) -> Result<(), String> {
+        let name_lower = name.to_lowercase();
+        if name_lower == "water_year" {
+            let month = value.trim().parse::<u8>()
+                .map_err(|_| format!("Invalid water year start month '{}'", value.trim()))?;
+            self.water_year = WaterYear::new(month)?;
+            return Ok(());
+        }
+        if name_lower == "assessment_months" {
+            self.assessment_months = value.split(',')
+                .map(|m| m.trim().parse::<u32>().ok().filter(|m| (1..=12).contains(m))
+                    .ok_or(format!("Invalid assessment month '{}': must be 1 to 12", m.trim())))
+                .collect::<Result<_, _>>()?;
+            return Ok(());
+        }
+        if let Some((forecast_name, property)) = name_lower.strip_prefix(FORECAST_PREFIX).and_then(|rest| rest.rsplit_once('.')) {
+            let forecast_name = &name[FORECAST_PREFIX.len()..FORECAST_PREFIX.len() + forecast_name.len()];
+            return self.add_forecast_property(forecast_name, property, value, data_cache);
+        }
+        let (account_name, property) = name_lower.strip_prefix(ACCOUNT_PREFIX)
+            .and_then(|rest| rest.rsplit_once('.'))
+            .filter(|(account, _)| !account.is_empty())
+            .ok_or(format!("Invalid allocation property '{}'. Expected 'water_year', 'assessment_months', \
+                            'forecast.NAME.PROPERTY' or 'account.NAME.PROPERTY'", name))?;
+
+        // Keep the account name as written, so the section is saved the way it was read
+        let account_name = &name[ACCOUNT_PREFIX.len()..ACCOUNT_PREFIX.len() + account_name.len()];
+        let account = match self.accounts.iter().position(|a| a.name.eq_ignore_ascii_case(account_name)) {
+            Some(i) => &mut self.accounts[i],
+            None => {
+                self.accounts.push(CarryoverAccount { name: account_name.to_string(), ..Default::default() });
+                self.accounts.last_mut().unwrap()
+            }
+        };
+        let number = || value.trim().parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)
+            .ok_or(format!("Invalid '{}' value '{}': must be a number of at least 0", name, value.trim()));
+        match property {
+            "storage" => account.storage = value.trim().to_string(),
+            "allocation" => account.allocation = DynamicInput::from_string(value, data_cache, true, None)?,
+            "carryover_limit" => account.carryover_limit = number()?,
+            "initial_carryover" => account.initial_carryover = number()?,
+            "initial_allocation" => account.initial_allocation = Some(number()?),
+            _ => return Err(format!("Unknown allocation account property '{}'. Expected one of: storage, allocation, \
+                                     carryover_limit, initial_carryover, initial_allocation", property)),
+        }
+        Ok(())
+    }
+
+    fn add_forecast_property(&mut self, name: &str, property: &str, value: &str, data_cache: &mut DataCache) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("Inflow forecasts must be named, e.g. 'forecast.dam_inflow.series'".to_string());
+        }
+        let forecast = match self.forecasts.iter().position(|f| f.name.eq_ignore_ascii_case(name)) {
+            Some(i) => &mut self.forecasts[i],
+            None => {
+                self.forecasts.push(InflowForecast::new(name, data_cache));
+                self.forecasts.last_mut().unwrap()
+            }
+        };
+        match property {
+            "series" => forecast.series = value.trim().to_string(),
+            "method" => forecast.method = InflowForecastMethod::from_name(value)?,
+            "exceedance" => {
+                forecast.exceedance = value.trim().parse::<f64>().ok().filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or(format!("Invalid exceedance '{}' for inflow forecast '{}': must be a percentage from 0 to 100", value.trim(), name))?;
+            }
+            _ => return Err(format!("Unknown inflow forecast property '{}'. Expected one of: series, method, exceedance", property)),
+        }
+        Ok(())
+    }
+
+    /// Works out the inflow forecasts from their historical inflows, one series per forecast
+    pub fn assess_inflow_history(&mut self, histories: &[Timeseries]) -> Result<(), String> {
+        for (forecast, history) in self.forecasts.iter_mut().zip(histories) {
+            forecast.assess_history(history, self.water_year)?;
+        }
+        Ok(())
+    }
+
+    /// Finds each account's user and storage and its result recorders. References in the
+    /// allocation expressions to results only known after the flow phase are changed to read
+    /// the previous timestep's value.
+    pub fn resolve(&mut self, nodes: &[NodeEnum], functions: &UserFunctions, data_cache: &DataCache) -> Result<(), String> {
+        let find = |name: &str| nodes.iter().position(|n| n.get_name().eq_ignore_ascii_case(name));
+        for account in self.accounts.iter_mut() {
+            account.user_idx = find(&account.name)
+                .filter(|&i| matches!(nodes[i], NodeEnum::RegulatedUserNode(_)))
+                .ok_or(format!("Allocation account '{}' must be named after a regulated user node", account.name))?;
+            if account.storage.is_empty() {
+                return Err(format!("Allocation account '{}' has no storage. Add 'account.{}.storage = <node>' to [allocation]",
+                                   account.name, account.name));
+            }
+            account.storage_idx = find(&account.storage)
+                .filter(|&i| matches!(nodes[i], NodeEnum::StorageNode(_)))
+                .ok_or(format!("Allocation account '{}' is held in '{}', which is not a storage node", account.name, account.storage))?;
+            if matches!(account.allocation, DynamicInput::None { .. }) {
+                return Err(format!("Allocation account '{}' has no allocation. Add 'account.{}.allocation = <expression>' to [allocation]",
+                                   account.name, account.name));
+            }
+
+            for idx in account.allocation.current_step_references() {
+                if functions.is_after_flow_series(&data_cache.series_name[idx].to_lowercase()) {
+                    account.allocation.use_previous_value(idx)
+                        .map_err(|e| format!("Allocation account '{}': {}", account.name, e))?;
+                }
+            }
+
+            let recorder = |result: &str| data_cache.get_existing_series_idx(&make_acc_result_name(&account.name, result));
+            account.recorder_idx_allocation = recorder("allocation");
+            account.recorder_idx_announced = recorder("announced");
+            account.recorder_idx_carryover = recorder("carryover");
+            account.recorder_idx_balance = recorder("balance");
+            account.recorder_idx_evap_loss = recorder("evap_loss");
+            account.recorder_idx_spill_loss = recorder("spill_loss");
+        }
+        for forecast in &self.forecasts {
+            if forecast.series.is_empty() {
+                return Err(format!("Inflow forecast '{}' has no series. Add 'forecast.{}.series = <input series>' to [allocation]",
+                                   forecast.name, forecast.name));
+            }
+        }
+        self.storage_volumes = vec![0.0; self.accounts.len()];
+        Ok(())
+    }
+
+    /// Opens the accounts on the first timestep, rolls them over at the start of each water year
+    /// and reassesses allocations, then limits each user's order to its balance. Runs before
+    /// the order phase.
+    pub fn begin_timestep(&mut self, nodes: &mut [NodeEnum], data_cache: &mut DataCache) {
+        let first_step = data_cache.current_step == 0;
+        let new_year = data_cache.is_start_of_water_year(&self.water_year);
+        let month = data_cache.get_timestamp_month();
+        let assessment = first_step || new_year || (data_cache.get_timestamp_day() == 1
+            && data_cache.get_timestamp_seconds() == 0 && self.assessment_months.contains(&month));
+
+        // Inflow forecasts first, so that the allocations can use them
+        for forecast in self.forecasts.iter_mut() {
+            if assessment {
+                forecast.assess(month);
+            }
+            forecast.record(data_cache);
+        }
+
+        for (account, storage_volume) in self.accounts.iter_mut().zip(self.storage_volumes.iter_mut()) {
+            if first_step {
+                account.carryover = account.initial_carryover;
+                account.announced = account.initial_allocation
+                    .unwrap_or_else(|| account.allocation.get_value(data_cache).max(0.0));
+                account.allocation_balance = account.announced;
+            } else if new_year {
+                account.carryover = (account.carryover + account.allocation_balance).min(account.carryover_limit);
+                account.announced = account.allocation.get_value(data_cache).max(0.0);
+                account.allocation_balance = account.announced;
+            } else if assessment {
+                let increase = account.allocation.get_value(data_cache) - account.announced;
+                if increase > 0.0 {
+                    account.announced += increase;
+                    account.allocation_balance += increase;
+                }
+            }
+            if let NodeEnum::StorageNode(storage) = &nodes[account.storage_idx] {
+                *storage_volume = storage.volume;
+            }
+            if let NodeEnum::RegulatedUserNode(user) = &mut nodes[account.user_idx] {
+                user.account_limit = account.balance();
+            }
+        }
+    }
+
+    /// Charges carried-over water with its losses in the storage, takes each user's diversion
+    /// from its account, and records the results. Runs after the flow phase.
+    pub fn end_timestep(&mut self, nodes: &[NodeEnum], data_cache: &mut DataCache) {
+        for i in 0..self.accounts.len() {
+            let storage_idx = self.accounts[i].storage_idx;
+            let total_carryover: f64 = self.accounts.iter()
+                .filter(|a| a.storage_idx == storage_idx)
+                .map(|a| a.carryover)
+                .sum();
+            let (evap_loss, spill_loss) = match &nodes[storage_idx] {
+                NodeEnum::StorageNode(storage) if total_carryover > 0.0 => {
+                    let carried_fraction = (total_carryover / self.storage_volumes[i]).min(1.0);
+                    let evap_loss = (storage.evaporation() * carried_fraction).min(total_carryover);
+                    let spill_loss = storage.spill().min(total_carryover - evap_loss);
+                    (evap_loss, spill_loss)
+                }
+                _ => (0.0, 0.0),
+            };
+
+            // This account's share, in proportion to its carryover before any losses
+            let account = &mut self.accounts[i];
+            let share = if total_carryover > 0.0 { account.carryover / total_carryover } else { 0.0 };
+            account.evap_loss = evap_loss * share;
+            account.spill_loss = spill_loss * share;
+        }
+
+        for account in self.accounts.iter_mut() {
+            account.carryover = (account.carryover - account.evap_loss - account.spill_loss).max(0.0);
+            if let NodeEnum::RegulatedUserNode(user) = &nodes[account.user_idx] {
+                let from_carryover = user.diversion().min(account.carryover);
+                account.carryover -= from_carryover;
+                account.allocation_balance = (account.allocation_balance - (user.diversion() - from_carryover)).max(0.0);
+            }
+
+            // Record results
+            if let Some(idx) = account.recorder_idx_allocation {
+                data_cache.add_value_at_index(idx, account.allocation_balance);
+            }
+            if let Some(idx) = account.recorder_idx_announced {
+                data_cache.add_value_at_index(idx, account.announced);
+            }
+            if let Some(idx) = account.recorder_idx_carryover {
+                data_cache.add_value_at_index(idx, account.carryover);
+            }
+            if let Some(idx) = account.recorder_idx_balance {
+                data_cache.add_value_at_index(idx, account.balance());
+            }
+            if let Some(idx) = account.recorder_idx_evap_loss {
+                data_cache.add_value_at_index(idx, account.evap_loss);
+            }
+            if let Some(idx) = account.recorder_idx_spill_loss {
+                data_cache.add_value_at_index(idx, account.spill_loss);
+            }
+        }
+    }
+}
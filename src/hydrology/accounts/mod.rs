@@ -1,4 +1,6 @@
 pub mod account_manager;
 pub mod account;
+pub mod carryover;
+pub mod resource_assessment;
 mod maintenance;
 mod trigger;
\ No newline at end of file
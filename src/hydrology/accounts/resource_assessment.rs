@@ -0,0 +1,151 @@
+//! Inflow forecasts for the resource assessments of the `[allocation]` section
+//!
+//! ```ini
+//! [allocation]
+//! assessment_months = 7, 9, 11
+//! forecast.dam_inflow.series = data.inflows.by_name.dam
+//! forecast.dam_inflow.method = exceedance
+//! forecast.dam_inflow.exceedance = 95
+//! account.irrigator.allocation = min(5000, node.dam.volume[-1, 0] + forecast.dam_inflow - 3000)
+//! ```
+//!
+//! Allocations are usually announced from the water in storage plus a conservative forecast of
+//! the inflows still to come in the water year. A forecast is worked out from the whole of an
+//! input series, not just the simulation period. For each month, the inflow from the first day
+//! of the month to the end of the water year is summed in every complete water year of the
+//! data (years with missing values in that part are left out), and the forecast is:
+//!
+//! - `minimum`: the smallest of the sums, i.e. the historical minimum sequence (the default)
+//! - `exceedance`: the sum exceeded in `exceedance` percent of years (default 95)
+//!
+//! The forecast is recorded as the `forecast.NAME` series. It is updated at each assessment,
+//! and holds the forecast from the latest one in between.
+
+use crate::data_management::data_cache::DataCache;
+use crate::model_inputs::input_scaling::quantile;
+use crate::tid::utils::u64_to_year_month_day_and_seconds;
+use crate::tid::water_year::WaterYear;
+use crate::timeseries::Timeseries;
+
+/// Prefix of the series recording each inflow forecast
+pub const FORECAST_PREFIX: &str = "forecast.";
+
+/// How a forecast is drawn from the historical inflows
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum InflowForecastMethod {
+    #[default]
+    Minimum,
+    Exceedance,
+}
+
+impl InflowForecastMethod {
+    pub const ALL: [InflowForecastMethod; 2] = [InflowForecastMethod::Minimum, InflowForecastMethod::Exceedance];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InflowForecastMethod::Minimum => "minimum",
+            InflowForecastMethod::Exceedance => "exceedance",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<InflowForecastMethod, String> {
+        let lower = name.trim().to_lowercase();
+        InflowForecastMethod::ALL.into_iter()
+            .find(|m| m.as_str() == lower)
+            .ok_or_else(|| format!("Unknown inflow forecast method '{}'. Expected one of: {}", name,
+                                   InflowForecastMethod::ALL.map(|m| m.as_str()).join(", ")))
+    }
+}
+
+#[derive(Clone)]
+pub struct InflowForecast {
+    /// Name of the forecast as written, e.g. `dam_inflow` for `forecast.dam_inflow`
+    pub name: String,
+    /// The input series of historical inflows
+    pub series: String,
+    pub method: InflowForecastMethod,
+    /// Percentage of years in which the forecast is exceeded, for the exceedance method
+    pub exceedance: f64,
+    /// Forecast from the first day of each month (January first) to the end of the water year
+    by_month: [f64; 12],
+    value: f64,
+    series_idx: usize,
+}
+
+impl InflowForecast {
+    /// A forecast by the default method, registering its result series in the data cache
+    pub fn new(name: &str, data_cache: &mut DataCache) -> Self {
+        let series_idx = data_cache.get_or_add_new_series(&format!("{}{}", FORECAST_PREFIX, name.to_lowercase()), false);
+        InflowForecast {
+            name: name.to_string(),
+            series: String::new(),
+            method: InflowForecastMethod::default(),
+            exceedance: 95.0,
+            by_month: [f64::NAN; 12],
+            value: f64::NAN,
+            series_idx,
+        }
+    }
+
+    /// The forecast from the first day of `month` (Jan=1) to the end of the water year
+    pub fn for_month(&self, month: u32) -> f64 {
+        self.by_month[month as usize - 1]
+    }
+
+    /// Works out the forecast for each month from the historical inflows
+    pub fn assess_history(&mut self, history: &Timeseries, water_year: WaterYear) -> Result<(), String> {
+        let n = history.values.len();
+        let timestamp = |i: usize| history.timestamps.get(i).copied()
+            .unwrap_or(history.start_timestamp + i as u64 * history.step_size);
+        let no_years = || format!("Inflow forecast '{}': '{}' does not cover a complete water year", self.name, self.series);
+        if n == 0 {
+            return Err(no_years());
+        }
+
+        // Complete water years: from the first that starts within the data to the last that ends within it
+        let (y, m, d, s) = u64_to_year_month_day_and_seconds(timestamp(0));
+        let first_year = water_year.year_of_month(y, m) + if water_year.is_start(m, d, s) { 0 } else { 1 };
+        let (y, m, _, _) = u64_to_year_month_day_and_seconds(timestamp(n - 1) + history.step_size);
+        let last_year = water_year.year_of_month(y, m) - 1;
+        if last_year < first_year {
+            return Err(no_years());
+        }
+
+        // Inflow in each month of each year, by the month's position in the water year
+        let mut monthly = vec![[0.0; 12]; (last_year - first_year + 1) as usize];
+        for (i, &value) in history.values.iter().enumerate() {
+            let (y, m, _, _) = u64_to_year_month_day_and_seconds(timestamp(i));
+            let year = water_year.year_of_month(y, m);
+            if (first_year..=last_year).contains(&year) {
+                monthly[(year - first_year) as usize][water_year.month_of_year(m) as usize - 1] += value;
+            }
+        }
+
+        for month in 1..=12 {
+            let position = water_year.month_of_year(month) as usize - 1;
+            let mut sums: Vec<f64> = monthly.iter()
+                .map(|year| year[position..].iter().sum::<f64>())
+                .filter(|sum| sum.is_finite())
+                .collect();
+            if sums.is_empty() {
+                return Err(format!("Inflow forecast '{}': '{}' has missing values in every water year", self.name, self.series));
+            }
+            sums.sort_by(f64::total_cmp);
+            self.by_month[month as usize - 1] = match self.method {
+                InflowForecastMethod::Minimum => sums[0],
+                InflowForecastMethod::Exceedance => quantile(&sums, 1.0 - self.exceedance / 100.0),
+            };
+        }
+        Ok(())
+    }
+
+    /// Updates the forecast for an assessment in `month`
+    pub fn assess(&mut self, month: u32) {
+        self.value = self.for_month(month);
+    }
+
+    /// Records the forecast from the latest assessment
+    pub fn record(&self, data_cache: &mut DataCache) {
+        data_cache.add_value_at_index(self.series_idx, self.value);
+    }
+}
@@ -1,11 +1,12 @@
 use crate::data_management::data_cache::DataCache;
+use crate::tid::water_year::WaterYear;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Trigger {
     EveryTimestep(),
     StartMonth(),
     StartCalendarYear(),
-    StartWaterYear(u8), //Value is wy_month with Jan=1
+    StartWaterYear(WaterYear),
 }
 
 impl Trigger {
@@ -14,19 +15,7 @@ impl Trigger {
             Self::EveryTimestep() => true,
             Self::StartMonth() => data_cache.get_timestamp_day() == 1,
             Self::StartCalendarYear() => data_cache.get_day_of_year() == 1,
-            Self::StartWaterYear(wy_month) => {
-                let d = data_cache.get_timestamp_day();
-                if d == 1 {
-                    let m = data_cache.get_timestamp_month() as u8;
-                    if m == *wy_month {
-                        let s = data_cache.get_timestamp_seconds();
-                        if s == 0 {
-                            return true;
-                        }
-                    }
-                }
-                false
-            },
+            Self::StartWaterYear(water_year) => data_cache.is_start_of_water_year(water_year),
         }
     }
 }
@@ -0,0 +1,266 @@
+//! Constituent transport, e.g. salt carried with the flow
+//!
+//! Constituents are declared in the `[constituents]` section and enter the network with
+//! the lateral inflow of inflow nodes (`conc_<name>` keys) and with the runoff of rainfall
+//! runoff nodes. Each node is treated as fully mixed. Nodes without storage pass on the
+//! flow-weighted concentration of everything that entered them, so users and losses extract
+//! mass in proportion to the water they take. Storages and routing reaches mix their inflow
+//! with the water they hold, so evaporation concentrates a constituent and rainfall dilutes
+//! it. Each node reports how water moved through it with `Node::get_node_water`.
+//!
+//! Each constituent is defined by a comma-separated list of terms. The first gives how it
+//! behaves in stored water, and the others are optional:
+//!
+//! ```ini
+//! [constituents]
+//! salt = conservative
+//! tn = decay 0.05, emc 2.5, dwc 0.8
+//! tss = conservative, settling 0.3, emc 150, dwc 20
+//! ```
+//!
+//! `decay` is a first-order rate per day, `settling` a settling velocity in m/day (applied in
+//! storages, where the depth is the volume over the surface area), and `emc`/`dwc` are the
+//! event mean and dry weather concentrations of the quickflow and baseflow from rainfall
+//! runoff nodes.
+//!
+//! Concentrations are in the units of the inflow concentrations (e.g. mg/L), and a node's
+//! concentration is recorded as `node.<name>.constituent.<constituent>`.
+
+use crate::data_management::data_cache::DataCache;
+use crate::misc::misc_functions::make_result_name;
+use crate::nodes::{Node, NodeEnum};
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// How a constituent behaves between entering and leaving the network
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConstituentKind {
+    /// Neither created nor destroyed, e.g. salt
+    #[default]
+    Conservative,
+    /// Lost from stored water at a first-order rate per day, e.g. nutrients
+    Decay { rate_per_day: f64 },
+}
+
+impl ConstituentKind {
+    fn parse(term: &str) -> Result<ConstituentKind, String> {
+        let parts: Vec<&str> = term.split_whitespace().collect();
+        match parts.as_slice() {
+            ["conservative"] => Ok(ConstituentKind::Conservative),
+            ["decay", rate] => match parse_non_negative(rate) {
+                Some(rate_per_day) => Ok(ConstituentKind::Decay { rate_per_day }),
+                None => Err(format!("Invalid decay rate '{}'", rate)),
+            },
+            _ => Err(format!("Unknown constituent kind '{}'. Expected 'conservative' or 'decay <rate per day>'", term)),
+        }
+    }
+
+    fn to_definition(self) -> String {
+        match self {
+            ConstituentKind::Conservative => "conservative".to_string(),
+            ConstituentKind::Decay { rate_per_day } => format!("decay {}", rate_per_day),
+        }
+    }
+
+    /// Fraction of the mass held in a node that remains after one timestep
+    pub fn retained_fraction(&self, step_seconds: u64) -> f64 {
+        match self {
+            ConstituentKind::Conservative => 1.0,
+            ConstituentKind::Decay { rate_per_day } => (-rate_per_day * step_seconds as f64 / SECONDS_PER_DAY).exp(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Constituent {
+    /// Lowercase name, e.g. `salt`
+    pub name: String,
+    pub kind: ConstituentKind,
+    /// Settling velocity in storages [m/day]
+    pub settling_velocity: f64,
+    /// Event mean concentration of quickflow from rainfall runoff nodes
+    pub emc: f64,
+    /// Dry weather concentration of baseflow from rainfall runoff nodes
+    pub dwc: f64,
+}
+
+impl Constituent {
+    /// Parses a definition such as `decay 0.05, emc 2.5, dwc 0.8`
+    pub fn parse(name: &str, definition: &str) -> Result<Constituent, String> {
+        let mut terms = definition.split(',').map(|t| t.trim().to_lowercase());
+        let mut constituent = Constituent {
+            name: name.to_lowercase(),
+            kind: ConstituentKind::parse(terms.next().unwrap_or_default().as_str())?,
+            ..Default::default()
+        };
+        for term in terms {
+            let (option, value) = term.split_once(' ').unwrap_or((term.as_str(), ""));
+            let value = parse_non_negative(value)
+                .ok_or(format!("Invalid value in '{}'. Expected a non-negative number", term))?;
+            match option {
+                "settling" => constituent.settling_velocity = value,
+                "emc" => constituent.emc = value,
+                "dwc" => constituent.dwc = value,
+                _ => return Err(format!("Unknown constituent option '{}'. Expected 'settling', 'emc' or 'dwc'", option)),
+            }
+        }
+        Ok(constituent)
+    }
+
+    /// The canonical form of the definition, as written back to the model file
+    pub fn definition(&self) -> String {
+        let mut terms = vec![self.kind.to_definition()];
+        if self.settling_velocity > 0.0 {
+            terms.push(format!("settling {}", self.settling_velocity));
+        }
+        if self.emc > 0.0 {
+            terms.push(format!("emc {}", self.emc));
+        }
+        if self.dwc > 0.0 {
+            terms.push(format!("dwc {}", self.dwc));
+        }
+        terms.join(", ")
+    }
+
+    /// Fraction of the mass held in a store that remains after one timestep, from decay
+    /// and from settling out of water `depth_m` deep
+    fn retained_fraction(&self, step_seconds: u64, depth_m: f64) -> f64 {
+        let mut retained = self.kind.retained_fraction(step_seconds);
+        if self.settling_velocity > 0.0 && depth_m > 0.0 {
+            retained *= (-self.settling_velocity / depth_m * step_seconds as f64 / SECONDS_PER_DAY).exp();
+        }
+        retained
+    }
+}
+
+fn parse_non_negative(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)
+}
+
+/// How water moved through a node in one timestep, as far as mixing is concerned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeWater {
+    /// The node holds no water. `lateral_inflow` is water it added to the flow (e.g. runoff),
+    /// with the concentration given by `Node::get_inflow_concentration`.
+    PassThrough { lateral_inflow: f64 },
+    /// The node holds no water and added runoff to the flow, which carries each constituent's
+    /// `emc` in the quickflow and `dwc` in the baseflow.
+    Runoff { quickflow: f64, baseflow: f64 },
+    /// The node holds `volume` at the end of the timestep and released `outflow` during it.
+    /// `outflow` includes every release that carries constituents (downstream outlets,
+    /// diversions, seepage) but not evaporation. Constituents settle over `area_km2` (zero
+    /// for no settling).
+    Store { volume: f64, outflow: f64, area_km2: f64 },
+}
+
+/// Constituent state for every node in a model. Empty (and free to run) unless the model
+/// declares constituents.
+#[derive(Debug, Clone, Default)]
+pub struct ConstituentSystem {
+    pub constituents: Vec<Constituent>,
+    step_seconds: u64,
+    // The vectors below are indexed by node * n_constituents + constituent
+    usflow: Vec<f64>,
+    usmass: Vec<f64>,
+    stored_mass: Vec<f64>,
+    concentration: Vec<f64>,
+    recorder_idx: Vec<Option<usize>>,
+}
+
+impl ConstituentSystem {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constituents.is_empty()
+    }
+
+    pub fn get_constituent_idx(&self, name: &str) -> Option<usize> {
+        let name_lower = name.to_lowercase();
+        self.constituents.iter().position(|c| c.name == name_lower)
+    }
+
+    /// Resets the state for a run. Recorders are found here, so outputs must already be
+    /// registered in the data cache.
+    pub fn initialise(&mut self, nodes: &[NodeEnum], data_cache: &mut DataCache, step_seconds: u64) {
+        let n = nodes.len() * self.constituents.len();
+        self.step_seconds = step_seconds;
+        self.usflow = vec![0.0; nodes.len()];
+        self.usmass = vec![0.0; n];
+        self.stored_mass = vec![0.0; n];
+        self.concentration = vec![0.0; n];
+        self.recorder_idx = nodes.iter()
+            .flat_map(|node| self.constituents.iter().map(move |c| (node.get_name(), &c.name)))
+            .map(|(node_name, c_name)| {
+                data_cache.get_series_idx(make_result_name(node_name, &format!("constituent.{}", c_name)).as_str(), false)
+            })
+            .collect();
+    }
+
+    /// Clears the flow and mass arriving at each node
+    pub fn begin_timestep(&mut self) {
+        self.usflow.fill(0.0);
+        self.usmass.fill(0.0);
+    }
+
+    /// Mixes what arrived at a node after its flow phase has run, and records the result
+    pub fn mix(&mut self, node_idx: usize, node: &NodeEnum, data_cache: &mut DataCache) {
+        let n_c = self.constituents.len();
+        let water = node.get_node_water();
+        for c in 0..n_c {
+            let i = node_idx * n_c + c;
+            let constituent = &self.constituents[c];
+            let concentration = match water {
+                NodeWater::PassThrough { lateral_inflow } => {
+                    let lateral_inflow = lateral_inflow.max(0.0);
+                    let lateral_mass = match lateral_inflow > 0.0 {
+                        true => lateral_inflow * node.get_inflow_concentration(&constituent.name, data_cache),
+                        false => 0.0,
+                    };
+                    let water_in = self.usflow[node_idx] + lateral_inflow;
+                    if water_in > 0.0 { (self.usmass[i] + lateral_mass) / water_in } else { f64::NAN }
+                }
+                NodeWater::Runoff { quickflow, baseflow } => {
+                    let (quickflow, baseflow) = (quickflow.max(0.0), baseflow.max(0.0));
+                    let lateral_mass = quickflow * constituent.emc + baseflow * constituent.dwc;
+                    let water_in = self.usflow[node_idx] + quickflow + baseflow;
+                    if water_in > 0.0 { (self.usmass[i] + lateral_mass) / water_in } else { f64::NAN }
+                }
+                NodeWater::Store { volume, outflow, area_km2 } => {
+                    // ML over km2 is mm, so the depth in metres is V / (A * 1000)
+                    let depth_m = if area_km2 > 0.0 { volume.max(0.0) / (area_km2 * 1000.0) } else { 0.0 };
+                    let retained = constituent.retained_fraction(self.step_seconds, depth_m);
+                    let mass = self.stored_mass[i] * retained + self.usmass[i];
+                    let water = volume.max(0.0) + outflow.max(0.0);
+                    let concentration = if water > 0.0 { mass / water } else { f64::NAN };
+                    self.stored_mass[i] = if volume > 0.0 { concentration * volume } else { 0.0 };
+                    concentration
+                }
+            };
+            self.concentration[i] = concentration;
+            if let Some(idx) = self.recorder_idx[i] {
+                data_cache.add_value_at_index(idx, concentration);
+            }
+        }
+    }
+
+    /// Carries `flow` from one node to the next at the upstream node's concentration
+    pub fn transfer(&mut self, from_node: usize, to_node: usize, flow: f64) {
+        let n_c = self.constituents.len();
+        self.usflow[to_node] += flow;
+        for c in 0..n_c {
+            let concentration = self.concentration[from_node * n_c + c];
+            if concentration.is_finite() {
+                self.usmass[to_node * n_c + c] += flow * concentration;
+            }
+        }
+    }
+
+    /// Concentration at a node after the last timestep (NaN if no water passed through it)
+    pub fn get_concentration(&self, node_idx: usize, constituent_idx: usize) -> f64 {
+        self.concentration[node_idx * self.constituents.len() + constituent_idx]
+    }
+}
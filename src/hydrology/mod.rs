@@ -2,3 +2,4 @@
 pub mod rainfall_runoff;
 pub mod routing;
 pub mod accounts;
+pub mod constituents;
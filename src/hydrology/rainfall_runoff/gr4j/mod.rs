@@ -1,3 +1,5 @@
+use crate::hydrology::routing::unit_hydrograph::uh_dyn::UHDyn;
+
 /// Selects the model formulation. The two variants are structurally identical
 /// (same stores, splits, exchange and routing); they differ only in two
 /// constants that were recalibrated for sub-daily timesteps. See airGR
@@ -44,15 +46,10 @@ pub struct Gr4j {
     //Model formulation (daily GR4J vs sub-daily GR4H)
     pub variant: Gr4Variant,
 
-    //UH kernel
+    //Unit hydrographs (kernels and storages)
     uh1_len: usize,
-    uh2_len: usize,
-    uh1_ordinates: Vec<f64>,
-    uh2_ordinates: Vec<f64>,
-
-    //UH storages
-    uh1: Vec<f64>,
-    uh2: Vec<f64>,
+    uh1: UHDyn,
+    uh2: UHDyn,
 
     // Precomputed 1.0 / (perc_factor * x1), derived from variant + x1 in
     // initialize(). Keeps the percolation step in run_step() a single multiply
@@ -63,6 +60,9 @@ pub struct Gr4j {
     // Public so that gr4j nodes may read them
     pub production_store: f64,
     pub routing_store: f64,
+
+    // Routed part of the runoff from the last step [mm]. The rest is direct flow.
+    pub routed_flow: f64,
 }
 
 impl Gr4j {
@@ -73,10 +73,6 @@ impl Gr4j {
             x2: 0.0,
             x3: 90.0,
             x4: 1.7,
-            uh1_ordinates: Vec::new(),
-            uh2_ordinates: Vec::new(),
-            uh1: Vec::new(),
-            uh2: Vec::new(),
             production_store: 0.0,
             routing_store: 0.0,
             ..Default::default()
@@ -95,17 +91,13 @@ impl Gr4j {
         //Set up the unit hydrograph kernels and stores (OBS! THESE DEPEND ON x4 AND THE VARIANT)
         let uh_exponent = self.variant.uh_exponent();
         self.uh1_len = self.x4.ceil() as usize;
-        self.uh2_len = (2.0 * self.x4).ceil() as usize;
-        self.uh1_ordinates = vec![0.0; self.uh1_len];
-        self.uh2_ordinates = vec![0.0; self.uh2_len];
-        self.uh1 = vec![0.0; self.uh1_len];
-        self.uh2 = vec![0.0; self.uh2_len];
-        for t in 0..(self.uh1_len as usize) {
-            self.uh1_ordinates[t] = s_curves1(t + 1, self.x4, uh_exponent) - s_curves1(t, self.x4, uh_exponent);
-        }
-        for t in 0..(self.uh2_len as usize) {
-            self.uh2_ordinates[t] = s_curves2(t + 1, self.x4, uh_exponent) - s_curves2(t, self.x4, uh_exponent);
-        }
+        let uh2_len = (2.0 * self.x4).ceil() as usize;
+        self.uh1 = UHDyn::from_kernel((0..self.uh1_len)
+            .map(|t| s_curves1(t + 1, self.x4, uh_exponent) - s_curves1(t, self.x4, uh_exponent))
+            .collect());
+        self.uh2 = UHDyn::from_kernel((0..uh2_len)
+            .map(|t| s_curves2(t + 1, self.x4, uh_exponent) - s_curves2(t, self.x4, uh_exponent))
+            .collect());
 
         //Precompute the percolation divisor (run-invariant: depends only on variant and x1)
         self.inv_perc_x1 = 1.0 / (self.variant.perc_factor() * self.x1);
@@ -115,6 +107,23 @@ impl Gr4j {
         self.routing_store = 0.0;
     }
 
+    /// The production and routing stores, then the water held in the unit hydrographs
+    pub fn get_state(&self) -> Vec<f64> {
+        let mut state = vec![self.production_store, self.routing_store];
+        state.extend(self.uh1.get_state());
+        state.extend(self.uh2.get_state());
+        state
+    }
+
+    /// Restores a `get_state` taken with the same x4 and variant
+    pub fn set_state(&mut self, state: &[f64]) {
+        self.production_store = state[0];
+        self.routing_store = state[1];
+        let (uh1, uh2) = state[2..].split_at(self.uh1_len);
+        self.uh1.set_state(uh1);
+        self.uh2.set_state(uh2);
+    }
+
     /// Switch the model formulation. Re-initialises the UH kernels and the
     /// percolation divisor, both of which depend on the variant.
     pub fn set_variant(&mut self, variant: Gr4Variant) {
@@ -156,29 +165,22 @@ impl Gr4j {
         let pr = perc + pn - ps;
 
         //Unit hydrographs
-        let pr90 = pr * 0.9; //90% goes through UH1 and then non-linear routing
-        for i in 0..self.uh1_len - 1 {
-            self.uh1[i] = self.uh1[i + 1] + self.uh1_ordinates[i] * pr90;
-        }
-        self.uh1[self.uh1_len - 1] = self.uh1_ordinates[self.uh1_len - 1] * pr90;
-        let pr10 = pr * 0.1; //10% goes through UH2 and no routing
-        for i in 0..self.uh2_len - 1 {
-            self.uh2[i] = self.uh2[i + 1] + self.uh2_ordinates[i] * pr10;
-        }
-        self.uh2[self.uh2_len - 1] = self.uh2_ordinates[self.uh2_len - 1] * pr10;
+        let uh1_out = self.uh1.run_step(pr * 0.9); //90% goes through UH1 and then non-linear routing
+        let uh2_out = self.uh2.run_step(pr * 0.1); //10% goes through UH2 and no routing
 
         //Groundwater exchange rate
         let groundwater_exchange = self.x2 * (self.routing_store / self.x3).powf(3.5);
 
         //Routing store (applies to UH1)
-        self.routing_store = f64::max(0.0, self.routing_store + self.uh1[0] + groundwater_exchange);
+        self.routing_store = f64::max(0.0, self.routing_store + uh1_out + groundwater_exchange);
         let qr = self.routing_store * (1.0 - (1.0 + (self.routing_store / self.x3).powi(4)).powf(-0.25));
         self.routing_store -= qr;
 
         //Direct flow
-        let qd = f64::max(0.0, self.uh2[0] + groundwater_exchange);
+        let qd = f64::max(0.0, uh2_out + groundwater_exchange);
 
         //Return the total flow
+        self.routed_flow = qr;
         qr + qd
     }
 }
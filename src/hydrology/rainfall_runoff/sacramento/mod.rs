@@ -201,6 +201,11 @@ impl Sacramento {
 
 
     /// Public getter for laguh parameter
+    /// Baseflow part of the runoff from the last step [mm]. The rest is quickflow.
+    pub fn get_baseflow(&self) -> f64 {
+        self.baseflow
+    }
+
     pub fn get_laguh(&self) -> f64 {
         self.laguh
     }
@@ -249,6 +254,44 @@ impl Sacramento {
     }
 
 
+    /// Contents of the stores, in the order taken by [`Sacramento::set_stores`]
+    pub fn get_stores(&self) -> [f64; 5] {
+        [self.uztwc, self.uzfwc, self.lztwc, self.lzfsc, self.lzfpc]
+    }
+
+    /// The stores, the additional impervious area store, then the water in the unit hydrograph
+    pub fn get_state(&self) -> Vec<f64> {
+        let mut state = self.get_stores().to_vec();
+        state.push(self.adimc);
+        state.extend(self.unit_hydrograph.get_state());
+        state
+    }
+
+    /// Restores a `get_state` taken with the same parameters
+    pub fn set_state(&mut self, state: &[f64]) {
+        let mut stores = [0.0; 5];
+        stores.copy_from_slice(&state[..5]);
+        self.set_stores(stores);
+        self.adimc = state[5];
+        self.unit_hydrograph.set_state(&state[6..]);
+    }
+
+    /// Sets the contents of the upper zone tension and free water stores and the lower zone
+    /// tension, supplementary free and primary free water stores, in that order, each limited
+    /// to its capacity, and updates the state variables that depend on them.
+    pub fn set_stores(&mut self, stores: [f64; 5]) -> &mut Self {
+        self.uztwc = stores[0].min(self.uztwm);
+        self.uzfwc = stores[1].min(self.uzfwm);
+        self.lztwc = stores[2].min(self.lztwm);
+        self.lzfsc = stores[3].min(self.lzfsm);
+        self.lzfpc = stores[4].min(self.lzfpm);
+        self.alzfsc = self.lzfsc * (1f64 + self.side);
+        self.alzfpc = self.lzfpc * (1f64 + self.side);
+        self.adimc = self.uztwc + self.lztwc;
+        self
+    }
+
+
     /**
      *
      */
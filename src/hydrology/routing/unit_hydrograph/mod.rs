@@ -2,4 +2,22 @@ pub mod uh_dyn;
 
 pub mod uh_prealloc_32;
 
-
+/// Runs a step of a unit hydrograph held in a circular buffer: spreads `input` over the
+/// storages by the kernel, then releases the storage at `head` and advances it. The storages
+/// from the head to the end of the buffer take the start of the kernel and those before it the
+/// rest, so both are contiguous loops the compiler can vectorise, with no modulo per ordinate.
+#[inline]
+pub(crate) fn ring_step(storage: &mut [f64], kernel: &[f64], head: &mut usize, input: f64) -> f64 {
+    let len = storage.len();
+    let (before_head, from_head) = storage.split_at_mut(*head);
+    let (kernel_from_head, kernel_before_head) = kernel.split_at(len - *head);
+    for (s, &k) in from_head.iter_mut().zip(kernel_from_head) {
+        *s += input * k;
+    }
+    for (s, &k) in before_head.iter_mut().zip(kernel_before_head) {
+        *s += input * k;
+    }
+    let answer = std::mem::take(&mut storage[*head]);
+    *head = if *head + 1 == len { 0 } else { *head + 1 };
+    answer
+}
@@ -1,8 +1,12 @@
 use crate::timeseries::Timeseries;
+use super::ring_step;
 
+/// A unit hydrograph of any length, held in a circular buffer
+#[derive(Default, Clone)]
 pub struct UHDyn {
     kernel: Vec<f64>,
     storage: Vec<f64>,
+    head: usize,  // Circular buffer head pointer
 }
 
 impl UHDyn {
@@ -11,6 +15,7 @@ impl UHDyn {
         let mut answer = UHDyn {
             kernel: Vec::with_capacity(l),
             storage: Vec::with_capacity(l),
+            head: 0,
         };
         for _ in 0..length {
             answer.kernel.push(0.0);
@@ -20,6 +25,15 @@ impl UHDyn {
         return answer;
     }
 
+    /// A unit hydrograph with the given kernel, and empty storages
+    pub fn from_kernel(kernel: Vec<f64>) -> UHDyn {
+        UHDyn {
+            storage: vec![0.0; kernel.len()],
+            kernel,
+            head: 0,
+        }
+    }
+
     pub fn set_kernel(&mut self, i: i32, value: f64) {
         let u = i as usize;
         self.kernel[u] = value;
@@ -29,6 +43,7 @@ impl UHDyn {
         for i in 0..self.storage.len() {
             self.storage[i] = 0.0;
         }
+        self.head = 0;
         if (self.get_kernel_sum() - 1f64).abs() > 0.000001 {
             panic!("Kernel sum must be equal to 1");
         }
@@ -42,17 +57,20 @@ impl UHDyn {
         sum
     }
 
+    /// The water held in the storages, starting from the next to be released
+    pub fn get_state(&self) -> Vec<f64> {
+        let (before_head, from_head) = self.storage.split_at(self.head);
+        [from_head, before_head].concat()
+    }
+
+    /// Restores a `get_state` of a unit hydrograph of the same length
+    pub fn set_state(&mut self, state: &[f64]) {
+        self.storage.copy_from_slice(state);
+        self.head = 0;
+    }
+
     pub fn run_step(&mut self, input_value: f64) -> f64 {
-        let n = self.kernel.len();
-        for i in 0..n {
-            self.storage[i] += input_value * self.kernel[i];
-        }
-        let answer = self.storage[0];
-        for i in 0..n - 1 {
-            self.storage[i] = self.storage[i + 1];
-        }
-        self.storage[n - 1] = 0.0;
-        return answer;
+        ring_step(&mut self.storage, &self.kernel, &mut self.head, input_value)
     }
 
     pub fn run(&mut self, input_timeseries: Timeseries) -> Timeseries {
@@ -70,4 +88,4 @@ impl UHDyn {
         //Return the results
         return answer;
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,6 @@
 
+use super::ring_step;
+
 #[derive(Default)]
 #[derive(Clone)]
 pub struct UHPrealloc32 {
@@ -8,7 +10,8 @@ pub struct UHPrealloc32 {
     need to use all of them, and this will work normally for any UH with
     32 or fewer elements.
 
-    Uses a circular buffer to avoid shifting the entire array each timestep.
+    Uses a circular buffer to avoid shifting the entire array each timestep,
+    and runs it without a modulo per ordinate (see ring_step).
      */
     kernel: [f64; 32],
     storage: [f64; 32],
@@ -54,6 +57,18 @@ impl UHPrealloc32 {
     }
 
 
+    /// The water held in the storages, starting from the next to be released
+    pub fn get_state(&self) -> Vec<f64> {
+        (0..self.len).map(|i| self.storage[(self.head + i) % self.len]).collect()
+    }
+
+    /// Restores a `get_state` of a unit hydrograph of the same length
+    pub fn set_state(&mut self, state: &[f64]) {
+        self.storage[..self.len].copy_from_slice(state);
+        self.head = 0;
+    }
+
+
     pub fn get_kernel_sum(&self) -> f64 {
         let mut sum = 0f64;
         for i in 0..self.len {
@@ -63,21 +78,6 @@ impl UHPrealloc32 {
     }
 
     pub fn run_step(&mut self, input_value: f64) -> f64 {
-        // Add input weighted by kernel at circular positions
-        for i in 0..self.len {
-            let pos = (self.head + i) % self.len;
-            self.storage[pos] += input_value * self.kernel[i];
-        }
-
-        // Get output from current head position
-        let answer = self.storage[self.head];
-
-        // Zero out the position we just output from
-        self.storage[self.head] = 0.0;
-
-        // Advance the head pointer (circular)
-        self.head = (self.head + 1) % self.len;
-
-        answer
+        ring_step(&mut self.storage[..self.len], &self.kernel[..self.len], &mut self.head, input_value)
     }
 }
\ No newline at end of file
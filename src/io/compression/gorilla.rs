@@ -182,7 +182,10 @@ impl GorillaCompressor {
     }
 
     fn compress_timestamp(&self, writer: &mut BitWriter, timestamp: u64, prev_timestamp: u64, prev_delta: u64) -> u64 {
-        let delta = timestamp - prev_timestamp;
+        // Timestamps are Unix seconds offset by 2^63, so they stay in order through 1970 (just
+        // below 2^63 before it, just above after it). Wrapping arithmetic lets a timestamp that
+        // goes backwards give a negative delta rather than overflow; the decoder wraps it back.
+        let delta = timestamp.wrapping_sub(prev_timestamp);
 
         if delta == self.timestep {
             // Common case: regular timestep
@@ -195,7 +198,7 @@ impl GorillaCompressor {
             prev_delta
         } else {
             // Need to encode delta of deltas
-            let delta_of_deltas = (delta as i64) - (prev_delta as i64);
+            let delta_of_deltas = (delta as i64).wrapping_sub(prev_delta as i64);
             writer.write_bit(true);
             writer.write_bit(true);
 
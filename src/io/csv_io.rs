@@ -1,7 +1,7 @@
 extern crate csv;
 
 use crate::timeseries::Timeseries;
-use crate::tid::utils::{date_string_to_u64_flexible, date_string_to_u64_with_format, u64_to_date_string_for_step_size};
+use crate::tid::utils::{date_string_to_u64_flexible, date_string_to_u64_with_format, u64_to_date_string_for_step_size, u64_to_year_month_day_and_seconds};
 use std::fs;
 use std::path::Path;
 
@@ -23,15 +23,32 @@ impl From<CsvError> for String {
 }
 
 pub fn read_ts(filename: &str) -> Result<Vec<Timeseries>, String> {
-    // Here is where we will construct our result
-    let mut answer: Vec<Timeseries> = Vec::new();
-
     // Create a new csv reader with flexible record lengths
-    // This allows rows with trailing commas (extra empty fields) without error
-    let mut reader = csv::ReaderBuilder::new()
+    // This allows rows with trailing commas (extra empty fields) without error. Lines
+    // starting with '#' are comments, e.g. the provenance header of a run's outputs.
+    let reader = csv::ReaderBuilder::new()
         .flexible(true)
+        .comment(Some(b'#'))
         .from_path(filename)
         .map_err(|e| format!("Failed to open file '{}': {}", filename, e))?;
+    read_ts_from_reader(reader, filename)
+}
+
+
+/// Reads timeseries from CSV text held in memory (same layout as [`read_ts`]). `source` names
+/// the text in error messages.
+pub fn read_ts_from_str(csv_text: &str, source: &str) -> Result<Vec<Timeseries>, String> {
+    let reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_reader(csv_text.as_bytes());
+    read_ts_from_reader(reader, source)
+}
+
+
+fn read_ts_from_reader<R: std::io::Read>(mut reader: csv::Reader<R>, filename: &str) -> Result<Vec<Timeseries>, String> {
+    // Here is where we will construct our result
+    let mut answer: Vec<Timeseries> = Vec::new();
 
     // Get the first row (what csv crate thinks are headers)
     let first_row = reader.headers()
@@ -117,6 +134,11 @@ pub fn read_ts(filename: &str) -> Result<Vec<Timeseries>, String> {
         let t_str = record.get(0)
             .ok_or_else(|| format!("Missing timestamp in '{}' line {}", filename, file_line))?;
 
+        // A units row under the header (see CsvWriteOptions::units) is not data
+        if has_header && detected_format.is_none() && t_str.trim().eq_ignore_ascii_case(UNITS_ROW_LABEL) {
+            continue;
+        }
+
         // Detect format on first data row
         let t_u64 = if detected_format.is_none() {
             let (timestamp, format) = date_string_to_u64_flexible(t_str)
@@ -203,51 +225,165 @@ fn infer_step_size(timestamps: &[u64]) -> Result<Option<u64>, String> {
 
 
 pub fn write_ts(filename: &str, timeseries_vector: Vec<&Timeseries>) -> Result<(), CsvError> {
+    write_ts_with_comments(filename, timeseries_vector, &[])
+}
+
+
+/// Writes timeseries as for [`write_ts`], after a `# <comment>` line for each comment, e.g. the
+/// provenance of a run. The readers here skip lines starting with `#`.
+pub fn write_ts_with_comments(filename: &str, timeseries_vector: Vec<&Timeseries>, comments: &[String]) -> Result<(), CsvError> {
+    let options = CsvWriteOptions { comments: comments.to_vec(), ..CsvWriteOptions::default() };
+    write_ts_wide(filename, &timeseries_vector, &options).map(|_| ())
+}
+
+
+/// First cell of the units row of a wide CSV
+const UNITS_ROW_LABEL: &str = "Units";
+
+
+/// Layout of a wide CSV written by [`write_ts_wide`]. The default is the layout of [`write_ts`].
+#[derive(Clone, Debug)]
+pub struct CsvWriteOptions {
+    /// Header of the date column
+    pub date_header: String,
+    /// Units of each series in order, written as a row under the header starting `Units`.
+    /// The readers here skip that row.
+    pub units: Option<Vec<String>>,
+    /// Written in place of missing (NaN) values, e.g. `""` or `-9999`
+    pub missing_value: String,
+    /// Write one file per calendar year, named `<stem>_<year>.<ext>`, rather than one file
+    pub split_by_year: bool,
+    /// Written as `# <comment>` lines at the top of each file
+    pub comments: Vec<String>,
+    /// Separates the columns, e.g. `\t` for TSV
+    pub delimiter: char,
+    /// Separates the whole and fractional parts of values
+    pub decimal_separator: char,
+}
+
+impl Default for CsvWriteOptions {
+    fn default() -> Self {
+        Self {
+            date_header: "Time".to_string(),
+            units: None,
+            missing_value: "NaN".to_string(),
+            split_by_year: false,
+            comments: vec![],
+            delimiter: ',',
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl CsvWriteOptions {
+    /// Tab-separated values
+    pub fn tsv() -> Self {
+        Self { delimiter: '\t', ..Self::default() }
+    }
+
+    /// Semicolon-separated values with decimal commas, as Excel reads CSV in European locales
+    pub fn ssv() -> Self {
+        Self { delimiter: ';', decimal_separator: ',', ..Self::default() }
+    }
+
+    /// The layout for a file by its extension: `.tsv` for [`CsvWriteOptions::tsv`], `.ssv` for
+    /// [`CsvWriteOptions::ssv`], and comma-separated otherwise
+    pub fn for_path(filename: &str) -> Self {
+        let lower = filename.to_ascii_lowercase();
+        if lower.ends_with(".tsv") {
+            Self::tsv()
+        } else if lower.ends_with(".ssv") {
+            Self::ssv()
+        } else {
+            Self::default()
+        }
+    }
+}
+
+
+/// Writes timeseries to a wide CSV: an ISO date column, then one column per series headed by
+/// its name. Returns the paths of the files written.
+pub fn write_ts_wide(filename: &str, timeseries_vector: &[&Timeseries], options: &CsvWriteOptions) -> Result<Vec<String>, CsvError> {
 
     // Check that all timeseries in the vector have the same length
-    let data_length = match timeseries_vector.len() {
-        0 => { 0 }
-        _ => {timeseries_vector[0].timestamps.len() }
-    };
-    for tsv in &timeseries_vector {
-        if tsv.timestamps.len() != data_length {
+    let timestamps: &[u64] = timeseries_vector.first().map_or(&[], |ts| &ts.timestamps);
+    for tsv in timeseries_vector {
+        if tsv.timestamps.len() != timestamps.len() {
             return Err(CsvError::WriteError("Cannot handle timeseries with different lengths.".to_string()))
         }
     }
 
-    // Starting building the file contents, starting with the header row
-    let mut data_string = String::new();
-    data_string.push_str("Time");
-    for ts in timeseries_vector.iter() {
-        data_string.push_str(",");
-        data_string.push_str(&ts.name);
+    // Build the header, which starts every file
+    let mut header = String::new();
+    for comment in &options.comments {
+        header.push_str(&format!("# {}\r\n", comment));
+    }
+    header.push_str(&options.date_header);
+    for ts in timeseries_vector {
+        header.push(options.delimiter);
+        header.push_str(&ts.name);
+    }
+    header.push_str("\r\n");
+    if let Some(units) = &options.units {
+        if units.len() != timeseries_vector.len() {
+            return Err(CsvError::WriteError(format!(
+                "Got units for {} series but writing {}.", units.len(), timeseries_vector.len())));
+        }
+        header.push_str(UNITS_ROW_LABEL);
+        for unit in units {
+            header.push(options.delimiter);
+            header.push_str(unit);
+        }
+        header.push_str("\r\n");
+    }
+
+    // Split the rows into one file, or a file for each year
+    let mut files: Vec<(String, std::ops::Range<usize>)> = vec![];
+    if options.split_by_year && !timestamps.is_empty() {
+        let year = |i: usize| u64_to_year_month_day_and_seconds(timestamps[i]).0;
+        let path = Path::new(filename);
+        let stem = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().to_string());
+        let extension = path.extension().map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
+        let mut start = 0;
+        for i in 1..=timestamps.len() {
+            if i == timestamps.len() || year(i) != year(start) {
+                let year_path = path.with_file_name(format!("{}_{}{}", stem, year(start), extension));
+                files.push((year_path.to_string_lossy().to_string(), start..i));
+                start = i;
+            }
+        }
+    } else {
+        files.push((filename.to_string(), 0..timestamps.len()));
     }
-    data_string.push_str("\r\n");
 
-    // Build the data section. Pick a single date format for the whole file based on the
-    // step_size of the first series (all series in a write share the same step_size in
+    // Build the data section of each file. Pick a single date format for the whole file based
+    // on the step_size of the first series (all series in a write share the same step_size in
     // practice). Sub-daily data gets ISO datetime; daily-or-coarser gets date-only.
-    let mut i = 0;
-    if timeseries_vector.len() > 0 {
-        let step_size = timeseries_vector[0].step_size;
-        for timestamp in timeseries_vector[0].timestamps.iter() {
-            let timestamp_string = u64_to_date_string_for_step_size(*timestamp, step_size);
-            data_string.push_str(&timestamp_string);
-            for ts in timeseries_vector.iter() {
+    let step_size = timeseries_vector.first().map_or(0, |ts| ts.step_size);
+    for (path, rows) in &files {
+        let mut data_string = header.clone();
+        for i in rows.clone() {
+            data_string.push_str(&u64_to_date_string_for_step_size(timestamps[i], step_size));
+            for ts in timeseries_vector {
                 let value = ts.values[i];
-                data_string.push_str(format!(",{value}").as_str());
+                data_string.push(options.delimiter);
+                if value.is_nan() {
+                    data_string.push_str(&options.missing_value);
+                } else if options.decimal_separator != '.' {
+                    data_string.push_str(&value.to_string().replace('.', &options.decimal_separator.to_string()));
+                } else {
+                    data_string.push_str(&value.to_string());
+                }
             }
             data_string.push_str("\r\n");
-            i += 1;
         }
-    }
 
-    // Write it all to file
-    let filename_path = Path::new(filename);
-    match fs::write(filename_path, data_string) {
-        Ok(_) => Ok(()),
-        Err(_) => Err(CsvError::WriteError(format!("Error writing file {filename}.")))
+        // Write it all to file
+        if fs::write(Path::new(path), data_string).is_err() {
+            return Err(CsvError::WriteError(format!("Error writing file {path}.")));
+        }
     }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
 }
 
 
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use indexmap::IndexMap;
 
 #[derive(Debug, Clone)]
@@ -11,6 +12,45 @@ pub struct IniProperty {
     pub valid: bool,                  // Used for mark-and-sweep updates
 }
 
+impl IniProperty {
+    /// Finds the line number and column (both from 1) in the file of a character of the
+    /// joined `value`, following it onto continuation lines where necessary. A position
+    /// past the end of the value is just after its last character.
+    pub fn value_position(&self, char_idx: usize) -> (usize, usize) {
+        // Position in the file of every character in the joined value
+        let mut positions: Vec<(usize, usize)> = vec![];
+        for (i, raw_line) in self.raw_lines.iter().enumerate() {
+            let offset = match i {
+                0 => match raw_line.find('=') {
+                    Some(eq_pos) => eq_pos + 1,
+                    None => break,
+                },
+                _ => 0,
+            };
+            let text = &raw_line[offset..];
+            let start = offset + text.len() - text.trim_start().len();
+            let rest = &raw_line[start..];
+            let part = match IniDocument::find_comment_start(rest) {
+                Some(comment_pos) => &rest[..comment_pos],
+                None => rest,
+            }.trim_end();
+            if part.is_empty() {
+                continue;
+            }
+            if let Some(&(line, column)) = positions.last() {
+                positions.push((line, column + 1)); // The space joining continuation lines
+            }
+            let column = raw_line[..start].chars().count() + 1;
+            positions.extend((0..part.chars().count()).map(|k| (self.line_number + i, column + k)));
+        }
+        match (positions.get(char_idx), positions.last()) {
+            (Some(&position), _) => position,
+            (None, Some(&(line, column))) => (line, column + 1),
+            (None, None) => (self.line_number, 1),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IniSection {
     pub properties: IndexMap<String, IniProperty>,
@@ -19,8 +59,19 @@ pub struct IniSection {
     pub valid: bool,                   // Used for mark-and-sweep updates
 }
 
+/// An `include = other.ini` directive, which comes before the first section
+#[derive(Debug, Clone)]
+pub struct IniInclude {
+    pub path: String,                  // Path of the included file, relative to the including file
+    pub line_number: usize,            // Line where the directive appears
+    pub comment: Option<String>,       // Inline comment
+    pub raw_line: String,              // Original line for round-tripping
+    pub leading_lines: Vec<String>,    // Comments and blank lines before the directive
+}
+
 #[derive(Debug, Clone)]
 pub struct IniDocument {
+    pub includes: Vec<IniInclude>,      // Files to include, not yet resolved
     pub sections: IndexMap<String, IniSection>,
     pub trailing_comments: Vec<String>, // Comments at end of file
 }
@@ -35,12 +86,14 @@ impl IniDocument {
     /// Create a new empty IniDocument
     pub fn new() -> Self {
         IniDocument {
+            includes: Vec::new(),
             sections: IndexMap::new(),
             trailing_comments: Vec::new(),
         }
     }
 
     pub fn parse(content: &str) -> Result<Self, String> {
+        let mut includes = Vec::new();
         let mut sections = IndexMap::new();
         let mut trailing_comments = Vec::new();
         let mut state = ParseState::BetweenSections;
@@ -91,6 +144,31 @@ impl IniDocument {
                 let key = trimmed[..eq_pos].trim().to_string();
                 let mut value_part = trimmed[eq_pos + 1..].trim();
 
+                // Include directives before the first section
+                if key == "include" {
+                    if let ParseState::InSection(section_name) = &state {
+                        return Err(format!("Error on line {}: include found in section '{}', but includes must come before the first section",
+                                           line_number, section_name));
+                    }
+                    let (path, comment) = match Self::find_comment_start(value_part) {
+                        Some(comment_pos) => (value_part[..comment_pos].trim(), Some(value_part[comment_pos..].trim().to_string())),
+                        None => (value_part, None),
+                    };
+                    if path.is_empty() {
+                        return Err(format!("Error on line {}: include needs the path of a file", line_number));
+                    }
+                    includes.push(IniInclude {
+                        path: path.to_string(),
+                        line_number,
+                        comment,
+                        raw_line: line.to_string(),
+                        leading_lines: pending_comments.clone(),
+                    });
+                    pending_comments.clear();
+                    line_idx += 1;
+                    continue;
+                }
+
                 // Check for inline comment
                 let mut inline_comment = None;
                 if let Some(comment_pos) = Self::find_comment_start(value_part) {
@@ -202,6 +280,7 @@ impl IniDocument {
         trailing_comments.extend(pending_comments);
 
         Ok(IniDocument {
+            includes,
             sections,
             trailing_comments,
         })
@@ -329,6 +408,15 @@ impl IniDocument {
     pub fn to_string(&self) -> String {
         let mut result = String::new();
 
+        for include in &self.includes {
+            for comment in &include.leading_lines {
+                result.push_str(comment);
+                result.push('\n');
+            }
+            result.push_str(&include.raw_line);
+            result.push('\n');
+        }
+
         // Sort sections for consistent output
         // Iterate in insertion order (preserved by IndexMap)
         for section_name in self.sections.keys() {
@@ -403,6 +491,119 @@ impl IniDocument {
         result
     }
 
+    /// Apply an overlay document on top of this one, e.g. a scenario that only changes a few
+    /// properties of a base model.
+    ///
+    /// Each overlay property replaces the property with the same key (keeping its position)
+    /// or is appended to the section, and new sections are appended. A key prefixed with
+    /// '-' removes that property (e.g. `-node.a.ds_1` in `[outputs]`), and a section header
+    /// prefixed with '-' (e.g. `[-node.old_dam]`) removes the whole section. Removing
+    /// something that does not exist is an error, since it usually means a typo.
+    pub fn apply_overlay(&mut self, overlay: IniDocument) -> Result<(), String> {
+        for (section_name, overlay_section) in overlay.sections {
+            if let Some(removed) = section_name.strip_prefix('-') {
+                if self.sections.shift_remove(removed.trim()).is_none() {
+                    return Err(format!("Error on line {}: Cannot remove section '{}' as it does not exist",
+                                       overlay_section.line_number, removed.trim()));
+                }
+                continue;
+            }
+            let section = self.sections.entry(section_name.clone()).or_insert_with(|| IniSection {
+                properties: IndexMap::new(),
+                leading_lines: overlay_section.leading_lines.clone(),
+                line_number: overlay_section.line_number,
+                valid: true,
+            });
+            for (key, mut property) in overlay_section.properties {
+                if let Some(removed) = key.strip_prefix('-') {
+                    if section.properties.shift_remove(removed.trim()).is_none() {
+                        return Err(format!("Error on line {}: Cannot remove '{}' from section '{}' as it does not exist",
+                                           property.line_number, removed.trim(), section_name));
+                    }
+                    continue;
+                }
+                if let Some(existing) = section.properties.get(&key) {
+                    property.leading_lines = existing.leading_lines.clone();
+                }
+                section.properties.insert(key, property);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the `include` directives with the sections of the files they name, so that
+    /// shared sections such as `[inputs]`, `[constants]` and `[tables]` can be kept in one
+    /// file and used by a family of scenario models.
+    ///
+    /// Included files are read relative to `base_dir`, the folder of the including file, and
+    /// may include other files themselves. They are combined in order, and then this
+    /// document is applied on top of them as an overlay (see `apply_overlay`), so a model
+    /// can replace or remove (with a '-' prefix) anything it includes. Errors name the file
+    /// and line of the include that caused them, with `source` naming this document, and an
+    /// include that leads back to a file already being included is an error. Paths inside
+    /// the included sections (e.g. `[inputs]`) are not changed, so they are still relative to
+    /// the model's folder.
+    pub fn resolve_includes(&mut self, base_dir: &Path, source: &str) -> Result<(), String> {
+        self.resolve_includes_within(base_dir, source, &mut Vec::new())
+    }
+
+    /// Reads and parses a file, resolving its includes relative to the file's folder
+    pub fn read_file(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
+        let mut doc = Self::parse(content.as_str())?;
+        if !doc.includes.is_empty() {
+            let canonical = Path::new(path).canonicalize()
+                .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
+            let base_dir = canonical.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+            doc.resolve_includes_within(&base_dir, path, &mut vec![canonical])?;
+        }
+        Ok(doc)
+    }
+
+    /// Resolves includes, with `stack` holding the files currently being included
+    fn resolve_includes_within(&mut self, base_dir: &Path, source: &str, stack: &mut Vec<PathBuf>) -> Result<(), String> {
+        if self.includes.is_empty() {
+            return Ok(());
+        }
+        let mut combined: Option<IniDocument> = None;
+        for include in std::mem::take(&mut self.includes) {
+            let location = format!("'{}' on line {}", source, include.line_number);
+            let path = base_dir.join(&include.path);
+            let canonical = path.canonicalize()
+                .map_err(|e| format!("Error in {}: Failed to read included file '{}': {}", location, include.path, e))?;
+            if stack.contains(&canonical) {
+                let cycle: Vec<String> = stack.iter().chain(std::iter::once(&canonical))
+                    .map(|p| p.display().to_string())
+                    .collect();
+                return Err(format!("Error in {}: Including '{}' creates a cycle: {}", location, include.path, cycle.join(" -> ")));
+            }
+            let content = std::fs::read_to_string(&canonical)
+                .map_err(|e| format!("Error in {}: Failed to read included file '{}': {}", location, include.path, e))?;
+            let included_source = path.display().to_string();
+            let mut included = IniDocument::parse(&content)
+                .map_err(|e| format!("Included file '{}' (included from {}): {}", included_source, location, e))?;
+            let included_dir = canonical.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+            stack.push(canonical);
+            included.resolve_includes_within(&included_dir, &included_source, stack)?;
+            stack.pop();
+            match combined.as_mut() {
+                None => combined = Some(included),
+                Some(doc) => doc.apply_overlay(included)
+                    .map_err(|e| format!("Included file '{}' (included from {}): {}", included_source, location, e))?,
+            }
+        }
+
+        // This document goes on top of everything it includes
+        let mut combined = combined.unwrap_or_else(IniDocument::new);
+        let own = std::mem::replace(self, IniDocument::new());
+        let trailing_comments = own.trailing_comments.clone();
+        combined.apply_overlay(own).map_err(|e| format!("'{}': {}", source, e))?;
+        combined.trailing_comments = trailing_comments;
+        *self = combined;
+        Ok(())
+    }
+
     /// Convert to the HashMap format expected by existing model loading code
     pub fn to_legacy_format(&self) -> HashMap<String, HashMap<String, Option<String>>> {
         let mut result = HashMap::new();
@@ -456,6 +657,50 @@ key3 = value3
         assert_eq!(section2.properties["key3"].value, "value3");
     }
 
+    #[test]
+    fn test_apply_overlay() {
+        let mut doc = IniDocument::parse(r#"
+[node.a]
+# Catchment area
+area = 10
+ds_1 = b
+
+[node.b]
+type = blackhole
+
+[outputs]
+node.a.ds_1
+node.b.dsflow
+"#).unwrap();
+        let overlay = IniDocument::parse(r#"
+[node.a]
+area = 20
+meta_scenario = wet
+
+[outputs]
+-node.b.dsflow
+node.a.usflow
+
+[node.c]
+type = blackhole
+"#).unwrap();
+        doc.apply_overlay(overlay).unwrap();
+
+        let a = &doc.sections["node.a"];
+        assert_eq!(a.properties.keys().collect::<Vec<_>>(), vec!["area", "ds_1", "meta_scenario"]);
+        assert_eq!(a.properties["area"].value, "20");
+        assert_eq!(a.properties["area"].leading_lines, vec!["# Catchment area"]);
+        assert_eq!(doc.sections["outputs"].properties.keys().collect::<Vec<_>>(), vec!["node.a.ds_1", "node.a.usflow"]);
+        assert_eq!(doc.sections.keys().collect::<Vec<_>>(), vec!["node.a", "node.b", "outputs", "node.c"]);
+
+        doc.apply_overlay(IniDocument::parse("[-node.c]\n").unwrap()).unwrap();
+        assert!(!doc.sections.contains_key("node.c"));
+
+        // Removing something that is not there is an error
+        assert!(doc.apply_overlay(IniDocument::parse("[-node.c]\n").unwrap()).is_err());
+        assert!(doc.apply_overlay(IniDocument::parse("[node.a]\n-bogus\n").unwrap()).is_err());
+    }
+
     #[test]
     fn test_line_continuation() {
         let content = r#"
@@ -0,0 +1,282 @@
+//! Canonical formatting of model INI files (`kalix fmt`)
+//!
+//! The file is rewritten from its parsed [`IniDocument`] rather than by building a model, so
+//! comments survive and no input data needs to be found. The formatted file has:
+//!
+//! * `key = value` spacing, and a single blank line between sections,
+//! * `type` then `loc` first in each node section, then the properties of that node type in
+//!   the order Kalix writes them, any others in their original order, and the links last,
+//! * the rows of multi-line numeric tables aligned in columns, and
+//! * every comment, kept with the property or section it comes before.
+//!
+//! Formatting never changes a value: the formatted file is parsed again and compared with the
+//! original before it is returned.
+
+use crate::io::custom_ini_parser::{IniDocument, IniProperty};
+
+/// Properties of each node type in the order they are written, after `type` and `loc`
+const NODE_PROPERTY_ORDER: &[(&str, &[&str])] = &[
+    ("blackhole", &[]),
+    ("confluence", &["harmony_fraction"]),
+    ("gauge", &["force_flow", "reference_flow", "min_flow", "residual", "reference_level", "rating",
+        "quality", "quality_threshold"]),
+    ("order_control", &["min_order", "max_order", "set_order", "delay_order_steps"]),
+    ("gr4j", &["variant", "evap", "rain", "area", "params", "initial_stores"]),
+    ("inflow", &["inflow", "expected_inflow"]),
+    ("loss", &["table", "loss_scale", "monthly_factors", "loss_factor", "return_fraction", "return_lag"]),
+    ("routing", &["n_divs", "x", "lag", "nlm", "pwl", "split_table", "typical_regulated_flow",
+        "initial_volume"]),
+    ("sacramento", &["evap", "rain", "area", "params", "initial_stores"]),
+    ("splitter", &["table", "ds_2_flow", "ds_2_target", "ds_2_min", "ds_2_max"]),
+    ("transfer", &["capacity", "trigger", "loss_fraction"]),
+    ("storage", &["evap", "rain", "seep", "pond_demand", "target_level", "initial_volume",
+        "order_through", "shortfall_sharing", "dimensions"]),
+    ("unregulated_user", &["demand", "demand_multiplier", "pump", "flow_threshold", "commence_threshold",
+        "account", "annual_cap", "demand_carryover"]),
+    ("regulated_user", &["order", "demand_multiplier", "pump", "off_allocation", "off_allocation_volume",
+        "priority", "entitlement"]),
+];
+
+/// Suffixes of the properties of a link, written after its `ds_N`
+const LINK_PROPERTY_SUFFIXES: &[&str] = &["capacity", "efficiency"];
+
+/// Gap between a value and its inline comment
+const COMMENT_GAP: &str = "  ";
+
+/// Formats the content of a model INI file
+pub fn format_ini(content: &str) -> Result<String, String> {
+    let doc = IniDocument::parse(content)?;
+    let mut out = String::new();
+    for (i, include) in doc.includes.iter().enumerate() {
+        let leading = match i {
+            0 => without_leading_blank(tidy_lines(&include.leading_lines)),
+            _ => tidy_lines(&include.leading_lines),
+        };
+        push_lines(&mut out, &leading);
+        match &include.comment {
+            Some(comment) => out.push_str(&format!("include = {}{}{}\n", include.path, COMMENT_GAP, comment)),
+            None => out.push_str(&format!("include = {}\n", include.path)),
+        }
+    }
+    for (section_name, section) in &doc.sections {
+        // Comments straight after the previous section stay with it, and a blank line
+        // separates the sections
+        let leading = match out.is_empty() {
+            true => without_leading_blank(tidy_lines(&section.leading_lines)),
+            false => tidy_lines(&section.leading_lines),
+        };
+        if !out.is_empty() && !leading.iter().any(|line| line.is_empty()) {
+            out.push('\n');
+        }
+        push_lines(&mut out, &leading);
+        out.push_str(&format!("[{}]\n", section_name));
+
+        let keys: Vec<&String> = section.properties.keys().collect();
+        let ordered = match (section_name.starts_with("node."), section.properties.get("type")) {
+            (true, Some(node_type)) => node_property_order(&keys, node_type.value.trim()),
+            _ => keys,
+        };
+        for (j, key) in ordered.into_iter().enumerate() {
+            let property = &section.properties[key];
+            let leading = match j {
+                0 => without_leading_blank(tidy_lines(&property.leading_lines)),
+                _ => tidy_lines(&property.leading_lines),
+            };
+            push_lines(&mut out, &leading);
+            out.push_str(&format_property(key, property));
+        }
+    }
+    let mut trailing = tidy_lines(&doc.trailing_comments);
+    if trailing.last().is_some_and(|line| line.is_empty()) {
+        trailing.pop();
+    }
+    if out.is_empty() {
+        trailing = without_leading_blank(trailing);
+    }
+    push_lines(&mut out, &trailing);
+
+    check_unchanged(&doc, &out)?;
+    Ok(out)
+}
+
+/// Whether the content of a model INI file is already formatted
+pub fn is_formatted(content: &str) -> Result<bool, String> {
+    Ok(format_ini(content)? == content)
+}
+
+/// Orders the properties of a node section: `type`, `loc`, the node type's own properties,
+/// any others as they were, then each link followed by its properties
+fn node_property_order<'a>(keys: &[&'a String], node_type: &str) -> Vec<&'a String> {
+    let known: &[&str] = NODE_PROPERTY_ORDER.iter()
+        .find(|(name, _)| *name == node_type)
+        .map_or(&[], |(_, properties)| properties);
+    let link_number = |key: &str| -> Option<u32> {
+        let n = key.strip_prefix("ds_")?;
+        if n.chars().all(|c| c.is_ascii_digit()) { n.parse().ok() } else { None }
+    };
+    let link_property = |key: &str| -> Option<(u32, usize)> {
+        LINK_PROPERTY_SUFFIXES.iter().enumerate().find_map(|(rank, suffix)| {
+            let n = key.strip_suffix(suffix)?.strip_suffix('_')?;
+            link_number(n).map(|n| (n, rank + 1))
+        })
+    };
+
+    let mut ordered: Vec<&String> = vec![];
+    for name in ["type", "loc"].iter().chain(known) {
+        if let Some(key) = keys.iter().find(|key| key.as_str() == *name) {
+            ordered.push(key);
+        }
+    }
+    let mut links: Vec<(u32, usize, &String)> = vec![];
+    for key in keys {
+        if ordered.contains(key) {
+            continue;
+        }
+        match (link_number(key), link_property(key)) {
+            (Some(n), _) => links.push((n, 0, key)),
+            (None, Some((n, rank))) => links.push((n, rank, key)),
+            (None, None) => ordered.push(key),
+        }
+    }
+    links.sort_by_key(|(n, rank, _)| (*n, *rank));
+    ordered.extend(links.into_iter().map(|(_, _, key)| key));
+    ordered
+}
+
+/// Formats a property as `key = value`, or a list item as itself, putting the rows of a
+/// multi-line value under the first
+fn format_property(key: &str, property: &IniProperty) -> String {
+    if property.comments.is_empty() {
+        // A list item, e.g. in [outputs]
+        return format!("{}\n", key);
+    }
+
+    // The value and comment of each line, leaving out blank continuation lines
+    let rows: Vec<(String, Option<&String>)> = property.raw_lines.iter().enumerate()
+        .filter_map(|(i, raw_line)| {
+            let comment = property.comments.get(i).and_then(|c| c.as_ref());
+            let text = match i {
+                0 => raw_line.split_once('=').map_or("", |(_, rest)| rest),
+                _ => raw_line.as_str(),
+            };
+            let text = match comment {
+                Some(comment) => text.trim().strip_suffix(comment.as_str()).unwrap_or(text),
+                None => text,
+            }.trim();
+            (i == 0 || !text.is_empty() || comment.is_some()).then(|| (text.to_string(), comment))
+        })
+        .collect();
+    let values: Vec<&str> = rows.iter().map(|(value, _)| value.as_str()).collect();
+    let values = if rows.len() > 1 { align_table(&values) } else { values.iter().map(|v| v.to_string()).collect() };
+
+    let prefix = format!("{} = ", key);
+    let indent = " ".repeat(prefix.chars().count());
+    let has_comments = rows.iter().any(|(_, comment)| comment.is_some());
+    let width = values.iter().map(|v| v.chars().count()).max().unwrap_or(0);
+    let mut text = String::new();
+    for (i, (value, (_, comment))) in values.iter().zip(&rows).enumerate() {
+        let mut line = format!("{}{}", if i == 0 { &prefix } else { &indent }, value);
+        if let Some(comment) = comment {
+            if rows.len() > 1 && has_comments {
+                line.push_str(&" ".repeat(width - value.chars().count()));
+            }
+            line.push_str(COMMENT_GAP);
+            line.push_str(comment);
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+/// Pads the cells of the rows of a numeric table so its columns line up. Rows that are not
+/// all numbers are left as they are.
+fn align_table(rows: &[&str]) -> Vec<String> {
+    let split = |row: &str| -> (Vec<String>, bool) {
+        let trailing_comma = row.ends_with(',');
+        let row = row.strip_suffix(',').unwrap_or(row);
+        let cells = if row.trim().is_empty() { vec![] } else { row.split(',').map(|c| c.trim().to_string()).collect() };
+        (cells, trailing_comma)
+    };
+    let table: Vec<(Vec<String>, bool)> = rows.iter().map(|row| split(row)).collect();
+    let numeric = table.iter().all(|(cells, _)| cells.iter().all(|c| c.parse::<f64>().is_ok()));
+    if !numeric {
+        return rows.iter().map(|row| row.to_string()).collect();
+    }
+
+    let n_cols = table.iter().map(|(cells, _)| cells.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..n_cols)
+        .map(|col| table.iter().filter_map(|(cells, _)| cells.get(col)).map(|c| c.len()).max().unwrap_or(0))
+        .collect();
+    table.iter().map(|(cells, trailing_comma)| {
+        let mut line = String::new();
+        for (col, cell) in cells.iter().enumerate() {
+            line.push_str(cell);
+            if col + 1 < cells.len() {
+                line.push(',');
+                line.push_str(&" ".repeat(widths[col] - cell.len() + 1));
+            }
+        }
+        if *trailing_comma {
+            line.push(',');
+        }
+        line
+    }).collect()
+}
+
+/// Trims whitespace from comment and blank lines, and collapses each run of blank lines
+/// into one
+fn tidy_lines(lines: &[String]) -> Vec<String> {
+    let mut tidy: Vec<String> = vec![];
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() && tidy.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        tidy.push(line.to_string());
+    }
+    tidy
+}
+
+/// Drops a blank line from the start, where the formatter decides the spacing itself
+fn without_leading_blank(mut lines: Vec<String>) -> Vec<String> {
+    if lines.first().is_some_and(|line| line.is_empty()) {
+        lines.remove(0);
+    }
+    lines
+}
+
+fn push_lines(out: &mut String, lines: &[String]) {
+    for line in lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Checks that the formatted file has the same sections, properties and values as the
+/// original. Values are compared without whitespace since only spacing may change.
+fn check_unchanged(original: &IniDocument, formatted: &str) -> Result<(), String> {
+    let formatted = IniDocument::parse(formatted)
+        .map_err(|e| format!("Formatting produced a file that could not be read: {}", e))?;
+    let squash = |s: &str| -> String { s.chars().filter(|c| !c.is_whitespace()).collect() };
+    let include_paths = |doc: &IniDocument| -> Vec<String> { doc.includes.iter().map(|i| i.path.clone()).collect() };
+    if include_paths(&formatted) != include_paths(original) {
+        return Err("Formatting would change the includes.".to_string());
+    }
+    for (section_name, section) in &original.sections {
+        let Some(formatted_section) = formatted.sections.get(section_name) else {
+            return Err(format!("Formatting would lose section [{}].", section_name));
+        };
+        if formatted_section.properties.len() != section.properties.len() {
+            return Err(format!("Formatting would change the properties of section [{}].", section_name));
+        }
+        for (key, property) in &section.properties {
+            match formatted_section.properties.get(key) {
+                Some(p) if squash(&p.value) == squash(&property.value) => {}
+                _ => return Err(format!("Formatting would change '{}' in section [{}] (line {}).",
+                    key, section_name, property.line_number)),
+            }
+        }
+    }
+    Ok(())
+}
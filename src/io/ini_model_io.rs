@@ -1,6 +1,6 @@
 use crate::model::Model;
 use crate::io::custom_ini_parser::IniDocument;
-use crate::io::ini_model_io_versions::ini_doc_model_io_0_0_1::{ini_doc_to_model_0_0_1, model_to_ini_doc_0_0_1};
+use crate::io::ini_model_io_versions::ini_doc_model_io_0_0_1::{ini_doc_to_model_0_0_1, model_to_ini_doc_0_0_1, render_canonical_0_0_1};
 
 #[derive(Default)]
 pub struct IniModelIO {
@@ -19,7 +19,8 @@ impl IniModelIO {
     ///
     /// This function takes an INI-formatted file containing a complete model definition
     /// and converts it into a Model object. The format must follow the Kalix model
-    /// specification.
+    /// specification. Files named by `include` directives are read relative to the model
+    /// file and combined with it (see `IniDocument::resolve_includes`).
     ///
     /// # Arguments
     ///
@@ -31,9 +32,38 @@ impl IniModelIO {
     /// * `Err(String)` - Error message describing parsing failure, validation error, or
     ///   unsupported format version.
     pub fn read_model_file(&self, path: &str) -> Result<Model, String> {
-        // Read file content
-        let content = std::fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
+        self.read_model_file_with_overlays(path, &[])
+    }
+
+    /// Parses a hydrological model from a base model file and a sequence of overlay files.
+    ///
+    /// An overlay is an INI file holding only the sections and properties that differ from
+    /// the base model (e.g. a different demand input, a larger storage). The overlays are
+    /// applied in order as described in `IniDocument::apply_overlay`, so later overlays win.
+    /// Relative paths are resolved against the base model's folder, whichever file they
+    /// appear in.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string slice containing the path to the base model file.
+    /// * `overlay_paths` - Paths to the overlay files, in the order they are applied.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Model)` - Successfully parsed and validated model ready for simulation
+    /// * `Err(String)` - Error message describing parsing failure (naming the overlay file if
+    ///   the error is in one), validation error, or unsupported format version.
+    pub fn read_model_file_with_overlays(&self, path: &str, overlay_paths: &[String]) -> Result<Model, String> {
+        // Read file content, along with any files it includes
+        let mut ini_doc = IniDocument::read_file(path)?;
+        for overlay_path in overlay_paths {
+            let overlay_content = std::fs::read_to_string(overlay_path)
+                .map_err(|e| format!("Failed to read file '{}': {}", overlay_path, e))?;
+            let overlay = IniDocument::parse(overlay_content.as_str())
+                .map_err(|e| format!("Overlay '{}': {}", overlay_path, e))?;
+            ini_doc.apply_overlay(overlay)
+                .map_err(|e| format!("Overlay '{}': {}", overlay_path, e))?;
+        }
 
         // Convert to absolute path and extract the directory containing the model file
         let abs_path = std::path::Path::new(path)
@@ -55,7 +85,7 @@ impl IniModelIO {
 
         // Parse the model with the working directory set BEFORE loading any data
         // This allows relative paths in the INI to be resolved correctly
-        let model = self.read_model_string_with_working_directory(content.as_str(), Some(model_dir))?;
+        let model = Self::ini_doc_to_model_with_working_directory(ini_doc, Some(model_dir))?;
 
         Ok(model)
     }
@@ -84,7 +114,8 @@ impl IniModelIO {
     /// # Arguments
     ///
     /// * `ini_string` - A string slice containing the complete INI-formatted model definition
-    /// * `working_directory` - Optional working directory for resolving relative paths
+    /// * `working_directory` - Optional working directory for resolving relative paths,
+    ///   including the files named by `include` directives (the current directory if None)
     ///
     /// # Returns
     ///
@@ -92,7 +123,9 @@ impl IniModelIO {
     /// * `Err(String)` - Error message describing parsing failure, validation error, or
     ///   unsupported format version.
     pub fn read_model_string_with_working_directory(&self, ini_string: &str, working_directory: Option<std::path::PathBuf>) -> Result<Model, String> {
-        let ini_doc = IniDocument::parse(ini_string)?;
+        let mut ini_doc = IniDocument::parse(ini_string)?;
+        let base_dir = working_directory.clone().unwrap_or_else(|| std::path::PathBuf::from("."));
+        ini_doc.resolve_includes(&base_dir, "model")?;
         let model = Self::ini_doc_to_model_with_working_directory(ini_doc, working_directory)?;
         Ok(model)
     }
@@ -164,4 +197,55 @@ impl IniModelIO {
         // Convert to string
         ini_doc.to_string()
     }
-}
\ No newline at end of file
+
+    /// Convert a Model to an INI document, keeping the formatting of the file it was read
+    /// from for everything that did not change
+    pub fn model_to_ini_doc(&self, model: &Model) -> IniDocument {
+        model_to_ini_doc_0_0_1(model)
+    }
+
+    /// Convert a Model to an INI document in canonical form, ignoring the formatting of the
+    /// file it was read from. Useful for comparing models.
+    pub fn model_to_canonical_ini_doc(&self, model: &Model) -> IniDocument {
+        render_canonical_0_0_1(model)
+    }
+
+    /// Convert a Model to a JSON value with one entry per INI section.
+    ///
+    /// `[kalix]` and `[constants]` become objects of string values, `[inputs]` becomes an array of
+    /// `{"path": ..}` objects (with an `"alias"` when the input is aliased), every `[node.NAME]`
+    /// becomes an entry of the `"nodes"` object, and `[outputs]` becomes an array of series names.
+    /// Property values are kept as the strings that would be written to the INI file.
+    pub fn model_to_json(&self, model: &Model) -> serde_json::Value {
+        use serde_json::{json, Map, Value};
+
+        let ini_doc = model_to_ini_doc_0_0_1(model);
+        let mut root = Map::new();
+        let mut nodes = Map::new();
+        for (section_name, section) in &ini_doc.sections {
+            let properties = || -> Map<String, Value> {
+                section.properties.iter()
+                    .map(|(key, prop)| (key.clone(), Value::String(prop.value.clone())))
+                    .collect()
+            };
+            if let Some(node_name) = section_name.strip_prefix("node.") {
+                nodes.insert(node_name.to_string(), Value::Object(properties()));
+            } else if section_name == "inputs" {
+                let inputs = section.properties.iter()
+                    .map(|(key, prop)| match prop.value.is_empty() {
+                        true => json!({ "path": key }),
+                        false => json!({ "alias": key, "path": prop.value }),
+                    })
+                    .collect();
+                root.insert(section_name.clone(), Value::Array(inputs));
+            } else if section_name == "outputs" {
+                let outputs = section.properties.keys().map(|k| Value::String(k.clone())).collect();
+                root.insert(section_name.clone(), Value::Array(outputs));
+            } else {
+                root.insert(section_name.clone(), Value::Object(properties()));
+            }
+        }
+        root.insert("nodes".to_string(), Value::Object(nodes));
+        Value::Object(root)
+    }
+}
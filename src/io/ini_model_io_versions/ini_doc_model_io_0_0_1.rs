@@ -1,15 +1,24 @@
+use crate::data_management::data_cache::DataCache;
+use crate::functions::closest_match;
 use crate::hydrology::accounts::account::Account;
 use crate::io::csv_io::{csv_string_to_f64_vec, csv_to_string_vec};
-use crate::io::custom_ini_parser::{IniDocument, IniSection};
+use crate::io::custom_ini_parser::{IniDocument, IniProperty, IniSection};
+use crate::io::ini_templates::expand_templates;
+use crate::misc::configuration::{CircularReferences, DEFAULT_NEGATIVE_TOLERANCE, DEFAULT_SPIN_UP_MAX_LOOPS, DEFAULT_SPIN_UP_TOLERANCE};
 use crate::misc::location::Location;
-use crate::model_inputs::DynamicInput;
+use crate::model_inputs::{Coverage, DynamicInput, InputCoverage, InputExtension, InputNanPolicy, InputResampling, InputScaling, NanPolicy, Resample};
+use crate::model_inputs::user_functions::FUNCTION_PREFIX;
 use crate::numerical::table::Table;
 use crate::model::Model;
 use crate::misc::link_helper::LinkHelper;
-use crate::tid::utils::{date_string_to_u64_flexible, u64_to_date_string_for_step_size};
-use crate::misc::misc_functions::{is_valid_variable_name, split_interleaved, parse_csv_to_bool_option_u8, require_non_empty, format_vec_as_multiline_table, set_property_if_not_empty, set_property_unless_default, format_f64};
-use crate::nodes::{NodeEnum, blackhole_node::BlackholeNode, confluence_node::ConfluenceNode, gauge_node::GaugeNode, loss_node::LossNode, splitter_node::SplitterNode, regulated_user_node::RegulatedUserNode, unregulated_user_node::UnregulatedUserNode, gr4j_node::Gr4jNode, inflow_node::InflowNode, routing_node::RoutingNode, sacramento_node::SacramentoNode, storage_node::StorageNode, order_control_node::OrderControlNode, Node};
+use crate::tid::utils::{date_string_to_u64_flexible, u64_to_date_string, u64_to_date_string_for_step_size};
+use crate::tid::water_year::WaterYear;
+use crate::hydrology::accounts::resource_assessment::InflowForecastMethod;
+use crate::misc::misc_functions::{is_valid_variable_name, true_or_false, split_interleaved, parse_csv_to_bool_option_u8, require_non_empty, format_vec_as_multiline_table, set_property_if_not_empty, set_property_unless_default, format_f64};
+use crate::nodes::{NodeEnum, NodeMetadata, blackhole_node::BlackholeNode, confluence_node::ConfluenceNode, gauge_node::GaugeNode, loss_node::LossNode, splitter_node::SplitterNode, transfer_node::TransferNode, regulated_user_node::RegulatedUserNode, unregulated_user_node::UnregulatedUserNode, gr4j_node::Gr4jNode, inflow_node::InflowNode, routing_node::RoutingNode, sacramento_node::SacramentoNode, storage_node::StorageNode, order_control_node::OrderControlNode, Node};
 use crate::hydrology::rainfall_runoff::gr4j::Gr4Variant;
+use crate::hydrology::constituents::Constituent;
+use crate::ordering::shortfall_sharing::SharingRule;
 use crate::nodes::storage_node::OutletDefinition;
 use crate::nodes::storage_node::OutletDefinition::{OutletWithMOLAndCapacity, OutletWithMOL};
 
@@ -18,6 +27,8 @@ const DS_1_OUTLET: u8 = 0; //ds_1 is outlet 0
 const DS_2_OUTLET: u8 = 1; //ds_2 is outlet 1
 const DS_3_OUTLET: u8 = 2; //ds_3 is outlet 2
 const DS_4_OUTLET: u8 = 3; //ds_4 is outlet 3
+const META_PREFIX: &str = "meta_"; //user metadata keys on nodes
+const CONC_PREFIX: &str = "conc_"; //constituent concentration keys on inflow nodes
 
 
 
@@ -28,7 +39,7 @@ const DS_4_OUTLET: u8 = 3; //ds_4 is outlet 3
 /// * `ini_doc` - The parsed INI document
 /// * `working_directory` - Optional working directory for resolving relative paths.
 ///   If None, uses the current working directory.
-pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<std::path::PathBuf>) -> Result<Model, String> {
+pub fn ini_doc_to_model_0_0_1(mut ini_doc: IniDocument, working_directory: Option<std::path::PathBuf>) -> Result<Model, String> {
 
     // Create a new model
     let mut model = Model::new();
@@ -41,13 +52,23 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
     // Store a copy of the ini_doc in the model for later use
     model.ini_document = Some(ini_doc.clone());
 
+    // Templated sub-networks become ordinary node sections
+    expand_templates(&mut ini_doc)?;
+
     // For building links I need to keep a list of link details, and then create the links
     // after all the nodes are done. The function model.add_link(...) accepts node and outlet
     // indices rather than names. So I'll need to know those indices.
     let mut vec_link_defs: Vec<LinkHelper> = Vec::new();
 
+    // Concentrations are checked against [constituents] once every section has been read,
+    // as the sections may come in any order. Each entry is (node, constituent, line).
+    let mut concentration_refs: Vec<(String, String, usize)> = Vec::new();
+
+    // Link attributes are applied once the links exist. Each entry is (node, outlet, key, value, line).
+    let mut link_attributes: Vec<(String, u8, String, f64, usize)> = Vec::new();
+
     // Iterate over the sections of the ini_doc and construct the model as we go
-    for (section_name, ini_section) in ini_doc.sections {
+    for (section_name, mut ini_section) in ini_doc.sections {
 
         if section_name == "kalix" {
             // -------------------------------------------------------------------------------------
@@ -62,6 +83,46 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                 } else if name_lower == "end" {
                     let timestamp = date_string_to_u64_flexible(ini_property.value.as_str())?.0;
                     model.configuration.specified_sim_end_timestamp = Some(timestamp);
+                } else if name_lower == "circular_references" {
+                    let v = ini_property.value.as_str();
+                    model.configuration.circular_references = CircularReferences::from_name(v)
+                        .ok_or(format!("Error on line {}: Unknown circular_references option '{}'. Expected one of: {}",
+                                       ini_property.line_number, v,
+                                       CircularReferences::ALL.map(|c| c.as_str()).join(", ")))?;
+                } else if name_lower == "nan_policy" {
+                    let v = ini_property.value.as_str();
+                    model.configuration.nan_policy = NanPolicy::from_name(v)
+                        .ok_or(format!("Error on line {}: Unknown nan_policy option '{}'. Expected one of: {}",
+                                       ini_property.line_number, v,
+                                       NanPolicy::ALL.map(|p| p.as_str()).join(", ")))?;
+                } else if name_lower == "compress_results" {
+                    model.configuration.compress_results = true_or_false(&ini_property.value)
+                        .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                } else if name_lower == "single_precision" {
+                    model.configuration.single_precision = true_or_false(&ini_property.value)
+                        .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                } else if name_lower == "strict" {
+                    model.configuration.strict = true_or_false(&ini_property.value)
+                        .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                } else if name_lower == "negative_tolerance" {
+                    model.configuration.negative_tolerance = ini_property.value.trim().parse::<f64>().ok()
+                        .filter(|v| *v >= 0.0)
+                        .ok_or(format!("Error on line {}: Invalid negative_tolerance '{}': required non-negative number",
+                                       ini_property.line_number, ini_property.value))?;
+                } else if name_lower == "spin_up_years" {
+                    model.configuration.spin_up_years = ini_property.value.trim().parse::<u32>()
+                        .map_err(|_| format!("Error on line {}: Invalid spin_up_years '{}': required non-negative integer",
+                                             ini_property.line_number, ini_property.value))?;
+                } else if name_lower == "spin_up_tolerance" {
+                    model.configuration.spin_up_tolerance = ini_property.value.trim().parse::<f64>().ok()
+                        .filter(|v| *v > 0.0)
+                        .ok_or(format!("Error on line {}: Invalid spin_up_tolerance '{}': required positive number",
+                                       ini_property.line_number, ini_property.value))?;
+                } else if name_lower == "spin_up_max_loops" {
+                    model.configuration.spin_up_max_loops = ini_property.value.trim().parse::<usize>().ok()
+                        .filter(|v| *v > 0)
+                        .ok_or(format!("Error on line {}: Invalid spin_up_max_loops '{}': required positive integer",
+                                       ini_property.line_number, ini_property.value))?;
                 }
             }
         } else if section_name == "inputs" {
@@ -72,7 +133,30 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                 // Input files can be specified in two formats:
                 // 1. Direct file path: ./path/to/file.csv (value is empty, key is the path)
                 // 2. Aliased file path: alias = ./path/to/file.csv (value is the path, key is the alias)
-                if ini_property.value.is_empty() {
+                // A file whose timestep differs from the model's may also be given a resampling
+                // policy, by alias or file name: resample.climate = sum
+                // and a policy for where it does not cover the simulation period: coverage.climate = error
+                // and for its missing values: nan_policy.climate = zero
+                let name_lower = name.to_lowercase();
+                if let (Some(source), false) = (name_lower.strip_prefix("resample."), ini_property.value.is_empty()) {
+                    let v = ini_property.value.as_str();
+                    let method = Resample::from_name(v)
+                        .ok_or(format!("Error on line {}: Unknown resample option '{}'. Expected one of: {}",
+                                       ini_property.line_number, v,
+                                       Resample::ALL.map(|r| r.as_str()).join(", ")))?;
+                    model.input_resampling.push(InputResampling { source: source.to_string(), method });
+                } else if let (Some(source), false) = (name_lower.strip_prefix("coverage."), ini_property.value.is_empty()) {
+                    let coverage = Coverage::from_name(ini_property.value.as_str())
+                        .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                    model.input_coverage.push(InputCoverage { source: source.to_string(), coverage });
+                } else if let (Some(source), false) = (name_lower.strip_prefix("nan_policy."), ini_property.value.is_empty()) {
+                    let v = ini_property.value.as_str();
+                    let policy = NanPolicy::from_name(v)
+                        .ok_or(format!("Error on line {}: Unknown nan_policy option '{}'. Expected one of: {}",
+                                       ini_property.line_number, v,
+                                       NanPolicy::ALL.map(|p| p.as_str()).join(", ")))?;
+                    model.input_nan_policies.push(InputNanPolicy { source: source.to_string(), policy });
+                } else if ini_property.value.is_empty() {
                     // Direct file path (no alias)
                     model.load_input_data(name.as_str(), None)
                         .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
@@ -94,6 +178,84 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                     .map_err(|_| format!("Error on line {}: Value for constant '{}': must be a number", ini_property.line_number, ini_property.value))?;
                 model.data_cache.constants.set_value(const_name.as_str(), const_value);
             }
+        } else if section_name == "tables" {
+            // -------------------------------------------------------------------------------------
+            // Parsing tables
+            // -------------------------------------------------------------------------------------
+            for (name, ini_property) in ini_section.properties {
+                // Each name defines a table, and each value is its values or a CSV file path
+                let v = require_non_empty(&ini_property.value, &name, ini_property.line_number)?;
+                model.load_table(name.as_str(), v)
+                    .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+            }
+        } else if section_name == "constituents" {
+            // -------------------------------------------------------------------------------------
+            // Parsing constituents
+            // -------------------------------------------------------------------------------------
+            for (name, ini_property) in ini_section.properties {
+                // Each name defines a constituent, and each value is its definition
+                let constituent_name = name.to_lowercase();
+                if !is_valid_variable_name(&constituent_name) { Err(format!("Error on line {}: Invalid constituent name '{}'", ini_property.line_number, name))?; }
+                let constituent = Constituent::parse(&constituent_name, ini_property.value.as_str())
+                    .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                model.constituents.constituents.push(constituent);
+            }
+        } else if section_name == "scaling" {
+            // -------------------------------------------------------------------------------------
+            // Parsing scaling
+            // -------------------------------------------------------------------------------------
+            for (name, ini_property) in ini_section.properties {
+                // Each name is an input series, and each value a list of adjustments to it
+                if !name.to_lowercase().starts_with("data.") {
+                    return Err(format!("Error on line {}: Scaled series '{}' must be a data reference (data.*)", ini_property.line_number, name));
+                }
+                let scaling = InputScaling::parse(name.as_str(), ini_property.value.as_str())
+                    .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                model.input_scaling.push(scaling);
+            }
+        } else if section_name == "extension" {
+            // -------------------------------------------------------------------------------------
+            // Parsing extension
+            // -------------------------------------------------------------------------------------
+            for (name, ini_property) in ini_section.properties {
+                // Each name is an input series, and each value how it is extended
+                if !name.to_lowercase().starts_with("data.") {
+                    return Err(format!("Error on line {}: Extended series '{}' must be a data reference (data.*)", ini_property.line_number, name));
+                }
+                let extension = InputExtension::parse(name.as_str(), ini_property.value.as_str())
+                    .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                model.input_extension.push(extension);
+            }
+        } else if section_name == "functions" {
+            // -------------------------------------------------------------------------------------
+            // Parsing functions
+            // -------------------------------------------------------------------------------------
+            for (name, ini_property) in ini_section.properties {
+                // Each name defines a function, and each value is its expression
+                let v = require_non_empty(&ini_property.value, &name, ini_property.line_number)?;
+                model.functions.add(name.as_str(), v, &mut model.data_cache)
+                    .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+            }
+        } else if section_name == "policies" {
+            // -------------------------------------------------------------------------------------
+            // Parsing policies
+            // -------------------------------------------------------------------------------------
+            for (name, ini_property) in ini_section.properties {
+                // Each name is a stage trigger or action, and each value its expression
+                let v = require_non_empty(&ini_property.value, &name, ini_property.line_number)?;
+                model.policies.add(name.as_str(), v, &mut model.data_cache)
+                    .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+            }
+        } else if section_name == "allocation" {
+            // -------------------------------------------------------------------------------------
+            // Parsing allocation accounts
+            // -------------------------------------------------------------------------------------
+            for (name, ini_property) in ini_section.properties {
+                // The water year, or a property of an account
+                let v = require_non_empty(&ini_property.value, &name, ini_property.line_number)?;
+                model.allocation.add(name.as_str(), v, &mut model.data_cache)
+                    .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+            }
         } else if section_name.starts_with("node.") {
             // -------------------------------------------------------------------------------------
             // Parsing nodes
@@ -106,6 +268,41 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
             let node_type = ini_section.properties.get("type")
                 .ok_or(format!("Error on line {}: Missing 'type'", ini_section.line_number))?.value.to_lowercase();
 
+            // User metadata ("meta_*" keys) is the same for every node type, so take it out first
+            let mut metadata = NodeMetadata::new();
+            for (name, ini_property) in &ini_section.properties {
+                let name_lower = name.to_lowercase();
+                if let Some(key) = name_lower.strip_prefix(META_PREFIX) {
+                    if key.is_empty() || !is_valid_variable_name(key) {
+                        return Err(format!("Error on line {}: Invalid metadata key '{}' for node '{}'",
+                                           ini_property.line_number, name, node_name));
+                    }
+                    metadata.insert(key.to_string(), ini_property.value.clone());
+                }
+            }
+            ini_section.properties.retain(|name, _| !name.to_lowercase().starts_with(META_PREFIX));
+
+            // So are the attributes of its links ("ds_N_capacity" and "ds_N_efficiency")
+            for (name, ini_property) in &ini_section.properties {
+                let name_lower = name.to_lowercase();
+                if let Some((outlet, attribute)) = link_attribute_key(&name_lower) {
+                    let value = ini_property.value.trim().parse::<f64>()
+                        .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                             ini_property.line_number, name, node_name))?;
+                    let valid = match attribute {
+                        "capacity" => value >= 0.0,
+                        _ => value > 0.0 && value <= 1.0,
+                    };
+                    if !valid {
+                        let expected = if attribute == "capacity" { "zero or more" } else { "greater than 0 and at most 1" };
+                        return Err(format!("Error on line {}: '{}' for node '{}' must be {}",
+                                           ini_property.line_number, name, node_name, expected));
+                    }
+                    link_attributes.push((node_name.to_string(), outlet, name.clone(), value, ini_property.line_number));
+                }
+            }
+            ini_section.properties.retain(|name, _| link_attribute_key(&name.to_lowercase()).is_none());
+
             // Now match on the type and do different stuff per type
             let node_enum= match node_type.as_str() {
                 "blackhole" => {
@@ -142,8 +339,7 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "ds_1" {
                             vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
                         } else if name_lower == "harmony_fraction" {
-                            n.harmony_fraction = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.harmony_fraction = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'", ini_property.line_number, name, node_name));
                         }
@@ -164,11 +360,25 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "ds_1" {
                             vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
                         } else if name_lower == "force_flow" {
-                            n.force_flow_input = DynamicInput::from_string(v, &mut model.data_cache, false, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.force_flow_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
                         } else if name_lower == "reference_flow" {
-                            n.reference_flow_input = DynamicInput::from_string(v, &mut model.data_cache, false, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.reference_flow_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
+                        } else if name_lower == "min_flow" {
+                            n.min_flow_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
+                        } else if name_lower == "residual" {
+                            n.residual_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
+                        } else if name_lower == "reference_level" {
+                            n.reference_level_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
+                        } else if name_lower == "rating" {
+                            n.rating_table = Table::from_csv_string(v, 2, false)
+                                .map_err(|e| format!("Error on line {}: Could not parse rating table for node '{}': {}",
+                                                     ini_property.line_number, node_name, e))?;
+                        } else if name_lower == "quality" {
+                            n.quality_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
+                        } else if name_lower == "quality_threshold" {
+                            n.quality_threshold = Some(v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?);
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -194,14 +404,11 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                                 format!("Error on line {}: Invalid '{}' value for node '{}': required non-negative integer",
                                         ini_property.line_number, name, node_name))?;
                         } else if name_lower == "min_order" {
-                            n.min_order_input = DynamicInput::from_string(v, &mut model.data_cache, false, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.min_order_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
                         } else if name_lower == "max_order" {
-                            n.max_order_input = DynamicInput::from_string(v, &mut model.data_cache, false, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.max_order_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
                         } else if name_lower == "set_order" {
-                            n.set_order_input = DynamicInput::from_string(v, &mut model.data_cache, false, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.set_order_input = read_dynamic_input(&ini_property, &mut model.data_cache, false, self_ctx)?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                                ini_property.line_number, name, node_name));
@@ -223,11 +430,9 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "ds_1" {
                             vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
                         } else if name_lower == "evap" {
-                            n.evap_mm_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.evap_mm_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "rain" {
-                            n.rain_mm_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.rain_mm_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "area" {
                             n.area_km2 = v.parse::<f64>()
                                 .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
@@ -253,6 +458,12 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                             n.gr4j_model.x2 = params[1];
                             n.gr4j_model.x3 = params[2];
                             n.gr4j_model.x4 = params[3];
+                        } else if name_lower == "initial_stores" {
+                            let stores = csv_string_to_f64_vec(v)
+                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.initial_stores = stores.try_into()
+                                .map_err(|s: Vec<f64>| format!("Error on line {}: GR4J initial_stores must have 2 values (production, routing), got {}",
+                                                               ini_property.line_number, s.len()))?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -274,11 +485,13 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "ds_1" {
                             vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
                         } else if name_lower == "inflow" {
-                            n.inflow_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.inflow_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "expected_inflow" {
-                            n.expected_inflow_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.expected_inflow_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if let Some(constituent) = name_lower.strip_prefix(CONC_PREFIX) {
+                            let input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                            concentration_refs.push((node_name.to_string(), constituent.to_string(), ini_property.line_number));
+                            n.concentration_inputs.push((constituent.to_string(), input));
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -303,6 +516,29 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                             n.loss_table = Table::from_csv_string(v, 2, false)
                                 .map_err(|e| format!("Error on line {}: Could not parse loss table for node '{}': {}",
                                                      ini_property.line_number, node_name, e))?;
+                        } else if name_lower == "loss_scale" {
+                            n.loss_scale = v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?;
+                        } else if name_lower == "monthly_factors" {
+                            n.monthly_factors = csv_string_to_f64_vec(v)
+                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            if n.monthly_factors.len() != 12 {
+                                return Err(format!("Error on line {}: Monthly_factors must contain 12 values (January first), but found {}",
+                                                   ini_property.line_number, n.monthly_factors.len()));
+                            }
+                        } else if name_lower == "loss_factor" {
+                            n.loss_factor = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "return_fraction" {
+                            n.return_fraction = v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?;
+                        } else if name_lower == "return_lag" {
+                            n.return_lag = v.parse::<usize>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid non-negative integer",
+                                                     ini_property.line_number, name, node_name))?;
+                        } else if name_lower == "ds_2" {
+                            vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_2_OUTLET, INLET))
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -360,10 +596,20 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                             }
                             let (index_flows, index_times) = split_interleaved(&all_values);
                             n.set_routing_table(index_flows, index_times);
+                        } else if name_lower == "ds_2" {
+                            vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_2_OUTLET, INLET))
+                        } else if name_lower == "split_table" {
+                            n.split_table = Table::from_csv_string(v, 2, false)
+                                .map_err(|e| format!("Error on line {}: Could not parse split table for node '{}': {}",
+                                                     ini_property.line_number, node_name, e))?;
                         } else if name_lower == "typical_regulated_flow" {
                             n.typical_regulated_flow = v.parse::<f64>()
                                 .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
                                                      ini_property.line_number, name, node_name))?;
+                        } else if name_lower == "initial_volume" {
+                            n.initial_volume = v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -385,11 +631,9 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "ds_1" {
                             vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
                         } else if name_lower == "evap" {
-                            n.evap_mm_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.evap_mm_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "rain" {
-                            n.rain_mm_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.rain_mm_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "area" {
                             n.area_km2 = v.parse::<f64>()
                                 .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
@@ -402,6 +646,12 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                                                    ini_property.line_number, params.len()));
                             }
                             n.sacramento_model.set_params_by_vec(params);
+                        } else if name_lower == "initial_stores" {
+                            let stores = csv_string_to_f64_vec(v)
+                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.initial_stores = stores.try_into()
+                                .map_err(|s: Vec<f64>| format!("Error on line {}: Sacramento initial_stores must have 5 values (uztwc, uzfwc, lztwc, lzfsc, lzfpc), got {}",
+                                                               ini_property.line_number, s.len()))?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -428,6 +678,14 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                             n.splitter_table = Table::from_csv_string(v, 2, false)
                                 .map_err(|e| format!("Error on line {}: Could not parse splitter table for node '{}': {}",
                                                      ini_property.line_number, node_name, e))?;
+                        } else if name_lower == "ds_2_flow" {
+                            n.ds_2_flow_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "ds_2_target" {
+                            n.ds_2_target_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "ds_2_min" {
+                            n.ds_2_min_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "ds_2_max" {
+                            n.ds_2_max_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -435,6 +693,36 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                     }
                     NodeEnum::SplitterNode(n)
                 }
+                "transfer" => {
+                    let mut n = TransferNode::new();
+                    n.name = node_name.to_string();
+                    for (name, ini_property) in ini_section.properties {
+                        let name_lower = name.to_lowercase();
+                        let v = require_non_empty(&ini_property.value, &name, ini_property.line_number)?;
+                        if name_lower == "loc" {
+                            n.location = Location::from_str(v)
+                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                        } else if name_lower == "type" {
+                            // Skipping this
+                        } else if name_lower == "ds_1" {
+                            vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
+                        } else if name_lower == "ds_2" {
+                            vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_2_OUTLET, INLET))
+                        } else if name_lower == "capacity" {
+                            n.capacity = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "trigger" {
+                            n.trigger = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "loss_fraction" {
+                            n.loss_fraction = v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?;
+                        } else {
+                            return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
+                                              ini_property.line_number, name, node_name));
+                        }
+                    }
+                    NodeEnum::TransferNode(n)
+                }
                 "storage" => {
                     let mut n = StorageNode::new();
                     n.name = node_name.to_string();
@@ -475,27 +763,29 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                                 ));
                             }
                             let i_outlet = ds_num - 1;
-                            n.ds_force_release_input[i_outlet] = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.ds_force_release_input[i_outlet] = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "evap" {
-                            n.evap_mm_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.evap_mm_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "rain" {
-                            n.rain_mm_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.rain_mm_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "seep" {
-                            n.seep_mm_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.seep_mm_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "pond_demand" {
-                            n.pond_demand_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.pond_demand_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "target_level" {
-                            n.target_level = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.target_level = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "dimensions" {
                             n.dimensions = Table::from_csv_string(v, 4, false)
                                 .map_err(|e| format!("Error on line {}: Could not parse dimensions table for node '{}': {}",
                                                      ini_property.line_number, node_name, e))?;
+                        } else if let Some(date_str) = name_lower.strip_prefix("dimensions_") {
+                            let (date, _) = date_string_to_u64_flexible(date_str)
+                                .map_err(|e| format!("Error on line {}: Invalid date in '{}' for node '{}': {}",
+                                                     ini_property.line_number, name, node_name, e))?;
+                            let table = Table::from_csv_string(v, 4, false)
+                                .map_err(|e| format!("Error on line {}: Could not parse {} table for node '{}': {}",
+                                                     ini_property.line_number, name, node_name, e))?;
+                            n.dated_dimensions.push((date, table));
                         } else if name_lower == "initial_volume" {
                             n.vol_initial = v.parse::<f64>()
                                 .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
@@ -503,12 +793,21 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "order_through" {
                             (n.order_through, _) = parse_csv_to_bool_option_u8(v)
                                 .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                        } else if name_lower == "shortfall_sharing" {
+                            n.shortfall_sharing = SharingRule::from_name(v)
+                                .ok_or(format!("Error on line {}: Unknown shortfall_sharing rule '{}'. Expected one of: {}",
+                                               ini_property.line_number, v,
+                                               SharingRule::ALL.map(|r| r.as_str()).join(", ")))?;
                         }
                         else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
                         }
                     }
+                    if n.dimensions.nrows() > 0 && !n.dated_dimensions.is_empty() {
+                        return Err(format!("Node '{}' has both 'dimensions' and dated dimensions tables. Give one or the other.",
+                                           node_name));
+                    }
                     NodeEnum::StorageNode(n)
                 }
                 "unregulated_user" => {
@@ -525,8 +824,11 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "ds_1" {
                             vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
                         } else if name_lower == "demand" {
-                            n.demand_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.demand_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "demand_multiplier" {
+                            n.demand_multiplier = v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?;
                         } else if name_lower == "account" {
                             let params =  csv_to_string_vec(v);
                             if params.len() != 4 {
@@ -555,16 +857,20 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                                                    ini_property.line_number, params.len()));
                             }
                             n.annual_cap = Some(params[0]);
-                            n.annual_cap_reset_month = params[1] as u8;
+                            n.annual_cap_year = WaterYear::new(params[1] as u8)
+                                .map_err(|e| format!("Error on line {}: Invalid 'annual_cap' reset month. {}", ini_property.line_number, e))?;
                         } else if name_lower == "pump" {
-                            n.pump_capacity = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.pump_capacity = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "flow_threshold" {
-                            n.flow_threshold = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.flow_threshold = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "commence_threshold" {
+                            n.commence_threshold = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
                         } else if name_lower == "demand_carryover" {
-                            (n.demand_carryover_allowed, n.demand_carryover_reset_month) = parse_csv_to_bool_option_u8(v)
+                            let (allowed, reset_month) = parse_csv_to_bool_option_u8(v)
                                 .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.demand_carryover_allowed = allowed;
+                            n.demand_carryover_year = reset_month.map(WaterYear::new).transpose()
+                                .map_err(|e| format!("Error on line {}: Invalid 'demand_carryover' reset month. {}", ini_property.line_number, e))?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                               ini_property.line_number, name, node_name));
@@ -586,11 +892,25 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                         } else if name_lower == "ds_1" {
                             vec_link_defs.push(LinkHelper::new_from_names(&n.name, v, DS_1_OUTLET, INLET))
                         } else if name_lower == "order" {
-                            n.order_input = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.order_input = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "demand_multiplier" {
+                            n.demand_multiplier = v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?;
                         } else if name_lower == "pump" {
-                            n.pump_capacity = DynamicInput::from_string(v, &mut model.data_cache, true, self_ctx)
-                                .map_err(|e| format!("Error on line {}: {}", ini_property.line_number, e))?;
+                            n.pump_capacity = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "off_allocation" {
+                            n.off_allocation_trigger = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "off_allocation_volume" {
+                            n.off_allocation_volume = read_dynamic_input(&ini_property, &mut model.data_cache, true, self_ctx)?;
+                        } else if name_lower == "priority" {
+                            n.priority = v.parse::<u32>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a whole number",
+                                                     ini_property.line_number, name, node_name))?;
+                        } else if name_lower == "entitlement" {
+                            n.entitlement = v.parse::<f64>()
+                                .map_err(|_| format!("Error on line {}: Invalid '{}' value for node '{}': not a valid number",
+                                                     ini_property.line_number, name, node_name))?;
                         } else {
                             return Err(format!("Error on line {}: Unexpected parameter '{}' for node '{}'",
                                                ini_property.line_number, name, node_name));
@@ -606,6 +926,8 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
                     return Err(format!("Error on line {}: Unknown node type '{}'",  line_number, node_type))
                 }
             };
+            let mut node_enum = node_enum;
+            *node_enum.get_metadata_mut() = metadata;
             model.add_node(node_enum);
         } else if section_name == "outputs" {
             // -------------------------------------------------------------------------------------
@@ -623,6 +945,37 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
         }
     }
 
+    // -------------------------------------------------------------------------------------
+    // Check that concentrations refer to declared constituents
+    // -------------------------------------------------------------------------------------
+    for (node_name, constituent, line_number) in concentration_refs {
+        if model.constituents.get_constituent_idx(&constituent).is_none() {
+            return Err(format!("Error on line {}: Node '{}' has a concentration for '{}', which is not declared in [constituents]",
+                               line_number, node_name, constituent));
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+    // Check that function references refer to defined functions
+    // -------------------------------------------------------------------------------------
+    for series_name in &model.data_cache.series_name {
+        let series_name = series_name.to_lowercase();
+        if series_name.starts_with(FUNCTION_PREFIX) && !model.functions.functions.iter().any(|f| f.name == series_name) {
+            let defined = model.functions.functions.iter().map(|f| f.name.as_str());
+            return Err(format!("Function '{}' is used but not defined in [functions]{}", series_name,
+                               did_you_mean(&series_name, defined)));
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+    // Check that table lookups refer to defined tables
+    // -------------------------------------------------------------------------------------
+    if let Some(table_name) = model.data_cache.tables.list_unassigned_names().first() {
+        let defined = model.data_cache.tables.get_name_definition_pairs();
+        return Err(format!("Table '{}' is used but not defined in [tables]{}", table_name,
+                           did_you_mean(table_name, defined.iter().map(|(name, _)| name.as_str()))));
+    }
+
     // -------------------------------------------------------------------------------------
     // Create all the links
     // -------------------------------------------------------------------------------------
@@ -633,6 +986,17 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
             .ok_or(format!("Node '{}' not found", link_helper.to_node_name))?;
         model.add_link(from_node_idx, to_node_idx, link_helper.from_outlet, link_helper.to_inlet);
     }
+    for (node_name, outlet, key, value, line_number) in link_attributes {
+        let from_node_idx = model.get_node_idx(&node_name)
+            .ok_or(format!("Node '{}' not found", node_name))?;
+        let link = model.links.iter_mut()
+            .find(|link| link.from_node == from_node_idx && link.from_outlet == outlet)
+            .ok_or(format!("Error on line {}: Node '{}' has '{}' but no ds_{} link", line_number, node_name, key, outlet + 1))?;
+        match link_attribute_key(&key.to_lowercase()) {
+            Some((_, "capacity")) => link.capacity = Some(value),
+            _ => link.efficiency = Some(value),
+        }
+    }
 
     // -------------------------------------------------------------------------------------
     // Capture the canonical baseline of the model exactly as loaded
@@ -650,6 +1014,40 @@ pub fn ini_doc_to_model_0_0_1(ini_doc: IniDocument, working_directory: Option<st
 
 
 
+/// Reads an expression property into a DynamicInput. Errors in the expression are reported
+/// at their line and column in the file, followed by the expression with the error underlined.
+fn read_dynamic_input(ini_property: &IniProperty, data_cache: &mut DataCache, flag_as_critical: bool, self_context: Option<&str>) -> Result<DynamicInput, String> {
+    DynamicInput::parse(&ini_property.value, data_cache, flag_as_critical, self_context).map_err(|e| match e.span() {
+        Some(span) => {
+            let (line, column) = ini_property.value_position(span.start);
+            format!("Error on line {}, column {}: {}", line, column, e.render(ini_property.value.trim()))
+        }
+        None => format!("Error on line {}: {}", ini_property.line_number, e),
+    })
+}
+
+
+/// The outlet (from 0) and attribute of a link attribute key, e.g. `ds_2_capacity` is
+/// `(1, "capacity")`
+fn link_attribute_key(name_lower: &str) -> Option<(u8, &str)> {
+    let (ds_num, attribute) = name_lower.strip_prefix("ds_")?.split_once('_')?;
+    let ds_num = ds_num.parse::<u8>().ok().filter(|&n| n >= 1)?;
+    match attribute {
+        "capacity" | "efficiency" => Some((ds_num - 1, attribute)),
+        _ => None,
+    }
+}
+
+
+/// A " Did you mean '...'?" sentence for an undefined name, if one of the candidates is close
+fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    match closest_match(name, candidates) {
+        Some(suggestion) => format!(". Did you mean '{}'?", suggestion),
+        None => String::new(),
+    }
+}
+
+
 /// Render a model to an INI document canonically — every property formatted
 /// from the model's typed fields. Starts from the model's `ini_document` (when
 /// present) so leading comments, blank lines and section order survive, but all
@@ -680,17 +1078,116 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
     if let Some(end_timestamp) = model.configuration.specified_sim_end_timestamp {
         ini_doc.set_property("kalix", "end", &u64_to_date_string_for_step_size(end_timestamp, sim_stepsize));
     }
+    if model.configuration.circular_references != CircularReferences::Error {
+        ini_doc.set_property("kalix", "circular_references", model.configuration.circular_references.as_str());
+    }
+    if model.configuration.nan_policy != NanPolicy::Propagate {
+        ini_doc.set_property("kalix", "nan_policy", model.configuration.nan_policy.as_str());
+    }
+    if model.configuration.compress_results {
+        ini_doc.set_property("kalix", "compress_results", "true");
+    }
+    if model.configuration.single_precision {
+        ini_doc.set_property("kalix", "single_precision", "true");
+    }
+    if model.configuration.strict {
+        ini_doc.set_property("kalix", "strict", "true");
+    }
+    if model.configuration.negative_tolerance != DEFAULT_NEGATIVE_TOLERANCE {
+        ini_doc.set_property("kalix", "negative_tolerance", &model.configuration.negative_tolerance.to_string());
+    }
+    if model.configuration.spin_up_years > 0 {
+        ini_doc.set_property("kalix", "spin_up_years", &model.configuration.spin_up_years.to_string());
+    }
+    if model.configuration.spin_up_tolerance != DEFAULT_SPIN_UP_TOLERANCE {
+        ini_doc.set_property("kalix", "spin_up_tolerance", &model.configuration.spin_up_tolerance.to_string());
+    }
+    if model.configuration.spin_up_max_loops != DEFAULT_SPIN_UP_MAX_LOOPS {
+        ini_doc.set_property("kalix", "spin_up_max_loops", &model.configuration.spin_up_max_loops.to_string());
+    }
 
     // List all input files
     for file_path in &model.input_file_paths {
         ini_doc.set_property("inputs", file_path.as_str(), "");
     }
+    for r in &model.input_resampling {
+        ini_doc.set_property("inputs", &format!("resample.{}", r.source), r.method.as_str());
+    }
+    for c in &model.input_coverage {
+        ini_doc.set_property("inputs", &format!("coverage.{}", c.source), c.coverage.definition().as_str());
+    }
+    for p in &model.input_nan_policies {
+        ini_doc.set_property("inputs", &format!("nan_policy.{}", p.source), p.policy.as_str());
+    }
 
     // List all constants
     for (name, value) in model.data_cache.constants.get_name_value_pairs() {
         ini_doc.set_property("constants", name.as_str(), value.to_string().as_str());
     }
 
+    // List all constituents
+    for constituent in &model.constituents.constituents {
+        ini_doc.set_property("constituents", constituent.name.as_str(), constituent.definition().as_str());
+    }
+
+    // List all input scaling
+    for scaling in &model.input_scaling {
+        ini_doc.set_property("scaling", scaling.series.as_str(), scaling.definition().as_str());
+    }
+
+    // List all input extension
+    for extension in &model.input_extension {
+        ini_doc.set_property("extension", extension.series.as_str(), extension.definition().as_str());
+    }
+
+    // List all tables
+    for (name, definition) in model.data_cache.tables.get_name_definition_pairs() {
+        ini_doc.set_property("tables", name.as_str(), definition.as_str());
+    }
+
+    // List all functions
+    for f in &model.functions.functions {
+        ini_doc.set_property("functions", f.name.as_str(), f.expression.to_string().as_str());
+    }
+
+    // List all policy stages
+    for stage in &model.policies.stages {
+        if let Some(trigger) = &stage.trigger {
+            ini_doc.set_property("policies", format!("stage.{}", stage.number).as_str(), trigger.to_string().as_str());
+        }
+        for action in &stage.actions {
+            let key = format!("stage.{}.{}", stage.number, action.key());
+            ini_doc.set_property("policies", key.as_str(), action.value.to_string().as_str());
+        }
+    }
+
+    // List all allocation accounts
+    if !model.allocation.is_empty() && model.allocation.water_year != WaterYear::JULY {
+        ini_doc.set_property("allocation", "water_year", model.allocation.water_year.start_month().to_string().as_str());
+    }
+    if !model.allocation.assessment_months.is_empty() {
+        let months: Vec<String> = model.allocation.assessment_months.iter().map(|m| m.to_string()).collect();
+        ini_doc.set_property("allocation", "assessment_months", months.join(", ").as_str());
+    }
+    for forecast in &model.allocation.forecasts {
+        let key = |property: &str| format!("forecast.{}.{}", forecast.name, property);
+        ini_doc.set_property("allocation", key("series").as_str(), forecast.series.as_str());
+        ini_doc.set_property("allocation", key("method").as_str(), forecast.method.as_str());
+        if forecast.method == InflowForecastMethod::Exceedance {
+            ini_doc.set_property("allocation", key("exceedance").as_str(), format_f64(forecast.exceedance).as_str());
+        }
+    }
+    for account in &model.allocation.accounts {
+        let key = |property: &str| format!("account.{}.{}", account.name, property);
+        ini_doc.set_property("allocation", key("storage").as_str(), account.storage.as_str());
+        ini_doc.set_property("allocation", key("allocation").as_str(), account.allocation.to_string().as_str());
+        set_property_unless_default(&mut ini_doc, "allocation", key("carryover_limit").as_str(), &format_f64(account.carryover_limit), "0");
+        set_property_unless_default(&mut ini_doc, "allocation", key("initial_carryover").as_str(), &format_f64(account.initial_carryover), "0");
+        if let Some(v) = account.initial_allocation {
+            ini_doc.set_property("allocation", key("initial_allocation").as_str(), format_f64(v).as_str());
+        }
+    }
+
     // List all nodes
     for node_enum in &model.nodes {
         match node_enum {
@@ -711,6 +1208,16 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 ini_doc.set_property(section_name.as_str(), "type", "gauge");
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "force_flow", &n.force_flow_input.to_string());
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "reference_flow", &n.reference_flow_input.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "min_flow", &n.min_flow_input.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "residual", &n.residual_input.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "reference_level", &n.reference_level_input.to_string());
+                let rating_table_values = n.rating_table.get_values_as_vec();
+                let rating_table_str = format_vec_as_multiline_table(&rating_table_values, n.rating_table.ncols(), 4);
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "rating", rating_table_str.as_str());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "quality", &n.quality_input.to_string());
+                if let Some(threshold) = n.quality_threshold {
+                    ini_doc.set_property(section_name.as_str(), "quality_threshold", format_f64(threshold).as_str());
+                }
             }
             NodeEnum::OrderControlNode(n) => {
                 let section_name = format!("node.{}", n.name);
@@ -734,6 +1241,10 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 ini_doc.set_property(section_name.as_str(), "area", n.area_km2.to_string().as_str());
                 let params_str = format!("{}, {}, {}, {}", n.gr4j_model.x1, n.gr4j_model.x2, n.gr4j_model.x3, n.gr4j_model.x4);
                 ini_doc.set_property(section_name.as_str(), "params", params_str.as_str());
+                if n.initial_stores.iter().any(|&v| v != 0.0) {
+                    let stores: Vec<String> = n.initial_stores.iter().map(|v| v.to_string()).collect();
+                    ini_doc.set_property(section_name.as_str(), "initial_stores", stores.join(", ").as_str());
+                }
             }
             NodeEnum::InflowNode(n) => {
                 let section_name = format!("node.{}", n.name);
@@ -741,6 +1252,9 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 ini_doc.set_property(section_name.as_str(), "type", "inflow");
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "inflow", &n.inflow_input.to_string());
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "expected_inflow", &n.expected_inflow_input.to_string());
+                for (constituent, input) in &n.concentration_inputs {
+                    ini_doc.set_property(section_name.as_str(), format!("{}{}", CONC_PREFIX, constituent).as_str(), input.to_string().as_str());
+                }
             }
             NodeEnum::LossNode(n) => {
                 let section_name = format!("node.{}", n.name);
@@ -750,6 +1264,14 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 let loss_table_str = format_vec_as_multiline_table(&loss_table_values, n.loss_table.ncols(), 4);
                 //ini_doc.set_property (section_name.as_str(), "table", loss_table_str.as_str());
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "table", loss_table_str.as_str());
+                if n.loss_scale != 1.0 { ini_doc.set_property(section_name.as_str(), "loss_scale", n.loss_scale.to_string().as_str()); }
+                if !n.monthly_factors.is_empty() {
+                    let factors: Vec<String> = n.monthly_factors.iter().map(|f| format_f64(*f)).collect();
+                    ini_doc.set_property(section_name.as_str(), "monthly_factors", factors.join(", ").as_str());
+                }
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "loss_factor", &n.loss_factor.to_string());
+                set_property_unless_default(&mut ini_doc, section_name.as_str(), "return_fraction", &n.return_fraction.to_string(), "0");
+                set_property_unless_default(&mut ini_doc, section_name.as_str(), "return_lag", &n.return_lag.to_string(), "0");
             }
             NodeEnum::RoutingNode(n) => {
                 let section_name = format!("node.{}", n.name);
@@ -772,7 +1294,11 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                         ini_doc.set_property(section_name.as_str(), "pwl", pwl_values_str.as_str());
                     }
                 }
+                let split_table_values = n.split_table.get_values_as_vec();
+                let split_table_str = format_vec_as_multiline_table(&split_table_values, n.split_table.ncols(), 4);
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "split_table", split_table_str.as_str());
                 set_property_unless_default(&mut ini_doc, section_name.as_str(), "typical_regulated_flow", &n.typical_regulated_flow.to_string(), "0");
+                set_property_unless_default(&mut ini_doc, section_name.as_str(), "initial_volume", &n.initial_volume.to_string(), "0");
             }
             NodeEnum::SacramentoNode(n) => {
                 let section_name = format!("node.{}", n.name);
@@ -784,6 +1310,10 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 let params = n.sacramento_model.get_params_as_vec();
                 let params_str = format_vec_as_multiline_table(&params, 4, 4);
                 ini_doc.set_property(section_name.as_str(), "params", params_str.as_str());
+                if n.initial_stores.iter().any(|&v| v != 0.0) {
+                    let stores: Vec<String> = n.initial_stores.iter().map(|v| v.to_string()).collect();
+                    ini_doc.set_property(section_name.as_str(), "initial_stores", stores.join(", ").as_str());
+                }
             }
             NodeEnum::SplitterNode(n) => {
                 let section_name = format!("node.{}", n.name);
@@ -793,6 +1323,18 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 let splitter_table_str = format_vec_as_multiline_table(&splitter_table_values, n.splitter_table.ncols(), 4);
                 //ini_doc.set_property(section_name.as_str(), "table", splitter_table_str.as_str());
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "table", splitter_table_str.as_str());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "ds_2_flow", &n.ds_2_flow_input.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "ds_2_target", &n.ds_2_target_input.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "ds_2_min", &n.ds_2_min_input.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "ds_2_max", &n.ds_2_max_input.to_string());
+            }
+            NodeEnum::TransferNode(n) => {
+                let section_name = format!("node.{}", n.name);
+                ini_doc.set_property(section_name.as_str(), "loc", n.location.to_string().as_str());
+                ini_doc.set_property(section_name.as_str(), "type", "transfer");
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "capacity", &n.capacity.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "trigger", &n.trigger.to_string());
+                set_property_unless_default(&mut ini_doc, section_name.as_str(), "loss_fraction", &n.loss_fraction.to_string(), "0");
             }
             NodeEnum::StorageNode(n) => {
                 let section_name = format!("node.{}", n.name);
@@ -808,9 +1350,19 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 if n.order_through {
                     ini_doc.set_property(section_name.as_str(), "order_through", "true");
                 }
-                let dimensions_values = n.dimensions.get_values_as_vec();
-                let dimensions_str = format_vec_as_multiline_table(&dimensions_values, n.dimensions.ncols(), 4);
-                ini_doc.set_property(section_name.as_str(), "dimensions", dimensions_str.as_str());
+                if n.shortfall_sharing != SharingRule::FirstComeFirstServed {
+                    ini_doc.set_property(section_name.as_str(), "shortfall_sharing", n.shortfall_sharing.as_str());
+                }
+                if n.dated_dimensions.is_empty() {
+                    let dimensions_values = n.dimensions.get_values_as_vec();
+                    let dimensions_str = format_vec_as_multiline_table(&dimensions_values, n.dimensions.ncols(), 4);
+                    ini_doc.set_property(section_name.as_str(), "dimensions", dimensions_str.as_str());
+                }
+                for (date, table) in &n.dated_dimensions {
+                    let property_name = format!("dimensions_{}", u64_to_date_string(*date));
+                    let table_str = format_vec_as_multiline_table(&table.get_values_as_vec(), table.ncols(), 4);
+                    ini_doc.set_property(section_name.as_str(), &property_name, table_str.as_str());
+                }
                 for (i, outlet_def) in n.outlet_definition.iter().enumerate() {
                     let property_name = format!("ds_{}_outlet", i + 1);
                     let value = match outlet_def {
@@ -826,8 +1378,10 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 ini_doc.set_property(section_name.as_str(), "loc", n.location.to_string().as_str());
                 ini_doc.set_property(section_name.as_str(), "type", "unregulated_user");
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "demand", &n.demand_input.to_string());
+                if n.demand_multiplier != 1.0 { ini_doc.set_property(section_name.as_str(), "demand_multiplier", n.demand_multiplier.to_string().as_str()); }
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "pump", &n.pump_capacity.to_string());
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "flow_threshold", &n.flow_threshold.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "commence_threshold", &n.commence_threshold.to_string());
                 // Re-emit the account definition (name, type, size, wy_month) by
                 // looking it up in the account manager via the node's registered index.
                 if let Some(account_idx) = n.account_idx {
@@ -838,13 +1392,13 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 }
                 match n.annual_cap {
                     Some(cap) => {
-                        let value_str = format!("{},{}", cap, n.annual_cap_reset_month);
+                        let value_str = format!("{},{}", cap, n.annual_cap_year.start_month());
                         ini_doc.set_property(section_name.as_str(), "annual_cap", value_str.as_str()); }
                     None => {}
                 }
                 if n.demand_carryover_allowed {
-                    let value = match n.demand_carryover_reset_month {
-                        Some(month) => format!("true, {}", month),
+                    let value = match n.demand_carryover_year {
+                        Some(year) => format!("true, {}", year.start_month()),
                         None => "true".to_string()
                     };
                     ini_doc.set_property(section_name.as_str(), "demand_carryover", value.as_str());
@@ -855,11 +1409,24 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
                 ini_doc.set_property(section_name.as_str(), "loc", n.location.to_string().as_str());
                 ini_doc.set_property(section_name.as_str(), "type", "regulated_user");
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "order", &n.order_input.to_string());
+                if n.demand_multiplier != 1.0 { ini_doc.set_property(section_name.as_str(), "demand_multiplier", n.demand_multiplier.to_string().as_str()); }
                 set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "pump", &n.pump_capacity.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "off_allocation", &n.off_allocation_trigger.to_string());
+                set_property_if_not_empty(&mut ini_doc, section_name.as_str(), "off_allocation_volume", &n.off_allocation_volume.to_string());
+                set_property_unless_default(&mut ini_doc, section_name.as_str(), "priority", &n.priority.to_string(), "1");
+                set_property_unless_default(&mut ini_doc, section_name.as_str(), "entitlement", &n.entitlement.to_string(), "0");
             }
         }
     }
 
+    // Node metadata
+    for node_enum in &model.nodes {
+        let section_name = format!("node.{}", node_enum.get_name());
+        for (key, value) in node_enum.get_metadata() {
+            ini_doc.set_property(section_name.as_str(), format!("{}{}", META_PREFIX, key).as_str(), value);
+        }
+    }
+
     // Put in the links
     for link in &model.links {
         let us_node_name = model.nodes[link.from_node].get_name();
@@ -871,6 +1438,12 @@ pub fn render_canonical_0_0_1(model: &Model) -> IniDocument {
             _ => { format!("ds_{}", link.from_outlet + 1) }, //plus one
         };
         ini_doc.set_property(section_name.as_str(), property_name.as_str(), ds_node_name);
+        if let Some(capacity) = link.capacity {
+            ini_doc.set_property(section_name.as_str(), format!("{}_capacity", property_name).as_str(), format_f64(capacity).as_str());
+        }
+        if let Some(efficiency) = link.efficiency {
+            ini_doc.set_property(section_name.as_str(), format!("{}_efficiency", property_name).as_str(), format_f64(efficiency).as_str());
+        }
     }
 
     // List all the recorders
@@ -915,11 +1488,28 @@ pub fn model_to_ini_doc_0_0_1(model: &Model) -> IniDocument {
     let mut out = original.clone();
     out.invalidate_all();
 
+    // Templates and instances are kept as they are, and so stand for the nodes they create
+    // unless those nodes changed
+    let instance_nodes = expand_templates(&mut original.clone()).unwrap_or_default();
+    let template_sections: Vec<String> = out.sections.keys()
+        .filter(|s| s.starts_with("template.") || s.starts_with("instance."))
+        .cloned()
+        .collect();
+    for section_name in template_sections {
+        out.validate_section(&section_name);
+        let keys: Vec<String> = out.sections[&section_name].properties.keys().cloned().collect();
+        for key in keys {
+            out.validate_property(&section_name, &key);
+        }
+    }
+
     for (section_name, current_section) in &current.sections {
         let unchanged = baseline.sections.get(section_name)
             .map_or(false, |base| sections_canonically_equal(base, current_section));
 
-        if unchanged && out.sections.contains_key(section_name) {
+        if unchanged && instance_nodes.contains(section_name) {
+            // Still as its instance creates it
+        } else if unchanged && out.sections.contains_key(section_name) {
             // Keep the original section verbatim (preserves raw_lines and comments).
             out.validate_section(section_name);
             let keys: Vec<String> = out.sections[section_name].properties.keys().cloned().collect();
@@ -0,0 +1,208 @@
+//! Templated sub-networks: a group of nodes defined once and instantiated many times.
+//!
+//! A `[template.NAME]` section defines the nodes of the group with `NODE.PROPERTY` keys, and
+//! values may hold `{placeholder}`s. Each `[instance.NAME]` section names its `template`, gives
+//! a value for every placeholder, and optionally a `loc` that the template's node locations
+//! are offset by. For example:
+//!
+//! ```ini
+//! [template.catchment]
+//! runoff.type = gr4j
+//! runoff.loc = 0, 0
+//! runoff.rain = data.rain_{station}
+//! runoff.params = {x1}, 0, 90, 1.7
+//! runoff.ds_1 = reach
+//! reach.type = routing
+//! reach.loc = 0, 10
+//! reach.lag = 1
+//! reach.ds_1 = {outlet}
+//!
+//! [instance.upper]
+//! template = catchment
+//! loc = 100, 200
+//! station = 410001
+//! x1 = 350
+//! outlet = main_gauge
+//! ```
+//!
+//! Each instance becomes node sections named `INSTANCE_NODE` (here `upper_runoff` and
+//! `upper_reach`) in place of its section. A `ds_N` link to another node of the template is
+//! renamed to match, and `{instance}` gives the instance name, e.g. for `node.{instance}_reach.dsflow`.
+//! A `[node.NAME]` section with the name of a node an instance would create replaces that
+//! node, which is how edits to one instance's nodes are saved.
+
+use indexmap::IndexMap;
+use crate::io::custom_ini_parser::{IniDocument, IniProperty, IniSection};
+use crate::io::csv_io::csv_string_to_f64_vec;
+use crate::misc::misc_functions::{format_f64, is_valid_variable_name};
+
+const TEMPLATE_PREFIX: &str = "template.";
+const INSTANCE_PREFIX: &str = "instance.";
+
+/// The placeholders in a value, e.g. `["x1", "station"]` for `data.rain_{station} * {x1}`
+fn placeholders(value: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut rest = value;
+    while let Some(start) = rest.find('{') {
+        match rest[start..].find('}') {
+            Some(end) => {
+                names.push(&rest[start + 1..start + end]);
+                rest = &rest[start + end + 1..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+/// Whether a key is a link (`ds_1`, `ds_2`, ..) rather than a property like `ds_1_capacity`
+fn is_link_key(key: &str) -> bool {
+    key.strip_prefix("ds_").is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Adds an instance's `loc` to a template node's `loc`
+fn offset_location(loc: &str, offset: (f64, f64), line_number: usize) -> Result<String, String> {
+    match csv_string_to_f64_vec(loc) {
+        Ok(coords) if coords.len() == 2 => Ok(format!("{}, {}", format_f64(coords[0] + offset.0), format_f64(coords[1] + offset.1))),
+        _ => Err(format!("Error on line {}: Error parsing node location: expected 2 coordinates.", line_number)),
+    }
+}
+
+/// A node of a template, as `(node name, properties)`
+type TemplateNode = (String, Vec<(String, IniProperty)>);
+
+fn read_template(name: &str, section: &IniSection) -> Result<Vec<TemplateNode>, String> {
+    let mut nodes: Vec<TemplateNode> = vec![];
+    for (key, property) in &section.properties {
+        let (node_name, property_name) = key.split_once('.')
+            .filter(|(node, prop)| !node.is_empty() && !prop.is_empty())
+            .ok_or(format!("Error on line {}: Template '{}' keys must be NODE.PROPERTY, e.g. 'runoff.type', not '{}'",
+                           property.line_number, name, key))?;
+        let node_name = node_name.to_lowercase();
+        match nodes.iter_mut().find(|(n, _)| *n == node_name) {
+            Some((_, properties)) => properties.push((property_name.to_string(), property.clone())),
+            None => nodes.push((node_name, vec![(property_name.to_string(), property.clone())])),
+        }
+    }
+    Ok(nodes)
+}
+
+/// The node sections for one instance of a template
+fn instantiate(instance_name: &str, instance: &IniSection, template_name: &str, template: &[TemplateNode])
+               -> Result<Vec<(String, IniSection)>, String> {
+
+    // Placeholder values, and the location offset
+    let mut values: IndexMap<String, (String, usize)> = IndexMap::new();
+    let mut offset = None;
+    for (key, property) in &instance.properties {
+        let key_lower = key.to_lowercase();
+        if key_lower == "template" {
+            continue;
+        } else if key_lower == "loc" {
+            match csv_string_to_f64_vec(&property.value) {
+                Ok(coords) if coords.len() == 2 => offset = Some((coords[0], coords[1])),
+                _ => return Err(format!("Error on line {}: Error parsing node location: expected 2 coordinates.", property.line_number)),
+            }
+        } else if key_lower == "instance" || !is_valid_variable_name(&key_lower) {
+            return Err(format!("Error on line {}: Invalid placeholder name '{}' in instance '{}'",
+                               property.line_number, key, instance_name));
+        } else {
+            values.insert(key_lower, (property.value.clone(), property.line_number));
+        }
+    }
+    values.insert("instance".to_string(), (instance_name.to_string(), instance.line_number));
+
+    // Every value is for a placeholder of the template, to catch typos
+    let used: Vec<String> = template.iter()
+        .flat_map(|(_, properties)| properties.iter().flat_map(|(_, p)| placeholders(&p.value)))
+        .map(|p| p.to_lowercase())
+        .collect();
+    if let Some((name, (_, line_number))) = values.iter().find(|(name, _)| *name != "instance" && !used.contains(name)) {
+        return Err(format!("Error on line {}: Template '{}' has no placeholder '{{{}}}'", line_number, template_name, name));
+    }
+
+    let node_names: Vec<&str> = template.iter().map(|(n, _)| n.as_str()).collect();
+    let mut sections = vec![];
+    for (node_name, properties) in template {
+        let mut section = IniSection {
+            properties: IndexMap::new(),
+            leading_lines: vec![],
+            line_number: instance.line_number,
+            valid: true,
+        };
+        for (key, template_property) in properties {
+            let mut value = template_property.value.clone();
+            for placeholder in placeholders(&template_property.value) {
+                let (replacement, _) = values.get(&placeholder.to_lowercase())
+                    .ok_or(format!("Error on line {}: Instance '{}' gives no value for '{{{}}}', used by template '{}'",
+                                   instance.line_number, instance_name, placeholder, template_name))?;
+                value = value.replace(&format!("{{{}}}", placeholder), replacement);
+            }
+            let key_lower = key.to_lowercase();
+            if is_link_key(&key_lower) && node_names.contains(&value.trim().to_lowercase().as_str()) {
+                value = format!("{}_{}", instance_name, value.trim());
+            } else if key_lower == "loc" {
+                if let Some(offset) = offset {
+                    value = offset_location(&value, offset, template_property.line_number)?;
+                }
+            }
+            let property = IniProperty {
+                raw_lines: vec![format!("{} = {}", key, value)],
+                value,
+                leading_lines: vec![],
+                comments: vec![None],
+                ..template_property.clone()
+            };
+            section.properties.insert(key.clone(), property);
+        }
+        sections.push((format!("node.{}_{}", instance_name, node_name), section));
+    }
+    Ok(sections)
+}
+
+/// Replaces the `[instance.NAME]` sections of a model document with the node sections they
+/// create, and removes the `[template.NAME]` sections. Returns the names of the node sections
+/// created, which leaves out any replaced by a `[node.NAME]` section of the document.
+pub fn expand_templates(doc: &mut IniDocument) -> Result<Vec<String>, String> {
+    if !doc.sections.keys().any(|s| s.starts_with(TEMPLATE_PREFIX) || s.starts_with(INSTANCE_PREFIX)) {
+        return Ok(vec![]);
+    }
+
+    let mut templates: IndexMap<String, Vec<TemplateNode>> = IndexMap::new();
+    for (section_name, section) in &doc.sections {
+        if let Some(name) = section_name.strip_prefix(TEMPLATE_PREFIX) {
+            templates.insert(name.to_lowercase(), read_template(name, section)?);
+        }
+    }
+
+    let sections = std::mem::take(&mut doc.sections);
+    let mut created = vec![];
+    for (section_name, section) in &sections {
+        if section_name.starts_with(TEMPLATE_PREFIX) {
+            continue;
+        }
+        let Some(instance_name) = section_name.strip_prefix(INSTANCE_PREFIX) else {
+            doc.sections.insert(section_name.clone(), section.clone());
+            continue;
+        };
+        let template_property = section.properties.iter()
+            .find(|(key, _)| key.to_lowercase() == "template")
+            .map(|(_, property)| property)
+            .ok_or(format!("Error on line {}: Instance '{}' has no 'template'", section.line_number, instance_name))?;
+        let template_name = template_property.value.trim().to_lowercase();
+        let template = templates.get(&template_name)
+            .ok_or(format!("Error on line {}: Template '{}' is not defined", template_property.line_number, template_property.value.trim()))?;
+        for (node_section_name, node_section) in instantiate(instance_name, section, &template_name, template)? {
+            if sections.contains_key(&node_section_name) {
+                continue; // Replaced by a section of its own
+            }
+            if created.contains(&node_section_name) {
+                return Err(format!("Error on line {}: Instance '{}' creates '{}', which another instance also creates",
+                                   section.line_number, instance_name, node_section_name));
+            }
+            created.push(node_section_name.clone());
+            doc.sections.insert(node_section_name, node_section);
+        }
+    }
+    Ok(created)
+}
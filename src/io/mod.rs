@@ -1,10 +1,14 @@
 pub mod csv_io;
+pub mod source_res_io;
 pub mod ini_model_io;
 pub mod custom_ini_parser;
+pub mod ini_formatter;
+pub mod ini_templates;
 pub mod compression;
 pub mod pixie_io;
 pub mod kalix_path;
 pub mod optimisation_config_io;
+pub mod parameter_file_io;
 
 #[cfg(test)]
 pub mod pixie_io_example;
@@ -15,9 +15,13 @@
 use std::fs;
 use indexmap::IndexMap;
 use crate::io::custom_ini_parser::IniDocument;
-use crate::numerical::opt::parameter_mapping::ParameterMappingConfig;
+use crate::numerical::opt::parameter_mapping::{ConstraintHandling, ParameterMappingConfig};
 use crate::numerical::opt::objectives::ObjectiveFunction;
+use crate::numerical::opt::regional::{catchment_scope, CATCHMENT_PREFIX};
+use crate::numerical::opt::series_transform::SeriesTransform;
+use crate::numerical::opt::termination::EarlyStopping;
 use crate::timeseries_input::TimeseriesInput;
+use crate::tid::utils::date_string_to_u64_flexible;
 
 /// Algorithm-specific parameters for optimisation
 #[derive(Debug, Clone, PartialEq)]
@@ -38,6 +42,18 @@ pub enum AlgorithmParams {
         complexes: usize,
         // Note: points_per_complex is calculated as 2*n_params + 1 (Duan et al. 1994)
     },
+    /// NSGA-II multi-objective algorithm
+    NSGA2 {
+        population_size: usize,
+        crossover_probability: f64,  // SBX crossover probability (typically 0.9)
+        eta_c: f64,  // SBX distribution index (typically 15)
+        eta_m: f64,  // Polynomial mutation distribution index (typically 20)
+    },
+    /// Dynamically Dimensioned Search
+    DDS {
+        r: f64,  // Perturbation size as a fraction of the parameter range (typically 0.2)
+        starts: usize,  // Independent searches sharing the budget, run in parallel
+    },
 }
 
 impl AlgorithmParams {
@@ -47,17 +63,22 @@ impl AlgorithmParams {
             AlgorithmParams::DE { .. } => "DE",
             AlgorithmParams::CMAES { .. } => "CMAES",
             AlgorithmParams::SCEUA { .. } => "SCE",
+            AlgorithmParams::NSGA2 { .. } => "NSGA2",
+            AlgorithmParams::DDS { .. } => "DDS",
         }
     }
 
     /// Get population size (common across all algorithms)
     ///
-    /// For SCE, returns number of complexes (actual population = complexes * (2*n_params + 1))
+    /// For SCE, returns number of complexes (actual population = complexes * (2*n_params + 1)).
+    /// For DDS, returns the number of independent starts.
     pub fn population_size(&self) -> usize {
         match self {
             AlgorithmParams::DE { population_size, .. } => *population_size,
             AlgorithmParams::CMAES { population_size, .. } => *population_size,
             AlgorithmParams::SCEUA { complexes } => *complexes,
+            AlgorithmParams::NSGA2 { population_size, .. } => *population_size,
+            AlgorithmParams::DDS { starts, .. } => *starts,
         }
     }
 }
@@ -152,23 +173,60 @@ pub struct Term {
     pub observed_file: String,
     pub observed_series: SeriesSpec,
     pub statistic: ObjectiveFunction,
+    /// Weight in the default objective (the weighted sum of terms). Defaults to 1.
+    pub weight: f64,
+    /// Pipeline applied to both series before the statistic, from `transform = ...` (empty = none)
+    pub transforms: Vec<SeriesTransform>,
+    /// Catchment whose model the simulated series comes from, in a regional calibration
+    pub catchment: Option<String>,
+}
+
+/// A catchment of a regional calibration, from a `[catchment.NAME]` section
+#[derive(Debug, Clone)]
+pub struct Catchment {
+    pub name: String,
+    pub model_file: String,
+}
+
+/// A named objective for multi-objective optimisation, from an `[objective.NAME]` section
+///
+/// Like `objective_expression`, the expression combines term names into a scalar loss.
+#[derive(Debug, Clone)]
+pub struct Objective {
+    pub name: String,
+    pub expression: String,
 }
 
 /// Optimisation configuration from INI format
 ///
 /// Composed of one or more [`Term`]s plus an `objective_expression` that combines them
-/// into a scalar fitness via the `crate::functions` expression parser.
+/// into a scalar fitness via the `crate::functions` expression parser. Multi-objective
+/// algorithms (NSGA2) instead use two or more [`Objective`]s.
 #[derive(Debug, Clone)]
 pub struct OptimisationConfig {
     // [optimisation] section - Problem definition
     pub model_file: Option<String>,  // Optional: can be provided via inline model instead
     pub terms: Vec<Term>,
     /// Expression over term names, e.g. `term1 + 0.5 * term2`. Parsed by `crate::functions`.
+    /// Defaults to the first objective's expression when `[objective.*]` sections are given,
+    /// otherwise to the weighted sum of all terms.
     pub objective_expression: String,
+    /// Objectives from `[objective.NAME]` sections, in declaration order (empty if none)
+    pub objectives: Vec<Objective>,
+    /// Catchments from `[catchment.NAME]` sections, calibrated together (empty = one model)
+    pub catchments: Vec<Catchment>,
     pub output_file: Option<String>,
+    /// CSV file receiving every evaluated parameter set and objective value (None = no trace)
+    pub trace_file: Option<String>,
+    /// Inclusive (start, end) timestamps the objective is computed over (None = all data)
+    pub calibration_period: Option<(u64, u64)>,
+    /// Inclusive (start, end) timestamps the best parameter set is evaluated over afterwards
+    pub validation_period: Option<(u64, u64)>,
 
     // [optimisation] section - Algorithm configuration
     pub termination_evaluations: usize,  // Termination criterion: stop after approximately this many function evaluations
+    /// Optional criteria for stopping before `termination_evaluations` (single-objective algorithms)
+    pub early_stopping: EarlyStopping,
     pub random_seed: Option<u64>,
     pub n_threads: usize,
     pub algorithm: AlgorithmParams,
@@ -200,17 +258,49 @@ impl OptimisationConfig {
         // Parse terms from [term.NAME] sections in declaration order
         let terms = Self::parse_terms(&data)?;
 
-        let objective_expression = data.require_property("optimisation", "objective_expression")?.to_string();
+        // Parse objectives from [objective.NAME] sections (multi-objective algorithms only)
+        let objectives = Self::parse_objectives(&data, &terms)?;
+
+        let objective_expression = match (data.get_property("optimisation", "objective_expression"), objectives.first()) {
+            (Some(expression), _) => expression.to_string(),
+            (None, Some(first)) => first.expression.clone(),
+            (None, None) => Self::weighted_sum_expression(&terms),
+        };
+
+        // Weights only shape the default objective; an explicit expression would silently ignore them
+        if objective_expression != Self::weighted_sum_expression(&terms) {
+            if let Some(term) = terms.iter().find(|t| t.weight != 1.0) {
+                return Err(format!(
+                    "'weight' in [term.{}] is only used when objective_expression is omitted; \
+                     put the weight in the expression instead",
+                    term.name
+                ));
+            }
+        }
         Self::validate_objective_expression(&objective_expression, &terms)?;
 
+        let catchments = Self::parse_catchments(&data, &terms)?;
+
         let output_file = data.get_property("optimisation", "output_file")
             .map(|s| s.to_string());
 
+        let trace_file = data.get_property("optimisation", "trace_file")
+            .map(|s| s.to_string());
+
+        let calibration_period = data.get_property("optimisation", "calibration_period")
+            .map(|p| Self::parse_period(p).map_err(|e| format!("Invalid 'calibration_period': {}", e)))
+            .transpose()?;
+        let validation_period = data.get_property("optimisation", "validation_period")
+            .map(|p| Self::parse_period(p).map_err(|e| format!("Invalid 'validation_period': {}", e)))
+            .transpose()?;
+
         // Algorithm configuration (same section)
         let termination_evaluations = data.require_property("optimisation", "termination_evaluations")?
             .parse::<usize>()
             .map_err(|_| "Invalid 'termination_evaluations' value")?;
 
+        let early_stopping = Self::parse_early_stopping(&data)?;
+
         let random_seed = data.get_property("optimisation", "random_seed")
             .and_then(|p| p.parse::<u64>().ok());
 
@@ -259,12 +349,61 @@ impl OptimisationConfig {
 
                 AlgorithmParams::SCEUA { complexes }
             },
+            "NSGA2" => {
+                let population_size = data.require_property("optimisation", "population_size")?
+                    .parse::<usize>()
+                    .map_err(|_| "Invalid 'population_size' for NSGA2")?;
+
+                let crossover_probability = data.get_property("optimisation", "nsga2_pc")
+                    .and_then(|p| p.parse::<f64>().ok())
+                    .unwrap_or(0.9);
+
+                let eta_c = data.get_property("optimisation", "nsga2_eta_c")
+                    .and_then(|p| p.parse::<f64>().ok())
+                    .unwrap_or(15.0);
+
+                let eta_m = data.get_property("optimisation", "nsga2_eta_m")
+                    .and_then(|p| p.parse::<f64>().ok())
+                    .unwrap_or(20.0);
+
+                if objectives.len() < 2 {
+                    return Err("NSGA2 requires at least two [objective.NAME] sections".to_string());
+                }
+
+                AlgorithmParams::NSGA2 { population_size, crossover_probability, eta_c, eta_m }
+            },
+            "DDS" => {
+                let r = data.get_property("optimisation", "dds_r")
+                    .and_then(|p| p.parse::<f64>().ok())
+                    .unwrap_or(0.2);
+
+                let starts = match data.get_property("optimisation", "dds_starts") {
+                    Some(p) => p.parse::<usize>()
+                        .ok()
+                        .filter(|&s| s > 0)
+                        .ok_or("Invalid 'dds_starts' for DDS (must be a positive integer)")?,
+                    None => 1,
+                };
+
+                AlgorithmParams::DDS { r, starts }
+            },
             _ => return Err(format!(
-                "Unknown algorithm: '{}'. Valid options: DE, CMAES, SCE",
+                "Unknown algorithm: '{}'. Valid options: DE, CMAES, SCE, NSGA2, DDS",
                 algorithm_name
             )),
         };
 
+        if !catchments.is_empty() && !objectives.is_empty() {
+            return Err("[objective.NAME] sections are not supported with [catchment.NAME] sections".to_string());
+        }
+
+        if !objectives.is_empty() && !matches!(algorithm, AlgorithmParams::NSGA2 { .. }) {
+            return Err(format!(
+                "[objective.NAME] sections require a multi-objective algorithm (NSGA2), not {}",
+                algorithm.name()
+            ));
+        }
+
         // Parse [Parameters] section
         let parameters_section = data.get_section("parameters")
             .ok_or_else(|| "Missing [Parameters] section".to_string())?;
@@ -276,16 +415,50 @@ impl OptimisationConfig {
             param_strings.push(mapping_str);
         }
 
-        let parameter_config = ParameterMappingConfig::from_strings(
+        let mut parameter_config = ParameterMappingConfig::from_strings(
             param_strings.iter().map(|s| s.as_str()).collect()
         )?;
 
+        // Parameters for one catchment only name a catchment that is defined
+        for mapping in &parameter_config.mappings {
+            if let Some((name, _)) = catchment_scope(&mapping.target) {
+                if !catchments.iter().any(|c| c.name.eq_ignore_ascii_case(name)) {
+                    return Err(format!("Parameter '{}' is for catchment '{}', which has no [catchment.{}] section",
+                                       mapping.target, name, name));
+                }
+            } else if mapping.target.to_lowercase().starts_with(CATCHMENT_PREFIX) {
+                return Err(format!("Invalid parameter '{}': expected catchment.NAME.ADDRESS", mapping.target));
+            }
+        }
+
+        // Parse optional [constraints] section: "name = inequality over parameter targets"
+        if let Some(constraints_section) = data.get_section("constraints") {
+            for (name, expression) in &constraints_section.properties {
+                parameter_config.add_constraint(name, expression)?;
+            }
+        }
+
+        parameter_config.constraint_handling = match data.get_property("optimisation", "constraint_handling") {
+            Some(h) => match h.to_lowercase().as_str() {
+                "reject" => ConstraintHandling::Reject,
+                "repair" => ConstraintHandling::Repair,
+                _ => return Err(format!("Invalid 'constraint_handling': '{}'. Valid options: reject, repair", h)),
+            },
+            None => ConstraintHandling::default(),
+        };
+
         Ok(Self {
             model_file,
             terms,
             objective_expression,
+            objectives,
+            catchments,
             output_file,
+            trace_file,
+            calibration_period,
+            validation_period,
             termination_evaluations,
+            early_stopping,
             random_seed,
             n_threads,
             algorithm,
@@ -293,6 +466,46 @@ impl OptimisationConfig {
         })
     }
 
+    /// Parse the optional early-stopping keys of the [optimisation] section
+    fn parse_early_stopping(data: &OptimisationConfigData) -> Result<EarlyStopping, String> {
+        let stagnation_evaluations = data.get_property("optimisation", "stagnation_evaluations")
+            .map(|p| p.parse::<usize>().map_err(|_| "Invalid 'stagnation_evaluations' value"))
+            .transpose()?;
+
+        let min_improvement = data.get_property("optimisation", "min_improvement")
+            .map(|p| p.parse::<f64>().map_err(|_| "Invalid 'min_improvement' value"))
+            .transpose()?
+            .unwrap_or(0.0);
+        if min_improvement < 0.0 {
+            return Err("'min_improvement' must be non-negative".to_string());
+        }
+
+        let objective_tolerance = data.get_property("optimisation", "objective_tolerance")
+            .map(|p| p.parse::<f64>().map_err(|_| "Invalid 'objective_tolerance' value"))
+            .transpose()?;
+
+        let max_time = data.get_property("optimisation", "max_time_seconds")
+            .map(|p| match p.parse::<f64>() {
+                Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => Ok(std::time::Duration::from_secs_f64(seconds)),
+                _ => Err("Invalid 'max_time_seconds' value"),
+            })
+            .transpose()?;
+
+        Ok(EarlyStopping { stagnation_evaluations, min_improvement, objective_tolerance, max_time, stop_flag: None })
+    }
+
+    /// Parse an inclusive date range, e.g. `1990-01-01, 1999-12-31`
+    pub fn parse_period(s: &str) -> Result<(u64, u64), String> {
+        let (start, end) = s.split_once(',')
+            .ok_or_else(|| format!("expected 'start, end' but got '{}'", s))?;
+        let start = date_string_to_u64_flexible(start.trim())?.0;
+        let end = date_string_to_u64_flexible(end.trim())?.0;
+        if start > end {
+            return Err(format!("start is after end in '{}'", s));
+        }
+        Ok((start, end))
+    }
+
     /// Parse all `[term.NAME]` sections in declaration order
     fn parse_terms(data: &OptimisationConfigData) -> Result<Vec<Term>, String> {
         let mut terms: Vec<Term> = Vec::new();
@@ -337,12 +550,31 @@ impl OptimisationConfig {
             let statistic = Self::parse_statistic(statistic_str)
                 .map_err(|e| format!("In [term.{}]: {}", term_name, e))?;
 
+            let weight = match section.properties.get("weight") {
+                Some(w) => w.parse::<f64>()
+                    .ok()
+                    .filter(|w| w.is_finite() && *w >= 0.0)
+                    .ok_or_else(|| format!("Invalid 'weight' in [term.{}] (must be a non-negative number)", term_name))?,
+                None => 1.0,
+            };
+
+            let transforms = match section.properties.get("transform") {
+                Some(t) => SeriesTransform::parse_pipeline(t)
+                    .map_err(|e| format!("Invalid 'transform' in [term.{}]: {}", term_name, e))?,
+                None => Vec::new(),
+            };
+
+            let catchment = section.properties.get("catchment").map(|c| c.trim().to_string());
+
             terms.push(Term {
                 name: term_name,
                 simulated_series,
                 observed_file,
                 observed_series,
                 statistic,
+                weight,
+                transforms,
+                catchment,
             });
         }
 
@@ -353,6 +585,97 @@ impl OptimisationConfig {
         Ok(terms)
     }
 
+    /// Parse all `[catchment.NAME]` sections in declaration order. With catchments, every term
+    /// names the catchment it belongs to; without them, no term may.
+    fn parse_catchments(data: &OptimisationConfigData, terms: &[Term]) -> Result<Vec<Catchment>, String> {
+        let mut catchments: Vec<Catchment> = Vec::new();
+
+        for (section_key, section) in &data.sections {
+            if !section_key.starts_with("catchment.") {
+                continue;
+            }
+
+            let name = section.original_name
+                .split_once('.')
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_default();
+            if name.is_empty() || name.contains('.') {
+                return Err(format!("Invalid catchment section name: [{}]", section.original_name));
+            }
+            if catchments.iter().any(|c| c.name.eq_ignore_ascii_case(&name)) {
+                return Err(format!("Duplicate catchment name '{}'", name));
+            }
+
+            let model_file = section.properties.get("model_file")
+                .ok_or_else(|| format!("Missing 'model_file' in [catchment.{}]", name))?
+                .to_string();
+
+            catchments.push(Catchment { name, model_file });
+        }
+
+        for term in terms {
+            match (&term.catchment, catchments.is_empty()) {
+                (Some(_), true) => return Err(format!(
+                    "'catchment' in [term.{}] needs a matching [catchment.NAME] section", term.name)),
+                (None, false) => return Err(format!(
+                    "Missing 'catchment' in [term.{}]; with [catchment.NAME] sections every term names its catchment",
+                    term.name)),
+                (Some(catchment), false) if !catchments.iter().any(|c| c.name.eq_ignore_ascii_case(catchment)) => {
+                    return Err(format!("Unknown catchment '{}' in [term.{}]", catchment, term.name));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(catchments)
+    }
+
+    /// Default objective: the weighted sum of all terms, e.g. `upstream + 0.5 * downstream`
+    fn weighted_sum_expression(terms: &[Term]) -> String {
+        terms.iter()
+            .map(|t| if t.weight == 1.0 { t.name.clone() } else { format!("{} * {}", t.weight, t.name) })
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+
+    /// Parse all `[objective.NAME]` sections in declaration order
+    fn parse_objectives(data: &OptimisationConfigData, terms: &[Term]) -> Result<Vec<Objective>, String> {
+        let mut objectives: Vec<Objective> = Vec::new();
+
+        for (section_key, section) in &data.sections {
+            if !section_key.starts_with("objective.") {
+                continue;
+            }
+
+            let name = section.original_name
+                .split_once('.')
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_default();
+            if name.is_empty() {
+                return Err(format!("Empty objective name in section [{}]", section.original_name));
+            }
+
+            let expression = section.properties.get("expression")
+                .ok_or_else(|| format!("Missing 'expression' in [objective.{}]", name))?
+                .to_string();
+            Self::validate_objective_expression(&expression, terms)
+                .map_err(|e| format!("In [objective.{}]: {}", name, e))?;
+
+            objectives.push(Objective { name, expression });
+        }
+
+        Ok(objectives)
+    }
+
+    /// Parse each objective's expression, for building a multi-objective problem
+    pub fn parse_objective_expressions(&self) -> Result<Vec<(String, crate::functions::ParsedFunction)>, String> {
+        self.objectives.iter()
+            .map(|o| crate::functions::parse_function(&o.expression)
+                .map(|parsed| (o.name.clone(), parsed))
+                .map_err(|e| format!("Failed to parse expression for objective '{}': {}", o.name, e)))
+            .collect()
+    }
+
     /// Validate the objective expression: parses, and every variable matches a term name
     fn validate_objective_expression(expression: &str, terms: &[Term]) -> Result<(), String> {
         let parsed = crate::functions::parse_function(expression)
@@ -388,11 +711,13 @@ impl OptimisationConfig {
             "RMSE" => Ok(ObjectiveFunction::RMSE(RmseObjective::new())),
             "MAE" => Ok(ObjectiveFunction::MAE(MaeObjective::new())),
             "ONE_MINUS_KGE" => Ok(ObjectiveFunction::OneMinusKge(KgeObjective::new())),
+            "ONE_MINUS_KGE_PRIME" => Ok(ObjectiveFunction::OneMinusKgePrime(KgePrimeObjective::new())),
+            "ONE_MINUS_KGE_NP" => Ok(ObjectiveFunction::OneMinusKgeNp(KgeNpObjective::new())),
             "ABS_PBIAS" => Ok(ObjectiveFunction::AbsPbias(PbiasObjective::new())),
             "SDEB" => Ok(ObjectiveFunction::SDEB(SdebObjective::new())),
             "ONE_MINUS_PEARS_R" => Ok(ObjectiveFunction::OneMinusPearsR(PearsObjective::new())),
             _ => Err(format!(
-                "Unknown statistic: '{}'. Valid options: ONE_MINUS_NSE, ONE_MINUS_LNSE, RMSE, MAE, ONE_MINUS_KGE, ABS_PBIAS, SDEB, ONE_MINUS_PEARS_R",
+                "Unknown statistic: '{}'. Valid options: ONE_MINUS_NSE, ONE_MINUS_LNSE, RMSE, MAE, ONE_MINUS_KGE, ONE_MINUS_KGE_PRIME, ONE_MINUS_KGE_NP, ABS_PBIAS, SDEB, ONE_MINUS_PEARS_R",
                 s
             )),
         }
@@ -453,6 +778,7 @@ mod tests {
 model_file = test.ini
 objective_expression = term1
 output_file = results.txt
+trace_file = Trace.csv
 algorithm = DE
 population_size = 30
 termination_evaluations = 50
@@ -480,6 +806,7 @@ node.gr4j.x2 = lin_range(g(2), -5, 3)
         assert_eq!(config.terms[0].observed_series, SeriesSpec::ByName("flow".to_string()));
         assert_eq!(config.terms[0].statistic.name(), "ONE_MINUS_NSE");
         assert_eq!(config.objective_expression, "term1");
+        assert_eq!(config.trace_file, Some("Trace.csv".to_string()));
         assert_eq!(config.algorithm.name(), "DE");
         assert_eq!(config.algorithm.population_size(), 30);
         assert_eq!(config.termination_evaluations, 50);
@@ -514,6 +841,7 @@ simulated = node.gauge.ds_1
 observed_file = data.csv
 observed_series = 2
 statistic = ABS_PBIAS
+transform = monthly_sum, boxcox(0.3)
 
 [parameters]
 node.x.x1 = lin_range(g(1), 0, 10)
@@ -521,6 +849,12 @@ node.x.x1 = lin_range(g(1), 0, 10)
 
         let config = OptimisationConfig::from_ini(ini_content).unwrap();
         assert_eq!(config.terms.len(), 2);
+        assert!(config.terms[0].transforms.is_empty());
+        let transforms: Vec<String> = config.terms[1].transforms.iter().map(|t| t.to_string()).collect();
+        assert_eq!(transforms, ["monthly_sum", "boxcox(0.3)"]);
+
+        let err = OptimisationConfig::from_ini(&ini_content.replace("boxcox(0.3)", "cube")).unwrap_err();
+        assert!(err.contains("Invalid 'transform' in [term.term2]"), "got: {}", err);
         assert_eq!(config.terms[0].name, "term1");
         assert_eq!(config.terms[1].name, "term2");
         assert_eq!(config.terms[1].observed_series, SeriesSpec::ByIndex(2));
@@ -529,6 +863,103 @@ node.x.x1 = lin_range(g(1), 0, 10)
         assert_eq!(config.objective_expression, "term1 + 0.5 * term2");
     }
 
+    const NSGA2_CONFIG: &str = r#"
+[optimisation]
+algorithm = NSGA2
+population_size = 40
+termination_evaluations = 100
+nsga2_eta_m = 10
+
+[term.daily]
+simulated = node.outlet.ds_1
+observed_file = data.csv
+observed_series = 1
+statistic = ONE_MINUS_NSE
+
+[term.volume]
+simulated = node.outlet_monthly.ds_1
+observed_file = data.csv
+observed_series = 2
+statistic = ABS_PBIAS
+
+[objective.Daily_NSE]
+expression = daily
+
+[objective.monthly_bias]
+expression = volume
+
+[parameters]
+node.x.x1 = lin_range(g(1), 0, 10)
+"#;
+
+    #[test]
+    fn test_parse_nsga2_objectives() {
+        let config = OptimisationConfig::from_ini(NSGA2_CONFIG).unwrap();
+        assert_eq!(config.objectives.len(), 2);
+        assert_eq!(config.objectives[0].name, "Daily_NSE");
+        assert_eq!(config.objectives[0].expression, "daily");
+        assert_eq!(config.objectives[1].name, "monthly_bias");
+
+        // Scalar expression defaults to the first objective
+        assert_eq!(config.objective_expression, "daily");
+        match &config.algorithm {
+            AlgorithmParams::NSGA2 { population_size, crossover_probability, eta_c, eta_m } => {
+                assert_eq!(*population_size, 40);
+                assert_eq!(*crossover_probability, 0.9);
+                assert_eq!(*eta_c, 15.0);
+                assert_eq!(*eta_m, 10.0);
+            },
+            _ => panic!("Expected NSGA2 algorithm"),
+        }
+        assert_eq!(config.parse_objective_expressions().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_nsga2_objective_errors() {
+        // Needs two objectives
+        let one = NSGA2_CONFIG.replace("[objective.monthly_bias]\nexpression = volume\n", "");
+        let err = OptimisationConfig::from_ini(&one).unwrap_err();
+        assert!(err.contains("at least two [objective.NAME]"), "got: {}", err);
+
+        // Objectives must reference defined terms
+        let bad = NSGA2_CONFIG.replace("expression = volume", "expression = volume + bogus");
+        let err = OptimisationConfig::from_ini(&bad).unwrap_err();
+        assert!(err.contains("[objective.monthly_bias]") && err.contains("bogus"), "got: {}", err);
+
+        // Objectives are not silently ignored by single-objective algorithms
+        let de = NSGA2_CONFIG.replace("algorithm = NSGA2", "algorithm = DE");
+        let err = OptimisationConfig::from_ini(&de).unwrap_err();
+        assert!(err.contains("NSGA2"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_parse_dds() {
+        let base = r#"
+[optimisation]
+algorithm = dds
+termination_evaluations = 500
+objective_expression = term1
+
+[term.term1]
+simulated = node.outlet.ds_1
+observed_file = data.csv
+observed_series = 1
+statistic = ONE_MINUS_NSE
+
+[parameters]
+node.x.x1 = lin_range(g(1), 0, 10)
+"#;
+        let config = OptimisationConfig::from_ini(base).unwrap();
+        assert_eq!(config.algorithm, AlgorithmParams::DDS { r: 0.2, starts: 1 });
+
+        let custom = base.replace("algorithm = dds", "algorithm = dds\ndds_r = 0.3\ndds_starts = 4");
+        let config = OptimisationConfig::from_ini(&custom).unwrap();
+        assert_eq!(config.algorithm, AlgorithmParams::DDS { r: 0.3, starts: 4 });
+
+        let invalid = base.replace("algorithm = dds", "algorithm = dds\ndds_starts = 0");
+        assert!(OptimisationConfig::from_ini(&invalid).unwrap_err().contains("dds_starts"));
+    }
+
     #[test]
     fn test_case_insensitive_keys_preserve_value_case() {
         let ini_content = r#"
@@ -564,6 +995,8 @@ Node.GR4J.X1 = log_range(g(1), 100, 1200)
         assert_eq!(OptimisationConfig::parse_statistic("ONE_MINUS_NSE").unwrap().name(), "ONE_MINUS_NSE");
         assert_eq!(OptimisationConfig::parse_statistic("one_minus_nse").unwrap().name(), "ONE_MINUS_NSE");
         assert_eq!(OptimisationConfig::parse_statistic("ONE_MINUS_KGE").unwrap().name(), "ONE_MINUS_KGE");
+        assert_eq!(OptimisationConfig::parse_statistic("one_minus_kge_prime").unwrap().name(), "ONE_MINUS_KGE_PRIME");
+        assert_eq!(OptimisationConfig::parse_statistic("ONE_MINUS_KGE_NP").unwrap().name(), "ONE_MINUS_KGE_NP");
         assert_eq!(OptimisationConfig::parse_statistic("ABS_PBIAS").unwrap().name(), "ABS_PBIAS");
         assert_eq!(OptimisationConfig::parse_statistic("RMSE").unwrap().name(), "RMSE");
         // Old names must be rejected
@@ -641,24 +1074,173 @@ node.x.x1 = lin_range(g(1), 0, 10)
     }
 
     #[test]
-    fn test_missing_objective_expression() {
+    fn test_weighted_terms_default_objective() {
         let ini_content = r#"
 [optimisation]
 algorithm = DE
 population_size = 10
 termination_evaluations = 10
 
+[term.upstream]
+simulated = node.a.ds_1
+observed_file = a.csv
+observed_series = 1
+statistic = ONE_MINUS_NSE
+
+[term.downstream]
+simulated = node.b.ds_1
+observed_file = b.csv
+observed_series = 1
+statistic = ONE_MINUS_KGE
+weight = 0.5
+
+[parameters]
+node.x.x1 = lin_range(g(1), 0, 10)
+"#;
+        let config = OptimisationConfig::from_ini(ini_content).unwrap();
+        assert_eq!(config.terms[0].weight, 1.0);
+        assert_eq!(config.terms[1].weight, 0.5);
+        assert_eq!(config.objective_expression, "upstream + 0.5 * downstream");
+    }
+
+    #[test]
+    fn test_term_weight_errors() {
+        let base = r#"
+[optimisation]
+algorithm = DE
+population_size = 10
+termination_evaluations = 10
+EXPRESSION
+
 [term.term1]
 simulated = node.a.ds_1
 observed_file = o.csv
 observed_series = 1
 statistic = RMSE
+weight = WEIGHT
 
 [parameters]
 node.x.x1 = lin_range(g(1), 0, 10)
 "#;
-        let err = OptimisationConfig::from_ini(ini_content).unwrap_err();
-        assert!(err.contains("objective_expression"), "got: {}", err);
+        let ini = |expression: &str, weight: &str| base.replace("EXPRESSION", expression).replace("WEIGHT", weight);
+
+        let err = OptimisationConfig::from_ini(&ini("", "-1")).unwrap_err();
+        assert!(err.contains("Invalid 'weight' in [term.term1]"), "got: {}", err);
+
+        // An explicit expression would ignore the weight
+        let err = OptimisationConfig::from_ini(&ini("objective_expression = term1", "2")).unwrap_err();
+        assert!(err.contains("only used when objective_expression is omitted"), "got: {}", err);
+        assert!(OptimisationConfig::from_ini(&ini("objective_expression = term1", "1")).is_ok());
+    }
+
+    #[test]
+    fn test_parse_calibration_validation_periods() {
+        let base = r#"
+[optimisation]
+objective_expression = term1
+algorithm = DE
+population_size = 10
+termination_evaluations = 10
+PERIODS
+
+[term.term1]
+simulated = node.a.ds_1
+observed_file = o.csv
+observed_series = 1
+statistic = RMSE
+
+[parameters]
+node.x.x1 = lin_range(g(1), 0, 10)
+"#;
+        let config = OptimisationConfig::from_ini(&base.replace("PERIODS", "")).unwrap();
+        assert_eq!(config.calibration_period, None);
+        assert_eq!(config.validation_period, None);
+
+        let periods = "calibration_period = 1990-01-01, 1999-12-31\nvalidation_period = 2000-01-01, 2009-12-31";
+        let config = OptimisationConfig::from_ini(&base.replace("PERIODS", periods)).unwrap();
+        let ts = |d: &str| date_string_to_u64_flexible(d).unwrap().0;
+        assert_eq!(config.calibration_period, Some((ts("1990-01-01"), ts("1999-12-31"))));
+        assert_eq!(config.validation_period, Some((ts("2000-01-01"), ts("2009-12-31"))));
+
+        let err = OptimisationConfig::from_ini(&base.replace("PERIODS", "calibration_period = 1999-12-31, 1990-01-01")).unwrap_err();
+        assert!(err.contains("Invalid 'calibration_period'"), "got: {}", err);
+        let err = OptimisationConfig::from_ini(&base.replace("PERIODS", "validation_period = 2000-01-01")).unwrap_err();
+        assert!(err.contains("Invalid 'validation_period'"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_parse_early_stopping() {
+        let base = r#"
+[optimisation]
+objective_expression = term1
+algorithm = SCE
+complexes = 2
+termination_evaluations = 10
+STOPPING
+
+[term.term1]
+simulated = node.a.ds_1
+observed_file = o.csv
+observed_series = 1
+statistic = RMSE
+
+[parameters]
+node.x.x1 = lin_range(g(1), 0, 10)
+"#;
+        let config = OptimisationConfig::from_ini(&base.replace("STOPPING", "")).unwrap();
+        assert!(!config.early_stopping.is_enabled());
+
+        let keys = "stagnation_evaluations = 500\nmin_improvement = 1e-4\nobjective_tolerance = 0.05\nmax_time_seconds = 90";
+        let config = OptimisationConfig::from_ini(&base.replace("STOPPING", keys)).unwrap();
+        assert_eq!(config.early_stopping, EarlyStopping {
+            stagnation_evaluations: Some(500),
+            min_improvement: 1e-4,
+            objective_tolerance: Some(0.05),
+            max_time: Some(std::time::Duration::from_secs(90)),
+            stop_flag: None,
+        });
+
+        let err = OptimisationConfig::from_ini(&base.replace("STOPPING", "max_time_seconds = soon")).unwrap_err();
+        assert!(err.contains("max_time_seconds"), "got: {}", err);
+        let err = OptimisationConfig::from_ini(&base.replace("STOPPING", "min_improvement = -1")).unwrap_err();
+        assert!(err.contains("min_improvement"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_parse_constraints() {
+        let base = r#"
+[optimisation]
+objective_expression = term1
+algorithm = DE
+population_size = 10
+termination_evaluations = 10
+HANDLING
+
+[term.term1]
+simulated = node.a.ds_1
+observed_file = o.csv
+observed_series = 1
+statistic = RMSE
+
+[parameters]
+node.sac.uztwm = lin_range(g(1), 10, 150)
+node.sac.lztwm = lin_range(g(2), 10, 500)
+
+[constraints]
+uz_below_lz = node.sac.uztwm < node.sac.lztwm
+"#;
+        let config = OptimisationConfig::from_ini(&base.replace("HANDLING", "")).unwrap();
+        let pc = &config.parameter_config;
+        assert_eq!(pc.constraints.len(), 1);
+        assert_eq!(pc.constraints[0].name, "uz_below_lz");
+        assert_eq!(pc.constraint_handling, ConstraintHandling::Reject);
+        assert!(pc.try_evaluate(&[1.0, 0.0]).is_err());
+
+        let config = OptimisationConfig::from_ini(&base.replace("HANDLING", "constraint_handling = Repair")).unwrap();
+        assert_eq!(config.parameter_config.constraint_handling, ConstraintHandling::Repair);
+
+        let err = OptimisationConfig::from_ini(&base.replace("HANDLING", "constraint_handling = ignore")).unwrap_err();
+        assert!(err.contains("Invalid 'constraint_handling'"), "got: {}", err);
     }
 
     #[test]
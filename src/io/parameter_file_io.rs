@@ -0,0 +1,96 @@
+//! Parameter files: sets of parameter values kept apart from the model structure, so that
+//! calibrated parameter sets can be versioned and swapped without editing the model file.
+//!
+//! Parameters are addressed as for calibration (see [`Model::set_parameter`]), e.g.
+//! `node.catchment.x1` or `c.demand_factor`. Two formats are read, chosen by extension:
+//!
+//! - CSV (`.csv`): rows of `parameter,value`, with an optional header row
+//! - INI (any other extension): `parameter = value` lines in a `[parameters]` section
+
+use crate::io::custom_ini_parser::IniDocument;
+use crate::model::Model;
+
+/// A parameter value, with the line of the file it came from
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterValue {
+    pub target: String,
+    pub value: f64,
+    pub line_number: usize,
+}
+
+fn parse_value(target: &str, value: &str, line_number: usize) -> Result<ParameterValue, String> {
+    let value = value.trim().parse::<f64>()
+        .map_err(|_| format!("Error on line {}: Invalid value '{}' for parameter '{}'", line_number, value.trim(), target))?;
+    Ok(ParameterValue { target: target.trim().to_string(), value, line_number })
+}
+
+/// Reads parameter values from CSV text: rows of `parameter,value`. A first row whose value
+/// is not a number is taken to be a header.
+pub fn parse_parameter_csv(text: &str) -> Result<Vec<ParameterValue>, String> {
+    let mut values = vec![];
+    let mut first_row = true;
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (target, value) = line.split_once(',')
+            .ok_or(format!("Error on line {}: Expected 'parameter,value'", i + 1))?;
+        if std::mem::take(&mut first_row) && value.trim().parse::<f64>().is_err() {
+            continue; // Header
+        }
+        values.push(parse_value(target, value, i + 1)?);
+    }
+    Ok(values)
+}
+
+/// Reads parameter values from INI text: `parameter = value` lines in a `[parameters]` section
+pub fn parse_parameter_ini(text: &str) -> Result<Vec<ParameterValue>, String> {
+    let doc = IniDocument::parse(text)?;
+    let mut values = vec![];
+    for (section_name, section) in &doc.sections {
+        if section_name.to_lowercase() != "parameters" {
+            return Err(format!("Error on line {}: Unexpected section '{}'. Parameters go in a [parameters] section",
+                               section.line_number, section_name));
+        }
+        for (target, property) in &section.properties {
+            values.push(parse_value(target, &property.value, property.line_number)?);
+        }
+    }
+    Ok(values)
+}
+
+/// Reads a parameter file, as CSV if its extension is `.csv` and as INI otherwise
+pub fn read_parameter_file(path: &str) -> Result<Vec<ParameterValue>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
+    let is_csv = std::path::Path::new(path).extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+    let values = match is_csv {
+        true => parse_parameter_csv(&text),
+        false => parse_parameter_ini(&text),
+    };
+    values.map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Writes parameter values as CSV, in the form [`parse_parameter_csv`] reads. Values are
+/// written in full, so a model given them runs exactly as the one they came from.
+pub fn parameters_to_csv(values: &[(String, f64)]) -> String {
+    let mut csv = String::from("parameter,value\n");
+    for (target, value) in values {
+        csv.push_str(&format!("{},{}\n", target, value));
+    }
+    csv
+}
+
+impl Model {
+    /// Applies parameter values over the model. Every parameter must already exist, so a
+    /// misspelt name is an error rather than being ignored.
+    pub fn apply_parameters(&mut self, values: &[ParameterValue]) -> Result<(), String> {
+        for p in values {
+            self.get_parameter(&p.target)
+                .and_then(|_| self.set_parameter(&p.target, p.value))
+                .map_err(|e| format!("Error on line {}: {}", p.line_number, e))?;
+        }
+        Ok(())
+    }
+}
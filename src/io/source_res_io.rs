@@ -0,0 +1,106 @@
+//! Results files in the layout eWater Source exports (`.res.csv`)
+//!
+//! Comparison spreadsheets and tools built around Source read its results files directly, so
+//! Kalix can write its outputs in the same layout: a metadata block ending `EOM`, a block of
+//! run details ending `EOC`, a table describing each column, then the column headers ending
+//! `EOH` and the data, with missing values written as -9999.
+
+use crate::io::csv_io::CsvError;
+use crate::timeseries::Timeseries;
+use crate::tid::utils::{u64_to_date_string, u64_to_date_string_for_step_size};
+use std::fs;
+
+/// Written in place of missing (NaN) values
+pub const MISSING_DATA_VALUE: &str = "-9999";
+
+/// Whether a file name asks for the Source results layout
+pub fn is_res_csv(filename: &str) -> bool {
+    filename.to_ascii_lowercase().ends_with(".res.csv")
+}
+
+/// Describes the run and the columns of a results file
+#[derive(Clone, Debug, Default)]
+pub struct ResCsvOptions {
+    /// Written as the project name, e.g. the model file's name
+    pub project_name: String,
+    /// Units of each series in order (blank if not given)
+    pub units: Option<Vec<String>>,
+    /// Type of the node each series comes from in order, e.g. `gr4j` (blank if not given).
+    /// Written as the water feature type.
+    pub feature_types: Option<Vec<String>>,
+}
+
+/// Splits a result name into the site and element of a Source column, e.g. `node.dam.volume`
+/// into `dam` and `volume`
+fn site_and_element(name: &str) -> (&str, &str) {
+    let name = name.strip_prefix("node.").unwrap_or(name);
+    match name.split_once('.') {
+        Some((site, element)) => (site, element),
+        None => (name, ""),
+    }
+}
+
+/// Writes timeseries to a results file in the Source layout
+pub fn write_res_csv(filename: &str, timeseries_vector: &[&Timeseries], options: &ResCsvOptions) -> Result<(), CsvError> {
+    let timestamps: &[u64] = timeseries_vector.first().map_or(&[], |ts| &ts.timestamps);
+    for ts in timeseries_vector {
+        if ts.timestamps.len() != timestamps.len() {
+            return Err(CsvError::WriteError("Cannot handle timeseries with different lengths.".to_string()));
+        }
+    }
+    let column_info = |info: &Option<Vec<String>>, what: &str| -> Result<Vec<String>, CsvError> {
+        match info {
+            Some(values) if values.len() != timeseries_vector.len() => Err(CsvError::WriteError(format!(
+                "Got {} for {} series but writing {}.", what, values.len(), timeseries_vector.len()))),
+            Some(values) => Ok(values.clone()),
+            None => Ok(vec![String::new(); timeseries_vector.len()]),
+        }
+    };
+    let units = column_info(&options.units, "units")?;
+    let feature_types = column_info(&options.feature_types, "feature types")?;
+
+    // Metadata and run details
+    let mut text = format!("File version,3\nMissing data value,{}\nEOM\n", MISSING_DATA_VALUE);
+    text.push_str(&format!("Project name,{}\n", options.project_name));
+    text.push_str(&format!("Kalix version,{}\n", env!("KALIX_VERSION")));
+    text.push_str(&format!("Latest result run time,{}\n", chrono::Local::now().format("%Y-%m-%d %H:%M:%S")));
+    if let (Some(first), Some(last)) = (timestamps.first(), timestamps.last()) {
+        text.push_str(&format!("Simulation time,{} - {}\n", u64_to_date_string(*first), u64_to_date_string(*last)));
+    }
+    text.push_str("Field,Units,RunName,ScenarioName,ScenarioInputSetName,Name,Site,ElementName,WaterFeatureType,ElementType,Structure,Custom\nEOC\n");
+
+    // One row describing each column
+    text.push_str(&format!("{}\n", timeseries_vector.len()));
+    let mut headers = String::from("Date");
+    for (i, ts) in timeseries_vector.iter().enumerate() {
+        let (site, element) = site_and_element(&ts.name);
+        let name = match feature_types[i].as_str() {
+            "" => format!("{}: {}", site, element),
+            feature_type => format!("{}: {}: {}", feature_type, site, element),
+        };
+        let element_type = if ts.name.starts_with("node.") { "Node" } else { "" };
+        text.push_str(&format!("{},{},Latest Run,Kalix,Default Input Set,{},{},{},{},{},{},,\n",
+            i + 1, units[i], name, site, element, feature_types[i], element_type, element));
+        headers.push_str(&format!(",{}>{}>{}", i + 1, site, element));
+    }
+    text.push_str(&headers);
+    text.push_str("\nEOH\n");
+
+    // The data
+    let step_size = timeseries_vector.first().map_or(0, |ts| ts.step_size);
+    for (i, timestamp) in timestamps.iter().enumerate() {
+        text.push_str(&u64_to_date_string_for_step_size(*timestamp, step_size));
+        for ts in timeseries_vector {
+            let value = ts.values[i];
+            text.push(',');
+            if value.is_nan() {
+                text.push_str(MISSING_DATA_VALUE);
+            } else {
+                text.push_str(&value.to_string());
+            }
+        }
+        text.push('\n');
+    }
+
+    fs::write(filename, text).map_err(|_| CsvError::WriteError(format!("Error writing file {filename}.")))
+}
@@ -0,0 +1,200 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use rayon::prelude::*;
+use crate::model::Model;
+
+/// One parameter set for a batch run: (target address, physical value) pairs, using the same
+/// addresses as [`Model::set_parameter`], e.g. `("node.sacramento_a.lztwm", 120.0)`.
+pub type ParameterSet = Vec<(String, f64)>;
+
+/// Options controlling what [`Model::run_batch`] returns.
+#[derive(Clone, Debug, Default)]
+pub struct BatchOptions {
+    /// Names of the series to report for each run. Empty means the model's `[outputs]`.
+    pub series: Vec<String>,
+
+    /// Also return the full values of each series, not just its summary statistics.
+    pub return_series: bool,
+
+    /// Number of worker threads. 0 means one per core. Results are the same, and in the same
+    /// order, for any number of threads.
+    pub n_threads: usize,
+}
+
+/// Summary statistics of one series from one run. Statistics ignore NaN values.
+#[derive(Clone, Debug, Default)]
+pub struct SeriesSummary {
+    pub count: usize,
+    pub sum: f64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl SeriesSummary {
+    pub fn from_values(values: &[f64]) -> Self {
+        let mut summary = SeriesSummary {
+            min: f64::NAN,
+            max: f64::NAN,
+            mean: f64::NAN,
+            ..Default::default()
+        };
+        for &v in values.iter().filter(|v| !v.is_nan()) {
+            summary.count += 1;
+            summary.sum += v;
+            summary.min = if summary.min.is_nan() { v } else { summary.min.min(v) };
+            summary.max = if summary.max.is_nan() { v } else { summary.max.max(v) };
+        }
+        if summary.count > 0 {
+            summary.mean = summary.sum / summary.count as f64;
+        }
+        summary
+    }
+}
+
+/// A reported series from one run of a batch.
+#[derive(Clone, Debug)]
+pub struct BatchSeriesResult {
+    pub name: String,
+    pub summary: SeriesSummary,
+    pub values: Option<Vec<f64>>,
+}
+
+/// The outcome of one parameter set. A failed run has `error` set and no series, and does not
+/// stop the rest of the batch.
+#[derive(Clone, Debug)]
+pub struct BatchRunResult {
+    pub index: usize,
+    pub error: Option<String>,
+    pub series: Vec<BatchSeriesResult>,
+}
+
+
+impl Model {
+
+    /// Runs the model once for each parameter set, in parallel, and returns one result per set
+    /// (in the same order). See [`Model::run_batch_with_interrupt`].
+    pub fn run_batch(&mut self, param_sets: &[ParameterSet], options: &BatchOptions) -> Result<Vec<BatchRunResult>, String> {
+        self.run_batch_with_interrupt(param_sets, options, || false, None)
+            .map(|results| results.unwrap_or_default())
+    }
+
+    /// Runs the model once for each parameter set, in parallel.
+    ///
+    /// The model is configured once, then cloned once per worker thread. Each worker applies its
+    /// parameter sets in turn, restoring the previous values between runs, so no INI reload or
+    /// per-run clone is needed. Returns `Ok(None)` if `interrupt_check` fired before the batch
+    /// finished. `progress_callback` receives (completed runs, total runs).
+    pub fn run_batch_with_interrupt<F>(&mut self, param_sets: &[ParameterSet], options: &BatchOptions,
+                                       interrupt_check: F,
+                                       progress_callback: Option<Box<dyn Fn(usize, usize) + Send + Sync>>)
+        -> Result<Option<Vec<BatchRunResult>>, String>
+    where
+        F: Fn() -> bool + Sync,
+    {
+        self.configure()?;
+        if param_sets.is_empty() {
+            return Ok(Some(vec![]));
+        }
+
+        let series_names = if options.series.is_empty() { self.outputs.clone() } else { options.series.clone() };
+        self.record_series_in_full(&series_names);
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.n_threads)
+            .build()
+            .map_err(|e| format!("Failed to create thread pool: {}", e))?;
+        let n_workers = pool.current_num_threads().clamp(1, param_sets.len());
+        let chunk_size = param_sets.len().div_ceil(n_workers);
+
+        let completed = AtomicUsize::new(0);
+        let interrupted = AtomicBool::new(false);
+        let base: &Model = self;
+
+        let results: Vec<BatchRunResult> = pool.install(|| {
+            param_sets.par_chunks(chunk_size).enumerate().flat_map_iter(|(chunk_idx, chunk)| {
+                let mut model = base.clone();
+                let mut chunk_results = Vec::with_capacity(chunk.len());
+                for (i, param_set) in chunk.iter().enumerate() {
+                    if interrupted.load(Ordering::Relaxed) || interrupt_check() {
+                        interrupted.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    let index = chunk_idx * chunk_size + i;
+                    let result = match model.run_parameter_set(param_set, &series_names, options.return_series, &interrupt_check) {
+                        Ok(Some(series)) => BatchRunResult { index, error: None, series },
+                        Ok(None) => {
+                            interrupted.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        Err(e) => BatchRunResult { index, error: Some(e), series: vec![] },
+                    };
+                    chunk_results.push(result);
+                    let n_done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                    if let Some(callback) = &progress_callback {
+                        callback(n_done, param_sets.len());
+                    }
+                }
+                chunk_results
+            }).collect()
+        });
+
+        if interrupted.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        Ok(Some(results))
+    }
+
+    /// Applies one parameter set, runs, collects the requested series, then restores the
+    /// parameters to their previous values. Returns `Ok(None)` if the run was interrupted.
+    fn run_parameter_set<F>(&mut self, param_set: &ParameterSet, series_names: &[String],
+                            return_series: bool, interrupt_check: &F)
+        -> Result<Option<Vec<BatchSeriesResult>>, String>
+    where
+        F: Fn() -> bool,
+    {
+        let mut previous: Vec<(&str, f64)> = Vec::with_capacity(param_set.len());
+        for (target, _) in param_set {
+            previous.push((target.as_str(), self.get_parameter(target)?));
+        }
+
+        let mut outcome = Ok(None);
+        let mut applied = 0;
+        for (target, value) in param_set {
+            if let Err(e) = self.set_parameter(target, *value) {
+                outcome = Err(e);
+                break;
+            }
+            applied += 1;
+        }
+
+        if applied == param_set.len() {
+            outcome = match self.run_with_interrupt(interrupt_check, None) {
+                Ok(true) => self.collect_batch_series(series_names, return_series).map(Some),
+                Ok(false) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+
+        // Restore in reverse order so that derived parameters unwind correctly
+        for &(target, value) in previous[..applied].iter().rev() {
+            self.set_parameter(target, value)?;
+        }
+        outcome
+    }
+
+    fn collect_batch_series(&self, series_names: &[String], return_series: bool) -> Result<Vec<BatchSeriesResult>, String> {
+        let mut results = Vec::with_capacity(series_names.len());
+        for name in series_names {
+            let idx = self.data_cache.get_existing_series_idx(name)
+                .ok_or_else(|| format!("Series not found: {}", name))?;
+            let series = self.data_cache.get_series(idx);
+            let values = &series.values;
+            results.push(BatchSeriesResult {
+                name: name.clone(),
+                summary: SeriesSummary::from_values(values),
+                values: if return_series { Some(values.clone()) } else { None },
+            });
+        }
+        Ok(results)
+    }
+}
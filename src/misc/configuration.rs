@@ -1,3 +1,8 @@
+use crate::model_inputs::NanPolicy;
+
+pub const DEFAULT_SPIN_UP_TOLERANCE: f64 = 0.001;
+pub const DEFAULT_SPIN_UP_MAX_LOOPS: usize = 20;
+pub const DEFAULT_NEGATIVE_TOLERANCE: f64 = 1e-6;
 
 #[derive(Debug)]
 #[derive(Clone)]
@@ -10,6 +15,42 @@ pub struct Configuration {
     pub sim_start_timestamp: u64,                   //The time (u64 representation) at the start of the FIRST simulated timestep.
     pub sim_end_timestamp: u64,                     //The time (u64 representation) at the start of the LAST simulated timestep.
     pub sim_nsteps: u64,                            //The number of simulated timesteps including the FIRST and LAST.
+
+    pub circular_references: CircularReferences,    //What to do when node inputs use each other's results from the same timestep.
+    pub compress_results: bool,                     //Hold recorded results in compressed storage during the run, to save memory.
+    pub single_precision: bool,                     //Hold recorded results in single precision (f32), to save memory.
+
+    pub spin_up_years: u32,                         //Years at the start of the period to loop until states settle before the run. 0 for none.
+    pub spin_up_tolerance: f64,                     //Largest relative change in a node state over a loop for the spin-up to have settled.
+    pub spin_up_max_loops: usize,                   //Loops after which the spin-up stops even if states have not settled.
+    pub nan_policy: NanPolicy,                      //What nodes do with a missing input value, unless set for the input's file.
+    pub strict: bool,                               //Whether a negative flow or storage volume stops the run, rather than being set to zero.
+    pub negative_tolerance: f64,                    //How far below zero a flow or volume may be (ML) before it is guarded against.
+}
+
+/// How circular references between nodes are handled, set by `circular_references` in [kalix]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CircularReferences {
+    /// Configuring the model fails, naming the nodes on the loop
+    #[default]
+    Error,
+    /// A reference on the loop reads the previous timestep's value instead, as if written `[-1, 0]`
+    PreviousValue,
+}
+
+impl CircularReferences {
+    pub const ALL: [CircularReferences; 2] = [CircularReferences::Error, CircularReferences::PreviousValue];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircularReferences::Error => "error",
+            CircularReferences::PreviousValue => "previous_value",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CircularReferences> {
+        CircularReferences::ALL.into_iter().find(|c| c.as_str() == name.trim().to_lowercase())
+    }
 }
 
 impl Configuration {
@@ -21,6 +62,15 @@ impl Configuration {
             sim_start_timestamp: 0,
             sim_end_timestamp: 0,
             sim_nsteps: 1, //1 + ((sim_end_timestamp - sim_start_timestamp) / sim_stepsize)
+            circular_references: CircularReferences::Error,
+            compress_results: false,
+            single_precision: false,
+            spin_up_years: 0,
+            spin_up_tolerance: DEFAULT_SPIN_UP_TOLERANCE,
+            spin_up_max_loops: DEFAULT_SPIN_UP_MAX_LOOPS,
+            nan_policy: NanPolicy::Propagate,
+            strict: false,
+            negative_tolerance: DEFAULT_NEGATIVE_TOLERANCE,
         }
     }
 }
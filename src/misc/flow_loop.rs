@@ -0,0 +1,16 @@
+//! Variants of the flow loop
+//!
+//! The flow phase visits every node in execution order and passes its outflows down its
+//! links. Which [`FlowLoop`] a run uses is chosen once, when the run is prepared, so the loop
+//! does not check at every node for features the run does not use.
+
+/// The variant of the flow loop a run uses
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct FlowLoop {
+    /// Whether the run skips nodes a re-run does not affect, and keeps the flows it delivers
+    pub(crate) incremental: bool,
+    /// Whether the time each node takes is added to the model's `node_timer`
+    pub(crate) profiled: bool,
+    /// Whether constituents are mixed in the nodes and carried down the links
+    pub(crate) constituents: bool,
+}
@@ -0,0 +1,375 @@
+//! Forecasting: an ensemble of runs from the state of the system on the forecast date.
+//!
+//! The history run takes the model from the start of its period to the step before
+//! `forecast_start`, and the stores it ends with (see [`Node::get_state`]) are the initial
+//! state of the forecast. A state saved from an earlier history run can be given instead, and
+//! then no history run is needed. Each forecast member runs from `forecast_start` to
+//! `forecast_end` from that state, with the forecast inputs replaced by the member's values.
+//! The columns of a forecast input are its members, and every forecast input has the same
+//! number of members. Inputs without a forecast keep their own data, which must cover the
+//! forecast period.
+//!
+//! Members run in parallel. The result holds each member's series, and quantiles across the
+//! members at each timestep, named as for GLUE (e.g. `node.x.dsflow_p50`).
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use rayon::prelude::*;
+use crate::data_management::data_cache::DataCache;
+use crate::io::csv_io;
+use crate::misc::simulation_context::clear_context;
+use crate::model::Model;
+use crate::nodes::Node;
+use crate::numerical::glue::{quantile_series_name, weighted_quantile};
+use crate::tid::utils::u64_to_date_string_for_step_size;
+use crate::timeseries::Timeseries;
+
+/// The stores of a model's nodes, as (node name, state) pairs, for the nodes that have any
+pub type ModelState = Vec<(String, Vec<f64>)>;
+
+/// The forecast members of one input series
+#[derive(Clone, Default)]
+pub struct ForecastInput {
+    /// The input series the members replace, e.g. `data.climate.by_name.rain`
+    pub series: String,
+    pub members: Vec<Timeseries>,
+}
+
+impl ForecastInput {
+    /// Reads the members from CSV text with a date column and one column per member
+    pub fn from_csv(series: &str, csv_text: &str) -> Result<ForecastInput, String> {
+        let source = format!("forecast for '{}'", series);
+        let members = csv_io::read_ts_from_str(csv_text, &source)?;
+        Ok(ForecastInput { series: series.to_string(), members })
+    }
+}
+
+/// Forecast configuration
+#[derive(Clone)]
+pub struct ForecastConfig {
+    /// Timestamp of the first forecast step
+    pub forecast_start: u64,
+
+    /// Timestamp of the last forecast step. None means the last step every member covers.
+    pub forecast_end: Option<u64>,
+
+    pub inputs: Vec<ForecastInput>,
+
+    /// The state to start the forecast from. None means it comes from a history run.
+    pub initial_state: Option<ModelState>,
+
+    /// Series to forecast. Empty means the model's `[outputs]`.
+    pub series: Vec<String>,
+
+    /// Quantiles across the members, e.g. 0.1, 0.5 and 0.9
+    pub quantiles: Vec<f64>,
+
+    /// Number of worker threads. 0 means one per core.
+    pub n_threads: usize,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            forecast_start: 0,
+            forecast_end: None,
+            inputs: vec![],
+            initial_state: None,
+            series: vec![],
+            quantiles: vec![0.1, 0.5, 0.9],
+            n_threads: 0,
+        }
+    }
+}
+
+/// The forecast of one series
+#[derive(Clone, Debug)]
+pub struct ForecastSeries {
+    pub name: String,
+
+    /// The values of each member, in member order
+    pub members: Vec<Vec<f64>>,
+
+    /// (data cache name, values) of each quantile, e.g. `node.x.dsflow_p50`
+    pub quantiles: Vec<(String, Vec<f64>)>,
+}
+
+/// Outcome of a forecast
+#[derive(Clone, Debug)]
+pub struct ForecastResult {
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+    pub step_size: u64,
+    pub n_members: usize,
+
+    /// The state the members started from. It can be saved and given as the initial state of
+    /// a later forecast from the same date.
+    pub initial_state: ModelState,
+
+    pub series: Vec<ForecastSeries>,
+}
+
+/// A member's simulation timestep and the values of each forecast series, or None if the run
+/// was interrupted
+type MemberRun = Option<(u64, Vec<Vec<f64>>)>;
+
+/// Data cache name for a member of a series, numbered from 1, e.g. `node.x.dsflow_m3`
+pub fn member_series_name(series_name: &str, member: usize) -> String {
+    format!("{}_m{}", series_name, member + 1)
+}
+
+impl ForecastResult {
+    /// Writes each member and quantile into the data cache under its `_mN` or `_pNN` name,
+    /// replacing any existing series of the same name.
+    pub fn write_to_data_cache(&self, data_cache: &mut DataCache) {
+        for s in &self.series {
+            let members = s.members.iter().enumerate()
+                .map(|(m, values)| (member_series_name(&s.name, m), values));
+            let quantiles = s.quantiles.iter().map(|(name, values)| (name.clone(), values));
+            for (name, values) in members.chain(quantiles) {
+                let idx = data_cache.get_or_add_new_series(&name, false);
+                let mut ts = Timeseries::new(self.step_size);
+                ts.name = name;
+                ts.start_timestamp = self.start_timestamp;
+                for &v in values {
+                    ts.push_value(v);
+                }
+                data_cache.replace_series(idx, ts);
+            }
+        }
+    }
+}
+
+/// The input series with its data before `forecast_start` followed by the member's values up
+/// to `forecast_end`
+fn splice_member(history: &Timeseries, member: &Timeseries, forecast_start: u64, forecast_end: u64) -> Timeseries {
+    let step = history.step_size;
+    let mut ts = Timeseries::new(step);
+    ts.start_timestamp = history.start_timestamp.min(forecast_start);
+    let mut t = ts.start_timestamp;
+    while t < forecast_start {
+        let i = ((t - history.start_timestamp) / step) as usize;
+        ts.push_value(history.values.get(i).copied().unwrap_or(f64::NAN));
+        t += step;
+    }
+    while t <= forecast_end {
+        let i = ((t - member.start_timestamp) / step) as usize;
+        ts.push_value(member.values[i]);
+        t += step;
+    }
+    ts
+}
+
+impl Model {
+    /// The stores of every node that has any (see [`Node::get_state`])
+    pub fn node_states(&self) -> ModelState {
+        self.nodes.iter()
+            .map(|n| (n.get_name().to_string(), n.get_state()))
+            .filter(|(_, state)| !state.is_empty())
+            .collect()
+    }
+
+    /// Restores node stores taken with [`Model::node_states`]. Must follow the initialisation
+    /// of a run, which resets them.
+    pub fn set_node_states(&mut self, states: &ModelState) -> Result<(), String> {
+        for (name, state) in states {
+            let idx = self.get_node_idx(name)
+                .ok_or_else(|| format!("State for unknown node '{}'", name))?;
+            let expected = self.nodes[idx].get_state().len();
+            if state.len() != expected {
+                return Err(format!("State for node '{}' has {} values but the node has {}", name, state.len(), expected));
+            }
+            self.nodes[idx].set_state(state);
+        }
+        Ok(())
+    }
+
+    /// Runs the configured period starting from `states`. Returns false if interrupted.
+    fn run_from_state<F>(&mut self, states: &ModelState, interrupt_check: &F) -> Result<bool, String>
+    where
+        F: Fn() -> bool,
+    {
+        self.prepare_run(self.configuration.sim_nsteps as usize)?;
+        self.set_node_states(states)?;
+        self.data_cache.set_current_step(0);
+        while self.data_cache.current_timestamp <= self.configuration.sim_end_timestamp {
+            if interrupt_check() {
+                clear_context();
+                return Ok(false);
+            }
+            self.run_timestep_catching_panics()?;
+            self.data_cache.increment_current_step();
+        }
+        clear_context();
+        Ok(true)
+    }
+
+    /// Runs the history up to the step before `forecast_start` and returns the state it ends
+    /// with, or None if interrupted
+    fn history_state<F>(&self, forecast_start: u64, interrupt_check: &F) -> Result<Option<ModelState>, String>
+    where
+        F: Fn() -> bool,
+    {
+        let mut history = self.clone();
+        history.configure()?;
+        let start = history.configuration.sim_start_timestamp;
+        let step = history.configuration.sim_stepsize;
+        if forecast_start <= start {
+            return Err(format!("The forecast starts on or before the start of the model ({}), so there is no history to run. Give a saved state instead.",
+                               u64_to_date_string_for_step_size(start, step)));
+        }
+        history.configuration.specified_sim_end_timestamp = Some(forecast_start - step);
+        history.configure()
+            .map_err(|e| format!("History run: {}", e))?;
+        match history.run_with_interrupt(interrupt_check, None) {
+            Ok(true) => Ok(Some(history.node_states())),
+            Ok(false) => Ok(None),
+            Err(e) => Err(format!("History run: {}", e)),
+        }
+    }
+
+    /// Runs a forecast ensemble (see the [module docs](self)).
+    ///
+    /// Returns `Ok(None)` if `interrupt_check` fired before the forecast finished.
+    /// `progress_callback` receives (completed members, total members).
+    pub fn run_forecast<F>(&self, config: &ForecastConfig, interrupt_check: F,
+                           progress_callback: Option<Box<dyn Fn(usize, usize) + Send + Sync>>)
+        -> Result<Option<ForecastResult>, String>
+    where
+        F: Fn() -> bool + Sync,
+    {
+        let forecast_start = config.forecast_start;
+        let n_members = config.inputs.first().map_or(0, |input| input.members.len());
+        if n_members == 0 {
+            return Err("A forecast needs at least one input with at least one member".to_string());
+        }
+        if !config.quantiles.iter().all(|&q| 0.0 < q && q < 1.0) {
+            return Err(format!("Forecast quantiles must be between 0 and 1, got {:?}", config.quantiles));
+        }
+
+        // Members must be on their input's timestep and cover the forecast period
+        let mut inputs = config.inputs.clone();
+        let (mut last_covered, mut last_step) = (u64::MAX, 0);
+        for input in inputs.iter_mut() {
+            let step = self.get_input_series(&input.series)
+                .ok_or_else(|| format!("Input series not found: {}", input.series))?
+                .step_size;
+            if input.members.len() != n_members {
+                return Err(format!("Forecast for '{}' has {} members but forecast for '{}' has {}",
+                                   input.series, input.members.len(), config.inputs[0].series, n_members));
+            }
+            for (m, member) in input.members.iter_mut().enumerate() {
+                if member.values.len() < 2 {
+                    member.step_size = step; // A single value has no step of its own
+                }
+                if member.step_size != step {
+                    return Err(format!("Member {} of the forecast for '{}' has step_size {} but the input has step_size {}",
+                                       m + 1, input.series, member.step_size, step));
+                }
+                if member.values.is_empty() || member.start_timestamp > forecast_start {
+                    return Err(format!("Member {} of the forecast for '{}' starts after the forecast start ({})",
+                                       m + 1, input.series, u64_to_date_string_for_step_size(forecast_start, step)));
+                }
+                let last = member.start_timestamp + (member.values.len() as u64 - 1) * step;
+                if last < last_covered {
+                    (last_covered, last_step) = (last, step);
+                }
+            }
+        }
+        let forecast_end = config.forecast_end.unwrap_or(last_covered);
+        if forecast_end < forecast_start {
+            return Err("The forecast ends before it starts".to_string());
+        }
+        if forecast_end > last_covered {
+            return Err(format!("The forecast members end on {}, before the end of the forecast",
+                               u64_to_date_string_for_step_size(last_covered, last_step)));
+        }
+
+        let initial_state = match &config.initial_state {
+            Some(state) => state.clone(),
+            None => match self.history_state(forecast_start, &interrupt_check)? {
+                Some(state) => state,
+                None => return Ok(None),
+            },
+        };
+
+        let series_names = if config.series.is_empty() { self.outputs.clone() } else { config.series.clone() };
+        if series_names.is_empty() {
+            return Err("A forecast needs at least one series (none given and the model has no [outputs])".to_string());
+        }
+        let mut base = self.clone();
+        base.configuration.spin_up_years = 0;
+        base.configuration.specified_sim_start_timestamp = Some(forecast_start);
+        base.configuration.specified_sim_end_timestamp = Some(forecast_end);
+        base.record_series_in_full(&series_names);
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.n_threads)
+            .build()
+            .map_err(|e| format!("Failed to create thread pool: {}", e))?;
+        let completed = AtomicUsize::new(0);
+        let interrupted = AtomicBool::new(false);
+        let run_member = |m: usize| -> Result<MemberRun, String> {
+            if interrupted.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            let mut model = base.clone();
+            for input in &inputs {
+                let spliced = splice_member(base.get_input_series(&input.series).unwrap(), &input.members[m],
+                                            forecast_start, forecast_end);
+                model.replace_input_series(&input.series, spliced)?;
+            }
+            model.configure()?;
+            if !model.run_from_state(&initial_state, &interrupt_check)? {
+                interrupted.store(true, Ordering::Relaxed);
+                return Ok(None);
+            }
+            let values = series_names.iter()
+                .map(|name| model.data_cache.get_existing_series_idx(name)
+                    .map(|idx| model.data_cache.get_series(idx).values.clone())
+                    .ok_or_else(|| format!("Series not found: {}", name)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let n_done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(callback) = &progress_callback {
+                callback(n_done, n_members);
+            }
+            Ok(Some((model.configuration.sim_stepsize, values)))
+        };
+        let members: Vec<MemberRun> = pool.install(|| {
+            (0..n_members).into_par_iter()
+                .map(|m| run_member(m).map_err(|e| format!("Member {}: {}", m + 1, e)))
+                .collect::<Result<_, _>>()
+        })?;
+        let Some(members) = members.into_iter().collect::<Option<Vec<_>>>() else {
+            return Ok(None);
+        };
+        let step_size = members[0].0;
+
+        let series = series_names.iter().enumerate().map(|(s, name)| {
+            let member_values: Vec<Vec<f64>> = members.iter().map(|(_, m)| m[s].clone()).collect();
+            let n_steps = member_values.iter().map(|v| v.len()).max().unwrap_or(0);
+            let mut step_values: Vec<(f64, f64)> = Vec::with_capacity(n_members);
+            let mut quantiles: Vec<(String, Vec<f64>)> = config.quantiles.iter()
+                .map(|&q| (quantile_series_name(name, q), Vec::with_capacity(n_steps)))
+                .collect();
+            for t in 0..n_steps {
+                step_values.clear();
+                step_values.extend(member_values.iter()
+                    .filter_map(|v| v.get(t).filter(|v| !v.is_nan()).map(|&v| (v, 1.0))));
+                step_values.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (&q, (_, values)) in config.quantiles.iter().zip(quantiles.iter_mut()) {
+                    values.push(weighted_quantile(&step_values, q));
+                }
+            }
+            ForecastSeries { name: name.clone(), members: member_values, quantiles }
+        }).collect();
+
+        Ok(Some(ForecastResult {
+            start_timestamp: forecast_start,
+            end_timestamp: forecast_end,
+            step_size,
+            n_members,
+            initial_state,
+            series,
+        }))
+    }
+}
@@ -0,0 +1,115 @@
+use crate::model::Model;
+use crate::nodes::Node;
+
+/// Formats the node-link network can be exported in, for viewing in other tools
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT: the network topology, for `dot` and other Graphviz viewers
+    Dot,
+    /// GeoJSON: nodes as points and links as lines at the nodes' `loc` coordinates, for GIS tools
+    GeoJson,
+}
+
+impl GraphFormat {
+    pub const ALL: [GraphFormat; 2] = [GraphFormat::Dot, GraphFormat::GeoJson];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::GeoJson => "geojson",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<GraphFormat, String> {
+        let lower = name.trim().to_lowercase();
+        GraphFormat::ALL.into_iter()
+            .find(|f| f.as_str() == lower)
+            .ok_or_else(|| format!("Unknown graph format '{}'. Expected one of: {}", name,
+                                   GraphFormat::ALL.map(|f| f.as_str()).join(", ")))
+    }
+
+    /// The format for a file, by its extension (`.dot`/`.gv` or `.geojson`/`.json`)
+    pub fn from_path(path: &str) -> Result<GraphFormat, String> {
+        let extension = std::path::Path::new(path).extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match extension.as_str() {
+            "dot" | "gv" => Ok(GraphFormat::Dot),
+            "geojson" | "json" => Ok(GraphFormat::GeoJson),
+            _ => Err(format!("Cannot tell the graph format of '{}'. Use a .dot, .gv, .geojson or .json file", path)),
+        }
+    }
+}
+
+/// Escapes text for a quoted DOT string
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Quotes a name as a DOT identifier
+fn dot_id(s: &str) -> String {
+    format!("\"{}\"", dot_escape(s))
+}
+
+impl Model {
+    /// The node-link network as text in the given format. Nodes are in model order and links
+    /// in the order they were added. A link from any outlet but a node's first is labelled with
+    /// its outlet, e.g. `ds_2`.
+    ///
+    /// GeoJSON coordinates are the nodes' `loc` values as given in the model, so they are only
+    /// geographic if the model's locations are longitudes and latitudes.
+    pub fn export_graph(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.graph_to_dot(),
+            GraphFormat::GeoJson => self.graph_to_geojson(),
+        }
+    }
+
+    fn graph_to_dot(&self) -> String {
+        let mut dot = String::from("digraph kalix {\n");
+        for node in &self.nodes {
+            // Labelled with the name above the type, split by DOT's "\n" escape
+            dot.push_str(&format!("    {} [label=\"{}\\n{}\"];\n", dot_id(node.get_name()),
+                                  dot_escape(node.get_name()), node.get_type_as_string()));
+        }
+        for link in &self.links {
+            let from = dot_id(self.nodes[link.from_node].get_name());
+            let to = dot_id(self.nodes[link.to_node].get_name());
+            if link.from_outlet == 0 {
+                dot.push_str(&format!("    {} -> {};\n", from, to));
+            } else {
+                dot.push_str(&format!("    {} -> {} [label=\"ds_{}\"];\n", from, to, link.from_outlet as u32 + 1));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn graph_to_geojson(&self) -> String {
+        let coordinates = |idx: usize| {
+            let location = self.nodes[idx].get_location();
+            serde_json::json!([location.x(), location.y()])
+        };
+        let nodes = self.nodes.iter().enumerate().map(|(idx, node)| serde_json::json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": coordinates(idx) },
+            "properties": { "kind": "node", "name": node.get_name(), "node_type": node.get_type_as_string() }
+        }));
+        let links = self.links.iter().map(|link| serde_json::json!({
+            "type": "Feature",
+            "geometry": { "type": "LineString", "coordinates": [coordinates(link.from_node), coordinates(link.to_node)] },
+            "properties": {
+                "kind": "link",
+                "from": self.nodes[link.from_node].get_name(),
+                "to": self.nodes[link.to_node].get_name(),
+                "outlet": format!("ds_{}", link.from_outlet as u32 + 1)
+            }
+        }));
+        let collection = serde_json::json!({
+            "type": "FeatureCollection",
+            "features": nodes.chain(links).collect::<Vec<_>>()
+        });
+        serde_json::to_string_pretty(&collection).unwrap()
+    }
+}
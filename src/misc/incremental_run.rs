@@ -0,0 +1,334 @@
+//! Incremental runs: after a localised edit, re-simulating only the part of the network it
+//! affects.
+//!
+//! With incremental runs enabled (see [`Model::enable_incremental_runs`]), each run keeps the
+//! flow delivered along every link at every timestep, and its nodes and links as they were at
+//! the end of the run. Changes made through [`Model::set_parameter`] and
+//! [`Model::replace_input_series`] are noted, and the next run works out which nodes they
+//! affect: the nodes changed or reading a changed input, the nodes downstream of them, and the
+//! nodes whose inputs read any of their results. Where one of them passes orders, so do all
+//! the nodes that pass orders. Only the affected nodes are re-simulated, and the ordering phase
+//! only runs if they pass orders. The rest keep their results and their state from the last
+//! run, and their cached flows are fed to the affected nodes below them.
+//!
+//! The whole model is re-run, and the reason logged as `full_rerun`, where a change can reach
+//! the nodes in other ways: a constant changed, the period or the model's structure is not
+//! the last run's, the model has spin-up, constituents, policies, allocation or accounts, or a
+//! function reads a result of an affected node or a changed input. Other changes to the model
+//! (e.g. to its nodes' fields directly) are not noticed, and need [`Model::clear_run_cache`].
+//!
+//! The cache holds one value per link per timestep, on top of the results. Results that
+//! inputs read are recorded in full, so the affected nodes can read them from the last run.
+
+use std::sync::Arc;
+use rustc_hash::FxHashSet;
+use crate::data_management::run_log::LogLevel;
+use crate::model::Model;
+use crate::nodes::{Link, Node, NodeEnum};
+
+/// The period and shape of the model a run was made with: start, end and step, the numbers
+/// of nodes, links and series, and whether events were recorded
+type Fingerprint = (u64, u64, u64, usize, usize, usize, bool);
+
+/// What a complete run leaves for the next run to re-use
+#[derive(Clone)]
+struct CachedRun {
+    fingerprint: Fingerprint,
+    /// The flow delivered along each link at each timestep, step by step, with the links of
+    /// each step indexed like `Model::links`
+    link_flows: Vec<f64>,
+    n_links: usize,
+    /// The nodes and links as at the end of the run
+    nodes: Vec<NodeEnum>,
+    links: Vec<Link>,
+}
+
+/// The last run kept for incremental runs, and the changes made since
+#[derive(Clone, Default)]
+pub struct RunCache {
+    enabled: bool,
+    last: Option<Arc<CachedRun>>,
+    /// Nodes whose parameters were set since the last run
+    changed_nodes: Vec<usize>,
+    /// Inputs whose data was replaced since the last run
+    changed_inputs: Vec<usize>,
+    /// Set by changes that may reach any node, e.g. to a constant
+    changed_all: bool,
+    /// The run being kept, while the model runs
+    recording: Option<CachedRun>,
+    /// Which nodes are re-simulated, while the model re-runs part of the network
+    rerun: Option<Vec<bool>>,
+    /// Whether the re-run leaves out the ordering phase, as it affects none of the nodes that
+    /// pass orders
+    skip_ordering: bool,
+    /// The nodes the last run re-simulated, or None if it ran the whole model
+    last_rerun: Option<Vec<usize>>,
+}
+
+impl RunCache {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Notes a change to a node's parameters
+    pub(crate) fn node_changed(&mut self, node_idx: usize) {
+        if !self.changed_nodes.contains(&node_idx) {
+            self.changed_nodes.push(node_idx);
+        }
+    }
+
+    /// Notes a change to an input's data
+    pub(crate) fn input_changed(&mut self, input_idx: usize) {
+        if !self.changed_inputs.contains(&input_idx) {
+            self.changed_inputs.push(input_idx);
+        }
+    }
+
+    /// Notes a change that may reach any node
+    pub(crate) fn everything_changed(&mut self) {
+        self.changed_all = true;
+    }
+
+    /// Forgets any run being kept, ready for a new run. Results that runs other than
+    /// incremental ones leave behind can't be re-used, so the last run is forgotten too.
+    pub(crate) fn begin_run(&mut self) {
+        self.last = None;
+        self.recording = None;
+        self.rerun = None;
+        self.skip_ordering = false;
+    }
+
+    /// Whether the run is being kept for the next run to re-use
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Whether the node is left out of the timestep, as a re-run does not affect it
+    #[inline]
+    pub(crate) fn skips(&self, node_idx: usize) -> bool {
+        self.rerun.as_ref().is_some_and(|rerun| !rerun[node_idx])
+    }
+
+    /// Whether the ordering phase is left out of the timestep. The nodes that pass orders are
+    /// either all re-simulated or none are, and if none are, they keep their orders and
+    /// results from the last run.
+    #[inline]
+    pub(crate) fn skips_ordering(&self) -> bool {
+        self.skip_ordering
+    }
+
+    /// The flow the last run delivered along a link at a step
+    #[inline]
+    pub(crate) fn cached_flow(&self, link_idx: usize, step: usize) -> f64 {
+        self.recording.as_ref().map_or(0.0, |run| run.link_flows[step * run.n_links + link_idx])
+    }
+
+    /// Keeps the flow delivered along a link at a step, if the run is being kept
+    #[inline]
+    pub(crate) fn record_flow(&mut self, link_idx: usize, step: usize, flow: f64) {
+        if let Some(run) = &mut self.recording {
+            run.link_flows[step * run.n_links + link_idx] = flow;
+        }
+    }
+}
+
+impl Model {
+    /// Sets whether runs keep what the next run needs to re-simulate only the nodes that
+    /// changes affect. Turning it off forgets the last run.
+    pub fn enable_incremental_runs(&mut self, enabled: bool) {
+        self.run_cache.enabled = enabled;
+        if !enabled {
+            self.clear_run_cache();
+        }
+    }
+
+    /// Forgets the last run, so the next run simulates the whole model. Needed after changes
+    /// other than through `set_parameter` and `replace_input_series`.
+    pub fn clear_run_cache(&mut self) {
+        self.run_cache.begin_run();
+        self.run_cache.last_rerun = None;
+    }
+
+    /// The nodes the last run re-simulated, in execution order, or None if it ran the whole
+    /// model
+    pub fn rerun_nodes(&self) -> Option<&[usize]> {
+        self.run_cache.last_rerun.as_deref()
+    }
+
+    fn run_fingerprint(&self) -> Fingerprint {
+        (self.configuration.sim_start_timestamp, self.configuration.sim_end_timestamp,
+         self.configuration.sim_stepsize, self.nodes.len(), self.links.len(),
+         self.data_cache.series.len(), self.data_cache.events.enabled)
+    }
+
+    /// Prepares a run as `prepare_run()` does, and with incremental runs enabled, sets it up
+    /// to re-simulate only the nodes affected by the changes since the last run, if it can
+    pub(crate) fn prepare_incremental_run(&mut self, total_steps: usize) -> Result<(), String> {
+        let last = self.run_cache.last.take();
+        let plan = match &last {
+            Some(last) if self.run_cache.enabled => self.affected_nodes(last),
+            _ => Ok(None),
+        };
+        self.run_cache.changed_nodes.clear();
+        self.run_cache.changed_inputs.clear();
+        self.run_cache.changed_all = false;
+        self.run_cache.last_rerun = None;
+
+        // The results of nodes that are not re-simulated keep their log entries and events
+        let previous = match &plan {
+            Ok(Some(_)) => Some((std::mem::take(&mut self.data_cache.log), self.data_cache.events.clone())),
+            _ => None,
+        };
+        self.prepare_run(total_steps)?;
+        if !self.run_cache.enabled || self.configuration.spin_up_years > 0 {
+            return Ok(());
+        }
+
+        let mut recording = match (plan, last) {
+            (Ok(Some(affected)), Some(last)) => {
+                let mut last = Arc::try_unwrap(last).unwrap_or_else(|last| (*last).clone());
+                let (mut log, mut events) = previous.unwrap_or_default();
+                // Sources are node names, or for links, the node name and outlet (e.g. "dam.ds_1")
+                let unaffected: FxHashSet<&str> = self.nodes.iter().enumerate()
+                    .filter(|&(i, _)| !affected[i])
+                    .map(|(_, node)| node.get_name())
+                    .collect();
+                let kept = |source: &str| source.split('.').next().is_some_and(|name| unaffected.contains(name));
+                log.retain(|entry| kept(&entry.source));
+                events.retain(|source| kept(source));
+                self.data_cache.log.append(log);
+                self.data_cache.events = events;
+
+                for (i, node) in std::mem::take(&mut last.nodes).into_iter().enumerate() {
+                    if !affected[i] {
+                        self.nodes[i] = node;
+                    }
+                }
+                for (i, link) in std::mem::take(&mut last.links).into_iter().enumerate() {
+                    if !affected[link.from_node] {
+                        self.links[i] = link;
+                    }
+                }
+                let rerun: Vec<usize> = self.execution_order.iter().copied().filter(|&i| affected[i]).collect();
+                let message = format!("Re-simulated {} of {} nodes, for the changes since the last run",
+                                      rerun.len(), self.nodes.len());
+                self.data_cache.log.record(LogLevel::Info, "incremental_run", "model", None, || message);
+                self.run_cache.last_rerun = Some(rerun);
+                self.run_cache.skip_ordering = self.simple_ordering_system.ordered_nodes().all(|i| !affected[i]);
+                self.run_cache.rerun = Some(affected);
+                last
+            }
+            (plan, _) => {
+                if let Err(reason) = plan {
+                    let message = format!("The whole model was re-run, as {}", reason);
+                    self.data_cache.log.record(LogLevel::Info, "full_rerun", "model", None, || message);
+                }
+                CachedRun {
+                    fingerprint: self.run_fingerprint(),
+                    link_flows: vec![0.0; total_steps * self.links.len()],
+                    n_links: self.links.len(),
+                    nodes: vec![],
+                    links: vec![],
+                }
+            }
+        };
+        recording.fingerprint = self.run_fingerprint();
+        self.run_cache.recording = Some(recording);
+        self.choose_flow_loop();
+        Ok(())
+    }
+
+    /// Keeps the run just completed for the next run to re-use, if it was being kept
+    pub(crate) fn finish_incremental_run(&mut self) {
+        if let Some(mut run) = self.run_cache.recording.take() {
+            run.nodes = self.nodes.clone();
+            run.links = self.links.clone();
+            self.run_cache.last = Some(Arc::new(run));
+        }
+        self.run_cache.skip_ordering = false;
+        if self.run_cache.rerun.take().is_some() {
+            self.data_cache.events.sort_by_step();
+        }
+    }
+
+    /// The nodes (flagged by index) that the changes since `last` affect, or None if there
+    /// is nothing to re-use. Fails, saying why, where the whole model has to be re-run.
+    fn affected_nodes(&mut self, last: &CachedRun) -> Result<Option<Vec<bool>>, String> {
+        if self.run_cache.changed_all {
+            return Err("a constant was changed, and constants may be read anywhere".to_string());
+        }
+        if last.fingerprint != self.run_fingerprint() {
+            return Err("the simulation period or the model is not the last run's".to_string());
+        }
+        let coupled = [
+            (!self.constituents.is_empty(), "constituents"),
+            (!self.policies.is_empty(), "policies"),
+            (!self.allocation.is_empty(), "allocation"),
+            (!self.account_manager.is_empty(), "accounts"),
+        ];
+        if let Some((_, feature)) = coupled.iter().find(|(has, _)| *has) {
+            return Err(format!("the model has {}, which may carry a change to any node", feature));
+        }
+
+        // The series of each changed input, and the node each series is a result of
+        let mut changed_series = vec![false; self.data_cache.series.len()];
+        for &i in &self.run_cache.changed_inputs {
+            let input = &self.inputs[i];
+            let paths = [Some(&input.full_colname_path), Some(&input.full_colindex_path),
+                         input.alias_colname_path.as_ref(), input.alias_colindex_path.as_ref()];
+            for path in paths.into_iter().flatten() {
+                if let Some(idx) = self.data_cache.get_existing_series_idx(path) {
+                    changed_series[idx] = true;
+                }
+            }
+        }
+        let result_of: Vec<Option<usize>> = self.data_cache.series_name.iter()
+            .map(|name| name.to_lowercase().strip_prefix("node.")
+                .and_then(|path| path.split_once('.'))
+                .and_then(|(node_name, _)| self.node_lookup.get(node_name).copied()))
+            .collect();
+        let reads: Vec<Vec<usize>> = self.nodes.iter_mut()
+            .map(|node| node.dynamic_inputs_mut().into_iter()
+                .flat_map(|(_, input)| input.lookback_depths())
+                .map(|(idx, _)| idx)
+                .collect())
+            .collect();
+
+        let mut affected = vec![false; self.nodes.len()];
+        for &i in &self.run_cache.changed_nodes {
+            affected[i] = true;
+        }
+        for (i, series) in reads.iter().enumerate() {
+            affected[i] |= series.iter().any(|&idx| changed_series[idx]);
+        }
+        let ordered: Vec<usize> = self.simple_ordering_system.ordered_nodes().collect();
+        loop {
+            let mut grew = false;
+            for i in 0..self.nodes.len() {
+                if !affected[i] && (self.incoming_links[i].iter().any(|&l| affected[self.links[l].from_node])
+                    || reads[i].iter().any(|&idx| result_of[idx].is_some_and(|n| affected[n]))) {
+                    affected[i] = true;
+                    grew = true;
+                }
+            }
+            if ordered.iter().any(|&i| affected[i]) && ordered.iter().any(|&i| !affected[i]) {
+                for &i in &ordered {
+                    affected[i] = true;
+                }
+                grew = true;
+            }
+            if !grew {
+                break;
+            }
+        }
+
+        for f in &self.functions.functions {
+            let reads_change = f.expression.lookback_depths().iter()
+                .any(|&(idx, _)| changed_series[idx] || result_of[idx].is_some_and(|n| affected[n]));
+            if reads_change {
+                return Err(format!("function '{}' reads a result or input that changed", f.name));
+            }
+        }
+        Ok(Some(affected))
+    }
+}
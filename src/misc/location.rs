@@ -25,6 +25,14 @@ impl Location {
         }
     }
     
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
     pub fn to_string(&self) -> String {
         format!("{}, {}", self.x, self.y)
     }
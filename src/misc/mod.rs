@@ -4,4 +4,18 @@ pub mod cli_helpers;
 pub mod componenet_identification;
 pub mod misc_functions;
 pub mod link_helper;
-pub mod simulation_context;
\ No newline at end of file
+pub mod simulation_context;pub mod model_validation;
+pub mod batch_run;
+pub mod node_timing;
+pub mod graph_export;
+pub mod run_report;
+pub mod output_statistics;
+pub mod provenance;
+pub mod spin_up;
+pub mod model_diff;
+pub mod regionalisation;
+pub mod forecast;
+pub mod residual;
+pub mod stepping;
+pub mod incremental_run;
+pub mod flow_loop;
@@ -0,0 +1,277 @@
+//! Semantic differences between two models, for reviewing scenario changes.
+//!
+//! Models are compared node by node rather than line by line, so reordering sections,
+//! reformatting tables or editing comments makes no difference. Both models are rendered
+//! canonically first, and values are compared item by item, with numbers compared as numbers
+//! (`1.0` and `1` are the same).
+
+use std::collections::{BTreeSet, HashMap};
+use crate::io::custom_ini_parser::IniSection;
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::misc_functions::format_f64;
+use crate::model::Model;
+use crate::nodes::Node;
+
+/// Values longer than this are shortened in the text report
+const MAX_DISPLAY_LEN: usize = 40;
+
+/// Numeric values with more items than this report a summary of their changes
+const MAX_LISTED_DELTAS: usize = 6;
+
+/// A property added, removed or changed between two models
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyChange {
+    /// Section of the property, e.g. `kalix` or `node.dam`
+    pub section: String,
+    pub key: String,
+    /// Value in the first model, or None if the property was added
+    pub old_value: Option<String>,
+    /// Value in the second model, or None if the property was removed
+    pub new_value: Option<String>,
+}
+
+impl PropertyChange {
+    /// The change in each number of a numeric value (e.g. each of a node's `params`), if both
+    /// values are lists of the same count of numbers
+    pub fn numeric_deltas(&self) -> Option<Vec<f64>> {
+        let old = parse_numbers(self.old_value.as_deref()?)?;
+        let new = parse_numbers(self.new_value.as_deref()?)?;
+        if old.len() != new.len() {
+            return None;
+        }
+        Some(old.iter().zip(&new).map(|(o, n)| n - o).collect())
+    }
+}
+
+/// A node outlet linked to a different node (or to none) between two models
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkChange {
+    pub from_node: String,
+    /// Outlet numbered from 1, as in the `ds_N` keys
+    pub outlet: usize,
+    /// Downstream node in the first model, or None if the outlet was unlinked
+    pub old_to_node: Option<String>,
+    /// Downstream node in the second model, or None if the outlet is now unlinked
+    pub new_to_node: Option<String>,
+}
+
+/// The differences between two models. Properties and links of added or removed nodes are
+/// not listed separately.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModelDiff {
+    /// Nodes only in the second model, as `(name, type)`
+    pub added_nodes: Vec<(String, String)>,
+    /// Nodes only in the first model, as `(name, type)`
+    pub removed_nodes: Vec<(String, String)>,
+    /// Properties that differ, in nodes in both models and in the other sections
+    pub changed_properties: Vec<PropertyChange>,
+    /// Outlets of nodes in both models that link to a different node
+    pub rerouted_links: Vec<LinkChange>,
+}
+
+impl ModelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty() && self.removed_nodes.is_empty()
+            && self.changed_properties.is_empty() && self.rerouted_links.is_empty()
+    }
+
+    /// The differences as a report for the terminal
+    pub fn to_text(&self) -> String {
+        if self.is_empty() {
+            return "No differences.\n".to_string();
+        }
+        let mut text = String::new();
+        if !self.added_nodes.is_empty() {
+            text.push_str("Added nodes:\n");
+            for (name, node_type) in &self.added_nodes {
+                text.push_str(&format!("  + {} ({})\n", name, node_type));
+            }
+        }
+        if !self.removed_nodes.is_empty() {
+            text.push_str("Removed nodes:\n");
+            for (name, node_type) in &self.removed_nodes {
+                text.push_str(&format!("  - {} ({})\n", name, node_type));
+            }
+        }
+        if !self.changed_properties.is_empty() {
+            text.push_str("Changed properties:\n");
+            for change in &self.changed_properties {
+                let description = match (&change.old_value, &change.new_value) {
+                    (None, Some(new)) if new.is_empty() => "added".to_string(),
+                    (Some(old), None) if old.is_empty() => "removed".to_string(),
+                    (None, Some(new)) => format!("added as {}", shorten(new)),
+                    (Some(old), None) => format!("removed (was {})", shorten(old)),
+                    (Some(old), Some(new)) => match change.numeric_deltas() {
+                        Some(deltas) => format!("{} -> {} ({})", shorten(old), shorten(new), describe_deltas(&deltas)),
+                        None => format!("{} -> {}", shorten(old), shorten(new)),
+                    },
+                    (None, None) => continue,
+                };
+                text.push_str(&format!("  [{}] {}: {}\n", change.section, change.key, description));
+            }
+        }
+        if !self.rerouted_links.is_empty() {
+            text.push_str("Re-routed links:\n");
+            for link in &self.rerouted_links {
+                text.push_str(&format!("  {} ds_{}: {} -> {}\n", link.from_node, link.outlet,
+                                       link.old_to_node.as_deref().unwrap_or("none"),
+                                       link.new_to_node.as_deref().unwrap_or("none")));
+            }
+        }
+        text
+    }
+}
+
+/// The items of a value, split at commas and whitespace
+fn value_items(value: &str) -> Vec<&str> {
+    value.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()).collect()
+}
+
+fn parse_numbers(value: &str) -> Option<Vec<f64>> {
+    let items = value_items(value);
+    if items.is_empty() {
+        return None;
+    }
+    items.iter().map(|s| s.parse::<f64>().ok()).collect()
+}
+
+/// Whether two values hold the same items, comparing numbers by value
+fn values_equivalent(a: &str, b: &str) -> bool {
+    let (a, b) = (value_items(a), value_items(b));
+    a.len() == b.len() && a.iter().zip(&b).all(|(x, y)| {
+        x == y || matches!((x.parse::<f64>(), y.parse::<f64>()), (Ok(x), Ok(y)) if x == y)
+    })
+}
+
+fn shorten(value: &str) -> String {
+    match value.char_indices().nth(MAX_DISPLAY_LEN) {
+        Some((i, _)) => format!("{}...", &value[..i]),
+        None => value.to_string(),
+    }
+}
+
+/// A change with its sign, e.g. `+10` or `-0.5`
+fn format_delta(delta: f64) -> String {
+    match delta > 0.0 {
+        true => format!("+{}", format_f64(delta)),
+        false => format_f64(delta),
+    }
+}
+
+fn describe_deltas(deltas: &[f64]) -> String {
+    if deltas.len() <= MAX_LISTED_DELTAS {
+        let listed: Vec<String> = deltas.iter().map(|&d| format_delta(d)).collect();
+        return format!("change {}", listed.join(", "));
+    }
+    let n_changed = deltas.iter().filter(|&&d| d != 0.0).count();
+    let largest = deltas.iter().copied().fold(0.0, |a: f64, d| if d.abs() > a.abs() { d } else { a });
+    format!("{} of {} values changed, largest change {}", n_changed, deltas.len(), format_delta(largest))
+}
+
+/// Whether a key is a link (`ds_1`, `ds_2`, ..) rather than a property like `ds_1_outlet`
+fn is_link_key(key: &str) -> bool {
+    key.strip_prefix("ds_").is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Changes between two versions of a section. Links are left out.
+fn section_changes(section_name: &str, old: Option<&IniSection>, new: Option<&IniSection>) -> Vec<PropertyChange> {
+    let mut changes = vec![];
+    let change = |key: &str, old_value: Option<&str>, new_value: Option<&str>| PropertyChange {
+        section: section_name.to_string(),
+        key: key.to_string(),
+        old_value: old_value.map(str::to_string),
+        new_value: new_value.map(str::to_string),
+    };
+    if let Some(old) = old {
+        for (key, old_prop) in &old.properties {
+            if is_link_key(key) {
+                continue;
+            }
+            match new.and_then(|s| s.properties.get(key)) {
+                None => changes.push(change(key, Some(&old_prop.value), None)),
+                Some(new_prop) if !values_equivalent(&old_prop.value, &new_prop.value) => {
+                    changes.push(change(key, Some(&old_prop.value), Some(&new_prop.value)));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    if let Some(new) = new {
+        for (key, new_prop) in &new.properties {
+            if !is_link_key(key) && old.is_none_or(|s| !s.properties.contains_key(key)) {
+                changes.push(change(key, None, Some(&new_prop.value)));
+            }
+        }
+    }
+    changes
+}
+
+/// Downstream node of each linked outlet, keyed by `(node name, outlet from 1)`
+fn links_by_outlet(model: &Model) -> HashMap<(String, usize), String> {
+    model.links.iter()
+        .map(|link| ((model.nodes[link.from_node].get_name().to_string(), link.from_outlet as usize + 1),
+                     model.nodes[link.to_node].get_name().to_string()))
+        .collect()
+}
+
+impl Model {
+    /// The differences from this model to `other`: nodes added and removed (matched by name),
+    /// properties changed, and links re-routed
+    pub fn diff(&self, other: &Model) -> ModelDiff {
+        let io = IniModelIO::new();
+        let old_doc = io.model_to_canonical_ini_doc(self);
+        let new_doc = io.model_to_canonical_ini_doc(other);
+        let mut diff = ModelDiff::default();
+
+        // Nodes
+        let old_names: BTreeSet<&str> = self.nodes.iter().map(|n| n.get_name()).collect();
+        let new_names: BTreeSet<&str> = other.nodes.iter().map(|n| n.get_name()).collect();
+        diff.added_nodes = other.nodes.iter()
+            .filter(|n| !old_names.contains(n.get_name()))
+            .map(|n| (n.get_name().to_string(), n.get_type_as_string()))
+            .collect();
+        diff.removed_nodes = self.nodes.iter()
+            .filter(|n| !new_names.contains(n.get_name()))
+            .map(|n| (n.get_name().to_string(), n.get_type_as_string()))
+            .collect();
+
+        // Properties, of nodes in both models and of every other section
+        let is_added_or_removed_node = |section_name: &str| section_name.strip_prefix("node.")
+            .is_some_and(|name| !(old_names.contains(name) && new_names.contains(name)));
+        for (section_name, old_section) in &old_doc.sections {
+            if !is_added_or_removed_node(section_name) {
+                diff.changed_properties.extend(section_changes(section_name, Some(old_section),
+                                                               new_doc.sections.get(section_name)));
+            }
+        }
+        for (section_name, new_section) in &new_doc.sections {
+            if !is_added_or_removed_node(section_name) && !old_doc.sections.contains_key(section_name) {
+                diff.changed_properties.extend(section_changes(section_name, None, Some(new_section)));
+            }
+        }
+
+        // Links from nodes in both models
+        let old_links = links_by_outlet(self);
+        let new_links = links_by_outlet(other);
+        for node in self.nodes.iter().filter(|n| new_names.contains(n.get_name())) {
+            let name = node.get_name().to_string();
+            let outlets: BTreeSet<usize> = old_links.keys().chain(new_links.keys())
+                .filter(|(from, _)| *from == name)
+                .map(|(_, outlet)| *outlet)
+                .collect();
+            for outlet in outlets {
+                let key = (name.clone(), outlet);
+                let (old_to, new_to) = (old_links.get(&key), new_links.get(&key));
+                if old_to != new_to {
+                    diff.rerouted_links.push(LinkChange {
+                        from_node: name.clone(),
+                        outlet,
+                        old_to_node: old_to.cloned(),
+                        new_to_node: new_to.cloned(),
+                    });
+                }
+            }
+        }
+        diff
+    }
+}
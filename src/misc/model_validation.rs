@@ -0,0 +1,319 @@
+use crate::model::Model;
+use crate::nodes::{Node, NodeEnum};
+use crate::nodes::storage_node::{LEVL, VOLU};
+use crate::ordering::execution_order::topological_sort;
+use crate::tid::utils::u64_to_iso_datetime_string;
+use crate::timeseries_input::TimeseriesInput;
+
+/// How serious a validation finding is. Errors will stop the model from running (or make the
+/// results meaningless); warnings flag things that are legal but probably not intended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationSeverity {
+    Error,
+    Warning,
+}
+
+/// A single finding from [`Model::validate`].
+///
+/// `code` is a short, stable identifier (e.g. `"cycle"`, `"missing_input"`) that front ends can
+/// switch on. `section` and `line_number` point back into the attached INI document when the
+/// model was loaded from one, and are `None` otherwise.
+#[derive(Debug, Clone)]
+pub struct ValidationIssue {
+    pub severity: ValidationSeverity,
+    pub code: String,
+    pub message: String,
+    pub section: Option<String>,
+    pub line_number: Option<usize>,
+}
+
+/// The structured result of [`Model::validate`].
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn new() -> Self {
+        Self { ..Default::default() }
+    }
+
+    /// True if there are no errors (warnings are allowed).
+    pub fn is_valid(&self) -> bool {
+        !self.issues.iter().any(|i| i.severity == ValidationSeverity::Error)
+    }
+
+    pub fn errors(&self) -> Vec<&ValidationIssue> {
+        self.issues.iter().filter(|i| i.severity == ValidationSeverity::Error).collect()
+    }
+
+    pub fn warnings(&self) -> Vec<&ValidationIssue> {
+        self.issues.iter().filter(|i| i.severity == ValidationSeverity::Warning).collect()
+    }
+
+    fn push(&mut self, severity: ValidationSeverity, code: &str, message: String,
+            location: Option<(String, usize)>) {
+        let (section, line_number) = match location {
+            Some((s, l)) => (Some(s), Some(l)),
+            None => (None, None),
+        };
+        self.issues.push(ValidationIssue {
+            severity,
+            code: code.to_string(),
+            message,
+            section,
+            line_number,
+        });
+    }
+}
+
+
+impl Model {
+
+    /// Checks the model for structural and data problems without running it.
+    ///
+    /// Unlike `configure()` and `run()`, which stop at the first problem, this collects every
+    /// finding into a [`ValidationReport`]. It does not modify the model. The checks are:
+    ///   1) nodes with no links (disconnected), when the model has more than one node
+    ///   2) cycles in the link graph
+    ///   3) `data.*` references that do not match any loaded input series
+    ///   4) constants that are referenced but never assigned
+    ///   5) rainfall-runoff nodes with zero catchment area
+    ///   6) storage dimension tables whose levels or volumes are not strictly increasing
+    ///   7) inconsistent step sizes or date ranges between the inputs and the specified period
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::new();
+        self.validate_connectivity(&mut report);
+        self.validate_cycles(&mut report);
+        self.validate_input_references(&mut report);
+        self.validate_constants(&mut report);
+        self.validate_tables(&mut report);
+        self.validate_node_properties(&mut report);
+        self.validate_date_ranges(&mut report);
+        report
+    }
+
+    fn validate_connectivity(&self, report: &mut ValidationReport) {
+        if self.nodes.len() < 2 {
+            return;
+        }
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if self.incoming_links[idx].is_empty() && self.outgoing_links[idx].is_empty() {
+                let name = node.get_name();
+                report.push(ValidationSeverity::Warning, "disconnected_node",
+                            format!("Node '{}' is not connected to any other node.", name),
+                            self.ini_section_location(&format!("node.{}", name)));
+            }
+        }
+    }
+
+    fn validate_cycles(&self, report: &mut ValidationReport) {
+        if let Err(unordered) = topological_sort(self.nodes.len(), &self.links) {
+            let names: Vec<&str> = unordered.iter().map(|&i| self.nodes[i].get_name()).collect();
+            let location = names.first()
+                .and_then(|name| self.ini_section_location(&format!("node.{}", name)));
+            report.push(ValidationSeverity::Error, "cycle",
+                        format!("The link graph contains a cycle involving nodes: {}", names.join(", ")),
+                        location);
+        }
+    }
+
+    fn validate_input_references(&self, report: &mut ValidationReport) {
+        for (idx, name) in self.data_cache.series_name.iter().enumerate() {
+            if !name.to_lowercase().starts_with("data.") {
+                continue;
+            }
+            if self.find_input_for_reference(name).is_none() {
+                let kind = if self.data_cache.is_critical[idx] { "Critical input" } else { "Input" };
+                report.push(ValidationSeverity::Error, "missing_input",
+                            format!("{} '{}' was not found in any input file.", kind, name),
+                            self.ini_reference_location(name));
+            }
+        }
+    }
+
+    fn validate_constants(&self, report: &mut ValidationReport) {
+        for name in self.data_cache.constants.list_unassigned_names() {
+            report.push(ValidationSeverity::Error, "unassigned_constant",
+                        format!("Constant '{}' is referenced but has not been assigned a value.", name),
+                        self.ini_reference_location(&name));
+        }
+    }
+
+    fn validate_tables(&self, report: &mut ValidationReport) {
+        for name in self.data_cache.tables.list_unassigned_names() {
+            report.push(ValidationSeverity::Error, "undefined_table",
+                        format!("Table '{}' is referenced but has not been defined.", name),
+                        self.ini_reference_location(&name));
+        }
+    }
+
+    fn validate_node_properties(&self, report: &mut ValidationReport) {
+        for node in &self.nodes {
+            let section = format!("node.{}", node.get_name());
+            match node {
+                NodeEnum::SacramentoNode(n) if n.area_km2 == 0.0 => {
+                    report.push(ValidationSeverity::Warning, "zero_area",
+                                format!("Node '{}' has zero catchment area and will produce no runoff.", n.name),
+                                self.ini_property_location(&section, "area"));
+                }
+                NodeEnum::Gr4jNode(n) if n.area_km2 == 0.0 => {
+                    report.push(ValidationSeverity::Warning, "zero_area",
+                                format!("Node '{}' has zero catchment area and will produce no runoff.", n.name),
+                                self.ini_property_location(&section, "area"));
+                }
+                NodeEnum::StorageNode(n) => {
+                    for (col, label) in [(LEVL, "levels"), (VOLU, "volumes")] {
+                        for row in 1..n.dimensions.nrows() {
+                            if n.dimensions.get_value(row, col) <= n.dimensions.get_value(row - 1, col) {
+                                report.push(ValidationSeverity::Error, "non_monotonic_table",
+                                            format!("Node '{}' dimension table {} are not strictly increasing (violation at row {}).",
+                                                    n.name, label, row + 1),
+                                            self.ini_property_location(&section, "dimensions"));
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn validate_date_ranges(&self, report: &mut ValidationReport) {
+        let start = self.configuration.specified_sim_start_timestamp;
+        let end = self.configuration.specified_sim_end_timestamp;
+        if let (Some(s), Some(e)) = (start, end) {
+            if s > e {
+                report.push(ValidationSeverity::Error, "date_range",
+                            "Specified start date is after the specified end date.".to_string(),
+                            self.ini_property_location("kalix", "start"));
+            }
+        }
+
+        // Availability of every referenced input that comes from a file
+        let mut step_size: Option<(u64, String)> = None;
+        let mut overlap: Option<(u64, u64)> = None;
+        for (idx, name) in self.data_cache.series_name.iter().enumerate() {
+            let input = match self.find_input_for_reference(name) {
+                Some(input) if !input.source_path.is_empty() => input,
+                _ => continue,
+            };
+            let ts = &input.timeseries;
+
+            match &step_size {
+                None => step_size = Some((ts.step_size, name.clone())),
+                Some((first_step, first_name)) if *first_step != ts.step_size => {
+                    report.push(ValidationSeverity::Error, "step_size_mismatch",
+                                format!("Input '{}' has step size {} but '{}' has step size {}.",
+                                        name, ts.step_size, first_name, first_step),
+                                self.ini_reference_location(name));
+                }
+                _ => {}
+            }
+
+            let (first, last) = match available_range(ts.start_timestamp, ts.step_size, &ts.values) {
+                Some(range) => range,
+                None => {
+                    report.push(ValidationSeverity::Error, "date_range",
+                                format!("Input '{}' contains no data.", name),
+                                self.ini_reference_location(name));
+                    continue;
+                }
+            };
+
+            if self.data_cache.is_critical[idx] {
+                overlap = Some(match overlap {
+                    None => (first, last),
+                    Some((a, b)) => (a.max(first), b.min(last)),
+                });
+            } else if start.is_some_and(|s| s < first) || end.is_some_and(|e| e > last) {
+                report.push(ValidationSeverity::Warning, "date_range",
+                            format!("Input '{}' ({} to {}) does not cover the specified simulation period.",
+                                    name, u64_to_iso_datetime_string(first), u64_to_iso_datetime_string(last)),
+                            self.ini_reference_location(name));
+            }
+        }
+
+        if let Some((a, b)) = overlap {
+            if a > b {
+                report.push(ValidationSeverity::Error, "date_range",
+                            "The critical inputs do not share any common period of data.".to_string(),
+                            None);
+                return;
+            }
+            let period = format!("{} to {}", u64_to_iso_datetime_string(a), u64_to_iso_datetime_string(b));
+            if let Some(s) = start {
+                if s < a || s > b {
+                    report.push(ValidationSeverity::Error, "date_range",
+                                format!("Specified start {} is outside the available input data ({}).",
+                                        u64_to_iso_datetime_string(s), period),
+                                self.ini_property_location("kalix", "start"));
+                }
+            }
+            if let Some(e) = end {
+                if e < a || e > b {
+                    report.push(ValidationSeverity::Error, "date_range",
+                                format!("Specified end {} is outside the available input data ({}).",
+                                        u64_to_iso_datetime_string(e), period),
+                                self.ini_property_location("kalix", "end"));
+                }
+            }
+        }
+    }
+
+    /// Finds the loaded input that a `data.*` reference resolves to (case-insensitive).
+    fn find_input_for_reference(&self, name: &str) -> Option<&TimeseriesInput> {
+        let name_lower = name.to_lowercase();
+        self.inputs.iter().find(|ts| {
+            name_lower == ts.full_colindex_path
+                || name_lower == ts.full_colname_path
+                || ts.alias_colindex_path.as_ref().is_some_and(|p| name_lower == *p)
+                || ts.alias_colname_path.as_ref().is_some_and(|p| name_lower == *p)
+        })
+    }
+
+    /// Line of a `[section]` header in the attached INI document.
+    fn ini_section_location(&self, section: &str) -> Option<(String, usize)> {
+        let doc = self.ini_document.as_ref()?;
+        doc.sections.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(section))
+            .map(|(name, s)| (name.clone(), s.line_number))
+    }
+
+    /// Line of a property within a section, falling back to the section header.
+    fn ini_property_location(&self, section: &str, key: &str) -> Option<(String, usize)> {
+        let doc = self.ini_document.as_ref()?;
+        let (name, s) = doc.sections.iter().find(|(name, _)| name.eq_ignore_ascii_case(section))?;
+        let line = s.properties.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, p)| p.line_number)
+            .unwrap_or(s.line_number);
+        Some((name.clone(), line))
+    }
+
+    /// Line of the first node property whose value mentions the given reference.
+    fn ini_reference_location(&self, reference: &str) -> Option<(String, usize)> {
+        let doc = self.ini_document.as_ref()?;
+        let needle = reference.to_lowercase();
+        for (name, s) in doc.sections.iter() {
+            if !name.to_lowercase().starts_with("node.") {
+                continue;
+            }
+            for p in s.properties.values() {
+                if p.value.to_lowercase().contains(&needle) {
+                    return Some((name.clone(), p.line_number));
+                }
+            }
+        }
+        None
+    }
+}
+
+
+/// Returns the timestamps of the first and last non-NaN values, or None if there are none.
+fn available_range(start_timestamp: u64, step_size: u64, values: &[f64]) -> Option<(u64, u64)> {
+    let first = values.iter().position(|v| !v.is_nan())?;
+    let last = values.iter().rposition(|v| !v.is_nan())?;
+    Some((start_timestamp + first as u64 * step_size, start_timestamp + last as u64 * step_size))
+}
@@ -0,0 +1,111 @@
+//! Per-node execution timing, for finding the nodes that make a large model slow
+//!
+//! When a [`NodeTimer`] is attached to a model (`Model::node_timer`), the run loop adds the
+//! time each node spends in its flow phase. The timer is shared through an `Arc` and uses
+//! atomics, so a progress callback can report a breakdown while the simulation is running.
+
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Cumulative flow-phase time for each node, and the number of times it ran, indexed like
+/// `Model::nodes`
+#[derive(Debug, Default)]
+pub struct NodeTimer {
+    nanos: Vec<AtomicU64>,
+    calls: Vec<AtomicU64>,
+}
+
+/// Cumulative times at one point in a run
+#[derive(Debug, Clone)]
+pub struct NodeTimingReport {
+    /// Time over all nodes
+    pub total: Duration,
+    /// Time per node type, slowest first
+    pub by_type: Vec<(String, Duration)>,
+    /// The slowest individual nodes, slowest first
+    pub slowest: Vec<(String, Duration)>,
+}
+
+impl NodeTimer {
+    pub fn new(n_nodes: usize) -> Self {
+        Self {
+            nanos: (0..n_nodes).map(|_| AtomicU64::new(0)).collect(),
+            calls: (0..n_nodes).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nanos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nanos.is_empty()
+    }
+
+    pub fn add(&self, node_idx: usize, elapsed: Duration) {
+        if let (Some(n), Some(c)) = (self.nanos.get(node_idx), self.calls.get(node_idx)) {
+            n.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+            c.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// How many times the node's flow phase was timed
+    pub fn calls(&self, node_idx: usize) -> u64 {
+        self.calls.get(node_idx).map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    pub fn get(&self, node_idx: usize) -> Duration {
+        Duration::from_nanos(self.nanos.get(node_idx).map_or(0, |n| n.load(Ordering::Relaxed)))
+    }
+
+    /// Summarises the times so far. `names` and `types` are indexed like the nodes.
+    pub fn report(&self, names: &[String], types: &[String], n_slowest: usize) -> NodeTimingReport {
+        let times: Vec<Duration> = (0..self.nanos.len()).map(|i| self.get(i)).collect();
+
+        let mut by_type: Vec<(String, Duration)> = Vec::new();
+        for (node_type, &time) in types.iter().zip(&times) {
+            match by_type.iter_mut().find(|(t, _)| t == node_type) {
+                Some((_, total)) => *total += time,
+                None => by_type.push((node_type.clone(), time)),
+            }
+        }
+        by_type.sort_by_key(|(_, time)| Reverse(*time));
+
+        let mut slowest: Vec<(String, Duration)> = names.iter().cloned().zip(times.iter().copied()).collect();
+        slowest.sort_by_key(|(_, time)| Reverse(*time));
+        slowest.truncate(n_slowest);
+
+        NodeTimingReport { total: times.iter().sum(), by_type, slowest }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_groups_by_type_and_ranks_nodes() {
+        let timer = NodeTimer::new(3);
+        timer.add(0, Duration::from_micros(10));
+        timer.add(1, Duration::from_micros(30));
+        timer.add(2, Duration::from_micros(5));
+        timer.add(2, Duration::from_micros(5));
+        timer.add(7, Duration::from_micros(99)); // Out of range is ignored
+        assert_eq!((timer.calls(2), timer.calls(7)), (2, 0));
+
+        let names = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let types = vec!["gr4j".to_string(), "storage".to_string(), "gr4j".to_string()];
+        let report = timer.report(&names, &types, 2);
+
+        assert_eq!(report.total, Duration::from_micros(50));
+        assert_eq!(report.by_type, vec![
+            ("storage".to_string(), Duration::from_micros(30)),
+            ("gr4j".to_string(), Duration::from_micros(20)),
+        ]);
+        assert_eq!(report.slowest, vec![
+            ("b".to_string(), Duration::from_micros(30)),
+            ("a".to_string(), Duration::from_micros(10)),
+        ]);
+    }
+}
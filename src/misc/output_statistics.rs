@@ -0,0 +1,79 @@
+//! Summary statistics of a run's output series, printed after `kalix run` as a quick check
+//! that the results look sensible before opening them.
+
+use crate::misc::batch_run::SeriesSummary;
+use crate::model::Model;
+use crate::timeseries::Timeseries;
+
+const SECONDS_PER_YEAR: f64 = 365.25 * 86400.0;
+
+/// Statistics of one output series. All but `nan_count` ignore missing values.
+#[derive(Clone, Debug, Default)]
+pub struct OutputStatistics {
+    pub name: String,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Mean total per year, i.e. the mean multiplied by the number of steps in a year
+    pub annual_mean: f64,
+    /// Percentage of steps with a value of exactly zero, e.g. cease-to-flow days
+    pub zero_percent: f64,
+    pub nan_count: usize,
+}
+
+impl OutputStatistics {
+    pub fn from_series(ts: &Timeseries) -> Self {
+        let summary = SeriesSummary::from_values(&ts.values);
+        let n_zero = ts.values.iter().filter(|&&v| v == 0.0).count();
+        let steps_per_year = if ts.step_size > 0 { SECONDS_PER_YEAR / ts.step_size as f64 } else { f64::NAN };
+        OutputStatistics {
+            name: ts.name.clone(),
+            mean: summary.mean,
+            min: summary.min,
+            max: summary.max,
+            annual_mean: summary.mean * steps_per_year,
+            zero_percent: if summary.count > 0 { 100.0 * n_zero as f64 / summary.count as f64 } else { f64::NAN },
+            nan_count: ts.values.len() - summary.count,
+        }
+    }
+}
+
+const HEADINGS: [&str; 7] = ["series", "mean", "min", "max", "annual_mean", "zero_%", "nan_count"];
+
+/// The statistics as a text table, one row per series, for printing to the terminal
+pub fn statistics_table(stats: &[OutputStatistics]) -> String {
+    let width = stats.iter().map(|s| s.name.len()).max().unwrap_or(0).max(HEADINGS[0].len());
+    let mut table = format!("{:<width$} {:>14} {:>14} {:>14} {:>14} {:>8} {:>9}\n",
+                            HEADINGS[0], HEADINGS[1], HEADINGS[2], HEADINGS[3], HEADINGS[4], HEADINGS[5], HEADINGS[6]);
+    for s in stats {
+        table.push_str(&format!("{:<width$} {:>14.6} {:>14.6} {:>14.6} {:>14.6} {:>8.2} {:>9}\n",
+                                s.name, s.mean, s.min, s.max, s.annual_mean, s.zero_percent, s.nan_count));
+    }
+    table
+}
+
+/// The statistics as CSV text, one row per series. Undefined statistics are left blank.
+pub fn statistics_csv(stats: &[OutputStatistics]) -> String {
+    let number = |v: f64| if v.is_nan() { String::new() } else { v.to_string() };
+    let mut csv = HEADINGS.join(",") + "\n";
+    for s in stats {
+        let name = if s.name.contains(',') || s.name.contains('"') {
+            format!("\"{}\"", s.name.replace('"', "\"\""))
+        } else {
+            s.name.clone()
+        };
+        csv.push_str(&format!("{},{},{},{},{},{},{}\n", name, number(s.mean), number(s.min), number(s.max),
+                              number(s.annual_mean), number(s.zero_percent), s.nan_count));
+    }
+    csv
+}
+
+impl Model {
+    /// Statistics of each of the model's `[outputs]` after a run, in the order they are declared.
+    /// Outputs that were not recorded are left out, as for [`Model::write_outputs`].
+    pub fn output_statistics(&self) -> Vec<OutputStatistics> {
+        self.collect_output_series().iter()
+            .map(|ts| OutputStatistics::from_series(ts))
+            .collect()
+    }
+}
@@ -0,0 +1,161 @@
+//! Provenance of a run: what was run, on what data, with what, and when, so results can be
+//! traced back to their exact inputs.
+//!
+//! The fingerprint is a SHA-256 hash of everything that determines the results: the Kalix
+//! version, the model as run (including overlays and parameter changes), the contents of its
+//! input and table files, and any random seeds. Two runs with the same fingerprint give the
+//! same results. The run time and command line are recorded but not part of the fingerprint.
+
+use sha2::{Digest, Sha256};
+use crate::io::csv_io::csv_string_to_f64_vec;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+
+/// A file read by a run and the SHA-256 hash of its contents
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileHash {
+    /// The path as given in the model or on the command line
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Provenance {
+    pub kalix_version: String,
+    /// The model file, if the model was loaded from one. Its hash is of the file as written,
+    /// before any overlays or command line overrides.
+    pub model_file: Option<FileHash>,
+    /// Hash of the model as run, in the canonical INI format
+    pub model_sha256: String,
+    /// The `[inputs]` files, then any table files
+    pub input_files: Vec<FileHash>,
+    /// Named random seeds, e.g. an optimiser's
+    pub seeds: Vec<(String, u64)>,
+    /// When the provenance was taken, in RFC 3339 format (UTC)
+    pub run_timestamp: String,
+    pub command_line: Option<String>,
+}
+
+/// The SHA-256 hash of some bytes, as lowercase hex
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hash_file(path: &str, resolved: &std::path::Path) -> Result<FileHash, String> {
+    let bytes = std::fs::read(resolved).map_err(|e| format!("Could not read file '{}': {}", path, e))?;
+    Ok(FileHash { path: path.to_string(), sha256: sha256_hex(&bytes) })
+}
+
+impl Provenance {
+    /// Provenance of `model` as it stands, with no model file, seeds or command line
+    pub fn new(model: &Model) -> Result<Provenance, String> {
+        let mut input_files = vec![];
+        for path in &model.input_file_paths {
+            input_files.push(hash_file(path, &model.resolve_path(path)?)?);
+        }
+        for (_, definition) in model.data_cache.tables.get_name_definition_pairs() {
+            if csv_string_to_f64_vec(&definition).is_err() {
+                input_files.push(hash_file(&definition, &model.resolve_path(&definition)?)?);
+            }
+        }
+        Ok(Provenance {
+            kalix_version: env!("CARGO_PKG_VERSION").to_string(),
+            model_sha256: sha256_hex(IniModelIO::new().model_to_string(model).as_bytes()),
+            input_files,
+            run_timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            ..Default::default()
+        })
+    }
+
+    /// Records the file the model was loaded from
+    pub fn with_model_file(mut self, path: &str) -> Result<Provenance, String> {
+        self.model_file = Some(hash_file(path, std::path::Path::new(path))?);
+        Ok(self)
+    }
+
+    pub fn with_seed(mut self, name: &str, seed: u64) -> Provenance {
+        self.seeds.push((name.to_string(), seed));
+        self
+    }
+
+    pub fn with_command_line(mut self, command_line: &str) -> Provenance {
+        self.command_line = Some(command_line.to_string());
+        self
+    }
+
+    /// The run fingerprint: a SHA-256 hash of the version, model, input files and seeds
+    pub fn fingerprint(&self) -> String {
+        let mut text = format!("kalix {}\nmodel {}\n", self.kalix_version, self.model_sha256);
+        for file in &self.input_files {
+            text.push_str(&format!("input {}\n", file.sha256));
+        }
+        for (name, seed) in &self.seeds {
+            text.push_str(&format!("seed {} {}\n", name, seed));
+        }
+        sha256_hex(text.as_bytes())
+    }
+
+    /// `(key, value)` pairs, one per line of a provenance header, starting with the fingerprint
+    pub fn to_pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = vec![
+            ("fingerprint".to_string(), self.fingerprint()),
+            ("kalix_version".to_string(), self.kalix_version.clone()),
+            ("run_timestamp".to_string(), self.run_timestamp.clone()),
+        ];
+        if let Some(command_line) = &self.command_line {
+            pairs.push(("command_line".to_string(), command_line.clone()));
+        }
+        if let Some(file) = &self.model_file {
+            pairs.push(("model_file".to_string(), format!("{} sha256:{}", file.path, file.sha256)));
+        }
+        pairs.push(("model_sha256".to_string(), self.model_sha256.clone()));
+        for file in &self.input_files {
+            pairs.push(("input_file".to_string(), format!("{} sha256:{}", file.path, file.sha256)));
+        }
+        for (name, seed) in &self.seeds {
+            pairs.push(("seed".to_string(), format!("{} {}", name, seed)));
+        }
+        pairs
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let file = |f: &FileHash| serde_json::json!({ "path": f.path, "sha256": f.sha256 });
+        serde_json::json!({
+            "fingerprint": self.fingerprint(),
+            "kalix_version": self.kalix_version,
+            "run_timestamp": self.run_timestamp,
+            "command_line": self.command_line,
+            "model_file": self.model_file.as_ref().map(file),
+            "model_sha256": self.model_sha256,
+            "input_files": self.input_files.iter().map(file).collect::<Vec<_>>(),
+            "seeds": self.seeds.iter().map(|(name, seed)| serde_json::json!({ "name": name, "seed": seed })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Model {
+    /// The fingerprint of a run of the model as it stands. See [`Provenance::fingerprint`].
+    pub fn fingerprint(&self) -> Result<String, String> {
+        Ok(Provenance::new(self)?.fingerprint())
+    }
+
+    /// Writes the outputs as for [`Model::write_outputs`], with the provenance of the run. CSV
+    /// files start with a `# key: value` line for each provenance item; for Pixie files it is
+    /// written alongside, as `<output name without extension>.provenance.json`.
+    pub fn write_outputs_with_provenance(&self, filename: &str, provenance: &Provenance) -> Result<(), String> {
+        // Formats without comment lines get the provenance in a file alongside
+        let lower = filename.to_ascii_lowercase();
+        if lower.ends_with(".pxb") || lower.ends_with(".pxt") || crate::io::source_res_io::is_res_csv(filename) {
+            self.write_outputs(filename)?;
+            let path = std::path::Path::new(filename).with_extension("provenance.json");
+            std::fs::write(&path, serde_json::to_string_pretty(&provenance.to_json()).unwrap())
+                .map_err(|e| format!("Could not write file {}: {}", path.display(), e))
+        } else {
+            let comments: Vec<String> = provenance.to_pairs().iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect();
+            let options = crate::io::csv_io::CsvWriteOptions { comments, ..crate::io::csv_io::CsvWriteOptions::for_path(filename) };
+            self.write_outputs_csv(filename, &options).map(|_| ())
+        }
+    }
+}
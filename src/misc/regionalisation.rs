@@ -0,0 +1,271 @@
+//! Regionalisation: transferring calibrated rainfall-runoff parameters to ungauged catchments.
+//!
+//! Two tables are given, each a CSV file with a header row and one row per catchment, named in
+//! the first column by its node name:
+//!
+//! - attributes, e.g. `catchment,area,mean_rain,forest` for gauged and ungauged catchments
+//! - donors, the calibrated parameters of the gauged catchments, e.g. `catchment,x1,x2,x3,x4`
+//!
+//! Every Gr4j or Sacramento node of the model that is not a donor, and that has every
+//! parameter of the donor table, is ungauged and is given parameters by one of:
+//!
+//! - nearest neighbour: the mean parameters of the `k` donors nearest in attribute space
+//!   (attributes standardised by the donors' mean and standard deviation)
+//! - regression: a least-squares linear fit of each parameter on the standardised attributes,
+//!   with predictions kept within the range of the donors' values
+
+use crate::model::Model;
+use crate::nodes::{Node, NodeEnum};
+
+/// How parameters are transferred from donors to ungauged catchments
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferMethod {
+    NearestNeighbour,
+    Regression,
+}
+
+impl TransferMethod {
+    pub const ALL: [TransferMethod; 2] = [TransferMethod::NearestNeighbour, TransferMethod::Regression];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferMethod::NearestNeighbour => "nearest",
+            TransferMethod::Regression => "regression",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<TransferMethod, String> {
+        let lower = name.trim().to_lowercase();
+        TransferMethod::ALL.into_iter()
+            .find(|m| m.as_str() == lower)
+            .ok_or_else(|| format!("Unknown transfer method '{}'. Expected one of: {}", name,
+                                   TransferMethod::ALL.map(|m| m.as_str()).join(", ")))
+    }
+}
+
+/// A table of values by catchment, read from CSV
+#[derive(Clone, Debug, Default)]
+pub struct CatchmentTable {
+    /// Column names, after the catchment column
+    pub columns: Vec<String>,
+    /// Catchment (node) names and their values, one per column
+    pub rows: Vec<(String, Vec<f64>)>,
+}
+
+impl CatchmentTable {
+    /// Reads a table from CSV text: a header row, then a catchment name and one number per
+    /// column on each row
+    pub fn parse(text: &str) -> Result<CatchmentTable, String> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let (_, header) = lines.next().ok_or("The table is empty")?;
+        let columns: Vec<String> = header.split(',').skip(1).map(|c| c.trim().to_string()).collect();
+        if columns.is_empty() {
+            return Err("Error on line 1: Expected a catchment column and at least one value column".to_string());
+        }
+
+        let mut table = CatchmentTable { columns, rows: vec![] };
+        for (i, line) in lines {
+            let mut fields = line.split(',');
+            let name = fields.next().unwrap_or_default().trim().to_string();
+            let values = fields
+                .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+                    .ok_or(format!("Error on line {}: Invalid value '{}' for catchment '{}'", i + 1, v.trim(), name)))
+                .collect::<Result<Vec<f64>, String>>()?;
+            if values.len() != table.columns.len() {
+                return Err(format!("Error on line {}: Expected {} values but found {}", i + 1, table.columns.len(), values.len()));
+            }
+            if table.row(&name).is_some() {
+                return Err(format!("Error on line {}: Catchment '{}' is listed twice", i + 1, name));
+            }
+            table.rows.push((name, values));
+        }
+        Ok(table)
+    }
+
+    /// Reads a table from a CSV file
+    pub fn read(path: &str) -> Result<CatchmentTable, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
+        CatchmentTable::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// The values of a catchment (case-insensitive)
+    pub fn row(&self, name: &str) -> Option<&[f64]> {
+        self.rows.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, values)| values.as_slice())
+    }
+}
+
+/// The parameters given to one ungauged node
+#[derive(Clone, Debug)]
+pub struct ParameterTransfer {
+    pub node: String,
+    /// The donors the parameters came from, nearest first (empty for regression)
+    pub donors: Vec<String>,
+    /// (parameter name, value) pairs, in donor table order
+    pub parameters: Vec<(String, f64)>,
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting, or None if `a` is singular
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col].clone();
+            for (x, p) in a[row].iter_mut().zip(&pivot_row).skip(col) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Standardises attributes by the donors' mean and standard deviation. Attributes that are the
+/// same for every donor cannot tell them apart, and are left out.
+struct Standardisation {
+    columns: Vec<(usize, f64, f64)>, // (column, mean, standard deviation)
+}
+
+impl Standardisation {
+    fn new(donor_attributes: &[&[f64]], n_columns: usize) -> Self {
+        let n = donor_attributes.len() as f64;
+        let columns = (0..n_columns)
+            .filter_map(|c| {
+                let mean = donor_attributes.iter().map(|a| a[c]).sum::<f64>() / n;
+                let sd = (donor_attributes.iter().map(|a| (a[c] - mean).powi(2)).sum::<f64>() / n).sqrt();
+                (sd > 0.0).then_some((c, mean, sd))
+            })
+            .collect();
+        Standardisation { columns }
+    }
+
+    fn apply(&self, attributes: &[f64]) -> Vec<f64> {
+        self.columns.iter().map(|&(c, mean, sd)| (attributes[c] - mean) / sd).collect()
+    }
+}
+
+impl Model {
+    /// Gives each ungauged Gr4j or Sacramento node parameters transferred from the donors by
+    /// `method` (see the [module docs](self)), and writes them into the model and its INI
+    /// document. `neighbours` is the number of donors averaged by the nearest-neighbour method.
+    pub fn regionalise(&mut self, attributes: &CatchmentTable, donors: &CatchmentTable,
+                       method: TransferMethod, neighbours: usize) -> Result<Vec<ParameterTransfer>, String> {
+
+        // Donor attributes and parameters
+        let mut donor_names = vec![];
+        let mut donor_attributes = vec![];
+        for (name, _) in &donors.rows {
+            let row = attributes.row(name)
+                .ok_or(format!("Donor '{}' has no row in the attribute table", name))?;
+            donor_names.push(name.clone());
+            donor_attributes.push(row);
+        }
+        if donor_names.is_empty() {
+            return Err("The donor table has no catchments".to_string());
+        }
+
+        // Ungauged nodes: rainfall-runoff nodes that are not donors and have every donor parameter
+        let targets: Vec<String> = self.nodes.iter()
+            .filter(|n| matches!(n, NodeEnum::Gr4jNode(_) | NodeEnum::SacramentoNode(_)))
+            .map(|n| n.get_name().to_string())
+            .filter(|name| donors.row(name).is_none())
+            .filter(|name| {
+                let params = self.list_node_parameters(name).unwrap_or_default();
+                donors.columns.iter().all(|c| params.iter().any(|p| p.eq_ignore_ascii_case(c)))
+            })
+            .collect();
+        if targets.is_empty() {
+            return Err(format!("No ungauged node has the parameters {}", donors.columns.join(", ")));
+        }
+
+        let standardisation = Standardisation::new(&donor_attributes, attributes.columns.len());
+        let donor_z: Vec<Vec<f64>> = donor_attributes.iter().map(|a| standardisation.apply(a)).collect();
+
+        // Regression coefficients for each parameter, over an intercept and each attribute
+        let coefficients: Vec<Vec<f64>> = match method {
+            TransferMethod::NearestNeighbour => {
+                if neighbours == 0 || neighbours > donor_names.len() {
+                    return Err(format!("The number of neighbours must be between 1 and the number of donors ({})", donor_names.len()));
+                }
+                vec![]
+            }
+            TransferMethod::Regression => {
+                let n_coefficients = standardisation.columns.len() + 1;
+                if donor_names.len() < n_coefficients {
+                    return Err(format!("Regression on {} attributes needs at least {} donors, but there are {}",
+                                       n_coefficients - 1, n_coefficients, donor_names.len()));
+                }
+                let design: Vec<Vec<f64>> = donor_z.iter()
+                    .map(|z| std::iter::once(1.0).chain(z.iter().copied()).collect())
+                    .collect();
+                let xtx: Vec<Vec<f64>> = (0..n_coefficients)
+                    .map(|i| (0..n_coefficients).map(|j| design.iter().map(|row| row[i] * row[j]).sum()).collect())
+                    .collect();
+                (0..donors.columns.len())
+                    .map(|p| {
+                        let xty = (0..n_coefficients)
+                            .map(|i| design.iter().zip(&donors.rows).map(|(row, (_, values))| row[i] * values[p]).sum())
+                            .collect();
+                        solve(xtx.clone(), xty)
+                            .ok_or("The attributes of the donors are collinear, so the regression has no unique fit".to_string())
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let mut transfers = vec![];
+        for node in &targets {
+            let z = standardisation.apply(attributes.row(node)
+                .ok_or(format!("Ungauged node '{}' has no row in the attribute table", node))?);
+            let (donors_used, values): (Vec<String>, Vec<f64>) = match method {
+                TransferMethod::NearestNeighbour => {
+                    let mut by_distance: Vec<(f64, usize)> = donor_z.iter().enumerate()
+                        .map(|(i, d)| (d.iter().zip(&z).map(|(a, b)| (a - b).powi(2)).sum::<f64>(), i))
+                        .collect();
+                    by_distance.sort_by(|a, b| a.0.total_cmp(&b.0));
+                    let nearest: Vec<usize> = by_distance.iter().take(neighbours).map(|&(_, i)| i).collect();
+                    let values = (0..donors.columns.len())
+                        .map(|p| nearest.iter().map(|&i| donors.rows[i].1[p]).sum::<f64>() / nearest.len() as f64)
+                        .collect();
+                    (nearest.iter().map(|&i| donor_names[i].clone()).collect(), values)
+                }
+                TransferMethod::Regression => {
+                    let values = coefficients.iter().enumerate()
+                        .map(|(p, b)| {
+                            let predicted = b[0] + b[1..].iter().zip(&z).map(|(b, z)| b * z).sum::<f64>();
+                            let (min, max) = donors.rows.iter().map(|(_, v)| v[p])
+                                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+                            predicted.clamp(min, max)
+                        })
+                        .collect();
+                    (vec![], values)
+                }
+            };
+            transfers.push(ParameterTransfer {
+                node: node.clone(),
+                donors: donors_used,
+                parameters: donors.columns.iter().cloned().zip(values).collect(),
+            });
+        }
+
+        let assignments: Vec<(String, f64)> = transfers.iter()
+            .flat_map(|t| t.parameters.iter().map(|(p, v)| (format!("node.{}.{}", t.node, p), *v)))
+            .collect();
+        self.apply_calibration(&assignments)?;
+        Ok(transfers)
+    }
+}
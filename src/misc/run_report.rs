@@ -0,0 +1,346 @@
+//! Standalone HTML report of a model run
+//!
+//! The report has a summary of the model, its mass balance, statistics of the output series,
+//! statistics of each gauge's simulated flow (`dsflow`) against its observed `reference_flow`
+//! (NSE, KGE and PBIAS) with hydrograph and flow duration curve figures, and the model's
+//! parameters. It is one HTML file with inline styles and SVG figures, so it can be shared or
+//! archived as is.
+//!
+//! Gauge results are only kept if they are recorded, so call `Model::record_gauge_series`
+//! before configuring and running a model that will be reported on.
+
+use crate::misc::batch_run::SeriesSummary;
+use crate::model::Model;
+use crate::nodes::{Node, NodeEnum};
+use crate::numerical::opt::objectives::{KgeObjective, NseObjective, ObjectiveFunction};
+use crate::numerical::series_comparison::downsample_mean;
+use crate::terminal_plot::Line;
+use crate::tid::utils::u64_to_date_string_for_step_size;
+use crate::timeseries::Timeseries;
+
+/// Most points drawn for each series in a figure. Longer series are averaged down to this.
+const MAX_FIGURE_POINTS: usize = 1000;
+
+/// How well a gauge's simulated flow matches its observed reference flow, over the timesteps
+/// where both have values
+#[derive(Clone, Debug, PartialEq)]
+pub struct GaugeStatistics {
+    pub gauge: String,
+    pub n_compared: usize,
+    /// Nash-Sutcliffe efficiency. NaN if the observed flow is constant.
+    pub nse: f64,
+    /// Kling-Gupta efficiency. NaN if the observed flow is constant.
+    pub kge: f64,
+    /// Percent bias, `100 * sum(sim - obs) / sum(obs)`. Positive if the model is too wet.
+    pub pbias: f64,
+}
+
+impl Model {
+    /// Records each gauge's `dsflow` and `reference_flow` in full, for
+    /// [`Model::gauge_statistics`] and the HTML report. Call before the model is configured.
+    pub fn record_gauge_series(&mut self) {
+        let mut names = Vec::new();
+        for node in &self.nodes {
+            if let NodeEnum::GaugeNode(gauge) = node {
+                names.push(format!("node.{}.dsflow", gauge.name));
+                names.push(format!("node.{}.reference_flow", gauge.name));
+            }
+        }
+        for name in &names {
+            self.data_cache.get_or_add_new_series(&name.to_lowercase(), false);
+        }
+        self.record_series_in_full(&names);
+    }
+
+    /// The simulated and observed flow of each gauge with recorded results and at least one
+    /// observed value
+    fn gauge_series(&self) -> Vec<(String, Timeseries, Timeseries)> {
+        let series = |name: String| {
+            self.data_cache.get_existing_series_idx(&name)
+                .map(|idx| self.data_cache.get_series(idx).into_owned())
+                .filter(|ts| !ts.values.is_empty())
+        };
+        self.nodes.iter()
+            .filter(|node| matches!(node, NodeEnum::GaugeNode(_)))
+            .filter_map(|node| {
+                let name = node.get_name();
+                let simulated = series(format!("node.{}.dsflow", name))?;
+                let observed = series(format!("node.{}.reference_flow", name))?;
+                observed.values.iter().any(|v| v.is_finite())
+                    .then(|| (name.to_string(), simulated, observed))
+            })
+            .collect()
+    }
+
+    /// Statistics of each gauge with recorded results and observed flows, in model order
+    pub fn gauge_statistics(&self) -> Vec<GaugeStatistics> {
+        self.gauge_series().into_iter()
+            .map(|(gauge, simulated, observed)| {
+                let (obs, sim): (Vec<f64>, Vec<f64>) = observed.values.iter().zip(&simulated.values)
+                    .filter(|(o, s)| o.is_finite() && s.is_finite())
+                    .unzip();
+                let efficiency = |objective: ObjectiveFunction| {
+                    objective.calculate(&obs, &sim).map_or(f64::NAN, |loss| 1.0 - loss)
+                };
+                let sum_obs: f64 = obs.iter().sum();
+                GaugeStatistics {
+                    gauge,
+                    n_compared: obs.len(),
+                    nse: efficiency(ObjectiveFunction::OneMinusNse(NseObjective::new())),
+                    kge: efficiency(ObjectiveFunction::OneMinusKge(KgeObjective::new())),
+                    pbias: if sum_obs != 0.0 { 100.0 * (sim.iter().sum::<f64>() - sum_obs) / sum_obs } else { f64::NAN },
+                }
+            })
+            .collect()
+    }
+
+    /// The report of the model's last run as a standalone HTML page. The parameter table lists
+    /// `parameters` if given (e.g. the result of a calibration), otherwise the model's
+    /// constants and node parameters.
+    pub fn html_report(&self, title: &str, parameters: Option<&[(String, f64)]>) -> String {
+        let config = &self.configuration;
+        let step = config.sim_stepsize;
+        let mut html = String::new();
+        html.push_str(&format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n", escape(title)));
+        html.push_str(STYLE);
+        html.push_str(&format!("</head>\n<body>\n<h1>{}</h1>\n", escape(title)));
+
+        // Model summary
+        let mut node_types: Vec<(String, usize)> = Vec::new();
+        for node in &self.nodes {
+            let node_type = node.get_type_as_string();
+            match node_types.iter_mut().find(|(t, _)| *t == node_type) {
+                Some((_, count)) => *count += 1,
+                None => node_types.push((node_type, 1)),
+            }
+        }
+        let node_summary = node_types.iter().map(|(t, n)| format!("{} {}", n, t)).collect::<Vec<_>>().join(", ");
+        html.push_str("<h2>Model</h2>\n");
+        html.push_str(&table(&["", ""], &[
+            vec!["Start".to_string(), u64_to_date_string_for_step_size(config.sim_start_timestamp, step)],
+            vec!["End".to_string(), u64_to_date_string_for_step_size(config.sim_end_timestamp, step)],
+            vec!["Timestep (s)".to_string(), step.to_string()],
+            vec!["Timesteps".to_string(), config.sim_nsteps.to_string()],
+            vec!["Nodes".to_string(), format!("{} ({})", self.nodes.len(), node_summary)],
+            vec!["Links".to_string(), self.links.len().to_string()],
+            vec!["Input files".to_string(), self.input_file_paths.len().to_string()],
+            vec!["Outputs".to_string(), self.outputs.len().to_string()],
+        ]));
+
+        // Mass balance
+        let nsteps = config.sim_nsteps.max(1) as f64;
+        let mut rows: Vec<Vec<String>> = self.nodes.iter()
+            .map(|node| vec![escape(node.get_name()), node.get_type_as_string(), number(node.get_mass_balance() / nsteps)])
+            .collect();
+        let total: f64 = self.nodes.iter().map(|node| node.get_mass_balance() / nsteps).sum();
+        rows.push(vec!["<b>Total</b>".to_string(), String::new(), format!("<b>{}</b>", number(total))]);
+        html.push_str("<h2>Mass balance</h2>\n<p>Mean mass balance of each node, in ML per timestep, as in the mass balance report.</p>\n");
+        html.push_str(&table(&["Node", "Type", "Mass balance"], &rows));
+
+        // Output series
+        let outputs = self.collect_output_series();
+        if !outputs.is_empty() {
+            let rows: Vec<Vec<String>> = outputs.iter()
+                .map(|ts| {
+                    let s = SeriesSummary::from_values(&ts.values);
+                    vec![escape(&ts.name), s.count.to_string(), number(s.mean), number(s.min), number(s.max)]
+                })
+                .collect();
+            html.push_str("<h2>Outputs</h2>\n");
+            html.push_str(&table(&["Series", "Count", "Mean", "Min", "Max"], &rows));
+        }
+
+        // Gauges
+        html.push_str("<h2>Gauges</h2>\n");
+        let gauges = self.gauge_series();
+        if gauges.is_empty() {
+            html.push_str("<p>No gauges with recorded results and observed flows.</p>\n");
+        } else {
+            let rows: Vec<Vec<String>> = self.gauge_statistics().iter()
+                .map(|s| vec![escape(&s.gauge), s.n_compared.to_string(), fixed(s.nse, 3), fixed(s.kge, 3), fixed(s.pbias, 1)])
+                .collect();
+            html.push_str(&table(&["Gauge", "Compared", "NSE", "KGE", "PBIAS (%)"], &rows));
+            for (gauge, simulated, observed) in &gauges {
+                html.push_str(&format!("<h3>{}</h3>\n<div class=\"figures\">\n", escape(gauge)));
+                let (obs, sim) = (downsample_mean(observed, MAX_FIGURE_POINTS), downsample_mean(simulated, MAX_FIGURE_POINTS));
+                let start = observed.start_timestamp;
+                let date = |x: f64| u64_to_date_string_for_step_size(start + x as u64 * step, step);
+                let hydrograph = |ts: &Timeseries| -> Vec<(f64, f64)> {
+                    let block = (ts.step_size / step.max(1)) as f64;
+                    ts.values.iter().enumerate().map(|(i, &v)| (i as f64 * block, v)).collect()
+                };
+                html.push_str(&svg_chart("Hydrograph", "Flow", &[
+                    ("Observed", OBSERVED_COLOR, hydrograph(&obs)),
+                    ("Simulated", SIMULATED_COLOR, hydrograph(&sim)),
+                ], false, &date));
+                let fdc = |ts: &Timeseries| -> Vec<(f64, f64)> {
+                    let points = Line::flow_duration_curve(ts).points;
+                    let block = points.len().div_ceil(MAX_FIGURE_POINTS).max(1);
+                    points.into_iter().step_by(block).collect()
+                };
+                html.push_str(&svg_chart("Flow duration curve", "Flow", &[
+                    ("Observed", OBSERVED_COLOR, fdc(observed)),
+                    ("Simulated", SIMULATED_COLOR, fdc(simulated)),
+                ], true, &|x| format!("{:.0}%", x)));
+                html.push_str("</div>\n");
+            }
+        }
+
+        // Parameters
+        let parameters: Vec<(String, f64)> = match parameters {
+            Some(p) => p.to_vec(),
+            None => {
+                let mut p = self.data_cache.constants.get_name_value_pairs();
+                for node in &self.nodes {
+                    let name = node.get_name();
+                    for param in self.list_node_parameters(name).unwrap_or_default() {
+                        let target = format!("node.{}.{}", name, param);
+                        if let Ok(value) = self.get_parameter(&target) {
+                            p.push((target, value));
+                        }
+                    }
+                }
+                p
+            }
+        };
+        html.push_str("<h2>Parameters</h2>\n");
+        if parameters.is_empty() {
+            html.push_str("<p>The model has no parameters.</p>\n");
+        } else {
+            let rows: Vec<Vec<String>> = parameters.iter().map(|(name, value)| vec![escape(name), number(*value)]).collect();
+            html.push_str(&table(&["Parameter", "Value"], &rows));
+        }
+
+        html.push_str(&format!("<footer>Kalix {}</footer>\n</body>\n</html>\n", env!("CARGO_PKG_VERSION")));
+        html
+    }
+}
+
+const OBSERVED_COLOR: &str = "#333333";
+const SIMULATED_COLOR: &str = "#d62728";
+
+const STYLE: &str = "<style>
+body { font-family: sans-serif; margin: 2em auto; max-width: 1100px; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: left; }
+td:not(:first-child) { text-align: right; }
+th { background: #f2f2f2; }
+.figures { display: flex; flex-wrap: wrap; gap: 1em; }
+footer { margin-top: 2em; color: #888; font-size: small; }
+</style>
+";
+
+/// Escapes text for HTML content and attribute values
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A value with up to six significant figures, or blank if missing
+fn number(value: f64) -> String {
+    if value.is_nan() {
+        String::new()
+    } else if value != 0.0 && (value.abs() >= 1e7 || value.abs() < 1e-4) {
+        format!("{:.4e}", value)
+    } else {
+        let decimals = (5 - value.abs().log10().floor().max(0.0) as i32).max(0) as usize;
+        let text = format!("{:.*}", decimals, value);
+        if text.contains('.') { text.trim_end_matches('0').trim_end_matches('.').to_string() } else { text }
+    }
+}
+
+/// A value to a fixed number of decimals, or blank if missing
+fn fixed(value: f64, decimals: usize) -> String {
+    if value.is_nan() { String::new() } else { format!("{:.*}", decimals, value) }
+}
+
+/// An HTML table. Cells are inserted as given, so text in them must already be escaped.
+fn table(headings: &[&str], rows: &[Vec<String>]) -> String {
+    let mut html = String::from("<table>\n");
+    if headings.iter().any(|h| !h.is_empty()) {
+        html.push_str(&format!("<tr>{}</tr>\n", headings.iter().map(|h| format!("<th>{}</th>", h)).collect::<String>()));
+    }
+    for row in rows {
+        html.push_str(&format!("<tr>{}</tr>\n", row.iter().map(|c| format!("<td>{}</td>", c)).collect::<String>()));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// A series in a chart: its label, colour and (x, y) points
+type ChartLine<'a> = (&'a str, &'a str, Vec<(f64, f64)>);
+
+/// An SVG line chart of several series, with a legend. Points with missing values (or values
+/// that are not positive, on a log y-axis) break the line. `x_label` formats the x-axis ticks.
+fn svg_chart(title: &str, y_title: &str, lines: &[ChartLine], log_y: bool,
+             x_label: &dyn Fn(f64) -> String) -> String {
+    const WIDTH: f64 = 520.0;
+    const HEIGHT: f64 = 300.0;
+    const LEFT: f64 = 70.0;
+    const RIGHT: f64 = 15.0;
+    const TOP: f64 = 30.0;
+    const BOTTOM: f64 = 40.0;
+
+    let to_y = |v: f64| if log_y { if v > 0.0 { v.log10() } else { f64::NAN } } else { v };
+    let points = || lines.iter().flat_map(|(_, _, p)| p.iter()).filter(|(_, y)| to_y(*y).is_finite());
+    let (mut x_min, mut x_max) = points().fold((f64::INFINITY, f64::NEG_INFINITY), |(a, b), (x, _)| (a.min(*x), b.max(*x)));
+    let (mut y_min, mut y_max) = points().fold((f64::INFINITY, f64::NEG_INFINITY), |(a, b), (_, y)| (a.min(to_y(*y)), b.max(to_y(*y))));
+    if x_min >= x_max {
+        (x_min, x_max) = (x_min.min(0.0), x_min.max(0.0) + 1.0);
+    }
+    if log_y {
+        (y_min, y_max) = (y_min.floor(), y_max.ceil());
+    }
+    if y_min >= y_max {
+        (y_min, y_max) = if y_min.is_finite() { (y_min - 1.0, y_min + 1.0) } else { (0.0, 1.0) };
+    }
+    if !log_y && y_min > 0.0 && y_min < 0.5 * y_max {
+        y_min = 0.0;
+    }
+
+    let sx = |x: f64| LEFT + (x - x_min) / (x_max - x_min) * (WIDTH - LEFT - RIGHT);
+    let sy = |y: f64| HEIGHT - BOTTOM - (y - y_min) / (y_max - y_min) * (HEIGHT - TOP - BOTTOM);
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-size=\"11\">\n", WIDTH, HEIGHT);
+    svg.push_str(&format!("<text x=\"{}\" y=\"16\" font-size=\"13\" font-weight=\"bold\">{}</text>\n", LEFT, escape(title)));
+    svg.push_str(&format!("<text transform=\"translate(14,{}) rotate(-90)\" text-anchor=\"middle\">{}</text>\n",
+                          (TOP + HEIGHT - BOTTOM) / 2.0, escape(y_title)));
+
+    // Axes and ticks
+    svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#999\"/>\n",
+                          LEFT, TOP, WIDTH - LEFT - RIGHT, HEIGHT - TOP - BOTTOM));
+    for i in 0..=4 {
+        let x = x_min + (x_max - x_min) * i as f64 / 4.0;
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+                              sx(x), HEIGHT - BOTTOM + 16.0, escape(&x_label(x))));
+    }
+    let y_ticks: Vec<f64> = if log_y {
+        let step = ((y_max - y_min) / 6.0).ceil().max(1.0);
+        (0..).map(|i| y_min + i as f64 * step).take_while(|y| *y <= y_max).collect()
+    } else {
+        (0..=4).map(|i| y_min + (y_max - y_min) * i as f64 / 4.0).collect()
+    };
+    for y in y_ticks {
+        let label = if log_y { number(10f64.powf(y)) } else { number(y) };
+        svg.push_str(&format!("<line x1=\"{}\" x2=\"{}\" y1=\"{:.1}\" y2=\"{:.1}\" stroke=\"#eee\"/>\n", LEFT, WIDTH - RIGHT, sy(y), sy(y)));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\n", LEFT - 4.0, sy(y) + 4.0, label));
+    }
+
+    // Lines, then the legend
+    for (i, (label, color, points)) in lines.iter().enumerate() {
+        let mut path = String::new();
+        let mut pen_down = false;
+        for &(x, y) in points {
+            let y = to_y(y);
+            if y.is_finite() {
+                path.push_str(&format!("{}{:.1},{:.1}", if pen_down { " L" } else { " M" }, sx(x), sy(y)));
+            }
+            pen_down = y.is_finite();
+        }
+        svg.push_str(&format!("<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.2\"/>\n", path.trim(), color));
+        let legend_x = WIDTH - RIGHT - 90.0 * (lines.len() - i) as f64;
+        svg.push_str(&format!("<line x1=\"{}\" x2=\"{}\" y1=\"12\" y2=\"12\" stroke=\"{}\" stroke-width=\"2\"/>\n", legend_x, legend_x + 18.0, color));
+        svg.push_str(&format!("<text x=\"{}\" y=\"16\">{}</text>\n", legend_x + 22.0, escape(label)));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -0,0 +1,88 @@
+//! Spin-up: settling a model's stores before a run, instead of discarding its first years.
+//!
+//! The first `spin_up_years` of the simulation period are run over and over, each loop
+//! starting from the state the last one ended in, until no store changes by more than
+//! `spin_up_tolerance` over a loop. The run then starts from that state. The stores carried
+//! over are those a node reports through [`Node::get_state`]: storage volumes, routing reach
+//! contents and rainfall-runoff stores.
+
+use chrono::Months;
+use crate::data_management::run_log::LogLevel;
+use crate::model::Model;
+use crate::nodes::Node;
+use crate::tid::utils::{naive_datetime_to_u64, u64_to_naive_datetime};
+
+/// The largest change between two sets of node states, as `(change, node index)`. A change is
+/// relative to the size of the state, or absolute for states smaller than 1.
+fn largest_change(before: &[Vec<f64>], after: &[Vec<f64>]) -> Option<(f64, usize)> {
+    let mut largest: Option<(f64, usize)> = None;
+    for (i, (b, a)) in before.iter().zip(after).enumerate() {
+        for (x, y) in b.iter().zip(a) {
+            let change = (y - x).abs() / x.abs().max(y.abs()).max(1.0);
+            if largest.is_none_or(|(c, _)| change > c) {
+                largest = Some((change, i));
+            }
+        }
+    }
+    largest
+}
+
+impl Model {
+    /// Start of the first timestep after the spin-up window, or after the end of the run if
+    /// the window is longer than the simulation period
+    fn spin_up_end_timestamp(&self) -> u64 {
+        let start = self.configuration.sim_start_timestamp;
+        let run_end = self.configuration.sim_end_timestamp + self.configuration.sim_stepsize;
+        u64_to_naive_datetime(start)
+            .and_then(|dt| dt.checked_add_months(Months::new(12 * self.configuration.spin_up_years)))
+            .map(naive_datetime_to_u64)
+            .map_or(run_end, |end| end.min(run_end))
+    }
+
+    /// Loops the spin-up window until the node states settle, then prepares the run to start
+    /// from them. The outcome is recorded in the run log. Returns false if interrupted.
+    pub(crate) fn spin_up<F>(&mut self, interrupt_check: &F, total_steps: usize) -> Result<bool, String>
+    where
+        F: Fn() -> bool,
+    {
+        let end = self.spin_up_end_timestamp();
+        let tolerance = self.configuration.spin_up_tolerance;
+        let mut states: Vec<Vec<f64>> = self.nodes.iter().map(|n| n.get_state()).collect();
+        let mut loops = 0;
+        let mut change = None;
+        while loops < self.configuration.spin_up_max_loops {
+            loops += 1;
+            self.data_cache.set_current_step(0);
+            while self.data_cache.current_timestamp < end {
+                if interrupt_check() {
+                    return Ok(false);
+                }
+                self.run_timestep_catching_panics()?;
+                self.data_cache.increment_current_step();
+            }
+            let new_states: Vec<Vec<f64>> = self.nodes.iter().map(|n| n.get_state()).collect();
+            change = largest_change(&states, &new_states);
+            states = new_states;
+            if change.is_none_or(|(c, _)| c <= tolerance) {
+                break;
+            }
+        }
+
+        // Start the run afresh, apart from the states
+        self.prepare_run(total_steps)?;
+        for (node, state) in self.nodes.iter_mut().zip(&states) {
+            if !state.is_empty() {
+                node.set_state(state);
+            }
+        }
+
+        let (level, message) = match change {
+            Some((c, i)) if c > tolerance => (LogLevel::Warning, format!(
+                "Spin-up did not settle in {} loops. The largest change over the last loop was {:.3e}, at node '{}'.",
+                loops, c, self.nodes[i].get_name())),
+            _ => (LogLevel::Info, format!("Spin-up settled after {} loops.", loops)),
+        };
+        self.data_cache.log.record(level, "spin_up", "model", None, || message);
+        Ok(true)
+    }
+}
@@ -0,0 +1,110 @@
+//! Stepped runs: running a model a few timesteps at a time, so its logic can be debugged.
+//!
+//! A stepped run is prepared as a full run is (including its spin-up) and then paused before
+//! its first timestep. It goes on a number of timesteps at a time, or up to a date, and while
+//! it is paused the nodes can be inspected: the water held in their stores, the water that
+//! moved through them, and their results, all as at the end of the last timestep run. The
+//! results available are those the run records anyway, i.e. outputs and results that
+//! expressions refer to.
+
+use crate::hydrology::constituents::NodeWater;
+use crate::misc::simulation_context::clear_context;
+use crate::model::Model;
+use crate::nodes::Node;
+use crate::tid::utils::u64_to_date_string_for_step_size;
+
+/// A node as at the end of the last timestep of a stepped run
+pub struct NodeInspection {
+    pub name: String,
+    pub node_type: String,
+    /// The water in the node's stores (see [`Node::get_state`])
+    pub state: Vec<f64>,
+    pub water: NodeWater,
+    pub mass_balance: f64,
+    /// The node's recorded results, e.g. `("node.dam.volume", 1200.0)`
+    pub results: Vec<(String, f64)>,
+}
+
+impl Model {
+    /// Prepares a run and pauses it before its first timestep. The model must be configured.
+    pub fn start_stepping(&mut self) -> Result<(), String> {
+        let total_steps = self.configuration.sim_nsteps as usize;
+        self.prepare_run(total_steps)?;
+        if self.configuration.spin_up_years > 0 {
+            self.spin_up(&|| false, total_steps)?;
+        }
+        self.data_cache.set_current_step(0);
+        self.stepping = true;
+        Ok(())
+    }
+
+    /// Whether a stepped run is paused with timesteps left to run
+    pub fn is_stepping(&self) -> bool {
+        self.stepping
+    }
+
+    /// Runs up to `n` more timesteps of a stepped run, and returns how many were run. The run
+    /// ends after its last timestep, or at an error.
+    pub fn step(&mut self, n: usize) -> Result<usize, String> {
+        if !self.stepping {
+            return Err("There is no stepped run paused".to_string());
+        }
+        let mut steps = 0;
+        while steps < n && self.data_cache.current_timestamp <= self.configuration.sim_end_timestamp {
+            if let Err(e) = self.run_timestep_catching_panics() {
+                self.stepping = false;
+                clear_context();
+                return Err(e);
+            }
+            self.data_cache.increment_current_step();
+            steps += 1;
+        }
+        if self.data_cache.current_timestamp > self.configuration.sim_end_timestamp {
+            self.stepping = false;
+            clear_context();
+        }
+        Ok(steps)
+    }
+
+    /// Runs a stepped run up to the start of the timestep that includes `timestamp`, and
+    /// returns how many timesteps were run
+    pub fn run_to(&mut self, timestamp: u64) -> Result<usize, String> {
+        let current = self.data_cache.current_timestamp;
+        if self.stepping && timestamp < current {
+            return Err(format!("The run is already paused at {}",
+                               u64_to_date_string_for_step_size(current, self.configuration.sim_stepsize)));
+        }
+        self.step((timestamp.saturating_sub(current) / self.configuration.sim_stepsize) as usize)
+    }
+
+    /// The start of the next timestep of a stepped run, or None after its last
+    pub fn next_step_timestamp(&self) -> Option<u64> {
+        self.stepping.then_some(self.data_cache.current_timestamp)
+    }
+
+    /// The start of the last timestep run, or None before the first
+    pub fn last_step_timestamp(&self) -> Option<u64> {
+        (self.data_cache.current_step > 0)
+            .then(|| self.data_cache.current_timestamp - self.configuration.sim_stepsize)
+    }
+
+    /// The node named `name` as at the end of the last timestep run
+    pub fn inspect_node(&self, name: &str) -> Result<NodeInspection, String> {
+        let node = &self.nodes[self.get_node_idx(name)
+            .ok_or(format!("Node '{}' was not found", name))?];
+        let prefix = format!("node.{}.", node.get_name().to_lowercase());
+        let results = (0..self.data_cache.series.len())
+            .filter(|&idx| self.data_cache.series_name[idx].to_lowercase().starts_with(&prefix))
+            .map(|idx| (self.data_cache.series_name[idx].clone(),
+                        self.data_cache.get_value_with_offset_or_default(idx, -1, f64::NAN)))
+            .collect();
+        Ok(NodeInspection {
+            name: node.get_name().to_string(),
+            node_type: node.get_type_as_string(),
+            state: node.get_state(),
+            water: node.get_node_water(),
+            mass_balance: node.get_mass_balance(),
+            results,
+        })
+    }
+}
@@ -1,20 +1,44 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::panic::{catch_unwind, AssertUnwindSafe};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 use rustc_hash::FxHashMap;
 use crate::nodes::{Node, NodeEnum, Link};
-use crate::data_management::data_cache::DataCache;
+use crate::data_management::data_cache::{DataCache, Recording};
+use crate::data_management::events::{EventKind, EventTable};
+use crate::data_management::run_log::{LogLevel, RunLog};
 use crate::hydrology::accounts::account_manager::AccountManager;
-use crate::io::csv_io::write_ts;
+use crate::hydrology::accounts::carryover::CarryoverAccounts;
+use crate::hydrology::constituents::ConstituentSystem;
+use crate::data_management::tables_cache::TABLE_PREFIX;
+use crate::io::csv_io::{csv_string_to_f64_vec, write_ts_wide, CsvWriteOptions};
+use crate::io::source_res_io::{self, write_res_csv, ResCsvOptions};
 use crate::io::pixie_io;
 use crate::io::custom_ini_parser::IniDocument;
-use crate::misc::configuration::Configuration;
+use crate::io::ini_model_io::IniModelIO;
+use crate::functions::closest_match;
+use crate::misc::configuration::{CircularReferences, Configuration};
+use crate::misc::flow_loop::FlowLoop;
+use crate::misc::incremental_run::RunCache;
+use crate::misc::node_timing::NodeTimer;
+use crate::model_inputs::{Coverage, DynamicInput, InputCoverage, InputExtension, InputNanPolicy, InputResampling, InputScaling, NanPolicy, Policies, Resample, UserFunctions};
+use crate::model_inputs::input_coverage::SimulationWindow;
+use crate::model_inputs::input_extension::{observed_record, ExtensionMethod};
+use crate::model_inputs::input_resampling::resample;
 use crate::misc::simulation_context::{
     set_context_phase, set_context_node,
     clear_context, format_simulation_error, SimPhase
 };
+use crate::misc::misc_functions::is_valid_variable_name;
+use crate::numerical::opt::OptimisableComponent;
+use crate::numerical::table::Table;
+use crate::ordering::dependency_graph::{find_cycle, find_dependencies};
+use crate::ordering::execution_order::topological_sort_with_dependencies;
 use crate::ordering::simple_nodewise_ordering::SimpleNodewiseOrderingSystem;
-use crate::tid::utils::u64_to_iso_datetime_string;
+use crate::perf::profiler::Profile;
+use crate::tid::utils::{u64_to_date_string_for_step_size, u64_to_iso_datetime_string};
 use crate::timeseries::Timeseries;
 use crate::timeseries_input::TimeseriesInput;
 
@@ -23,10 +47,42 @@ pub struct Model {
     pub configuration: Configuration,
     pub inputs: Vec<TimeseriesInput>,
     pub input_file_paths: Vec<String>,
+    /// Adjustments from the `[scaling]` section, applied to the input data at configure time
+    pub input_scaling: Vec<InputScaling>,
+    /// How input files with a different timestep are converted to the simulation step, from
+    /// `resample.<source>` properties in the `[inputs]` section
+    pub input_resampling: Vec<InputResampling>,
+    /// What happens where input files do not cover the simulation period, from
+    /// `coverage.<source>` properties in the `[inputs]` section
+    pub input_coverage: Vec<InputCoverage>,
+    /// What nodes do with missing values of input files, where it differs from the model's
+    /// `nan_policy`, from `nan_policy.<source>` properties in the `[inputs]` section
+    pub input_nan_policies: Vec<InputNanPolicy>,
+    /// The simulation period from the last configure, and the inputs or dates that set it
+    pub simulation_window: SimulationWindow,
+    /// Short input series extended to cover the simulation period, from the `[extension]` section
+    pub input_extension: Vec<InputExtension>,
+    /// The extended data of each input, indexed like `inputs` (`None` for inputs that are not
+    /// extended), worked out at configure time
+    extended_inputs: Vec<Option<Timeseries>>,
+    /// Whether a stepped run is paused (see [`crate::misc::stepping`])
+    pub(crate) stepping: bool,
+    /// The last run and the changes since, for incremental runs (see
+    /// [`crate::misc::incremental_run`])
+    pub(crate) run_cache: RunCache,
+    /// Named expressions from the `[functions]` section, evaluated every timestep
+    pub functions: UserFunctions,
+    /// Staged restrictions from the `[policies]` section, applied every timestep
+    pub policies: Policies,
+    /// Allocation and carryover accounts of regulated users, from the `[allocation]` section
+    pub allocation: CarryoverAccounts,
     pub outputs: Vec<String>,
     pub account_manager: AccountManager,
     pub data_cache: DataCache,
 
+    /// Constituents declared in the `[constituents]` section and their state in each node
+    pub constituents: ConstituentSystem,
+
     /// Working directory for resolving relative file paths
     /// - Set to model file's directory when loaded from INI file
     /// - Set to current working directory when created programmatically
@@ -45,6 +101,9 @@ pub struct Model {
     // Pre-computed execution order
     pub execution_order: Vec<usize>,
 
+    // Variant of the flow loop, chosen when the run is prepared
+    flow_loop: FlowLoop,
+
     // Ordering system
     pub simple_ordering_system: SimpleNodewiseOrderingSystem,
 
@@ -60,6 +119,17 @@ pub struct Model {
     /// preserving save can re-emit only those (state-diff). `None` for models
     /// built programmatically, where there is nothing to preserve.
     pub baseline_canonical: Option<IniDocument>,
+
+    /// When set, each node's flow-phase time is added to this timer during runs. It is read
+    /// when a run starts, so attaching it part way through a run has no effect.
+    pub node_timer: Option<Arc<NodeTimer>>,
+
+    /// Where the time went in the last complete run, node by node, if it had a `node_timer`
+    pub last_profile: Option<Profile>,
+
+    /// Series recorded in full as well as the outputs, e.g. those compared by an optimisation.
+    /// Other results are only kept as far back as expressions read them.
+    pub record_in_full: Vec<String>,
 }
 
 
@@ -126,25 +196,56 @@ impl Model {
         //2) Nodes ask data_cache for idx of relevant data series for input
         self.initialize_nodes()?;
 
+        //2b) Check that nodes using each other's results from the same timestep can be ordered
+        self.resolve_circular_references()?;
+
+        //2c) Find the nodes that policy stages restrict
+        self.policies.resolve(&self.nodes, &self.functions, &self.data_cache)?;
+
+        //2d) Find the users and storages of the allocation accounts
+        self.allocation.resolve(&self.nodes, &self.functions, &self.data_cache)?;
+
+        //2e) Forecast inflows for resource assessments from the whole of their input series
+        let histories = self.allocation.forecasts.iter()
+            .map(|f| self.get_input_series(&f.series).cloned()
+                .ok_or(format!("Inflow forecast '{}' uses '{}', which is not an input series{}",
+                               f.name, f.series, self.did_you_mean_input(&f.series))))
+            .collect::<Result<Vec<_>, String>>()?;
+        self.allocation.assess_inflow_history(&histories)?;
+
         //3) Read the input data from file
         // TODO: Here is where we would load data IF we wanted to read only the stuff that was required.
         //       E.g. if we were doing reload on run with a subset of the data, or
+        //   Inputs named in the [extension] section are extended to cover the other inputs
+        self.extended_inputs = self.extended_input_series()?;
 
         //4) Determine simulation period
         //5) Supports sim period specified by user (done in the same step)
         self.auto_determine_simulation_period()?;
 
-        //6) Load input data into the data_cache, properly aligned with simulation period
-        for i in 0..self.inputs.len() {
-            let input_ts = &self.inputs[i].timeseries;
+        //5b) Settings that nodes read from the data_cache during the run
+        self.data_cache.nan_policy = self.configuration.nan_policy;
+        self.data_cache.strict = self.configuration.strict;
+        self.data_cache.negative_tolerance = self.configuration.negative_tolerance;
 
-            // Validate that input step size matches simulation step size
-            if input_ts.step_size != self.configuration.sim_stepsize {
-                return Err(format!(
-                    "Input timeseries '{}' has step_size {} but simulation requires step_size {}",
-                    input_ts.name, input_ts.step_size, self.configuration.sim_stepsize
-                ));
-            }
+        //6) Load input data into the data_cache, properly aligned with simulation period
+        //   Inputs named in the [scaling] section are adjusted on the way in
+        //   and inputs with a different timestep are resampled to the simulation step
+        //   Missing values are filled according to the coverage of the input's file, and those
+        //   left are handled by the nodes according to its NaN policy
+        let scaled_values = self.scaled_input_values()?;
+        for (i, scaled) in scaled_values.into_iter().enumerate() {
+            let input_ts = match scaled {
+                Some(values) => Cow::Owned(Timeseries { values, ..Timeseries::clone(&self.inputs[i].timeseries) }),
+                None => Cow::Borrowed(self.extended_inputs[i].as_ref().unwrap_or(&self.inputs[i].timeseries)),
+            };
+            let input_ts = self.resampled_input(i, &input_ts, self.configuration.sim_start_timestamp)?;
+            let input_values = &input_ts.values;
+            let fill = match self.input_coverage_policy(i)? {
+                Coverage::Fill(value) => value,
+                _ => f64::NAN,
+            };
+            let nan_policy = self.input_nan_policy(i)?;
 
             // Calculate how many timesteps we need for the simulation
             let sim_steps = 1 + ((self.configuration.sim_end_timestamp
@@ -164,41 +265,49 @@ impl Model {
             if let Some(alias_colindex) = &self.inputs[i].alias_colindex_path {
                 paths_to_fill.push(alias_colindex.clone());
             }
-            for full_path in paths_to_fill {
-                if let Some(idx) = self.data_cache.get_series_idx(&*full_path, false) {
-                    self.data_cache.series[idx].values.clear();
-                    self.data_cache.series[idx].timestamps.clear();
-                    self.data_cache.series[idx].start_timestamp = self.configuration.sim_start_timestamp;
-                    self.data_cache.series[idx].step_size = self.configuration.sim_stepsize;
-
-                    // For each simulation timestep, find corresponding input value
-                    for step in 0..sim_steps {
-                        let sim_timestamp = self.configuration.sim_start_timestamp
-                            + (step as u64 * self.configuration.sim_stepsize);
-
-                        // Find value at this timestamp in input data
-                        let value = if sim_timestamp >= input_ts.start_timestamp {
-                            let steps_from_input_start = (sim_timestamp - input_ts.start_timestamp)
-                                / input_ts.step_size;
-                            let input_idx = steps_from_input_start as usize;
-
-                            if input_idx < input_ts.values.len() {
-                                input_ts.values[input_idx]
-                            } else {
-                                f64::NAN  // Beyond input data range
-                            }
-                        } else {
-                            f64::NAN  // Before input data starts
-                        };
-
-                        self.data_cache.series[idx].push_value(value);
+            let indices: Vec<usize> = paths_to_fill.iter()
+                .filter_map(|full_path| self.data_cache.get_series_idx(full_path, false))
+                .collect();
+            if indices.is_empty() {
+                continue;
+            }
+
+            // For each simulation timestep, find corresponding input value
+            let values: Vec<f64> = (0..sim_steps).map(|step| {
+                let sim_timestamp = self.configuration.sim_start_timestamp
+                    + (step as u64 * self.configuration.sim_stepsize);
+
+                // Find value at this timestamp in input data
+                let value = if sim_timestamp >= input_ts.start_timestamp {
+                    let steps_from_input_start = (sim_timestamp - input_ts.start_timestamp)
+                        / input_ts.step_size;
+                    let input_idx = steps_from_input_start as usize;
+
+                    if input_idx < input_values.len() {
+                        input_values[input_idx]
+                    } else {
+                        f64::NAN  // Beyond input data range
                     }
-                }
+                } else {
+                    f64::NAN  // Before input data starts
+                };
+                if value.is_nan() { fill } else { value }
+            }).collect();
+
+            // The values are shared by every path to the input, and by clones of the model
+            let values = Arc::new(values);
+            for idx in indices {
+                self.data_cache.set_nan_policy(idx, nan_policy);
+                self.data_cache.set_shared_values(idx, Arc::clone(&values),
+                    self.configuration.sim_start_timestamp, self.configuration.sim_stepsize);
             }
         }
         self.data_cache.set_start_and_stepsize(self.configuration.sim_start_timestamp,
                                                self.configuration.sim_stepsize);
 
+        //Results left from an earlier, longer run must not run on past the end of this period
+        self.data_cache.truncate(self.configuration.sim_nsteps as usize);
+
         //7) Nodes ask data_cache for idx for modelled series they might be responsible for populating
         //TODO: I think this was already appropriately done in step 2.
 
@@ -232,10 +341,12 @@ impl Model {
                     }
                 }
                 if !found {
-                    return Err(format!(
-                        "Data reference '{}' was not found in any input file. Check for typos in your model file.",
-                        name
-                    ));
+                    let suggestion = self.did_you_mean_input(&name_lower);
+                    return Err(if suggestion.is_empty() {
+                        format!("Data reference '{}' was not found in any input file. Check for typos in your model file.", name)
+                    } else {
+                        format!("Data reference '{}' was not found in any input file{}", name, suggestion)
+                    });
                 }
             }
         }
@@ -253,19 +364,20 @@ impl Model {
     where
         F: Fn() -> bool,
     {
-        //Initialise the node network
-        self.initialize_network()?;
-
-        //Initialise the water management systems
-        self.account_manager.initialize(&mut self.data_cache);
-
-        // Clear any stale simulation context
-        clear_context();
-
         //Calculate total steps for progress reporting
+        let started = Instant::now();
+        self.last_profile = None;
         let total_steps = ((self.configuration.sim_end_timestamp - self.configuration.sim_start_timestamp)
             / self.configuration.sim_stepsize) + 1;
 
+        self.prepare_incremental_run(total_steps as usize)?;
+
+        //Settle the states by looping the spin-up window, then start the run from them
+        if self.configuration.spin_up_years > 0 && !self.spin_up(&interrupt_check, total_steps as usize)? {
+            clear_context();
+            return Ok(false); // Simulation was interrupted
+        }
+
         //Run all timesteps
         self.data_cache.set_current_step(0);
         while self.data_cache.current_timestamp <= self.configuration.sim_end_timestamp {
@@ -277,17 +389,7 @@ impl Model {
             }
 
             // Run the network with panic catching for better error messages
-            let result = catch_unwind(AssertUnwindSafe(|| {
-                self.run_timestep(self.data_cache.current_timestamp);
-            }));
-
-            if let Err(panic_info) = result {
-                return Err(format_simulation_error(
-                    panic_info,
-                    self.data_cache.current_timestamp,
-                    |idx| self.nodes.get(idx).map(|n| n.get_name().to_string()),
-                ));
-            }
+            self.run_timestep_catching_panics()?;
 
             //Report progress if callback provided
             if let Some(ref mut callback) = progress_callback {
@@ -301,10 +403,73 @@ impl Model {
 
         // Clear context on successful completion
         clear_context();
+        self.finish_incremental_run();
+        if let Some(timer) = &self.node_timer {
+            self.last_profile = Some(Profile::new(self, timer, started.elapsed()));
+        }
 
         Ok(true) // Simulation completed successfully
     }
 
+    /// Initialises everything a run needs, ready to run from the first timestep
+    pub(crate) fn prepare_run(&mut self, total_steps: usize) -> Result<(), String> {
+        //A new run replaces any stepped run that was paused, and the results of the last run
+        self.stepping = false;
+        self.run_cache.begin_run();
+
+        //Initialise the node network
+        self.initialize_network()?;
+
+        //Initialise the water management systems
+        self.account_manager.initialize(&mut self.data_cache);
+
+        //Initialise constituent transport
+        self.constituents.initialise(&self.nodes, &mut self.data_cache, self.configuration.sim_stepsize);
+
+        // Clear any stale simulation context and diagnostics from the previous run
+        clear_context();
+        self.data_cache.log.clear();
+        self.data_cache.events.clear();
+        self.log_extended_inputs();
+        if !self.simulation_window.clipped.is_empty() {
+            let window = self.simulation_window.describe();
+            self.data_cache.log.record(LogLevel::Warning, "simulation_window_clipped", "model", None, || window);
+        }
+
+        //Decide how much of each result to keep, and whether to hold it compressed
+        self.apply_recording_policy();
+
+        //Evaluate expressions that depend only on input data for the whole run up front
+        self.data_cache.precompute_expressions(total_steps);
+        self.choose_flow_loop();
+        Ok(())
+    }
+
+    /// Chooses the variant of the flow loop for the features the run uses
+    pub(crate) fn choose_flow_loop(&mut self) {
+        self.flow_loop = FlowLoop {
+            incremental: self.run_cache.is_recording(),
+            profiled: self.node_timer.is_some(),
+            constituents: !self.constituents.is_empty(),
+        };
+    }
+
+    /// Runs the current timestep, turning a panic into an error naming the node and time
+    pub(crate) fn run_timestep_catching_panics(&mut self) -> Result<(), String> {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            self.run_timestep(self.data_cache.current_timestamp);
+        }));
+
+        if let Err(panic_info) = result {
+            return Err(format_simulation_error(
+                panic_info,
+                self.data_cache.current_timestamp,
+                |idx| self.nodes.get(idx).map(|n| n.get_name().to_string()),
+            ));
+        }
+        Ok(())
+    }
+
     /// Determine the simulation period on the basis of the available input data
     pub fn auto_determine_simulation_period(&mut self) -> Result<(), String> {
 
@@ -338,21 +503,28 @@ impl Model {
             self.configuration.sim_stepsize = 86400;
             self.configuration.sim_nsteps = 1 + (self.configuration.sim_end_timestamp -
                 self.configuration.sim_start_timestamp) / self.configuration.sim_stepsize;
+            self.simulation_window = SimulationWindow {
+                start: self.configuration.sim_start_timestamp,
+                end: self.configuration.sim_end_timestamp,
+                step_size: self.configuration.sim_stepsize,
+                start_set_by: vec!["start".to_string()],
+                end_set_by: vec!["end".to_string()],
+                clipped: vec![],
+            };
 
             // Return
             return Ok(());
         }
 
         // Go through all the critical inputs and make sure they are all in the model.
-        // As you find them, you can go ahead and update the mask of data availability.
-        let mut critical_data_availability_mask: Option<Timeseries> = None;
+        let mut critical_inputs: Vec<usize> = vec![];
         for ci in civ {
 
             let ci_lower = ci.to_lowercase();
 
             // Searching for timeseries that matches ci
             let mut found : bool = false;
-            for ts in self.inputs.iter() {
+            for (i, ts) in self.inputs.iter().enumerate() {
                 let matches = (ci_lower == ts.full_colindex_path)
                     || (ci_lower == ts.full_colname_path)
                     || (ts.alias_colindex_path.as_ref().map_or(false, |p| ci_lower == *p))
@@ -365,88 +537,171 @@ impl Model {
                     // If it is a critical input AND THE SOURCE IS A FILE then the model run
                     // will be limited by the data available in the file.
                     if ts.source_path != "" {
-                        match critical_data_availability_mask {
-                            None => {
-                                //This is the first critical data file
-                                // println!("Initial mask based on {}", ts.source_path);
-                                critical_data_availability_mask = Some(ts.timeseries.clone());
-                            }
-                            Some(ref mut mask) => {
-                                // println!("Mask updated based on {}", ts.source_path);
-                                mask.mask_with(&ts.timeseries);
-                            }
-                        }
-                    } else {
-                        // println!("Mask not influenced by {}", ts.source_path);
+                        critical_inputs.push(i);
                     }
                 }
             }
 
             if !found {
-                return Err(format!("Could not find input data: {}", ci));
+                return Err(format!("Could not find input data: {}{}", ci, self.did_you_mean_input(&ci_lower)));
             }
         }
 
-        // The model could run for any sequence where critical_data_availability_mask has values.
-        // Like Fors, we are going to default to the first period.
-        let mask = critical_data_availability_mask.unwrap();
-
-        //Look for the start.
-        //Start and 0 and break when we find the first non-nan value.
-        let mut start_index = 0;
-        for i in 0..mask.len() {
-            if !mask.values[i].is_nan() {
-                start_index = i;
-                break;
+        // The simulation step is the coarsest step of the critical inputs, leaving out those
+        // that are to be repeated or interpolated (unless that is all of them). Finer inputs are
+        // aggregated to it, on a grid of timesteps lined up with the first input at that step.
+        let fills_in = |i: &usize| self.input_resample_method(*i).is_some_and(|m| m.is_for_coarser_data());
+        let step_of = |i: &usize| self.input_timeseries(*i).step_size;
+        let sim_stepsize = match critical_inputs.iter().filter(|i| !fills_in(i)).map(step_of).max() {
+            Some(step) => step,
+            None => critical_inputs.iter().map(step_of).min().unwrap_or(86400),
+        };
+        let origin = critical_inputs.iter()
+            .find(|i| step_of(i) == sim_stepsize)
+            .map_or(0, |&i| self.input_timeseries(i).start_timestamp);
+        self.configuration.sim_stepsize = sim_stepsize;
+
+        // Inputs set the period according to the coverage policy of their files. Clipped inputs
+        // set it if there are any, otherwise those that must cover it do, and filled inputs never do.
+        critical_inputs.sort_unstable();
+        critical_inputs.dedup();
+        let with_coverage = |coverage: fn(&Coverage) -> bool| -> Result<Vec<usize>, String> {
+            let mut inputs = vec![];
+            for &i in &critical_inputs {
+                if coverage(&self.input_coverage_policy(i)?) {
+                    inputs.push(i);
+                }
             }
-        }
-
-        //Look for the end (exclusive)
-        //Start at start_index and then break when we find the first nan value.
-        let mut end_index = mask.len();
-        for i in start_index..mask.len() {
-            if mask.values[i].is_nan() {
-                end_index = i;
-                break;
+            Ok(inputs)
+        };
+        let clipped = with_coverage(|c| *c == Coverage::Clip)?;
+        let checked = with_coverage(|c| *c == Coverage::Error)?;
+        let specified_start = self.configuration.specified_sim_start_timestamp;
+        let specified_end = self.configuration.specified_sim_end_timestamp;
+        let name_of = |i: usize| self.inputs[i].alias_colname_path.clone().unwrap_or(self.inputs[i].full_colname_path.clone());
+        let resampled = |inputs: &[usize]| inputs.iter()
+            .map(|&i| Ok((i, self.resampled_input(i, self.input_timeseries(i), origin)?.into_owned())))
+            .collect::<Result<Vec<(usize, Timeseries)>, String>>();
+
+        if clipped.is_empty() {
+            // Without clipped inputs, the period is where all the inputs that must cover it have
+            // data, unless the start and end are specified. Gaps are found when they are checked.
+            let mut records = vec![];
+            for (i, ts) in resampled(&checked)? {
+                records.push((i, observed_record(&ts).ok_or(format!("Input '{}' has no data", name_of(i)))?));
             }
+            let (start, end) = match (records.iter().map(|r| r.1.0).max(), records.iter().map(|r| r.1.1).min()) {
+                (Some(start), Some(end)) => (start, end),
+                _ => match (specified_start, specified_end) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => return Err("All the critical input data is filled, so it does not set the simulation period. Please specify start and end.".to_string()),
+                },
+            };
+            let window = SimulationWindow {
+                step_size: sim_stepsize,
+                start: specified_start.unwrap_or(start),
+                end: specified_end.unwrap_or(end),
+                start_set_by: match specified_start {
+                    Some(_) => vec!["start".to_string()],
+                    None => records.iter().filter(|r| r.1.0 == start).map(|r| name_of(r.0)).collect(),
+                },
+                end_set_by: match specified_end {
+                    Some(_) => vec!["end".to_string()],
+                    None => records.iter().filter(|r| r.1.1 == end).map(|r| name_of(r.0)).collect(),
+                },
+                clipped: vec![],
+            };
+            if window.start > window.end {
+                return Err(format!("The critical input data has no period in common: it starts on {} and ends on {}",
+                                   u64_to_date_string_for_step_size(window.start, sim_stepsize), u64_to_date_string_for_step_size(window.end, sim_stepsize)));
+            }
+            return self.set_simulation_window(window, &checked, origin);
         }
 
-        // Update the configuration
-        let n_steps = (end_index - start_index) as u64;
-        let start_timestamp = mask.start_timestamp + (start_index as u64 * mask.step_size);
-        let end_timestamp = mask.start_timestamp + ((end_index - 1) as u64 * mask.step_size);
-        self.configuration.sim_stepsize = mask.step_size;
-        self.configuration.sim_start_timestamp = start_timestamp;
-        self.configuration.sim_end_timestamp = end_timestamp;
-        self.configuration.sim_nsteps = n_steps;
-
-        // Override with dates specified in the model, if relevant
-        match self.configuration.specified_sim_start_timestamp {
-            Some(timestamp) => {
-                if (timestamp < self.configuration.sim_start_timestamp) ||
-                    (timestamp > self.configuration.sim_end_timestamp) {
-                    return Err("Specified start inconsistent with input data.".to_string());
+        // Where every clipped input has data. Like Fors, we default to the first such period, or
+        // the first after the specified start.
+        let series = resampled(&clipped)?;
+        let mut mask = series[0].1.clone();
+        for (_, ts) in &series[1..] {
+            mask.mask_with(ts);
+        }
+        let step = mask.step_size;
+        let timestamp_of = |index: usize| mask.start_timestamp + index as u64 * step;
+        let first = specified_start.map_or(0, |t| t.saturating_sub(mask.start_timestamp).div_ceil(step) as usize);
+        let start_index = (first..mask.len()).find(|&i| !mask.values[i].is_nan())
+            .ok_or(format!("The critical input data has no period in common{}",
+                           specified_start.map_or(String::new(), |t| format!(" from the specified start {}", u64_to_date_string_for_step_size(t, step)))))?;
+        let end_index = (start_index..mask.len()).find(|&i| mask.values[i].is_nan()).unwrap_or(mask.len()) - 1;
+        let value_at = |ts: &Timeseries, t: u64| match t.checked_sub(ts.start_timestamp) {
+            Some(offset) if offset % ts.step_size == 0 => ts.values.get((offset / ts.step_size) as usize).copied().unwrap_or(f64::NAN),
+            _ => f64::NAN,
+        };
+
+        // The dates given in [kalix] are used where the data covers them, and clipped otherwise
+        let set_by = |t: u64| -> Vec<String> {
+            series.iter().filter(|(_, ts)| value_at(ts, t).is_nan()).map(|(i, _)| name_of(*i)).collect()
+        };
+        let mut window = SimulationWindow { step_size: step, ..Default::default() };
+        let data_start = timestamp_of(start_index);
+        match specified_start {
+            Some(t) if t >= data_start => {
+                window.start = t;
+                window.start_set_by = vec!["start".to_string()];
+            }
+            _ => {
+                window.start = data_start;
+                window.start_set_by = set_by(data_start.saturating_sub(step));
+                if specified_start.is_some() {
+                    window.clipped.extend(window.start_set_by.iter().cloned());
                 }
-                self.configuration.sim_start_timestamp = timestamp;
-                self.configuration.sim_nsteps = 1 + (self.configuration.sim_end_timestamp -
-                    self.configuration.sim_start_timestamp) / self.configuration.sim_stepsize;
             }
-            None => {}
         }
-        match self.configuration.specified_sim_end_timestamp {
-            Some(timestamp) => {
-                if (timestamp < self.configuration.sim_start_timestamp) ||
-                    (timestamp > self.configuration.sim_end_timestamp) {
-                    return Err("Specified end inconsistent with input data.".to_string());
+        let data_end = timestamp_of(end_index);
+        match specified_end {
+            Some(t) if t <= data_end => {
+                window.end = t;
+                window.end_set_by = vec!["end".to_string()];
+            }
+            _ => {
+                window.end = data_end;
+                window.end_set_by = set_by(data_end + step);
+                if specified_end.is_some() {
+                    window.clipped.extend(window.end_set_by.iter().filter(|n| !window.clipped.contains(n)).cloned().collect::<Vec<_>>());
                 }
-                self.configuration.sim_end_timestamp = timestamp;
-                self.configuration.sim_nsteps = 1 + (self.configuration.sim_end_timestamp -
-                    self.configuration.sim_start_timestamp) / self.configuration.sim_stepsize;
             }
-            None => {}
         }
+        if window.start > window.end {
+            return Err(format!("The specified period ends before the critical input data starts, on {}",
+                               u64_to_date_string_for_step_size(window.start, step)));
+        }
+        self.set_simulation_window(window, &checked, origin)
+    }
 
-        // Return ok
+    /// Sets the simulation period to `window`, after checking that each of the `checked` inputs
+    /// (whose files' coverage is `error`) has data over the whole of it
+    fn set_simulation_window(&mut self, window: SimulationWindow, checked: &[usize], origin: u64) -> Result<(), String> {
+        let step = self.configuration.sim_stepsize;
+        for &i in checked {
+            let ts = self.resampled_input(i, self.input_timeseries(i), origin)?;
+            let mut t = window.start;
+            while t <= window.end {
+                let value = match t.checked_sub(ts.start_timestamp) {
+                    Some(offset) => ts.values.get((offset / ts.step_size) as usize).copied().unwrap_or(f64::NAN),
+                    None => f64::NAN,
+                };
+                if value.is_nan() {
+                    let name = self.inputs[i].alias_colname_path.as_ref().unwrap_or(&self.inputs[i].full_colname_path);
+                    return Err(format!("Input '{}' has no data on {}, within the simulation period {} to {}. Its file's coverage is 'error'",
+                                       name, u64_to_date_string_for_step_size(t, step), u64_to_date_string_for_step_size(window.start, step),
+                                       u64_to_date_string_for_step_size(window.end, step)));
+                }
+                t += step;
+            }
+        }
+        self.configuration.sim_start_timestamp = window.start;
+        self.configuration.sim_end_timestamp = window.end;
+        self.configuration.sim_nsteps = 1 + (window.end - window.start) / step;
+        self.simulation_window = window;
         Ok(())
     }
 
@@ -456,46 +711,135 @@ impl Model {
         // Accounting tasks
         self.account_manager.run_maintenance(&self.data_cache);
 
-        // Execute order phase
+        // Functions that only depend on inputs and earlier results
+        self.functions.evaluate_before_flow(&mut self.data_cache);
+
+        // Restrictions of the active policy stage
+        self.policies.apply(&mut self.nodes, &mut self.data_cache);
+
+        // Users can only order what is left in their allocation accounts
+        self.allocation.begin_timestep(&mut self.nodes, &mut self.data_cache);
+
+        // Execute order phase, unless a re-run leaves the nodes that pass orders as they were
         set_context_phase(SimPhase::Ordering);
-        self.simple_ordering_system.run_ordering_phase(&mut self.nodes, &mut self.data_cache);
+        if !self.run_cache.skips_ordering() {
+            self.simple_ordering_system.run_ordering_phase(&mut self.nodes, &self.links, &mut self.data_cache);
+        }
 
         // Execute nodes with flow phase
         set_context_phase(SimPhase::Flow);
+        let FlowLoop { incremental, profiled, constituents } = self.flow_loop;
+        match (incremental, profiled, constituents) {
+            (false, false, false) => self.run_flow_loop::<false, false, false>(),
+            (false, false, true) => self.run_flow_loop::<false, false, true>(),
+            (false, true, false) => self.run_flow_loop::<false, true, false>(),
+            (false, true, true) => self.run_flow_loop::<false, true, true>(),
+            (true, false, false) => self.run_flow_loop::<true, false, false>(),
+            (true, false, true) => self.run_flow_loop::<true, false, true>(),
+            (true, true, false) => self.run_flow_loop::<true, true, false>(),
+            (true, true, true) => self.run_flow_loop::<true, true, true>(),
+        }
+
+        // Allocation accounts are charged with diversions and carryover losses
+        self.allocation.end_timestep(&self.nodes, &mut self.data_cache);
+
+        // Functions of this timestep's node results
+        self.functions.evaluate_after_flow(&mut self.data_cache);
+
+        // Accounting recorders
+        self.account_manager.record_results(&mut self.data_cache);
+    }
+
+    /// Runs the flow phase of each node in execution order, passing its outflows down its
+    /// links. Each variant of the loop is compiled separately, so a run only pays for the
+    /// features it uses.
+    fn run_flow_loop<const INCREMENTAL: bool, const PROFILED: bool, const CONSTITUENTS: bool>(&mut self) {
+        if CONSTITUENTS {
+            self.constituents.begin_timestep();
+        }
+        let step = self.data_cache.current_step;
         for &node_idx in &self.execution_order {
 
+            // A re-run only simulates the nodes that changes affect, and the others pass on the
+            // flows they delivered in the last run
+            if INCREMENTAL && self.run_cache.skips(node_idx) {
+                for &link_idx in &self.outgoing_links[node_idx] {
+                    let link = &self.links[link_idx];
+                    let flow = self.run_cache.cached_flow(link_idx, step);
+                    if !self.run_cache.skips(link.to_node) && (flow > 0.0 || flow.is_nan()) {
+                        self.nodes[link.to_node].add_usflow(flow, link.to_inlet);
+                    }
+                    self.run_cache.record_flow(link_idx, step, flow);
+                }
+                continue;
+            }
+
             // Set node context for error reporting (just stores the index)
             set_context_node(node_idx);
 
             // Run the node's flow phase
-            self.nodes[node_idx].run_flow_phase(&mut self.data_cache, &mut self.account_manager);
+            if PROFILED {
+                let started = Instant::now();
+                self.nodes[node_idx].run_flow_phase(&mut self.data_cache, &mut self.account_manager);
+                if let Some(timer) = &self.node_timer {
+                    timer.add(node_idx, started.elapsed());
+                }
+            } else {
+                self.nodes[node_idx].run_flow_phase(&mut self.data_cache, &mut self.account_manager);
+            }
+            if CONSTITUENTS {
+                self.constituents.mix(node_idx, &self.nodes[node_idx], &mut self.data_cache);
+            }
 
-            // Immediately propagate outflows to downstream nodes
+            // Immediately propagate outflows to downstream nodes. A missing outflow is passed on
+            // too, as the nan_policy of the input it came from is to propagate it.
             for &link_idx in &self.outgoing_links[node_idx] {
-                let link = &self.links[link_idx];
-                let outflow = self.nodes[node_idx].remove_dsflow(link.from_outlet);
+                let link = &mut self.links[link_idx];
+                let mut outflow = self.nodes[node_idx].remove_dsflow(link.from_outlet);
+                if outflow < 0.0 {
+                    outflow = self.data_cache.guard_negative(EventKind::NegativeFlowClamped, link.event_source, outflow);
+                }
 
-                if outflow > 0.0 {
+                if outflow > 0.0 || outflow.is_nan() {
+                    if link.has_attributes() {
+                        let (delivered, excess) = link.convey(outflow);
+                        if excess > 0.0 {
+                            self.data_cache.emit_event(EventKind::LinkCapacityExceeded, link.event_source, excess);
+                        }
+                        outflow = delivered;
+                    }
                     self.nodes[link.to_node].add_usflow(outflow, link.to_inlet);
+                    if CONSTITUENTS {
+                        self.constituents.transfer(node_idx, link.to_node, outflow);
+                    }
+                } else {
+                    outflow = 0.0;
+                }
+                if INCREMENTAL {
+                    self.run_cache.record_flow(link_idx, step, outflow);
                 }
             }
         }
-
-        // Accounting recorders
-        self.account_manager.record_results(&mut self.data_cache);
     }
 
     pub fn initialize_network(&mut self) -> Result<(), String> {
 
         // Initialize the nodes and execution order
         self.initialize_nodes()?;
-        self.check_execution_order()?;
+        for link in &mut self.links {
+            link.mbal = 0.0;
+        }
+        self.resolve_execution_order()?;
+        for link in &mut self.links {
+            let source = format!("{}.ds_{}", self.nodes[link.from_node].get_name(), link.from_outlet + 1);
+            link.event_source = self.data_cache.add_event_source(&source);
+        }
         // TODO: why am I doing the execution order here in "initialize_network"? Cant we just do this once during configure?
 
         // Initialise the ordering system
         // TODO: I am doing this in "initialize_network" because it relies on execution order being resolved (which we do above).
         self.simple_ordering_system.initialize(
-            &mut self.nodes, &self.links, &self.incoming_links
+            &mut self.nodes, &self.links, &self.incoming_links, &self.execution_order
         );
 
         // Return
@@ -510,12 +854,55 @@ impl Model {
 
     /// Resolve a file path relative to the model's working directory.
     /// Supports absolute, relative, and trailhead (`^/`) paths.
-    fn resolve_path(&self, path: &str) -> Result<PathBuf, String> {
+    pub(crate) fn resolve_path(&self, path: &str) -> Result<PathBuf, String> {
+        if cfg!(target_arch = "wasm32") {
+            return Err(format!("Cannot read '{}': files are not available in WebAssembly builds", path));
+        }
         let mut kp = crate::io::kalix_path::KalixPath::parse(path)?;
         kp.resolve(&self.working_directory)?;
         Ok(kp.resolved)
     }
 
+    /// Defines a table for `lookup()` from its `[tables]` definition: either the values
+    /// inline (`x1, y1, x2, y2, ...`) or the path of a CSV file with two columns (x, y)
+    /// and an optional header row.
+    pub fn load_table(&mut self, name: &str, definition: &str) -> Result<(), String> {
+        let name_lower = name.to_lowercase();
+        match name_lower.strip_prefix(TABLE_PREFIX) {
+            Some(short_name) if is_valid_variable_name(short_name) => {}
+            _ => return Err(format!("Invalid table name '{}'. Table names must start with '{}'", name, TABLE_PREFIX)),
+        }
+
+        let values = match csv_string_to_f64_vec(definition) {
+            Ok(values) => values,
+            Err(_) => {
+                let resolved_path = self.resolve_path(definition.trim())?;
+                let text = std::fs::read_to_string(&resolved_path)
+                    .map_err(|e| format!("Could not read table file '{}': {}", definition, e))?;
+                let mut values = vec![];
+                for (i, line) in text.lines().map(str::trim).filter(|l| !l.is_empty()).enumerate() {
+                    match csv_string_to_f64_vec(line) {
+                        Ok(row) if row.len() == 2 => values.extend(row),
+                        Err(_) if i == 0 => {} // Header row
+                        _ => return Err(format!("Table file '{}' must have two numeric columns (x, y)", definition)),
+                    }
+                }
+                values
+            }
+        };
+        if values.len() % 2 != 0 {
+            return Err(format!("Table '{}' must be given as x, y pairs", name));
+        }
+
+        let mut table = Table::new(2);
+        for (row, pair) in values.chunks(2).enumerate() {
+            table.set_value(row, 0, pair[0]);
+            table.set_value(row, 1, pair[1]);
+        }
+        self.data_cache.tables.set_table(&name_lower, table, definition.trim())?;
+        Ok(())
+    }
+
     pub fn load_input_data(&mut self, file_path: &str, alias: Option<&str>) -> Result<usize, String> {
         // Remember the ORIGINAL input file path (for serialization/display)
         self.input_file_paths.push(file_path.to_string());
@@ -532,32 +919,298 @@ impl Model {
         Ok(len)
     }
 
+    /// Adds input data from CSV text held in memory, addressed by `file_name` (and `alias`, if
+    /// given) as if it had been loaded from a file of that name. Used where there is no file
+    /// system, e.g. in the WebAssembly build. Unlike [`Model::load_input_data`] the data is not
+    /// listed in the `[inputs]` section when the model is saved. The model must be configured
+    /// again for the data to take effect.
+    pub fn load_input_csv_str(&mut self, file_name: &str, csv_text: &str, alias: Option<&str>) -> Result<usize, String> {
+        let mut x = TimeseriesInput::load_from_str(csv_text, file_name, alias)?;
+        let len = x.len();
+        self.inputs.append(&mut x);
+        Ok(len)
+    }
+
+
+    /// The resampling policy given for an input's file, by alias or file name
+    fn input_resample_method(&self, i: usize) -> Option<Resample> {
+        let input = &self.inputs[i];
+        self.input_resampling.iter()
+            .find(|r| r.source == input.source_name || input.alias.as_ref() == Some(&r.source))
+            .map(|r| r.method)
+    }
+
+    /// The coverage policy given for an input's file, by alias or file name
+    fn input_coverage_policy(&self, i: usize) -> Result<Coverage, String> {
+        for c in &self.input_coverage {
+            if !self.inputs.iter().any(|input| input.source_name == c.source || input.alias.as_ref() == Some(&c.source)) {
+                return Err(format!("Input file '{}' named by 'coverage.{}' was not found", c.source, c.source));
+            }
+        }
+        let input = &self.inputs[i];
+        Ok(self.input_coverage.iter()
+            .find(|c| c.source == input.source_name || input.alias.as_ref() == Some(&c.source))
+            .map_or(Coverage::Clip, |c| c.coverage))
+    }
+
+    /// The NaN policy given for an input's file, by alias or file name, if any
+    fn input_nan_policy(&self, i: usize) -> Result<Option<NanPolicy>, String> {
+        for p in &self.input_nan_policies {
+            if !self.inputs.iter().any(|input| input.source_name == p.source || input.alias.as_ref() == Some(&p.source)) {
+                return Err(format!("Input file '{}' named by 'nan_policy.{}' was not found", p.source, p.source));
+            }
+        }
+        let input = &self.inputs[i];
+        Ok(self.input_nan_policies.iter()
+            .find(|p| p.source == input.source_name || input.alias.as_ref() == Some(&p.source))
+            .map(|p| p.policy))
+    }
+
+    /// Returns input `i`'s data (`ts`, which may have been scaled) on the simulation step, with
+    /// timesteps lined up with `origin`. Interpolating coarser data is logged as a warning.
+    fn resampled_input<'a>(&self, i: usize, ts: &'a Timeseries, origin: u64) -> Result<Cow<'a, Timeseries>, String> {
+        for r in &self.input_resampling {
+            if !self.inputs.iter().any(|input| input.source_name == r.source || input.alias.as_ref() == Some(&r.source)) {
+                return Err(format!("Input file '{}' named by 'resample.{}' was not found", r.source, r.source));
+            }
+        }
+        let step_size = self.configuration.sim_stepsize;
+        if ts.step_size == step_size {
+            return Ok(Cow::Borrowed(ts));
+        }
+        let method = self.input_resample_method(i);
+        let resampled = resample(ts, origin, step_size, method)
+            .map_err(|e| format!("Input timeseries '{}' {}", ts.name, e))?;
+        if method == Some(Resample::Interpolate) {
+            log::warn!("Input timeseries '{}' has been interpolated from step_size {} to step_size {}",
+                       ts.name, ts.step_size, step_size);
+        }
+        Ok(Cow::Owned(resampled))
+    }
+
+    /// Gets the data of a loaded input series, addressed by any of its `data.*` paths
+    /// (e.g. `data.rain_csv.by_name.rain` or an alias path)
+    pub fn get_input_series(&self, name: &str) -> Option<&Timeseries> {
+        self.find_input_idx(name).map(|i| &*self.inputs[i].timeseries)
+    }
+
+    /// Replaces the data of a loaded input series (see [`Model::get_input_series`]). The new
+    /// data takes effect from the next `configure()`. Returns the data it replaced so the
+    /// caller can put it back.
+    pub fn replace_input_series(&mut self, name: &str, mut timeseries: Timeseries) -> Result<Timeseries, String> {
+        let idx = self.find_input_idx(name)
+            .ok_or_else(|| format!("Input series not found: {}", name))?;
+        self.run_cache.input_changed(idx);
+        let input = &mut self.inputs[idx];
+        timeseries.name = input.timeseries.name.clone();
+        Ok(Arc::unwrap_or_clone(std::mem::replace(&mut input.timeseries, Arc::new(timeseries))))
+    }
+
+    /// A " Did you mean '...'?" sentence for a data reference that matches no input, naming
+    /// the input (by column name) that it is closest to, if any is close
+    fn did_you_mean_input(&self, name: &str) -> String {
+        let candidates = self.inputs.iter()
+            .flat_map(|ts| std::iter::once(&ts.full_colname_path).chain(ts.alias_colname_path.as_ref()))
+            .map(|path| path.as_str());
+        match closest_match(name, candidates) {
+            Some(suggestion) => format!(". Did you mean '{}'?", suggestion),
+            None => String::new(),
+        }
+    }
 
-    /// Check execution order
-    fn check_execution_order(&mut self) -> Result<(), String> {
+    /// The data of input `i`, extended if the `[extension]` section names it
+    fn input_timeseries(&self, i: usize) -> &Timeseries {
+        self.extended_inputs.get(i).and_then(|ts| ts.as_ref()).unwrap_or(&self.inputs[i].timeseries)
+    }
 
-        // Execution order according to node index
-        self.execution_order.clear();
-        for node_idx in 0..self.nodes.len() {
-            self.execution_order.push(node_idx);
+    /// The extended data of each input named in the `[extension]` section, indexed like
+    /// `self.inputs`. Monthly means extend a series out to the earliest and latest data of any
+    /// input, and a regression out to the record of the series it is fitted on.
+    fn extended_input_series(&self) -> Result<Vec<Option<Timeseries>>, String> {
+        let mut extended: Vec<Option<Timeseries>> = vec![None; self.inputs.len()];
+        let span = self.inputs.iter()
+            .filter_map(|input| observed_record(&input.timeseries))
+            .fold(None, |span: Option<(u64, u64)>, (first, last)| match span {
+                Some((a, b)) => Some((a.min(first), b.max(last))),
+                None => Some((first, last)),
+            });
+        for extension in &self.input_extension {
+            let i = self.find_input_idx(&extension.series)
+                .ok_or(format!("Extended series '{}' was not found in any input file{}",
+                               extension.series, self.did_you_mean_input(&extension.series)))?;
+            if extended[i].is_some() {
+                return Err(format!("Series '{}' is extended more than once", extension.series));
+            }
+            let predictor = match &extension.method {
+                ExtensionMethod::MonthlyMean => None,
+                ExtensionMethod::Regression(name) => self.get_input_series(name),
+            };
+            let span = span.ok_or(format!("Extended series '{}' has no values", extension.series))?;
+            extended[i] = Some(extension.apply(&self.inputs[i].timeseries, span, predictor)?);
         }
+        Ok(extended)
+    }
 
-        // Check execution order is consistent with flow phase using link info:
-        // The node below each link must have a higher index than the node above the link
-        for link in &self.links {
-            //println!("{} -> {}", link.from_node, link.to_node);
-            if link.from_node >= link.to_node {
-                let from_name = self.nodes[link.from_node].get_name();
-                let to_name = self.nodes[link.to_node].get_name();
+    /// Flags the simulated values of each extended input that are filled rather than observed
+    fn log_extended_inputs(&mut self) {
+        let (start, step, n) = (self.configuration.sim_start_timestamp, self.configuration.sim_stepsize,
+                                self.configuration.sim_nsteps);
+        for extension in &self.input_extension {
+            let Some(i) = self.find_input_idx(&extension.series) else { continue };
+            let Some((first, last)) = observed_record(&self.inputs[i].timeseries) else { continue };
+            // Simulated steps before the first observation, and after the last
+            let before = if first > start { (first - start).div_ceil(step).min(n) } else { 0 };
+            let after = if last >= start { n.saturating_sub((last - start) / step + 1) } else { n };
+            if before + after > 0 {
+                let method = extension.definition();
+                self.data_cache.log.record(LogLevel::Warning, "input_extended", &extension.series, None, || format!(
+                    "Observed from {} to {}. {} simulated steps before and {} after are filled by {}.",
+                    u64_to_iso_datetime_string(first), u64_to_iso_datetime_string(last), before, after, method));
+            }
+        }
+    }
+
+    fn find_input_idx(&self, name: &str) -> Option<usize> {
+        let name_lower = name.to_lowercase();
+        self.inputs.iter().position(|ts| name_lower == ts.full_colindex_path
+            || name_lower == ts.full_colname_path
+            || ts.alias_colindex_path.as_ref() == Some(&name_lower)
+            || ts.alias_colname_path.as_ref() == Some(&name_lower))
+    }
+
+    /// Finds loops of nodes that use each other's results from the same timestep (through
+    /// their inputs and the links between them). Each loop is an error, unless the model sets
+    /// `circular_references = previous_value`, in which case a reference on the loop is
+    /// changed to read the previous timestep's value until there are no loops left.
+    fn resolve_circular_references(&mut self) -> Result<(), String> {
+        loop {
+            let dependencies = find_dependencies(&mut self.nodes, &self.data_cache, &self.node_lookup);
+            let Some((i, cycle)) = find_cycle(self.nodes.len(), &self.links, &dependencies) else {
+                return Ok(());
+            };
+            let dependency = &dependencies[i];
+            let path = cycle.iter().map(|&idx| self.nodes[idx].get_name()).collect::<Vec<_>>().join(" → ");
+            let node_name = self.nodes[dependency.node].get_name().to_string();
+            let series_name = self.data_cache.series_name[dependency.series_idx].clone();
+
+            if self.configuration.circular_references == CircularReferences::Error {
                 return Err(format!(
-                    "Node '{}' must be defined before '{}'",
-                    from_name, to_name
+                    "Circular reference between nodes: {}. '{}' on node '{}' uses {} from the same timestep. \
+                    Use {}[-1, 0] for the previous timestep's value, or set 'circular_references = previous_value' in [kalix].",
+                    path, dependency.input, node_name, series_name, series_name
                 ));
             }
+            log::warn!("Circular reference between nodes: {}. '{}' on node '{}' uses the previous timestep's value of {}.",
+                path, dependency.input, node_name, series_name);
+
+            let (input_key, series_idx) = (dependency.input.clone(), dependency.series_idx);
+            for (key, input) in self.nodes[dependency.node].dynamic_inputs_mut() {
+                if key == input_key {
+                    input.use_previous_value(series_idx)
+                        .map_err(|e| format!("Circular reference between nodes: {}. {} in '{}' on node '{}'",
+                                             path, e, key, node_name))?;
+                }
+            }
         }
+    }
 
-        // Done
-        Ok(())
+    /// Adds series to `record_in_full`, so every value is kept when the model runs
+    pub fn record_series_in_full(&mut self, names: &[String]) {
+        for name in names {
+            if !self.record_in_full.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                self.record_in_full.push(name.clone());
+            }
+        }
+    }
+
+    /// Sets how each series is recorded for the run. Inputs, outputs and the series in
+    /// `record_in_full` are recorded in full (compressed if `compress_results` is set, otherwise
+    /// in single precision if `single_precision` is set and nothing reads them back). Other
+    /// results only exist because an expression reads them, so only the values as far back as
+    /// any expression reads are kept, unless incremental runs are enabled. Then those that
+    /// nodes read are recorded in full too, for the nodes a re-run simulates to read.
+    fn apply_recording_policy(&mut self) {
+        let n_series = self.data_cache.series.len();
+        let mut lookback = vec![0usize; n_series];
+        let mut lookahead = vec![0usize; n_series];
+        let mut read = vec![false; n_series];
+        let mut read_by_node = vec![false; n_series];
+        let mut record = |input: &DynamicInput| {
+            for (idx, depth) in input.lookback_depths() {
+                lookback[idx] = lookback[idx].max(depth);
+                read[idx] = true;
+            }
+            for (idx, depth) in input.lookahead_depths() {
+                lookahead[idx] = lookahead[idx].max(depth);
+                read[idx] = true;
+            }
+        };
+        for node in self.nodes.iter_mut() {
+            for (_, input) in node.dynamic_inputs_mut() {
+                if self.run_cache.is_enabled() {
+                    for (idx, _) in input.lookback_depths() {
+                        read_by_node[idx] = true;
+                    }
+                }
+                record(input);
+            }
+        }
+        for f in &self.functions.functions {
+            record(&f.expression);
+        }
+        for input in self.policies.expressions() {
+            record(input);
+        }
+        for account in &self.allocation.accounts {
+            record(&account.allocation);
+        }
+
+        let in_full: Vec<String> = self.outputs.iter().chain(&self.record_in_full)
+            .map(|name| name.to_lowercase())
+            .collect();
+        for (idx, depth) in lookback.into_iter().enumerate() {
+            self.data_cache.set_reads(idx, depth, lookahead[idx]);
+            let name = self.data_cache.series_name[idx].to_lowercase();
+            let recording = if name.starts_with("data.") || self.data_cache.is_critical[idx] {
+                Recording::Full
+            } else if !in_full.contains(&name) && !read_by_node[idx] {
+                Recording::Recent(depth)
+            } else if self.configuration.compress_results {
+                Recording::Compressed
+            } else if self.configuration.single_precision && !read[idx] {
+                Recording::Single
+            } else {
+                Recording::Full
+            };
+            self.data_cache.set_recording(idx, recording);
+        }
+    }
+
+    /// Resolve the execution order from a topological sort of the link graph, with each node
+    /// after any nodes whose results from the same timestep it uses.
+    fn resolve_execution_order(&mut self) -> Result<(), String> {
+        let dependencies = find_dependencies(&mut self.nodes, &self.data_cache, &self.node_lookup);
+        match topological_sort_with_dependencies(self.nodes.len(), &self.links, &dependencies) {
+            Ok(order) => {
+                self.execution_order = order;
+                Ok(())
+            }
+            Err(unordered) => {
+                let names: Vec<&str> = unordered.iter()
+                    .map(|&idx| self.nodes[idx].get_name())
+                    .collect();
+                Err(format!(
+                    "The node network contains a cycle. Could not determine an execution order for: {}",
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+
+    /// Returns the node indices in the order they are executed during the flow phase. This is
+    /// resolved when the network is initialised (i.e. at the start of each run) and is empty before then.
+    pub fn execution_order(&self) -> &[usize] {
+        &self.execution_order
     }
 
     /// Initialize all the nodes
@@ -568,6 +1221,30 @@ impl Model {
         Ok(())
     }
 
+    /// The values of each input with the `[scaling]` adjustments applied, indexed like
+    /// `self.inputs` (`None` for inputs that are not scaled). A scaling entry may name an input
+    /// by any of its references (column name or index, with or without the alias).
+    fn scaled_input_values(&self) -> Result<Vec<Option<Vec<f64>>>, String> {
+        let mut scaled: Vec<Option<Vec<f64>>> = vec![None; self.inputs.len()];
+        for scaling in &self.input_scaling {
+            let name_lower = scaling.series.to_lowercase();
+            let i = self.inputs.iter()
+                .position(|input| name_lower == input.full_colname_path
+                    || name_lower == input.full_colindex_path
+                    || input.alias_colname_path.as_ref() == Some(&name_lower)
+                    || input.alias_colindex_path.as_ref() == Some(&name_lower))
+                .ok_or(format!("Scaled series '{}' was not found in any input file.", scaling.series))?;
+
+            // Entries that name the same input are applied one after another
+            let mut ts = self.input_timeseries(i).clone();
+            if let Some(values) = scaled[i].take() {
+                ts.values = values;
+            }
+            scaled[i] = Some(scaling.apply(&ts));
+        }
+        Ok(scaled)
+    }
+
     /// Returns a reference to the node with a given ID
     pub fn get_node(&self, name: &str) -> Option<&NodeEnum> {
         for x in &self.nodes {
@@ -635,7 +1312,7 @@ impl Model {
             "sacramento", "gr4j",
             "regulated_user", "unregulated_user", "loss",
             "storage", "routing",
-            "splitter", "confluence", "gauge",
+            "splitter", "transfer", "confluence", "gauge",
             "blackhole"] {
             match report_section_dict.get(type_name) {
                 Some(s) => {
@@ -647,6 +1324,22 @@ impl Model {
             //TODO: I did this list so that I could control the order, but what if the type_name is not in this list (e.g. due to someone adding a node type).
         }
 
+        // Links that lose water, named by their upstream node and outlet
+        if self.links.iter().any(|link| link.has_attributes()) {
+            report.push_str("LINKS\n");
+            let mut lines: Vec<(String, f64)> = self.links.iter()
+                .filter(|link| link.has_attributes())
+                .map(|link| (format!("{}.ds_{}", self.nodes[link.from_node].get_name(), link.from_outlet + 1),
+                             link.mbal / (self.configuration.sim_nsteps as f64)))
+                .collect();
+            lines.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, mbal_per_timestep) in lines {
+                report.push_str(format!("  {}, {}\n", name, mbal_per_timestep).as_str());
+                total_mbal += mbal_per_timestep;
+            }
+            report.push('\n');
+        }
+
         // Write the total line
         report.push_str("----------------------------------\n");
         report.push_str(format!("TOTAL = {}\n", total_mbal).as_str());
@@ -678,13 +1371,13 @@ impl Model {
     /// simulation horizon (`sim_nsteps`). An output declared in `[outputs]` but never
     /// populated by any component (e.g. an invalid recorder) is left empty in the data cache;
     /// such series are silently omitted so that one bad recorder does not fail the whole
-    /// export. Returned in the order the outputs are declared.
-    pub(crate) fn collect_output_series(&self) -> Vec<&Timeseries> {
+    /// export. Returned in the order the outputs are declared, decompressed if need be.
+    pub(crate) fn collect_output_series(&self) -> Vec<Cow<'_, Timeseries>> {
         let expected_len = self.configuration.sim_nsteps as usize;
-        let mut vec_ts: Vec<&Timeseries> = Vec::new();
+        let mut vec_ts: Vec<Cow<'_, Timeseries>> = Vec::new();
         for output_name in &self.outputs {
             if let Some(idx) = self.data_cache.get_existing_series_idx(output_name) {
-                let ts = &self.data_cache.series[idx];
+                let ts = self.data_cache.get_series(idx);
                 if ts.timestamps.len() == expected_len {
                     vec_ts.push(ts);
                 }
@@ -695,21 +1388,168 @@ impl Model {
 
     pub fn write_outputs(&self, filename: &str) -> Result<(), String> {
 
-        let vec_ts = self.collect_output_series();
+        let output_series = self.collect_output_series();
+        let vec_ts: Vec<&Timeseries> = output_series.iter().map(|ts| ts.as_ref()).collect();
 
-        // Dispatch by extension: .pxb or .pxt → paired Pixie format,
-        // anything else → CSV.
+        // Dispatch by extension: .pxb or .pxt → paired Pixie format, .res.csv → Source
+        // results layout, .tsv or .ssv → tab or semicolon separated, anything else → CSV.
         let lower = filename.to_ascii_lowercase();
         if lower.ends_with(".pxb") || lower.ends_with(".pxt") {
             let base_path = &filename[..filename.len() - 4];
             pixie_io::write_series(base_path, &vec_ts)
                 .map_err(|e| format!("Could not write file {}: {:?}", filename, e))
+        } else if source_res_io::is_res_csv(filename) {
+            self.write_outputs_res_csv(filename, None)
         } else {
-            write_ts(filename, vec_ts)
-                .map_err(|_| format!("Could not write file {}", filename))
+            self.write_outputs_csv(filename, &CsvWriteOptions::for_path(filename)).map(|_| ())
         }
     }
 
+    /// Writes the outputs as a Source results file (see [`source_res_io`]), naming the type
+    /// of the node each output comes from. `units` gives the units of each output in order.
+    pub fn write_outputs_res_csv(&self, filename: &str, units: Option<Vec<String>>) -> Result<(), String> {
+        let output_series = self.collect_output_series();
+        let vec_ts: Vec<&Timeseries> = output_series.iter().map(|ts| ts.as_ref()).collect();
+        let feature_types = vec_ts.iter()
+            .map(|ts| {
+                let name = ts.name.strip_prefix("node.").unwrap_or("");
+                let node_name = name.split('.').next().unwrap_or("");
+                self.get_node_idx(node_name)
+                    .map_or(String::new(), |idx| self.nodes[idx].get_type_as_string())
+            })
+            .collect();
+        let project_name = Path::new(filename).file_name()
+            .map_or(String::new(), |f| f.to_string_lossy().trim_end_matches(".res.csv").to_string());
+        let options = ResCsvOptions { project_name, units, feature_types: Some(feature_types) };
+        write_res_csv(filename, &vec_ts, &options)
+            .map_err(|e| format!("Could not write file {}: {}", filename, String::from(e)))
+    }
+
+    /// Writes the outputs to a wide CSV laid out by `options`. Returns the files written, which
+    /// are one per year when `options.split_by_year` is set.
+    pub fn write_outputs_csv(&self, filename: &str, options: &CsvWriteOptions) -> Result<Vec<String>, String> {
+        let output_series = self.collect_output_series();
+        let vec_ts: Vec<&Timeseries> = output_series.iter().map(|ts| ts.as_ref()).collect();
+        write_ts_wide(filename, &vec_ts, options)
+            .map_err(|e| format!("Could not write file {}: {}", filename, String::from(e)))
+    }
+
+    /// Diagnostics raised by the last run, e.g. storages spilling or demands going unmet
+    pub fn run_log(&self) -> &RunLog {
+        &self.data_cache.log
+    }
+
+    /// Events raised by the last run. They are only recorded while `data_cache.events.enabled`
+    /// is set; the run log has a folded summary either way.
+    pub fn events(&self) -> &EventTable {
+        &self.data_cache.events
+    }
+
+    /// Writes the run log next to an output file, as `<output name without extension>.log.csv`.
+    /// Nothing is written if the last run raised no events. Returns the path written.
+    pub fn write_run_log_alongside(&self, output_filename: &str) -> Result<Option<String>, String> {
+        if self.data_cache.log.is_empty() {
+            return Ok(None);
+        }
+        let path = Path::new(output_filename).with_extension("log.csv");
+        let path = path.to_string_lossy().to_string();
+        self.data_cache.log.write_csv(&path)?;
+        Ok(Some(path))
+    }
+
+    /// Sets an optimisable parameter by its target address. Supports these address formats:
+    /// - "node.name.param" - for node parameters, e.g. rainfall-runoff parameters, a user's
+    ///   `demand_multiplier`, a loss node's `loss_scale` or a catchment's `area`
+    /// - "node.name.dimensions.row.column" - for a cell of a storage's dimensions table, with
+    ///   the row counted from 1 and the column named (level, volume, area or spill)
+    /// - "c.blah.blah.blah" - for constants, which can in turn drive any DynamicInput
+    ///   expression that references them (e.g. `demand = c.demand_factor * data.demand`)
+    pub fn set_parameter(&mut self, target: &str, value: f64) -> Result<(), String> {
+        let parts: Vec<&str> = target.split('.').collect();
+
+        if parts.len() >= 2 && parts[0] == "c" {
+            // Handle constant: "c.something"
+            self.run_cache.everything_changed();
+            self.data_cache.set_param(target, value)
+                .map_err(|e| format!("Error setting constant {}: {}", target, e))
+        } else if parts.len() >= 3 && parts[0] == "node" {
+            // Handle node parameter: "node.name.param", or a row of a storage's dimensions
+            // table: "node.name.dimensions.row.column"
+            let node_name = parts[1];
+            let param_name = parts[2..].join(".");
+            let node_idx = self.get_node_idx(node_name)
+                .ok_or_else(|| format!("Node not found: {}", node_name))?;
+
+            let result = match (&mut self.nodes[node_idx], &parts[2..]) {
+                (NodeEnum::SacramentoNode(node), ["area"]) => node.set_area(value),
+                (NodeEnum::Gr4jNode(node), ["area"]) => node.set_area(value),
+                (NodeEnum::StorageNode(node), ["dimensions", row, column]) => node.set_dimension(row, column, value),
+                (_, [_, _, ..]) => return Err(format!("Invalid target address: '{}'. Expected 'node.name.param' or 'c.constant_name'", target)),
+                (NodeEnum::SacramentoNode(node), _) => node.set_param(&param_name, value),
+                (NodeEnum::Gr4jNode(node), _) => node.set_param(&param_name, value),
+                (NodeEnum::UnregulatedUserNode(node), _) => node.set_param(&param_name, value),
+                (NodeEnum::RegulatedUserNode(node), _) => node.set_param(&param_name, value),
+                (NodeEnum::LossNode(node), _) => node.set_param(&param_name, value),
+                (other, _) => return Err(format!(
+                    "Node '{}' (type: {}) does not support parameter optimisation",
+                    node_name, other.get_type_as_string()
+                )),
+            };
+            self.run_cache.node_changed(node_idx);
+            result.map_err(|e| format!("Error setting {}.{}: {}", node_name, param_name, e))
+        } else {
+            Err(format!("Invalid target address: '{}'. Expected 'node.name.param' or 'c.constant_name'", target))
+        }
+    }
+
+    /// Gets an optimisable parameter by its target address. See [`Model::set_parameter`].
+    pub fn get_parameter(&self, target: &str) -> Result<f64, String> {
+        let parts: Vec<&str> = target.split('.').collect();
+
+        if parts.len() >= 2 && parts[0] == "c" {
+            self.data_cache.get_param(target)
+        } else if parts.len() >= 3 && parts[0] == "node" {
+            let node_name = parts[1];
+            let param_name = parts[2];
+            let node_idx = self.get_node_idx(node_name)
+                .ok_or_else(|| format!("Node not found: {}", node_name))?;
+
+            match (&self.nodes[node_idx], &parts[2..]) {
+                (NodeEnum::SacramentoNode(node), ["area"]) => Ok(node.area_km2),
+                (NodeEnum::Gr4jNode(node), ["area"]) => Ok(node.area_km2),
+                (NodeEnum::StorageNode(node), ["dimensions", row, column]) => node.get_dimension(row, column),
+                (_, [_, _, ..]) => Err(format!("Invalid target address: '{}'. Expected 'node.name.param' or 'c.constant_name'", target)),
+                (NodeEnum::SacramentoNode(node), _) => node.get_param(param_name),
+                (NodeEnum::Gr4jNode(node), _) => node.get_param(param_name),
+                (NodeEnum::UnregulatedUserNode(node), _) => node.get_param(param_name),
+                (NodeEnum::RegulatedUserNode(node), _) => node.get_param(param_name),
+                (NodeEnum::LossNode(node), _) => node.get_param(param_name),
+                (other, _) => Err(format!(
+                    "Node '{}' (type: {}) does not support parameter optimisation",
+                    node_name, other.get_type_as_string()
+                )),
+            }
+        } else {
+            Err(format!("Invalid target address: '{}'. Expected 'node.name.param' or 'c.constant_name'", target))
+        }
+    }
+
+    /// Lists the parameter names a node exposes to [`Model::set_parameter`], without the
+    /// "node.name." prefix. Nodes without optimisable parameters return an empty list.
+    pub fn list_node_parameters(&self, node_name: &str) -> Result<Vec<String>, String> {
+        let node_idx = self.get_node_idx(node_name)
+            .ok_or_else(|| format!("Node not found: {}", node_name))?;
+
+        Ok(match &self.nodes[node_idx] {
+            NodeEnum::SacramentoNode(node) => node.list_params(),
+            NodeEnum::Gr4jNode(node) => node.list_params(),
+            NodeEnum::UnregulatedUserNode(node) => node.list_params(),
+            NodeEnum::RegulatedUserNode(node) => node.list_params(),
+            NodeEnum::LossNode(node) => node.list_params(),
+            _ => vec![],
+        })
+    }
+
     /// Update a node's parameter in the attached INI document
     /// This is typically used after parameter optimisation
     pub fn update_node_parameter_in_ini(&mut self, node_name: &str, param_name: &str, value: &str) -> Result<(), String> {
@@ -722,6 +1562,30 @@ impl Model {
         }
     }
 
+    /// Applies calibrated parameter values, as `(target, value)` pairs, and writes them back
+    /// into the model's INI document, so a later save keeps the file's comments and layout.
+    /// Every parameter must already exist, as for [`Model::apply_parameters`]. If any of them
+    /// can't be applied, the model is left as it was.
+    pub fn apply_calibration(&mut self, parameters: &[(String, f64)]) -> Result<(), String> {
+        let previous = parameters.iter()
+            .map(|(target, _)| self.get_parameter(target))
+            .collect::<Result<Vec<f64>, String>>()?;
+        for (i, (target, value)) in parameters.iter().enumerate() {
+            if let Err(e) = self.set_parameter(target, *value) {
+                for ((target, _), value) in parameters[..i].iter().zip(&previous).rev() {
+                    let _ = self.set_parameter(target, *value);
+                }
+                return Err(e);
+            }
+        }
+        if self.ini_document.is_some() {
+            let io = IniModelIO::new();
+            self.ini_document = Some(io.model_to_ini_doc(self));
+            self.baseline_canonical = Some(io.model_to_canonical_ini_doc(self));
+        }
+        Ok(())
+    }
+
     /// Save the model's INI document to a file
     /// This preserves the original formatting for unchanged properties
     pub fn save_ini_to_file(&self, path: &str) -> Result<(), String> {
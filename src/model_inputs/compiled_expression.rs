@@ -0,0 +1,280 @@
+//! Expressions compiled to a flat sequence of stack operations
+//!
+//! A [`DynamicInput::Function`](super::DynamicInput) is resolved to an
+//! [`OptimizedExpressionNode`] tree when the model is read, then compiled here to postfix
+//! operations. Evaluating walks the operations once with a small fixed-size stack, so it does
+//! no allocation and no recursion (the tree evaluator collected function arguments into a
+//! new `Vec` on every call). Argument counts and stack depth are known at compile time.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::data_management::data_cache::DataCache;
+use crate::functions::ast::{evaluate_binary_op, evaluate_unary_op, FunctionRef, StatefulFunction, WindowFunction};
+use crate::functions::functions::BuiltinFunction;
+use crate::functions::operators::{BinaryOperator, UnaryOperator};
+use crate::model_inputs::dynamic_input::{OptimizedExpressionNode, SimField};
+
+/// Expressions needing a deeper stack than this use a heap-allocated one
+const STACK_SIZE: usize = 32;
+
+/// One stack operation. Operations push their result after popping their arguments.
+#[derive(Debug, Clone)]
+enum Op {
+    Constant(f64),
+    Data(usize),
+    DataWithOffset { idx: usize, offset: isize, default_value: f64 },
+    ConstantReference(usize),
+    Sim(SimField),
+    Window { func: WindowFunction, idx: usize, window: usize },
+    /// Pops the x value
+    Lookup(usize),
+    /// Pops two operands
+    Binary(BinaryOperator),
+    /// Pops one operand
+    Unary(UnaryOperator),
+    /// Pops `argc` arguments
+    Call { func: BuiltinFunction, argc: usize },
+    /// Pops the value, and the reset condition if there is one
+    Stateful { func: StatefulFunction, has_reset: bool, state: FunctionState },
+}
+
+/// An expression compiled to postfix operations with pre-resolved data cache indices
+#[derive(Debug, Clone)]
+pub struct CompiledExpression {
+    ops: Vec<Op>,
+    max_depth: usize,
+}
+
+impl CompiledExpression {
+    /// Compiles a resolved expression tree. Fails if it calls a function that is not a
+    /// built-in (those are only available when evaluating optimisation parameters).
+    pub fn compile(node: &OptimizedExpressionNode) -> Result<Self, String> {
+        let mut compiled = Self { ops: vec![], max_depth: 0 };
+        let mut depth = 0;
+        compiled.compile_node(node, &mut depth)?;
+        Ok(compiled)
+    }
+
+    /// Appends `op`, which pops `pops` values and pushes one
+    fn push_op(&mut self, op: Op, pops: usize, depth: &mut usize) {
+        *depth = *depth + 1 - pops;
+        self.max_depth = self.max_depth.max(*depth);
+        self.ops.push(op);
+    }
+
+    fn compile_node(&mut self, node: &OptimizedExpressionNode, depth: &mut usize) -> Result<(), String> {
+        match node {
+            OptimizedExpressionNode::Constant { value } => self.push_op(Op::Constant(*value), 0, depth),
+            OptimizedExpressionNode::DataCacheReference { cache_index } => self.push_op(Op::Data(*cache_index), 0, depth),
+            OptimizedExpressionNode::DataCacheReferenceWithOffset { cache_index, offset, default_value } => {
+                self.push_op(Op::DataWithOffset { idx: *cache_index, offset: *offset, default_value: *default_value }, 0, depth)
+            }
+            OptimizedExpressionNode::ConstantReference { cache_index } => self.push_op(Op::ConstantReference(*cache_index), 0, depth),
+            OptimizedExpressionNode::SimContext { field } => self.push_op(Op::Sim(*field), 0, depth),
+            OptimizedExpressionNode::WindowAggregate { func, cache_index, window } => {
+                self.push_op(Op::Window { func: *func, idx: *cache_index, window: *window }, 0, depth)
+            }
+            OptimizedExpressionNode::TableLookup { table_index, arg } => {
+                self.compile_node(arg, depth)?;
+                self.push_op(Op::Lookup(*table_index), 1, depth);
+            }
+            OptimizedExpressionNode::BinaryOp { left, op, right } => {
+                self.compile_node(left, depth)?;
+                self.compile_node(right, depth)?;
+                self.push_op(Op::Binary(*op), 2, depth);
+            }
+            OptimizedExpressionNode::UnaryOp { op, operand } => {
+                self.compile_node(operand, depth)?;
+                self.push_op(Op::Unary(*op), 1, depth);
+            }
+            OptimizedExpressionNode::FunctionCall { func, args } => {
+                let func = match func {
+                    FunctionRef::Builtin(b) => *b,
+                    FunctionRef::Named(name) => return Err(format!("Unknown function: {}", name)),
+                };
+                for arg in args {
+                    self.compile_node(arg, depth)?;
+                }
+                // Zero-argument calls still push a result (and report the arity error)
+                self.push_op(Op::Call { func, argc: args.len() }, args.len(), depth);
+            }
+            OptimizedExpressionNode::StatefulCall { func, args } => {
+                for arg in args {
+                    self.compile_node(arg, depth)?;
+                }
+                let op = Op::Stateful { func: *func, has_reset: args.len() > 1, state: FunctionState::default() };
+                self.push_op(op, args.len(), depth);
+            }
+        }
+        Ok(())
+    }
+
+    /// Data cache series read at the current timestep (rather than an earlier one)
+    pub fn current_step_references(&self) -> Vec<usize> {
+        self.current_step_series().collect()
+    }
+
+    /// Iterates the series of `current_step_references` without collecting them
+    pub fn current_step_series(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ops.iter().filter_map(|op| match op {
+            Op::Data(idx) => Some(*idx),
+            Op::DataWithOffset { idx, offset, .. } if *offset >= 0 => Some(*idx),
+            _ => None,
+        })
+    }
+
+    /// Data cache series read by the expression, each with how many timesteps back it reads
+    pub fn lookback_depths(&self) -> Vec<(usize, usize)> {
+        self.ops.iter().filter_map(|op| match op {
+            Op::Data(idx) => Some((*idx, 0)),
+            Op::DataWithOffset { idx, offset, .. } => Some((*idx, (*offset).min(0).unsigned_abs())),
+            Op::Window { idx, window, .. } => Some((*idx, *window)),
+            _ => None,
+        }).collect()
+    }
+
+    /// Data cache series read at later timesteps, each with how many timesteps ahead
+    pub fn lookahead_depths(&self) -> Vec<(usize, usize)> {
+        self.ops.iter().filter_map(|op| match op {
+            Op::DataWithOffset { idx, offset, .. } if *offset > 0 => Some((*idx, *offset as usize)),
+            _ => None,
+        }).collect()
+    }
+
+    /// Reads series `series_idx` at the previous timestep (defaulting to 0) wherever the
+    /// expression reads it at the current one. Later timesteps are left as they are.
+    pub fn use_previous_value(&mut self, series_idx: usize) {
+        for op in self.ops.iter_mut() {
+            match op {
+                Op::Data(idx) | Op::DataWithOffset { idx, offset: 0, .. } if *idx == series_idx => {
+                    *op = Op::DataWithOffset { idx: series_idx, offset: -1, default_value: 0.0 };
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Evaluates the expression for the current timestep
+    pub fn evaluate(&self, data_cache: &DataCache) -> Result<f64, String> {
+        if self.max_depth <= STACK_SIZE {
+            self.run(data_cache, &mut [0.0; STACK_SIZE])
+        } else {
+            self.run(data_cache, &mut vec![0.0; self.max_depth])
+        }
+    }
+
+    fn run(&self, data_cache: &DataCache, stack: &mut [f64]) -> Result<f64, String> {
+        let mut sp = 0;
+        for op in &self.ops {
+            let value = match op {
+                Op::Constant(value) => *value,
+                Op::Data(idx) => data_cache.get_current_value(*idx),
+                Op::DataWithOffset { idx, offset, default_value } => {
+                    data_cache.get_value_with_offset_or_default(*idx, *offset, *default_value)
+                }
+                Op::ConstantReference(idx) => data_cache.constants.get_value(*idx),
+                Op::Sim(field) => match field {
+                    SimField::Year => data_cache.get_timestamp_year() as f64,
+                    SimField::Month => data_cache.get_timestamp_month() as f64,
+                    SimField::Day => data_cache.get_timestamp_day() as f64,
+                    SimField::DayOfYear => data_cache.get_day_of_year() as f64,
+                    SimField::DaysInMonth => data_cache.get_days_in_month() as f64,
+                    SimField::IsLeapYear => data_cache.is_leap_year_now() as u8 as f64,
+                    SimField::Step => data_cache.current_step as f64,
+                },
+                Op::Window { func, idx, window } => {
+                    let window = *window as isize;
+                    func.aggregate((-window..0).map(|offset| {
+                        data_cache.get_value_with_offset_or_default(*idx, offset, f64::NAN)
+                    }))
+                }
+                Op::Lookup(table_idx) => {
+                    sp -= 1;
+                    data_cache.tables.lookup(*table_idx, stack[sp])
+                }
+                Op::Binary(op) => {
+                    sp -= 2;
+                    evaluate_binary_op(*op, stack[sp], stack[sp + 1]).map_err(|e| format!("{}", e))?
+                }
+                Op::Unary(op) => {
+                    sp -= 1;
+                    evaluate_unary_op(*op, stack[sp]).map_err(|e| format!("{}", e))?
+                }
+                Op::Call { func, argc } => {
+                    sp -= argc;
+                    func.call(&stack[sp..sp + argc]).map_err(|e| format!("Function error: {}", e))?
+                }
+                Op::Stateful { func, has_reset, state } => {
+                    let reset = if *has_reset {
+                        sp -= 1;
+                        stack[sp] != 0.0
+                    } else {
+                        false
+                    };
+                    sp -= 1;
+                    state.update(data_cache.current_step, func.increment(stack[sp]), reset)
+                }
+            };
+            stack[sp] = value;
+            sp += 1;
+        }
+        Ok(stack[0])
+    }
+}
+
+/// Running value of a stateful function such as `accumulate(...)`
+///
+/// The value before the current timestep is kept as well, so that evaluating an expression
+/// more than once in a timestep (e.g. in both the order and flow phases) gives the same
+/// result. A timestep earlier than the last one seen means a new run, which starts from zero.
+///
+/// Expressions are evaluated through shared references and nodes must be `Sync`, so the
+/// values are kept in atomics (f64s as their bits).
+#[derive(Debug)]
+pub struct FunctionState {
+    step: AtomicUsize,
+    previous: AtomicU64,
+    current: AtomicU64,
+}
+
+impl FunctionState {
+    /// Marks a state that has not been evaluated yet
+    const NO_STEP: usize = usize::MAX;
+
+    /// Adds `increment` to the running value for timestep `step`, first restarting from zero
+    /// if `reset` is true. Returns the new running value.
+    fn update(&self, step: usize, increment: f64, reset: bool) -> f64 {
+        let last_step = self.step.load(Ordering::Relaxed);
+        let previous = if last_step == step {
+            f64::from_bits(self.previous.load(Ordering::Relaxed))
+        } else if last_step < step {
+            f64::from_bits(self.current.load(Ordering::Relaxed))
+        } else {
+            0.0
+        };
+        let value = if reset { 0.0 } else { previous } + increment;
+        self.step.store(step, Ordering::Relaxed);
+        self.previous.store(previous.to_bits(), Ordering::Relaxed);
+        self.current.store(value.to_bits(), Ordering::Relaxed);
+        value
+    }
+}
+
+impl Default for FunctionState {
+    fn default() -> Self {
+        Self {
+            step: AtomicUsize::new(Self::NO_STEP),
+            previous: AtomicU64::new(0.0f64.to_bits()),
+            current: AtomicU64::new(0.0f64.to_bits()),
+        }
+    }
+}
+
+impl Clone for FunctionState {
+    fn clone(&self) -> Self {
+        Self {
+            step: AtomicUsize::new(self.step.load(Ordering::Relaxed)),
+            previous: AtomicU64::new(self.previous.load(Ordering::Relaxed)),
+            current: AtomicU64::new(self.current.load(Ordering::Relaxed)),
+        }
+    }
+}
@@ -12,6 +12,10 @@
 /// - `Constant`: Zero overhead (returns stored value)
 /// - `Function`: Minimal overhead (direct array indexing + arithmetic, no HashMap lookups)
 ///
+/// Function expressions that refer only to `data.*` inputs, constants, tables and `sim.*` are
+/// also registered in the data cache's `PrecomputedCache`. The model evaluates these over the
+/// whole simulation period before the run, after which they cost a single array lookup.
+///
 /// # Error Handling - IEEE 754 Standard
 ///
 /// Mathematical operations follow IEEE 754 floating-point standard:
@@ -24,10 +28,18 @@
 
 use std::collections::HashMap;
 use crate::data_management::data_cache::DataCache;
-use crate::functions::{parse_function, EvaluationConfig, VariableContext};
-use crate::functions::ast::{ExpressionNode, evaluate_binary_op, evaluate_unary_op};
+use crate::data_management::run_log::LogLevel;
+use crate::data_management::tables_cache::TABLE_PREFIX;
+use crate::functions::{closest_match, parse_function, EvaluationConfig, ParseError, Span, VariableContext};
+use crate::functions::ast::{ASTNode, ExpressionNode, FunctionRef, StatefulFunction, WindowFunction};
+use crate::functions::parser::model_function_names;
 use crate::functions::operators::{BinaryOperator, UnaryOperator};
+use crate::model_inputs::NanPolicy;
+use crate::model_inputs::compiled_expression::CompiledExpression;
 use crate::model_inputs::linear_combination::detect_linear_combination;
+use crate::model_inputs::user_functions::FUNCTION_PREFIX;
+use crate::model_inputs::policies::POLICY_STAGE_SERIES;
+use crate::hydrology::accounts::resource_assessment::FORECAST_PREFIX;
 use crate::misc::misc_functions::format_f64;
 
 /// Expand `this.` references in an expression to the full node reference.
@@ -63,6 +75,13 @@ fn expand_this(expression: &str, self_context: &str) -> String {
     result
 }
 
+/// True for references to values computed by the model (node results, `[functions]`, the
+/// policy stage and inflow forecasts), as opposed to input data
+fn is_model_result(lower_name: &str) -> bool {
+    lower_name.starts_with("node.") || lower_name.starts_with(FUNCTION_PREFIX) || lower_name == POLICY_STAGE_SERIES
+        || lower_name.starts_with(FORECAST_PREFIX)
+}
+
 /// Simulation context field types for the `sim.*` namespace
 ///
 /// These fields provide access to simulation date/time information within expressions.
@@ -77,23 +96,32 @@ pub enum SimField {
     Day,
     /// Day of year (1-366)
     DayOfYear,
+    /// Number of days in the current month (28-31)
+    DaysInMonth,
+    /// 1 in a leap year, otherwise 0
+    IsLeapYear,
     /// Current simulation step index (0-based)
     Step,
 }
 
+/// The `sim.*` variables and the fields they refer to
+const SIM_FIELDS: [(&str, SimField); 7] = [
+    ("sim.year", SimField::Year),
+    ("sim.month", SimField::Month),
+    ("sim.day", SimField::Day),
+    ("sim.day_of_year", SimField::DayOfYear),
+    ("sim.days_in_month", SimField::DaysInMonth),
+    ("sim.is_leap_year", SimField::IsLeapYear),
+    ("sim.step", SimField::Step),
+];
+
 /// Parse a `sim.*` variable name into a SimField
 fn parse_sim_field(name: &str) -> Option<SimField> {
-    match name {
-        "sim.year" => Some(SimField::Year),
-        "sim.month" => Some(SimField::Month),
-        "sim.day" => Some(SimField::Day),
-        "sim.day_of_year" => Some(SimField::DayOfYear),
-        "sim.step" => Some(SimField::Step),
-        _ => None,
-    }
+    SIM_FIELDS.iter().find(|(n, _)| *n == name).map(|(_, field)| *field)
 }
 
-/// Optimized AST that uses direct data cache indices instead of variable names
+/// Optimized AST that uses direct data cache indices instead of variable names. It is
+/// compiled to a [`CompiledExpression`] for evaluation.
 #[derive(Debug, Clone)]
 pub enum OptimizedExpressionNode {
     /// A constant value
@@ -115,6 +143,13 @@ pub enum OptimizedExpressionNode {
         default_value: f64
     },
 
+    /// Aggregation over the previous `window` timesteps of a data cache series
+    WindowAggregate {
+        func: WindowFunction,
+        cache_index: usize,
+        window: usize,
+    },
+
     /// Direct reference to a constant cache value by index
     ConstantReference {
         cache_index: usize
@@ -143,6 +178,18 @@ pub enum OptimizedExpressionNode {
         args: Vec<Box<OptimizedExpressionNode>>,
     },
 
+    /// Lookup in a table from the tables cache
+    TableLookup {
+        table_index: usize,
+        arg: Box<OptimizedExpressionNode>,
+    },
+
+    /// Stateful function call (the running value is kept by the compiled expression)
+    StatefulCall {
+        func: StatefulFunction,
+        args: Vec<Box<OptimizedExpressionNode>>,
+    },
+
     /// Simulation context reference (sim.* namespace)
     /// Provides access to date/time information during evaluation
     SimContext {
@@ -151,65 +198,12 @@ pub enum OptimizedExpressionNode {
 }
 
 impl OptimizedExpressionNode {
-    /// Evaluate the expression using direct data cache access
-    ///
-    /// This method provides high-performance evaluation with no HashMap lookups
-    /// or string operations - just direct array indexing and arithmetic.
-    pub fn evaluate(&self, data_cache: &DataCache) -> Result<f64, String> {
-        match self {
-            OptimizedExpressionNode::Constant { value } => Ok(*value),
-
-            OptimizedExpressionNode::DataCacheReference { cache_index } => {
-                Ok(data_cache.get_current_value(*cache_index))
-            }
-
-            OptimizedExpressionNode::DataCacheReferenceWithOffset { cache_index, offset, default_value } => {
-                Ok(data_cache.get_value_with_offset_or_default(*cache_index, *offset, *default_value))
-            }
-
-            OptimizedExpressionNode::ConstantReference { cache_index } => {
-                Ok(data_cache.constants.get_value(*cache_index))
-            }
-
-            OptimizedExpressionNode::BinaryOp { left, op, right } => {
-                let left_val = left.evaluate(data_cache)?;
-                let right_val = right.evaluate(data_cache)?;
-                evaluate_binary_op(*op, left_val, right_val)
-                    .map_err(|e| format!("{}", e))
-            }
-
-            OptimizedExpressionNode::UnaryOp { op, operand } => {
-                let val = operand.evaluate(data_cache)?;
-                evaluate_unary_op(*op, val)
-                    .map_err(|e| format!("{}", e))
-            }
-
-            OptimizedExpressionNode::FunctionCall { func, args } => {
-                let arg_values: Result<Vec<f64>, String> = args
-                    .iter()
-                    .map(|arg| arg.evaluate(data_cache))
-                    .collect();
-                let arg_values = arg_values?;
-                evaluate_function(func, &arg_values)
-            }
-
-            OptimizedExpressionNode::SimContext { field } => {
-                Ok(match field {
-                    SimField::Year => data_cache.get_timestamp_year() as f64,
-                    SimField::Month => data_cache.get_timestamp_month() as f64,
-                    SimField::Day => data_cache.get_timestamp_day() as f64,
-                    SimField::DayOfYear => data_cache.get_day_of_year() as f64,
-                    SimField::Step => data_cache.current_step as f64,
-                })
-            }
-        }
-    }
-
     /// Transform an ExpressionNode to an OptimizedExpressionNode by resolving variables to indices
     fn from_expression_node(
         node: &ExpressionNode,
         data_variable_map: &HashMap<String, usize>,
-        constant_variable_map: &HashMap<String, usize>
+        constant_variable_map: &HashMap<String, usize>,
+        table_map: &HashMap<String, usize>
     ) -> Result<Self, String> {
         match node {
             ExpressionNode::Constant { value } => {
@@ -248,8 +242,8 @@ impl OptimizedExpressionNode {
                     return Err(format!("Offset syntax not supported for simulation context: {}", name));
                 }
 
-                // Node outputs cannot look forward - future values haven't been computed
-                if lower_name.starts_with("node.") && *offset > 0 {
+                // Node outputs and functions cannot look forward - future values haven't been computed
+                if is_model_result(&lower_name) && *offset > 0 {
                     return Err(format!("Forward lookup not supported for node outputs: {}", name));
                 }
 
@@ -268,6 +262,12 @@ impl OptimizedExpressionNode {
                 }
                 Err(format!("Variable '{}' not found in variable maps", name))
             }
+            ExpressionNode::WindowAggregate { func, name, window } => {
+                match data_variable_map.get(&name.to_lowercase()) {
+                    Some(&idx) => Ok(OptimizedExpressionNode::WindowAggregate { func: *func, cache_index: idx, window: *window }),
+                    None => Err(format!("Variable '{}' not found in variable maps", name)),
+                }
+            }
             ExpressionNode::BinaryOp { left, op, right } => {
                 // Need to downcast the boxed ASTNode children to ExpressionNode
                 let left_expr = (left.as_ref() as &dyn std::any::Any)
@@ -277,8 +277,8 @@ impl OptimizedExpressionNode {
                     .downcast_ref::<ExpressionNode>()
                     .ok_or("Failed to downcast right operand")?;
 
-                let left_opt = Self::from_expression_node(left_expr, data_variable_map, constant_variable_map)?;
-                let right_opt = Self::from_expression_node(right_expr, data_variable_map, constant_variable_map)?;
+                let left_opt = Self::from_expression_node(left_expr, data_variable_map, constant_variable_map, table_map)?;
+                let right_opt = Self::from_expression_node(right_expr, data_variable_map, constant_variable_map, table_map)?;
 
                 Ok(OptimizedExpressionNode::BinaryOp {
                     left: Box::new(left_opt),
@@ -291,7 +291,7 @@ impl OptimizedExpressionNode {
                     .downcast_ref::<ExpressionNode>()
                     .ok_or("Failed to downcast operand")?;
 
-                let operand_opt = Self::from_expression_node(operand_expr, data_variable_map, constant_variable_map)?;
+                let operand_opt = Self::from_expression_node(operand_expr, data_variable_map, constant_variable_map, table_map)?;
 
                 Ok(OptimizedExpressionNode::UnaryOp {
                     op: *op,
@@ -305,7 +305,7 @@ impl OptimizedExpressionNode {
                         let arg_expr = (arg.as_ref() as &dyn std::any::Any)
                             .downcast_ref::<ExpressionNode>()
                             .ok_or("Failed to downcast function argument")?;
-                        Self::from_expression_node(arg_expr, data_variable_map, constant_variable_map)
+                        Self::from_expression_node(arg_expr, data_variable_map, constant_variable_map, table_map)
                     })
                     .collect();
                 let args_opt = args_opt?;
@@ -315,6 +315,32 @@ impl OptimizedExpressionNode {
                     args: args_opt.into_iter().map(Box::new).collect(),
                 })
             }
+            ExpressionNode::TableLookup { table, arg } => {
+                let arg_expr = (arg.as_ref() as &dyn std::any::Any)
+                    .downcast_ref::<ExpressionNode>()
+                    .ok_or("Failed to downcast function argument")?;
+                let arg_opt = Self::from_expression_node(arg_expr, data_variable_map, constant_variable_map, table_map)?;
+                match table_map.get(table) {
+                    Some(&idx) => Ok(OptimizedExpressionNode::TableLookup { table_index: idx, arg: Box::new(arg_opt) }),
+                    None => Err(format!("Table '{}' not found in table map", table)),
+                }
+            }
+            ExpressionNode::StatefulCall { func, args } => {
+                let args_opt: Result<Vec<_>, String> = args
+                    .iter()
+                    .map(|arg| {
+                        let arg_expr = (arg.as_ref() as &dyn std::any::Any)
+                            .downcast_ref::<ExpressionNode>()
+                            .ok_or("Failed to downcast function argument")?;
+                        Self::from_expression_node(arg_expr, data_variable_map, constant_variable_map, table_map)
+                    })
+                    .collect();
+
+                Ok(OptimizedExpressionNode::StatefulCall {
+                    func: *func,
+                    args: args_opt?.into_iter().map(Box::new).collect(),
+                })
+            }
         }
     }
 }
@@ -387,10 +413,12 @@ pub enum DynamicInput {
         original: String,
     },
 
-    /// Function expression (optimised for performance)
+    /// Function expression (compiled to stack operations for performance)
     Function {
         expression: String,  // Original expression for error messages and serialization
-        optimised_ast: OptimizedExpressionNode
+        compiled: CompiledExpression,
+        /// Index into the data cache's precomputed expressions, if it only depends on inputs
+        precomputed: Option<usize>
     },
 }
 
@@ -414,8 +442,17 @@ impl DynamicInput {
     ///
     /// # Returns
     ///
-    /// A DynamicInput that needs initialization, or an error if parsing fails
+    /// A DynamicInput that needs initialization, or an error if parsing fails. Errors in
+    /// the expression are followed by the expression with the offending part underlined.
     pub fn from_string(expression: &str, data_cache: &mut DataCache, flag_as_critical: bool, self_context: Option<&str>) -> Result<Self, String> {
+        Self::parse(expression, data_cache, flag_as_critical, self_context)
+            .map_err(|e| e.render(expression.trim()))
+    }
+
+    /// Create a DynamicInput from a string expression, as for `from_string()`, but return
+    /// the error itself so callers can report where in the expression it is. Positions are
+    /// characters of the trimmed expression, before any `this.` is expanded.
+    pub fn parse(expression: &str, data_cache: &mut DataCache, flag_as_critical: bool, self_context: Option<&str>) -> Result<Self, ParseError> {
         let trimmed = expression.trim();
 
         if trimmed.is_empty() {
@@ -430,9 +467,13 @@ impl DynamicInput {
             None => trimmed.to_string(),
         };
 
-        // Parse the expression (using the expanded form)
-        let parsed = parse_function(&working_copy)
-            .map_err(|e| format!("Failed to parse expression '{}': {}", trimmed, e))?;
+        // Parse the expression as written, so error positions refer to it, and then again in
+        // the expanded form (expansion only renames variables, so it won't introduce errors)
+        let mut parsed = parse_function(trimmed)?;
+        if working_copy != trimmed {
+            parsed = parse_function(&working_copy)?;
+        }
+        check_names(&parsed, trimmed)?;
 
         // Check if it's a linear combination pattern first
         let ast = parsed.get_ast();
@@ -444,8 +485,8 @@ impl DynamicInput {
                 // Resolve variable names to data cache indices
                 for var_name in &linear_info.variables {
                     let lower_name = var_name.to_lowercase();
-                    // node.* references are not critical inputs (they're outputs from other nodes)
-                    let is_critical = flag_as_critical && !lower_name.starts_with("node.");
+                    // node.* and f.* references are not critical inputs (they're computed by the model)
+                    let is_critical = flag_as_critical && !is_model_result(&lower_name);
                     let idx = data_cache.get_or_add_new_series(&lower_name, is_critical);
                     data_indices.push(idx);
                 }
@@ -483,6 +524,7 @@ impl DynamicInput {
         // and avoid duplicate entries for the same variable with different cases
         let mut data_variable_map = HashMap::new();
         let mut constant_variable_map = HashMap::new();
+        let mut table_map = HashMap::new();
 
         for var_name in variables.iter() {
             let lower_name = var_name.to_lowercase();
@@ -491,12 +533,16 @@ impl DynamicInput {
                 // Simulation context variables - no cache lookup needed
                 // They are resolved directly in from_expression_node via parse_sim_field
                 continue;
+            } else if lower_name.starts_with(TABLE_PREFIX) {
+                // Resolve to tables cache (only used by lookup(), so never a series)
+                let idx = data_cache.tables.add_if_needed_and_get_idx(&lower_name);
+                table_map.insert(lower_name.clone(), idx);
             } else if lower_name.starts_with("c.") {
                 // Resolve to constants cache
                 let idx = data_cache.constants.add_if_needed_and_get_idx(&lower_name);
                 constant_variable_map.insert(lower_name.clone(), idx);
-            } else if lower_name.starts_with("node.") {
-                // Resolve to data cache but NOT as critical input (node outputs don't determine simulation period)
+            } else if is_model_result(&lower_name) {
+                // Resolve to data cache but NOT as critical input (node outputs and functions don't determine simulation period)
                 let idx = data_cache.get_or_add_new_series(lower_name.as_str(), false);
                 data_variable_map.insert(lower_name.clone(), idx);
             } else {
@@ -507,14 +553,14 @@ impl DynamicInput {
         }
 
         // Optimize based on expression type
-        if variables.is_empty() {
+        if variables.is_empty() && !parsed.is_stateful() {
             // No variables -> constant expression
             // Evaluate once and store the value
             let config = EvaluationConfig::default();
             let empty_vars = HashMap::new();
             let context = VariableContext::new(&empty_vars, &config);
             let value = parsed.evaluate(&context)
-                .map_err(|e| format!("Failed to evaluate constant expression: {}", e))?;
+                .map_err(|e| invalid(format!("Failed to evaluate constant expression: {}", e)))?;
 
             Ok(DynamicInput::Constant {
                 value,
@@ -527,17 +573,17 @@ impl DynamicInput {
 
             // Constants don't support offset
             if lower_var.starts_with("c.") {
-                return Err(format!("Offset syntax not supported for constants: {}", var_name));
+                return Err(invalid(format!("Offset syntax not supported for constants: {}", var_name)));
             }
 
             // Simulation context variables don't support offset
             if lower_var.starts_with("sim.") {
-                return Err(format!("Offset syntax not supported for simulation context: {}", var_name));
+                return Err(invalid(format!("Offset syntax not supported for simulation context: {}", var_name)));
             }
 
-            // Node outputs cannot look forward
-            if lower_var.starts_with("node.") && offset > 0 {
-                return Err(format!("Forward lookup not supported for node outputs: {}", var_name));
+            // Node outputs and functions cannot look forward
+            if is_model_result(&lower_var) && offset > 0 {
+                return Err(invalid(format!("Forward lookup not supported for node outputs: {}", var_name)));
             }
 
             if let Some(&idx) = data_variable_map.get(&lower_var) {
@@ -556,7 +602,7 @@ impl DynamicInput {
                     })
                 }
             } else {
-                Err(format!("Variable '{}' not found in variable maps", var_name))
+                Err(invalid(format!("Variable '{}' not found in variable maps", var_name)))
             }
         } else if let Some(var_name) = parsed.is_single_variable() {
             // It's a direct reference to a single variable (no operations, no offset)
@@ -565,11 +611,8 @@ impl DynamicInput {
 
             // sim.* variables need to go through the Function path
             if lower_var.starts_with("sim.") {
-                let optimised_ast = transform_to_optimised_ast(&parsed, &data_variable_map, &constant_variable_map)?;
-                Ok(DynamicInput::Function {
-                    expression: trimmed.to_string(),
-                    optimised_ast
-                })
+                let compiled = compile_expression(&parsed, &data_variable_map, &constant_variable_map, &table_map).map_err(invalid)?;
+                Ok(new_function(trimmed, &working_copy, compiled, &data_variable_map, data_cache))
             } else if let Some(&idx) = constant_variable_map.get(&lower_var) {
                 Ok(DynamicInput::DirectConstantReference {
                     idx,
@@ -581,15 +624,12 @@ impl DynamicInput {
                     original: trimmed.to_string()
                 })
             } else {
-                Err(format!("Variable '{}' not found in variable maps", var_name))
+                Err(invalid(format!("Variable '{}' not found in variable maps", var_name)))
             }
         } else {
             // Multiple variables or complex expression -> function expression
-            let optimised_ast = transform_to_optimised_ast(&parsed, &data_variable_map, &constant_variable_map)?;
-            Ok(DynamicInput::Function {
-                expression: trimmed.to_string(),
-                optimised_ast
-            })
+            let compiled = compile_expression(&parsed, &data_variable_map, &constant_variable_map, &table_map).map_err(invalid)?;
+            Ok(new_function(trimmed, &working_copy, compiled, &data_variable_map, data_cache))
         }
     }
 
@@ -631,15 +671,118 @@ impl DynamicInput {
                     .map(|(&idx, &weight)| data_cache.get_current_value(idx) * weight)
                     .sum()
             }
-            DynamicInput::Function { expression, optimised_ast } => {
-                optimised_ast.evaluate(data_cache).unwrap_or_else(|e| {
-                    eprintln!("ERROR: Critical evaluation failure in expression '{}': {}. Returning 0.0. This indicates a parser bug.", expression, e);
+            DynamicInput::Function { expression, compiled, precomputed } => {
+                if let Some(value) = precomputed.and_then(|idx| data_cache.precomputed.get_value(idx, data_cache.current_step)) {
+                    return value;
+                }
+                compiled.evaluate(data_cache).unwrap_or_else(|e| {
+                    log::error!("Critical evaluation failure in expression '{}': {}. Returning 0.0. This indicates a parser bug.", expression, e);
                     0.0
                 })
             }
         }
     }
 
+    /// Evaluates the input as [`get_value`](Self::get_value) does, with a missing value handled
+    /// by the NaN policy of the series it reads: passed on, replaced by 0, or stopping the run
+    /// (by panicking, which the model reports with the node and date). Missing values are
+    /// logged against `node`, with `label` naming the input, e.g. `Rain`.
+    pub fn get_value_or_policy(&self, data_cache: &mut DataCache, node: &str, label: &str) -> f64 {
+        let value = self.get_value(data_cache);
+        if !value.is_nan() {
+            return value;
+        }
+        match self.nan_policy(data_cache) {
+            NanPolicy::Propagate => {
+                data_cache.log_event(LogLevel::Warning, "input_nan", node,
+                    || format!("{} input is missing; downstream flows will be missing too", label));
+                value
+            }
+            NanPolicy::Zero => {
+                data_cache.log_event(LogLevel::Warning, "input_nan_substituted", node,
+                    || format!("{} input is missing; 0 was used instead", label));
+                0.0
+            }
+            NanPolicy::Error => panic!("{} input '{}' is missing and its nan_policy is 'error'", label, self.original_string()),
+        }
+    }
+
+    /// The NaN policy of the series this input reads at the current timestep. Unlike
+    /// `current_step_references` it doesn't allocate, as it is called inside the run.
+    fn nan_policy(&self, data_cache: &DataCache) -> NanPolicy {
+        match self {
+            DynamicInput::DirectReference { idx, .. } => data_cache.nan_policy_of([*idx]),
+            DynamicInput::DirectReferenceWithOffset { idx, offset, .. } if *offset >= 0 => data_cache.nan_policy_of([*idx]),
+            DynamicInput::LinearCombination { data_indices, .. } => data_cache.nan_policy_of(data_indices.iter().copied()),
+            DynamicInput::Function { compiled, .. } => data_cache.nan_policy_of(compiled.current_step_series()),
+            _ => data_cache.nan_policy_of([]),
+        }
+    }
+
+    /// Whether this is a function expression that is evaluated before the run rather than at
+    /// every timestep
+    pub fn is_precomputed(&self) -> bool {
+        matches!(self, DynamicInput::Function { precomputed: Some(_), .. })
+    }
+
+    /// Data cache series that this input reads at the current timestep
+    pub fn current_step_references(&self) -> Vec<usize> {
+        match self {
+            DynamicInput::DirectReference { idx, .. } => vec![*idx],
+            DynamicInput::DirectReferenceWithOffset { idx, offset, .. } if *offset >= 0 => vec![*idx],
+            DynamicInput::LinearCombination { data_indices, .. } => data_indices.clone(),
+            DynamicInput::Function { compiled, .. } => compiled.current_step_references(),
+            _ => vec![],
+        }
+    }
+
+    /// Data cache series that this input reads, each with how many timesteps back it reads
+    pub fn lookback_depths(&self) -> Vec<(usize, usize)> {
+        match self {
+            DynamicInput::DirectReference { idx, .. } => vec![(*idx, 0)],
+            DynamicInput::DirectReferenceWithOffset { idx, offset, .. } => vec![(*idx, (*offset).min(0).unsigned_abs())],
+            DynamicInput::LinearCombination { data_indices, .. } => data_indices.iter().map(|&idx| (idx, 0)).collect(),
+            DynamicInput::Function { compiled, .. } => compiled.lookback_depths(),
+            _ => vec![],
+        }
+    }
+
+    /// Data cache series the input reads at later timesteps, each with how many timesteps
+    /// ahead it reads
+    pub fn lookahead_depths(&self) -> Vec<(usize, usize)> {
+        match self {
+            DynamicInput::DirectReferenceWithOffset { idx, offset, .. } if *offset > 0 => vec![(*idx, *offset as usize)],
+            DynamicInput::Function { compiled, .. } => compiled.lookahead_depths(),
+            _ => vec![],
+        }
+    }
+
+    /// Reads series `series_idx` at the previous timestep instead of the current one, as if
+    /// it had been written `[-1, 0]`. The expression string is left as the user wrote it.
+    /// Fails if the input can't be changed this way (a linear combination, or a reference to
+    /// a later timestep).
+    pub fn use_previous_value(&mut self, series_idx: usize) -> Result<(), String> {
+        match self {
+            DynamicInput::DirectReference { idx, original } if *idx == series_idx => {
+                *self = DynamicInput::DirectReferenceWithOffset {
+                    idx: series_idx,
+                    offset: -1,
+                    default_value: 0.0,
+                    original: std::mem::take(original),
+                };
+            }
+            DynamicInput::DirectReferenceWithOffset { idx, offset, .. } if *idx == series_idx && *offset == 0 => {
+                *offset = -1;
+            }
+            DynamicInput::Function { compiled, .. } => compiled.use_previous_value(series_idx),
+            _ => {}
+        }
+        if self.current_step_references().contains(&series_idx) {
+            return Err(format!("'{}' can't be changed to use the previous timestep's value", self.original_string()));
+        }
+        Ok(())
+    }
+
     /// Get the expression string for serialization
     /// For LinearCombination, this returns the optimized expression with current weights
     pub fn to_string(&self) -> String {
@@ -687,37 +830,103 @@ impl DynamicInput {
     }
 }
 
-/// Transform a ParsedFunction to an OptimizedExpressionNode
-fn transform_to_optimised_ast(
+/// Wraps a compiled expression as a `DynamicInput::Function`, registering it for precomputation
+/// if it depends only on input data (not on node results or functions, which are only known
+/// during the run)
+fn new_function(
+    expression: &str,
+    expanded: &str,
+    compiled: CompiledExpression,
+    data_variable_map: &HashMap<String, usize>,
+    data_cache: &mut DataCache
+) -> DynamicInput {
+    let precomputed = if data_variable_map.keys().all(|name| name.starts_with("data.")) {
+        Some(data_cache.precomputed.add_if_needed_and_get_idx(expanded, &compiled))
+    } else {
+        None
+    };
+    DynamicInput::Function {
+        expression: expression.to_string(),
+        compiled,
+        precomputed
+    }
+}
+
+/// Wraps a message about an expression that is well-formed but can't be used
+fn invalid(message: String) -> ParseError {
+    ParseError::InvalidExpression { message }
+}
+
+/// Checks that the functions and `sim.*` variables an expression uses exist. Unlike other
+/// variables these are a fixed set, so a misspelling can be caught (and a correction
+/// suggested) as soon as the expression is read.
+fn check_names(parsed: &crate::functions::parser::ParsedFunction, expression: &str) -> Result<(), ParseError> {
+    for variable in parsed.get_variables() {
+        let lower_name = variable.to_lowercase();
+        if lower_name.starts_with("sim.") && parse_sim_field(&lower_name).is_none() {
+            return Err(ParseError::UnknownVariable {
+                name: variable.clone(),
+                span: find_name(expression, variable),
+                suggestion: closest_match(&lower_name, SIM_FIELDS.iter().map(|(name, _)| *name)).map(String::from),
+            });
+        }
+    }
+
+    fn find_unknown_function(node: &dyn ASTNode) -> Option<String> {
+        let children: Vec<&dyn ASTNode> = match (node as &dyn std::any::Any).downcast_ref::<ExpressionNode>()? {
+            ExpressionNode::FunctionCall { func: FunctionRef::Named(name), .. } => return Some(name.clone()),
+            ExpressionNode::FunctionCall { args, .. } | ExpressionNode::StatefulCall { args, .. } => {
+                args.iter().map(|a| a.as_ref()).collect()
+            }
+            ExpressionNode::BinaryOp { left, right, .. } => vec![left.as_ref(), right.as_ref()],
+            ExpressionNode::UnaryOp { operand, .. } => vec![operand.as_ref()],
+            ExpressionNode::TableLookup { arg, .. } => vec![arg.as_ref()],
+            _ => vec![],
+        };
+        children.into_iter().find_map(find_unknown_function)
+    }
+    if let Some(name) = find_unknown_function(parsed.get_ast()) {
+        return Err(ParseError::UnknownFunction {
+            span: find_name(expression, &name),
+            suggestion: closest_match(&name, model_function_names()).map(String::from),
+            name,
+        });
+    }
+    Ok(())
+}
+
+/// Finds where a variable or function name first appears in an expression (ignoring case
+/// and only matching whole names). Falls back to the whole expression if it isn't found.
+fn find_name(expression: &str, name: &str) -> Span {
+    let chars: Vec<char> = expression.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let is_name_char = |c: &char| c.is_alphanumeric() || *c == '_' || *c == '.';
+    for start in 0..chars.len() {
+        let end = start + name.len();
+        if end <= chars.len()
+            && chars[start..end].iter().zip(name.iter()).all(|(a, b)| a.eq_ignore_ascii_case(b))
+            && (start == 0 || !is_name_char(&chars[start - 1]))
+            && chars.get(end).is_none_or(|c| !is_name_char(c)) {
+            return Span::new(start, end);
+        }
+    }
+    Span::new(0, chars.len())
+}
+
+/// Resolve a ParsedFunction to an OptimizedExpressionNode and compile it
+fn compile_expression(
     parsed: &crate::functions::parser::ParsedFunction,
     data_variable_map: &HashMap<String, usize>,
-    constant_variable_map: &HashMap<String, usize>
-) -> Result<OptimizedExpressionNode, String> {
+    constant_variable_map: &HashMap<String, usize>,
+    table_map: &HashMap<String, usize>
+) -> Result<CompiledExpression, String> {
     let ast = parsed.get_ast();
 
     // Downcast to ExpressionNode
     if let Some(expr_node) = (ast as &dyn std::any::Any).downcast_ref::<ExpressionNode>() {
-        OptimizedExpressionNode::from_expression_node(expr_node, data_variable_map, constant_variable_map)
+        let optimised_ast = OptimizedExpressionNode::from_expression_node(expr_node, data_variable_map, constant_variable_map, table_map)?;
+        CompiledExpression::compile(&optimised_ast)
     } else {
         Err("Failed to downcast AST node".to_string())
     }
 }
-
-// Function evaluation. The simulation hot path: dispatches via the parse-time-resolved
-// FunctionRef. Built-in calls hit an enum jump table directly; Named falls back to an
-// error here (model evaluation has no FunctionRegistry — context functions like
-// `lin_range`/`log_range`/`g` are only meaningful inside the optimisation parameter path).
-fn evaluate_function(
-    func: &crate::functions::ast::FunctionRef,
-    args: &[f64],
-) -> Result<f64, String> {
-    use crate::functions::ast::FunctionRef;
-    match func {
-        FunctionRef::Builtin(b) => b.call(args).map_err(|e| format!("Function error: {}", e)),
-        FunctionRef::Named(name) => Err(format!(
-            "Unknown function: {} (no context function registry available here)",
-            name
-        )),
-    }
-}
-
@@ -0,0 +1,97 @@
+//! What happens when an input file does not cover the whole simulation period
+//!
+//! The policy for each input file is given in the `[inputs]` section, by alias or file name:
+//!
+//! ```ini
+//! [inputs]
+//! climate = ./climate.csv
+//! ./diversions.csv
+//! coverage.climate = error
+//! coverage.diversions_csv = fill(0)
+//! ```
+//!
+//! - `clip` (the default): the simulation period is clipped to where the file's data that the
+//!   model uses is present, including a `start` and `end` given in `[kalix]`
+//! - `error`: the file's data must be present over the whole simulation period, otherwise the
+//!   model does not configure
+//! - `fill(VALUE)` or `fill(nan)`: the file does not limit the simulation period, and its
+//!   missing values are replaced by VALUE (or left missing)
+//!
+//! The period that results, and the inputs or dates that set each end of it, are reported as
+//! the model's [`SimulationWindow`].
+
+use crate::tid::utils::u64_to_date_string_for_step_size;
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Coverage {
+    #[default]
+    Clip,
+    Error,
+    /// Missing values are replaced by the value (NaN to leave them missing)
+    Fill(f64),
+}
+
+impl Coverage {
+    pub fn from_name(name: &str) -> Result<Coverage, String> {
+        let lower = name.trim().to_lowercase();
+        let fill = lower.strip_prefix("fill(").and_then(|v| v.strip_suffix(')')).map(str::trim);
+        match (lower.as_str(), fill) {
+            ("clip", _) => Ok(Coverage::Clip),
+            ("error", _) => Ok(Coverage::Error),
+            (_, Some("nan")) => Ok(Coverage::Fill(f64::NAN)),
+            (_, Some(v)) => v.parse::<f64>().ok().filter(|v| v.is_finite()).map(Coverage::Fill)
+                .ok_or(format!("Invalid fill value '{}'. Expected a number or nan", v)),
+            _ => Err(format!("Unknown coverage option '{}'. Expected one of: clip, error, fill(VALUE), fill(nan)", name.trim())),
+        }
+    }
+
+    /// The option as written in the model file
+    pub fn definition(&self) -> String {
+        match self {
+            Coverage::Clip => "clip".to_string(),
+            Coverage::Error => "error".to_string(),
+            Coverage::Fill(v) if v.is_nan() => "fill(nan)".to_string(),
+            Coverage::Fill(v) => format!("fill({})", v),
+        }
+    }
+}
+
+/// The coverage policy for one input file, from a `coverage.<source> = <option>` property
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputCoverage {
+    /// The file's alias or sanitized file name, e.g. `climate` or `diversions_csv`
+    pub source: String,
+    pub coverage: Coverage,
+}
+
+/// The simulation period worked out when the model is configured, and what set each end of it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationWindow {
+    pub start: u64,
+    pub end: u64,
+    pub step_size: u64,
+    /// The inputs (`data.*` references) whose data starts or ends there, or `start` or `end`
+    /// if the date given in `[kalix]` set it
+    pub start_set_by: Vec<String>,
+    pub end_set_by: Vec<String>,
+    /// Inputs clipped to the period, that did not cover a `start` or `end` given in `[kalix]`
+    pub clipped: Vec<String>,
+}
+
+impl SimulationWindow {
+    /// One-line description, e.g. for the run log
+    pub fn describe(&self) -> String {
+        let set_by = |names: &[String]| match names.is_empty() {
+            true => "the inputs".to_string(),
+            false => names.join(", "),
+        };
+        let mut text = format!("Simulation period {} to {}. The start is set by {} and the end by {}.",
+                               u64_to_date_string_for_step_size(self.start, self.step_size),
+                               u64_to_date_string_for_step_size(self.end, self.step_size),
+                               set_by(&self.start_set_by), set_by(&self.end_set_by));
+        if !self.clipped.is_empty() {
+            text.push_str(&format!(" The period given in [kalix] was clipped to the data of {}.", self.clipped.join(", ")));
+        }
+        text
+    }
+}
@@ -0,0 +1,186 @@
+//! Extending short input series to cover the simulation period, from the `[extension]` section
+//!
+//! ```ini
+//! [extension]
+//! data.flows_csv.by_name.tributary = monthly_mean
+//! data.flows_csv.by_name.spring = regression data.flows_csv.by_name.main_river
+//! ```
+//!
+//! The simulation period is where every input that the model uses has data, so a short input
+//! would otherwise truncate it. An extended series keeps its observed record (from its first
+//! value to its last, gaps included) and is filled before and after it:
+//!
+//! - `monthly_mean`: with the mean of the observed values in the same calendar month, out to
+//!   the earliest and latest data of any input
+//! - `regression SERIES`: with a linear fit on another input series, wherever that series has
+//!   data. A fit is made for each calendar month with at least 3 pairs of values, and a fit to
+//!   all pairs is used for the other months. Fitted values are kept at or above 0 unless the
+//!   observed record has negative values.
+//!
+//! Filled values are flagged in the run log with the code `input_extended`.
+
+use crate::tid::utils::u64_to_year_month_day_and_seconds;
+use crate::timeseries::Timeseries;
+
+const MIN_PAIRS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtensionMethod {
+    MonthlyMean,
+    /// Linear fit on the input series named as written in the model file
+    Regression(String),
+}
+
+/// How one input series is extended
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputExtension {
+    /// Data reference as written in the model file, e.g. `data.flows.by_name.tributary`
+    pub series: String,
+    pub method: ExtensionMethod,
+}
+
+/// Linear fit `y = a + b x`, or None without enough pairs of values that vary
+fn fit(pairs: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if pairs.len() < MIN_PAIRS {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = pairs.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = pairs.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    if sxx <= 0.0 {
+        return None;
+    }
+    let b = sxy / sxx;
+    Some((mean_y - b * mean_x, b))
+}
+
+fn month_of(timestamp: u64) -> usize {
+    u64_to_year_month_day_and_seconds(timestamp).1 as usize
+}
+
+/// The value of `ts` at `timestamp`, or NaN off its record
+fn value_at(ts: &Timeseries, timestamp: u64) -> f64 {
+    if timestamp < ts.start_timestamp || !(timestamp - ts.start_timestamp).is_multiple_of(ts.step_size) {
+        return f64::NAN;
+    }
+    let i = ((timestamp - ts.start_timestamp) / ts.step_size) as usize;
+    ts.values.get(i).copied().unwrap_or(f64::NAN)
+}
+
+/// Timestamps of the first and last finite values of `ts`
+pub fn observed_record(ts: &Timeseries) -> Option<(u64, u64)> {
+    let first = ts.values.iter().position(|v| v.is_finite())?;
+    let last = ts.values.iter().rposition(|v| v.is_finite())?;
+    let timestamp = |i: usize| ts.start_timestamp + i as u64 * ts.step_size;
+    Some((timestamp(first), timestamp(last)))
+}
+
+impl InputExtension {
+    /// Parses a definition such as `monthly_mean` or `regression data.flows.by_name.main`
+    pub fn parse(series: &str, definition: &str) -> Result<InputExtension, String> {
+        let mut parts = definition.split_whitespace();
+        let method = match (parts.next().map(|m| m.to_lowercase()).as_deref(), parts.next(), parts.next()) {
+            (Some("monthly_mean"), None, _) => ExtensionMethod::MonthlyMean,
+            (Some("regression"), Some(predictor), None) if predictor.to_lowercase().starts_with("data.") => {
+                ExtensionMethod::Regression(predictor.to_string())
+            }
+            _ => return Err(format!("Invalid extension '{}' for '{}'. Expected 'monthly_mean' or 'regression data.*'",
+                                    definition.trim(), series)),
+        };
+        Ok(InputExtension { series: series.to_string(), method })
+    }
+
+    /// The definition as written back to the model file
+    pub fn definition(&self) -> String {
+        match &self.method {
+            ExtensionMethod::MonthlyMean => "monthly_mean".to_string(),
+            ExtensionMethod::Regression(predictor) => format!("regression {}", predictor),
+        }
+    }
+
+    /// Returns `ts` extended before and after its observed record, to cover `span` (first and
+    /// last timestamps of the data of every input) for the monthly mean method, or the record
+    /// of `predictor` for the regression method
+    pub fn apply(&self, ts: &Timeseries, span: (u64, u64), predictor: Option<&Timeseries>) -> Result<Timeseries, String> {
+        let step = ts.step_size;
+        let (first, last) = observed_record(ts)
+            .ok_or(format!("Extended series '{}' has no values", self.series))?;
+
+        // Intercept and slope of the fill in each calendar month (Jan=1): the mean and 0 for the
+        // monthly mean method, or the fit on the predictor
+        let mut by_month = [(0.0, 0.0); 13];
+        let mut fill_span = span;
+        match (&self.method, predictor) {
+            (ExtensionMethod::MonthlyMean, _) => {
+                let mut sums = [(0.0, 0usize); 13];
+                for (i, v) in ts.values.iter().enumerate().filter(|(_, v)| v.is_finite()) {
+                    let m = month_of(ts.start_timestamp + i as u64 * step);
+                    sums[m] = (sums[m].0 + v, sums[m].1 + 1);
+                }
+                for m in 1..=12 {
+                    if sums[m].1 == 0 {
+                        return Err(format!("Extended series '{}' has no values in month {}, so it has no monthly mean", self.series, m));
+                    }
+                    by_month[m] = (sums[m].0 / sums[m].1 as f64, 0.0);
+                }
+            }
+            (ExtensionMethod::Regression(name), Some(x)) => {
+                if x.step_size != step || !x.start_timestamp.abs_diff(ts.start_timestamp).is_multiple_of(step) {
+                    return Err(format!("Extended series '{}' and '{}' must have the same timesteps", self.series, name));
+                }
+                let mut pairs: Vec<Vec<(f64, f64)>> = vec![vec![]; 13];
+                for (i, &y) in ts.values.iter().enumerate().filter(|(_, y)| y.is_finite()) {
+                    let t = ts.start_timestamp + i as u64 * step;
+                    let x = value_at(x, t);
+                    if x.is_finite() {
+                        pairs[month_of(t)].push((x, y));
+                    }
+                }
+                let all: Vec<(f64, f64)> = pairs.concat();
+                let overall = fit(&all)
+                    .ok_or(format!("Extended series '{}' needs at least {} values overlapping '{}', and they must vary",
+                                   self.series, MIN_PAIRS, name))?;
+                for m in 1..=12 {
+                    by_month[m] = fit(&pairs[m]).unwrap_or(overall);
+                }
+                fill_span = observed_record(x).ok_or(format!("Series '{}' has no values", name))?;
+            }
+            (ExtensionMethod::Regression(name), None) => {
+                return Err(format!("Series '{}' was not found in any input file", name));
+            }
+        }
+        let floor = if ts.values.iter().any(|v| *v < 0.0) { f64::NEG_INFINITY } else { 0.0 };
+        let fill = |t: u64| {
+            let (a, b) = by_month[month_of(t)];
+            match predictor {
+                Some(x) if matches!(self.method, ExtensionMethod::Regression(_)) => {
+                    let y = a + b * value_at(x, t);
+                    if y.is_nan() { y } else { y.max(floor) }
+                }
+                _ => a,
+            }
+        };
+
+        // On the series' own timesteps, from the earlier to the later of the record and the span
+        let start = match fill_span.0 < first {
+            true => first - (first - fill_span.0).div_ceil(step) * step,
+            false => first,
+        };
+        let end = match fill_span.1 > last {
+            true => last + (fill_span.1 - last).div_ceil(step) * step,
+            false => last,
+        };
+        let mut extended = Timeseries::new(step);
+        extended.name = ts.name.clone();
+        extended.start_timestamp = start;
+        let mut t = start;
+        while t <= end {
+            let in_record = (first..=last).contains(&t);
+            extended.push(t, if in_record { value_at(ts, t) } else { fill(t) });
+            t += step;
+        }
+        Ok(extended)
+    }
+}
@@ -0,0 +1,54 @@
+//! What nodes do with a missing (NaN) value of an input
+//!
+//! The policy is set for the whole model in `[kalix]`, and may be set for each input file in
+//! `[inputs]`, by alias or file name:
+//!
+//! ```ini
+//! [kalix]
+//! nan_policy = zero
+//!
+//! [inputs]
+//! climate = ./climate.csv
+//! nan_policy.climate = error
+//! ```
+//!
+//! - `propagate` (the default): the missing value is used, so the node's results are missing too
+//! - `zero`: 0 is used instead
+//! - `error`: the run stops, naming the node and the date
+//!
+//! Missing values are logged against the node that read them, with the code `input_nan` or
+//! `input_nan_substituted`, and the number of substitutions is reported at the end of a run.
+
+/// Ordered from most to least lenient, so an input that reads several series takes the
+/// strictest of their policies
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum NanPolicy {
+    #[default]
+    Propagate,
+    Zero,
+    Error,
+}
+
+impl NanPolicy {
+    pub const ALL: [NanPolicy; 3] = [NanPolicy::Propagate, NanPolicy::Zero, NanPolicy::Error];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NanPolicy::Propagate => "propagate",
+            NanPolicy::Zero => "zero",
+            NanPolicy::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<NanPolicy> {
+        NanPolicy::ALL.into_iter().find(|p| p.as_str() == name.trim().to_lowercase())
+    }
+}
+
+/// The NaN policy for one input file, from a `nan_policy.<source> = <option>` property
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputNanPolicy {
+    /// The file's alias or sanitized file name, e.g. `climate` or `diversions_csv`
+    pub source: String,
+    pub policy: NanPolicy,
+}
@@ -0,0 +1,177 @@
+//! Converting input series whose timestep differs from the simulation step
+//!
+//! Data finer than the simulation step (e.g. hourly rainfall in a daily model) is aggregated
+//! to the model step when the model is configured. Coarser data (e.g. daily evaporation in an
+//! hourly model) is only used if its input file says how to fill the steps in between. The
+//! policy for each input file is given in the `[inputs]` section, by alias or file name:
+//!
+//! ```ini
+//! [inputs]
+//! climate = ./climate_hourly.csv
+//! ./evap_daily.csv
+//! resample.climate = sum
+//! resample.evap_daily_csv = repeat
+//! ```
+//!
+//! The simulation step must be a whole multiple (or fraction) of the input step, and the
+//! input timestamps must fall on the simulation's grid of timesteps.
+
+use crate::timeseries::Timeseries;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resample {
+    /// Average of the finer values in each step (the default for finer data)
+    Mean,
+    /// Total of the finer values in each step, e.g. for rainfall depths
+    Sum,
+    /// Each coarser value is used for every step it covers
+    Repeat,
+    /// Steps between coarser values are linearly interpolated
+    Interpolate,
+}
+
+impl Resample {
+    pub const ALL: [Resample; 4] = [Resample::Mean, Resample::Sum, Resample::Repeat, Resample::Interpolate];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resample::Mean => "mean",
+            Resample::Sum => "sum",
+            Resample::Repeat => "repeat",
+            Resample::Interpolate => "interpolate",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Resample> {
+        let name = name.trim().to_lowercase();
+        Resample::ALL.into_iter().find(|r| r.as_str() == name)
+    }
+
+    /// Whether this policy fills in coarser data, rather than aggregating finer data
+    pub fn is_for_coarser_data(&self) -> bool {
+        matches!(self, Resample::Repeat | Resample::Interpolate)
+    }
+}
+
+/// The resampling policy for one input file, from a `resample.<source> = <method>` property
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputResampling {
+    /// The file's alias or sanitized file name, e.g. `climate` or `evap_daily_csv`
+    pub source: String,
+    pub method: Resample,
+}
+
+/// Returns `ts` on the grid of timesteps `origin + k * step_size`, covering the span of the
+/// input data. With no `method`, finer data is averaged and coarser data is an error.
+/// Aggregated steps are missing unless every value in them is present.
+pub fn resample(ts: &Timeseries, origin: u64, step_size: u64, method: Option<Resample>) -> Result<Timeseries, String> {
+    let input_step = ts.step_size;
+    if input_step == step_size {
+        return Ok(ts.clone());
+    }
+    let mismatch = || format!("has step_size {} but simulation requires step_size {}", input_step, step_size);
+    if input_step == 0 || step_size == 0 || !input_step.max(step_size).is_multiple_of(input_step.min(step_size)) {
+        return Err(mismatch());
+    }
+    if !ts.start_timestamp.abs_diff(origin).is_multiple_of(input_step.min(step_size)) {
+        return Err(format!("{}, and its timestamps are not aligned with the simulation's timesteps", mismatch()));
+    }
+
+    let (start, end) = (ts.start_timestamp as i128, ts.start_timestamp as i128 + (ts.len() as u64 * input_step) as i128);
+    let (origin, step) = (origin as i128, step_size as i128);
+    let mut result = Timeseries::new(step_size);
+    result.name = ts.name.clone();
+
+    if input_step < step_size {
+        if method.is_some_and(|m| m.is_for_coarser_data()) {
+            return Err(format!("{}. '{}' only applies to data coarser than the simulation step",
+                               mismatch(), method.unwrap().as_str()));
+        }
+        // Each output step is the block of input values from its timestamp up to the next
+        let ratio = (step_size / input_step) as i128;
+        let first = origin + (start - origin).div_euclid(step) * step;
+        result.start_timestamp = first as u64;
+        let mut t = first;
+        while t < end {
+            let block: Vec<f64> = (0..ratio)
+                .map(|j| {
+                    let i = (t - start) / input_step as i128 + j;
+                    if i < 0 { f64::NAN } else { ts.values.get(i as usize).copied().unwrap_or(f64::NAN) }
+                })
+                .collect();
+            let total: f64 = block.iter().sum();
+            result.push_value(match method {
+                Some(Resample::Sum) => total,
+                _ => total / ratio as f64,
+            });
+            t += step;
+        }
+    } else {
+        let method = match method {
+            Some(m) if m.is_for_coarser_data() => m,
+            _ => return Err(format!("{}. Coarser data must be set to repeat or interpolate", mismatch())),
+        };
+        let first = origin + (start - origin + step - 1).div_euclid(step) * step;
+        result.start_timestamp = first as u64;
+        let mut t = first;
+        while t < end {
+            let offset = (t - start) as u64;
+            let i = (offset / input_step) as usize;
+            let frac = (offset % input_step) as f64 / input_step as f64;
+            result.push_value(match method {
+                Resample::Interpolate if frac > 0.0 => match ts.values.get(i + 1) {
+                    Some(next) => ts.values[i] + frac * (next - ts.values[i]),
+                    None => f64::NAN,
+                },
+                _ => ts.values[i],
+            });
+            t += step;
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(step_size: u64, start: u64, values: &[f64]) -> Timeseries {
+        let mut ts = Timeseries::new(step_size);
+        ts.start_timestamp = start;
+        for &v in values {
+            ts.push_value(v);
+        }
+        ts
+    }
+
+    #[test]
+    fn test_aggregate_finer_data() {
+        // Six-hourly values from 06:00 on day 1, so the first day is incomplete
+        let ts = series(21600, 86400 + 21600, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        let mean = resample(&ts, 0, 86400, None).unwrap();
+        assert_eq!(mean.start_timestamp, 86400);
+        assert_eq!(mean.len(), 2);
+        assert!(mean.values[0].is_nan());
+        assert_eq!(mean.values[1], 5.5);
+        assert_eq!(resample(&ts, 0, 86400, Some(Resample::Sum)).unwrap().values[1], 22.0);
+
+        assert!(resample(&ts, 0, 86400, Some(Resample::Repeat)).is_err());
+        assert!(resample(&ts, 3600, 86400, None).is_err());
+        assert!(resample(&series(7200, 0, &[1.0]), 0, 10800, None).is_err());
+    }
+
+    #[test]
+    fn test_fill_coarser_data() {
+        let ts = series(86400, 86400, &[4.0, 8.0]);
+        let err = resample(&ts, 0, 43200, None).err().unwrap();
+        assert_eq!(err, "has step_size 86400 but simulation requires step_size 43200. Coarser data must be set to repeat or interpolate");
+
+        let repeated = resample(&ts, 0, 43200, Some(Resample::Repeat)).unwrap();
+        assert_eq!(repeated.start_timestamp, 86400);
+        assert_eq!(repeated.values, vec![4.0, 4.0, 8.0, 8.0]);
+
+        let interpolated = resample(&ts, 0, 43200, Some(Resample::Interpolate)).unwrap();
+        assert_eq!(interpolated.values[..3], [4.0, 6.0, 8.0]);
+        assert!(interpolated.values[3].is_nan());
+    }
+}
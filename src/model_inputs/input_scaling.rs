@@ -0,0 +1,257 @@
+//! Adjustments applied to input series when a model is configured, e.g. for climate change
+//! stress tests without generating new input files
+//!
+//! Each property of the `[scaling]` section names an input series and gives a comma-separated
+//! list of adjustments, applied in order:
+//!
+//! ```ini
+//! [scaling]
+//! data.climate.by_name.rain = -5% winter, +2% summer
+//! data.climate.by_name.pet = *1.1
+//! data.flows_csv.by_index.1 = +10% q90-100
+//! data.climate.by_name.temp = +1.5 dec-feb
+//! ```
+//!
+//! An adjustment is a percentage change (`+5%`), a factor (`*1.05`) or an amount added to
+//! each value (`+1.5`). It applies to the whole series unless followed by a selector: a month
+//! (`jan`), a range of months (`dec-feb`), a season or a quantile band (`q90-100` selects the
+//! values at or above the 90th percentile). Seasons are southern hemisphere, so `summer` is
+//! `dec-feb`. Quantiles are taken from the unscaled series over its whole record.
+
+use crate::tid::utils::u64_to_year_month_day_and_seconds;
+use crate::timeseries::Timeseries;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const SEASONS: [(&str, u32, u32); 4] = [("summer", 12, 2), ("autumn", 3, 5), ("winter", 6, 8), ("spring", 9, 11)];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalingOperation {
+    /// Percentage change, e.g. -5 for `-5%`
+    Percent(f64),
+    /// Multiply by a factor, e.g. `*1.05`
+    Factor(f64),
+    /// Add an amount, e.g. `+1.5`
+    Add(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalingSelector {
+    All,
+    /// Months `first` to `last` inclusive (1 = January), wrapping past December
+    Months { first: u32, last: u32 },
+    /// Values between two percentiles of the series, inclusive
+    Quantiles { lower: f64, upper: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScalingTerm {
+    pub operation: ScalingOperation,
+    pub selector: ScalingSelector,
+}
+
+/// The adjustments for one input series
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputScaling {
+    /// Data reference as written in the model file, e.g. `data.climate.by_name.rain`
+    pub series: String,
+    pub terms: Vec<ScalingTerm>,
+}
+
+impl ScalingOperation {
+    fn parse(s: &str) -> Result<ScalingOperation, String> {
+        let number = |t: &str| t.trim().parse::<f64>().ok().filter(|v| v.is_finite());
+        let op = if let Some(factor) = s.strip_prefix('*') {
+            number(factor).map(ScalingOperation::Factor)
+        } else if let Some(percent) = s.strip_suffix('%') {
+            number(percent).map(ScalingOperation::Percent)
+        } else if s.starts_with('+') || s.starts_with('-') {
+            number(s).map(ScalingOperation::Add)
+        } else {
+            None
+        };
+        op.ok_or(format!("Invalid adjustment '{}'. Expected a percentage (+5%), factor (*1.05) or signed amount (+1.5)", s))
+    }
+
+    fn apply(&self, value: f64) -> f64 {
+        match *self {
+            ScalingOperation::Percent(p) => value * (1.0 + p / 100.0),
+            ScalingOperation::Factor(f) => value * f,
+            ScalingOperation::Add(a) => value + a,
+        }
+    }
+
+    fn to_definition(self) -> String {
+        let signed = |v: f64| if v < 0.0 { v.to_string() } else { format!("+{}", v) };
+        match self {
+            ScalingOperation::Percent(p) => format!("{}%", signed(p)),
+            ScalingOperation::Factor(f) => format!("*{}", f),
+            ScalingOperation::Add(a) => signed(a),
+        }
+    }
+}
+
+impl ScalingSelector {
+    fn parse(s: &str) -> Result<ScalingSelector, String> {
+        let month = |m: &str| MONTHS.iter().position(|&name| name == m).map(|i| i as u32 + 1);
+        if let Some(&(_, first, last)) = SEASONS.iter().find(|(name, _, _)| *name == s) {
+            return Ok(ScalingSelector::Months { first, last });
+        }
+        if let Some(m) = month(s) {
+            return Ok(ScalingSelector::Months { first: m, last: m });
+        }
+        if let Some((a, b)) = s.split_once('-') {
+            if let (Some(first), Some(last)) = (month(a), month(b)) {
+                return Ok(ScalingSelector::Months { first, last });
+            }
+            if let Some(a) = a.strip_prefix('q') {
+                let lower = a.parse::<f64>().ok();
+                let upper = b.parse::<f64>().ok();
+                if let (Some(lower), Some(upper)) = (lower, upper) {
+                    if 0.0 <= lower && lower < upper && upper <= 100.0 {
+                        return Ok(ScalingSelector::Quantiles { lower, upper });
+                    }
+                }
+            }
+        }
+        Err(format!("Invalid selector '{}'. Expected a month (jan), month range (dec-feb), season (summer) or quantile band (q90-100)", s))
+    }
+
+    fn includes_month(&self, month: u32) -> bool {
+        match *self {
+            ScalingSelector::Months { first, last } if first <= last => (first..=last).contains(&month),
+            ScalingSelector::Months { first, last } => month >= first || month <= last,
+            _ => true,
+        }
+    }
+
+    fn to_definition(self) -> Option<String> {
+        match self {
+            ScalingSelector::All => None,
+            ScalingSelector::Months { first, last } if first == last => Some(MONTHS[first as usize - 1].to_string()),
+            ScalingSelector::Months { first, last } => {
+                Some(format!("{}-{}", MONTHS[first as usize - 1], MONTHS[last as usize - 1]))
+            }
+            ScalingSelector::Quantiles { lower, upper } => Some(format!("q{}-{}", lower, upper)),
+        }
+    }
+}
+
+impl InputScaling {
+    /// Parses a definition such as `-5% winter, +2% summer`
+    pub fn parse(series: &str, definition: &str) -> Result<InputScaling, String> {
+        let mut terms = Vec::new();
+        for term in definition.split(',').map(|t| t.trim().to_lowercase()) {
+            let mut parts = term.split_whitespace();
+            let operation = match parts.next() {
+                Some(op) => ScalingOperation::parse(op)?,
+                None => return Err(format!("Empty adjustment for '{}'", series)),
+            };
+            let selector = match parts.next() {
+                Some(sel) => ScalingSelector::parse(sel)?,
+                None => ScalingSelector::All,
+            };
+            if let Some(extra) = parts.next() {
+                return Err(format!("Unexpected '{}' in adjustment '{}'", extra, term));
+            }
+            terms.push(ScalingTerm { operation, selector });
+        }
+        Ok(InputScaling { series: series.to_string(), terms })
+    }
+
+    /// The canonical form of the definition, as written back to the model file
+    pub fn definition(&self) -> String {
+        self.terms.iter()
+            .map(|t| match t.selector.to_definition() {
+                Some(sel) => format!("{} {}", t.operation.to_definition(), sel),
+                None => t.operation.to_definition(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Returns the values of `ts` with every adjustment applied. Missing values stay missing.
+    pub fn apply(&self, ts: &Timeseries) -> Vec<f64> {
+        let mut sorted: Vec<f64> = ts.values.iter().copied().filter(|v| v.is_finite()).collect();
+        sorted.sort_by(f64::total_cmp);
+        let months: Vec<u32> = (0..ts.values.len())
+            .map(|i| u64_to_year_month_day_and_seconds(ts.start_timestamp + i as u64 * ts.step_size).1)
+            .collect();
+
+        let mut values = ts.values.clone();
+        for term in &self.terms {
+            let band = match term.selector {
+                ScalingSelector::Quantiles { lower, upper } => Some((quantile(&sorted, lower / 100.0), quantile(&sorted, upper / 100.0))),
+                _ => None,
+            };
+            for (i, value) in values.iter_mut().enumerate() {
+                let selected = match band {
+                    Some((lo, hi)) => ts.values[i] >= lo && ts.values[i] <= hi,
+                    None => term.selector.includes_month(months[i]),
+                };
+                if selected {
+                    *value = term.operation.apply(*value);
+                }
+            }
+        }
+        values
+    }
+}
+
+/// Linearly interpolated quantile of sorted values (NaN if there are none)
+pub(crate) fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let pos = q * (sorted.len() - 1) as f64;
+    let (i, frac) = (pos.floor() as usize, pos.fract());
+    match sorted.get(i + 1) {
+        Some(next) => sorted[i] + frac * (next - sorted[i]),
+        None => sorted[i],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tid::utils::date_string_to_u64;
+
+    #[test]
+    fn test_parse_and_definition() {
+        let s = InputScaling::parse("data.rain", "-5% Winter, +2% dec-feb, *1.1, +0.5 jan, -10% q90-100").unwrap();
+        assert_eq!(s.terms.len(), 5);
+        assert_eq!(s.terms[0], ScalingTerm {
+            operation: ScalingOperation::Percent(-5.0),
+            selector: ScalingSelector::Months { first: 6, last: 8 },
+        });
+        assert_eq!(s.terms[4].selector, ScalingSelector::Quantiles { lower: 90.0, upper: 100.0 });
+        assert_eq!(s.definition(), "-5% jun-aug, +2% dec-feb, *1.1, +0.5 jan, -10% q90-100");
+
+        assert!(InputScaling::parse("data.rain", "5").is_err());
+        assert!(InputScaling::parse("data.rain", "+5% winter summer").is_err());
+        assert!(InputScaling::parse("data.rain", "+5% q90-80").is_err());
+        assert!(InputScaling::parse("data.rain", "+5%,").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        // 30 Nov, 1 Dec and 2 Dec: one spring day then two summer days
+        let months = InputScaling::parse("x", "+50% summer, +1 nov").unwrap();
+        let mut daily = Timeseries::new_daily();
+        daily.start_timestamp = date_string_to_u64("2000-11-30").unwrap();
+        for v in [10.0, 20.0, f64::NAN] {
+            daily.push_value(v);
+        }
+        let scaled = months.apply(&daily);
+        assert_eq!(scaled[..2], [11.0, 30.0]);
+        assert!(scaled[2].is_nan());
+
+        // Top quarter of 0..=8 is 6, 7 and 8
+        let mut flows = Timeseries::new_daily();
+        flows.start_timestamp = daily.start_timestamp;
+        for v in 0..=8 {
+            flows.push_value(v as f64);
+        }
+        let top = InputScaling::parse("x", "*2 q75-100").unwrap().apply(&flows);
+        assert_eq!(top, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 12.0, 14.0, 16.0]);
+    }
+}
@@ -1,17 +1,39 @@
-/// Model Inputs Module
-///
-/// This module contains types and utilities for defining model inputs.
-/// Model inputs can be simple data references, constants, or dynamic
-/// function expressions that are evaluated at each timestep.
-///
-/// # Types
-///
-/// - `InputDataDefinition`: Simple reference to a timeseries in the data cache
-/// - `DynamicInput`: Flexible input supporting constants, data references, or function expressions
+//! Model Inputs Module
+//!
+//! This module contains types and utilities for defining model inputs.
+//! Model inputs can be simple data references, constants, or dynamic
+//! function expressions that are evaluated at each timestep.
+//!
+//! # Types
+//!
+//! - `InputDataDefinition`: Simple reference to a timeseries in the data cache
+//! - `DynamicInput`: Flexible input supporting constants, data references, or function expressions
+//! - `InputScaling`: Adjustments to an input series from the `[scaling]` section
+//! - `InputCoverage`: What happens where an input file does not cover the simulation period
+//! - `InputNanPolicy`: What nodes do with a missing value of an input file
+//! - `InputExtension`: How a short input series is extended, from the `[extension]` section
+//! - `InputResampling`: How an input file with a different timestep is converted to the model step
+//! - `UserFunctions`: Named expressions from the `[functions]` section
+//! - `Policies`: Staged restrictions from the `[policies]` section
 
 pub mod input_data_definition;
 pub mod dynamic_input;
+pub mod compiled_expression;
 pub mod linear_combination;
+pub mod input_scaling;
+pub mod input_resampling;
+pub mod input_extension;
+pub mod input_coverage;
+pub mod input_nan_policy;
+pub mod user_functions;
+pub mod policies;
 
 pub use input_data_definition::InputDataDefinition;
 pub use dynamic_input::DynamicInput;
+pub use input_scaling::InputScaling;
+pub use input_resampling::{InputResampling, Resample};
+pub use input_extension::InputExtension;
+pub use input_coverage::{Coverage, InputCoverage};
+pub use input_nan_policy::{InputNanPolicy, NanPolicy};
+pub use user_functions::UserFunctions;
+pub use policies::Policies;
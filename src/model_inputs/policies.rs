@@ -0,0 +1,262 @@
+//! Staged restriction policies from the `[policies]` section, such as drought restrictions that
+//! tighten as a storage draws down
+//!
+//! ```ini
+//! [policies]
+//! stage.1 = node.dam.volume < 60000
+//! stage.1.demand_factor = 0.8
+//! stage.2 = node.dam.volume < 30000
+//! stage.2.demand_factor = 0.5
+//! stage.2.demand_factor.town = 0.7
+//! stage.2.release_cap.dam = 100
+//! ```
+//!
+//! Each stage has a trigger expression, and the active stage is the highest-numbered stage whose
+//! trigger is positive, or 0 when none is. Triggers are evaluated at the start of each timestep,
+//! before the order phase, so node results and functions of node results are read from the
+//! previous timestep (on the first timestep there are none, so no stage is active). The active
+//! stage is recorded as the `policy.stage` series, which expressions can use and `[outputs]`
+//! can list.
+//!
+//! Only the actions of the active stage apply, and they hold for that timestep:
+//! - `demand_factor` scales the demand of every regulated and unregulated user, and
+//!   `demand_factor.NODE` the demand of one user, overriding the first
+//! - `release_cap.NODE` caps the controlled release from each outlet of storage NODE (spill is
+//!   not capped)
+//!
+//! Action values are expressions too, evaluated like the triggers.
+
+use crate::data_management::data_cache::DataCache;
+use crate::model_inputs::user_functions::UserFunctions;
+use crate::model_inputs::DynamicInput;
+use crate::nodes::{Node, NodeEnum};
+
+/// Name of the series recording the active stage
+pub const POLICY_STAGE_SERIES: &str = "policy.stage";
+
+const STAGE_PREFIX: &str = "stage.";
+
+/// What a stage does to a node while it is active
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PolicyActionKind {
+    DemandFactor,
+    ReleaseCap,
+}
+
+impl PolicyActionKind {
+    pub const ALL: [PolicyActionKind; 2] = [PolicyActionKind::DemandFactor, PolicyActionKind::ReleaseCap];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyActionKind::DemandFactor => "demand_factor",
+            PolicyActionKind::ReleaseCap => "release_cap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<PolicyActionKind, String> {
+        let lower = name.trim().to_lowercase();
+        PolicyActionKind::ALL.into_iter()
+            .find(|k| k.as_str() == lower)
+            .ok_or_else(|| format!("Unknown policy action '{}'. Expected one of: {}", name,
+                                   PolicyActionKind::ALL.map(|k| k.as_str()).join(", ")))
+    }
+
+    /// The value of this action on a node while no stage restricts it
+    fn unrestricted(&self) -> f64 {
+        match self {
+            PolicyActionKind::DemandFactor => 1.0,
+            PolicyActionKind::ReleaseCap => f64::INFINITY,
+        }
+    }
+
+    /// Sets the action's value on a node of the kind it applies to
+    fn set(&self, node: &mut NodeEnum, value: f64) {
+        match (self, node) {
+            (PolicyActionKind::DemandFactor, NodeEnum::RegulatedUserNode(n)) => n.policy_demand_factor = value,
+            (PolicyActionKind::DemandFactor, NodeEnum::UnregulatedUserNode(n)) => n.policy_demand_factor = value,
+            (PolicyActionKind::ReleaseCap, NodeEnum::StorageNode(n)) => n.policy_release_cap = value,
+            _ => {}
+        }
+    }
+
+    fn applies_to(&self, node: &NodeEnum) -> bool {
+        match self {
+            PolicyActionKind::DemandFactor => matches!(node, NodeEnum::RegulatedUserNode(_) | NodeEnum::UnregulatedUserNode(_)),
+            PolicyActionKind::ReleaseCap => matches!(node, NodeEnum::StorageNode(_)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PolicyAction {
+    pub kind: PolicyActionKind,
+    /// The node the action applies to as written, or None for every node it can apply to
+    pub node: Option<String>,
+    pub value: DynamicInput,
+    /// Indices of the nodes it applies to, resolved at configure time
+    node_indices: Vec<usize>,
+}
+
+impl PolicyAction {
+    /// The property name of the action within its stage, e.g. `demand_factor.town`
+    pub fn key(&self) -> String {
+        match &self.node {
+            Some(node) => format!("{}.{}", self.kind.as_str(), node),
+            None => self.kind.as_str().to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PolicyStage {
+    pub number: usize,
+    pub trigger: Option<DynamicInput>,
+    pub actions: Vec<PolicyAction>,
+}
+
+#[derive(Clone, Default)]
+pub struct Policies {
+    /// Stages in order of number once the model is configured
+    pub stages: Vec<PolicyStage>,
+    series_idx: usize,
+    /// (action kind, node index) of every node that any stage restricts
+    targets: Vec<(PolicyActionKind, usize)>,
+}
+
+impl Policies {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Adds a property of the `[policies]` section: a stage's trigger (`stage.N`) or one of its
+    /// actions (`stage.N.ACTION` or `stage.N.ACTION.NODE`)
+    pub fn add(&mut self, name: &str, expression: &str, data_cache: &mut DataCache) -> Result<(), String> {
+        let invalid = || format!("Invalid policy property '{}'. Expected 'stage.N' or 'stage.N.ACTION[.NODE]'", name);
+        let rest = name.to_lowercase().strip_prefix(STAGE_PREFIX).ok_or_else(invalid)?.to_string();
+        let (number, action) = match rest.split_once('.') {
+            Some((number, action)) => (number.to_string(), Some(action.to_string())),
+            None => (rest, None),
+        };
+        let number = number.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(invalid)?;
+
+        let value = DynamicInput::from_string(expression, data_cache, true, None)?;
+        self.series_idx = data_cache.get_or_add_new_series(POLICY_STAGE_SERIES, false);
+        let stage = match self.stages.iter().position(|s| s.number == number) {
+            Some(i) => &mut self.stages[i],
+            None => {
+                self.stages.push(PolicyStage { number, trigger: None, actions: vec![] });
+                self.stages.last_mut().unwrap()
+            }
+        };
+
+        let Some(action) = action else {
+            if stage.trigger.is_some() {
+                return Err(format!("Stage {} has more than one trigger", number));
+            }
+            stage.trigger = Some(value);
+            return Ok(());
+        };
+        let (kind, node) = match action.split_once('.') {
+            Some((kind, node)) => (PolicyActionKind::from_name(kind)?, Some(node.to_string())),
+            None => (PolicyActionKind::from_name(&action)?, None),
+        };
+        if kind == PolicyActionKind::ReleaseCap && node.is_none() {
+            return Err(format!("'{}' needs a storage node, e.g. 'stage.{}.release_cap.dam'", name, number));
+        }
+        // Keep the node name as written, so the section is saved the way it was read
+        let node = node.map(|n| name[name.len() - n.len()..].to_string());
+        if stage.actions.iter().any(|a| a.kind == kind && a.node.as_ref().map(|n| n.to_lowercase()) == node.as_ref().map(|n| n.to_lowercase())) {
+            return Err(format!("'{}' is defined more than once", name));
+        }
+        stage.actions.push(PolicyAction { kind, node, value, node_indices: vec![] });
+        Ok(())
+    }
+
+    /// Expressions of every trigger and action
+    pub fn expressions(&self) -> impl Iterator<Item = &DynamicInput> {
+        self.stages.iter().flat_map(|s| s.trigger.iter().chain(s.actions.iter().map(|a| &a.value)))
+    }
+
+    /// Checks the stages and finds the nodes their actions apply to. References to results that
+    /// are only known after the flow phase are changed to read the previous timestep's value.
+    pub fn resolve(&mut self, nodes: &[NodeEnum], functions: &UserFunctions, data_cache: &DataCache) -> Result<(), String> {
+        self.stages.sort_by_key(|s| s.number);
+        let mut targets = vec![];
+        for (i, stage) in self.stages.iter_mut().enumerate() {
+            if stage.number != i + 1 {
+                return Err(format!("Policy stages must be numbered 1, 2, 3, ... without gaps, but stage {} is missing", i + 1));
+            }
+            if stage.trigger.is_none() {
+                return Err(format!("Policy stage {} has no trigger. Add 'stage.{} = <expression>' to [policies]", stage.number, stage.number));
+            }
+
+            // Every-node actions go first, so that a node's own action overrides them
+            stage.actions.sort_by_key(|a| a.node.is_some());
+            for action in stage.actions.iter_mut() {
+                action.node_indices = match &action.node {
+                    None => (0..nodes.len()).filter(|&n| action.kind.applies_to(&nodes[n])).collect(),
+                    Some(name) => {
+                        let idx = nodes.iter().position(|n| n.get_name().eq_ignore_ascii_case(name))
+                            .ok_or(format!("Policy stage {} refers to node '{}', which is not defined", stage.number, name))?;
+                        if !action.kind.applies_to(&nodes[idx]) {
+                            let expected = match action.kind {
+                                PolicyActionKind::DemandFactor => "a regulated or unregulated user",
+                                PolicyActionKind::ReleaseCap => "a storage",
+                            };
+                            return Err(format!("Policy stage {} sets '{}' on node '{}', which is not {}",
+                                               stage.number, action.kind.as_str(), name, expected));
+                        }
+                        vec![idx]
+                    }
+                };
+                targets.extend(action.node_indices.iter().map(|&n| (action.kind, n)));
+            }
+
+            let inputs = stage.trigger.iter_mut().chain(stage.actions.iter_mut().map(|a| &mut a.value));
+            for input in inputs {
+                for idx in input.current_step_references() {
+                    let name = data_cache.series_name[idx].to_lowercase();
+                    if functions.is_after_flow_series(&name) || name == POLICY_STAGE_SERIES {
+                        input.use_previous_value(idx)
+                            .map_err(|e| format!("Policy stage {}: {}", stage.number, e))?;
+                    }
+                }
+            }
+        }
+        targets.sort_by_key(|&(kind, n)| (n, kind.as_str()));
+        targets.dedup();
+        self.targets = targets;
+        Ok(())
+    }
+
+    /// Works out the active stage, records it, and applies its actions for this timestep
+    pub fn apply(&self, nodes: &mut [NodeEnum], data_cache: &mut DataCache) {
+        if self.stages.is_empty() {
+            return;
+        }
+        let active = if data_cache.current_step == 0 {
+            None
+        } else {
+            self.stages.iter().rposition(|s| s.trigger.as_ref().is_some_and(|t| t.get_value(data_cache) > 0.0))
+        };
+        data_cache.add_value_at_index(self.series_idx, active.map_or(0.0, |i| self.stages[i].number as f64));
+
+        for &(kind, n) in &self.targets {
+            kind.set(&mut nodes[n], kind.unrestricted());
+        }
+        if let Some(i) = active {
+            for action in &self.stages[i].actions {
+                let value = action.value.get_value(data_cache);
+                for &n in &action.node_indices {
+                    action.kind.set(&mut nodes[n], value);
+                }
+            }
+        }
+    }
+}
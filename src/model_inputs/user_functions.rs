@@ -0,0 +1,105 @@
+//! Named expressions from the `[functions]` section, so a long expression can be written once
+//! and referenced anywhere a data reference can
+//!
+//! ```ini
+//! [functions]
+//! f.total_inflow = node.a.dsflow + node.b.dsflow
+//! f.dry = if(data.climate.by_name.rain < 1, 1, 0)
+//! ```
+//!
+//! Each function is evaluated once per timestep and stored in the data cache under its name,
+//! so it can be used in any expression (e.g. `f.total_inflow[-1]`) and listed in `[outputs]`.
+//! Functions that refer to node results, directly or through another function, are evaluated
+//! after the flow phase so that they see this timestep's results; nodes therefore see their
+//! previous value. The others are evaluated at the start of the timestep, before any node
+//! runs. A function may only refer to functions defined above it.
+
+use crate::data_management::data_cache::DataCache;
+use crate::functions::parse_function;
+use crate::misc::misc_functions::is_valid_variable_name;
+use crate::model_inputs::DynamicInput;
+
+pub const FUNCTION_PREFIX: &str = "f.";
+
+#[derive(Clone)]
+pub struct UserFunction {
+    /// Lowercase name including the prefix, e.g. `f.total_inflow`
+    pub name: String,
+    pub expression: DynamicInput,
+    /// Evaluated after the flow phase rather than before the timestep
+    pub after_flow: bool,
+    series_idx: usize,
+}
+
+#[derive(Clone, Default)]
+pub struct UserFunctions {
+    pub functions: Vec<UserFunction>,
+}
+
+impl UserFunctions {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Defines a function, registering its result series in the data cache
+    pub fn add(&mut self, name: &str, expression: &str, data_cache: &mut DataCache) -> Result<(), String> {
+        let name_lower = name.to_lowercase();
+        match name_lower.strip_prefix(FUNCTION_PREFIX) {
+            Some(short_name) if is_valid_variable_name(short_name) => {}
+            _ => return Err(format!("Invalid function name '{}'. Function names must start with '{}'", name, FUNCTION_PREFIX)),
+        }
+        if self.functions.iter().any(|f| f.name == name_lower) {
+            return Err(format!("Function '{}' is defined more than once", name));
+        }
+
+        // Work out when the function must be evaluated from what it refers to
+        let parsed = parse_function(expression.trim())
+            .map_err(|e| e.render(expression.trim()))?;
+        let mut after_flow = false;
+        for variable in parsed.get_variables() {
+            let variable = variable.to_lowercase();
+            if variable.starts_with("node.") {
+                after_flow = true;
+            } else if variable.starts_with(FUNCTION_PREFIX) {
+                match self.functions.iter().find(|f| f.name == variable) {
+                    Some(f) => after_flow |= f.after_flow,
+                    None => return Err(format!("Function '{}' refers to '{}', which is not defined above it", name, variable)),
+                }
+            }
+        }
+
+        let expression = DynamicInput::from_string(expression, data_cache, true, None)?;
+        let series_idx = data_cache.get_or_add_new_series(&name_lower, false);
+        self.functions.push(UserFunction { name: name_lower, expression, after_flow, series_idx });
+        Ok(())
+    }
+
+    /// Evaluates the functions that do not depend on this timestep's node results
+    pub fn evaluate_before_flow(&self, data_cache: &mut DataCache) {
+        self.evaluate(data_cache, false);
+    }
+
+    /// Evaluates the functions that depend on this timestep's node results
+    pub fn evaluate_after_flow(&self, data_cache: &mut DataCache) {
+        self.evaluate(data_cache, true);
+    }
+
+    /// Whether a series (by lowercase name) is only known after the flow phase: node results
+    /// and the functions evaluated after it
+    pub fn is_after_flow_series(&self, lower_name: &str) -> bool {
+        lower_name.starts_with("node.") || self.functions.iter().any(|f| f.after_flow && f.name == lower_name)
+    }
+
+    fn evaluate(&self, data_cache: &mut DataCache, after_flow: bool) {
+        for f in self.functions.iter().filter(|f| f.after_flow == after_flow) {
+            let value = f.expression.get_value(data_cache);
+            data_cache.add_value_at_index(f.series_idx, value);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
@@ -13,6 +13,7 @@ const MAX_DS_LINKS: usize = 1;
 pub struct ConfluenceNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
 
     // Harmony fraction
@@ -1,19 +1,34 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::data_management::data_cache::DataCache;
+use crate::data_management::run_log::LogLevel;
 use crate::hydrology::accounts::account_manager::AccountManager;
 use crate::model_inputs::DynamicInput;
 use crate::misc::location::Location;
+use crate::numerical::table::Table;
 
 const MAX_DS_LINKS: usize = 1;
 
+/// Passes flow through, optionally forcing it, and compares it with an observed reference
+/// flow. The reference flow is either given directly or converted from an observed level
+/// with a rating table (columns: level, flow). Observed values whose quality code is above
+/// `quality_threshold` are excluded, i.e. the reference flow is NaN. A `residual` inflow (or
+/// loss, if negative) is added to the flow at the gauge, e.g. one estimated from observations
+/// by [`Model::estimate_residual`](crate::model::Model::estimate_residual).
 #[derive(Default, Clone)]
 pub struct GaugeNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub force_flow_input: DynamicInput,
     pub reference_flow_input: DynamicInput,
+    pub reference_level_input: DynamicInput,
+    pub rating_table: Table,            // Columns: Level, Flow ML
+    pub quality_input: DynamicInput,
+    pub quality_threshold: Option<f64>,
+    pub min_flow_input: DynamicInput, // Minimum flow requirement, ordered from upstream storages
+    pub residual_input: DynamicInput, // Unattributed inflow (+) or loss (-) added at the gauge
 
     // Internal state only
     usflow: f64,
@@ -21,15 +36,20 @@ pub struct GaugeNode {
 
     // Orders
     pub dsorders: [f64; MAX_DS_LINKS],
+    pub usorders: f64,
 
     // Recorders
     recorder_idx_delta: Option<usize>,
     recorder_idx_usflow: Option<usize>,
+    recorder_idx_residual: Option<usize>,
     recorder_idx_dsflow: Option<usize>,
     recorder_idx_ds_1: Option<usize>,
     recorder_idx_ds_1_order: Option<usize>,
     recorder_idx_force_flow: Option<usize>,
     recorder_idx_reference_flow: Option<usize>,
+    recorder_idx_reference_level: Option<usize>,
+    recorder_idx_min_flow: Option<usize>,
+    recorder_idx_min_flow_shortfall: Option<usize>,
 }
 
 impl GaugeNode {
@@ -41,6 +61,28 @@ impl GaugeNode {
             ..Default::default()
         }
     }
+
+    /// Observed flow for the current timestep, from the reference flow or the rated
+    /// reference level. NaN if it is missing, outside the rating, or excluded by its quality.
+    fn get_reference_flow(&self, data_cache: &mut DataCache) -> f64 {
+        let flow = match self.reference_level_input {
+            DynamicInput::None { .. } => match self.reference_flow_input {
+                DynamicInput::None { .. } => f64::NAN,
+                _ => self.reference_flow_input.get_value(data_cache),
+            },
+            _ => {
+                let level = self.reference_level_input.get_value(data_cache);
+                if let Some(idx) = self.recorder_idx_reference_level {
+                    data_cache.add_value_at_index(idx, level);
+                }
+                self.rating_table.interpolate(0, 1, level)
+            }
+        };
+        match self.quality_threshold {
+            Some(threshold) if self.quality_input.get_value(data_cache) > threshold => f64::NAN,
+            _ => flow,
+        }
+    }
 }
 
 impl Node for GaugeNode {
@@ -52,6 +94,24 @@ impl Node for GaugeNode {
 
         //DynamicInput is already initialized during parsing
 
+        // Checks
+        let has_level = !matches!(self.reference_level_input, DynamicInput::None { .. });
+        let has_rating = self.rating_table.nrows() > 0;
+        if has_level != has_rating {
+            return Err(format!("Error in node '{}'. A reference_level and a rating table must be given together.", self.name));
+        }
+        if has_level && !matches!(self.reference_flow_input, DynamicInput::None { .. }) {
+            return Err(format!("Error in node '{}'. Only one of reference_flow and reference_level may be given.", self.name));
+        }
+        if has_rating {
+            if let Err(e) = self.rating_table.assert_monotonically_increasing(0, 1) {
+                return Err(format!("Node '{}' rating table. {}", self.name, e));
+            }
+        }
+        if matches!(self.quality_input, DynamicInput::None { .. }) != self.quality_threshold.is_none() {
+            return Err(format!("Error in node '{}'. A quality series and a quality_threshold must be given together.", self.name));
+        }
+
         // Initialize result recorders
         self.recorder_idx_delta = data_cache.get_series_idx(
             make_result_name(&self.name, "delta").as_str(), false
@@ -59,6 +119,9 @@ impl Node for GaugeNode {
         self.recorder_idx_usflow = data_cache.get_series_idx(
             make_result_name(&self.name, "usflow").as_str(), false
         );
+        self.recorder_idx_residual = data_cache.get_series_idx(
+            make_result_name(&self.name, "residual").as_str(), false
+        );
         self.recorder_idx_dsflow = data_cache.get_series_idx(
             make_result_name(&self.name, "dsflow").as_str(), false
         );
@@ -74,6 +137,15 @@ impl Node for GaugeNode {
         self.recorder_idx_reference_flow = data_cache.get_series_idx(
             make_result_name(&self.name, "reference_flow").as_str(), false
         );
+        self.recorder_idx_reference_level = data_cache.get_series_idx(
+            make_result_name(&self.name, "reference_level").as_str(), false
+        );
+        self.recorder_idx_min_flow = data_cache.get_series_idx(
+            make_result_name(&self.name, "min_flow").as_str(), false
+        );
+        self.recorder_idx_min_flow_shortfall = data_cache.get_series_idx(
+            make_result_name(&self.name, "min_flow_shortfall").as_str(), false
+        );
 
         // Return
         Ok(())
@@ -83,12 +155,23 @@ impl Node for GaugeNode {
         &self.name
     }
 
+    fn requires_order_phase(&self) -> bool {
+        !matches!(self.min_flow_input, DynamicInput::None { .. })
+    }
+
     fn run_order_phase(&mut self, data_cache: &mut DataCache) {
 
         // Record downstream orders
         if let Some(idx) = self.recorder_idx_ds_1_order {
             data_cache.add_value_at_index(idx, self.dsorders[0]);
         }
+
+        // Water passing the gauge to meet downstream orders also counts towards the minimum
+        // flow, so only the larger of the two is ordered
+        self.usorders = match self.min_flow_input {
+            DynamicInput::None { .. } => self.dsorders[0],
+            _ => self.dsorders[0].max(self.min_flow_input.get_value(data_cache)),
+        };
     }
 
     fn run_flow_phase(&mut self, data_cache: &mut DataCache, _account_manager: &mut AccountManager) {
@@ -98,6 +181,24 @@ impl Node for GaugeNode {
             data_cache.add_value_at_index(idx, self.usflow);
         }
 
+        // Add the residual inflow, or take the residual loss (at most the whole flow)
+        if !matches!(self.residual_input, DynamicInput::None { .. }) {
+            let mut residual = self.residual_input.get_value(data_cache);
+            if residual.is_nan() {
+                residual = 0.0;
+                data_cache.log_event(LogLevel::Warning, "input_nan_substituted", &self.name,
+                    || "Residual input is missing; no residual was added".to_string());
+            }
+            let residual = residual.max(-self.usflow);
+            self.usflow += residual;
+            self.mbal += residual;
+            if let Some(idx) = self.recorder_idx_residual {
+                data_cache.add_value_at_index(idx, residual);
+            }
+        } else if let Some(idx) = self.recorder_idx_residual {
+            data_cache.add_value_at_index(idx, 0.0);
+        }
+
         // Force flows if required, otherwise pass upstream value
         let force_flow_value = match self.force_flow_input {
             DynamicInput::None { .. } => f64::NAN,
@@ -105,6 +206,10 @@ impl Node for GaugeNode {
         };
         if force_flow_value.is_nan() {
             self.dsflow_primary = self.usflow;
+            if !matches!(self.force_flow_input, DynamicInput::None { .. }) {
+                data_cache.log_event(LogLevel::Warning, "input_nan_substituted", &self.name,
+                    || "Force flow input is missing; the upstream flow was used instead".to_string());
+            }
         } else {
             self.dsflow_primary = force_flow_value;
             self.mbal += self.dsflow_primary - self.usflow;
@@ -114,12 +219,10 @@ impl Node for GaugeNode {
         if let Some(idx) = self.recorder_idx_force_flow {
             data_cache.add_value_at_index(idx, force_flow_value);
         }
-        let needs_reference_flow = self.recorder_idx_delta.is_some() || self.recorder_idx_reference_flow.is_some();
+        let needs_reference_flow = self.recorder_idx_delta.is_some() || self.recorder_idx_reference_flow.is_some()
+            || self.recorder_idx_reference_level.is_some();
         let reference_flow_value = if needs_reference_flow {
-            match self.reference_flow_input {
-                DynamicInput::None { .. } => f64::NAN,
-                _ => self.reference_flow_input.get_value(data_cache),
-            }
+            self.get_reference_flow(data_cache)
         } else {
             f64::NAN
         };
@@ -136,6 +239,20 @@ impl Node for GaugeNode {
             data_cache.add_value_at_index(idx, self.dsflow_primary);
         }
 
+        // Compliance with today's minimum flow
+        if self.recorder_idx_min_flow.is_some() || self.recorder_idx_min_flow_shortfall.is_some() {
+            let min_flow = match self.min_flow_input {
+                DynamicInput::None { .. } => f64::NAN,
+                _ => self.min_flow_input.get_value(data_cache),
+            };
+            if let Some(idx) = self.recorder_idx_min_flow {
+                data_cache.add_value_at_index(idx, min_flow);
+            }
+            if let Some(idx) = self.recorder_idx_min_flow_shortfall {
+                data_cache.add_value_at_index(idx, (min_flow - self.dsflow_primary).max(0.0));
+            }
+        }
+
         // Reset upstream inflow for next timestep
         self.usflow = 0.0;
     }
@@ -1,10 +1,11 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use super::rainfall_weights::RainfallWeightHandler;
 use crate::hydrology::rainfall_runoff::gr4j::Gr4j;
 use crate::misc::misc_functions::make_result_name;
 use crate::model_inputs::DynamicInput;
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
+use crate::hydrology::constituents::NodeWater;
 use crate::misc::location::Location;
 use crate::numerical::opt::optimisable_component::OptimisableComponent;
 
@@ -14,11 +15,13 @@ const MAX_DS_LINKS: usize = 1;
 pub struct Gr4jNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub rain_mm_input: DynamicInput,
     pub evap_mm_input: DynamicInput,
     pub area_km2: f64,
     pub gr4j_model: Gr4j,
+    pub initial_stores: [f64; 2], // Production and routing store levels at the start of a run [mm]
 
     // Internal state only
     usflow: f64,
@@ -56,6 +59,15 @@ impl Gr4jNode {
             ..Default::default()
         }
     }
+
+    /// Sets the catchment area (km2), which must not be negative
+    pub fn set_area(&mut self, value: f64) -> Result<(), String> {
+        if !(value >= 0.0 && value.is_finite()) {
+            return Err(format!("Catchment area must be a non-negative number, but was {}", value));
+        }
+        self.area_km2 = value;
+        Ok(())
+    }
 }
 
 impl Node for Gr4jNode {
@@ -80,6 +92,14 @@ impl Node for Gr4jNode {
             let message = format!("Error in node '{}'. Catchment area cannot be negative, but was {}.", self.name, self.area_km2);
             return Err(message);
         }
+        if self.initial_stores.iter().any(|&v| v < 0.0) {
+            let message = format!("Error in node '{}'. Initial stores cannot be negative.", self.name);
+            return Err(message);
+        }
+
+        // Start the stores from their initial levels, limited to their capacities
+        self.gr4j_model.production_store = self.initial_stores[0].min(self.gr4j_model.x1);
+        self.gr4j_model.routing_store = self.initial_stores[1].min(self.gr4j_model.x3);
 
         // Initialize result recorders
         self.recorder_idx_usflow = data_cache.get_series_idx(
@@ -137,8 +157,8 @@ impl Node for Gr4jNode {
         }
 
         // Get driving data
-        self.rain = self.rain_mm_input.get_value(data_cache);
-        self.pet = self.evap_mm_input.get_value(data_cache);
+        self.rain = self.rain_mm_input.get_value_or_policy(data_cache, &self.name, "Rain");
+        self.pet = self.evap_mm_input.get_value_or_policy(data_cache, &self.name, "Evaporation");
 
         // Run GR4J model to get runoff
         self.runoff_depth_mm = self.gr4j_model.run_step(self.rain, self.pet);
@@ -206,6 +226,19 @@ impl Node for Gr4jNode {
     fn dsorders_mut(&mut self) -> &mut [f64] {
         &mut self.dsorders
     }
+
+    fn get_state(&self) -> Vec<f64> {
+        self.gr4j_model.get_state()
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        self.gr4j_model.set_state(state);
+    }
+
+    fn get_node_water(&self) -> NodeWater {
+        let baseflow = self.gr4j_model.routed_flow * self.area_km2;
+        NodeWater::Runoff { quickflow: self.runoff_volume_megs - baseflow, baseflow }
+    }
 }
 
 // ============================================================================
@@ -1,8 +1,9 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::model_inputs::DynamicInput;
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
+use crate::hydrology::constituents::NodeWater;
 use crate::misc::location::Location;
 
 const MAX_DS_LINKS: usize = 1;
@@ -11,9 +12,12 @@ const MAX_DS_LINKS: usize = 1;
 pub struct InflowNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub inflow_input: DynamicInput,
     pub expected_inflow_input: DynamicInput,
+    /// Constituent concentrations in the inflow, by lowercase constituent name (`conc_*` keys)
+    pub concentration_inputs: Vec<(String, DynamicInput)>,
 
     // Internal state only
     usflow: f64,
@@ -110,7 +114,7 @@ impl Node for InflowNode {
         }
 
         // Get lateral inflow
-        self.inflow_value = self.inflow_input.get_value(data_cache);
+        self.inflow_value = self.inflow_input.get_value_or_policy(data_cache, &self.name, "Inflow");
 
         // Compute outflow based on inflow
         self.dsflow_primary = self.usflow + self.inflow_value;
@@ -158,4 +162,14 @@ impl Node for InflowNode {
     fn dsorders_mut(&mut self) -> &mut [f64] {
         &mut self.dsorders
     }
+
+    fn get_node_water(&self) -> NodeWater {
+        NodeWater::PassThrough { lateral_inflow: self.inflow_value }
+    }
+
+    fn get_inflow_concentration(&self, constituent: &str, data_cache: &DataCache) -> f64 {
+        self.concentration_inputs.iter()
+            .find(|(name, _)| name == constituent)
+            .map_or(0.0, |(_, input)| input.get_value(data_cache))
+    }
 }
@@ -5,6 +5,14 @@ pub struct Link {
     pub to_node: usize,
     pub from_outlet: u8,  // 0 = primary, 1 = secondary, etc. u8::MAX=255
     pub to_inlet: u8,     // 0 = primary, 1 = secondary, etc. u8::MAX=255
+    /// Largest flow the link can carry (ML/timestep). Flow beyond it is lost.
+    pub capacity: Option<f64>,
+    /// Fraction of the flow into the link that is delivered at its end
+    pub efficiency: Option<f64>,
+    /// Delivered less conveyed over the run (i.e. minus the losses)
+    pub mbal: f64,
+    /// Event source named by the upstream node and outlet (e.g. "dam.ds_1")
+    pub event_source: usize,
 }
 
 impl Link {
@@ -14,10 +22,42 @@ impl Link {
             from_node,
             to_node,
             from_outlet,
-            to_inlet
+            to_inlet,
+            ..Default::default()
         }
     }
 
+    /// Conveys a flow along the link, returning `(delivered, excess)` where `excess` is the
+    /// part of the flow beyond the link's capacity. The excess and the delivery losses are
+    /// added to the link's mass balance. A missing (NaN) flow is passed on as it is.
+    pub fn convey(&mut self, flow: f64) -> (f64, f64) {
+        if flow.is_nan() {
+            return (flow, 0.0);
+        }
+        let carried = match self.capacity {
+            Some(capacity) => flow.min(capacity),
+            None => flow,
+        };
+        let delivered = carried * self.efficiency.unwrap_or(1.0);
+        self.mbal += delivered - flow;
+        (delivered, flow - carried)
+    }
+
+    /// The order to place on the upstream node for `order` to be delivered, limited to
+    /// what the link can carry
+    pub fn order_at_head(&self, order: f64) -> f64 {
+        let order = order / self.efficiency.unwrap_or(1.0);
+        match self.capacity {
+            Some(capacity) => order.min(capacity),
+            None => order,
+        }
+    }
+
+    /// Whether the link limits or loses any flow
+    pub fn has_attributes(&self) -> bool {
+        self.capacity.is_some() || self.efficiency.is_some()
+    }
+
     pub fn remove_flow(&mut self) -> f64 {
         let flow = self.flow;
         self.flow = 0.0;
@@ -1,25 +1,43 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::numerical::table::Table;
+use crate::numerical::fifo_buffer::FifoBuffer;
+use crate::model_inputs::DynamicInput;
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
 use crate::misc::location::Location;
 use crate::numerical::table_discontinuous::TableDiscontinuous;
+use crate::numerical::opt::optimisable_component::OptimisableComponent;
 
-const MAX_DS_LINKS: usize = 1;
+const MAX_DS_LINKS: usize = 2;
 
+/// Removes water from the flow according to a table of loss against inflow. The loss can
+/// vary through the year with `monthly_factors` (one per month, January first) and with a
+/// `loss_factor` expression, which both multiply the table's loss. A `return_fraction` of the
+/// loss re-enters the network on ds_2 after `return_lag` timesteps (e.g. treated effluent).
 #[derive(Default, Clone)]
 pub struct LossNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub loss_table: Table,  // Columns: Inflow ML, Loss ML
+    pub loss_scale: f64,    // Multiplies the loss column of loss_table
+    pub monthly_factors: Vec<f64>,  // Multiplies the loss in each month (empty = 1 all year)
+    pub loss_factor: DynamicInput,  // Multiplies the loss each timestep (none = 1)
+    pub return_fraction: f64,       // Fraction of the loss returned on ds_2
+    pub return_lag: usize,          // Timesteps before returned water reaches ds_2
     pub order_translation_table: TableDiscontinuous,
 
     // Internal state only
+    scaled_loss_table: Table,
     usflow: f64,
     dsflow_primary: f64,
+    ds_2_flow: f64,
     loss: f64,
+    factor_value: f64,
+    translation_factor: f64,
+    return_buffer: FifoBuffer,
 
     // Orders
     pub dsorders: [f64; MAX_DS_LINKS],
@@ -31,6 +49,8 @@ pub struct LossNode {
     recorder_idx_ds_1: Option<usize>,
     recorder_idx_ds_1_order: Option<usize>,
     recorder_idx_loss: Option<usize>,
+    recorder_idx_ds_2: Option<usize>,
+    recorder_idx_loss_factor: Option<usize>,
 }
 
 impl LossNode {
@@ -39,9 +59,62 @@ impl LossNode {
     pub fn new() -> Self {
         Self {
             name: "".to_string(),
+            loss_scale: 1.0,
+            loss_factor: DynamicInput::default(),
             ..Default::default()
         }
     }
+
+    /// Multiplier on the table's loss for the current timestep
+    fn current_factor(&self, data_cache: &DataCache) -> f64 {
+        let mut factor = match self.monthly_factors.is_empty() {
+            true => 1.0,
+            false => self.monthly_factors[data_cache.get_timestamp_month() as usize - 1],
+        };
+        if let DynamicInput::None { .. } = self.loss_factor {} else {
+            factor *= self.loss_factor.get_value(data_cache);
+        }
+        factor.max(0.0)
+    }
+
+    /// Builds the order_translation_table for a loss multiplied by `factor`, or returns an
+    /// empty table if the loss table is all loss and cannot satisfy any orders.
+    fn build_order_translation_table(&mut self, factor: f64) {
+        // I require that the loss function does not cause the outflow to decrease. However,
+        // multiple consecutive inflow values may still produce the same outflow, and therefore
+        // ambiguity still exists in how much to order upstream. Moreover, if the PWL has multiple
+        // segments with constant outflow, then the interpolation may depend on which segment the
+        // binary search lands on!
+        // The answer is to define a new type of PWL table which ALLOWS FOR NON-CONTINUOUS y values,
+        // and to use a binary search to find where xlo < x <= xhi, which will always give us the
+        // lowest possible order that produces the required outflow.
+        // A factor above 1 can make the outflow decrease, so each point's outflow is held at
+        // no less than the previous one's.
+        let mut points = Vec::with_capacity(self.scaled_loss_table.nrows());
+        let mut max_outflow: f64 = 0.0;
+        for row in 0..self.scaled_loss_table.nrows() {
+            let inflow = self.scaled_loss_table.get_value(row, 0);
+            let loss = (self.scaled_loss_table.get_value(row, 1) * factor).min(inflow);
+            max_outflow = max_outflow.max(inflow - loss);
+            points.push((max_outflow, inflow));
+        }
+
+        self.order_translation_table = TableDiscontinuous::new();
+        self.order_translation_table.add_point(-1.0, 0.0);
+        self.order_translation_table.add_point(0.0, 0.0);
+        if max_outflow > 0.0 {
+            for (outflow, inflow) in points {
+                self.order_translation_table.add_point(outflow, inflow);
+            }
+        } else {
+            // Loss table has 100% loss and cant satisfy any orders.
+            // The only reasonable thing to do is to make a new table with no orders.
+            self.order_translation_table.add_point(0.0, 0.0);
+            self.order_translation_table.add_point(1.0, 0.0);
+        }
+        self.order_translation_table.cap_if_unfinished();
+        self.translation_factor = factor;
+    }
 }
 
 impl Node for LossNode {
@@ -50,7 +123,23 @@ impl Node for LossNode {
         self.mbal = 0.0;
         self.usflow = 0.0;
         self.dsflow_primary = 0.0;
+        self.ds_2_flow = 0.0;
         self.loss = 0.0;
+        self.factor_value = 1.0;
+        self.return_buffer = FifoBuffer::new(self.return_lag);
+
+        // Checks
+        if !self.monthly_factors.is_empty() && self.monthly_factors.len() != 12 {
+            return Err(format!("Error in node '{}'. Loss monthly_factors must have 12 values, but had {}.",
+                               self.name, self.monthly_factors.len()));
+        }
+        if self.monthly_factors.iter().any(|f| !f.is_finite() || *f < 0.0) {
+            return Err(format!("Error in node '{}'. Loss monthly_factors must not be negative.", self.name));
+        }
+        if !(0.0..=1.0).contains(&self.return_fraction) {
+            return Err(format!("Error in node '{}'. Loss return_fraction must be between 0 and 1, but was {}.",
+                               self.name, self.return_fraction));
+        }
 
         // If the loss table is incomplete, fix it.
         match self.loss_table.nrows() {
@@ -75,60 +164,37 @@ impl Node for LossNode {
             _ => { }
         }
 
+        // Apply the loss scale to a working copy so the configured table is preserved
+        self.scaled_loss_table = self.loss_table.clone();
+        for row in 0..self.scaled_loss_table.nrows() {
+            let loss = self.scaled_loss_table.get_value(row, 1);
+            self.scaled_loss_table.set_value(row, 1, loss * self.loss_scale);
+        }
+
         // Check the loss table is well-behaved (see the matching Table assertions):
         //  - it must be monotonically increasing
         //  - it must start at zero inflow
         //  - it must not have negative values
         //  - it must not have loss > inflow
         //  - its slope must not exceed 1:1, i.e. outflow must not decrease
-        if let Err(e) = self.loss_table.assert_monotonically_increasing(0, 1) {
+        if let Err(e) = self.scaled_loss_table.assert_monotonically_increasing(0, 1) {
             return Err(format!("Node '{}' loss table. {}", self.name, e));
         }
-        if let Err(e) = self.loss_table.assert_starts_at_zero(0) {
+        if let Err(e) = self.scaled_loss_table.assert_starts_at_zero(0) {
             return Err(format!("Node '{}' loss table. {}", self.name, e));
         }
-        if let Err(e) = self.loss_table.assert_non_negative() {
+        if let Err(e) = self.scaled_loss_table.assert_non_negative() {
             return Err(format!("Node '{}' loss table. {}", self.name, e));
         }
-        if let Err(e) = self.loss_table.assert_col_not_exceeding(1, 0) {
+        if let Err(e) = self.scaled_loss_table.assert_col_not_exceeding(1, 0) {
             return Err(format!("Node '{}' loss table has loss exceeding inflow. {}", self.name, e));
         }
-        if let Err(e) = self.loss_table.assert_slope_not_exceeding_one(0, 1) {
+        if let Err(e) = self.scaled_loss_table.assert_slope_not_exceeding_one(0, 1) {
             return Err(format!("Node '{}' loss table slope exceeds 1:1 (outflow would decrease). {}", self.name, e));
         }
 
-        // The maximum outflow is the last row's (inflow - loss); the slope check
-        // guarantees (inflow - loss) is non-decreasing, so the last row is the max.
-        let last_row = self.loss_table.nrows() - 1;
-        let max_outflow = self.loss_table.get_value(last_row, 0) - self.loss_table.get_value(last_row, 1);
-
         // Build order_translation_table from loss_table (for lookups during ordering)
-        // I require that the loss function does not cause the outflow to decrease. However,
-        // multiple consecutive inflow values may still produce the same outflow, and therefore
-        // ambiguity still exists in how much to order upstream. Moreover, if the PWL has multiple
-        // segments with constant outflow, then the interpolation may depend on which segment the
-        // binary search lands on!
-        // The answer is to define a new type of PWL table which ALLOWS FOR NON-CONTINUOUS y values,
-        // and to use a binary search to find where xlo < x <= xhi, which will always give us the
-        // lowest possible order that produces the required outflow.
-        self.order_translation_table = TableDiscontinuous::new();
-        self.order_translation_table.add_point(-1.0, 0.0);
-        self.order_translation_table.add_point(0.0, 0.0);
-        if max_outflow > 0.0 {
-            for row in 0..self.loss_table.nrows() {
-                let inflow = self.loss_table.get_value(row, 0);
-                let loss = self.loss_table.get_value(row, 1);
-                let outflow = inflow - loss;
-                self.order_translation_table.add_point(outflow, inflow);
-            }
-        } else {
-            // Loss table has 100% loss and cant satisfy any orders.
-            // The only reasonable thing to do is to make a new table with no orders.
-            //self.order_translation_table = TableDiscontinuous::new();
-            self.order_translation_table.add_point(0.0, 0.0);
-            self.order_translation_table.add_point(1.0, 0.0);
-        }
-        self.order_translation_table.cap_if_unfinished();
+        self.build_order_translation_table(1.0);
 
         // Initialize result recorders
         self.recorder_idx_usflow = data_cache.get_series_idx(
@@ -146,6 +212,12 @@ impl Node for LossNode {
         self.recorder_idx_loss = data_cache.get_series_idx(
             make_result_name(&self.name, "loss").as_str(), false
         );
+        self.recorder_idx_ds_2 = data_cache.get_series_idx(
+            make_result_name(&self.name, "ds_2").as_str(), false
+        );
+        self.recorder_idx_loss_factor = data_cache.get_series_idx(
+            make_result_name(&self.name, "loss_factor").as_str(), false
+        );
 
         // Return
         Ok(())
@@ -162,7 +234,11 @@ impl Node for LossNode {
             data_cache.add_value_at_index(idx, self.dsorders[0]);
         }
 
-        // Calculate usorders
+        // Calculate usorders, translating through today's loss
+        let factor = self.current_factor(data_cache);
+        if factor != self.translation_factor {
+            self.build_order_translation_table(factor);
+        }
         self.usorders = self.order_translation_table.interpolate_or_extrapolate(self.dsorders[0]);
     }
 
@@ -175,7 +251,8 @@ impl Node for LossNode {
 
         // Calculate loss flow from table (inflow rate -> loss rate)
         //let attempted_loss = self.loss_table.interpolate(0, 1, self.usflow).min(self.usflow);
-        let attempted_loss = self.loss_table.interpolate_or_extrapolate(0, 1, self.usflow);
+        self.factor_value = self.current_factor(data_cache);
+        let attempted_loss = self.scaled_loss_table.interpolate_or_extrapolate(0, 1, self.usflow) * self.factor_value;
         self.loss = attempted_loss.max(0f64).min(self.usflow);
 
         // Remaining flow after loss goes to ds_1
@@ -185,12 +262,15 @@ impl Node for LossNode {
             panic!("Negative downstream flow at '{}' when usflow={}, loss={}", self.name, self.usflow, self.loss);
         }
 
-        // Update mass balance
-        self.mbal -= self.loss;
+        // Part of the loss comes back on ds_2 after the return lag
+        self.ds_2_flow = self.return_buffer.push(self.loss * self.return_fraction);
+
+        // Update mass balance (returns in transit count as lost until they are released)
+        self.mbal += self.dsflow_primary + self.ds_2_flow - self.usflow;
 
         // Record results
         if let Some(idx) = self.recorder_idx_dsflow {
-            data_cache.add_value_at_index(idx, self.dsflow_primary);
+            data_cache.add_value_at_index(idx, self.dsflow_primary + self.ds_2_flow);
         }
         if let Some(idx) = self.recorder_idx_ds_1 {
             data_cache.add_value_at_index(idx, self.dsflow_primary);
@@ -201,6 +281,12 @@ impl Node for LossNode {
         if let Some(idx) = self.recorder_idx_loss {
             data_cache.add_value_at_index(idx, self.loss);
         }
+        if let Some(idx) = self.recorder_idx_ds_2 {
+            data_cache.add_value_at_index(idx, self.ds_2_flow);
+        }
+        if let Some(idx) = self.recorder_idx_loss_factor {
+            data_cache.add_value_at_index(idx, self.factor_value);
+        }
 
         // Reset upstream inflow for next timestep
         self.usflow = 0.0;
@@ -217,6 +303,11 @@ impl Node for LossNode {
                 self.dsflow_primary = 0.0;
                 outflow
             }
+            1 => {
+                let outflow = self.ds_2_flow;
+                self.ds_2_flow = 0.0;
+                outflow
+            }
             _ => 0.0,
         }
     }
@@ -228,4 +319,32 @@ impl Node for LossNode {
     fn dsorders_mut(&mut self) -> &mut [f64] {
         &mut self.dsorders
     }
-}
\ No newline at end of file
+}
+
+
+// ============================================================================
+// OptimisableComponent Implementation
+// ============================================================================
+
+impl OptimisableComponent for LossNode {
+    fn set_param(&mut self, name: &str, value: f64) -> Result<(), String> {
+        match name {
+            "loss_scale" => {
+                self.loss_scale = value;
+                Ok(())
+            },
+            _ => Err(format!("Unknown loss parameter: {}", name)),
+        }
+    }
+
+    fn get_param(&self, name: &str) -> Result<f64, String> {
+        match name {
+            "loss_scale" => Ok(self.loss_scale),
+            _ => Err(format!("Unknown loss parameter: {}", name)),
+        }
+    }
+
+    fn list_params(&self) -> Vec<String> {
+        vec!["loss_scale".to_string()]
+    }
+}
@@ -2,12 +2,17 @@ pub use node_trait::Node;
 pub use link::Link;
 pub use node_enum::NodeEnum;
 
+/// User-defined tags on a node (e.g. region, gauge ID, owner), read from `meta_*` keys in
+/// the INI file. Keys are stored lowercase without the prefix, in file order.
+pub type NodeMetadata = indexmap::IndexMap<String, String>;
+
 //List all the submodules here
 pub mod blackhole_node;
 pub mod confluence_node;
 pub mod gauge_node;
 pub mod loss_node;
 pub mod splitter_node;
+pub mod transfer_node;
 pub mod gr4j_node;
 pub mod inflow_node;
 pub mod storage_node;
@@ -1,6 +1,9 @@
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
-use crate::nodes::{Node, blackhole_node::BlackholeNode, confluence_node::ConfluenceNode, gauge_node::GaugeNode, loss_node::LossNode, splitter_node::SplitterNode, unregulated_user_node::UnregulatedUserNode, regulated_user_node::RegulatedUserNode, gr4j_node::Gr4jNode, inflow_node::InflowNode, routing_node::RoutingNode, sacramento_node::SacramentoNode, storage_node::StorageNode, order_control_node::OrderControlNode};
+use crate::hydrology::constituents::NodeWater;
+use crate::misc::location::Location;
+use crate::model_inputs::DynamicInput;
+use crate::nodes::{Node, NodeMetadata, blackhole_node::BlackholeNode, confluence_node::ConfluenceNode, gauge_node::GaugeNode, loss_node::LossNode, splitter_node::SplitterNode, transfer_node::TransferNode, unregulated_user_node::UnregulatedUserNode, regulated_user_node::RegulatedUserNode, gr4j_node::Gr4jNode, inflow_node::InflowNode, routing_node::RoutingNode, sacramento_node::SacramentoNode, storage_node::StorageNode, order_control_node::OrderControlNode};
 
 #[derive(Clone)]
 pub enum NodeEnum {
@@ -9,6 +12,7 @@ pub enum NodeEnum {
     GaugeNode(GaugeNode),
     LossNode(LossNode),
     SplitterNode(SplitterNode),
+    TransferNode(TransferNode),
     UnregulatedUserNode(UnregulatedUserNode),
     RegulatedUserNode(RegulatedUserNode),
     Gr4jNode(Gr4jNode),
@@ -27,6 +31,7 @@ impl NodeEnum {
             NodeEnum::GaugeNode(_) => "gauge".to_string(),
             NodeEnum::LossNode(_) => "loss".to_string(),
             NodeEnum::SplitterNode(_) => "splitter".to_string(),
+            NodeEnum::TransferNode(_) => "transfer".to_string(),
             NodeEnum::UnregulatedUserNode(_) => "unregulated_user".to_string(),
             NodeEnum::RegulatedUserNode(_) => "regulated_user".to_string(),
             NodeEnum::Gr4jNode(_) => "gr4j".to_string(),
@@ -37,6 +42,134 @@ impl NodeEnum {
             NodeEnum::OrderControlNode(_) => "order_control".to_string(),
         }
     }
+
+    pub fn get_location(&self) -> &Location {
+        match self {
+            NodeEnum::BlackholeNode(node) => &node.location,
+            NodeEnum::ConfluenceNode(node) => &node.location,
+            NodeEnum::GaugeNode(node) => &node.location,
+            NodeEnum::LossNode(node) => &node.location,
+            NodeEnum::SplitterNode(node) => &node.location,
+            NodeEnum::TransferNode(node) => &node.location,
+            NodeEnum::UnregulatedUserNode(node) => &node.location,
+            NodeEnum::RegulatedUserNode(node) => &node.location,
+            NodeEnum::Gr4jNode(node) => &node.location,
+            NodeEnum::InflowNode(node) => &node.location,
+            NodeEnum::RoutingNode(node) => &node.location,
+            NodeEnum::SacramentoNode(node) => &node.location,
+            NodeEnum::StorageNode(node) => &node.location,
+            NodeEnum::OrderControlNode(node) => &node.location,
+        }
+    }
+
+    pub fn get_metadata(&self) -> &NodeMetadata {
+        match self {
+            NodeEnum::BlackholeNode(node) => &node.metadata,
+            NodeEnum::ConfluenceNode(node) => &node.metadata,
+            NodeEnum::GaugeNode(node) => &node.metadata,
+            NodeEnum::LossNode(node) => &node.metadata,
+            NodeEnum::SplitterNode(node) => &node.metadata,
+            NodeEnum::TransferNode(node) => &node.metadata,
+            NodeEnum::UnregulatedUserNode(node) => &node.metadata,
+            NodeEnum::RegulatedUserNode(node) => &node.metadata,
+            NodeEnum::Gr4jNode(node) => &node.metadata,
+            NodeEnum::InflowNode(node) => &node.metadata,
+            NodeEnum::RoutingNode(node) => &node.metadata,
+            NodeEnum::SacramentoNode(node) => &node.metadata,
+            NodeEnum::StorageNode(node) => &node.metadata,
+            NodeEnum::OrderControlNode(node) => &node.metadata,
+        }
+    }
+
+    pub fn get_metadata_mut(&mut self) -> &mut NodeMetadata {
+        match self {
+            NodeEnum::BlackholeNode(node) => &mut node.metadata,
+            NodeEnum::ConfluenceNode(node) => &mut node.metadata,
+            NodeEnum::GaugeNode(node) => &mut node.metadata,
+            NodeEnum::LossNode(node) => &mut node.metadata,
+            NodeEnum::SplitterNode(node) => &mut node.metadata,
+            NodeEnum::TransferNode(node) => &mut node.metadata,
+            NodeEnum::UnregulatedUserNode(node) => &mut node.metadata,
+            NodeEnum::RegulatedUserNode(node) => &mut node.metadata,
+            NodeEnum::Gr4jNode(node) => &mut node.metadata,
+            NodeEnum::InflowNode(node) => &mut node.metadata,
+            NodeEnum::RoutingNode(node) => &mut node.metadata,
+            NodeEnum::SacramentoNode(node) => &mut node.metadata,
+            NodeEnum::StorageNode(node) => &mut node.metadata,
+            NodeEnum::OrderControlNode(node) => &mut node.metadata,
+        }
+    }
+
+    /// The node's expression inputs, each with the INI key it is read from
+    pub fn dynamic_inputs_mut(&mut self) -> Vec<(String, &mut DynamicInput)> {
+        let inputs: Vec<(&str, &mut DynamicInput)> = match self {
+            NodeEnum::BlackholeNode(_) => vec![],
+            NodeEnum::ConfluenceNode(node) => vec![("harmony_fraction", &mut node.harmony_fraction)],
+            NodeEnum::GaugeNode(node) => vec![
+                ("force_flow", &mut node.force_flow_input),
+                ("reference_flow", &mut node.reference_flow_input),
+                ("reference_level", &mut node.reference_level_input),
+                ("quality", &mut node.quality_input),
+                ("min_flow", &mut node.min_flow_input),
+                ("residual", &mut node.residual_input),
+            ],
+            NodeEnum::LossNode(node) => vec![("loss_factor", &mut node.loss_factor)],
+            NodeEnum::SplitterNode(node) => vec![
+                ("ds_2_flow", &mut node.ds_2_flow_input),
+                ("ds_2_target", &mut node.ds_2_target_input),
+                ("ds_2_min", &mut node.ds_2_min_input),
+                ("ds_2_max", &mut node.ds_2_max_input),
+            ],
+            NodeEnum::TransferNode(node) => vec![
+                ("capacity", &mut node.capacity),
+                ("trigger", &mut node.trigger),
+            ],
+            NodeEnum::UnregulatedUserNode(node) => vec![
+                ("demand", &mut node.demand_input),
+                ("pump", &mut node.pump_capacity),
+                ("flow_threshold", &mut node.flow_threshold),
+                ("commence_threshold", &mut node.commence_threshold),
+            ],
+            NodeEnum::RegulatedUserNode(node) => vec![
+                ("order", &mut node.order_input),
+                ("pump", &mut node.pump_capacity),
+                ("off_allocation", &mut node.off_allocation_trigger),
+                ("off_allocation_volume", &mut node.off_allocation_volume),
+            ],
+            NodeEnum::Gr4jNode(node) => vec![
+                ("rain", &mut node.rain_mm_input),
+                ("evap", &mut node.evap_mm_input),
+            ],
+            NodeEnum::InflowNode(node) => {
+                let mut inputs = vec![
+                    ("inflow".to_string(), &mut node.inflow_input),
+                    ("expected_inflow".to_string(), &mut node.expected_inflow_input),
+                ];
+                for (constituent, input) in node.concentration_inputs.iter_mut() {
+                    inputs.push((format!("conc_{}", constituent), input));
+                }
+                return inputs;
+            }
+            NodeEnum::RoutingNode(_) => vec![],
+            NodeEnum::SacramentoNode(node) => vec![
+                ("rain", &mut node.rain_mm_input),
+                ("evap", &mut node.evap_mm_input),
+            ],
+            NodeEnum::StorageNode(node) => vec![
+                ("rain", &mut node.rain_mm_input),
+                ("evap", &mut node.evap_mm_input),
+                ("seep", &mut node.seep_mm_input),
+                ("pond_demand", &mut node.pond_demand_input),
+                ("target_level", &mut node.target_level),
+            ],
+            NodeEnum::OrderControlNode(node) => vec![
+                ("min_order", &mut node.min_order_input),
+                ("max_order", &mut node.max_order_input),
+                ("set_order", &mut node.set_order_input),
+            ],
+        };
+        inputs.into_iter().map(|(key, input)| (key.to_string(), input)).collect()
+    }
 }
 
 impl Node for NodeEnum {
@@ -47,6 +180,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.initialise(data_cache, account_manager),
             NodeEnum::LossNode(node) => node.initialise(data_cache, account_manager),
             NodeEnum::SplitterNode(node) => node.initialise(data_cache, account_manager),
+            NodeEnum::TransferNode(node) => node.initialise(data_cache, account_manager),
             NodeEnum::UnregulatedUserNode(node) => node.initialise(data_cache, account_manager),
             NodeEnum::RegulatedUserNode(node) => node.initialise(data_cache, account_manager),
             NodeEnum::Gr4jNode(node) => node.initialise(data_cache, account_manager),
@@ -65,6 +199,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.get_name(),
             NodeEnum::LossNode(node) => node.get_name(),
             NodeEnum::SplitterNode(node) => node.get_name(),
+            NodeEnum::TransferNode(node) => node.get_name(),
             NodeEnum::UnregulatedUserNode(node) => node.get_name(),
             NodeEnum::RegulatedUserNode(node) => node.get_name(),
             NodeEnum::Gr4jNode(node) => node.get_name(),
@@ -83,6 +218,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.run_order_phase(data_cache),
             NodeEnum::LossNode(node) => node.run_order_phase(data_cache),
             NodeEnum::SplitterNode(node) => node.run_order_phase(data_cache),
+            NodeEnum::TransferNode(node) => node.run_order_phase(data_cache),
             NodeEnum::UnregulatedUserNode(node) => node.run_order_phase(data_cache),
             NodeEnum::RegulatedUserNode(node) => node.run_order_phase(data_cache),
             NodeEnum::Gr4jNode(node) => node.run_order_phase(data_cache),
@@ -94,6 +230,25 @@ impl Node for NodeEnum {
         }
     }
 
+    fn requires_order_phase(&self) -> bool {
+        match self {
+            NodeEnum::BlackholeNode(node) => node.requires_order_phase(),
+            NodeEnum::ConfluenceNode(node) => node.requires_order_phase(),
+            NodeEnum::GaugeNode(node) => node.requires_order_phase(),
+            NodeEnum::LossNode(node) => node.requires_order_phase(),
+            NodeEnum::SplitterNode(node) => node.requires_order_phase(),
+            NodeEnum::TransferNode(node) => node.requires_order_phase(),
+            NodeEnum::UnregulatedUserNode(node) => node.requires_order_phase(),
+            NodeEnum::RegulatedUserNode(node) => node.requires_order_phase(),
+            NodeEnum::Gr4jNode(node) => node.requires_order_phase(),
+            NodeEnum::InflowNode(node) => node.requires_order_phase(),
+            NodeEnum::RoutingNode(node) => node.requires_order_phase(),
+            NodeEnum::SacramentoNode(node) => node.requires_order_phase(),
+            NodeEnum::StorageNode(node) => node.requires_order_phase(),
+            NodeEnum::OrderControlNode(node) => node.requires_order_phase(),
+        }
+    }
+
     fn run_flow_phase(&mut self, data_cache: &mut DataCache, account_manager: &mut AccountManager) {
         match self {
             NodeEnum::BlackholeNode(node) => node.run_flow_phase(data_cache, account_manager),
@@ -101,6 +256,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.run_flow_phase(data_cache, account_manager),
             NodeEnum::LossNode(node) => node.run_flow_phase(data_cache, account_manager),
             NodeEnum::SplitterNode(node) => node.run_flow_phase(data_cache, account_manager),
+            NodeEnum::TransferNode(node) => node.run_flow_phase(data_cache, account_manager),
             NodeEnum::UnregulatedUserNode(node) => node.run_flow_phase(data_cache, account_manager),
             NodeEnum::RegulatedUserNode(node) => node.run_flow_phase(data_cache, account_manager),
             NodeEnum::Gr4jNode(node) => node.run_flow_phase(data_cache, account_manager),
@@ -119,6 +275,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.add_usflow(flow, inlet),
             NodeEnum::LossNode(node) => node.add_usflow(flow, inlet),
             NodeEnum::SplitterNode(node) => node.add_usflow(flow, inlet),
+            NodeEnum::TransferNode(node) => node.add_usflow(flow, inlet),
             NodeEnum::UnregulatedUserNode(node) => node.add_usflow(flow, inlet),
             NodeEnum::RegulatedUserNode(node) => node.add_usflow(flow, inlet),
             NodeEnum::Gr4jNode(node) => node.add_usflow(flow, inlet),
@@ -137,6 +294,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.remove_dsflow(outlet),
             NodeEnum::LossNode(node) => node.remove_dsflow(outlet),
             NodeEnum::SplitterNode(node) => node.remove_dsflow(outlet),
+            NodeEnum::TransferNode(node) => node.remove_dsflow(outlet),
             NodeEnum::UnregulatedUserNode(node) => node.remove_dsflow(outlet),
             NodeEnum::RegulatedUserNode(node) => node.remove_dsflow(outlet),
             NodeEnum::Gr4jNode(node) => node.remove_dsflow(outlet),
@@ -155,6 +313,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.get_mass_balance(),
             NodeEnum::LossNode(node) => node.get_mass_balance(),
             NodeEnum::SplitterNode(node) => node.get_mass_balance(),
+            NodeEnum::TransferNode(node) => node.get_mass_balance(),
             NodeEnum::UnregulatedUserNode(node) => node.get_mass_balance(),
             NodeEnum::RegulatedUserNode(node) => node.get_mass_balance(),
             NodeEnum::Gr4jNode(node) => node.get_mass_balance(),
@@ -173,6 +332,7 @@ impl Node for NodeEnum {
             NodeEnum::GaugeNode(node) => node.dsorders_mut(),
             NodeEnum::LossNode(node) => node.dsorders_mut(),
             NodeEnum::SplitterNode(node) => node.dsorders_mut(),
+            NodeEnum::TransferNode(node) => node.dsorders_mut(),
             NodeEnum::UnregulatedUserNode(node) => node.dsorders_mut(),
             NodeEnum::RegulatedUserNode(node) => node.dsorders_mut(),
             NodeEnum::Gr4jNode(node) => node.dsorders_mut(),
@@ -183,5 +343,80 @@ impl Node for NodeEnum {
             NodeEnum::OrderControlNode(node) => node.dsorders_mut(),
         }
     }
-}
 
+    fn get_node_water(&self) -> NodeWater {
+        match self {
+            NodeEnum::BlackholeNode(node) => node.get_node_water(),
+            NodeEnum::ConfluenceNode(node) => node.get_node_water(),
+            NodeEnum::GaugeNode(node) => node.get_node_water(),
+            NodeEnum::LossNode(node) => node.get_node_water(),
+            NodeEnum::SplitterNode(node) => node.get_node_water(),
+            NodeEnum::TransferNode(node) => node.get_node_water(),
+            NodeEnum::UnregulatedUserNode(node) => node.get_node_water(),
+            NodeEnum::RegulatedUserNode(node) => node.get_node_water(),
+            NodeEnum::Gr4jNode(node) => node.get_node_water(),
+            NodeEnum::InflowNode(node) => node.get_node_water(),
+            NodeEnum::RoutingNode(node) => node.get_node_water(),
+            NodeEnum::SacramentoNode(node) => node.get_node_water(),
+            NodeEnum::StorageNode(node) => node.get_node_water(),
+            NodeEnum::OrderControlNode(node) => node.get_node_water(),
+        }
+    }
+
+    fn get_inflow_concentration(&self, constituent: &str, data_cache: &DataCache) -> f64 {
+        match self {
+            NodeEnum::BlackholeNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::ConfluenceNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::GaugeNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::LossNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::SplitterNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::TransferNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::UnregulatedUserNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::RegulatedUserNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::Gr4jNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::InflowNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::RoutingNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::SacramentoNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::StorageNode(node) => node.get_inflow_concentration(constituent, data_cache),
+            NodeEnum::OrderControlNode(node) => node.get_inflow_concentration(constituent, data_cache),
+        }
+    }
+
+    fn get_state(&self) -> Vec<f64> {
+        match self {
+            NodeEnum::BlackholeNode(node) => node.get_state(),
+            NodeEnum::ConfluenceNode(node) => node.get_state(),
+            NodeEnum::GaugeNode(node) => node.get_state(),
+            NodeEnum::LossNode(node) => node.get_state(),
+            NodeEnum::SplitterNode(node) => node.get_state(),
+            NodeEnum::TransferNode(node) => node.get_state(),
+            NodeEnum::UnregulatedUserNode(node) => node.get_state(),
+            NodeEnum::RegulatedUserNode(node) => node.get_state(),
+            NodeEnum::Gr4jNode(node) => node.get_state(),
+            NodeEnum::InflowNode(node) => node.get_state(),
+            NodeEnum::RoutingNode(node) => node.get_state(),
+            NodeEnum::SacramentoNode(node) => node.get_state(),
+            NodeEnum::StorageNode(node) => node.get_state(),
+            NodeEnum::OrderControlNode(node) => node.get_state(),
+        }
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        match self {
+            NodeEnum::BlackholeNode(node) => node.set_state(state),
+            NodeEnum::ConfluenceNode(node) => node.set_state(state),
+            NodeEnum::GaugeNode(node) => node.set_state(state),
+            NodeEnum::LossNode(node) => node.set_state(state),
+            NodeEnum::SplitterNode(node) => node.set_state(state),
+            NodeEnum::TransferNode(node) => node.set_state(state),
+            NodeEnum::UnregulatedUserNode(node) => node.set_state(state),
+            NodeEnum::RegulatedUserNode(node) => node.set_state(state),
+            NodeEnum::Gr4jNode(node) => node.set_state(state),
+            NodeEnum::InflowNode(node) => node.set_state(state),
+            NodeEnum::RoutingNode(node) => node.set_state(state),
+            NodeEnum::SacramentoNode(node) => node.set_state(state),
+            NodeEnum::StorageNode(node) => node.set_state(state),
+            NodeEnum::OrderControlNode(node) => node.set_state(state),
+        }
+    }
+}
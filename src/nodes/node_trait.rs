@@ -1,16 +1,37 @@
 use dyn_clone::{clone_trait_object, DynClone};
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
+use crate::hydrology::constituents::NodeWater;
 
 pub trait Node: DynClone + Sync + Send {
     fn initialise(&mut self, data_cache: &mut DataCache, account_manager: &mut AccountManager) -> Result<(),String>;
     fn get_name(&self) -> &str;
     fn run_order_phase(&mut self, _data_cache: &mut DataCache) {}
+
+    /// Nodes that generate orders (rather than just pass them upstream) return true. The
+    /// ordering system then visits them, and everything upstream of them in a regulated zone,
+    /// during the ordering phase before the flow phase runs.
+    fn requires_order_phase(&self) -> bool { false }
     fn run_flow_phase(&mut self, data_cache: &mut DataCache, account_manager: &mut AccountManager);
     fn add_usflow(&mut self, flow: f64, inlet: u8);
     fn remove_dsflow(&mut self, outlet: u8) -> f64;
     fn get_mass_balance(&self) -> f64;
     fn dsorders_mut(&mut self) -> &mut [f64];
+
+    /// How water moved through the node in the last flow phase, for mixing constituents.
+    /// Nodes that hold water or add it to the flow override this.
+    fn get_node_water(&self) -> NodeWater { NodeWater::PassThrough { lateral_inflow: 0.0 } }
+
+    /// Concentration of a constituent in the lateral inflow reported by `get_node_water`
+    fn get_inflow_concentration(&self, _constituent: &str, _data_cache: &DataCache) -> f64 { 0.0 }
+
+    /// The water held in the node's stores at the end of the last timestep, e.g. a storage's
+    /// volume, so a spin-up can carry the state it reaches into the run. Nodes without stores
+    /// return nothing.
+    fn get_state(&self) -> Vec<f64> { vec![] }
+
+    /// Restores the stores from a `get_state` of the same node. Called after `initialise`.
+    fn set_state(&mut self, _state: &[f64]) {}
 }
 
 clone_trait_object!(Node);
@@ -1,4 +1,4 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
@@ -12,6 +12,7 @@ const MAX_DS_LINKS: usize = 1;
 pub struct OrderControlNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
 
     // Properties
@@ -115,6 +116,10 @@ impl Node for OrderControlNode {
         &self.name
     }
 
+    fn requires_order_phase(&self) -> bool {
+        true
+    }
+
     fn run_order_phase(&mut self, data_cache: &mut DataCache) {
 
         // Record downstream orders
@@ -1,10 +1,12 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::model_inputs::DynamicInput;
 use crate::data_management::data_cache::DataCache;
+use crate::data_management::events::EventKind;
 use crate::hydrology::accounts::account_manager::AccountManager;
 use crate::misc::location::Location;
 use crate::numerical::fifo_buffer::FifoBuffer;
+use crate::numerical::opt::optimisable_component::OptimisableComponent;
 
 const MAX_DS_LINKS: usize = 1;
 
@@ -14,14 +16,28 @@ pub struct RegulatedUserNode {
     // Properties - basic
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub order_input: DynamicInput,
+    pub demand_multiplier: f64,
+    /// Demand factor of the active `[policies]` stage, set each timestep (1 when unrestricted)
+    pub policy_demand_factor: f64,
+    /// Most the user may order, set each timestep from its `[allocation]` account (infinite
+    /// when it has none)
+    pub account_limit: f64,
 
     // Properties - regulated user stuff
     pub order_travel_time: usize,
     pub order_value: f64, //Captured during the ordering phase if in regulated zones
     pub order_buffer: FifoBuffer,
     pub pump_capacity: DynamicInput,
+    pub priority: u32,      // For sharing shortfalls by priority (1 is served first)
+    pub entitlement: f64,   // For sharing shortfalls pro-rata by entitlement
+
+    // Properties - off-allocation access. While the trigger is positive the user may also
+    // divert up to the announced volume on top of its order.
+    pub off_allocation_trigger: DynamicInput,
+    pub off_allocation_volume: DynamicInput,
 
     // Internal state only
     pub dsorders: [f64; MAX_DS_LINKS],
@@ -29,15 +45,23 @@ pub struct RegulatedUserNode {
     usflow: f64,
     dsflow_primary: f64,
     diversion: f64,
+    off_allocation_diversion: f64,
+    off_allocation_volume_value: f64,
     pump_capacity_value: f64,
 
+    // Source index of the events the node emits
+    event_source: usize,
+
     // Recorders
     recorder_idx_usflow: Option<usize>,
     recorder_idx_pump_capacity: Option<usize>,
     recorder_idx_order: Option<usize>,
+    recorder_idx_allocation: Option<usize>,
     recorder_idx_order_due: Option<usize>,
     recorder_idx_demand: Option<usize>,
     recorder_idx_diversion: Option<usize>,
+    recorder_idx_off_allocation_volume: Option<usize>,
+    recorder_idx_off_allocation_diversion: Option<usize>,
     recorder_idx_dsflow: Option<usize>,
     recorder_ids_ds_1: Option<usize>,
     recorder_idx_ds_1_order: Option<usize>,
@@ -52,10 +76,43 @@ impl RegulatedUserNode {
             name: "".to_string(),
             pump_capacity: DynamicInput::default(),
             order_input: DynamicInput::default(),
+            off_allocation_trigger: DynamicInput::default(),
+            off_allocation_volume: DynamicInput::default(),
+            demand_multiplier: 1.0,
+            policy_demand_factor: 1.0,
+            account_limit: f64::INFINITY,
             order_buffer: FifoBuffer::default(),
+            priority: 1,
             ..Default::default()
         }
     }
+
+    /// Cuts today's order back to `allocation` when the supply storage is short. Called by
+    /// the ordering system after the order phase (see ordering::shortfall_sharing).
+    pub fn restrict_order(&mut self, allocation: f64, data_cache: &mut DataCache) {
+        if allocation >= self.order_value {
+            return;
+        }
+        self.order_value = allocation;
+        if !self.order_buffer.set_back(allocation) {
+            // No travel time, so the order is due today
+            self.order_due = allocation;
+            if let Some(idx) = self.recorder_idx_order_due {
+                data_cache.add_value_at_index(idx, self.order_due);
+            }
+            if let Some(idx) = self.recorder_idx_demand {
+                data_cache.add_value_at_index(idx, self.order_due);
+            }
+        }
+        if let Some(idx) = self.recorder_idx_allocation {
+            data_cache.add_value_at_index(idx, allocation);
+        }
+    }
+
+    /// Water diverted against the order this timestep (not including off-allocation water)
+    pub fn diversion(&self) -> f64 {
+        self.diversion
+    }
 }
 
 impl Node for RegulatedUserNode {
@@ -65,13 +122,24 @@ impl Node for RegulatedUserNode {
         self.usflow = 0.0;
         self.dsflow_primary = 0.0;
         self.diversion = 0.0;
+        self.off_allocation_diversion = 0.0;
+        self.off_allocation_volume_value = 0.0;
         self.pump_capacity_value = f64::INFINITY;
+        self.policy_demand_factor = 1.0;
+        self.account_limit = f64::INFINITY;
 
         // Checks
-        // None
+        let has_trigger = !matches!(self.off_allocation_trigger, DynamicInput::None { .. });
+        let has_volume = !matches!(self.off_allocation_volume, DynamicInput::None { .. });
+        if has_trigger != has_volume {
+            let message = format!("Error in node '{}'. Off-allocation access needs both 'off_allocation' and 'off_allocation_volume'.", self.name);
+            return Err(message);
+        }
 
         // DynamicInput is already initialized during parsing
 
+        self.event_source = data_cache.add_event_source(&self.name);
+
         // Initialize result recorders
         self.recorder_idx_usflow = data_cache.get_series_idx(
             make_result_name(&self.name, "usflow").as_str(), false
@@ -82,6 +150,9 @@ impl Node for RegulatedUserNode {
         self.recorder_idx_order = data_cache.get_series_idx(
             make_result_name(&self.name, "order").as_str(), false
         );
+        self.recorder_idx_allocation = data_cache.get_series_idx(
+            make_result_name(&self.name, "allocation").as_str(), false
+        );
         self.recorder_idx_order_due = data_cache.get_series_idx(
             make_result_name(&self.name, "order_due").as_str(), false
         );
@@ -91,6 +162,12 @@ impl Node for RegulatedUserNode {
         self.recorder_idx_diversion = data_cache.get_series_idx(
             make_result_name(&self.name, "diversion").as_str(), false
         );
+        self.recorder_idx_off_allocation_volume = data_cache.get_series_idx(
+            make_result_name(&self.name, "off_allocation_volume").as_str(), false
+        );
+        self.recorder_idx_off_allocation_diversion = data_cache.get_series_idx(
+            make_result_name(&self.name, "off_allocation_diversion").as_str(), false
+        );
         self.recorder_idx_dsflow = data_cache.get_series_idx(
             make_result_name(&self.name, "dsflow").as_str(), false
         );
@@ -108,6 +185,10 @@ impl Node for RegulatedUserNode {
     fn get_name(&self) -> &str { &self.name }
 
 
+    fn requires_order_phase(&self) -> bool {
+        true
+    }
+
     fn run_order_phase(&mut self, data_cache: &mut DataCache) {
 
         // Record downstream orders
@@ -115,7 +196,8 @@ impl Node for RegulatedUserNode {
             data_cache.add_value_at_index(idx, self.dsorders[0]);
         }
 
-        self.order_value = self.order_input.get_value(data_cache);
+        self.order_value = (self.order_input.get_value_or_policy(data_cache, &self.name, "Order") * self.demand_multiplier * self.policy_demand_factor)
+            .min(self.account_limit);
 
         // TODO: is this where things are supposed to happen?
 
@@ -126,6 +208,9 @@ impl Node for RegulatedUserNode {
         if let Some(idx) = self.recorder_idx_order {
             data_cache.add_value_at_index(idx, self.order_value);
         }
+        if let Some(idx) = self.recorder_idx_allocation {
+            data_cache.add_value_at_index(idx, self.order_value);
+        }
         if let Some(idx) = self.recorder_idx_order_due {
             data_cache.add_value_at_index(idx, self.order_due);
         }
@@ -156,15 +241,35 @@ impl Node for RegulatedUserNode {
         // Determine the diversion value
         // assume demand = order_due
         self.diversion = self.order_due.min(available);
+        if self.diversion < self.order_due {
+            data_cache.emit_event(EventKind::DemandShortfall, self.event_source, self.order_due - self.diversion);
+        }
+
+        // Take off-allocation water on top of the order while it is announced
+        self.off_allocation_volume_value = match self.off_allocation_trigger {
+            DynamicInput::None { .. } => 0.0,
+            _ if self.off_allocation_trigger.get_value(data_cache) > 0.0 => {
+                self.off_allocation_volume.get_value(data_cache).max(0.0)
+            }
+            _ => 0.0,
+        };
+        self.off_allocation_diversion = self.off_allocation_volume_value.min(available - self.diversion).max(0.0);
 
         // Extract the water and update mbal
-        self.dsflow_primary = self.usflow - self.diversion;
-        self.mbal -= self.diversion;
+        let total_diversion = self.diversion + self.off_allocation_diversion;
+        self.dsflow_primary = self.usflow - total_diversion;
+        self.mbal -= total_diversion;
 
         // Record results
         if let Some(idx) = self.recorder_idx_diversion {
             data_cache.add_value_at_index(idx, self.diversion);
         }
+        if let Some(idx) = self.recorder_idx_off_allocation_volume {
+            data_cache.add_value_at_index(idx, self.off_allocation_volume_value);
+        }
+        if let Some(idx) = self.recorder_idx_off_allocation_diversion {
+            data_cache.add_value_at_index(idx, self.off_allocation_diversion);
+        }
         if let Some(idx) = self.recorder_idx_pump_capacity {
             data_cache.add_value_at_index(idx, self.pump_capacity_value)
         }
@@ -205,3 +310,31 @@ impl Node for RegulatedUserNode {
         &mut self.dsorders
     }
 }
+
+
+// ============================================================================
+// OptimisableComponent Implementation
+// ============================================================================
+
+impl OptimisableComponent for RegulatedUserNode {
+    fn set_param(&mut self, name: &str, value: f64) -> Result<(), String> {
+        match name {
+            "demand_multiplier" => {
+                self.demand_multiplier = value;
+                Ok(())
+            },
+            _ => Err(format!("Unknown regulated user parameter: {}", name)),
+        }
+    }
+
+    fn get_param(&self, name: &str) -> Result<f64, String> {
+        match name {
+            "demand_multiplier" => Ok(self.demand_multiplier),
+            _ => Err(format!("Unknown regulated user parameter: {}", name)),
+        }
+    }
+
+    fn list_params(&self) -> Vec<String> {
+        vec!["demand_multiplier".to_string()]
+    }
+}
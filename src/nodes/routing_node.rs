@@ -1,13 +1,16 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::data_management::data_cache::DataCache;
+use crate::data_management::events::EventKind;
 use crate::hydrology::accounts::account_manager::AccountManager;
+use crate::hydrology::constituents::NodeWater;
 use crate::misc::location::Location;
 use crate::numerical::mathfn::quadratic_plus;
 use crate::numerical::interpolation::lerp;
 use crate::numerical::opt::OptimisableComponent;
+use crate::numerical::table::Table;
 
-const MAX_DS_LINKS: usize = 1;
+const MAX_DS_LINKS: usize = 2;
 const PWL_TT_PREFIX: &str = "pwl_tt_";
 
 #[derive(Default, Clone)]
@@ -23,11 +26,13 @@ pub enum StorageRoutingMethod {
 pub struct RoutingNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
 
     // Internal state only
     usflow: f64,
     dsflow_primary: f64,
+    dsflow_secondary: f64,
     storage_volume: f64,
 
     //Parameters
@@ -71,16 +76,28 @@ pub struct RoutingNode {
     seg_par_bb: [f64; 32],    //PWL segment parameters - bb coefficient
     seg_par_cc: [f64; 32],    //PWL segment parameters - cc coefficient
 
+    // Distributary split. When given, the table gives the ds_2 flow for each routed outflow
+    // (an effluent rating), and the rest goes down ds_1.
+    pub split_table: Table,
+
+    // Volume in the reach at the start of a run, spread evenly across the storage divisions
+    pub initial_volume: f64,
+
     // Properties and internal state - ordering
     pub typical_regulated_flow: f64,
     pub dsorders: [f64; MAX_DS_LINKS],
 
+    // Source index of the events the node emits
+    event_source: usize,
+
     //Recorders
     recorder_idx_usflow: Option<usize>,
     recorder_idx_volume: Option<usize>,
     recorder_idx_dsflow: Option<usize>,
     recorder_idx_ds_1: Option<usize>,
     recorder_idx_ds_1_order: Option<usize>,
+    recorder_idx_ds_2: Option<usize>,
+    recorder_idx_ds_2_order: Option<usize>,
 }
 
 impl RoutingNode {
@@ -181,7 +198,7 @@ impl RoutingNode {
 
     /// Calculate the node storage by adding up all water volumes in the
     /// lag array and pwl arrays.
-    fn calculate_storage(&mut self) -> f64 {
+    fn calculate_storage(&self) -> f64 {
         let mut total_storage = 0.0;
 
         // Lag storage
@@ -206,6 +223,7 @@ impl Node for RoutingNode {
         self.mbal = 0.0;
         self.usflow = 0.0;
         self.dsflow_primary = 0.0;
+        self.dsflow_secondary = 0.0;
         self.storage_volume = 0.0;
         self.x_is_unity = self.x > 0.999999;
 
@@ -239,6 +257,25 @@ impl Node for RoutingNode {
             }
         }
 
+        // Validate the split table, as for the splitter node's table
+        if self.split_table.nrows() > 0 {
+            if let Err(e) = self.split_table.assert_monotonically_increasing(0, 1) {
+                return Err(format!("Node '{}' split table. {}", self.name, e));
+            }
+            if let Err(e) = self.split_table.assert_starts_at_zero(0) {
+                return Err(format!("Node '{}' split table. {}", self.name, e));
+            }
+            if let Err(e) = self.split_table.assert_non_negative() {
+                return Err(format!("Node '{}' split table. {}", self.name, e));
+            }
+            if let Err(e) = self.split_table.assert_col_not_exceeding(1, 0) {
+                return Err(format!("Node '{}' split table has ds_2 flow exceeding the routed flow. {}", self.name, e));
+            }
+            if let Err(e) = self.split_table.assert_slope_not_exceeding_one(0, 1) {
+                return Err(format!("Node '{}' split table slope exceeds 1:1 (ds_1 flow would decrease). {}", self.name, e));
+            }
+        }
+
         // Validate NLM parameters
         // k must not be negative (would silently fall through to PWL since NLM is detected by k>0).
         if self.nlm_k < 0.0 {
@@ -334,6 +371,20 @@ impl Node for RoutingNode {
 
         // Init PWL and NLM storage array
         self.div_sto_array.fill(0.0);
+        if self.initial_volume < 0.0 {
+            return Err(format!("Error in node '{}'. Initial volume cannot be negative, but was {}.",
+                               self.name, self.initial_volume));
+        }
+        if self.initial_volume > 0.0 {
+            if !nlm_is_defined && !pwl_is_defined {
+                return Err(format!("Error in node '{}'. An initial volume needs storage routing (pwl or nlm), not just lag.",
+                                   self.name));
+            }
+            let div_volume = self.initial_volume / self.n_divs as f64;
+            self.div_sto_array[..self.n_divs].fill(div_volume);
+        }
+
+        self.event_source = data_cache.add_event_source(&self.name);
 
         // Initialize result recorders
         self.recorder_idx_usflow = data_cache.get_series_idx(
@@ -351,6 +402,12 @@ impl Node for RoutingNode {
         self.recorder_idx_ds_1_order = data_cache.get_series_idx(
             make_result_name(&self.name, "ds_1_order").as_str(), false
         );
+        self.recorder_idx_ds_2 = data_cache.get_series_idx(
+            make_result_name(&self.name, "ds_2").as_str(), false
+        );
+        self.recorder_idx_ds_2_order = data_cache.get_series_idx(
+            make_result_name(&self.name, "ds_2_order").as_str(), false
+        );
 
         //Return
         Ok(())
@@ -366,6 +423,9 @@ impl Node for RoutingNode {
         if let Some(idx) = self.recorder_idx_ds_1_order {
             data_cache.add_value_at_index(idx, self.dsorders[0]);
         }
+        if let Some(idx) = self.recorder_idx_ds_2_order {
+            data_cache.add_value_at_index(idx, self.dsorders[1]);
+        }
     }
 
     /// Runs the node for the current timestep and updates the node state
@@ -385,7 +445,9 @@ impl Node for RoutingNode {
         self.lag_sto_array[oldest_index] = 0_f64; //set the element to zero
         self.lag_iter_index=oldest_index;
 
-        // PWL or NLM routing second
+        // PWL or NLM routing second. Negative outflows are clamped to zero (water does not
+        // flow upstream), and the total clamped is reported as an event.
+        let mut clamped = 0.0;
         match self.routing_method {
             StorageRoutingMethod::LagPlusNLM => {
                 let mut qout = flow_out_of_lag_reach; //ingested into the first division
@@ -400,6 +462,7 @@ impl Node for RoutingNode {
                         let vi = self.div_sto_array[i];
                         let vf_unclamped = k * qin.powf(m);
                         let (new_qout, vf) = if qin + vi - vf_unclamped < 0.0 {
+                            clamped -= qin + vi - vf_unclamped;
                             (0.0, vi + qin)
                         } else {
                             (qin + vi - vf_unclamped, vf_unclamped)
@@ -455,6 +518,7 @@ impl Node for RoutingNode {
                         let new_qout_raw = (y - x * qin) * inv_one_minus_x;
                         let (new_qout, vf) = if new_qout_raw < 0.0 {
                             // No upstream flow allowed; absorb inflow into storage.
+                            clamped -= new_qout_raw;
                             (0.0, vi + qin)
                         } else {
                             (new_qout_raw, vi + qin - new_qout_raw)
@@ -503,6 +567,7 @@ impl Node for RoutingNode {
 
                     //Do not allow water to flow upstream.
                     if qout < 0.0 {
+                        clamped -= qout;
                         qout = 0.0;
                         vf = vi + qin;
                     }
@@ -515,9 +580,20 @@ impl Node for RoutingNode {
                 self.dsflow_primary = qout;
             }
         }
+        if clamped > 0.0 {
+            data_cache.guard_negative(EventKind::NegativeFlowClamped, self.event_source, -clamped);
+        }
 
         // Update mass balance
-        self.mbal += self.dsflow_primary - self.usflow;
+        let dsflow = self.dsflow_primary;
+        self.mbal += dsflow - self.usflow;
+
+        // Split the routed flow between the outlets. Extrapolating beyond the table, and
+        // limiting the ds_2 flow to the routed flow, mirror the splitter node.
+        if self.split_table.nrows() > 0 {
+            self.dsflow_secondary = self.split_table.interpolate_or_extrapolate(0, 1, dsflow).max(0.0).min(dsflow);
+            self.dsflow_primary = dsflow - self.dsflow_secondary;
+        }
 
         // Record results
         if let Some(idx) = self.recorder_idx_volume {
@@ -525,11 +601,14 @@ impl Node for RoutingNode {
             data_cache.add_value_at_index(idx, self.storage_volume);
         }
         if let Some(idx) = self.recorder_idx_dsflow {
-            data_cache.add_value_at_index(idx, self.dsflow_primary);
+            data_cache.add_value_at_index(idx, dsflow);
         }
         if let Some(idx) = self.recorder_idx_ds_1 {
             data_cache.add_value_at_index(idx, self.dsflow_primary);
         }
+        if let Some(idx) = self.recorder_idx_ds_2 {
+            data_cache.add_value_at_index(idx, self.dsflow_secondary);
+        }
         // Reset upstream inflow for next timestep
         self.usflow = 0.0;
     }
@@ -545,10 +624,29 @@ impl Node for RoutingNode {
                 self.dsflow_primary = 0.0;
                 outflow
             }
+            1 => {
+                let outflow = self.dsflow_secondary;
+                self.dsflow_secondary = 0.0;
+                outflow
+            }
             _ => 0.0,
         }
     }
 
+    /// The water in transit through the lag, oldest first, then the volume in each division
+    fn get_state(&self) -> Vec<f64> {
+        let used = self.lag_sto_used;
+        let lag = (0..used).map(|i| self.lag_sto_array[(self.lag_iter_index + i) % used]);
+        lag.chain(self.div_sto_array[..self.n_divs].iter().copied()).collect()
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        let used = self.lag_sto_used;
+        self.lag_sto_array[..used].copy_from_slice(&state[..used]);
+        self.lag_iter_index = 0;
+        self.div_sto_array[..self.n_divs].copy_from_slice(&state[used..]);
+    }
+
     fn get_mass_balance(&self) -> f64 {
         self.mbal
     }
@@ -556,6 +654,11 @@ impl Node for RoutingNode {
     fn dsorders_mut(&mut self) -> &mut [f64] {
         &mut self.dsorders
     }
+
+    fn get_node_water(&self) -> NodeWater {
+        // Reaches have no surface area for settling
+        NodeWater::Store { volume: self.calculate_storage(), outflow: self.dsflow_primary + self.dsflow_secondary, area_km2: 0.0 }
+    }
 }
 
 // ============================================================================
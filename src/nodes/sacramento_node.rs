@@ -1,10 +1,11 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use super::rainfall_weights::RainfallWeightHandler;
 use crate::misc::misc_functions::make_result_name;
 use crate::model_inputs::DynamicInput;
 use crate::hydrology::rainfall_runoff::sacramento::Sacramento;
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
+use crate::hydrology::constituents::NodeWater;
 use crate::misc::location::Location;
 use crate::numerical::opt::optimisable_component::OptimisableComponent;
 
@@ -14,11 +15,13 @@ const MAX_DS_LINKS: usize = 1;
 pub struct SacramentoNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub rain_mm_input: DynamicInput,
     pub evap_mm_input: DynamicInput,
     pub area_km2: f64,
     pub sacramento_model: Sacramento,
+    pub initial_stores: [f64; 5], // uztwc, uzfwc, lztwc, lzfsc, lzfpc at the start of a run [mm]
 
     // Internal state only
     usflow: f64,
@@ -59,6 +62,15 @@ impl SacramentoNode {
             ..Default::default()
         }
     }
+
+    /// Sets the catchment area (km2), which must not be negative
+    pub fn set_area(&mut self, value: f64) -> Result<(), String> {
+        if !(value >= 0.0 && value.is_finite()) {
+            return Err(format!("Catchment area must be a non-negative number, but was {}", value));
+        }
+        self.area_km2 = value;
+        Ok(())
+    }
 }
 
 impl Node for SacramentoNode {
@@ -73,16 +85,21 @@ impl Node for SacramentoNode {
         self.runoff_depth_mm = 0.0;
         self.runoff_volume_megs = 0.0;
 
-        // Initialize inner Sacramento model
-        self.sacramento_model.initialize_state_empty();
-
-        // DynamicInput fields are already initialized during parsing
-
         // Checks
         if self.area_km2 < 0.0 {
             let message = format!("Error in node '{}'. Catchment area cannot be negative, but was {}.", self.name, self.area_km2);
             return Err(message);
         }
+        if self.initial_stores.iter().any(|&v| v < 0.0) {
+            let message = format!("Error in node '{}'. Initial stores cannot be negative.", self.name);
+            return Err(message);
+        }
+
+        // Initialize inner Sacramento model
+        self.sacramento_model.initialize_state_empty();
+        self.sacramento_model.set_stores(self.initial_stores);
+
+        // DynamicInput fields are already initialized during parsing
 
         // Initialize result recorders
         self.recorder_idx_usflow = data_cache.get_series_idx(
@@ -157,8 +174,8 @@ impl Node for SacramentoNode {
         }
 
         // Get driving data
-        self.rain = self.rain_mm_input.get_value(data_cache);
-        self.pet = self.evap_mm_input.get_value(data_cache);
+        self.rain = self.rain_mm_input.get_value_or_policy(data_cache, &self.name, "Rain");
+        self.pet = self.evap_mm_input.get_value_or_policy(data_cache, &self.name, "Evaporation");
 
         // Run Sacramento model to get runoff
         self.runoff_depth_mm = self.sacramento_model.run_step(self.rain, self.pet);
@@ -231,6 +248,19 @@ impl Node for SacramentoNode {
     fn dsorders_mut(&mut self) -> &mut [f64] {
         &mut self.dsorders
     }
+
+    fn get_state(&self) -> Vec<f64> {
+        self.sacramento_model.get_state()
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        self.sacramento_model.set_state(state);
+    }
+
+    fn get_node_water(&self) -> NodeWater {
+        let baseflow = self.sacramento_model.get_baseflow() * self.area_km2;
+        NodeWater::Runoff { quickflow: self.runoff_volume_megs - baseflow, baseflow }
+    }
 }
 
 // ============================================================================
@@ -1,18 +1,31 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::numerical::table::Table;
 use crate::data_management::data_cache::DataCache;
 use crate::hydrology::accounts::account_manager::AccountManager;
 use crate::misc::location::Location;
+use crate::model_inputs::DynamicInput;
 
 const MAX_DS_LINKS: usize = 5;
 
+/// Splits the flow between ds_1 and ds_2. The ds_2 flow comes from one of:
+///  - `splitter_table`, giving the ds_2 flow for each inflow (the default),
+///  - `ds_2_flow_input`, an expression for the ds_2 flow, or
+///  - `ds_2_target_input`, a target that ds_2 is filled to before anything goes to ds_1.
+///
+/// The result is then clamped to `ds_2_min_input` and `ds_2_max_input` (e.g. the capacity of
+/// a bypass channel), and never exceeds the inflow.
 #[derive(Default, Clone)]
 pub struct SplitterNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub splitter_table: Table,  // By default, the columns mean Inflow Rate ML, Effluent Rate ML (maybe ways to override this later)
+    pub ds_2_flow_input: DynamicInput,
+    pub ds_2_target_input: DynamicInput,
+    pub ds_2_min_input: DynamicInput,
+    pub ds_2_max_input: DynamicInput,
 
     // Internal state only
     usflow: f64,
@@ -21,6 +34,7 @@ pub struct SplitterNode {
 
     // Orders
     pub dsorders: [f64; MAX_DS_LINKS],
+    pub usorders: f64,
 
     // Recorders
     recorder_idx_usflow: Option<usize>,
@@ -37,9 +51,25 @@ impl SplitterNode {
     pub fn new() -> Self {
         Self {
             name: "".to_string(),
+            ds_2_flow_input: DynamicInput::default(),
+            ds_2_target_input: DynamicInput::default(),
+            ds_2_min_input: DynamicInput::default(),
+            ds_2_max_input: DynamicInput::default(),
             ..Default::default()
         }
     }
+
+    /// Clamps a ds_2 flow to the min and max capacities, if given
+    fn clamp_ds_2(&self, flow: f64, data_cache: &DataCache) -> f64 {
+        let mut flow = flow;
+        if let DynamicInput::None { .. } = self.ds_2_max_input {} else {
+            flow = flow.min(self.ds_2_max_input.get_value(data_cache));
+        }
+        if let DynamicInput::None { .. } = self.ds_2_min_input {} else {
+            flow = flow.max(self.ds_2_min_input.get_value(data_cache));
+        }
+        flow
+    }
 }
 
 impl Node for SplitterNode {
@@ -50,28 +80,39 @@ impl Node for SplitterNode {
         self.ds_1_flow = 0.0;
         self.ds_2_flow = 0.0;
 
-        // Check the splitter table is well-behaved (mirrors the loss node, see the
-        // matching Table assertions):
-        //  - it must be monotonically increasing (inflow ascending, effluent non-decreasing)
-        //  - it must start at zero inflow
-        //  - it must not have negative values
-        //  - it must not specify effluent greater than the inflow
-        //  - its slope must not exceed 1:1, i.e. the ds_1 continuation flow must
-        //    not decrease as inflow rises
-        if let Err(e) = self.splitter_table.assert_monotonically_increasing(0, 1) {
-            return Err(format!("Node '{}' splitter table. {}", self.name, e));
+        // Only one way of splitting the flow may be given
+        let uses_flow = !matches!(self.ds_2_flow_input, DynamicInput::None { .. });
+        let uses_target = !matches!(self.ds_2_target_input, DynamicInput::None { .. });
+        let uses_expression = uses_flow || uses_target;
+        if [self.splitter_table.nrows() > 0, uses_flow, uses_target].iter().filter(|&&given| given).count() > 1 {
+            return Err(format!("Error in node '{}'. Only one of the splitter table, ds_2_flow and ds_2_target may be given.",
+                               self.name));
         }
-        if let Err(e) = self.splitter_table.assert_starts_at_zero(0) {
-            return Err(format!("Node '{}' splitter table. {}", self.name, e));
-        }
-        if let Err(e) = self.splitter_table.assert_non_negative() {
-            return Err(format!("Node '{}' splitter table. {}", self.name, e));
-        }
-        if let Err(e) = self.splitter_table.assert_col_not_exceeding(1, 0) {
-            return Err(format!("Node '{}' splitter table has effluent exceeding inflow. {}", self.name, e));
-        }
-        if let Err(e) = self.splitter_table.assert_slope_not_exceeding_one(0, 1) {
-            return Err(format!("Node '{}' splitter table slope exceeds 1:1 (ds_1 flow would decrease). {}", self.name, e));
+
+        // Check the splitter table is well-behaved, unless an expression is used instead
+        // (mirrors the loss node, see the matching Table assertions):
+        if !uses_expression {
+            //  - it must be monotonically increasing (inflow ascending, effluent non-decreasing)
+            //  - it must start at zero inflow
+            //  - it must not have negative values
+            //  - it must not specify effluent greater than the inflow
+            //  - its slope must not exceed 1:1, i.e. the ds_1 continuation flow must
+            //    not decrease as inflow rises
+            if let Err(e) = self.splitter_table.assert_monotonically_increasing(0, 1) {
+                return Err(format!("Node '{}' splitter table. {}", self.name, e));
+            }
+            if let Err(e) = self.splitter_table.assert_starts_at_zero(0) {
+                return Err(format!("Node '{}' splitter table. {}", self.name, e));
+            }
+            if let Err(e) = self.splitter_table.assert_non_negative() {
+                return Err(format!("Node '{}' splitter table. {}", self.name, e));
+            }
+            if let Err(e) = self.splitter_table.assert_col_not_exceeding(1, 0) {
+                return Err(format!("Node '{}' splitter table has effluent exceeding inflow. {}", self.name, e));
+            }
+            if let Err(e) = self.splitter_table.assert_slope_not_exceeding_one(0, 1) {
+                return Err(format!("Node '{}' splitter table slope exceeds 1:1 (ds_1 flow would decrease). {}", self.name, e));
+            }
         }
 
         // Initialize result recorders
@@ -111,6 +152,16 @@ impl Node for SplitterNode {
         if let Some(idx) = self.recorder_idx_ds_2_order {
             data_cache.add_value_at_index(idx, self.dsorders[1]);
         }
+
+        // ds_2 is filled to its target first, so ds_1 orders are passed on with the target
+        // added. Otherwise the orders on both outlets are summed.
+        self.usorders = match self.ds_2_target_input {
+            DynamicInput::None { .. } => self.dsorders.iter().sum(),
+            _ => {
+                let target = self.clamp_ds_2(self.ds_2_target_input.get_value(data_cache), data_cache).max(0.0);
+                self.dsorders[0] + target
+            }
+        };
     }
 
     fn run_flow_phase(&mut self, data_cache: &mut DataCache, _account_manager: &mut AccountManager) {
@@ -124,7 +175,17 @@ impl Node for SplitterNode {
         // beyond the table domain extend the last segment rather than returning NaN
         // (NaN would slip through .min, sending the entire flow down ds_2). The
         // .max(0) guards the lower bound and the .min(usflow) guards over-extraction.
-        self.ds_2_flow = self.splitter_table.interpolate_or_extrapolate(0, 1, self.usflow).max(0f64).min(self.usflow);
+        let attempted_ds_2_flow = if let DynamicInput::None { .. } = self.ds_2_flow_input {
+            if let DynamicInput::None { .. } = self.ds_2_target_input {
+                self.splitter_table.interpolate_or_extrapolate(0, 1, self.usflow)
+            } else {
+                self.ds_2_target_input.get_value(data_cache)
+            }
+        } else {
+            self.ds_2_flow_input.get_value(data_cache)
+        };
+        let attempted_ds_2_flow = self.clamp_ds_2(attempted_ds_2_flow, data_cache);
+        self.ds_2_flow = attempted_ds_2_flow.max(0f64).min(self.usflow);
         self.ds_1_flow = self.usflow - self.ds_2_flow;
         if self.ds_1_flow < 0f64 {
             panic!("Negative ds_1 flow at '{}' when usflow={}, ds_1={}", self.name, self.usflow, self.ds_1_flow);
@@ -1,14 +1,18 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::model_inputs::DynamicInput;
 use crate::numerical::table::Table;
 use crate::data_management::data_cache::DataCache;
+use crate::data_management::events::EventKind;
 use crate::hydrology::accounts::account_manager::AccountManager;
+use crate::hydrology::constituents::NodeWater;
+use crate::ordering::shortfall_sharing::SharingRule;
 use crate::misc::location::Location;
 use crate::numerical::fifo_buffer::FifoBuffer;
+use crate::tid::utils::{seconds_between, u64_to_date_string};
 
-const LEVL: usize = 0;
-const VOLU: usize = 1;
+pub(crate) const LEVL: usize = 0;
+pub(crate) const VOLU: usize = 1;
 const AREA: usize = 2;
 const SPIL: usize = 3;
 const EPSILON: f64 = 1e-6;
@@ -28,8 +32,12 @@ pub enum OutletDefinition {
 pub struct StorageNode {
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub dimensions: Table,       // Level m, Volume ML, Area km2, Spill ML
+    // Dimensions tables from surveys at different dates, as (date, table). When given, the
+    // `dimensions` in use are interpolated between them by the simulation date.
+    pub dated_dimensions: Vec<(u64, Table)>,
     pub volume: f64,
     pub vol_initial: f64,
     pub order_through: bool,
@@ -39,6 +47,9 @@ pub struct StorageNode {
     pub pond_demand_input: DynamicInput,
     pub target_level: DynamicInput,
     pub ds_force_release_input: [DynamicInput; MAX_DS_LINKS],
+    /// Cap on the controlled release from each outlet, set each timestep by the active
+    /// `[policies]` stage (infinite when unrestricted). Spill is not capped.
+    pub policy_release_cap: f64,
 
     // Internal state only
     usflow: f64,
@@ -46,11 +57,14 @@ pub struct StorageNode {
     ds_flows: [f64; MAX_DS_LINKS],
     ds_release_due: [f64; MAX_DS_LINKS],
     level: f64,
+    area: f64,
     rain_vol: f64,
     evap_vol: f64,
     seep_vol: f64,
     pond_diversion: f64, //pond diversion
     spill: f64,
+    capacity: f64,
+    dimensions_timestamp: Option<u64>, // The date the dimensions were last interpolated for
 
     // Cached state for search optimization
     previous_istop: usize,  // Remember previous solution row for warm start
@@ -66,6 +80,9 @@ pub struct StorageNode {
     pub ds_3_order_buffer: FifoBuffer,
     pub ds_4_order_buffer: FifoBuffer,
 
+    // How a shortfall is shared between the regulated users this storage supplies
+    pub shortfall_sharing: SharingRule,
+
     // Outlet definitions (MOL, capacity) - parsed from INI
     pub outlet_definition: [OutletDefinition; MAX_DS_LINKS],
 
@@ -73,12 +90,16 @@ pub struct StorageNode {
     // 0.0 means no MOL constraint (outlet always active)
     min_operating_volume: [f64; MAX_DS_LINKS],
 
+    // Source index of the events the node emits
+    event_source: usize,
+
     // Recorders
     recorder_idx_usflow: Option<usize>,
     recorder_idx_volume: Option<usize>,
     recorder_idx_level: Option<usize>,
     recorder_idx_target_level: Option<usize>,
     recorder_idx_area: Option<usize>,
+    recorder_idx_capacity: Option<usize>,
     recorder_idx_seep_megs: Option<usize>,
     recorder_idx_evap_megs: Option<usize>,
     recorder_idx_rain_megs: Option<usize>,
@@ -123,6 +144,7 @@ impl StorageNode {
             dimensions: Table::new(4),
             order_through: false,
             usflow: 0.0,
+            policy_release_cap: f64::INFINITY,
             ..Default::default()
         }
     }
@@ -163,6 +185,151 @@ impl StorageNode {
         }
     }
 
+    /// A value of the dimensions table, addressed by its row (from 1) and column name (level,
+    /// volume, area or spill)
+    pub fn get_dimension(&self, row: &str, column: &str) -> Result<f64, String> {
+        let (row, col) = self.dimension_cell(row, column)?;
+        Ok(self.dimensions.get_value(row, col))
+    }
+
+    /// Sets a value of the dimensions table, addressed as for `get_dimension`. The table must
+    /// still be usable by the solver with the new value, otherwise it is left unchanged.
+    pub fn set_dimension(&mut self, row: &str, column: &str, value: f64) -> Result<(), String> {
+        let (row, col) = self.dimension_cell(row, column)?;
+        if !value.is_finite() {
+            return Err(format!("Storage dimensions must be finite, but was {}", value));
+        }
+        let mut table = self.dimensions.clone();
+        table.set_value(row, col, value);
+        self.validate_dimensions(&table, "dimension")?;
+        self.dimensions = table;
+        Ok(())
+    }
+
+    /// Row and column indices of a cell of the dimensions table
+    fn dimension_cell(&self, row: &str, column: &str) -> Result<(usize, usize), String> {
+        if !self.dated_dimensions.is_empty() {
+            return Err(format!("Storage '{}' uses dated dimensions tables, whose rows cannot be set individually", self.name));
+        }
+        let nrows = self.dimensions.nrows();
+        let row = row.parse::<usize>().ok()
+            .filter(|r| (1..=nrows).contains(r))
+            .ok_or_else(|| format!("Storage '{}' dimensions row must be from 1 to {}, but was '{}'", self.name, nrows, row))?;
+        let col = match column.to_lowercase().as_str() {
+            "level" => LEVL,
+            "volume" => VOLU,
+            "area" => AREA,
+            "spill" => SPIL,
+            _ => return Err(format!("Unknown storage dimensions column '{}'. Expected level, volume, area or spill", column)),
+        };
+        Ok((row - 1, col))
+    }
+
+    /// Checks a dimensions table is usable by the solver. `label` names the table in messages.
+    fn validate_dimensions(&self, table: &Table, label: &str) -> Result<(), String> {
+        if table.nrows() < 2 {
+            let message = format!("Error in node '{}'. Storage {} table must have at least 2 rows.", self.name, label);
+            return Err(message);
+        }
+        if table.get_value(0, VOLU) != 0_f64 {
+            let message = format!("Error in node '{}'. Storage {} table must begin with volume=0.", self.name, label);
+            return Err(message);
+        }
+        if table.get_value(0, AREA) != 0_f64 {
+            let message = format!("Error in node '{}'. Storage {} table must begin with area=0.", self.name, label);
+            return Err(message);
+        }
+
+        // Validate that volumes are strictly increasing (required for solver interpolation)
+        for i in 1..table.nrows() {
+            if table.get_value(i, VOLU) <= table.get_value(i - 1, VOLU) {
+                let message = format!(
+                    "Error in node '{}'. Storage {} table volumes must be strictly increasing (violation at row {}).",
+                    self.name, label, i + 1
+                );
+                return Err(message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Capacity of a dimensions table: the volume at which the storage starts to spill (the
+    /// last row with no spill), or the largest volume in the table if it never spills.
+    fn capacity_of(table: &Table) -> f64 {
+        let nrows = table.nrows();
+        (0..nrows)
+            .find(|&i| table.get_value(i, SPIL) > 0.0)
+            .map(|i| table.get_value(i.saturating_sub(1), VOLU))
+            .unwrap_or_else(|| table.get_value(nrows - 1, VOLU))
+    }
+
+    /// Converts the outlet MOL levels to volumes using the current dimensions.
+    fn update_min_operating_volumes(&mut self) {
+        for i in 0..MAX_DS_LINKS {
+            self.min_operating_volume[i] = match self.outlet_definition[i] {
+                OutletDefinition::None => 0.0,
+                OutletDefinition::OutletWithMOL(level) => {
+                    self.dimensions.interpolate(LEVL, VOLU, level)
+                }
+                OutletDefinition::OutletWithMOLAndCapacity(level, _capacity) => {
+                    self.dimensions.interpolate(LEVL, VOLU, level)
+                }
+            };
+        }
+    }
+
+    /// Sets the dimensions for the given date from the dated dimensions tables. Volumes,
+    /// areas and spills are interpolated row by row between the surveys either side of the
+    /// date; before the first survey and after the last, that survey's table is used. Does
+    /// nothing if there are no dated tables or the dimensions are already set for this date.
+    fn update_dimensions(&mut self, timestamp: u64) {
+        if self.dated_dimensions.is_empty() || self.dimensions_timestamp == Some(timestamp) {
+            return;
+        }
+        self.dimensions_timestamp = Some(timestamp);
+
+        let n = self.dated_dimensions.len();
+        let i_next = self.dated_dimensions.iter()
+            .position(|(date, _)| seconds_between(*date, timestamp) > 0)
+            .unwrap_or(n);
+        self.dimensions = if i_next == 0 {
+            self.dated_dimensions[0].1.clone()
+        } else if i_next == n {
+            self.dated_dimensions[n - 1].1.clone()
+        } else {
+            let (date_a, table_a) = &self.dated_dimensions[i_next - 1];
+            let (date_b, table_b) = &self.dated_dimensions[i_next];
+            let w = seconds_between(timestamp, *date_a) as f64 / seconds_between(*date_b, *date_a) as f64;
+            let mut table = table_a.clone();
+            for row in 0..table.nrows() {
+                for col in [VOLU, AREA, SPIL] {
+                    let a = table_a.get_value(row, col);
+                    let b = table_b.get_value(row, col);
+                    table.set_value(row, col, a + (b - a) * w);
+                }
+            }
+            table
+        };
+        self.capacity = Self::capacity_of(&self.dimensions);
+        self.update_min_operating_volumes();
+    }
+
+    /// Estimate of what an outlet can release this timestep: the volume above its minimum
+    /// operating volume. Used by the ordering system to share shortfalls.
+    pub fn available_release(&self, outlet: usize) -> f64 {
+        (self.volume - self.min_operating_volume[outlet]).max(0.0)
+    }
+
+    /// Volume spilled this timestep
+    pub fn spill(&self) -> f64 {
+        self.spill
+    }
+
+    /// Volume evaporated from the storage surface this timestep
+    pub fn evaporation(&self) -> f64 {
+        self.evap_vol
+    }
+
     /// Determines which outlets are active (able to release) at a given volume.
     /// An outlet is active if volume >= its minimum operating volume and there is demand
     /// (either from orders or forced releases).
@@ -205,13 +372,13 @@ impl StorageNode {
     ) -> (f64, [f64; MAX_DS_LINKS], f64, usize, f64) {
         let nrows = self.dimensions.nrows();
 
-        // Compute all release demands once (orders or forced releases)
+        // Compute all release demands once (orders or forced releases), within any policy cap
         for i in 0..MAX_DS_LINKS {
             self.ds_release_due[i] = Self::check_forced_release(
                 data_cache,
                 &self.ds_force_release_input[i],
                 self.ds_orders_due[i]
-            );
+            ).min(self.policy_release_cap);
         }
 
         // --- Pass 1: Solve spill-limited case (no controlled release on ds_1) ---
@@ -510,54 +677,52 @@ impl Node for StorageNode {
         self.ds_flows = [0.0; MAX_DS_LINKS];
         self.volume = self.vol_initial;
         self.level = 0.0;
+        self.area = 0.0;
         self.rain_vol = 0.0;
         self.evap_vol = 0.0;
         self.seep_vol = 0.0;
         self.pond_diversion = 0.0;
         self.spill = 0.0;
         self.previous_istop = 0;
+        self.dimensions_timestamp = None;
+        self.policy_release_cap = f64::INFINITY;
 
         // Checks
-        if self.dimensions.nrows() < 2 {
-            let message = format!("Error in node '{}'. Storage dimension table must have at least 2 rows.", self.name);
-            return Err(message);
-        }
-        if self.dimensions.get_value(0, VOLU) != 0_f64 {
-            let message = format!("Error in node '{}'. Storage dimension table must begin with volume=0.", self.name);
-            return Err(message);
-        }
-        if self.dimensions.get_value(0, AREA) != 0_f64 {
-            let message = format!("Error in node '{}'. Storage dimension table must begin with area=0.", self.name);
-            return Err(message);
-        }
-
-        // Validate that volumes are strictly increasing (required for solver interpolation)
-        for i in 1..self.dimensions.nrows() {
-            if self.dimensions.get_value(i, VOLU) <= self.dimensions.get_value(i - 1, VOLU) {
-                let message = format!(
-                    "Error in node '{}'. Storage dimension table volumes must be strictly increasing (violation at row {}).",
-                    self.name, i + 1
-                );
-                return Err(message);
+        if self.dated_dimensions.is_empty() {
+            self.validate_dimensions(&self.dimensions, "dimension")?;
+        } else {
+            // Dated tables are interpolated row by row, so they must share the same levels
+            self.dated_dimensions.sort_by_key(|(date, _)| *date);
+            for (i, (date, table)) in self.dated_dimensions.iter().enumerate() {
+                let label = format!("dimensions_{}", u64_to_date_string(*date));
+                self.validate_dimensions(table, &label)?;
+                if i == 0 {
+                    continue;
+                }
+                let (previous_date, first) = (&self.dated_dimensions[i - 1].0, &self.dated_dimensions[0].1);
+                if *date == *previous_date {
+                    return Err(format!("Error in node '{}'. Storage has more than one dimensions table dated {}.",
+                                       self.name, u64_to_date_string(*date)));
+                }
+                let same_levels = table.nrows() == first.nrows()
+                    && (0..table.nrows()).all(|row| table.get_value(row, LEVL) == first.get_value(row, LEVL));
+                if !same_levels {
+                    return Err(format!("Error in node '{}'. Storage {} table must have the same levels as the other dated dimensions tables.",
+                                       self.name, label));
+                }
             }
+            self.dimensions = self.dated_dimensions[0].1.clone();
         }
+        self.capacity = Self::capacity_of(&self.dimensions);
 
         // Convert outlet definitions (MOL levels) to volumes
-        for i in 0..MAX_DS_LINKS {
-            self.min_operating_volume[i] = match self.outlet_definition[i] {
-                OutletDefinition::None => 0.0,
-                OutletDefinition::OutletWithMOL(level) => {
-                    self.dimensions.interpolate(LEVL, VOLU, level)
-                }
-                OutletDefinition::OutletWithMOLAndCapacity(level, _capacity) => {
-                    self.dimensions.interpolate(LEVL, VOLU, level)
-                }
-            };
-        }
+        self.update_min_operating_volumes();
 
         // Check if the storage is targeting a level
         self.has_target_level = !matches!(&self.target_level, DynamicInput::None { .. });
 
+        self.event_source = data_cache.add_event_source(&self.name);
+
         // Initialize result recorders
         self.recorder_idx_usflow = data_cache.get_series_idx(
             make_result_name(&self.name, "usflow").as_str(), false
@@ -574,6 +739,9 @@ impl Node for StorageNode {
         self.recorder_idx_area = data_cache.get_series_idx(
             make_result_name(&self.name, "area").as_str(), false
         );
+        self.recorder_idx_capacity = data_cache.get_series_idx(
+            make_result_name(&self.name, "capacity").as_str(), false
+        );
         self.recorder_idx_seep_megs = data_cache.get_series_idx(
             make_result_name(&self.name, "seep_vol").as_str(), false
         );
@@ -679,8 +847,15 @@ impl Node for StorageNode {
 
     fn get_name(&self) -> &str { &self.name }
 
+    fn requires_order_phase(&self) -> bool {
+        true
+    }
+
     fn run_order_phase(&mut self, data_cache: &mut DataCache) {
 
+        // The target level is converted to a volume with today's dimensions
+        self.update_dimensions(data_cache.current_timestamp);
+
         // Record new downstream orders
         if let Some(idx) = self.recorder_idx_ds_1_order {
             data_cache.add_value_at_index(idx, self.ds_orders[0]);
@@ -764,11 +939,14 @@ impl Node for StorageNode {
             data_cache.add_value_at_index(idx, self.usflow);
         }
 
+        // Bring the dimensions up to date if they change over time
+        self.update_dimensions(data_cache.current_timestamp);
+
         // Get the driving data
-        let rain_mm = self.rain_mm_input.get_value(data_cache);
-        let evap_mm = self.evap_mm_input.get_value(data_cache);
-        let seep_mm = self.seep_mm_input.get_value(data_cache);
-        let pond_demand = self.pond_demand_input.get_value(data_cache);
+        let rain_mm = self.rain_mm_input.get_value_or_policy(data_cache, &self.name, "Rain");
+        let evap_mm = self.evap_mm_input.get_value_or_policy(data_cache, &self.name, "Evaporation");
+        let seep_mm = self.seep_mm_input.get_value_or_policy(data_cache, &self.name, "Seepage");
+        let pond_demand = self.pond_demand_input.get_value_or_policy(data_cache, &self.name, "Pond demand");
 
         // Add upstream inflows
         self.volume += self.usflow;
@@ -787,11 +965,16 @@ impl Node for StorageNode {
         // Update warm-start cache for next timestep (expects upper bracket)
         self.previous_istop = row + 1;
 
-        // Update state from solution (area already computed by solver)
-        self.volume = v_final;
-        self.level = self.dimensions.interpolate_row(row, VOLU, LEVL, v_final);
+        // Update state from solution (area already computed by solver). A negative volume from
+        // the tables is guarded against rather than carried into the next timestep.
+        self.volume = data_cache.guard_negative(EventKind::NegativeStorageClamped, self.event_source, v_final);
+        self.level = self.dimensions.interpolate_row(row, VOLU, LEVL, self.volume);
+        self.area = area_km2;
         self.spill = spill;
         self.ds_flows = ds_flows;
+        if spill > 0.0 {
+            data_cache.emit_event(EventKind::StorageSpill, self.event_source, spill);
+        }
         self.dsflow = self.ds_flows.iter().sum();
 
         // Compute climate volumes using solved area
@@ -812,6 +995,9 @@ impl Node for StorageNode {
         if let Some(idx) = self.recorder_idx_area {
             data_cache.add_value_at_index(idx, area_km2);
         }
+        if let Some(idx) = self.recorder_idx_capacity {
+            data_cache.add_value_at_index(idx, self.capacity);
+        }
         if let Some(idx) = self.recorder_idx_seep_megs {
             data_cache.add_value_at_index(idx, self.seep_vol);
         }
@@ -921,4 +1107,21 @@ impl Node for StorageNode {
     fn dsorders_mut(&mut self) -> &mut [f64] {
         &mut self.ds_orders
     }
+
+    fn get_state(&self) -> Vec<f64> {
+        vec![self.volume]
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        self.volume = state[0];
+    }
+
+    fn get_node_water(&self) -> NodeWater {
+        // Evaporation leaves constituents behind; everything else takes them with it
+        NodeWater::Store {
+            volume: self.volume,
+            outflow: self.dsflow + self.pond_diversion + self.seep_vol,
+            area_km2: self.area,
+        }
+    }
 }
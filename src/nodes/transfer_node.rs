@@ -0,0 +1,219 @@
+use super::{Node, NodeMetadata};
+use crate::misc::misc_functions::make_result_name;
+use crate::model_inputs::DynamicInput;
+use crate::data_management::data_cache::DataCache;
+use crate::hydrology::accounts::account_manager::AccountManager;
+use crate::misc::location::Location;
+
+const MAX_DS_LINKS: usize = 2;
+
+/// Moves water from one part of the network to another (e.g. an inter-valley pipeline).
+/// Flow continues on ds_1 at the source, and the transfer is delivered on ds_2, which may
+/// link to any node that is not upstream of the source.
+#[derive(Default, Clone)]
+pub struct TransferNode {
+    pub name: String,
+    pub location: Location,
+    pub metadata: NodeMetadata,
+    pub mbal: f64,
+    pub capacity: DynamicInput,     // Max volume taken into the transfer each timestep (none = unlimited)
+    pub trigger: DynamicInput,      // Transfers only run while this is positive (none = always on)
+    pub loss_fraction: f64,         // Fraction of the transferred water lost on the way
+
+    // Internal state only
+    usflow: f64,
+    ds_1_flow: f64,
+    ds_2_flow: f64,
+    transfer_volume: f64,
+    transfer_loss: f64,
+    capacity_value: f64,
+    trigger_value: f64,
+
+    // Orders
+    pub dsorders: [f64; MAX_DS_LINKS],
+    pub usorders: f64,
+
+    // Recorders
+    recorder_idx_usflow: Option<usize>,
+    recorder_idx_dsflow: Option<usize>,
+    recorder_idx_ds_1: Option<usize>,
+    recorder_idx_ds_1_order: Option<usize>,
+    recorder_idx_ds_2: Option<usize>,
+    recorder_idx_ds_2_order: Option<usize>,
+    recorder_idx_capacity: Option<usize>,
+    recorder_idx_trigger: Option<usize>,
+    recorder_idx_transfer_volume: Option<usize>,
+    recorder_idx_transfer_loss: Option<usize>,
+}
+
+impl TransferNode {
+
+    /// Base constructor
+    pub fn new() -> Self {
+        Self {
+            name: "".to_string(),
+            capacity: DynamicInput::default(),
+            trigger: DynamicInput::default(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Node for TransferNode {
+    fn initialise(&mut self, data_cache: &mut DataCache, _account_manager: &mut AccountManager) -> Result<(), String> {
+        // Initialize only internal state
+        self.mbal = 0.0;
+        self.usflow = 0.0;
+        self.ds_1_flow = 0.0;
+        self.ds_2_flow = 0.0;
+        self.transfer_volume = 0.0;
+        self.transfer_loss = 0.0;
+        self.capacity_value = f64::INFINITY;
+        self.trigger_value = 1.0;
+
+        // Checks
+        if !(0.0..=1.0).contains(&self.loss_fraction) {
+            return Err(format!("Error in node '{}'. Transfer loss_fraction must be between 0 and 1, but was {}.",
+                               self.name, self.loss_fraction));
+        }
+
+        // Initialize result recorders
+        self.recorder_idx_usflow = data_cache.get_series_idx(
+            make_result_name(&self.name, "usflow").as_str(), false
+        );
+        self.recorder_idx_dsflow = data_cache.get_series_idx(
+            make_result_name(&self.name, "dsflow").as_str(), false
+        );
+        self.recorder_idx_ds_1 = data_cache.get_series_idx(
+            make_result_name(&self.name, "ds_1").as_str(), false
+        );
+        self.recorder_idx_ds_1_order = data_cache.get_series_idx(
+            make_result_name(&self.name, "ds_1_order").as_str(), false
+        );
+        self.recorder_idx_ds_2 = data_cache.get_series_idx(
+            make_result_name(&self.name, "ds_2").as_str(), false
+        );
+        self.recorder_idx_ds_2_order = data_cache.get_series_idx(
+            make_result_name(&self.name, "ds_2_order").as_str(), false
+        );
+        self.recorder_idx_capacity = data_cache.get_series_idx(
+            make_result_name(&self.name, "capacity").as_str(), false
+        );
+        self.recorder_idx_trigger = data_cache.get_series_idx(
+            make_result_name(&self.name, "trigger").as_str(), false
+        );
+        self.recorder_idx_transfer_volume = data_cache.get_series_idx(
+            make_result_name(&self.name, "transfer_volume").as_str(), false
+        );
+        self.recorder_idx_transfer_loss = data_cache.get_series_idx(
+            make_result_name(&self.name, "transfer_loss").as_str(), false
+        );
+
+        // Return
+        Ok(())
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn run_order_phase(&mut self, data_cache: &mut DataCache) {
+
+        // Record downstream orders
+        if let Some(idx) = self.recorder_idx_ds_1_order {
+            data_cache.add_value_at_index(idx, self.dsorders[0]);
+        }
+        if let Some(idx) = self.recorder_idx_ds_2_order {
+            data_cache.add_value_at_index(idx, self.dsorders[1]);
+        }
+
+        // Orders at the far end are grossed up for the transfer losses
+        let ds_2_order = match self.loss_fraction < 1.0 {
+            true => self.dsorders[1] / (1.0 - self.loss_fraction),
+            false => 0.0,
+        };
+        self.usorders = self.dsorders[0] + ds_2_order;
+    }
+
+    fn run_flow_phase(&mut self, data_cache: &mut DataCache, _account_manager: &mut AccountManager) {
+
+        // Record results
+        if let Some(idx) = self.recorder_idx_usflow {
+            data_cache.add_value_at_index(idx, self.usflow);
+        }
+
+        // Evaluate the trigger and capacity
+        if let DynamicInput::None { .. } = self.trigger {} else {
+            self.trigger_value = self.trigger.get_value(data_cache);
+        }
+        if let DynamicInput::None { .. } = self.capacity {} else {
+            self.capacity_value = self.capacity.get_value(data_cache).max(0.0);
+        }
+
+        // Take water into the transfer, and lose some of it on the way
+        self.transfer_volume = match self.trigger_value > 0.0 {
+            true => self.usflow.max(0.0).min(self.capacity_value),
+            false => 0.0,
+        };
+        self.transfer_loss = self.transfer_volume * self.loss_fraction;
+        self.ds_2_flow = self.transfer_volume - self.transfer_loss;
+        self.ds_1_flow = self.usflow - self.transfer_volume;
+
+        // Update mass balance
+        self.mbal -= self.transfer_loss;
+
+        // Record results
+        if let Some(idx) = self.recorder_idx_dsflow {
+            data_cache.add_value_at_index(idx, self.ds_1_flow + self.ds_2_flow);
+        }
+        if let Some(idx) = self.recorder_idx_ds_1 {
+            data_cache.add_value_at_index(idx, self.ds_1_flow);
+        }
+        if let Some(idx) = self.recorder_idx_ds_2 {
+            data_cache.add_value_at_index(idx, self.ds_2_flow);
+        }
+        if let Some(idx) = self.recorder_idx_capacity {
+            data_cache.add_value_at_index(idx, self.capacity_value);
+        }
+        if let Some(idx) = self.recorder_idx_trigger {
+            data_cache.add_value_at_index(idx, self.trigger_value);
+        }
+        if let Some(idx) = self.recorder_idx_transfer_volume {
+            data_cache.add_value_at_index(idx, self.transfer_volume);
+        }
+        if let Some(idx) = self.recorder_idx_transfer_loss {
+            data_cache.add_value_at_index(idx, self.transfer_loss);
+        }
+
+        // Reset upstream inflow for next timestep
+        self.usflow = 0.0;
+    }
+
+    fn add_usflow(&mut self, flow: f64, _inlet: u8) {
+        self.usflow += flow;
+    }
+
+    fn remove_dsflow(&mut self, outlet: u8) -> f64 {
+        match outlet {
+            0 => {
+                let outflow = self.ds_1_flow;
+                self.ds_1_flow = 0.0;
+                outflow
+            }
+            1 => {
+                let outflow = self.ds_2_flow;
+                self.ds_2_flow = 0.0;
+                outflow
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn get_mass_balance(&self) -> f64 {
+        self.mbal
+    }
+
+    fn dsorders_mut(&mut self) -> &mut [f64] {
+        &mut self.dsorders
+    }
+}
@@ -1,9 +1,12 @@
-use super::Node;
+use super::{Node, NodeMetadata};
 use crate::misc::misc_functions::make_result_name;
 use crate::model_inputs::DynamicInput;
 use crate::data_management::data_cache::DataCache;
+use crate::data_management::events::EventKind;
 use crate::hydrology::accounts::account_manager::AccountManager;
 use crate::misc::location::Location;
+use crate::numerical::opt::optimisable_component::OptimisableComponent;
+use crate::tid::water_year::WaterYear;
 
 const MAX_DS_LINKS: usize = 1;
 
@@ -13,17 +16,22 @@ pub struct UnregulatedUserNode {
     // Properties - basic
     pub name: String,
     pub location: Location,
+    pub metadata: NodeMetadata,
     pub mbal: f64,
     pub demand_input: DynamicInput,
+    pub demand_multiplier: f64,
+    /// Demand factor of the active `[policies]` stage, set each timestep (1 when unrestricted)
+    pub policy_demand_factor: f64,
 
     // Properties - unreg user stuff
     pub pump_capacity: DynamicInput,
-    pub flow_threshold: DynamicInput,
+    pub flow_threshold: DynamicInput,     // Cease-to-pump: no diversion below this flow
+    pub commence_threshold: DynamicInput, // Commence-to-pump: once ceased, no diversion until the flow reaches this
     pub annual_cap: Option<f64>,
     pub account_idx: Option<usize>,
-    pub annual_cap_reset_month: u8,
+    pub annual_cap_year: WaterYear,                 // The year over which annual_cap applies
     pub demand_carryover_allowed: bool,
-    pub demand_carryover_reset_month: Option<u8>,
+    pub demand_carryover_year: Option<WaterYear>,   // Unmet demand is forgotten at the start of this year
 
     // Internal state only
     pub dsorders: [f64; MAX_DS_LINKS],
@@ -33,12 +41,19 @@ pub struct UnregulatedUserNode {
     annual_diversion: f64,
     pump_capacity_value: f64,
     flow_threshold_value: f64,
+    commence_threshold_value: f64,
+    pumping: bool,
     demand_carryover_value: f64,
+    below_flow_threshold: bool,
+
+    // Source index of the events the node emits
+    event_source: usize,
 
     // Recorders
     recorder_idx_usflow: Option<usize>,
     recorder_idx_pump_capacity: Option<usize>,
     recorder_idx_flow_threshold: Option<usize>,
+    recorder_idx_commence_threshold: Option<usize>,
     recorder_idx_demand_carryover: Option<usize>,
     recorder_idx_order: Option<usize>,
     recorder_idx_order_due: Option<usize>,
@@ -57,12 +72,15 @@ impl UnregulatedUserNode {
         Self {
             name: "".to_string(),
             demand_input: DynamicInput::default(),
+            demand_multiplier: 1.0,
+            policy_demand_factor: 1.0,
             pump_capacity: DynamicInput::default(),
             flow_threshold: DynamicInput::default(),
+            commence_threshold: DynamicInput::default(),
             annual_cap: None,
-            annual_cap_reset_month: 7,
+            annual_cap_year: WaterYear::JULY,
             demand_carryover_allowed: false,
-            demand_carryover_reset_month: None,
+            demand_carryover_year: None,
             ..Default::default()
         }
     }
@@ -83,25 +101,23 @@ impl Node for UnregulatedUserNode {
         self.annual_diversion = 0.0;
         self.demand_carryover_value = 0.0;
         self.flow_threshold_value = 0.0;
+        self.below_flow_threshold = false;
+        self.commence_threshold_value = 0.0;
+        self.pumping = false;
         self.pump_capacity_value = f64::INFINITY;
+        self.policy_demand_factor = 1.0;
 
         // Checks
-        if (self.annual_cap_reset_month < 1) || (self.annual_cap_reset_month > 12) {
-            return Err(format!("Invalid annual cap reset month at '{}': {}", self.name, self.annual_cap_reset_month).to_string());
-        }
         if let Some(v) = self.annual_cap {
             if v < 0.0 {
                 return Err(format!("Invalid annual cap at '{}': {} < 0", self.name, v).to_string());
             }
         }
-        if let Some(v) = self.demand_carryover_reset_month {
-            if (v < 1) || (v > 12) {
-                return Err(format!("Invalid demand carryover reset month at '{}': {}", self.name, v).to_string());
-            }
-        }
 
         // DynamicInput is already initialized during parsing
 
+        self.event_source = data_cache.add_event_source(&self.name);
+
         // Initialize result recorders
         self.recorder_idx_usflow = data_cache.get_series_idx(
             make_result_name(&self.name, "usflow").as_str(), false
@@ -112,6 +128,9 @@ impl Node for UnregulatedUserNode {
         self.recorder_idx_flow_threshold = data_cache.get_series_idx(
             make_result_name(&self.name, "flow_threshold").as_str(), false
         );
+        self.recorder_idx_commence_threshold = data_cache.get_series_idx(
+            make_result_name(&self.name, "commence_threshold").as_str(), false
+        );
         self.recorder_idx_demand_carryover = data_cache.get_series_idx(
             make_result_name(&self.name, "demand_carryover").as_str(), false
         );
@@ -159,17 +178,39 @@ impl Node for UnregulatedUserNode {
         }
 
         // Get demand value
-        let new_demand = self.demand_input.get_value(data_cache);
+        let new_demand = self.demand_input.get_value_or_policy(data_cache, &self.name, "Demand") * self.demand_multiplier * self.policy_demand_factor;
 
         // Work out availability considering flow threshold
         let mut available = match self.flow_threshold {
             DynamicInput::None { .. } => { self.usflow }
             _ => {
                 self.flow_threshold_value = self.flow_threshold.get_value(data_cache);
+                let below = self.usflow < self.flow_threshold_value;
+                if below && !self.below_flow_threshold {
+                    data_cache.emit_event(EventKind::CeaseToFlow, self.event_source, self.flow_threshold_value);
+                }
+                self.below_flow_threshold = below;
                 (self.usflow - self.flow_threshold_value).max(0.0)
             }
         };
 
+        // Once pumping has ceased it only commences again when the flow reaches the commence
+        // threshold. Pumping starts off ceased.
+        match self.commence_threshold {
+            DynamicInput::None { .. } => {}
+            _ => {
+                self.commence_threshold_value = self.commence_threshold.get_value(data_cache);
+                if self.usflow < self.flow_threshold_value {
+                    self.pumping = false;
+                } else if self.usflow >= self.commence_threshold_value {
+                    self.pumping = true;
+                }
+                if !self.pumping {
+                    available = 0.0;
+                }
+            }
+        };
+
         // Restrict for pump capacity
         match self.pump_capacity {
             DynamicInput::None { .. } => {}
@@ -183,13 +224,8 @@ impl Node for UnregulatedUserNode {
         match self.annual_cap {
             None => {}
             Some(annual_cap) => {
-                let d = data_cache.get_timestamp_day();
-                if d == 1 {
-                    let m = data_cache.get_timestamp_month() as u8;
-                    let s = data_cache.get_timestamp_seconds();
-                    if (m == self.annual_cap_reset_month) && (s == 0) {
-                        self.annual_diversion = 0.0;
-                    }
+                if data_cache.is_start_of_water_year(&self.annual_cap_year) {
+                    self.annual_diversion = 0.0;
                 }
                 available = available.min(annual_cap - self.annual_diversion);
             }
@@ -208,14 +244,9 @@ impl Node for UnregulatedUserNode {
         if self.demand_carryover_allowed {
             // Allowing demand carryover
             // Check if we need to reset the demand carryover today
-            if let Some(m_reset) = self.demand_carryover_reset_month {
-                let d = data_cache.get_timestamp_day();
-                if d == 1 {
-                    let m = data_cache.get_timestamp_month() as u8;
-                    let s = data_cache.get_timestamp_seconds();
-                    if (m == m_reset) && (s == 0) {
-                        self.demand_carryover_value = 0.0;
-                    }
+            if let Some(year) = &self.demand_carryover_year {
+                if data_cache.is_start_of_water_year(year) {
+                    self.demand_carryover_value = 0.0;
                 }
             }
             // Now calculate the diversion
@@ -224,6 +255,7 @@ impl Node for UnregulatedUserNode {
                 // we will not meet demand
                 self.diversion = available;
                 self.demand_carryover_value -= self.diversion;
+                data_cache.emit_event(EventKind::DemandShortfall, self.event_source, self.demand_carryover_value);
             } else {
                 // we will meet demand (incl carryover)
                 self.diversion = self.demand_carryover_value;
@@ -232,6 +264,9 @@ impl Node for UnregulatedUserNode {
         } else {
             // Not simulating carryover
             self.diversion = new_demand.min(available);
+            if self.diversion < new_demand {
+                data_cache.emit_event(EventKind::DemandShortfall, self.event_source, new_demand - self.diversion);
+            }
         }
 
         // Update account to reflect this diversion
@@ -265,6 +300,9 @@ impl Node for UnregulatedUserNode {
         if let Some(idx) = self.recorder_idx_flow_threshold {
             data_cache.add_value_at_index(idx, self.flow_threshold_value)
         }
+        if let Some(idx) = self.recorder_idx_commence_threshold {
+            data_cache.add_value_at_index(idx, self.commence_threshold_value)
+        }
         if let Some(idx) = self.recorder_idx_demand_carryover {
             data_cache.add_value_at_index(idx, self.demand_carryover_value)
         }
@@ -305,3 +343,31 @@ impl Node for UnregulatedUserNode {
         &mut self.dsorders
     }
 }
+
+
+// ============================================================================
+// OptimisableComponent Implementation
+// ============================================================================
+
+impl OptimisableComponent for UnregulatedUserNode {
+    fn set_param(&mut self, name: &str, value: f64) -> Result<(), String> {
+        match name {
+            "demand_multiplier" => {
+                self.demand_multiplier = value;
+                Ok(())
+            },
+            _ => Err(format!("Unknown unregulated user parameter: {}", name)),
+        }
+    }
+
+    fn get_param(&self, name: &str) -> Result<f64, String> {
+        match name {
+            "demand_multiplier" => Ok(self.demand_multiplier),
+            _ => Err(format!("Unknown unregulated user parameter: {}", name)),
+        }
+    }
+
+    fn list_params(&self) -> Vec<String> {
+        vec!["demand_multiplier".to_string()]
+    }
+}
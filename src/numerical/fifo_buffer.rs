@@ -60,6 +60,17 @@ impl FifoBuffer {
         self.data.iter().sum()
     }
 
+    /// Replaces the most recently inserted value. Returns false (and does nothing) if the
+    /// buffer has zero capacity.
+    pub fn set_back(&mut self, value: f64) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        let back_idx = (self.head + self.data.len() - 1) % self.data.len();
+        self.data[back_idx] = value;
+        true
+    }
+
     /// Returns the most recently inserted value without removing it.
     /// Returns None if buffer has zero capacity.
     pub fn back(&self) -> Option<f64> {
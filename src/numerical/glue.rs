@@ -0,0 +1,333 @@
+//! GLUE (Generalised Likelihood Uncertainty Estimation) Monte Carlo uncertainty analysis
+//!
+//! Parameter sets are sampled uniformly in gene space, i.e. from the prior ranges defined by
+//! the calibration config's `[parameters]` mappings (`lin_range`, `log_range`, ...), and run with
+//! the parallel batch runner ([`Model::run_batch_with_interrupt`]). Sets whose objective is below
+//! `threshold` are behavioural, with a likelihood weight of `threshold - objective`. Prediction
+//! bounds at each timestep are weighted quantiles of the behavioural simulations.
+//!
+//! Objectives are lower-better, so with `ONE_MINUS_NSE` a threshold of 0.5 keeps the sets with
+//! NSE > 0.5 and weights them by NSE - 0.5.
+//!
+//! Reference: Beven, K. and Binley, A. (1992). The future of distributed models: model
+//! calibration and uncertainty prediction. Hydrological Processes, 6(3), 279-298.
+
+use std::sync::Arc;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::data_management::data_cache::DataCache;
+use crate::misc::batch_run::{BatchOptions, ParameterSet};
+use crate::model::Model;
+use crate::numerical::opt::OptimisationProblem;
+use crate::timeseries::Timeseries;
+
+/// Parameter sets per call to the batch runner. Only behavioural simulations are kept between
+/// batches, which bounds memory use for large sample counts.
+const BATCH_SIZE: usize = 256;
+
+/// GLUE configuration
+#[derive(Clone, Debug)]
+pub struct GlueConfig {
+    /// Number of Monte Carlo parameter sets
+    pub n_samples: usize,
+
+    /// Objective value below which a parameter set is behavioural
+    pub threshold: f64,
+
+    /// Quantile for the lower prediction bound, e.g. 0.05
+    pub lower_quantile: f64,
+
+    /// Quantile for the upper prediction bound, e.g. 0.95
+    pub upper_quantile: f64,
+
+    /// Series to produce prediction bounds for. Empty means the model's `[outputs]`.
+    pub series: Vec<String>,
+
+    /// Random number generator seed (None = random seed)
+    pub seed: Option<u64>,
+
+    /// Number of worker threads. 0 means one per core.
+    pub n_threads: usize,
+}
+
+impl Default for GlueConfig {
+    fn default() -> Self {
+        Self {
+            n_samples: 1000,
+            threshold: 0.5,
+            lower_quantile: 0.05,
+            upper_quantile: 0.95,
+            series: vec![],
+            seed: None,
+            n_threads: 0,
+        }
+    }
+}
+
+/// A parameter set that passed the likelihood threshold
+#[derive(Clone, Debug)]
+pub struct BehaviouralSet {
+    /// Index of the sample among all Monte Carlo samples
+    pub sample: usize,
+
+    /// Normalised gene values in [0,1]
+    pub params: Vec<f64>,
+
+    pub objective: f64,
+
+    /// Likelihood weight, normalised to sum to 1 over all behavioural sets
+    pub weight: f64,
+}
+
+/// Prediction bounds for one series
+#[derive(Clone, Debug)]
+pub struct PredictionBounds {
+    pub series_name: String,
+
+    /// Data cache name of the lower bound, e.g. `node.x.dsflow_p05`
+    pub lower_name: String,
+
+    /// Data cache name of the upper bound, e.g. `node.x.dsflow_p95`
+    pub upper_name: String,
+
+    pub lower: Vec<f64>,
+    pub upper: Vec<f64>,
+}
+
+/// Outcome of a GLUE analysis
+#[derive(Clone, Debug)]
+pub struct GlueResult {
+    /// Gene names in parameter order, e.g. `["g(1)", "g(2)"]`
+    pub param_names: Vec<String>,
+
+    pub n_samples: usize,
+
+    /// Samples that violated a parameter constraint, or whose run or objective evaluation failed
+    pub n_failed: usize,
+
+    pub behavioural: Vec<BehaviouralSet>,
+
+    pub bounds: Vec<PredictionBounds>,
+}
+
+impl GlueResult {
+    /// Writes each lower and upper bound into the data cache under its `_pNN` name, replacing
+    /// any existing series of the same name.
+    pub fn write_to_data_cache(&self, data_cache: &mut DataCache) {
+        for b in &self.bounds {
+            for (name, values) in [(&b.lower_name, &b.lower), (&b.upper_name, &b.upper)] {
+                let idx = data_cache.get_or_add_new_series(name, false);
+                let mut ts = Timeseries::new(data_cache.step_size);
+                ts.name = name.clone();
+                ts.start_timestamp = data_cache.start_timestamp;
+                for &v in values {
+                    ts.push_value(v);
+                }
+                data_cache.replace_series(idx, ts);
+            }
+        }
+    }
+}
+
+/// Runs a GLUE analysis of the problem's model and objective.
+///
+/// `progress_callback` receives (completed runs, total runs). Returns `Ok(None)` if
+/// interrupted, and an error if no sample is behavioural.
+pub fn glue_analysis<F>(problem: &OptimisationProblem, config: &GlueConfig, interrupt_check: F,
+                        progress_callback: Option<Box<dyn Fn(usize, usize) + Send + Sync>>)
+    -> Result<Option<GlueResult>, String>
+where
+    F: Fn() -> bool + Sync,
+{
+    let k = problem.config.n_genes();
+    if k == 0 {
+        return Err("GLUE requires at least one parameter".to_string());
+    }
+    if config.n_samples == 0 {
+        return Err("GLUE requires at least one sample".to_string());
+    }
+    if !(0.0 < config.lower_quantile && config.lower_quantile < config.upper_quantile && config.upper_quantile < 1.0) {
+        return Err(format!("GLUE quantiles must satisfy 0 < lower < upper < 1, got {} and {}",
+                           config.lower_quantile, config.upper_quantile));
+    }
+
+    let mut model: Model = problem.model.clone();
+    model.configure()?;
+    let bound_series = if config.series.is_empty() { model.outputs.clone() } else { config.series.clone() };
+    if bound_series.is_empty() {
+        return Err("GLUE requires at least one series to bound (none given and the model has no [outputs])".to_string());
+    }
+
+    // The batch reports the bounded series plus whatever the objective needs
+    let mut batch_series = bound_series.clone();
+    for name in problem.simulated_series_names() {
+        if !batch_series.iter().any(|s| s.eq_ignore_ascii_case(&name)) {
+            batch_series.push(name);
+        }
+    }
+    let options = BatchOptions { series: batch_series, return_series: true, n_threads: config.n_threads };
+
+    let mut rng = match config.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let samples: Vec<Vec<f64>> = (0..config.n_samples)
+        .map(|_| (0..k).map(|_| rng.gen::<f64>()).collect())
+        .collect();
+
+    let progress_callback = progress_callback.map(Arc::new);
+    let start_timestamp = model.data_cache.start_timestamp;
+    let step_size = model.data_cache.step_size;
+
+    let mut n_failed = 0;
+    let mut behavioural: Vec<(BehaviouralSet, Vec<Vec<f64>>)> = Vec::new();
+    for (batch_idx, batch) in samples.chunks(BATCH_SIZE).enumerate() {
+        let offset = batch_idx * BATCH_SIZE;
+
+        // Samples that violate a parameter constraint are not run
+        let mut feasible: Vec<usize> = Vec::with_capacity(batch.len());
+        let mut param_sets: Vec<ParameterSet> = Vec::with_capacity(batch.len());
+        for (i, genes) in batch.iter().enumerate() {
+            match problem.config.try_evaluate(genes) {
+                Ok(set) => {
+                    feasible.push(i);
+                    param_sets.push(set);
+                }
+                Err(_) => n_failed += 1,
+            }
+        }
+
+        let batch_callback: Option<Box<dyn Fn(usize, usize) + Send + Sync>> = progress_callback.as_ref().map(|cb| {
+            let cb = Arc::clone(cb);
+            let total = config.n_samples;
+            let n_skipped = batch.len() - param_sets.len();
+            Box::new(move |completed: usize, _: usize| cb(offset + n_skipped + completed, total)) as Box<dyn Fn(usize, usize) + Send + Sync>
+        });
+
+        let results = match model.run_batch_with_interrupt(&param_sets, &options, &interrupt_check, batch_callback)? {
+            Some(results) => results,
+            None => return Ok(None),
+        };
+
+        for r in results {
+            if r.error.is_some() {
+                n_failed += 1;
+                continue;
+            }
+            let simulated: Vec<Timeseries> = r.series.into_iter().map(|s| {
+                let mut ts = Timeseries::new(step_size);
+                ts.name = s.name;
+                ts.start_timestamp = start_timestamp;
+                for v in s.values.unwrap_or_default() {
+                    ts.push_value(v);
+                }
+                ts
+            }).collect();
+
+            let objective = match problem.objective_from_series(&simulated) {
+                Ok(f) => f,
+                Err(_) => {
+                    n_failed += 1;
+                    continue;
+                }
+            };
+            if objective < config.threshold {
+                let values = simulated.into_iter().take(bound_series.len()).map(|ts| ts.values).collect();
+                let i = feasible[r.index];
+                let set = BehaviouralSet {
+                    sample: offset + i,
+                    params: batch[i].clone(),
+                    objective,
+                    weight: config.threshold - objective,
+                };
+                behavioural.push((set, values));
+            }
+        }
+    }
+
+    if behavioural.is_empty() {
+        return Err(format!("No behavioural parameter sets: none of the {} samples had an objective below the threshold {}",
+                           config.n_samples, config.threshold));
+    }
+
+    let total_weight: f64 = behavioural.iter().map(|(set, _)| set.weight).sum();
+    for (set, _) in behavioural.iter_mut() {
+        set.weight /= total_weight;
+    }
+    let weights: Vec<f64> = behavioural.iter().map(|(set, _)| set.weight).collect();
+
+    let bounds = bound_series.iter().enumerate().map(|(s, name)| {
+        let n_steps = behavioural.iter().map(|(_, values)| values[s].len()).max().unwrap_or(0);
+        let mut lower = Vec::with_capacity(n_steps);
+        let mut upper = Vec::with_capacity(n_steps);
+        let mut step_values: Vec<(f64, f64)> = Vec::with_capacity(behavioural.len());
+        for t in 0..n_steps {
+            step_values.clear();
+            step_values.extend(behavioural.iter().zip(&weights)
+                .filter_map(|((_, values), &w)| values[s].get(t).filter(|v| !v.is_nan()).map(|&v| (v, w))));
+            step_values.sort_by(|a, b| a.0.total_cmp(&b.0));
+            lower.push(weighted_quantile(&step_values, config.lower_quantile));
+            upper.push(weighted_quantile(&step_values, config.upper_quantile));
+        }
+        PredictionBounds {
+            series_name: name.clone(),
+            lower_name: quantile_series_name(name, config.lower_quantile),
+            upper_name: quantile_series_name(name, config.upper_quantile),
+            lower,
+            upper,
+        }
+    }).collect();
+
+    Ok(Some(GlueResult {
+        param_names: problem.config.gene_names(),
+        n_samples: config.n_samples,
+        n_failed,
+        behavioural: behavioural.into_iter().map(|(set, _)| set).collect(),
+        bounds,
+    }))
+}
+
+/// Data cache name for a quantile of a series, e.g. (`node.x.dsflow`, 0.05) -> `node.x.dsflow_p05`
+pub fn quantile_series_name(series_name: &str, quantile: f64) -> String {
+    format!("{}_p{:02}", series_name, (quantile * 100.0).round() as u32)
+}
+
+/// Quantile of a weighted sample sorted by value: the smallest value whose cumulative weight
+/// reaches `q` of the total. NaN for an empty sample.
+pub(crate) fn weighted_quantile(sorted: &[(f64, f64)], q: f64) -> f64 {
+    let total: f64 = sorted.iter().map(|&(_, w)| w).sum();
+    let mut cumulative = 0.0;
+    for &(v, w) in sorted {
+        cumulative += w;
+        if cumulative >= q * total {
+            return v;
+        }
+    }
+    sorted.last().map(|&(v, _)| v).unwrap_or(f64::NAN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_quantile() {
+        let equal: Vec<(f64, f64)> = (1..=10).map(|v| (v as f64, 1.0)).collect();
+        assert_eq!(weighted_quantile(&equal, 0.05), 1.0);
+        assert_eq!(weighted_quantile(&equal, 0.5), 5.0);
+        assert_eq!(weighted_quantile(&equal, 0.95), 10.0);
+
+        // A heavy weight drags both quantiles toward its value
+        let skewed = vec![(1.0, 0.01), (2.0, 0.98), (3.0, 0.01)];
+        assert_eq!(weighted_quantile(&skewed, 0.05), 2.0);
+        assert_eq!(weighted_quantile(&skewed, 0.95), 2.0);
+
+        assert!(weighted_quantile(&[], 0.5).is_nan());
+    }
+
+    #[test]
+    fn test_quantile_series_name() {
+        assert_eq!(quantile_series_name("node.a.dsflow", 0.05), "node.a.dsflow_p05");
+        assert_eq!(quantile_series_name("node.a.dsflow", 0.95), "node.a.dsflow_p95");
+    }
+}
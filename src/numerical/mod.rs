@@ -1,6 +1,9 @@
 pub mod table;
 pub mod mathfn;
 pub mod opt;
+pub mod sensitivity;
+pub mod glue;
 pub mod fifo_buffer;
 pub mod interpolation;
 pub mod table_discontinuous;
+pub mod series_comparison;
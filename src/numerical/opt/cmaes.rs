@@ -0,0 +1,594 @@
+//! CMA-ES (Covariance Matrix Adaptation Evolution Strategy) with IPOP restarts
+//!
+//! Samples each generation from a multivariate normal distribution whose mean, step size
+//! and covariance matrix are adapted from the best samples. When a run stagnates (flat
+//! objective, collapsed step size or ill-conditioned covariance) the search restarts from
+//! a new random mean with double the population size (IPOP), until the evaluation budget
+//! is exhausted.
+//!
+//! Parameters are kept in [0,1] by projecting samples onto the bounds before evaluation.
+//!
+//! References:
+//! - Hansen, N. (2016). The CMA evolution strategy: A tutorial. arXiv:1604.00772.
+//! - Auger, A. and Hansen, N. (2005). A restart CMA evolution strategy with increasing
+//!   population size. IEEE Congress on Evolutionary Computation, 1769-1776.
+
+use super::optimisable::Optimisable;
+use super::optimizer_trait::{OptimizationProgress, OptimizationResult, Optimizer, ProgressCallback};
+use super::termination::{EarlyStopping, EarlyStoppingMonitor};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Stop a run when the best objectives of recent generations differ by less than this
+const TOL_FUN: f64 = 1e-12;
+
+/// Stop a run when the step size in every coordinate is smaller than this
+const TOL_X: f64 = 1e-12;
+
+/// Stop a run when the covariance matrix condition number exceeds this
+const MAX_CONDITION: f64 = 1e14;
+
+/// CMA-ES optimiser configuration
+pub struct CmaEsConfig {
+    /// Initial population size (lambda). 0 = default of 4 + 3 ln(n). Doubled on each restart.
+    pub population_size: usize,
+
+    /// Initial step size in normalised [0,1] parameter space, typically 0.2 to 0.5
+    pub sigma: f64,
+
+    /// Termination criterion: stop after approximately this many function evaluations
+    pub termination_evaluations: usize,
+
+    /// Maximum number of IPOP restarts (the budget usually runs out first)
+    pub max_restarts: usize,
+
+    /// Random number generator seed (None = random seed)
+    pub seed: Option<u64>,
+
+    /// Number of threads for parallel evaluation (1 = single-threaded)
+    pub n_threads: usize,
+
+    /// Optional criteria for stopping before the evaluation budget is used
+    pub early_stopping: EarlyStopping,
+
+    /// Optional callback for progress reporting
+    pub progress_callback: Option<ProgressCallback>,
+}
+
+impl Default for CmaEsConfig {
+    fn default() -> Self {
+        Self {
+            population_size: 0,
+            sigma: 0.3,
+            termination_evaluations: 5000,
+            max_restarts: 9,
+            seed: None,
+            n_threads: 1,
+            early_stopping: EarlyStopping::default(),
+            progress_callback: None,
+        }
+    }
+}
+
+/// Strategy parameters for one run, which depend on the dimension and population size
+struct Strategy {
+    weights: Vec<f64>,
+    mueff: f64,
+    cc: f64,
+    cs: f64,
+    c1: f64,
+    cmu: f64,
+    damps: f64,
+    chi_n: f64,
+}
+
+impl Strategy {
+    fn new(n: usize, lambda: usize) -> Self {
+        let nf = n as f64;
+        let mu = lambda / 2;
+        let raw: Vec<f64> = (1..=mu).map(|i| (mu as f64 + 0.5).ln() - (i as f64).ln()).collect();
+        let total: f64 = raw.iter().sum();
+        let weights: Vec<f64> = raw.iter().map(|w| w / total).collect();
+        let mueff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+
+        let cc = (4.0 + mueff / nf) / (nf + 4.0 + 2.0 * mueff / nf);
+        let cs = (mueff + 2.0) / (nf + mueff + 5.0);
+        let c1 = 2.0 / ((nf + 1.3).powi(2) + mueff);
+        let cmu = (1.0 - c1).min(2.0 * (mueff - 2.0 + 1.0 / mueff) / ((nf + 2.0).powi(2) + mueff));
+        let damps = 1.0 + 2.0 * (((mueff - 1.0) / (nf + 1.0)).sqrt() - 1.0).max(0.0) + cs;
+        let chi_n = nf.sqrt() * (1.0 - 1.0 / (4.0 * nf) + 1.0 / (21.0 * nf * nf));
+
+        Self { weights, mueff, cc, cs, c1, cmu, damps, chi_n }
+    }
+}
+
+/// CMA-ES optimiser
+pub struct CmaEs {
+    config: CmaEsConfig,
+}
+
+impl CmaEs {
+    /// Create a new CMA-ES optimiser with given configuration
+    pub fn new(config: CmaEsConfig) -> Self {
+        Self { config }
+    }
+
+    /// Create a new CMA-ES optimiser with default configuration
+    pub fn with_defaults() -> Self {
+        Self::new(CmaEsConfig::default())
+    }
+
+    /// Set the early-stopping criteria
+    pub fn with_early_stopping(mut self, early_stopping: EarlyStopping) -> Self {
+        self.config.early_stopping = early_stopping;
+        self
+    }
+
+    /// Run the optimisation
+    pub fn optimise(&self, problem: &mut dyn Optimisable) -> OptimizationResult {
+        let start_time = Instant::now();
+        let n = problem.n_params();
+
+        let mut rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let thread_pool = if self.config.n_threads > 1 {
+            Some(rayon::ThreadPoolBuilder::new()
+                .num_threads(self.config.n_threads)
+                .build()
+                .unwrap())
+        } else {
+            None
+        };
+
+        let mut lambda = if self.config.population_size > 0 {
+            self.config.population_size.max(2)
+        } else {
+            4 + (3.0 * (n.max(1) as f64).ln()).floor() as usize
+        };
+
+        let mut n_evaluations = 0;
+        let mut best_params = vec![0.5; n];
+        let mut best_objective = f64::INFINITY;
+        let mut objective_history: Vec<f64> = Vec::new();
+        let mut generation = 0;
+        let mut restart = 0;
+        let mut monitor = EarlyStoppingMonitor::new(&self.config.early_stopping, start_time);
+        let mut stop_reason = None;
+
+        while n_evaluations < self.config.termination_evaluations {
+            let strategy = Strategy::new(n.max(1), lambda);
+            let mu = strategy.weights.len();
+
+            // Fresh run state: random mean, isotropic covariance
+            let mut mean: Vec<f64> = (0..n).map(|_| rng.gen::<f64>()).collect();
+            let mut sigma = self.config.sigma;
+            let mut cov = identity(n);
+            let mut b = identity(n);
+            let mut d = vec![1.0; n];
+            let mut pc = vec![0.0; n];
+            let mut ps = vec![0.0; n];
+            let mut run_history: Vec<f64> = Vec::new();
+            let history_len = 10 + (30.0 * n as f64 / lambda as f64).ceil() as usize;
+            let mut run_generation: i32 = 0;
+
+            loop {
+                if let Some(ref callback) = self.config.progress_callback {
+                    let progress = OptimizationProgress::new(n_evaluations, best_objective, start_time.elapsed())
+                        .with_data("generation", generation as f64)
+                        .with_data("restart", restart as f64)
+                        .with_data("population_size", lambda as f64)
+                        .with_data("sigma", sigma);
+                    callback(&progress);
+                }
+
+                // Sample lambda points: x = m + sigma * B * D * z, projected onto [0,1]
+                let mut samples: Vec<Vec<f64>> = Vec::with_capacity(lambda);
+                for _ in 0..lambda {
+                    let z: Vec<f64> = (0..n).map(|_| standard_normal(&mut rng)).collect();
+                    let x: Vec<f64> = (0..n)
+                        .map(|i| {
+                            let y: f64 = (0..n).map(|j| b[i][j] * d[j] * z[j]).sum();
+                            (mean[i] + sigma * y).clamp(0.0, 1.0)
+                        })
+                        .collect();
+                    samples.push(x);
+                }
+
+                let objectives = self.evaluate_all(problem, &samples, thread_pool.as_ref(), &mut n_evaluations);
+
+                let mut order: Vec<usize> = (0..lambda).collect();
+                order.sort_by(|&a, &b| objectives[a].total_cmp(&objectives[b]));
+
+                let gen_best = objectives[order[0]];
+                if gen_best < best_objective {
+                    best_objective = gen_best;
+                    best_params = samples[order[0]].clone();
+                }
+                objective_history.push(best_objective);
+                run_history.push(gen_best);
+                generation += 1;
+                run_generation += 1;
+                stop_reason = monitor.check(n_evaluations, best_objective);
+
+                if n_evaluations >= self.config.termination_evaluations || n == 0 || stop_reason.is_some() {
+                    break;
+                }
+
+                // Recombination: new mean from the mu best samples
+                let old_mean = mean.clone();
+                let ys: Vec<Vec<f64>> = order[..mu].iter()
+                    .map(|&k| (0..n).map(|i| (samples[k][i] - old_mean[i]) / sigma).collect())
+                    .collect();
+                let y_w: Vec<f64> = (0..n)
+                    .map(|i| strategy.weights.iter().zip(&ys).map(|(w, y)| w * y[i]).sum())
+                    .collect();
+                for i in 0..n {
+                    mean[i] = old_mean[i] + sigma * y_w[i];
+                }
+
+                // Step-size path uses C^(-1/2) * y_w = B * D^-1 * B^T * y_w
+                let bt_y: Vec<f64> = (0..n).map(|j| (0..n).map(|i| b[i][j] * y_w[i]).sum::<f64>() / d[j]).collect();
+                let c_inv_sqrt_y: Vec<f64> = (0..n).map(|i| (0..n).map(|j| b[i][j] * bt_y[j]).sum()).collect();
+                let cs_norm = (strategy.cs * (2.0 - strategy.cs) * strategy.mueff).sqrt();
+                for i in 0..n {
+                    ps[i] = (1.0 - strategy.cs) * ps[i] + cs_norm * c_inv_sqrt_y[i];
+                }
+                let ps_norm = ps.iter().map(|v| v * v).sum::<f64>().sqrt();
+                let h_sig = ps_norm / (1.0 - (1.0 - strategy.cs).powi(2 * run_generation)).sqrt() / strategy.chi_n
+                    < 1.4 + 2.0 / (n as f64 + 1.0);
+
+                let cc_norm = (strategy.cc * (2.0 - strategy.cc) * strategy.mueff).sqrt();
+                for i in 0..n {
+                    pc[i] = (1.0 - strategy.cc) * pc[i] + if h_sig { cc_norm * y_w[i] } else { 0.0 };
+                }
+
+                // Covariance update: rank-one (evolution path) plus rank-mu (selected steps)
+                let delta_h = if h_sig { 0.0 } else { strategy.cc * (2.0 - strategy.cc) };
+                for i in 0..n {
+                    for j in 0..=i {
+                        let rank_mu: f64 = strategy.weights.iter().zip(&ys).map(|(w, y)| w * y[i] * y[j]).sum();
+                        let value = (1.0 - strategy.c1 - strategy.cmu) * cov[i][j]
+                            + strategy.c1 * (pc[i] * pc[j] + delta_h * cov[i][j])
+                            + strategy.cmu * rank_mu;
+                        cov[i][j] = value;
+                        cov[j][i] = value;
+                    }
+                }
+
+                sigma *= ((strategy.cs / strategy.damps) * (ps_norm / strategy.chi_n - 1.0)).exp();
+
+                let (eigenvalues, eigenvectors) = symmetric_eigen(&cov);
+                d = eigenvalues.iter().map(|&e| e.max(1e-300).sqrt()).collect();
+                b = eigenvectors;
+
+                // Restart criteria
+                let recent = &run_history[run_history.len().saturating_sub(history_len)..];
+                let (lo, hi) = recent.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+                let flat = run_history.len() >= history_len && (hi - lo < TOL_FUN || !hi.is_finite());
+                let tiny_step = (0..n).all(|i| sigma * cov[i][i].sqrt().max(pc[i].abs()) < TOL_X);
+                let d_max = d.iter().cloned().fold(0.0, f64::max);
+                let d_min = d.iter().cloned().fold(f64::INFINITY, f64::min);
+                let ill_conditioned = (d_max / d_min).powi(2) > MAX_CONDITION;
+                if flat || tiny_step || ill_conditioned || !sigma.is_finite() {
+                    break;
+                }
+            }
+
+            if n_evaluations >= self.config.termination_evaluations
+                || restart >= self.config.max_restarts || n == 0 || stop_reason.is_some() {
+                break;
+            }
+            restart += 1;
+            lambda *= 2;
+        }
+
+        // Note: No final callback here - the CLI handles final rendering via render_final()
+
+        let (success, message) = if best_objective.is_finite() {
+            (true, stop_reason.unwrap_or_else(|| "Optimisation completed successfully".to_string()))
+        } else {
+            (false, "Optimization failed: all evaluations failed. \
+                     Check model configuration (node names, parameter targets, input data).".to_string())
+        };
+
+        OptimizationResult::new(best_params, best_objective, n_evaluations, success, message, start_time.elapsed())
+            .with_data("generations", serde_json::Value::from(generation))
+            .with_data("restarts", serde_json::Value::from(restart))
+            .with_data("objective_history", serde_json::to_value(&objective_history).unwrap())
+    }
+
+    /// Evaluate a batch of parameter vectors, sequentially or on the thread pool.
+    /// Failed evaluations get an objective of infinity.
+    fn evaluate_all(
+        &self,
+        problem: &mut dyn Optimisable,
+        params: &[Vec<f64>],
+        pool: Option<&rayon::ThreadPool>,
+        n_evaluations: &mut usize,
+    ) -> Vec<f64> {
+        let evaluate_one = |prob: &mut dyn Optimisable, x: &[f64]| -> Option<f64> {
+            prob.set_params(x).ok()?;
+            prob.evaluate().ok().map(|f| if f.is_nan() { f64::INFINITY } else { f })
+        };
+
+        let Some(pool) = pool else {
+            return params.iter().map(|x| {
+                let result = evaluate_one(problem, x);
+                if result.is_some() {
+                    *n_evaluations += 1;
+                }
+                result.unwrap_or(f64::INFINITY)
+            }).collect();
+        };
+
+        // Worker-based parallel evaluation, as in DE: one problem clone per thread
+        use rayon::prelude::*;
+        use std::sync::{Arc, Mutex};
+        let worker_problems: Vec<Arc<Mutex<Box<dyn Optimisable>>>> =
+            (0..self.config.n_threads)
+                .map(|_| Arc::new(Mutex::new(problem.clone_for_parallel())))
+                .collect();
+        let eval_counter = AtomicUsize::new(0);
+
+        let objectives = pool.install(|| {
+            params.par_iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    let mut prob = worker_problems[i % self.config.n_threads].lock().unwrap();
+                    match evaluate_one(prob.as_mut(), x) {
+                        Some(f) => {
+                            eval_counter.fetch_add(1, Ordering::Relaxed);
+                            f
+                        }
+                        None => f64::INFINITY,
+                    }
+                })
+                .collect()
+        });
+
+        *n_evaluations += eval_counter.load(Ordering::Relaxed);
+        objectives
+    }
+}
+
+fn identity(n: usize) -> Vec<Vec<f64>> {
+    (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect()
+}
+
+/// Standard normal sample via the Box-Muller transform
+fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();  // (0, 1], avoids ln(0)
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Eigen-decomposition of a symmetric matrix by cyclic Jacobi rotations
+///
+/// Returns (eigenvalues, eigenvectors) with eigenvector k in column k.
+fn symmetric_eigen(matrix: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = matrix.len();
+    let mut a: Vec<Vec<f64>> = matrix.to_vec();
+    let mut v = identity(n);
+
+    for _sweep in 0..100 {
+        let off_diagonal: f64 = (0..n).flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off_diagonal < 1e-30 {
+            break;
+        }
+        for p in 0..n {
+            for q in (p + 1)..n {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                // A' = J^T A J, applied as column then row rotations
+                for row in a.iter_mut() {
+                    let akp = row[p];
+                    let akq = row[q];
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p].clone(), a[q].clone());
+                for (k, (apk, aqk)) in row_p.into_iter().zip(row_q).enumerate() {
+                    a[p][k] = c * apk - s * aqk;
+                    a[q][k] = s * apk + c * aqk;
+                }
+                for row in v.iter_mut() {
+                    let vkp = row[p];
+                    let vkq = row[q];
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    ((0..n).map(|i| a[i][i]).collect(), v)
+}
+
+impl Optimizer for CmaEs {
+    fn optimize(
+        &self,
+        problem: &mut dyn Optimisable,
+        progress_callback: Option<Box<dyn Fn(&OptimizationProgress) + Send + Sync>>,
+    ) -> OptimizationResult {
+        if let Some(callback) = progress_callback {
+            // Callback provided via parameter overrides the config callback
+            let mut config = self.config.clone();
+            config.progress_callback = Some(callback);
+            CmaEs::new(config).optimise(problem)
+        } else {
+            self.optimise(problem)
+        }
+    }
+
+    fn name(&self) -> &str {
+        "CMA-ES"
+    }
+}
+
+// Implement Clone for CmaEsConfig to support wrapping
+impl Clone for CmaEsConfig {
+    fn clone(&self) -> Self {
+        Self {
+            population_size: self.population_size,
+            sigma: self.sigma,
+            termination_evaluations: self.termination_evaluations,
+            max_restarts: self.max_restarts,
+            seed: self.seed,
+            n_threads: self.n_threads,
+            early_stopping: self.early_stopping.clone(),
+            progress_callback: None, // Callbacks can't be cloned
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Rotated ellipsoid centred at 0.3 in every coordinate; needs covariance adaptation
+    #[derive(Clone)]
+    struct Ellipsoid {
+        x: Vec<f64>,
+    }
+
+    impl Optimisable for Ellipsoid {
+        fn n_params(&self) -> usize {
+            self.x.len()
+        }
+
+        fn set_params(&mut self, params: &[f64]) -> Result<(), String> {
+            self.x = params.to_vec();
+            Ok(())
+        }
+
+        fn get_params(&self) -> Vec<f64> {
+            self.x.clone()
+        }
+
+        fn evaluate(&mut self) -> Result<f64, String> {
+            let n = self.x.len();
+            Ok((0..n).map(|i| {
+                let partial: f64 = self.x[..=i].iter().map(|v| v - 0.3).sum();
+                1e3f64.powf(i as f64 / (n - 1) as f64) * partial * partial
+            }).sum())
+        }
+
+        fn clone_for_parallel(&self) -> Box<dyn Optimisable> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Rastrigin in [0,1]^n with optimum at 0.5; multimodal, so restarts help
+    #[derive(Clone)]
+    struct Rastrigin {
+        x: Vec<f64>,
+    }
+
+    impl Optimisable for Rastrigin {
+        fn n_params(&self) -> usize {
+            self.x.len()
+        }
+
+        fn set_params(&mut self, params: &[f64]) -> Result<(), String> {
+            self.x = params.to_vec();
+            Ok(())
+        }
+
+        fn get_params(&self) -> Vec<f64> {
+            self.x.clone()
+        }
+
+        fn evaluate(&mut self) -> Result<f64, String> {
+            Ok(self.x.iter().map(|v| {
+                let z = (v - 0.5) * 10.24;
+                z * z - 10.0 * (2.0 * std::f64::consts::PI * z).cos() + 10.0
+            }).sum())
+        }
+
+        fn clone_for_parallel(&self) -> Box<dyn Optimisable> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let m = vec![vec![2.0, 1.0], vec![1.0, 2.0]];
+        let (values, vectors) = symmetric_eigen(&m);
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        assert!((sorted[0] - 1.0).abs() < 1e-12);
+        assert!((sorted[1] - 3.0).abs() < 1e-12);
+
+        // A v = lambda v for each column
+        for k in 0..2 {
+            for i in 0..2 {
+                let av: f64 = (0..2).map(|j| m[i][j] * vectors[j][k]).sum();
+                assert!((av - values[k] * vectors[i][k]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cmaes_solves_rotated_ellipsoid() {
+        let config = CmaEsConfig {
+            termination_evaluations: 6000,
+            seed: Some(3),
+            ..Default::default()
+        };
+        let mut problem = Ellipsoid { x: vec![0.5; 8] };
+        let result = CmaEs::new(config).optimise(&mut problem);
+        assert!(result.success);
+        assert!(result.best_objective < 1e-8, "best objective {}", result.best_objective);
+        for p in &result.best_params {
+            assert!((p - 0.3).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_cmaes_restarts_with_larger_population() {
+        let populations = Arc::new(Mutex::new(Vec::<f64>::new()));
+        let seen = Arc::clone(&populations);
+        let callback: ProgressCallback = Box::new(move |p: &OptimizationProgress| {
+            let mut seen = seen.lock().unwrap();
+            let lambda = p.algorithm_data["population_size"];
+            if seen.last() != Some(&lambda) {
+                seen.push(lambda);
+            }
+        });
+
+        let config = CmaEsConfig {
+            termination_evaluations: 20000,
+            seed: Some(11),
+            n_threads: 2,
+            ..Default::default()
+        };
+        let mut problem = Rastrigin { x: vec![0.5; 4] };
+        let result = CmaEs::new(config).optimize(&mut problem, Some(callback));
+
+        let restarts = result.algorithm_data["restarts"].as_u64().unwrap() as usize;
+        assert!(restarts >= 1, "expected at least one IPOP restart");
+        assert!(result.n_evaluations >= 20000);
+        assert!(result.best_objective < 1.0, "best objective {}", result.best_objective);
+
+        // Population doubles on each restart: 4 + 3 ln(4) = 8, then 16, 32, ...
+        let populations = populations.lock().unwrap();
+        assert_eq!(populations.len(), restarts + 1);
+        for (k, lambda) in populations.iter().enumerate() {
+            assert_eq!(*lambda, (8 << k) as f64);
+        }
+    }
+}
@@ -8,6 +8,7 @@
 
 use super::optimisable::Optimisable;
 use super::optimizer_trait::OptimizationProgress;
+use super::termination::{EarlyStopping, EarlyStoppingMonitor};
 use rand::{Rng, RngCore, SeedableRng};
 use rand::rngs::StdRng;
 use rand::distributions::Uniform;
@@ -60,9 +61,14 @@ pub struct DEConfig {
     /// Random number generator seed (None = random seed)
     pub seed: Option<u64>,
 
-    /// Number of threads for parallel evaluation (1 = single-threaded)
+    /// Number of threads for parallel evaluation (1 = single-threaded). With a seed, the
+    /// results are the same for any number of threads: all random numbers are drawn on the
+    /// calling thread and the trials' objectives are collected in population order.
     pub n_threads: usize,
 
+    /// Optional criteria for stopping before the evaluation budget is used
+    pub early_stopping: EarlyStopping,
+
     /// Optional callback for progress reporting
     pub progress_callback: Option<Box<dyn Fn(&OptimizationProgress) + Send + Sync>>,
 }
@@ -76,6 +82,7 @@ impl Default for DEConfig {
             cr: 0.9,
             seed: None,
             n_threads: 1,
+            early_stopping: EarlyStopping::default(),
             progress_callback: None,
         }
     }
@@ -97,6 +104,12 @@ impl DifferentialEvolution {
         Self::new(DEConfig::default())
     }
 
+    /// Set the early-stopping criteria
+    pub fn with_early_stopping(mut self, early_stopping: EarlyStopping) -> Self {
+        self.config.early_stopping = early_stopping;
+        self
+    }
+
     /// Run optimisation on the given problem
     pub fn optimise(&self, problem: &mut dyn Optimisable) -> DEResult {
         let start_time = Instant::now();
@@ -143,12 +156,12 @@ impl DifferentialEvolution {
                         },
                         Err(e) => {
                             // If evaluation fails, leave objective as infinity (invalid solution)
-                            eprintln!("Warning: Evaluation failed for individual {}: {}", i, e);
+                            log::warn!("Evaluation failed for individual {}: {}", i, e);
                         }
                     }
                 },
                 Err(e) => {
-                    eprintln!("Warning: Failed to set params for individual {}: {}", i, e);
+                    log::warn!("Failed to set params for individual {}: {}", i, e);
                 }
             }
         }
@@ -181,9 +194,11 @@ impl DifferentialEvolution {
             };
         }
 
-        // Main DE loop - terminate based on evaluations
+        // Main DE loop - terminate based on evaluations or early-stopping criteria
         let mut generation = 0;
-        while n_evaluations < self.config.termination_evaluations {
+        let mut monitor = EarlyStoppingMonitor::new(&self.config.early_stopping, start_time);
+        let mut stop_reason = monitor.check(n_evaluations, best_objective);
+        while n_evaluations < self.config.termination_evaluations && stop_reason.is_none() {
 
             // Progress callback
             if let Some(ref callback) = self.config.progress_callback {
@@ -194,6 +209,7 @@ impl DifferentialEvolution {
                     n_evaluations,
                     best_objective,
                     population_objectives: Some(objective.clone()),
+                    population_params: Some(population.clone()),
                     elapsed: start_time.elapsed(),
                     algorithm_data,
                 };
@@ -252,6 +268,7 @@ impl DifferentialEvolution {
 
             objective_history.push(best_objective);
             generation += 1;
+            stop_reason = monitor.check(n_evaluations, best_objective);
         }
 
         // Note: No final callback here - the CLI handles final rendering via render_final()
@@ -264,7 +281,7 @@ impl DifferentialEvolution {
             n_evaluations,
             objective_history,
             success: true,
-            message: "Optimisation completed successfully".to_string(),
+            message: stop_reason.unwrap_or_else(|| "Optimisation completed successfully".to_string()),
             elapsed: start_time.elapsed(),
         }
     }
@@ -420,6 +437,7 @@ impl Clone for DEConfig {
             cr: self.cr,
             seed: self.seed,
             n_threads: self.n_threads,
+            early_stopping: self.early_stopping.clone(),
             progress_callback: None, // Callbacks can't be cloned
         }
     }
@@ -472,6 +490,7 @@ mod tests {
             cr: 0.9,
             seed: Some(42),
             n_threads: 1,
+            early_stopping: EarlyStopping::default(),
             progress_callback: None,
         };
 
@@ -480,6 +499,25 @@ mod tests {
         assert_eq!(de.config.termination_evaluations, 200);
     }
 
+    #[test]
+    fn test_de_early_stopping_reported_in_message() {
+        let mut problem = SimpleProblem { n_params: 3 };
+        let criteria = EarlyStopping { objective_tolerance: Some(1e-6), ..Default::default() };
+        let de = DifferentialEvolution::new(DEConfig { population_size: 10, seed: Some(1), ..Default::default() })
+            .with_early_stopping(criteria);
+        let result = de.optimise(&mut problem);
+        assert!(result.success);
+        assert_eq!(result.n_evaluations, 10);
+        assert!(result.message.contains("objective_tolerance"), "{}", result.message);
+
+        let criteria = EarlyStopping { stagnation_evaluations: Some(50), ..Default::default() };
+        let de = DifferentialEvolution::new(DEConfig { population_size: 10, seed: Some(1), ..Default::default() })
+            .with_early_stopping(criteria);
+        let result = de.optimise(&mut problem);
+        assert_eq!(result.n_evaluations, 60);
+        assert!(result.message.contains("no improvement"), "{}", result.message);
+    }
+
     #[test]
     fn test_select_random_indices() {
         let config = DEConfig {
@@ -6,13 +6,16 @@
 use super::{
     OptimisationConfig, AlgorithmParams, Optimizer,
     DifferentialEvolution, de::DEConfig,
-    Sce, sce::SceConfig
+    Sce, sce::SceConfig,
+    Nsga2, nsga2::Nsga2Config,
+    CmaEs, cmaes::CmaEsConfig,
+    Dds, dds::DdsConfig
 };
 
 /// Error type for optimizer creation
 #[derive(Debug, thiserror::Error)]
 pub enum OptimizerFactoryError {
-    #[error("Algorithm '{0}' is not yet implemented. Currently supported: DE")]
+    #[error("Algorithm '{0}' is not yet implemented. Currently supported: DE, SCE, CMAES, NSGA2, DDS")]
     NotImplemented(String),
 
     #[error("Invalid configuration: {0}")]
@@ -72,6 +75,7 @@ pub fn create_optimizer_with_callback(
                 cr: *cr,
                 seed: config.random_seed,
                 n_threads: config.n_threads,
+                early_stopping: config.early_stopping.clone(),
                 progress_callback,
             };
             Ok(Box::new(DifferentialEvolution::new(de_config)))
@@ -83,11 +87,33 @@ pub fn create_optimizer_with_callback(
                 config.random_seed,
                 config.n_threads,
                 progress_callback,
-            );
+            ).with_early_stopping(config.early_stopping.clone());
             Ok(Box::new(sce))
         }
-        AlgorithmParams::CMAES { .. } => {
-            Err(OptimizerFactoryError::NotImplemented("CMA-ES".to_string()))
+        AlgorithmParams::NSGA2 { .. } => {
+            Ok(Box::new(create_nsga2_optimizer_with_callback(config, progress_callback)))
+        }
+        AlgorithmParams::CMAES { population_size, sigma } => {
+            let cmaes = create_cmaes_optimizer_with_callback(
+                *population_size,
+                *sigma,
+                config.termination_evaluations,
+                config.random_seed,
+                config.n_threads,
+                progress_callback,
+            ).with_early_stopping(config.early_stopping.clone());
+            Ok(Box::new(cmaes))
+        }
+        AlgorithmParams::DDS { r, starts } => {
+            let dds = create_dds_optimizer_with_callback(
+                *r,
+                *starts,
+                config.termination_evaluations,
+                config.random_seed,
+                config.n_threads,
+                progress_callback,
+            ).with_early_stopping(config.early_stopping.clone());
+            Ok(Box::new(dds))
         }
     }
 }
@@ -163,6 +189,7 @@ pub fn create_de_optimizer_with_callback(
         cr,
         seed,
         n_threads,
+        early_stopping: Default::default(),
         progress_callback,
     };
 
@@ -223,12 +250,109 @@ pub fn create_sce_optimizer_with_callback(
         termination_evaluations,
         seed,
         n_threads,
+        early_stopping: Default::default(),
         progress_callback,
     };
 
     Sce::new(config)
 }
 
+/// Create a CMA-ES optimizer with a progress callback
+///
+/// # Arguments
+/// * `population_size` - Initial population size (0 = default 4 + 3 ln(n)), doubled on each IPOP restart
+/// * `sigma` - Initial step size in normalised [0,1] parameter space
+/// * `termination_evaluations` - When to stop optimization
+/// * `seed` - Optional random seed
+/// * `n_threads` - Number of threads for parallel evaluation
+/// * `progress_callback` - Optional progress callback receiving OptimizationProgress
+///
+/// # Returns
+/// A CmaEs optimizer with the callback configured
+pub fn create_cmaes_optimizer_with_callback(
+    population_size: usize,
+    sigma: f64,
+    termination_evaluations: usize,
+    seed: Option<u64>,
+    n_threads: usize,
+    progress_callback: Option<super::optimizer_trait::ProgressCallback>,
+) -> CmaEs {
+    let config = CmaEsConfig {
+        population_size,
+        sigma,
+        termination_evaluations,
+        seed,
+        n_threads,
+        progress_callback,
+        ..Default::default()
+    };
+
+    CmaEs::new(config)
+}
+
+/// Create a DDS optimizer with a progress callback
+///
+/// # Arguments
+/// * `r` - Perturbation size as a fraction of the parameter range (typically 0.2)
+/// * `starts` - Number of independent searches sharing the budget (1 = classic DDS)
+/// * `termination_evaluations` - When to stop optimization
+/// * `seed` - Optional random seed
+/// * `n_threads` - Number of threads for running multiple starts in parallel
+/// * `progress_callback` - Optional progress callback receiving OptimizationProgress
+///
+/// # Returns
+/// A Dds optimizer with the callback configured
+pub fn create_dds_optimizer_with_callback(
+    r: f64,
+    starts: usize,
+    termination_evaluations: usize,
+    seed: Option<u64>,
+    n_threads: usize,
+    progress_callback: Option<super::optimizer_trait::ProgressCallback>,
+) -> Dds {
+    let config = DdsConfig {
+        r,
+        starts,
+        termination_evaluations,
+        seed,
+        n_threads,
+        progress_callback,
+        ..Default::default()
+    };
+
+    Dds::new(config)
+}
+
+/// Create an NSGA-II optimizer with a progress callback
+///
+/// # Arguments
+/// * `config` - The optimization configuration (algorithm must be NSGA2 to use its
+///   parameters; otherwise the NSGA-II defaults are used)
+/// * `progress_callback` - Optional progress callback receiving OptimizationProgress
+///
+/// # Returns
+/// An Nsga2 optimizer with the callback configured
+pub fn create_nsga2_optimizer_with_callback(
+    config: &OptimisationConfig,
+    progress_callback: Option<super::optimizer_trait::ProgressCallback>,
+) -> Nsga2 {
+    let mut nsga2_config = Nsga2Config {
+        termination_evaluations: config.termination_evaluations,
+        seed: config.random_seed,
+        n_threads: config.n_threads,
+        progress_callback,
+        ..Default::default()
+    };
+    if let AlgorithmParams::NSGA2 { population_size, crossover_probability, eta_c, eta_m } = &config.algorithm {
+        nsga2_config.population_size = *population_size;
+        nsga2_config.crossover_probability = *crossover_probability;
+        nsga2_config.eta_c = *eta_c;
+        nsga2_config.eta_m = *eta_m;
+    }
+
+    Nsga2::new(nsga2_config)
+}
+
 /// Create an optimizer from configuration, matching on algorithm type
 ///
 /// This is a convenience wrapper that extracts algorithm parameters and
@@ -252,7 +376,7 @@ pub fn create_optimizer_instance(
                 *cr,
                 config.random_seed,
                 config.n_threads,
-            );
+            ).with_early_stopping(config.early_stopping.clone());
             Ok(OptimizerInstance::DE(de))
         }
         AlgorithmParams::SCEUA { complexes } => {
@@ -261,11 +385,33 @@ pub fn create_optimizer_instance(
                 config.termination_evaluations,
                 config.random_seed,
                 config.n_threads,
-            );
+            ).with_early_stopping(config.early_stopping.clone());
             Ok(OptimizerInstance::SCE(sce))
         }
-        AlgorithmParams::CMAES { .. } => {
-            Err(OptimizerFactoryError::NotImplemented("CMA-ES".to_string()))
+        AlgorithmParams::NSGA2 { .. } => {
+            Ok(OptimizerInstance::NSGA2(create_nsga2_optimizer_with_callback(config, None)))
+        }
+        AlgorithmParams::CMAES { population_size, sigma } => {
+            let cmaes = create_cmaes_optimizer_with_callback(
+                *population_size,
+                *sigma,
+                config.termination_evaluations,
+                config.random_seed,
+                config.n_threads,
+                None,
+            ).with_early_stopping(config.early_stopping.clone());
+            Ok(OptimizerInstance::CMAES(cmaes))
+        }
+        AlgorithmParams::DDS { r, starts } => {
+            let dds = create_dds_optimizer_with_callback(
+                *r,
+                *starts,
+                config.termination_evaluations,
+                config.random_seed,
+                config.n_threads,
+                None,
+            ).with_early_stopping(config.early_stopping.clone());
+            Ok(OptimizerInstance::DDS(dds))
         }
     }
 }
@@ -278,7 +424,9 @@ pub fn create_optimizer_instance(
 pub enum OptimizerInstance {
     DE(DifferentialEvolution),
     SCE(Sce),
-    // Future: CMAES(CmaEs),
+    NSGA2(Nsga2),
+    CMAES(CmaEs),
+    DDS(Dds),
 }
 
 impl OptimizerInstance {
@@ -287,6 +435,9 @@ impl OptimizerInstance {
         match self {
             OptimizerInstance::DE(_) => "DE",
             OptimizerInstance::SCE(_) => "SCE",
+            OptimizerInstance::NSGA2(_) => "NSGA2",
+            OptimizerInstance::CMAES(_) => "CMA-ES",
+            OptimizerInstance::DDS(_) => "DDS",
         }
     }
 }
@@ -307,10 +458,19 @@ mod tests {
                 observed_file: "test.csv".to_string(),
                 observed_series: SeriesSpec::ByIndex(1),
                 statistic: ObjectiveFunction::OneMinusNse(crate::numerical::opt::objectives::NseObjective::new()),
+                weight: 1.0,
+                transforms: vec![],
+                catchment: None,
             }],
             objective_expression: "term1".to_string(),
+            objectives: vec![],
+            catchments: vec![],
             output_file: None,
+            trace_file: None,
+            calibration_period: None,
+            validation_period: None,
             termination_evaluations: 1000,
+            early_stopping: Default::default(),
             random_seed: Some(42),
             n_threads: 1,
             algorithm: AlgorithmParams::DE {
@@ -345,19 +505,35 @@ mod tests {
     }
 
     #[test]
-    fn test_unsupported_algorithm_cmaes() {
+    fn test_create_nsga2_optimizer() {
+        let mut config = create_test_config();
+        config.algorithm = AlgorithmParams::NSGA2 {
+            population_size: 40,
+            crossover_probability: 0.9,
+            eta_c: 15.0,
+            eta_m: 20.0,
+        };
+        assert_eq!(create_optimizer(&config).unwrap().name(), "NSGA2");
+        assert_eq!(create_optimizer_instance(&config).unwrap().name(), "NSGA2");
+    }
+
+    #[test]
+    fn test_create_dds_optimizer() {
+        let mut config = create_test_config();
+        config.algorithm = AlgorithmParams::DDS { r: 0.2, starts: 2 };
+        assert_eq!(create_optimizer(&config).unwrap().name(), "DDS");
+        assert_eq!(create_optimizer_instance(&config).unwrap().name(), "DDS");
+    }
+
+    #[test]
+    fn test_create_cmaes_optimizer() {
         let mut config = create_test_config();
         config.algorithm = AlgorithmParams::CMAES {
             population_size: 20,
             sigma: 0.5,
         };
 
-        let result = create_optimizer(&config);
-        match result {
-            Err(OptimizerFactoryError::NotImplemented(name)) => {
-                assert_eq!(name, "CMA-ES");
-            }
-            _ => panic!("Expected NotImplemented error for CMA-ES"),
-        }
+        assert_eq!(create_optimizer(&config).unwrap().name(), "CMA-ES");
+        assert_eq!(create_optimizer_instance(&config).unwrap().name(), "CMA-ES");
     }
 }
@@ -1,6 +1,8 @@
 // Optimisation algorithms
 pub mod cmaes;
+pub mod dds;
 pub mod de;
+pub mod nsga2;
 pub mod sce;
 pub mod sp_uci;
 
@@ -12,22 +14,33 @@ pub mod genes;
 pub mod objectives;
 pub mod optimisation;
 pub mod optimizer_trait;
+pub mod regional;
+pub mod termination;
+pub mod trace;
+pub mod series_transform;
 pub mod factory;
 
 // Re-exports for convenience
 pub use optimisable::{Optimisable, clone_multi};
 pub use optimisable_component::OptimisableComponent;
-pub use parameter_mapping::{ParameterMapping, ParameterMappingConfig, Transform};
+pub use parameter_mapping::{ConstraintHandling, ParameterConstraint, ParameterMapping, ParameterMappingConfig, Transform};
 pub use genes::{Gene, GeneMode};
 pub use objectives::{ObjectiveFunction, SdebObjective};
-pub use optimisation::OptimisationProblem;
-pub use optimizer_trait::{Optimizer, OptimizationProgress, OptimizationResult};
+pub use optimisation::{OptimisationProblem, PeriodMetrics};
+pub use regional::{RegionalCatchment, RegionalProblem};
+pub use optimizer_trait::{Optimizer, OptimizationProgress, OptimizationResult, ProgressCallback};
+pub use termination::{EarlyStopping, EarlyStoppingMonitor, StopFlag};
+pub use trace::CalibrationTrace;
+pub use series_transform::SeriesTransform;
 pub use de::{DifferentialEvolution, DEConfig, DEResult};
+pub use cmaes::{CmaEs, CmaEsConfig};
+pub use dds::{Dds, DdsConfig};
+pub use nsga2::{Nsga2, Nsga2Config};
 pub use sce::{Sce, SceConfig};
-pub use factory::{create_optimizer, create_optimizer_with_callback, create_de_optimizer, create_de_optimizer_with_callback, create_optimizer_instance, OptimizerInstance, OptimizerFactoryError};
+pub use factory::{create_optimizer, create_optimizer_with_callback, create_de_optimizer, create_de_optimizer_with_callback, create_cmaes_optimizer_with_callback, create_dds_optimizer_with_callback, create_nsga2_optimizer_with_callback, create_optimizer_instance, OptimizerInstance, OptimizerFactoryError};
 
 // Re-export IO types for convenience
-pub use crate::io::optimisation_config_io::{OptimisationConfig, AlgorithmParams};
+pub use crate::io::optimisation_config_io::{OptimisationConfig, AlgorithmParams, Objective};
 
 // Legacy trait (to be potentially updated/replaced)
 #[allow(unused)]
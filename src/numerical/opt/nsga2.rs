@@ -0,0 +1,556 @@
+//! NSGA-II (Non-dominated Sorting Genetic Algorithm II) multi-objective optimiser
+//!
+//! Evolves a population towards the Pareto front of two or more objectives using
+//! fast non-dominated sorting, crowding distance, simulated binary crossover (SBX)
+//! and polynomial mutation.
+//!
+//! Objectives are obtained via `Optimisable::evaluate_objectives()`. The final
+//! non-dominated set is returned in `algorithm_data["pareto_front"]`. Because the
+//! common result type has a single "best", `best_params` and `best_objective` report
+//! the front member with the lowest value of the first objective.
+//!
+//! Reference: Deb, K., Pratap, A., Agarwal, S. and Meyarivan, T. (2002). A fast and
+//! elitist multiobjective genetic algorithm: NSGA-II. IEEE Transactions on
+//! Evolutionary Computation, 6(2), 182-197.
+
+use super::optimisable::Optimisable;
+use super::optimizer_trait::{OptimizationProgress, OptimizationResult, Optimizer, ProgressCallback};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::cmp::Ordering as CmpOrdering;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// NSGA-II optimiser configuration
+pub struct Nsga2Config {
+    /// Population size (rounded up to an even number)
+    pub population_size: usize,
+
+    /// Termination criterion: stop after approximately this many function evaluations
+    pub termination_evaluations: usize,
+
+    /// Probability of applying SBX crossover to a pair of parents, typically 0.9
+    pub crossover_probability: f64,
+
+    /// SBX distribution index (larger = children closer to parents), typically 15
+    pub eta_c: f64,
+
+    /// Polynomial mutation distribution index, typically 20
+    pub eta_m: f64,
+
+    /// Random number generator seed (None = random seed)
+    pub seed: Option<u64>,
+
+    /// Number of threads for parallel evaluation (1 = single-threaded)
+    pub n_threads: usize,
+
+    /// Optional callback for progress reporting
+    pub progress_callback: Option<ProgressCallback>,
+}
+
+impl Default for Nsga2Config {
+    fn default() -> Self {
+        Self {
+            population_size: 100,
+            termination_evaluations: 10000,
+            crossover_probability: 0.9,
+            eta_c: 15.0,
+            eta_m: 20.0,
+            seed: None,
+            n_threads: 1,
+            progress_callback: None,
+        }
+    }
+}
+
+/// A member of the population with its objective vector, front rank and crowding distance
+#[derive(Clone, Debug)]
+struct Member {
+    params: Vec<f64>,
+    objectives: Vec<f64>,
+    rank: usize,
+    crowding: f64,
+}
+
+/// NSGA-II optimiser
+pub struct Nsga2 {
+    config: Nsga2Config,
+}
+
+impl Nsga2 {
+    /// Create a new NSGA-II optimiser with given configuration
+    pub fn new(config: Nsga2Config) -> Self {
+        Self { config }
+    }
+
+    /// Create a new NSGA-II optimiser with default configuration
+    pub fn with_defaults() -> Self {
+        Self::new(Nsga2Config::default())
+    }
+
+    /// Run the optimisation, returning the common result with the Pareto front attached
+    pub fn optimise(&self, problem: &mut dyn Optimisable) -> OptimizationResult {
+        let start_time = Instant::now();
+        let n_params = problem.n_params();
+        let n_objectives = problem.n_objectives().max(1);
+        let pop_size = self.config.population_size.max(4).div_ceil(2) * 2;
+
+        let mut rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let thread_pool = if self.config.n_threads > 1 {
+            Some(rayon::ThreadPoolBuilder::new()
+                .num_threads(self.config.n_threads)
+                .build()
+                .unwrap())
+        } else {
+            None
+        };
+
+        // Initial population, uniformly random in [0, 1]^n
+        let initial: Vec<Vec<f64>> = (0..pop_size)
+            .map(|_| (0..n_params).map(|_| rng.gen::<f64>()).collect())
+            .collect();
+        let mut n_evaluations = 0;
+        let objectives = self.evaluate_all(problem, &initial, n_objectives, thread_pool.as_ref(), &mut n_evaluations);
+        let mut population: Vec<Member> = initial.into_iter().zip(objectives)
+            .map(|(params, objectives)| Member { params, objectives, rank: 0, crowding: 0.0 })
+            .collect();
+
+        if population.iter().all(|m| m.objectives[0].is_infinite()) {
+            return self.build_result(&population, n_evaluations, 0, false,
+                "Optimization failed: all initial evaluations failed. \
+                 Check model configuration (node names, parameter targets, input data).",
+                start_time);
+        }
+
+        assign_rank_and_crowding(&mut population);
+
+        let mut generation = 0;
+        while n_evaluations < self.config.termination_evaluations {
+            if let Some(ref callback) = self.config.progress_callback {
+                callback(&self.progress(&population, n_evaluations, generation, start_time));
+            }
+
+            // Offspring by binary tournament, SBX crossover and polynomial mutation
+            let mut offspring: Vec<Vec<f64>> = Vec::with_capacity(pop_size);
+            while offspring.len() < pop_size {
+                let p1 = &population[tournament(&population, &mut rng)].params;
+                let p2 = &population[tournament(&population, &mut rng)].params;
+                let (mut c1, mut c2) = self.sbx_crossover(p1, p2, &mut rng);
+                self.polynomial_mutation(&mut c1, &mut rng);
+                self.polynomial_mutation(&mut c2, &mut rng);
+                offspring.push(c1);
+                offspring.push(c2);
+            }
+
+            let objectives = self.evaluate_all(problem, &offspring, n_objectives, thread_pool.as_ref(), &mut n_evaluations);
+
+            // Elitist survival: sort parents + offspring into fronts, fill by rank then crowding
+            population.extend(offspring.into_iter().zip(objectives)
+                .map(|(params, objectives)| Member { params, objectives, rank: 0, crowding: 0.0 }));
+            assign_rank_and_crowding(&mut population);
+            population.sort_by(crowded_comparison);
+            population.truncate(pop_size);
+
+            generation += 1;
+        }
+
+        self.build_result(&population, n_evaluations, generation, true,
+                          "Optimisation completed successfully", start_time)
+    }
+
+    fn progress(&self, population: &[Member], n_evaluations: usize, generation: usize,
+                start_time: Instant) -> OptimizationProgress {
+        let first: Vec<f64> = population.iter().map(|m| m.objectives[0]).collect();
+        let best = first.iter().cloned().fold(f64::INFINITY, f64::min);
+        let front_size = population.iter().filter(|m| m.rank == 0).count();
+        OptimizationProgress::new(n_evaluations, best, start_time.elapsed())
+            .with_population(first)
+            .with_population_params(population.iter().map(|m| m.params.clone()).collect())
+            .with_data("generation", generation as f64)
+            .with_data("front_size", front_size as f64)
+    }
+
+    fn build_result(&self, population: &[Member], n_evaluations: usize, generations: usize,
+                    success: bool, message: &str, start_time: Instant) -> OptimizationResult {
+        let mut front: Vec<&Member> = population.iter()
+            .filter(|m| m.rank == 0 && m.objectives.iter().all(|f| f.is_finite()))
+            .collect();
+        front.sort_by(|a, b| a.objectives.partial_cmp(&b.objectives).unwrap_or(CmpOrdering::Equal));
+        front.dedup_by(|a, b| a.objectives == b.objectives);
+
+        let (best_params, best_objective) = match front.first() {
+            Some(m) => (m.params.clone(), m.objectives[0]),
+            None => (population[0].params.clone(), f64::INFINITY),
+        };
+
+        let pareto_front: Vec<serde_json::Value> = front.iter()
+            .map(|m| serde_json::json!({
+                "params": m.params,
+                "objectives": m.objectives,
+            }))
+            .collect();
+
+        OptimizationResult::new(best_params, best_objective, n_evaluations, success, message,
+                                start_time.elapsed())
+            .with_data("generations", serde_json::Value::from(generations))
+            .with_data("pareto_front", serde_json::Value::Array(pareto_front))
+    }
+
+    /// Simulated binary crossover on [0, 1] bounds (Deb & Agrawal, 1995)
+    fn sbx_crossover(&self, p1: &[f64], p2: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        let mut c1 = p1.to_vec();
+        let mut c2 = p2.to_vec();
+        if rng.gen::<f64>() > self.config.crossover_probability {
+            return (c1, c2);
+        }
+        let eta = self.config.eta_c;
+        for j in 0..p1.len() {
+            if rng.gen::<f64>() > 0.5 || (p1[j] - p2[j]).abs() < 1e-14 {
+                continue;
+            }
+            let (y1, y2) = if p1[j] < p2[j] { (p1[j], p2[j]) } else { (p2[j], p1[j]) };
+            let u: f64 = rng.gen();
+
+            // Bounded spread factors for each child
+            let beta_q = |beta: f64| {
+                let alpha = 2.0 - beta.powf(-(eta + 1.0));
+                if u <= 1.0 / alpha {
+                    (u * alpha).powf(1.0 / (eta + 1.0))
+                } else {
+                    (1.0 / (2.0 - u * alpha)).powf(1.0 / (eta + 1.0))
+                }
+            };
+            let bq1 = beta_q(1.0 + 2.0 * y1 / (y2 - y1));
+            let bq2 = beta_q(1.0 + 2.0 * (1.0 - y2) / (y2 - y1));
+            let mut v1 = (0.5 * ((y1 + y2) - bq1 * (y2 - y1))).clamp(0.0, 1.0);
+            let mut v2 = (0.5 * ((y1 + y2) + bq2 * (y2 - y1))).clamp(0.0, 1.0);
+            if rng.gen::<f64>() < 0.5 {
+                std::mem::swap(&mut v1, &mut v2);
+            }
+            c1[j] = v1;
+            c2[j] = v2;
+        }
+        (c1, c2)
+    }
+
+    /// Polynomial mutation on [0, 1] bounds, with per-gene probability 1/n
+    fn polynomial_mutation(&self, x: &mut [f64], rng: &mut StdRng) {
+        let n = x.len();
+        if n == 0 {
+            return;
+        }
+        let eta = self.config.eta_m;
+        let p_m = 1.0 / n as f64;
+        for xj in x.iter_mut() {
+            if rng.gen::<f64>() >= p_m {
+                continue;
+            }
+            let u: f64 = rng.gen();
+            let mut_pow = 1.0 / (eta + 1.0);
+            let delta_q = if u < 0.5 {
+                let xy = 1.0 - *xj;
+                let val = 2.0 * u + (1.0 - 2.0 * u) * xy.powf(eta + 1.0);
+                val.powf(mut_pow) - 1.0
+            } else {
+                let xy = *xj;
+                let val = 2.0 * (1.0 - u) + 2.0 * (u - 0.5) * xy.powf(eta + 1.0);
+                1.0 - val.powf(mut_pow)
+            };
+            *xj = (*xj + delta_q).clamp(0.0, 1.0);
+        }
+    }
+
+    /// Evaluate a batch of parameter vectors, sequentially or on the thread pool.
+    /// Failed evaluations get an objective vector of infinities.
+    fn evaluate_all(
+        &self,
+        problem: &mut dyn Optimisable,
+        params: &[Vec<f64>],
+        n_objectives: usize,
+        pool: Option<&rayon::ThreadPool>,
+        n_evaluations: &mut usize,
+    ) -> Vec<Vec<f64>> {
+        let failed = || vec![f64::INFINITY; n_objectives];
+        let evaluate_one = |prob: &mut dyn Optimisable, x: &[f64]| -> Option<Vec<f64>> {
+            prob.set_params(x).ok()?;
+            prob.evaluate_objectives().ok()
+                .filter(|f| f.len() == n_objectives)
+                .map(|f| f.into_iter().map(|v| if v.is_nan() { f64::INFINITY } else { v }).collect())
+        };
+
+        let Some(pool) = pool else {
+            return params.iter().map(|x| {
+                let result = evaluate_one(problem, x);
+                if result.is_some() {
+                    *n_evaluations += 1;
+                }
+                result.unwrap_or_else(failed)
+            }).collect();
+        };
+
+        // Worker-based parallel evaluation, as in DE: one problem clone per thread
+        use rayon::prelude::*;
+        use std::sync::{Arc, Mutex};
+        let worker_problems: Vec<Arc<Mutex<Box<dyn Optimisable>>>> =
+            (0..self.config.n_threads)
+                .map(|_| Arc::new(Mutex::new(problem.clone_for_parallel())))
+                .collect();
+        let eval_counter = AtomicUsize::new(0);
+
+        let objectives = pool.install(|| {
+            params.par_iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    let mut prob = worker_problems[i % self.config.n_threads].lock().unwrap();
+                    match evaluate_one(prob.as_mut(), x) {
+                        Some(f) => {
+                            eval_counter.fetch_add(1, Ordering::Relaxed);
+                            f
+                        }
+                        None => failed(),
+                    }
+                })
+                .collect()
+        });
+
+        *n_evaluations += eval_counter.load(Ordering::Relaxed);
+        objectives
+    }
+}
+
+/// True if `a` Pareto-dominates `b` (no worse in every objective, better in at least one)
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    let mut strictly_better = false;
+    for (x, y) in a.iter().zip(b) {
+        if x > y {
+            return false;
+        }
+        if x < y {
+            strictly_better = true;
+        }
+    }
+    strictly_better
+}
+
+/// Fast non-dominated sort; returns the member indices of each front, best front first
+fn non_dominated_fronts(objectives: &[&[f64]]) -> Vec<Vec<usize>> {
+    let n = objectives.len();
+    let mut dominated_by_count = vec![0usize; n];
+    let mut dominates_list: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if dominates(objectives[i], objectives[j]) {
+                dominates_list[i].push(j);
+                dominated_by_count[j] += 1;
+            } else if dominates(objectives[j], objectives[i]) {
+                dominates_list[j].push(i);
+                dominated_by_count[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = Vec::new();
+    let mut current: Vec<usize> = (0..n).filter(|&i| dominated_by_count[i] == 0).collect();
+    while !current.is_empty() {
+        let mut next = Vec::new();
+        for &i in &current {
+            for &j in &dominates_list[i] {
+                dominated_by_count[j] -= 1;
+                if dominated_by_count[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        fronts.push(current);
+        current = next;
+    }
+    fronts
+}
+
+/// Assigns each member its front rank and its crowding distance within that front
+fn assign_rank_and_crowding(population: &mut [Member]) {
+    let objectives: Vec<&[f64]> = population.iter().map(|m| m.objectives.as_slice()).collect();
+    let fronts = non_dominated_fronts(&objectives);
+
+    let n_objectives = population.first().map_or(0, |m| m.objectives.len());
+    for (rank, front) in fronts.iter().enumerate() {
+        for &i in front {
+            population[i].rank = rank;
+            population[i].crowding = 0.0;
+        }
+        for k in 0..n_objectives {
+            let mut sorted = front.clone();
+            sorted.sort_by(|&a, &b| population[a].objectives[k]
+                .partial_cmp(&population[b].objectives[k])
+                .unwrap_or(CmpOrdering::Equal));
+            let lo = population[sorted[0]].objectives[k];
+            let hi = population[sorted[sorted.len() - 1]].objectives[k];
+            population[sorted[0]].crowding = f64::INFINITY;
+            population[sorted[sorted.len() - 1]].crowding = f64::INFINITY;
+            let range = hi - lo;
+            if sorted.len() < 3 || !range.is_finite() || range <= 0.0 {
+                continue;
+            }
+            for w in 1..sorted.len() - 1 {
+                let gap = population[sorted[w + 1]].objectives[k] - population[sorted[w - 1]].objectives[k];
+                population[sorted[w]].crowding += gap / range;
+            }
+        }
+    }
+}
+
+/// Crowded-comparison ordering: lower rank first, then larger crowding distance
+fn crowded_comparison(a: &Member, b: &Member) -> CmpOrdering {
+    a.rank.cmp(&b.rank)
+        .then_with(|| b.crowding.partial_cmp(&a.crowding).unwrap_or(CmpOrdering::Equal))
+}
+
+/// Binary tournament selection using the crowded-comparison operator
+fn tournament(population: &[Member], rng: &mut StdRng) -> usize {
+    let a = rng.gen_range(0..population.len());
+    let b = rng.gen_range(0..population.len());
+    if crowded_comparison(&population[a], &population[b]) == CmpOrdering::Greater { b } else { a }
+}
+
+impl Optimizer for Nsga2 {
+    fn optimize(
+        &self,
+        problem: &mut dyn Optimisable,
+        progress_callback: Option<Box<dyn Fn(&OptimizationProgress) + Send + Sync>>,
+    ) -> OptimizationResult {
+        if let Some(callback) = progress_callback {
+            // Callback provided via parameter overrides the config callback
+            let mut config = self.config.clone();
+            config.progress_callback = Some(callback);
+            Nsga2::new(config).optimise(problem)
+        } else {
+            self.optimise(problem)
+        }
+    }
+
+    fn name(&self) -> &str {
+        "NSGA2"
+    }
+}
+
+// Implement Clone for Nsga2Config to support wrapping
+impl Clone for Nsga2Config {
+    fn clone(&self) -> Self {
+        Self {
+            population_size: self.population_size,
+            termination_evaluations: self.termination_evaluations,
+            crossover_probability: self.crossover_probability,
+            eta_c: self.eta_c,
+            eta_m: self.eta_m,
+            seed: self.seed,
+            n_threads: self.n_threads,
+            progress_callback: None, // Callbacks can't be cloned
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ZDT1: the Pareto-optimal front is f2 = 1 - sqrt(f1), attained when x[1..] = 0
+    #[derive(Clone)]
+    struct Zdt1 {
+        x: Vec<f64>,
+    }
+
+    impl Optimisable for Zdt1 {
+        fn n_params(&self) -> usize {
+            self.x.len()
+        }
+
+        fn set_params(&mut self, params: &[f64]) -> Result<(), String> {
+            self.x = params.to_vec();
+            Ok(())
+        }
+
+        fn get_params(&self) -> Vec<f64> {
+            self.x.clone()
+        }
+
+        fn evaluate(&mut self) -> Result<f64, String> {
+            Ok(self.evaluate_objectives()?[0])
+        }
+
+        fn n_objectives(&self) -> usize {
+            2
+        }
+
+        fn evaluate_objectives(&mut self) -> Result<Vec<f64>, String> {
+            let n = self.x.len() as f64;
+            let f1 = self.x[0];
+            let g = 1.0 + 9.0 * self.x[1..].iter().sum::<f64>() / (n - 1.0);
+            let f2 = g * (1.0 - (f1 / g).sqrt());
+            Ok(vec![f1, f2])
+        }
+
+        fn clone_for_parallel(&self) -> Box<dyn Optimisable> {
+            Box::new(self.clone())
+        }
+    }
+
+    fn run_zdt1(n_threads: usize) -> OptimizationResult {
+        let config = Nsga2Config {
+            population_size: 60,
+            termination_evaluations: 12000,
+            seed: Some(7),
+            n_threads,
+            ..Default::default()
+        };
+        let mut problem = Zdt1 { x: vec![0.5; 6] };
+        Nsga2::new(config).optimise(&mut problem)
+    }
+
+    #[test]
+    fn test_dominates() {
+        assert!(dominates(&[1.0, 2.0], &[1.0, 3.0]));
+        assert!(!dominates(&[1.0, 3.0], &[1.0, 3.0]));
+        assert!(!dominates(&[0.0, 4.0], &[1.0, 3.0]));
+    }
+
+    #[test]
+    fn test_non_dominated_fronts() {
+        let objectives: Vec<&[f64]> = vec![&[1.0, 4.0], &[2.0, 2.0], &[3.0, 3.0], &[4.0, 1.0], &[5.0, 5.0]];
+        let fronts = non_dominated_fronts(&objectives);
+        assert_eq!(fronts, vec![vec![0, 1, 3], vec![2], vec![4]]);
+    }
+
+    #[test]
+    fn test_nsga2_converges_to_zdt1_front() {
+        let result = run_zdt1(1);
+        assert!(result.success);
+        let front = result.algorithm_data["pareto_front"].as_array().unwrap();
+        assert!(front.len() >= 20, "front too small: {}", front.len());
+
+        // Every member should be close to the true front, and the front should be well spread
+        let mut f1s = vec![];
+        for member in front {
+            let f: Vec<f64> = serde_json::from_value(member["objectives"].clone()).unwrap();
+            assert!((f[1] - (1.0 - f[0].sqrt())).abs() < 0.1, "{:?} is far from the front", f);
+            f1s.push(f[0]);
+        }
+        let spread = f1s.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+            - f1s.iter().cloned().fold(f64::INFINITY, f64::min);
+        assert!(spread > 0.8, "front spread {}", spread);
+
+        // The reported best is the front member with the lowest first objective
+        assert_eq!(result.best_objective, f1s[0]);
+    }
+
+    #[test]
+    fn test_nsga2_parallel_evaluation() {
+        let result = run_zdt1(3);
+        assert!(result.success);
+        assert!(result.n_evaluations >= 12000);
+        assert!(!result.algorithm_data["pareto_front"].as_array().unwrap().is_empty());
+    }
+}
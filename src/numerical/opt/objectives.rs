@@ -25,6 +25,16 @@ pub enum ObjectiveFunction {
     /// 1 - Kling-Gupta Efficiency. Range: [0, ∞), 0 = perfect.
     OneMinusKge(KgeObjective),
 
+    /// 1 - modified Kling-Gupta Efficiency (KGE', Kling et al. 2012), which uses the ratio
+    /// of coefficients of variation instead of standard deviations so the variability and
+    /// bias terms are not cross-correlated. Range: [0, ∞), 0 = perfect.
+    OneMinusKgePrime(KgePrimeObjective),
+
+    /// 1 - non-parametric Kling-Gupta Efficiency (Pool et al. 2018): Spearman rank
+    /// correlation and a flow-duration-curve based variability term. Less sensitive to
+    /// extreme values, e.g. on ephemeral streams. Range: [0, ∞), 0 = perfect.
+    OneMinusKgeNp(KgeNpObjective),
+
     /// Absolute percent bias |PBIAS|. Range: [0, ∞), 0 = perfect.
     AbsPbias(PbiasObjective),
 
@@ -706,6 +716,216 @@ impl KgeObjective {
     }
 }
 
+/// KGE' (modified KGE) objective with lazy-initialized cache for parallel processing
+#[derive(Clone, Debug)]
+pub struct KgePrimeObjective {
+    cache: Arc<OnceLock<KgeCache>>,
+}
+
+impl Default for KgePrimeObjective {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KgePrimeObjective {
+    pub fn new() -> Self {
+        Self {
+            cache: Arc::new(OnceLock::new()),
+        }
+    }
+
+    fn calculate(&self, observed: &[f64], simulated: &[f64]) -> Result<f64, String> {
+        let cache = self.cache.get_or_init(|| {
+            KgeObjective::initialize_cache(observed, simulated)
+        });
+
+        let masked_simulated = KgeObjective::apply_mask(simulated, &cache.mask);
+
+        if masked_simulated.len() != cache.masked_observed.len() {
+            return Err("Masked data length mismatch".to_string());
+        }
+
+        if masked_simulated.is_empty() {
+            return Err("No valid data points after masking".to_string());
+        }
+
+        if cache.std_observed == 0.0 {
+            return Err("Observed data has zero variance".to_string());
+        }
+
+        if cache.mean_observed == 0.0 {
+            return Err("Observed data has zero mean".to_string());
+        }
+
+        let n = masked_simulated.len() as f64;
+        let mean_simulated: f64 = masked_simulated.iter().sum::<f64>() / n;
+        let std_simulated: f64 = (masked_simulated.iter()
+            .map(|x| (x - mean_simulated).powi(2))
+            .sum::<f64>() / n).sqrt();
+
+        let r = if std_simulated == 0.0 {
+            0.0
+        } else {
+            let cov: f64 = cache.masked_observed.iter()
+                .zip(&masked_simulated)
+                .map(|(o, s)| (o - cache.mean_observed) * (s - mean_simulated))
+                .sum::<f64>() / n;
+            cov / (cache.std_observed * std_simulated)
+        };
+
+        // Variability ratio of coefficients of variation; a zero simulated mean gives no variability match
+        let gamma = if mean_simulated == 0.0 {
+            0.0
+        } else {
+            (std_simulated / mean_simulated) / (cache.std_observed / cache.mean_observed)
+        };
+        let beta = mean_simulated / cache.mean_observed;
+
+        let kge = 1.0 - ((r - 1.0).powi(2) + (gamma - 1.0).powi(2) + (beta - 1.0).powi(2)).sqrt();
+
+        // Convert to loss form: 0 = perfect, increases as fit worsens
+        Ok(1.0 - kge)
+    }
+}
+
+/// Non-parametric KGE objective with lazy-initialized cache for parallel processing
+#[derive(Clone, Debug)]
+pub struct KgeNpObjective {
+    cache: Arc<OnceLock<KgeNpCache>>,
+}
+
+#[derive(Debug)]
+struct KgeNpCache {
+    mask: Vec<bool>,
+    mean_observed: f64,
+    /// Ranks of the masked observed values (ties get their average rank)
+    ranks_observed: Vec<f64>,
+    /// Sorted masked observed values divided by their total (normalised flow duration curve)
+    fdc_observed: Vec<f64>,
+}
+
+impl Default for KgeNpObjective {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KgeNpObjective {
+    pub fn new() -> Self {
+        Self {
+            cache: Arc::new(OnceLock::new()),
+        }
+    }
+
+    fn calculate(&self, observed: &[f64], simulated: &[f64]) -> Result<f64, String> {
+        let cache = self.cache.get_or_init(|| {
+            Self::initialize_cache(observed, simulated)
+        });
+
+        let masked_simulated = KgeObjective::apply_mask(simulated, &cache.mask);
+
+        if masked_simulated.len() != cache.ranks_observed.len() {
+            return Err("Masked data length mismatch".to_string());
+        }
+
+        if masked_simulated.is_empty() {
+            return Err("No valid data points after masking".to_string());
+        }
+
+        if cache.mean_observed == 0.0 {
+            return Err("Observed data has zero mean".to_string());
+        }
+
+        let mean_simulated: f64 = masked_simulated.iter().sum::<f64>() / masked_simulated.len() as f64;
+        let beta = mean_simulated / cache.mean_observed;
+
+        // Variability: 1 - half the summed absolute difference between normalised flow duration curves
+        let alpha = if mean_simulated == 0.0 {
+            0.0
+        } else {
+            let total_simulated = mean_simulated * masked_simulated.len() as f64;
+            let mut fdc_simulated = masked_simulated.clone();
+            fdc_simulated.sort_by(|a, b| a.total_cmp(b));
+            1.0 - 0.5 * fdc_simulated.iter()
+                .zip(&cache.fdc_observed)
+                .map(|(s, o)| (s / total_simulated - o).abs())
+                .sum::<f64>()
+        };
+
+        let r = pearson(&cache.ranks_observed, &ranks(&masked_simulated));
+
+        let kge = 1.0 - ((r - 1.0).powi(2) + (alpha - 1.0).powi(2) + (beta - 1.0).powi(2)).sqrt();
+
+        // Convert to loss form: 0 = perfect, increases as fit worsens
+        Ok(1.0 - kge)
+    }
+
+    fn initialize_cache(observed: &[f64], simulated: &[f64]) -> KgeNpCache {
+        let mask: Vec<bool> = observed.iter()
+            .zip(simulated)
+            .map(|(o, s)| o.is_finite() && s.is_finite())
+            .collect();
+
+        let masked_observed = KgeObjective::apply_mask(observed, &mask);
+        let total_observed: f64 = masked_observed.iter().sum();
+        let mean_observed = if masked_observed.is_empty() {
+            0.0
+        } else {
+            total_observed / masked_observed.len() as f64
+        };
+
+        let mut fdc_observed = masked_observed.clone();
+        fdc_observed.sort_by(|a, b| a.total_cmp(b));
+        if total_observed != 0.0 {
+            fdc_observed.iter_mut().for_each(|v| *v /= total_observed);
+        }
+
+        KgeNpCache {
+            mask,
+            mean_observed,
+            ranks_observed: ranks(&masked_observed),
+            fdc_observed,
+        }
+    }
+}
+
+/// Ranks (1-based) of `data`, with tied values given their average rank
+fn ranks(data: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..data.len()).collect();
+    order.sort_by(|&a, &b| data[a].total_cmp(&data[b]));
+
+    let mut ranks = vec![0.0; data.len()];
+    let mut i = 0;
+    while i < order.len() {
+        let mut j = i;
+        while j + 1 < order.len() && data[order[j + 1]] == data[order[i]] {
+            j += 1;
+        }
+        let average_rank = (i + j) as f64 / 2.0 + 1.0;
+        for &k in &order[i..=j] {
+            ranks[k] = average_rank;
+        }
+        i = j + 1;
+    }
+    ranks
+}
+
+/// Pearson correlation of two equal-length series (0 if either has zero variance)
+fn pearson(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+    let cov: f64 = x.iter().zip(y).map(|(a, b)| (a - mean_x) * (b - mean_y)).sum();
+    let var_x: f64 = x.iter().map(|a| (a - mean_x).powi(2)).sum();
+    let var_y: f64 = y.iter().map(|b| (b - mean_y).powi(2)).sum();
+    if var_x == 0.0 || var_y == 0.0 {
+        0.0
+    } else {
+        cov / (var_x * var_y).sqrt()
+    }
+}
+
 /// PBIAS objective with lazy-initialized cache for parallel processing
 #[derive(Clone, Debug)]
 pub struct PbiasObjective {
@@ -808,6 +1028,8 @@ impl ObjectiveFunction {
             ObjectiveFunction::RMSE(obj) => obj.calculate(observed, simulated),
             ObjectiveFunction::MAE(obj) => obj.calculate(observed, simulated),
             ObjectiveFunction::OneMinusKge(obj) => obj.calculate(observed, simulated),
+            ObjectiveFunction::OneMinusKgePrime(obj) => obj.calculate(observed, simulated),
+            ObjectiveFunction::OneMinusKgeNp(obj) => obj.calculate(observed, simulated),
             ObjectiveFunction::AbsPbias(obj) => obj.calculate(observed, simulated),
             ObjectiveFunction::SDEB(obj) => obj.calculate(observed, simulated),
             ObjectiveFunction::OneMinusPearsR(obj) => obj.calculate(observed, simulated),
@@ -822,6 +1044,8 @@ impl ObjectiveFunction {
             ObjectiveFunction::RMSE(_) => "RMSE",
             ObjectiveFunction::MAE(_) => "MAE",
             ObjectiveFunction::OneMinusKge(_) => "ONE_MINUS_KGE",
+            ObjectiveFunction::OneMinusKgePrime(_) => "ONE_MINUS_KGE_PRIME",
+            ObjectiveFunction::OneMinusKgeNp(_) => "ONE_MINUS_KGE_NP",
             ObjectiveFunction::AbsPbias(_) => "ABS_PBIAS",
             ObjectiveFunction::SDEB(_) => "SDEB",
             ObjectiveFunction::OneMinusPearsR(_) => "ONE_MINUS_PEARS_R",
@@ -838,6 +1062,8 @@ impl PartialEq for ObjectiveFunction {
             (Self::RMSE(_), Self::RMSE(_)) => true,
             (Self::MAE(_), Self::MAE(_)) => true,
             (Self::OneMinusKge(_), Self::OneMinusKge(_)) => true,
+            (Self::OneMinusKgePrime(_), Self::OneMinusKgePrime(_)) => true,
+            (Self::OneMinusKgeNp(_), Self::OneMinusKgeNp(_)) => true,
             (Self::AbsPbias(_), Self::AbsPbias(_)) => true,
             (Self::SDEB(_), Self::SDEB(_)) => true,
             (Self::OneMinusPearsR(_), Self::OneMinusPearsR(_)) => true,
@@ -907,6 +1133,44 @@ mod tests {
         assert!(obj.abs() < 1e-10, "Perfect fit should give 1-KGE=0, got {}", obj);
     }
 
+    #[test]
+    fn test_kge_prime_uses_coefficient_of_variation() {
+        let obs = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        let perfect = ObjectiveFunction::OneMinusKgePrime(KgePrimeObjective::new()).calculate(&obs, &obs).unwrap();
+        assert!(perfect.abs() < 1e-10);
+
+        // Doubling the flows keeps r = 1 and the CV ratio at 1, leaving only the bias term
+        let sim: Vec<f64> = obs.iter().map(|v| 2.0 * v).collect();
+        let prime = ObjectiveFunction::OneMinusKgePrime(KgePrimeObjective::new()).calculate(&obs, &sim).unwrap();
+        let kge = ObjectiveFunction::OneMinusKge(KgeObjective::new()).calculate(&obs, &sim).unwrap();
+        assert!((prime - 1.0).abs() < 1e-10, "1-KGE' = {}", prime);
+        assert!((kge - 2f64.sqrt()).abs() < 1e-10, "1-KGE = {}", kge);
+    }
+
+    #[test]
+    fn test_kge_np() {
+        let obs = vec![0.0, 0.0, 1.0, 4.0, 2.0, 0.0, 8.0];
+        let perfect = ObjectiveFunction::OneMinusKgeNp(KgeNpObjective::new()).calculate(&obs, &obs).unwrap();
+        assert!(perfect.abs() < 1e-10);
+
+        // A monotonic transform with the same mean and flow duration curve shape scores perfectly
+        // on correlation; scaling only affects the bias term
+        let sim: Vec<f64> = obs.iter().map(|v| 3.0 * v).collect();
+        let scaled = ObjectiveFunction::OneMinusKgeNp(KgeNpObjective::new()).calculate(&obs, &sim).unwrap();
+        assert!((scaled - 2.0).abs() < 1e-10, "1-KGE_np = {}", scaled);
+
+        // Reversed timing: identical flow duration curve and mean, negative rank correlation
+        let reversed: Vec<f64> = obs.iter().rev().cloned().collect();
+        let value = ObjectiveFunction::OneMinusKgeNp(KgeNpObjective::new()).calculate(&obs, &reversed).unwrap();
+        let r = pearson(&ranks(&obs), &ranks(&reversed));
+        assert!((value - (1.0 - r)).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_ranks_average_ties() {
+        assert_eq!(ranks(&[3.0, 1.0, 3.0, 2.0]), vec![3.5, 1.0, 3.5, 2.0]);
+    }
+
     #[test]
     fn test_sdeb_perfect() {
         let obs = vec![1.0, 2.0, 3.0, 4.0, 5.0];
@@ -24,6 +24,20 @@ pub trait Optimisable: Send {
     /// This typically involves running a model and comparing to observations.
     fn evaluate(&mut self) -> Result<f64, String>;
 
+    /// Number of objectives returned by `evaluate_objectives`
+    ///
+    /// Single-objective problems return 1 (the default).
+    fn n_objectives(&self) -> usize {
+        1
+    }
+
+    /// Evaluate every objective (each lower = better)
+    ///
+    /// Used by multi-objective optimisers such as NSGA-II. The default wraps `evaluate`.
+    fn evaluate_objectives(&mut self) -> Result<Vec<f64>, String> {
+        Ok(vec![self.evaluate()?])
+    }
+
     /// Get parameter names for reporting
     ///
     /// Returns human-readable names like "g(1)", "g(2)", etc.
@@ -4,19 +4,23 @@
 /// - Parameter mappings (genes -> model parameters)
 /// - One or more comparison pairs (observed/simulated/statistic terms)
 /// - A composite objective expression over the per-term losses
+/// - Optionally, several named objective expressions for multi-objective optimisers
 ///
 /// The wrapper implements the Optimisable trait, presenting a simple normalised
 /// parameter interface to optimisation algorithms.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
 use crate::model::Model;
-use crate::nodes::NodeEnum;
 use crate::timeseries::Timeseries;
 use crate::functions::{ParsedFunction, VariableContext, EvaluationConfig, parse_function};
 use super::optimisable::Optimisable;
-use super::optimisable_component::OptimisableComponent;
 use super::parameter_mapping::ParameterMappingConfig;
 use super::objectives::ObjectiveFunction;
+use super::series_transform::{apply_pipeline, SeriesTransform};
+use super::optimizer_trait::ProgressCallback;
+use super::trace::{tracking_callback, CalibrationTrace};
 
 /// One term in a composite optimisation objective
 ///
@@ -36,6 +40,25 @@ pub struct ComparisonPair {
 
     /// Statistic to compute over this (observed, simulated) pair (all return lower-better loss)
     pub statistic: ObjectiveFunction,
+
+    /// Transforms applied in order to both aligned series before the statistic (empty = none)
+    pub transforms: Vec<SeriesTransform>,
+}
+
+/// Aligned (timestamps, observed, simulated) values of one term
+type AlignedSeries = (Vec<u64>, Vec<f64>, Vec<f64>);
+
+/// Objective and per-term losses over one evaluation period
+#[derive(Clone, Debug)]
+pub struct PeriodMetrics {
+    /// Inclusive (start, end) timestamps, or None for all overlapping data
+    pub period: Option<(u64, u64)>,
+
+    /// Per-term losses, in term order
+    pub terms: Vec<(String, f64)>,
+
+    /// Composite objective value
+    pub objective: f64,
 }
 
 /// Wraps a Model to make it Optimisable
@@ -51,6 +74,7 @@ pub struct ComparisonPair {
 ///     observed: observed_timeseries,
 ///     simulated_series_name: "node.sacramento_a.dsflow".to_string(),
 ///     statistic: ObjectiveFunction::OneMinusNse(NseObjective::new()),
+///     transforms: vec![],
 /// };
 ///
 /// let expression = parse_function("term1").unwrap();
@@ -68,17 +92,97 @@ pub struct OptimisationProblem {
 
     /// Composite objective expression over per-term losses
     pub expression: ParsedFunction,
+
+    /// Named objective expressions for multi-objective optimisation (empty = single objective)
+    pub objectives: Vec<(String, ParsedFunction)>,
+
+    /// Inclusive (start, end) timestamp window the objective is computed over
+    /// (None = all overlapping data). The model still runs over its full simulation period.
+    pub period: Option<(u64, u64)>,
+
+    /// Trace file receiving every evaluation, shared with parallel clones (None = no trace)
+    pub trace: Option<Arc<CalibrationTrace>>,
+
+    /// Genes most recently passed to `set_params`, for the trace
+    last_genes: Vec<f64>,
 }
 
 impl OptimisationProblem {
     /// Create a new optimisation problem
     pub fn new(
-        model: Model,
+        mut model: Model,
         config: ParameterMappingConfig,
         comparisons: Vec<ComparisonPair>,
         expression: ParsedFunction,
     ) -> Self {
-        Self { model, config, comparisons, expression }
+        let simulated: Vec<String> = comparisons.iter().map(|c| c.simulated_series_name.clone()).collect();
+        model.record_series_in_full(&simulated);
+        Self {
+            model,
+            config,
+            comparisons,
+            expression,
+            objectives: Vec::new(),
+            period: None,
+            trace: None,
+            last_genes: Vec::new(),
+        }
+    }
+
+    /// Attach named objective expressions, making this a multi-objective problem
+    pub fn with_objectives(mut self, objectives: Vec<(String, ParsedFunction)>) -> Self {
+        self.objectives = objectives;
+        self
+    }
+
+    /// Restrict the objective to an inclusive (start, end) timestamp window, e.g. a calibration period
+    pub fn with_period(mut self, period: Option<(u64, u64)>) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Start writing every evaluation (physical parameter values and objectives) to a CSV
+    /// trace file at `path`
+    pub fn start_trace(&mut self, path: &str) -> Result<(), String> {
+        let parameter_names: Vec<String> = self.config.evaluate(&vec![0.5; self.config.n_genes()])
+            .into_iter()
+            .map(|(target, _)| target)
+            .collect();
+        let objective_names: Vec<String> = if self.objectives.is_empty() {
+            vec!["objective".to_string()]
+        } else {
+            self.objectives.iter().map(|(name, _)| name.clone()).collect()
+        };
+        self.trace = Some(Arc::new(CalibrationTrace::create(path, &parameter_names, &objective_names)?));
+        Ok(())
+    }
+
+    /// Wrap an optimiser progress callback so the trace records generation numbers.
+    /// Returns the callback unchanged when no trace is active.
+    pub fn trace_progress(&self, callback: Option<ProgressCallback>) -> Option<ProgressCallback> {
+        match &self.trace {
+            Some(trace) => Some(tracking_callback(trace, callback)),
+            None => callback,
+        }
+    }
+
+    /// Stop tracing and flush the trace file
+    pub fn finish_trace(&mut self) -> Result<(), String> {
+        match self.trace.take() {
+            Some(trace) => trace.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Append an evaluation to the trace, if one is active
+    fn record_trace(&self, objectives: &[f64]) {
+        if let Some(trace) = &self.trace {
+            let parameters: Vec<f64> = self.config.evaluate(&self.last_genes)
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect();
+            trace.record(&parameters, objectives);
+        }
     }
 
     /// Create a single-comparison problem with a trivial expression of just the term name
@@ -98,6 +202,7 @@ impl OptimisationProblem {
                 observed,
                 simulated_series_name,
                 statistic,
+                transforms: Vec::new(),
             }],
             expression,
         )
@@ -112,50 +217,12 @@ impl OptimisationProblem {
     /// - "node.name.param" - for node parameters
     /// - "c.blah.blah.blah" - for constants
     fn apply_params_to_model(&mut self, genes: &[f64]) -> Result<(), String> {
-        // Evaluate all mappings: genes -> (target, physical_value)
-        let param_values = self.config.evaluate(genes);
+        // Evaluate all mappings: genes -> (target, physical_value), rejecting infeasible candidates
+        let param_values = self.config.try_evaluate(genes)?;
 
         // Apply each parameter to the model
         for (target, value) in param_values {
-            // Parse target address
-            let parts: Vec<&str> = target.split('.').collect();
-
-            if parts.len() >= 2 && parts[0] == "c" {
-                // Handle constant: "c.something"
-                self.model.data_cache.set_param(&target, value)
-                    .map_err(|e| format!("Error setting constant {}: {}", &target, e))?;
-            } else if parts.len() == 3 && parts[0] == "node" {
-                // Handle node parameter: "node.name.param"
-                let node_name = parts[1];
-                let param_name = parts[2];
-
-                // Get node index
-                let node_idx = self
-                    .model
-                    .get_node_idx(node_name)
-                    .ok_or_else(|| format!("Node not found: {}", node_name))?;
-
-                // Set parameter on the node using OptimisableComponent trait
-                match &mut self.model.nodes[node_idx] {
-                    NodeEnum::SacramentoNode(node) => {
-                        node.set_param(param_name, value)
-                            .map_err(|e| format!("Error setting {}.{}: {}", node_name, param_name, e))?;
-                    }
-                    NodeEnum::Gr4jNode(node) => {
-                        node.set_param(param_name, value)
-                            .map_err(|e| format!("Error setting {}.{}: {}", node_name, param_name, e))?;
-                    }
-                    _ => {
-                        return Err(format!(
-                            "Node '{}' (type: {}) does not support parameter optimisation",
-                            node_name,
-                            self.model.nodes[node_idx].get_type_as_string()
-                        ));
-                    }
-                }
-            } else {
-                return Err(format!("Invalid target address: '{}'. Expected 'node.name.param' or 'c.constant_name'", target));
-            }
+            self.model.set_parameter(&target, value)?;
         }
 
         Ok(())
@@ -163,13 +230,14 @@ impl OptimisationProblem {
 
     /// Align observed and simulated timeseries temporally
     ///
-    /// Returns aligned (observed, simulated) vectors that only include timesteps
-    /// where both series have data.
+    /// Returns aligned (timestamps, observed, simulated) vectors that only include
+    /// timesteps where both series have data.
     fn align_timeseries(
         &self,
         observed: &Timeseries,
         simulated: &Timeseries,
-    ) -> Result<(Vec<f64>, Vec<f64>), String> {
+    ) -> Result<AlignedSeries, String> {
+        let mut aligned_timestamps = Vec::new();
         let mut aligned_obs = Vec::new();
         let mut aligned_sim = Vec::new();
 
@@ -181,26 +249,118 @@ impl OptimisationProblem {
             .map(|(&t, &v)| (t, v))
             .collect();
 
-        // Iterate through observed timestamps and find matches
-        for (&obs_time, &obs_value) in observed.timestamps.iter().zip(&observed.values) {
+        // Iterate through observed timestamps (within the period, if any) and find matches
+        let in_period = |t: u64| self.period.is_none_or(|(start, end)| t >= start && t <= end);
+        for (&obs_time, &obs_value) in observed.timestamps.iter().zip(&observed.values).filter(|(&t, _)| in_period(t)) {
             // Look for matching timestamp in simulated
             if let Some(&sim_value) = sim_map.get(&obs_time) {
+                aligned_timestamps.push(obs_time);
                 aligned_obs.push(obs_value);
                 aligned_sim.push(sim_value);
             }
         }
 
         if aligned_obs.is_empty() {
+            let within = match self.period {
+                Some((start, end)) => format!(" within the period {}..{}", start, end),
+                None => String::new(),
+            };
             return Err(format!(
-                "No overlapping timestamps found between observed ({}..{}) and simulated ({}..{}) data",
+                "No overlapping timestamps found between observed ({}..{}) and simulated ({}..{}) data{}",
                 observed.timestamps.first().unwrap_or(&0),
                 observed.timestamps.last().unwrap_or(&0),
                 simulated.timestamps.first().unwrap_or(&0),
                 simulated.timestamps.last().unwrap_or(&0),
+                within,
             ));
         }
 
-        Ok((aligned_obs, aligned_sim))
+        Ok((aligned_timestamps, aligned_obs, aligned_sim))
+    }
+
+    /// Run the model and compute each term's loss, keyed by term name
+    pub(crate) fn run_and_compute_terms(&mut self) -> Result<HashMap<String, f64>, String> {
+        // Configure model if needed (first time)
+        if self.model.execution_order.is_empty() {
+            self.model.configure()?;
+        }
+
+        // Run the model
+        self.model.run()?;
+
+        let data_cache = &self.model.data_cache;
+        self.compute_terms(|name| data_cache.get_existing_series_idx(name).map(|idx| data_cache.get_series(idx)))
+    }
+
+    /// Compute each term's loss from simulated series supplied by `lookup`
+    fn compute_terms<'a, L>(&self, lookup: L) -> Result<HashMap<String, f64>, String>
+    where
+        L: Fn(&str) -> Option<Cow<'a, Timeseries>>,
+    {
+        // Compute each term's loss and stash by term name for expression evaluation
+        let mut term_values: HashMap<String, f64> = HashMap::with_capacity(self.comparisons.len());
+        for comparison in &self.comparisons {
+            let simulated_ts = lookup(&comparison.simulated_series_name)
+                .ok_or_else(|| {
+                    format!(
+                        "Simulated series not found for term '{}': {}",
+                        comparison.name, comparison.simulated_series_name
+                    )
+                })?;
+
+            let (timestamps, mut aligned_obs, mut aligned_sim) = self.align_timeseries(&comparison.observed, &simulated_ts)
+                .map_err(|e| format!("In term '{}': {}", comparison.name, e))?;
+            if !comparison.transforms.is_empty() {
+                aligned_obs = apply_pipeline(&comparison.transforms, &timestamps, &aligned_obs).1;
+                aligned_sim = apply_pipeline(&comparison.transforms, &timestamps, &aligned_sim).1;
+            }
+
+            let value = comparison.statistic.calculate(&aligned_obs, &aligned_sim)
+                .map_err(|e| format!("In term '{}': {}", comparison.name, e))?;
+            term_values.insert(comparison.name.clone(), value);
+        }
+
+        Ok(term_values)
+    }
+
+    /// Evaluate the composite objective expression against per-term losses
+    fn evaluate_expression(&self, term_values: &HashMap<String, f64>) -> Result<f64, String> {
+        let eval_config = EvaluationConfig::default();
+        let context = VariableContext::new(term_values, &eval_config);
+        self.expression.evaluate(&context)
+            .map_err(|e| format!("Failed to evaluate objective_expression: {}", e))
+    }
+
+    /// Compute the composite objective from simulated series produced outside this problem,
+    /// e.g. by [`Model::run_batch`]. Series are matched to terms by their `name`
+    /// (case-insensitive).
+    pub fn objective_from_series(&self, simulated: &[Timeseries]) -> Result<f64, String> {
+        let term_values = self.compute_terms(|name| {
+            simulated.iter().find(|ts| ts.name.eq_ignore_ascii_case(name)).map(Cow::Borrowed)
+        })?;
+        self.evaluate_expression(&term_values)
+    }
+
+    /// Apply `genes`, run the model, and compute the objective and per-term losses over
+    /// `period` instead of the problem's own period. Used to report calibration and
+    /// validation metrics for a parameter set.
+    pub fn period_metrics(&mut self, genes: &[f64], period: Option<(u64, u64)>) -> Result<PeriodMetrics, String> {
+        self.set_params(genes)?;
+        let own_period = std::mem::replace(&mut self.period, period);
+        let term_values = self.run_and_compute_terms();
+        self.period = own_period;
+
+        let term_values = term_values?;
+        let objective = self.evaluate_expression(&term_values)?;
+        let terms = self.comparisons.iter()
+            .map(|c| (c.name.clone(), term_values[&c.name]))
+            .collect();
+        Ok(PeriodMetrics { period, terms, objective })
+    }
+
+    /// Names of the simulated series the objective is computed from, one per term
+    pub fn simulated_series_names(&self) -> Vec<String> {
+        self.comparisons.iter().map(|c| c.simulated_series_name.clone()).collect()
     }
 
     /// Extract current parameter values from model
@@ -227,6 +387,9 @@ impl Optimisable for OptimisationProblem {
             ));
         }
 
+        if self.trace.is_some() {
+            self.last_genes = genes.to_vec();
+        }
         self.apply_params_to_model(genes)
     }
 
@@ -235,42 +398,36 @@ impl Optimisable for OptimisationProblem {
     }
 
     fn evaluate(&mut self) -> Result<f64, String> {
-        // Configure model if needed (first time)
-        if self.model.execution_order.is_empty() {
-            self.model.configure()?;
-        }
-
-        // Run the model
-        self.model.run()?;
-
-        // Compute each term's loss and stash by term name for expression evaluation
-        let mut term_values: HashMap<String, f64> = HashMap::with_capacity(self.comparisons.len());
-        for comparison in &self.comparisons {
-            let sim_idx = self
-                .model
-                .data_cache
-                .get_series_idx(&comparison.simulated_series_name, false)
-                .ok_or_else(|| {
-                    format!(
-                        "Simulated series not found for term '{}': {}",
-                        comparison.name, comparison.simulated_series_name
-                    )
-                })?;
+        // Evaluate the composite expression against the per-term losses
+        let objective = self.run_and_compute_terms()
+            .and_then(|term_values| self.evaluate_expression(&term_values));
+        self.record_trace(&[*objective.as_ref().unwrap_or(&f64::INFINITY)]);
+        objective
+    }
 
-            let simulated_ts = &self.model.data_cache.series[sim_idx];
-            let (aligned_obs, aligned_sim) = self.align_timeseries(&comparison.observed, simulated_ts)
-                .map_err(|e| format!("In term '{}': {}", comparison.name, e))?;
+    fn n_objectives(&self) -> usize {
+        self.objectives.len().max(1)
+    }
 
-            let value = comparison.statistic.calculate(&aligned_obs, &aligned_sim)
-                .map_err(|e| format!("In term '{}': {}", comparison.name, e))?;
-            term_values.insert(comparison.name.clone(), value);
+    fn evaluate_objectives(&mut self) -> Result<Vec<f64>, String> {
+        if self.objectives.is_empty() {
+            return Ok(vec![self.evaluate()?]);
         }
 
-        // Evaluate the composite expression against the per-term losses
-        let eval_config = EvaluationConfig::default();
-        let context = VariableContext::new(&term_values, &eval_config);
-        self.expression.evaluate(&context)
-            .map_err(|e| format!("Failed to evaluate objective_expression: {}", e))
+        // One model run serves every objective
+        let objectives = self.run_and_compute_terms().and_then(|term_values| {
+            let eval_config = EvaluationConfig::default();
+            let context = VariableContext::new(&term_values, &eval_config);
+            self.objectives.iter()
+                .map(|(name, expression)| expression.evaluate(&context)
+                    .map_err(|e| format!("Failed to evaluate objective '{}': {}", name, e)))
+                .collect::<Result<Vec<f64>, String>>()
+        });
+        match &objectives {
+            Ok(values) => self.record_trace(values),
+            Err(_) => self.record_trace(&vec![f64::INFINITY; self.objectives.len()]),
+        }
+        objectives
     }
 
     fn param_names(&self) -> Vec<String> {
@@ -283,6 +440,10 @@ impl Optimisable for OptimisationProblem {
             config: self.config.clone(),
             comparisons: self.comparisons.clone(),
             expression: self.expression.clone(),
+            objectives: self.objectives.clone(),
+            period: self.period,
+            trace: self.trace.clone(),
+            last_genes: self.last_genes.clone(),
         })
     }
 }
@@ -290,7 +451,7 @@ impl Optimisable for OptimisationProblem {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::numerical::opt::objectives::{NseObjective, KgeObjective};
+    use crate::numerical::opt::objectives::{NseObjective, KgeObjective, RmseObjective};
 
     fn obs_fixture() -> Timeseries {
         let mut observed = Timeseries::new_daily();
@@ -326,6 +487,29 @@ mod tests {
         assert_eq!(problem.comparisons[0].statistic.name(), "ONE_MINUS_KGE");
     }
 
+    #[test]
+    fn test_transforms_apply_to_both_series() {
+        let mut observed = Timeseries::new_daily();
+        let mut simulated = Timeseries::new_daily();
+        simulated.name = "node.test.output".to_string();
+        for (t, (o, s)) in [(1.0, 4.0), (4.0, 16.0), (9.0, 36.0)].into_iter().enumerate() {
+            observed.push(t as u64, o);
+            simulated.push(t as u64, s);
+        }
+        let mut problem = OptimisationProblem::single_comparison(
+            Model::new(),
+            ParameterMappingConfig::new(),
+            observed,
+            "node.test.output".to_string(),
+            ObjectiveFunction::RMSE(RmseObjective::new()),
+        );
+        problem.comparisons[0].transforms = vec![SeriesTransform::Sqrt];
+
+        // sqrt gives observed 1, 2, 3 against simulated 2, 4, 6
+        let rmse = problem.objective_from_series(&[simulated]).unwrap();
+        assert!((rmse - (14.0f64 / 3.0).sqrt()).abs() < 1e-12);
+    }
+
     #[test]
     fn test_composite_expression_two_terms() {
         // Build a problem with two comparisons; evaluate the expression manually
@@ -341,4 +525,32 @@ mod tests {
         let result = expression.evaluate(&context).unwrap();
         assert!((result - (0.2 + 0.5 * 0.4)).abs() < 1e-12);
     }
+
+    #[test]
+    fn test_period_restricts_alignment() {
+        let mut simulated = Timeseries::new_daily();
+        for t in 0..3 {
+            simulated.push(t, 10.0 + t as f64);
+        }
+        let problem = OptimisationProblem::single_comparison(
+            Model::new(),
+            ParameterMappingConfig::new(),
+            obs_fixture(),
+            "node.test.output".to_string(),
+            ObjectiveFunction::OneMinusNse(NseObjective::new()),
+        );
+
+        let (_, obs, sim) = problem.align_timeseries(&obs_fixture(), &simulated).unwrap();
+        assert_eq!((obs.len(), sim.len()), (3, 3));
+
+        let problem = problem.with_period(Some((1, 2)));
+        let (timestamps, obs, sim) = problem.align_timeseries(&obs_fixture(), &simulated).unwrap();
+        assert_eq!(timestamps, vec![1, 2]);
+        assert_eq!(obs, vec![2.0, 3.0]);
+        assert_eq!(sim, vec![11.0, 12.0]);
+
+        let problem = problem.with_period(Some((5, 9)));
+        let err = problem.align_timeseries(&obs_fixture(), &simulated).unwrap_err();
+        assert!(err.contains("within the period 5..9"), "got: {}", err);
+    }
 }
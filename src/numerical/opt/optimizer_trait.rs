@@ -20,6 +20,10 @@ pub struct OptimizationProgress {
     /// Used by population-based algorithms (DE, CMA-ES, etc.)
     pub population_objectives: Option<Vec<f64>>,
 
+    /// Current population parameters (normalized [0,1]), one vector per individual
+    /// Used to show how far each parameter has converged
+    pub population_params: Option<Vec<Vec<f64>>>,
+
     /// Elapsed time since optimization started
     pub elapsed: Duration,
 
@@ -35,6 +39,7 @@ impl OptimizationProgress {
             n_evaluations,
             best_objective,
             population_objectives: None,
+            population_params: None,
             elapsed,
             algorithm_data: HashMap::new(),
         }
@@ -46,6 +51,12 @@ impl OptimizationProgress {
         self
     }
 
+    /// Add population parameters for convergence reporting
+    pub fn with_population_params(mut self, params: Vec<Vec<f64>>) -> Self {
+        self.population_params = Some(params);
+        self
+    }
+
     /// Add algorithm-specific data
     pub fn with_data(mut self, key: impl Into<String>, value: f64) -> Self {
         self.algorithm_data.insert(key.into(), value);
@@ -53,6 +64,9 @@ impl OptimizationProgress {
     }
 }
 
+/// Boxed progress callback accepted by optimizers
+pub type ProgressCallback = Box<dyn Fn(&OptimizationProgress) + Send + Sync>;
+
 /// Result of an optimization run (common across all algorithms)
 #[derive(Debug, Clone)]
 pub struct OptimizationResult {
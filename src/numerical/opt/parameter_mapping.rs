@@ -1,19 +1,25 @@
-/// Gene-based parameter mapping system for optimisation.
-///
-/// Each line in the INI `[parameters]` section is a `target = expression` mapping where
-/// `expression` is a full Kalix expression that may call `g(i)` (gene lookup), `lin_range`,
-/// `log_range`, or any of the built-in math functions. Expressions are parsed by the
-/// Kalix expression engine (`crate::functions`) and evaluated against a [`Gene`] that
-/// the optimiser populates each iteration.
-///
-/// The dimensionality of the optimisation problem is determined by a **discovery pass**:
-/// every expression is evaluated once with the gene in [`GeneMode::Discovery`], during
-/// which each call to `g(i)` registers `i` in the gene's map. After the pass, the gene's
-/// dimension count equals the number of unique indices the modeller referenced — sparse
-/// indices like `g(1)` and `g(3)` (skipping `g(2)`) produce a 2-D problem, not a 3-D one.
+//! Gene-based parameter mapping system for optimisation.
+//!
+//! Each line in the INI `[parameters]` section is a `target = expression` mapping where
+//! `expression` is a full Kalix expression that may call `g(i)` (gene lookup), `lin_range`,
+//! `log_range`, or any of the built-in math functions. Expressions are parsed by the
+//! Kalix expression engine (`crate::functions`) and evaluated against a [`Gene`] that
+//! the optimiser populates each iteration.
+//!
+//! The dimensionality of the optimisation problem is determined by a **discovery pass**:
+//! every expression is evaluated once with the gene in [`GeneMode::Discovery`], during
+//! which each call to `g(i)` registers `i` in the gene's map. After the pass, the gene's
+//! dimension count equals the number of unique indices the modeller referenced — sparse
+//! indices like `g(1)` and `g(3)` (skipping `g(2)`) produce a 2-D problem, not a 3-D one.
+//!
+//! Optional [`ParameterConstraint`]s (inequalities over mapped values) keep optimisers out
+//! of physically meaningless regions; infeasible candidates are rejected or repaired
+//! according to [`ConstraintHandling`].
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use crate::functions::{parse_function, EvaluationConfig, EvaluationError, FunctionRegistry, ParsedFunction, VariableContext};
 use crate::numerical::opt::genes::{Gene, GeneMode};
 
@@ -143,14 +149,47 @@ impl ParameterMapping {
     }
 }
 
+/// How candidates that violate a [`ParameterConstraint`] are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConstraintHandling {
+    /// Infeasible candidates fail without running the model, so optimisers score them as
+    /// invalid (infinite objective).
+    #[default]
+    Reject,
+
+    /// Infeasible candidates are moved along the line toward a feasible anchor point to the
+    /// closest feasible point (found by bisection), and that point is evaluated instead.
+    Repair,
+}
+
+/// An inequality over mapped parameter values, e.g. `node.sac.uztwm < node.sac.lztwm`.
+///
+/// Variables are parameter targets from the `[parameters]` section (case-insensitive).
+/// The constraint holds when its expression evaluates to a non-zero value.
+#[derive(Clone, Debug)]
+pub struct ParameterConstraint {
+    pub name: String,
+    pub text: String,
+    pub expression: ParsedFunction,
+    /// (variable name as written, index of the mapping it refers to)
+    references: Vec<(String, usize)>,
+}
+
 /// All parameter mappings for an optimisation, plus the [`Gene`] backing `g(i)` lookups.
 ///
 /// Eval-path objects (function registry, empty variable map, evaluation config) are
 /// built once at construction time and reused across all subsequent [`Self::evaluate`]
 /// calls — this is the optimiser's hot path (one evaluation per population member per
 /// generation), so we avoid allocating a fresh registry on every call.
+///
+/// A mapping's expression may reference parameters mapped earlier by their target name,
+/// which ties parameters together (`node.b.x3 = 2 * node.a.x1`). A mapping with no gene
+/// (`node.a.x2 = 15`) fixes the parameter.
 pub struct ParameterMappingConfig {
     pub mappings: Vec<ParameterMapping>,
+    /// Inequalities every candidate must satisfy, see [`ConstraintHandling`]
+    pub constraints: Vec<ParameterConstraint>,
+    pub constraint_handling: ConstraintHandling,
     /// Shared gene state used by the `g(i)` function closure.
     /// Cloned (deep-cloned, see [`Clone`] impl) per parallel worker.
     gene: Arc<Gene>,
@@ -158,61 +197,87 @@ pub struct ParameterMappingConfig {
     /// Rebuilt on [`Clone`] because the captured `Arc<Gene>` points at the
     /// cloned config's gene, not the original's.
     registry: FunctionRegistry,
-    /// Empty variable map reused across evaluations (parameter expressions don't
-    /// take user variables — only the registered functions).
+    /// For each mapping, the variable names later expressions use to refer to its value
+    aliases: Vec<Vec<String>>,
+    /// Empty variable map reused across evaluations when no mapping references another.
     empty_vars: HashMap<String, f64>,
     /// Cached evaluation config (stateless; cloned cheaply on each evaluation).
     eval_config: EvaluationConfig,
+    /// Feasible gene vector that repairs move toward. Found on first use and shared by
+    /// clones (it only depends on the mappings and constraints).
+    repair_anchor: Arc<OnceLock<Option<Vec<f64>>>>,
 }
 
+/// Number of bisection steps when repairing an infeasible candidate
+const REPAIR_BISECTION_STEPS: usize = 30;
+
+/// Number of random gene vectors tried when searching for a feasible repair anchor
+const REPAIR_ANCHOR_SAMPLES: usize = 10_000;
+
 impl ParameterMappingConfig {
     /// Create empty configuration (no mappings, no genes).
     pub fn new() -> Self {
         let gene = Arc::new(Gene::new());
         Self {
             mappings: Vec::new(),
+            constraints: Vec::new(),
+            constraint_handling: ConstraintHandling::default(),
             registry: build_opt_registry(gene.clone()),
             gene,
+            aliases: Vec::new(),
             empty_vars: HashMap::new(),
             eval_config: EvaluationConfig::default(),
+            repair_anchor: Arc::new(OnceLock::new()),
         }
     }
 
     /// Parse a list of mapping strings and run the gene-discovery pass.
     pub fn from_strings(strings: Vec<&str>) -> Result<Self, String> {
-        let mappings: Vec<ParameterMapping> = strings.iter()
-            .filter(|s| !s.trim().is_empty())
-            .map(|s| ParameterMapping::from_string(s))
-            .collect::<Result<_, _>>()?;
-
-        let gene = Arc::new(Gene::new());
-        let registry = build_opt_registry(gene.clone());
-        let empty_vars: HashMap<String, f64> = HashMap::new();
-        let eval_config = EvaluationConfig::default();
+        let mut config = Self::new();
+        for s in strings.iter().filter(|s| !s.trim().is_empty()) {
+            let mapping = ParameterMapping::from_string(s)?;
+            config.register_references(&mapping)?;
+            config.mappings.push(mapping);
+        }
 
         // Discovery pass: registry & empty_vars & eval_config are all reusable afterwards.
-        gene.set_mode(GeneMode::Discovery);
-        let ctx = VariableContext::new(&empty_vars, &eval_config).with_functions(&registry);
-        for m in &mappings {
-            m.expression.evaluate(&ctx).map_err(|e| {
-                format!("While discovering genes for '{}': {}", m.target, e)
-            })?;
-        }
-        gene.set_mode(GeneMode::Run);
-        drop(ctx);
+        config.gene.set_mode(GeneMode::Discovery);
+        let discovery = config.evaluate_mappings();
+        config.gene.set_mode(GeneMode::Run);
+        discovery.map_err(|(target, e)| format!("While discovering genes for '{}': {}", target, e))?;
 
-        Ok(Self { mappings, gene, registry, empty_vars, eval_config })
+        Ok(config)
     }
 
     pub fn add_mapping(&mut self, mapping: ParameterMapping) {
-        // Run a single-expression discovery pass to register any new genes
+        // References to unknown targets surface as evaluation errors, like any bad expression
+        let _ = self.register_references(&mapping);
+        self.mappings.push(mapping);
+
+        // Run a discovery pass to register any new genes
         self.gene.set_mode(GeneMode::Discovery);
-        let ctx = VariableContext::new(&self.empty_vars, &self.eval_config)
-            .with_functions(&self.registry);
-        let _ = mapping.expression.evaluate(&ctx);
-        drop(ctx);
+        let _ = self.evaluate_mappings();
         self.gene.set_mode(GeneMode::Run);
-        self.mappings.push(mapping);
+    }
+
+    /// Add a named constraint such as `node.sac.uztwm < node.sac.lztwm`. Every variable
+    /// must be a target of an existing mapping.
+    pub fn add_constraint(&mut self, name: &str, text: &str) -> Result<(), String> {
+        let expression = parse_function(text.trim()).map_err(|e| {
+            format!("Failed to parse constraint '{}': {}", name, e)
+        })?;
+        let references = expression.get_variables().iter()
+            .map(|var| self.find_target(var)
+                .map(|idx| (var.clone(), idx))
+                .ok_or_else(|| format!("Constraint '{}' references '{}', which is not a parameter in [parameters]", name, var)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.constraints.push(ParameterConstraint {
+            name: name.to_string(),
+            text: text.trim().to_string(),
+            expression,
+            references,
+        });
+        Ok(())
     }
 
     /// Number of optimisation dimensions (= number of unique gene indices used across all mappings).
@@ -224,26 +289,123 @@ impl ParameterMappingConfig {
     ///
     /// `genes.len()` must equal [`Self::n_genes`]. Values are written into the gene's map
     /// in dimension order (sorted-ascending registered indices), then each parameter
-    /// expression is evaluated to produce its physical value.
+    /// expression is evaluated to produce its physical value. Under
+    /// [`ConstraintHandling::Repair`], an infeasible candidate is repaired first.
     pub fn evaluate(&self, genes: &[f64]) -> Vec<(String, f64)> {
-        self.gene.set_values(genes);
+        let values = self.values_at(genes);
+        if self.constraint_handling == ConstraintHandling::Repair && self.violated_constraint(&values).is_some() {
+            if let Some(repaired) = self.repair(genes) {
+                return self.values_at(&repaired);
+            }
+        }
+        values
+    }
 
-        let ctx = VariableContext::new(&self.empty_vars, &self.eval_config)
-            .with_functions(&self.registry);
+    /// Like [`Self::evaluate`], but fails if the resulting values violate a constraint.
+    pub fn try_evaluate(&self, genes: &[f64]) -> Result<Vec<(String, f64)>, String> {
+        let values = self.evaluate(genes);
+        match self.violated_constraint(&values) {
+            Some(c) => Err(format!("Parameter constraint '{}' violated: {}", c.name, c.text)),
+            None => Ok(values),
+        }
+    }
 
-        self.mappings.iter()
-            .map(|m| {
-                let value = m.expression.evaluate(&ctx)
-                    .expect("expression already validated during discovery pass");
-                (m.target.clone(), value)
-            })
-            .collect()
+    /// The first constraint the given (target, value) pairs violate, if any. A constraint
+    /// that cannot be evaluated counts as violated.
+    pub fn violated_constraint(&self, values: &[(String, f64)]) -> Option<&ParameterConstraint> {
+        self.constraints.iter().find(|c| {
+            let vars: HashMap<String, f64> = c.references.iter()
+                .map(|(name, idx)| (name.clone(), values[*idx].1))
+                .collect();
+            let ctx = VariableContext::new(&vars, &self.eval_config);
+            !matches!(c.expression.evaluate(&ctx), Ok(v) if v != 0.0 && !v.is_nan())
+        })
     }
 
     /// Human-readable gene names in dimension order, e.g. `["g(1)", "g(3)"]`.
     pub fn gene_names(&self) -> Vec<String> {
         self.gene.gene_names()
     }
+
+    fn values_at(&self, genes: &[f64]) -> Vec<(String, f64)> {
+        self.gene.set_values(genes);
+        self.evaluate_mappings()
+            .expect("expression already validated during discovery pass")
+    }
+
+    /// Evaluate every mapping in order with the gene's current values, making each value
+    /// available to later mappings that reference it. Errors carry the failing target.
+    fn evaluate_mappings(&self) -> Result<Vec<(String, f64)>, (String, EvaluationError)> {
+        let mut vars: Option<HashMap<String, f64>> = if self.aliases.iter().all(|a| a.is_empty()) {
+            None
+        } else {
+            Some(HashMap::new())
+        };
+
+        let mut values = Vec::with_capacity(self.mappings.len());
+        for (i, m) in self.mappings.iter().enumerate() {
+            let ctx = VariableContext::new(vars.as_ref().unwrap_or(&self.empty_vars), &self.eval_config)
+                .with_functions(&self.registry);
+            let value = m.expression.evaluate(&ctx).map_err(|e| (m.target.clone(), e))?;
+            if let Some(vars) = vars.as_mut() {
+                for alias in &self.aliases[i] {
+                    vars.insert(alias.clone(), value);
+                }
+            }
+            values.push((m.target.clone(), value));
+        }
+        Ok(values)
+    }
+
+    /// Record which earlier mappings a new mapping's variables refer to
+    fn register_references(&mut self, mapping: &ParameterMapping) -> Result<(), String> {
+        self.aliases.push(Vec::new());
+        for var in mapping.expression.get_variables() {
+            let idx = self.find_target(var).ok_or_else(|| format!(
+                "'{}' in the mapping for '{}' is not a parameter defined earlier in [parameters]",
+                var, mapping.target
+            ))?;
+            self.aliases[idx].push(var.clone());
+        }
+        Ok(())
+    }
+
+    fn find_target(&self, name: &str) -> Option<usize> {
+        self.mappings.iter().position(|m| m.target.eq_ignore_ascii_case(name))
+    }
+
+    /// Bisect along the line from the feasible anchor to `genes` for the feasible point
+    /// closest to `genes`. None if no feasible anchor exists.
+    fn repair(&self, genes: &[f64]) -> Option<Vec<f64>> {
+        let anchor = self.repair_anchor.get_or_init(|| self.find_feasible_anchor()).as_ref()?;
+        let point_at = |t: f64| -> Vec<f64> {
+            anchor.iter().zip(genes).map(|(a, g)| a + t * (g - a)).collect()
+        };
+        let (mut lo, mut hi) = (0.0, 1.0);
+        for _ in 0..REPAIR_BISECTION_STEPS {
+            let mid = 0.5 * (lo + hi);
+            if self.violated_constraint(&self.values_at(&point_at(mid))).is_none() {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Some(point_at(lo))
+    }
+
+    /// The centre of gene space if feasible, otherwise the first feasible point in a fixed
+    /// pseudo-random sequence.
+    fn find_feasible_anchor(&self) -> Option<Vec<f64>> {
+        let n = self.n_genes();
+        let centre = vec![0.5; n];
+        if self.violated_constraint(&self.values_at(&centre)).is_none() {
+            return Some(centre);
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..REPAIR_ANCHOR_SAMPLES)
+            .map(|_| (0..n).map(|_| rng.gen::<f64>()).collect::<Vec<f64>>())
+            .find(|x| self.violated_constraint(&self.values_at(x)).is_none())
+    }
 }
 
 impl Default for ParameterMappingConfig {
@@ -254,6 +416,8 @@ impl std::fmt::Debug for ParameterMappingConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ParameterMappingConfig")
             .field("mappings", &self.mappings)
+            .field("constraints", &self.constraints)
+            .field("constraint_handling", &self.constraint_handling)
             .field("gene", &self.gene)
             .finish_non_exhaustive()
     }
@@ -267,10 +431,14 @@ impl Clone for ParameterMappingConfig {
         let registry = build_opt_registry(gene.clone());
         Self {
             mappings: self.mappings.clone(),
+            constraints: self.constraints.clone(),
+            constraint_handling: self.constraint_handling,
             gene,
             registry,
+            aliases: self.aliases.clone(),
             empty_vars: self.empty_vars.clone(),
             eval_config: self.eval_config.clone(),
+            repair_anchor: Arc::clone(&self.repair_anchor),
         }
     }
 }
@@ -393,6 +561,66 @@ mod tests {
         assert!((cloned_values[0].1 - 70.0).abs() < 1e-10);
     }
 
+    #[test]
+    fn tied_and_fixed_parameters() {
+        let strings = vec![
+            "node.a.x1 = lin_range(g(1), 0, 100)",
+            "node.a.x2 = 15",
+            "node.b.x3 = 2 * node.A.x1 + node.a.x2",
+        ];
+        let config = ParameterMappingConfig::from_strings(strings).unwrap();
+        assert_eq!(config.n_genes(), 1);
+        let values = config.evaluate(&[0.25]);
+        assert_eq!(values[1], ("node.a.x2".to_string(), 15.0));
+        assert!((values[2].1 - 65.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn reference_must_be_defined_earlier() {
+        let strings = vec![
+            "node.b.x3 = 2 * node.a.x1",
+            "node.a.x1 = lin_range(g(1), 0, 100)",
+        ];
+        let err = ParameterMappingConfig::from_strings(strings).unwrap_err();
+        assert!(err.contains("'node.a.x1' in the mapping for 'node.b.x3'"), "got: {}", err);
+    }
+
+    fn constrained_config(constraint: &str, handling: ConstraintHandling) -> ParameterMappingConfig {
+        let mut config = ParameterMappingConfig::from_strings(vec![
+            "node.sac.uztwm = lin_range(g(1), 0, 100)",
+            "node.sac.lztwm = lin_range(g(2), 0, 100)",
+        ]).unwrap();
+        config.add_constraint("uz_below_lz", constraint).unwrap();
+        config.constraint_handling = handling;
+        config
+    }
+
+    #[test]
+    fn constraint_rejects_infeasible_candidates() {
+        let config = constrained_config("node.sac.uztwm < node.sac.lztwm", ConstraintHandling::Reject);
+        assert!(config.try_evaluate(&[0.2, 0.8]).is_ok());
+        let err = config.try_evaluate(&[0.8, 0.2]).unwrap_err();
+        assert!(err.contains("'uz_below_lz' violated"), "got: {}", err);
+
+        let mut config = constrained_config("node.sac.uztwm < node.sac.lztwm", ConstraintHandling::Reject);
+        let err = config.add_constraint("bad", "node.sac.bogus > 0").unwrap_err();
+        assert!(err.contains("'node.sac.bogus'"), "got: {}", err);
+    }
+
+    #[test]
+    fn constraint_repairs_infeasible_candidates() {
+        let config = constrained_config("node.sac.uztwm <= node.sac.lztwm", ConstraintHandling::Repair);
+
+        // Feasible candidates are untouched
+        let values = config.try_evaluate(&[0.2, 0.8]).unwrap();
+        assert!((values[0].1 - 20.0).abs() < 1e-10);
+
+        // Infeasible ones move toward the centre anchor (0.5, 0.5) until just feasible
+        let values = config.try_evaluate(&[0.9, 0.1]).unwrap();
+        assert!(values[0].1 <= values[1].1);
+        assert!((values[0].1 - 50.0).abs() < 1e-3 && (values[1].1 - 50.0).abs() < 1e-3);
+    }
+
     #[test]
     fn transform_linear_apply() {
         let t = Transform::Linear { min: 10.0, max: 20.0 };
@@ -0,0 +1,187 @@
+//! Regional calibration: one parameter set calibrated across many catchment models at once
+//!
+//! Each catchment is a model of its own with its own gauges (terms). A parameter mapping
+//! whose target is an ordinary address such as `node.runoff.x1` is shared regionally: the
+//! same value is set in every catchment's model. A target scoped as
+//! `catchment.NAME.node.runoff.x2` is set in catchment NAME's model only, so it varies
+//! per catchment. The objective expression combines the terms of every catchment.
+//!
+//! The catchment models are run in parallel for each evaluation, and the problem can also
+//! be cloned for parallel evaluation of a population like [`OptimisationProblem`].
+
+use std::collections::HashMap;
+use rayon::prelude::*;
+use crate::functions::{ParsedFunction, VariableContext, EvaluationConfig};
+use super::optimisable::Optimisable;
+use super::optimisation::{OptimisationProblem, PeriodMetrics};
+use super::parameter_mapping::ParameterMappingConfig;
+
+/// Prefix of a parameter target that applies to one catchment only
+pub const CATCHMENT_PREFIX: &str = "catchment.";
+
+/// Splits a catchment-scoped target into (catchment name, address), e.g.
+/// `catchment.upper.node.runoff.x2` into `("upper", "node.runoff.x2")`.
+/// Returns None for a regional (unscoped) target.
+pub fn catchment_scope(target: &str) -> Option<(&str, &str)> {
+    target.strip_prefix(CATCHMENT_PREFIX)
+        .and_then(|rest| rest.split_once('.'))
+        .filter(|(name, address)| !name.is_empty() && !address.is_empty())
+}
+
+/// The parameter values that apply to one catchment, with any catchment scope removed
+pub fn catchment_parameters(catchment: &str, values: &[(String, f64)]) -> Vec<(String, f64)> {
+    values.iter()
+        .filter_map(|(target, value)| match catchment_scope(target) {
+            Some((name, address)) if name.eq_ignore_ascii_case(catchment) => Some((address.to_string(), *value)),
+            Some(_) => None,
+            None => Some((target.clone(), *value)),
+        })
+        .collect()
+}
+
+/// One catchment of a regional problem: its model and the terms compared against its gauges,
+/// held as a single-catchment [`OptimisationProblem`] with no parameter mappings of its own
+pub struct RegionalCatchment {
+    pub name: String,
+    pub problem: OptimisationProblem,
+}
+
+/// Wraps several catchment models to make them optimisable together
+pub struct RegionalProblem {
+    /// Gene-based parameter configuration over every catchment
+    pub config: ParameterMappingConfig,
+
+    /// The catchments, in config order
+    pub catchments: Vec<RegionalCatchment>,
+
+    /// Composite objective expression over the terms of every catchment
+    pub expression: ParsedFunction,
+}
+
+impl RegionalProblem {
+    /// Create a regional problem. Every regional parameter must exist in every catchment's
+    /// model, and every scoped parameter in its own catchment's model.
+    pub fn new(
+        config: ParameterMappingConfig,
+        catchments: Vec<RegionalCatchment>,
+        expression: ParsedFunction,
+    ) -> Result<Self, String> {
+        let problem = Self { config, catchments, expression };
+        let values = problem.config.evaluate(&vec![0.5; problem.config.n_genes()]);
+        for (target, _) in &values {
+            if let Some((name, _)) = catchment_scope(target) {
+                if !problem.catchments.iter().any(|c| c.name.eq_ignore_ascii_case(name)) {
+                    return Err(format!("Parameter '{}' is for catchment '{}', which is not defined", target, name));
+                }
+            }
+        }
+        for catchment in &problem.catchments {
+            for (address, _) in catchment_parameters(&catchment.name, &values) {
+                catchment.problem.model.get_parameter(&address)
+                    .map_err(|e| format!("In catchment '{}': {}", catchment.name, e))?;
+            }
+        }
+        Ok(problem)
+    }
+
+    /// Restrict the objective of every catchment to an inclusive (start, end) timestamp window
+    pub fn with_period(mut self, period: Option<(u64, u64)>) -> Self {
+        for catchment in &mut self.catchments {
+            catchment.problem.period = period;
+        }
+        self
+    }
+
+    /// Run every catchment's model, in parallel, and compute the loss of every term
+    fn run_and_compute_terms(&mut self) -> Result<HashMap<String, f64>, String> {
+        let losses: Vec<HashMap<String, f64>> = self.catchments.par_iter_mut()
+            .map(|c| c.problem.run_and_compute_terms()
+                .map_err(|e| format!("In catchment '{}': {}", c.name, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(losses.into_iter().flatten().collect())
+    }
+
+    fn evaluate_expression(&self, term_values: &HashMap<String, f64>) -> Result<f64, String> {
+        let eval_config = EvaluationConfig::default();
+        let context = VariableContext::new(term_values, &eval_config);
+        self.expression.evaluate(&context)
+            .map_err(|e| format!("Failed to evaluate objective_expression: {}", e))
+    }
+
+    /// Apply `genes` and compute the objective and per-term losses over `period` instead of
+    /// the problem's own period, as [`OptimisationProblem::period_metrics`] does
+    pub fn period_metrics(&mut self, genes: &[f64], period: Option<(u64, u64)>) -> Result<PeriodMetrics, String> {
+        self.set_params(genes)?;
+        let own_period = self.catchments.first().and_then(|c| c.problem.period);
+        for catchment in &mut self.catchments {
+            catchment.problem.period = period;
+        }
+        let term_values = self.run_and_compute_terms();
+        for catchment in &mut self.catchments {
+            catchment.problem.period = own_period;
+        }
+
+        let term_values = term_values?;
+        let objective = self.evaluate_expression(&term_values)?;
+        let terms = self.catchments.iter()
+            .flat_map(|c| c.problem.comparisons.iter())
+            .map(|c| (c.name.clone(), term_values[&c.name]))
+            .collect();
+        Ok(PeriodMetrics { period, terms, objective })
+    }
+}
+
+impl Optimisable for RegionalProblem {
+    fn n_params(&self) -> usize {
+        self.config.n_genes()
+    }
+
+    fn set_params(&mut self, genes: &[f64]) -> Result<(), String> {
+        if genes.len() != self.n_params() {
+            return Err(format!(
+                "Expected {} parameters, got {}",
+                self.n_params(),
+                genes.len()
+            ));
+        }
+
+        let values = self.config.try_evaluate(genes)?;
+        for catchment in &mut self.catchments {
+            for (address, value) in catchment_parameters(&catchment.name, &values) {
+                catchment.problem.model.set_parameter(&address, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_params(&self) -> Vec<f64> {
+        vec![0.5; self.config.n_genes()]
+    }
+
+    fn evaluate(&mut self) -> Result<f64, String> {
+        self.run_and_compute_terms()
+            .and_then(|term_values| self.evaluate_expression(&term_values))
+    }
+
+    fn param_names(&self) -> Vec<String> {
+        self.config.gene_names()
+    }
+
+    fn clone_for_parallel(&self) -> Box<dyn Optimisable> {
+        Box::new(Self {
+            config: self.config.clone(),
+            catchments: self.catchments.iter()
+                .map(|c| RegionalCatchment {
+                    name: c.name.clone(),
+                    problem: OptimisationProblem::new(
+                        c.problem.model.clone(),
+                        c.problem.config.clone(),
+                        c.problem.comparisons.clone(),
+                        c.problem.expression.clone(),
+                    ).with_period(c.problem.period),
+                })
+                .collect(),
+            expression: self.expression.clone(),
+        })
+    }
+}
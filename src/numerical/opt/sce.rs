@@ -18,6 +18,7 @@
 
 use super::optimisable::Optimisable;
 use super::optimizer_trait::{OptimizationProgress, OptimizationResult, Optimizer};
+use super::termination::{EarlyStopping, EarlyStoppingMonitor};
 use rand::prelude::*;
 use rand::seq::SliceRandom;
 use rayon::prelude::*;
@@ -39,9 +40,14 @@ pub struct SceConfig {
     /// Random seed (None for random)
     pub seed: Option<u64>,
 
-    /// Number of threads for parallel complex evolution
+    /// Number of threads for parallel complex evolution. With a seed, the results are the
+    /// same for any number of threads, as each complex evolves with its own random number
+    /// stream, seeded in complex order.
     pub n_threads: usize,
 
+    /// Optional criteria for stopping before the evaluation budget is used
+    pub early_stopping: EarlyStopping,
+
     /// Progress callback (receives OptimizationProgress)
     pub progress_callback: Option<Box<dyn Fn(&OptimizationProgress) + Send + Sync>>,
 }
@@ -95,6 +101,12 @@ impl Sce {
         Self { config }
     }
 
+    /// Set the early-stopping criteria
+    pub fn with_early_stopping(mut self, early_stopping: EarlyStopping) -> Self {
+        self.config.early_stopping = early_stopping;
+        self
+    }
+
     /// Run the SCE optimization algorithm
     pub fn optimize_detailed(
         &self,
@@ -163,6 +175,7 @@ impl Sce {
                 n_evaluations,
                 best_objective,
                 population_objectives: Some(population.iter().map(|ind| ind.objective).collect()),
+                population_params: Some(population.iter().map(|ind| ind.params.clone()).collect()),
                 elapsed: start_time.elapsed(),
                 algorithm_data: HashMap::new(),
             };
@@ -174,7 +187,9 @@ impl Sce {
 
         // Main optimization loop
         let mut shuffle_count = 0;
-        while n_evaluations < self.config.termination_evaluations {
+        let mut monitor = EarlyStoppingMonitor::new(&self.config.early_stopping, start_time);
+        let mut stop_reason = monitor.check(n_evaluations, best_objective);
+        while n_evaluations < self.config.termination_evaluations && stop_reason.is_none() {
             shuffle_count += 1;
 
             // Step 4: Evolve each complex (in parallel if configured)
@@ -223,6 +238,7 @@ impl Sce {
                     n_evaluations,
                     best_objective,
                     population_objectives: Some(population.iter().map(|ind| ind.objective).collect()),
+                    population_params: Some(population.iter().map(|ind| ind.params.clone()).collect()),
                     elapsed: start_time.elapsed(),
                     algorithm_data,
                 };
@@ -231,6 +247,7 @@ impl Sce {
 
             // Step 6: Re-partition (shuffle) complexes for next iteration
             complexes = self.partition_into_complexes(&population, self.config.complexes);
+            stop_reason = monitor.check(n_evaluations, best_objective);
         }
 
         // Return result
@@ -245,7 +262,7 @@ impl Sce {
             best_objective,
             n_evaluations,
             success: true,
-            message: "Optimization completed successfully".to_string(),
+            message: stop_reason.unwrap_or_else(|| "Optimization completed successfully".to_string()),
             elapsed: start_time.elapsed(),
             algorithm_data,
         }
@@ -0,0 +1,261 @@
+//! Transforms applied to observed and simulated series before an objective is computed
+//!
+//! A term's `transform` setting is a comma-separated pipeline applied in order to both
+//! aligned series, e.g. `transform = monthly_mean, sqrt`. Available transforms:
+//!
+//! - `sqrt`: square root of each value
+//! - `boxcox(lambda)`: Box-Cox power transform, `(x^lambda - 1) / lambda` (`ln(x)` for lambda = 0)
+//! - `rolling_mean(n)`: trailing mean over `n` consecutive values (the first `n - 1` are dropped)
+//! - `monthly_mean`, `monthly_sum`, `annual_mean`, `annual_sum`: calendar aggregation
+//! - `annual_mean(m)`, `annual_sum(m)`: aggregation over water years starting in month `m` (Jan=1)
+//!
+//! Values outside a transform's domain become NaN and are masked by the objective. An
+//! aggregate or rolling mean containing a NaN is itself NaN, so gaps in the observed data
+//! are not hidden by partial periods.
+
+use std::fmt;
+use crate::tid::utils::u64_to_year_month_day_and_seconds;
+use crate::tid::water_year::WaterYear;
+
+/// Calendar period for [`SeriesTransform::Aggregate`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregationPeriod {
+    Monthly,
+    /// Calendar years are `WaterYear::CALENDAR`
+    Annual(WaterYear),
+}
+
+/// How values within a period are combined for [`SeriesTransform::Aggregate`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregationMethod {
+    Mean,
+    Sum,
+}
+
+/// One step of a series transform pipeline
+#[derive(Debug, Clone, PartialEq)]
+pub enum SeriesTransform {
+    Sqrt,
+    BoxCox { lambda: f64 },
+    RollingMean { window: usize },
+    Aggregate { period: AggregationPeriod, method: AggregationMethod },
+}
+
+impl SeriesTransform {
+    /// Parse a single transform, e.g. `sqrt`, `boxcox(0.3)` or `rolling_mean(7)` (case-insensitive)
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim().to_lowercase();
+        let (name, arg) = match s.split_once('(') {
+            Some((name, rest)) => {
+                let arg = rest.strip_suffix(')')
+                    .ok_or_else(|| format!("Missing ')' in transform '{}'", s))?;
+                (name.trim().to_string(), Some(arg.trim().to_string()))
+            }
+            None => (s.clone(), None),
+        };
+
+        let transform = match (name.as_str(), arg) {
+            ("sqrt", None) => SeriesTransform::Sqrt,
+            ("boxcox", Some(arg)) => {
+                let lambda = arg.parse::<f64>().ok().filter(|l| l.is_finite())
+                    .ok_or_else(|| format!("Invalid Box-Cox lambda '{}'", arg))?;
+                SeriesTransform::BoxCox { lambda }
+            }
+            ("rolling_mean", Some(arg)) => {
+                let window = arg.parse::<usize>().ok().filter(|&w| w > 0)
+                    .ok_or_else(|| format!("Invalid rolling_mean window '{}' (must be a positive integer)", arg))?;
+                SeriesTransform::RollingMean { window }
+            }
+            ("monthly_mean", None) => SeriesTransform::Aggregate { period: AggregationPeriod::Monthly, method: AggregationMethod::Mean },
+            ("monthly_sum", None) => SeriesTransform::Aggregate { period: AggregationPeriod::Monthly, method: AggregationMethod::Sum },
+            ("annual_mean", None) => SeriesTransform::Aggregate { period: AggregationPeriod::Annual(WaterYear::CALENDAR), method: AggregationMethod::Mean },
+            ("annual_sum", None) => SeriesTransform::Aggregate { period: AggregationPeriod::Annual(WaterYear::CALENDAR), method: AggregationMethod::Sum },
+            ("annual_mean" | "annual_sum", Some(arg)) => {
+                let year = arg.parse::<u8>().map_err(|e| e.to_string()).and_then(WaterYear::new)
+                    .map_err(|_| format!("Invalid water year start month '{}' (must be 1 to 12)", arg))?;
+                let method = if name == "annual_sum" { AggregationMethod::Sum } else { AggregationMethod::Mean };
+                SeriesTransform::Aggregate { period: AggregationPeriod::Annual(year), method }
+            }
+            _ => return Err(format!(
+                "Unknown transform '{}'. Valid options: sqrt, boxcox(lambda), rolling_mean(n), \
+                 monthly_mean, monthly_sum, annual_mean, annual_sum, annual_mean(month), annual_sum(month)",
+                s
+            )),
+        };
+        Ok(transform)
+    }
+
+    /// Parse a comma-separated pipeline, e.g. `monthly_mean, boxcox(0.3)`
+    pub fn parse_pipeline(s: &str) -> Result<Vec<Self>, String> {
+        s.split(',')
+            .filter(|part| !part.trim().is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// Apply to a series, returning the (possibly shorter) timestamps and values
+    pub fn apply(&self, timestamps: &[u64], values: &[f64]) -> (Vec<u64>, Vec<f64>) {
+        match self {
+            SeriesTransform::Sqrt => (timestamps.to_vec(), values.iter().map(|v| v.sqrt()).collect()),
+            SeriesTransform::BoxCox { lambda } => {
+                let transformed = values.iter()
+                    .map(|&v| {
+                        if v < 0.0 {
+                            f64::NAN
+                        } else if *lambda == 0.0 {
+                            v.ln()
+                        } else {
+                            (v.powf(*lambda) - 1.0) / lambda
+                        }
+                    })
+                    .collect();
+                (timestamps.to_vec(), transformed)
+            }
+            SeriesTransform::RollingMean { window } => {
+                if values.len() < *window {
+                    return (vec![], vec![]);
+                }
+                let means = values.windows(*window)
+                    .map(|w| w.iter().sum::<f64>() / *window as f64)
+                    .collect();
+                (timestamps[window - 1..].to_vec(), means)
+            }
+            SeriesTransform::Aggregate { period, method } => aggregate(timestamps, values, *period, *method),
+        }
+    }
+}
+
+impl fmt::Display for SeriesTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeriesTransform::Sqrt => write!(f, "sqrt"),
+            SeriesTransform::BoxCox { lambda } => write!(f, "boxcox({})", lambda),
+            SeriesTransform::RollingMean { window } => write!(f, "rolling_mean({})", window),
+            SeriesTransform::Aggregate { period, method } => {
+                let method = match method {
+                    AggregationMethod::Mean => "mean",
+                    AggregationMethod::Sum => "sum",
+                };
+                match period {
+                    AggregationPeriod::Monthly => write!(f, "monthly_{}", method),
+                    AggregationPeriod::Annual(year) if *year == WaterYear::CALENDAR => write!(f, "annual_{}", method),
+                    AggregationPeriod::Annual(year) => write!(f, "annual_{}({})", method, year.start_month()),
+                }
+            }
+        }
+    }
+}
+
+/// Apply each transform in order
+pub fn apply_pipeline(transforms: &[SeriesTransform], timestamps: &[u64], values: &[f64]) -> (Vec<u64>, Vec<f64>) {
+    let mut timestamps = timestamps.to_vec();
+    let mut values = values.to_vec();
+    for transform in transforms {
+        (timestamps, values) = transform.apply(&timestamps, &values);
+    }
+    (timestamps, values)
+}
+
+/// Aggregate consecutive values falling in the same calendar period, stamped with the
+/// period's first timestamp
+fn aggregate(timestamps: &[u64], values: &[f64], period: AggregationPeriod,
+             method: AggregationMethod) -> (Vec<u64>, Vec<f64>) {
+    let key = |t: u64| {
+        let (year, month, _, _) = u64_to_year_month_day_and_seconds(t);
+        match period {
+            AggregationPeriod::Monthly => (year, month),
+            AggregationPeriod::Annual(water_year) => (water_year.year_of_month(year, month), 0),
+        }
+    };
+
+    let mut out_timestamps = Vec::new();
+    let mut out_values = Vec::new();
+    let mut start = 0;
+    while start < timestamps.len() {
+        let current = key(timestamps[start]);
+        let mut end = start + 1;
+        while end < timestamps.len() && key(timestamps[end]) == current {
+            end += 1;
+        }
+        let sum: f64 = values[start..end].iter().sum();
+        out_timestamps.push(timestamps[start]);
+        out_values.push(match method {
+            AggregationMethod::Sum => sum,
+            AggregationMethod::Mean => sum / (end - start) as f64,
+        });
+        start = end;
+    }
+    (out_timestamps, out_values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tid::utils::date_string_to_u64_flexible;
+
+    fn ts(date: &str) -> u64 {
+        date_string_to_u64_flexible(date).unwrap().0
+    }
+
+    #[test]
+    fn test_parse_pipeline() {
+        let pipeline = SeriesTransform::parse_pipeline("Monthly_Mean, boxcox(0.5), rolling_mean(3), sqrt").unwrap();
+        assert_eq!(pipeline, vec![
+            SeriesTransform::Aggregate { period: AggregationPeriod::Monthly, method: AggregationMethod::Mean },
+            SeriesTransform::BoxCox { lambda: 0.5 },
+            SeriesTransform::RollingMean { window: 3 },
+            SeriesTransform::Sqrt,
+        ]);
+        let names: Vec<String> = pipeline.iter().map(|t| t.to_string()).collect();
+        assert_eq!(names, ["monthly_mean", "boxcox(0.5)", "rolling_mean(3)", "sqrt"]);
+
+        assert!(SeriesTransform::parse("log").is_err());
+        assert!(SeriesTransform::parse("rolling_mean(0)").is_err());
+        assert!(SeriesTransform::parse("boxcox").is_err());
+        assert!(SeriesTransform::parse("boxcox(0.5").is_err());
+    }
+
+    #[test]
+    fn test_value_transforms() {
+        let t = [1, 2, 3, 4];
+        let v = [1.0, 4.0, 9.0, -1.0];
+        let (_, sqrt) = SeriesTransform::Sqrt.apply(&t, &v);
+        assert_eq!(&sqrt[..3], &[1.0, 2.0, 3.0]);
+        assert!(sqrt[3].is_nan());
+
+        let (_, boxcox) = SeriesTransform::BoxCox { lambda: 0.5 }.apply(&t, &v);
+        assert_eq!(&boxcox[..3], &[0.0, 2.0, 4.0]);
+        assert!(boxcox[3].is_nan());
+        let (_, log) = SeriesTransform::BoxCox { lambda: 0.0 }.apply(&t, &[1.0]);
+        assert_eq!(log, vec![0.0]);
+
+        let (rt, rolling) = SeriesTransform::RollingMean { window: 2 }.apply(&t, &v);
+        assert_eq!(rt, vec![2, 3, 4]);
+        assert_eq!(rolling, vec![2.5, 6.5, 4.0]);
+    }
+
+    #[test]
+    fn test_monthly_and_annual_aggregation() {
+        let t = [ts("2000-01-30"), ts("2000-01-31"), ts("2000-02-01"), ts("2001-03-01")];
+        let v = [1.0, 3.0, 10.0, f64::NAN];
+
+        let monthly = SeriesTransform::parse("monthly_mean").unwrap();
+        let (mt, mv) = monthly.apply(&t, &v);
+        assert_eq!(mt, vec![t[0], t[2], t[3]]);
+        assert_eq!(&mv[..2], &[2.0, 10.0]);
+        assert!(mv[2].is_nan());
+
+        let annual = SeriesTransform::parse("annual_sum").unwrap();
+        let (at, av) = annual.apply(&t, &v);
+        assert_eq!(at, vec![t[0], t[3]]);
+        assert_eq!(av[0], 14.0);
+
+        // Water years starting in February split January from the rest
+        let water_year = SeriesTransform::parse("annual_sum(2)").unwrap();
+        assert_eq!(water_year.to_string(), "annual_sum(2)");
+        let (wt, wv) = water_year.apply(&t, &v);
+        assert_eq!(wt, vec![t[0], t[2], t[3]]);
+        assert_eq!(&wv[..2], &[4.0, 10.0]);
+        assert!(SeriesTransform::parse("annual_mean(13)").is_err());
+    }
+}
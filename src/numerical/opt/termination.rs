@@ -0,0 +1,176 @@
+//! Early-stopping criteria shared by the single-objective optimizers
+//!
+//! Every optimizer runs until its evaluation budget (`termination_evaluations`) is used.
+//! These optional criteria can end a run sooner; the reason is reported in
+//! `OptimizationResult::message`, and an early stop still counts as success.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Flag shared with the caller that ends a run as soon as it is set, e.g. by a client's
+/// stop request. The run returns its best result so far.
+#[derive(Debug, Clone, Default)]
+pub struct StopFlag(Arc<AtomicBool>);
+
+impl StopFlag {
+    pub fn new(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for StopFlag {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Optional criteria that end an optimisation before the evaluation budget is used
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EarlyStopping {
+    /// Stop when the best objective has not improved (by more than `min_improvement`)
+    /// within this many evaluations
+    pub stagnation_evaluations: Option<usize>,
+
+    /// Smallest decrease in the best objective that counts as an improvement for stagnation
+    pub min_improvement: f64,
+
+    /// Stop once the best objective is at or below this value. Objectives are lower-better
+    /// with 0 for a perfect fit (e.g. 1 - NSE), so this is the tolerance from a perfect fit.
+    pub objective_tolerance: Option<f64>,
+
+    /// Stop once this much wall-clock time has elapsed
+    pub max_time: Option<Duration>,
+
+    /// Stop when this flag is set (not read from config files)
+    pub stop_flag: Option<StopFlag>,
+}
+
+impl EarlyStopping {
+    /// True if any criterion is set
+    pub fn is_enabled(&self) -> bool {
+        self.stagnation_evaluations.is_some() || self.objective_tolerance.is_some() || self.max_time.is_some()
+            || self.stop_flag.is_some()
+    }
+}
+
+/// Tracks the progress of one run against an [`EarlyStopping`] configuration
+pub struct EarlyStoppingMonitor {
+    criteria: EarlyStopping,
+    start_time: Instant,
+    reference_objective: f64,
+    last_improvement: usize,
+}
+
+impl EarlyStoppingMonitor {
+    /// Start monitoring; the wall-clock limit is measured from `start_time`
+    pub fn new(criteria: &EarlyStopping, start_time: Instant) -> Self {
+        Self {
+            criteria: criteria.clone(),
+            start_time,
+            reference_objective: f64::INFINITY,
+            last_improvement: 0,
+        }
+    }
+
+    /// Record the best objective after `n_evaluations` evaluations.
+    ///
+    /// Returns the termination message if the run should stop now.
+    pub fn check(&mut self, n_evaluations: usize, best_objective: f64) -> Option<String> {
+        if self.criteria.stop_flag.as_ref().is_some_and(StopFlag::is_set) {
+            return Some(format!("Stopped: interrupted after {} evaluations", n_evaluations));
+        }
+
+        if let Some(tolerance) = self.criteria.objective_tolerance {
+            if best_objective <= tolerance {
+                return Some(format!(
+                    "Stopped early: best objective {} reached objective_tolerance {}",
+                    best_objective, tolerance
+                ));
+            }
+        }
+
+        if best_objective < self.reference_objective - self.criteria.min_improvement
+            || (self.reference_objective.is_infinite() && best_objective.is_finite()) {
+            self.reference_objective = best_objective;
+            self.last_improvement = n_evaluations;
+        } else if let Some(window) = self.criteria.stagnation_evaluations {
+            if n_evaluations.saturating_sub(self.last_improvement) >= window {
+                return Some(format!(
+                    "Stopped early: no improvement in the best objective over the last {} evaluations",
+                    n_evaluations - self.last_improvement
+                ));
+            }
+        }
+
+        if let Some(max_time) = self.criteria.max_time {
+            if self.start_time.elapsed() >= max_time {
+                return Some(format!(
+                    "Stopped early: wall-clock limit of {} s reached",
+                    max_time.as_secs_f64()
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_never_stops() {
+        let criteria = EarlyStopping::default();
+        assert!(!criteria.is_enabled());
+        let mut monitor = EarlyStoppingMonitor::new(&criteria, Instant::now());
+        for n in 0..1000 {
+            assert_eq!(monitor.check(n, 1.0), None);
+        }
+    }
+
+    #[test]
+    fn test_stagnation_and_min_improvement() {
+        let criteria = EarlyStopping {
+            stagnation_evaluations: Some(100),
+            min_improvement: 0.01,
+            ..Default::default()
+        };
+        let mut monitor = EarlyStoppingMonitor::new(&criteria, Instant::now());
+        assert_eq!(monitor.check(10, 1.0), None);
+        assert_eq!(monitor.check(60, 0.5), None);
+        // Improvements smaller than min_improvement do not reset the window
+        assert_eq!(monitor.check(120, 0.495), None);
+        let message = monitor.check(160, 0.49).unwrap();
+        assert!(message.contains("no improvement"), "{}", message);
+        assert!(message.contains("100 evaluations"), "{}", message);
+    }
+
+    #[test]
+    fn test_objective_tolerance_and_time_limit() {
+        let criteria = EarlyStopping { objective_tolerance: Some(0.05), ..Default::default() };
+        let mut monitor = EarlyStoppingMonitor::new(&criteria, Instant::now());
+        assert_eq!(monitor.check(10, 0.2), None);
+        assert!(monitor.check(20, 0.05).unwrap().contains("objective_tolerance"));
+
+        let criteria = EarlyStopping { max_time: Some(Duration::ZERO), ..Default::default() };
+        let mut monitor = EarlyStoppingMonitor::new(&criteria, Instant::now());
+        assert!(monitor.check(1, 1.0).unwrap().contains("wall-clock"));
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let criteria = EarlyStopping { stop_flag: Some(StopFlag::new(Arc::clone(&flag))), ..Default::default() };
+        assert!(criteria.is_enabled());
+        let mut monitor = EarlyStoppingMonitor::new(&criteria, Instant::now());
+        assert_eq!(monitor.check(10, 1.0), None);
+        flag.store(true, Ordering::Relaxed);
+        assert!(monitor.check(20, 1.0).unwrap().contains("interrupted"));
+    }
+}
@@ -0,0 +1,109 @@
+//! Calibration trace: every evaluated parameter set written to a CSV file
+//!
+//! One row per evaluation with the evaluation number, the optimiser's generation (or SCE
+//! shuffle) as of its latest progress report, the physical parameter values and the
+//! objective value(s). Failed evaluations are recorded with an objective of `inf`. Rows
+//! are written in completion order, so parallel evaluations within a generation may be
+//! interleaved. The file supports post-hoc dotty plots and convergence diagnostics.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use super::optimizer_trait::{OptimizationProgress, ProgressCallback};
+
+/// CSV writer shared by an optimisation problem and its parallel clones
+pub struct CalibrationTrace {
+    path: String,
+    state: Mutex<TraceState>,
+    generation: AtomicUsize,
+    n_reports: AtomicUsize,
+}
+
+struct TraceState {
+    writer: BufWriter<File>,
+    n_rows: usize,
+    /// First write error, reported by `finish`
+    error: Option<String>,
+}
+
+impl CalibrationTrace {
+    /// Create the trace file and write the header row
+    pub fn create(path: &str, parameter_names: &[String], objective_names: &[String]) -> Result<Self, String> {
+        if path.to_lowercase().ends_with(".parquet") {
+            return Err(format!("Trace file '{}': only CSV trace files are supported", path));
+        }
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create trace file '{}': {}", path, e))?;
+        let mut writer = BufWriter::new(file);
+
+        let header: Vec<&str> = ["evaluation", "generation"].into_iter()
+            .chain(parameter_names.iter().map(|s| s.as_str()))
+            .chain(objective_names.iter().map(|s| s.as_str()))
+            .collect();
+        writeln!(writer, "{}", header.join(","))
+            .map_err(|e| format!("Failed to write trace file '{}': {}", path, e))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            state: Mutex::new(TraceState { writer, n_rows: 0, error: None }),
+            generation: AtomicUsize::new(0),
+            n_reports: AtomicUsize::new(0),
+        })
+    }
+
+    /// Append one evaluation
+    pub fn record(&self, parameters: &[f64], objectives: &[f64]) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let mut state = self.state.lock().unwrap();
+        if state.error.is_some() {
+            return;
+        }
+        state.n_rows += 1;
+        let mut row = format!("{},{}", state.n_rows, generation);
+        for value in parameters.iter().chain(objectives) {
+            row.push(',');
+            row.push_str(&value.to_string());
+        }
+        if let Err(e) = writeln!(state.writer, "{}", row) {
+            state.error = Some(format!("Failed to write trace file '{}': {}", self.path, e));
+        }
+    }
+
+    /// Update the generation column from a progress report: the algorithm's "generation"
+    /// or "shuffle" counter, or otherwise the number of reports seen so far
+    pub fn observe_progress(&self, progress: &OptimizationProgress) {
+        let n_reports = self.n_reports.fetch_add(1, Ordering::Relaxed);
+        let generation = progress.algorithm_data.get("generation")
+            .or_else(|| progress.algorithm_data.get("shuffle"))
+            .map(|&g| g as usize)
+            .unwrap_or(n_reports);
+        self.generation.store(generation, Ordering::Relaxed);
+    }
+
+    /// Number of rows written so far
+    pub fn n_rows(&self) -> usize {
+        self.state.lock().unwrap().n_rows
+    }
+
+    /// Flush the file, returning the first write error (if any)
+    pub fn finish(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(e) = state.error.take() {
+            return Err(e);
+        }
+        state.writer.flush()
+            .map_err(|e| format!("Failed to write trace file '{}': {}", self.path, e))
+    }
+}
+
+/// Wrap a progress callback so the trace's generation column follows the optimiser's progress
+pub fn tracking_callback(trace: &Arc<CalibrationTrace>, callback: Option<ProgressCallback>) -> ProgressCallback {
+    let trace = Arc::clone(trace);
+    Box::new(move |progress: &OptimizationProgress| {
+        trace.observe_progress(progress);
+        if let Some(ref callback) = callback {
+            callback(progress);
+        }
+    })
+}
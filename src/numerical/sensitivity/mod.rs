@@ -0,0 +1,87 @@
+//! Global sensitivity analysis of the calibration parameter space
+//!
+//! Both methods work on any [`Optimisable`] problem, usually an `OptimisationProblem`
+//! built from a calibration config, so the response is whatever objective (statistic or
+//! composite expression) the config defines. Parameters are the normalised genes in
+//! [0,1]. Model runs are evaluated in parallel, one problem clone per worker thread.
+//!
+//! - [`sobol::sobol_analysis`]: variance-based first-order and total-order indices
+//! - [`morris::morris_analysis`]: elementary effects screening (mu, mu*, sigma)
+
+pub mod morris;
+pub mod sobol;
+pub mod sobol_sequence;
+
+pub use morris::{morris_analysis, MorrisConfig, MorrisResult};
+pub use sobol::{sobol_analysis, SobolConfig, SobolResult};
+pub use sobol_sequence::{SobolSequence, MAX_SOBOL_DIMS};
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use rayon::prelude::*;
+use crate::numerical::opt::Optimisable;
+
+/// Progress callback receiving (completed evaluations, total evaluations)
+pub type SensitivityProgress = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Evaluates the problem at every point, in parallel, preserving order.
+///
+/// Returns `Ok(None)` if `interrupt_check` fired, and an error naming the first point
+/// whose evaluation failed (sensitivity estimators cannot skip samples).
+fn evaluate_points<F>(problem: &dyn Optimisable, points: &[Vec<f64>], n_threads: usize,
+                      interrupt_check: &F, progress_callback: Option<&SensitivityProgress>)
+    -> Result<Option<Vec<f64>>, String>
+where
+    F: Fn() -> bool + Sync,
+{
+    if points.is_empty() {
+        return Ok(Some(vec![]));
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(n_threads)
+        .build()
+        .map_err(|e| format!("Failed to create thread pool: {}", e))?;
+    let n_workers = pool.current_num_threads().clamp(1, points.len());
+    let chunk_size = points.len().div_ceil(n_workers);
+
+    // One problem clone per chunk; clones are made up front because the problem is not Sync
+    let n_chunks = points.len().div_ceil(chunk_size);
+    let workers: Vec<Mutex<Box<dyn Optimisable>>> = (0..n_chunks)
+        .map(|_| Mutex::new(problem.clone_for_parallel()))
+        .collect();
+
+    let completed = AtomicUsize::new(0);
+    let interrupted = AtomicBool::new(false);
+
+    let results: Vec<Result<f64, String>> = pool.install(|| {
+        points.par_chunks(chunk_size).enumerate().flat_map_iter(|(chunk_idx, chunk)| {
+            let mut worker = workers[chunk_idx].lock().unwrap();
+            let mut chunk_results = Vec::with_capacity(chunk.len());
+            for (i, x) in chunk.iter().enumerate() {
+                if interrupted.load(Ordering::Relaxed) || interrupt_check() {
+                    interrupted.store(true, Ordering::Relaxed);
+                    break;
+                }
+                let f = worker.set_params(x)
+                    .and_then(|_| worker.evaluate())
+                    .map_err(|e| format!("Evaluation failed for sample {}: {}", chunk_idx * chunk_size + i, e));
+                chunk_results.push(f);
+                let n_done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(callback) = progress_callback {
+                    callback(n_done, points.len());
+                }
+            }
+            chunk_results
+        }).collect()
+    });
+
+    if interrupted.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+    results.into_iter().collect::<Result<Vec<f64>, String>>().map(Some)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
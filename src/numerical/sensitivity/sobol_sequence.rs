@@ -0,0 +1,126 @@
+//! Sobol low-discrepancy sequence generator
+//!
+//! Uses the Antonov-Saleev Gray-code construction with the primitive polynomials and
+//! initial direction numbers of Joe & Kuo (2008), "new-joe-kuo-6.21201", for the first
+//! [`MAX_SOBOL_DIMS`] dimensions. The all-zero first point is skipped.
+
+/// Number of bits of precision per coordinate
+const BITS: usize = 32;
+
+/// Maximum supported dimension
+pub const MAX_SOBOL_DIMS: usize = 21;
+
+/// (degree s, polynomial coefficients a, initial direction numbers m) for dimensions 2..=21
+const JOE_KUO: [(u32, u32, &[u32]); MAX_SOBOL_DIMS - 1] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+/// Generates successive points of a Sobol sequence in [0, 1)^dims
+pub struct SobolSequence {
+    directions: Vec<[u32; BITS]>,
+    state: Vec<u32>,
+    index: u32,
+}
+
+impl SobolSequence {
+    pub fn new(dims: usize) -> Result<Self, String> {
+        if dims == 0 || dims > MAX_SOBOL_DIMS {
+            return Err(format!("Sobol sequence dimension must be between 1 and {}, got {}", MAX_SOBOL_DIMS, dims));
+        }
+
+        let mut directions = Vec::with_capacity(dims);
+
+        // First dimension: van der Corput sequence in base 2
+        let mut v = [0u32; BITS];
+        for (k, vk) in v.iter_mut().enumerate() {
+            *vk = 1 << (BITS - 1 - k);
+        }
+        directions.push(v);
+
+        for &(s, a, m) in JOE_KUO.iter().take(dims - 1) {
+            let s = s as usize;
+            let mut v = [0u32; BITS];
+            for k in 0..BITS {
+                if k < s {
+                    v[k] = m[k] << (BITS - 1 - k);
+                } else {
+                    v[k] = v[k - s] ^ (v[k - s] >> s);
+                    for l in 1..s {
+                        if (a >> (s - 1 - l)) & 1 == 1 {
+                            v[k] ^= v[k - l];
+                        }
+                    }
+                }
+            }
+            directions.push(v);
+        }
+
+        Ok(Self { directions, state: vec![0; dims], index: 0 })
+    }
+
+    /// Returns the next point of the sequence
+    pub fn next_point(&mut self) -> Vec<f64> {
+        // Gray-code update: flip the direction number at the lowest zero bit of the index
+        let c = (!self.index).trailing_zeros() as usize;
+        for (x, v) in self.state.iter_mut().zip(&self.directions) {
+            *x ^= v[c.min(BITS - 1)];
+        }
+        self.index = self.index.wrapping_add(1);
+        self.state.iter().map(|&x| x as f64 / (1u64 << BITS) as f64).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_points() {
+        let mut seq = SobolSequence::new(2).unwrap();
+        assert_eq!(seq.next_point(), vec![0.5, 0.5]);
+        assert_eq!(seq.next_point(), vec![0.75, 0.25]);
+        assert_eq!(seq.next_point(), vec![0.25, 0.75]);
+    }
+
+    #[test]
+    fn test_each_dimension_is_stratified() {
+        // With the skipped origin, the first 2^m points fill every one of 2^m bins per dimension
+        let mut seq = SobolSequence::new(MAX_SOBOL_DIMS).unwrap();
+        let n = 1024;
+        let mut hit = vec![vec![false; n]; MAX_SOBOL_DIMS];
+        for h in hit.iter_mut() {
+            h[0] = true;
+        }
+        for _ in 0..n - 1 {
+            for (d, x) in seq.next_point().into_iter().enumerate() {
+                hit[d][(x * n as f64) as usize] = true;
+            }
+        }
+        assert!(hit.iter().all(|h| h.iter().all(|&b| b)));
+    }
+
+    #[test]
+    fn test_dimension_limits() {
+        assert!(SobolSequence::new(0).is_err());
+        assert!(SobolSequence::new(MAX_SOBOL_DIMS + 1).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Difference statistics between two timeseries, e.g. the same output from a baseline and a
+//! scenario model
+//!
+//! The series are aligned on their timestamps and compared over the timesteps they share.
+//! Differences are `other - reference`, so a positive bias means `other` is higher. NSE is
+//! that of `other` against `reference` treated as the observation.
+
+use crate::timeseries::Timeseries;
+
+/// Summary of how `other` differs from `reference`
+#[derive(Clone)]
+pub struct SeriesComparison {
+    /// Number of shared timesteps where both series have finite values
+    pub n_compared: usize,
+    /// Mean of `other - reference`
+    pub bias: f64,
+    pub rmse: f64,
+    /// NaN if `reference` is constant over the compared timesteps
+    pub nse: f64,
+    pub max_abs_diff: f64,
+    /// Timestamp of the first shared timestep where the series differ by more than the
+    /// tolerance, or where only one of them has a value
+    pub first_divergence: Option<u64>,
+    /// `other - reference` over the shared timesteps (NaN where either is missing)
+    pub difference: Timeseries,
+}
+
+/// Compares two series with the same step size. Timesteps where the absolute difference
+/// is within `tolerance` do not count as divergence.
+pub fn compare_series(reference: &Timeseries, other: &Timeseries, tolerance: f64) -> Result<SeriesComparison, String> {
+    if reference.step_size != other.step_size {
+        return Err(format!(
+            "Series have different step sizes ({} and {})", reference.step_size, other.step_size
+        ));
+    }
+    let step_size = reference.step_size.max(1);
+
+    // Shared span of the two series
+    let end = |ts: &Timeseries| ts.start_timestamp + ts.values.len() as u64 * step_size;
+    let start = reference.start_timestamp.max(other.start_timestamp);
+    let stop = end(reference).min(end(other));
+    if reference.values.is_empty() || other.values.is_empty() || start >= stop
+        || (start - reference.start_timestamp) % step_size != (start - other.start_timestamp) % step_size {
+        return Err("Series do not share any timesteps".to_string());
+    }
+    let offset_ref = ((start - reference.start_timestamp) / step_size) as usize;
+    let offset_other = ((start - other.start_timestamp) / step_size) as usize;
+    let n_shared = ((stop - start) / step_size) as usize;
+    let a = &reference.values[offset_ref..offset_ref + n_shared];
+    let b = &other.values[offset_other..offset_other + n_shared];
+
+    let mut difference = Timeseries::new(reference.step_size);
+    difference.name = format!("{} - {}", other.name, reference.name);
+    difference.start_timestamp = start;
+
+    let mut first_divergence = None;
+    let (mut n, mut sum_diff, mut sum_sq_diff, mut sum_ref, mut max_abs_diff) = (0usize, 0.0, 0.0, 0.0, 0.0f64);
+    for i in 0..n_shared {
+        let (x, y) = (a[i], b[i]);
+        let diff = y - x;
+        difference.push_value(diff);
+        let diverged = match (x.is_finite(), y.is_finite()) {
+            (true, true) => {
+                n += 1;
+                sum_diff += diff;
+                sum_sq_diff += diff * diff;
+                sum_ref += x;
+                max_abs_diff = max_abs_diff.max(diff.abs());
+                diff.abs() > tolerance
+            }
+            (false, false) => false,
+            _ => true,
+        };
+        if diverged && first_divergence.is_none() {
+            first_divergence = Some(start + i as u64 * step_size);
+        }
+    }
+    if n == 0 {
+        return Err("Series have no shared timesteps with values in both".to_string());
+    }
+
+    let mean_ref = sum_ref / n as f64;
+    let ss_ref: f64 = a.iter().zip(b)
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .map(|(x, _)| (x - mean_ref).powi(2))
+        .sum();
+
+    Ok(SeriesComparison {
+        n_compared: n,
+        bias: sum_diff / n as f64,
+        rmse: (sum_sq_diff / n as f64).sqrt(),
+        nse: if ss_ref > 0.0 { 1.0 - sum_sq_diff / ss_ref } else { f64::NAN },
+        max_abs_diff,
+        first_divergence,
+        difference,
+    })
+}
+
+/// Default relative tolerance between results recorded in single precision and the same
+/// results in double precision. Storing a value in single precision changes it by at most
+/// about 6e-8 of its size, and errors can grow a little when the stored values are used
+/// again (e.g. `node.dam.volume[-1, 0]`).
+pub const SINGLE_PRECISION_TOLERANCE: f64 = 1e-5;
+
+/// Checks that `other` matches `reference` to within `tolerance` relative to the reference
+/// value, i.e. `|other - reference| <= tolerance * max(|reference|, 1)`, with missing values in
+/// the same places. The series must be the same length. Returns the first mismatch.
+pub fn check_relative_tolerance(reference: &[f64], other: &[f64], tolerance: f64) -> Result<(), String> {
+    if reference.len() != other.len() {
+        return Err(format!("Series have different lengths ({} and {})", reference.len(), other.len()));
+    }
+    for (i, (&x, &y)) in reference.iter().zip(other).enumerate() {
+        let matches = if x.is_nan() || y.is_nan() {
+            x.is_nan() && y.is_nan()
+        } else {
+            (y - x).abs() <= tolerance * x.abs().max(1.0)
+        };
+        if !matches {
+            return Err(format!("Values differ at step {}: {} and {} (tolerance {})", i, x, y, tolerance));
+        }
+    }
+    Ok(())
+}
+
+/// Reduces a series to at most `max_points` values by averaging consecutive blocks of equal
+/// length (ignoring NaNs). The result's step size is the block length times the original.
+pub fn downsample_mean(series: &Timeseries, max_points: usize) -> Timeseries {
+    let block = series.values.len().div_ceil(max_points.max(1)).max(1);
+    let mut result = Timeseries::new(series.step_size * block as u64);
+    result.name = series.name.clone();
+    result.start_timestamp = series.start_timestamp;
+    for chunk in series.values.chunks(block) {
+        let finite: Vec<f64> = chunk.iter().copied().filter(|v| v.is_finite()).collect();
+        result.push_value(match finite.len() {
+            0 => f64::NAN,
+            n => finite.iter().sum::<f64>() / n as f64,
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(start_step: u64, values: &[f64]) -> Timeseries {
+        let mut ts = Timeseries::new_daily();
+        ts.start_timestamp = start_step * 86400;
+        for &v in values {
+            ts.push_value(v);
+        }
+        ts
+    }
+
+    #[test]
+    fn test_identical_series() {
+        let a = series(0, &[1.0, 2.0, 3.0]);
+        let c = compare_series(&a, &a, 0.0).unwrap();
+        assert_eq!(c.n_compared, 3);
+        assert_eq!(c.bias, 0.0);
+        assert_eq!(c.rmse, 0.0);
+        assert_eq!(c.nse, 1.0);
+        assert_eq!(c.first_divergence, None);
+    }
+
+    #[test]
+    fn test_statistics_on_shared_timesteps() {
+        // other starts a day later and differs from the second shared step on
+        let reference = series(0, &[9.0, 1.0, 2.0, 3.0, 4.0]);
+        let other = series(1, &[1.0, 2.0, 5.0, 2.0, 7.0]);
+        let c = compare_series(&reference, &other, 1e-9).unwrap();
+        assert_eq!(c.n_compared, 4);
+        assert_eq!(c.difference.values, vec![0.0, 0.0, 2.0, -2.0]);
+        assert_eq!(c.difference.start_timestamp, 86400);
+        assert_eq!(c.bias, 0.0);
+        assert!((c.rmse - 2.0f64.sqrt()).abs() < 1e-12);
+        assert!((c.nse - (1.0 - 8.0 / 5.0)).abs() < 1e-12);
+        assert_eq!(c.max_abs_diff, 2.0);
+        assert_eq!(c.first_divergence, Some(3 * 86400));
+
+        // A larger tolerance ignores the differences
+        assert_eq!(compare_series(&reference, &other, 2.0).unwrap().first_divergence, None);
+    }
+
+    #[test]
+    fn test_missing_values() {
+        let reference = series(0, &[1.0, f64::NAN, f64::NAN]);
+        let other = series(0, &[1.0, f64::NAN, 3.0]);
+        let c = compare_series(&reference, &other, 0.0).unwrap();
+        assert_eq!(c.n_compared, 1);
+        assert_eq!(c.first_divergence, Some(2 * 86400));
+        assert!(c.nse.is_nan());
+    }
+
+    #[test]
+    fn test_disjoint_series_are_rejected() {
+        assert!(compare_series(&series(0, &[1.0]), &series(5, &[1.0]), 0.0).is_err());
+        let mut hourly = series(0, &[1.0]);
+        hourly.step_size = 3600;
+        assert!(compare_series(&series(0, &[1.0]), &hourly, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_relative_tolerance() {
+        let reference = [1e6, 0.5, f64::NAN, -20.0];
+        assert!(check_relative_tolerance(&reference, &[1e6 + 5.0, 0.5 + 5e-6, f64::NAN, -20.0], 1e-5).is_ok());
+        let err = check_relative_tolerance(&reference, &[1e6 + 20.0, 0.5, f64::NAN, -20.0], 1e-5).unwrap_err();
+        assert!(err.starts_with("Values differ at step 0"), "{}", err);
+        assert!(check_relative_tolerance(&reference, &[1e6, 0.5, 0.0, -20.0], 1e-5).is_err());
+        assert!(check_relative_tolerance(&reference, &[1e6], 1e-5).is_err());
+    }
+
+    #[test]
+    fn test_downsample_mean() {
+        let ts = series(0, &[1.0, 3.0, f64::NAN, 4.0, 5.0]);
+        let d = downsample_mean(&ts, 2);
+        assert_eq!(d.step_size, 3 * 86400);
+        assert_eq!(d.values, vec![2.0, 4.5]);
+        assert_eq!(downsample_mean(&ts, 10).values.len(), 5);
+    }
+}
@@ -0,0 +1,90 @@
+// About node dependencies
+// =========================================
+// A node input can use another node's result, e.g. `demand = 0.1 * node.gauge.dsflow`. Without
+// an offset this is the result from the same timestep, so the other node has to run first. Each
+// such reference is a dependency: an edge in the execution graph alongside the links.
+//
+// If a dependency closes a loop (e.g. a user upstream of the gauge it reads) there is no order
+// in which every node sees this timestep's results. The model reports the loop when it is
+// configured, or where the modeller allows it, reads the previous timestep's value instead.
+
+use std::collections::VecDeque;
+use rustc_hash::FxHashMap;
+use crate::data_management::data_cache::DataCache;
+use crate::nodes::{Link, NodeEnum};
+
+/// An input on `node` that reads a result of `on_node` from the same timestep
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub node: usize,
+    /// INI key of the input, e.g. "demand"
+    pub input: String,
+    pub series_idx: usize,
+    pub on_node: usize,
+}
+
+/// Finds the inputs that read other nodes' results from the same timestep. References to the
+/// node's own results, and to nodes that don't exist, are not dependencies.
+pub fn find_dependencies(nodes: &mut [NodeEnum], data_cache: &DataCache,
+                         node_lookup: &FxHashMap<String, usize>) -> Vec<Dependency> {
+    let mut dependencies = vec![];
+    for (node_idx, node) in nodes.iter_mut().enumerate() {
+        for (input, dynamic_input) in node.dynamic_inputs_mut() {
+            for series_idx in dynamic_input.current_step_references() {
+                let series_name = data_cache.series_name[series_idx].to_lowercase();
+                let on_node = series_name.strip_prefix("node.")
+                    .and_then(|path| path.split_once('.'))
+                    .and_then(|(node_name, _)| node_lookup.get(node_name).copied());
+                let is_new = |d: &Dependency| !(d.node == node_idx && d.input == input && d.series_idx == series_idx);
+                match on_node {
+                    Some(on_node) if on_node != node_idx && dependencies.iter().all(is_new) => {
+                        dependencies.push(Dependency { node: node_idx, input: input.clone(), series_idx, on_node });
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    dependencies
+}
+
+/// Finds a loop through the links and dependencies that includes at least one dependency.
+/// Returns the index of that dependency and the loop as node indices, starting and ending
+/// with the node that the dependency is on. Loops made only of links are left for the
+/// execution order to report.
+pub fn find_cycle(n_nodes: usize, links: &[Link], dependencies: &[Dependency]) -> Option<(usize, Vec<usize>)> {
+    let mut downstream: Vec<Vec<usize>> = vec![Vec::new(); n_nodes];
+    for link in links {
+        downstream[link.from_node].push(link.to_node);
+    }
+    for d in dependencies {
+        downstream[d.on_node].push(d.node);
+    }
+
+    // A dependency is on a loop if its node leads back to the node it depends on
+    for (i, d) in dependencies.iter().enumerate() {
+        let mut previous: Vec<Option<usize>> = vec![None; n_nodes];
+        let mut queue = VecDeque::from([d.node]);
+        previous[d.node] = Some(d.node);
+        while let Some(idx) = queue.pop_front() {
+            if idx == d.on_node {
+                let mut path = vec![d.on_node];
+                let mut current = d.on_node;
+                while current != d.node {
+                    current = previous[current].unwrap();
+                    path.push(current);
+                }
+                path.push(d.on_node);
+                path.reverse();
+                return Some((i, path));
+            }
+            for &to in &downstream[idx] {
+                if previous[to].is_none() {
+                    previous[to] = Some(idx);
+                    queue.push_back(to);
+                }
+            }
+        }
+    }
+    None
+}
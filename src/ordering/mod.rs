@@ -1,3 +1,4 @@
-
+pub mod dependency_graph;
+pub mod execution_order;
+pub mod shortfall_sharing;
 pub mod simple_nodewise_ordering;
-
@@ -0,0 +1,166 @@
+// About shortfall sharing
+// =========================================
+// A supply storage releases what the users in its regulated zone order. When it cannot release
+// it all, the users nearest the storage would otherwise divert their full orders and leave the
+// shortfall to those further down. A storage with a `shortfall_sharing` rule instead shares what
+// it can release between the regulated users in its zone when their orders are placed: each
+// user's order is cut back to its share, so it only diverts that share when the water arrives.
+//
+// What a storage can release is estimated at the start of the ordering phase as its volume
+// above the outlet's minimum operating volume. Orders reach the storage with losses and other
+// orders added, so users share the same fraction of their total orders as the storage can
+// release of everything ordered from it.
+
+use crate::data_management::data_cache::DataCache;
+use crate::nodes::NodeEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SharingRule {
+    /// Users divert as the water passes them, so upstream users are served first
+    #[default]
+    FirstComeFirstServed,
+    /// Users are served in order of `priority` (1 first). Users with equal priority share
+    /// pro-rata by order.
+    Priority,
+    /// Every user gets the same fraction of its order
+    ProRataOrder,
+    /// Supply is shared in proportion to each user's `entitlement`, and no user gets more than
+    /// it ordered. Users without an entitlement share what is left, pro-rata by order.
+    ProRataEntitlement,
+}
+
+impl SharingRule {
+    pub const ALL: [SharingRule; 4] = [
+        SharingRule::FirstComeFirstServed,
+        SharingRule::Priority,
+        SharingRule::ProRataOrder,
+        SharingRule::ProRataEntitlement,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SharingRule::FirstComeFirstServed => "first_come_first_served",
+            SharingRule::Priority => "priority",
+            SharingRule::ProRataOrder => "pro_rata_order",
+            SharingRule::ProRataEntitlement => "pro_rata_entitlement",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SharingRule> {
+        SharingRule::ALL.into_iter().find(|r| r.as_str() == name.trim().to_lowercase())
+    }
+}
+
+/// Shares `supply` between users with the given orders. Allocations never exceed orders,
+/// and add up to the smaller of `supply` and the total order.
+pub fn allocate(rule: SharingRule, supply: f64, orders: &[f64], priorities: &[u32], entitlements: &[f64]) -> Vec<f64> {
+    let total: f64 = orders.iter().sum();
+    if supply >= total {
+        return orders.to_vec();
+    }
+    let supply = supply.max(0.0);
+    match rule {
+        SharingRule::FirstComeFirstServed | SharingRule::ProRataOrder => {
+            orders.iter().map(|o| o * supply / total).collect()
+        }
+        SharingRule::Priority => {
+            let mut levels: Vec<u32> = priorities.to_vec();
+            levels.sort_unstable();
+            levels.dedup();
+            let mut allocations = vec![0.0; orders.len()];
+            let mut remaining = supply;
+            for level in levels {
+                let members: Vec<usize> = (0..orders.len()).filter(|&i| priorities[i] == level).collect();
+                let level_order: f64 = members.iter().map(|&i| orders[i]).sum();
+                let fraction = if level_order > remaining { remaining / level_order } else { 1.0 };
+                for &i in &members {
+                    allocations[i] = orders[i] * fraction;
+                }
+                remaining = (remaining - level_order).max(0.0);
+            }
+            allocations
+        }
+        SharingRule::ProRataEntitlement => {
+            // Share by entitlement, capping users at their orders and passing their surplus on
+            // to the others until nothing is left over
+            let mut allocations = vec![0.0; orders.len()];
+            let mut remaining = supply;
+            let mut unmet: Vec<usize> = (0..orders.len()).filter(|&i| entitlements[i] > 0.0 && orders[i] > 0.0).collect();
+            while remaining > 0.0 && !unmet.is_empty() {
+                let total_entitlement: f64 = unmet.iter().map(|&i| entitlements[i]).sum();
+                let share = remaining / total_entitlement;
+                let capped: Vec<usize> = unmet.iter().copied()
+                    .filter(|&i| allocations[i] + entitlements[i] * share >= orders[i])
+                    .collect();
+                if capped.is_empty() {
+                    for &i in &unmet {
+                        allocations[i] += entitlements[i] * share;
+                    }
+                    remaining = 0.0;
+                } else {
+                    for &i in &capped {
+                        remaining -= orders[i] - allocations[i];
+                        allocations[i] = orders[i];
+                    }
+                    unmet.retain(|i| !capped.contains(i));
+                }
+            }
+
+            // Anything left goes to users without an entitlement
+            let rest: Vec<usize> = (0..orders.len()).filter(|&i| entitlements[i] <= 0.0).collect();
+            let rest_order: f64 = rest.iter().map(|&i| orders[i]).sum();
+            if remaining > 0.0 && rest_order > 0.0 {
+                let fraction = (remaining / rest_order).min(1.0);
+                for &i in &rest {
+                    allocations[i] = orders[i] * fraction;
+                }
+            }
+            allocations
+        }
+    }
+}
+
+/// A supply storage outlet with a sharing rule, and the regulated users in its zone
+#[derive(Clone, Debug)]
+pub(crate) struct SharingZone {
+    pub storage_idx: usize,
+    pub outlet: usize,
+    pub users: Vec<usize>,
+}
+
+impl SharingZone {
+    /// Cuts back today's user orders if the storage cannot release everything ordered from
+    /// it. Runs after every node's order phase.
+    pub fn share(&self, nodes: &mut [NodeEnum], data_cache: &mut DataCache) {
+        let (rule, requested, available) = match &nodes[self.storage_idx] {
+            NodeEnum::StorageNode(s) => (s.shortfall_sharing, s.ds_orders_due[self.outlet], s.available_release(self.outlet)),
+            _ => return,
+        };
+        if requested <= available {
+            return;
+        }
+
+        let mut orders = Vec::with_capacity(self.users.len());
+        let mut priorities = Vec::with_capacity(self.users.len());
+        let mut entitlements = Vec::with_capacity(self.users.len());
+        for &user_idx in &self.users {
+            if let NodeEnum::RegulatedUserNode(u) = &nodes[user_idx] {
+                orders.push(u.order_value.max(0.0));
+                priorities.push(u.priority);
+                entitlements.push(u.entitlement);
+            }
+        }
+        let total: f64 = orders.iter().sum();
+        if total <= 0.0 {
+            return;
+        }
+
+        let supply = total * available / requested;
+        let allocations = allocate(rule, supply, &orders, &priorities, &entitlements);
+        for (&user_idx, allocation) in self.users.iter().zip(allocations) {
+            if let NodeEnum::RegulatedUserNode(u) = &mut nodes[user_idx] {
+                u.restrict_order(allocation, data_cache);
+            }
+        }
+    }
+}
@@ -1,10 +1,11 @@
 // About the ordering system
 // =========================================
-// This system iterates over nodes in reverse definition order (highest index to lowest). Since
-// check_execution_order() enforces from_node < to_node for all links, reverse node order guarantees
+// This system iterates over nodes in reverse execution order. Since the execution order is a
+// topological sort of the link graph (see execution_order.rs), reverse execution order guarantees
 // that downstream nodes are always processed before upstream nodes.
 //
-// The initialize() method - zone propagation and lag computation depend on forward link iteration.
+// The initialize() method - zone propagation and lag computation depend on iterating links in
+// execution order of their upstream node.
 //
 // The run_ordering_phase() - iterates nodes.
 // - Only regulated nodes are visited (pre-filtered during initialize)
@@ -14,6 +15,7 @@ use crate::data_management::data_cache::DataCache;
 use crate::misc::simulation_context::set_context_node;
 use crate::nodes::{Link, Node, NodeEnum};
 use crate::numerical::fifo_buffer::FifoBuffer;
+use crate::ordering::shortfall_sharing::{SharingRule, SharingZone};
 
 /// Pre-computed information about an incoming regulated link to a node.
 #[derive(Clone, Default, Debug)]
@@ -38,9 +40,12 @@ pub struct SimpleNodewiseOrderingSystem {
     /// Flat contiguous storage for all incoming regulated links, grouped by node.
     flat_incoming_links: Vec<IncomingRegulatedLink>,
 
-    /// One entry per regulated node (in reverse definition order), pointing into flat_incoming_links.
+    /// One entry per regulated node (in reverse execution order), pointing into flat_incoming_links.
     regulated_nodes: Vec<RegulatedNodeEntry>,
 
+    /// Supply storage outlets that share shortfalls between their users
+    sharing_zones: Vec<SharingZone>,
+
     regulated_zone_counter: usize,
     model_has_ordering: bool,
 }
@@ -51,6 +56,7 @@ impl SimpleNodewiseOrderingSystem {
             links_simple_ordering: Vec::new(),
             flat_incoming_links: Vec::new(),
             regulated_nodes: Vec::new(),
+            sharing_zones: Vec::new(),
             regulated_zone_counter: 0,
             model_has_ordering: false,
         }
@@ -59,21 +65,35 @@ impl SimpleNodewiseOrderingSystem {
     pub fn initialize(&mut self,
                       nodes: &mut Vec<NodeEnum>,
                       links: &Vec<Link>,
-                      incoming_links: &Vec<Vec<usize>>) -> () {
+                      incoming_links: &[Vec<usize>],
+                      execution_order: &[usize]) {
         // 'nodes' is a borrowed vector of all nodes (as NodeEnums) in definition order
         // 'links' is a borrowed vector of all links, where a link has from_node, from_outlet,
         //         to_node, to_inlet.
         // 'incoming_links' is a derived adjacency list where
         //         incoming_links[node_idx] = vec of indices for link coming into node idx. This
         //         is handy for navigating up the network.
+        // 'execution_order' is the topologically sorted node order resolved by the model.
 
         // Start clean
-        self.links_simple_ordering.clear();
+        self.links_simple_ordering = vec![LinkInfo::default(); links.len()];
         self.regulated_zone_counter = 0;
+        self.sharing_zones.clear();
+        let mut zone_sharing: Vec<Option<usize>> = Vec::new(); // zone -> index into sharing_zones
+
+        // Visit links in execution order of their upstream node, so that every link into a node
+        // has been processed before any link out of it. The sort is stable, so links from the
+        // same node keep their definition order.
+        let mut rank = vec![0usize; nodes.len()];
+        for (position, &node_idx) in execution_order.iter().enumerate() {
+            rank[node_idx] = position;
+        }
+        let mut link_order: Vec<usize> = (0..links.len()).collect();
+        link_order.sort_by_key(|&idx| rank[links[idx].from_node]);
 
         // Phase 1: Build the links_simple_ordering vector and initialize nodes.
         // This is identical to SimpleOrderingSystem::initialize().
-        for idx in 0..links.len() {
+        for &idx in &link_order {
 
             // Create a new link info item
             let mut new_link_item = LinkInfo {
@@ -96,6 +116,20 @@ impl SimpleNodewiseOrderingSystem {
                 // This is a new zone.
                 new_link_item.zone_idx = Some(self.regulated_zone_counter);
                 self.regulated_zone_counter += 1;
+
+                // Remember the supply if it shares shortfalls
+                let shares = match &nodes[new_link_item.from_node] {
+                    NodeEnum::StorageNode(n) => n.shortfall_sharing != SharingRule::FirstComeFirstServed,
+                    _ => false,
+                };
+                zone_sharing.push(shares.then_some(self.sharing_zones.len()));
+                if shares {
+                    self.sharing_zones.push(SharingZone {
+                        storage_idx: new_link_item.from_node,
+                        outlet: new_link_item.from_outlet as usize,
+                        users: Vec::new(),
+                    });
+                }
             } else {
                 // Zone info based on upstream link.
                 // If the upstream node has multiple incoming links, we look at the one with the longest lag.
@@ -154,6 +188,12 @@ impl SimpleNodewiseOrderingSystem {
                         }
                     },
                     NodeEnum::RegulatedUserNode(node) => {
+                        if let Some(sharing_idx) = new_link_item.zone_idx.and_then(|z| zone_sharing[z]) {
+                            let users = &mut self.sharing_zones[sharing_idx].users;
+                            if !users.contains(&new_link_item.to_node) {
+                                users.push(new_link_item.to_node);
+                            }
+                        }
                         let int_lag = new_link_item.lag.round() as usize;
                         if int_lag > node.order_travel_time {
                             // TODO: why do I have the above clause? I cant remember? If you remember, make a note.
@@ -189,8 +229,8 @@ impl SimpleNodewiseOrderingSystem {
                 }
             }
 
-            // Add the new_link_item to the vec
-            self.links_simple_ordering.push(new_link_item);
+            // Store the new_link_item against its link index
+            self.links_simple_ordering[idx] = new_link_item;
         }
 
         // Phase 2: Determine which regulated nodes actually need to be visited.
@@ -200,16 +240,12 @@ impl SimpleNodewiseOrderingSystem {
         // will only ever see zero dsorders, so visiting them is wasted work.
         let mut needed = vec![false; nodes.len()];
         for (i, node) in nodes.iter().enumerate() {
-            match node {
-                NodeEnum::StorageNode(_) |
-                NodeEnum::RegulatedUserNode(_) |
-                NodeEnum::OrderControlNode(_) => needed[i] = true,
-                _ => {}
-            }
+            needed[i] = node.requires_order_phase();
         }
         // Propagate backward through regulated links: if to_node is needed, from_node is too.
-        // Reverse iteration ensures transitivity (links are ordered with from_node < to_node).
-        for li in self.links_simple_ordering.iter().rev() {
+        // Reverse execution order ensures transitivity.
+        for &idx in link_order.iter().rev() {
+            let li = &self.links_simple_ordering[idx];
             if li.zone_idx.is_some() && needed[li.to_node] {
                 needed[li.from_node] = true;
             }
@@ -230,7 +266,7 @@ impl SimpleNodewiseOrderingSystem {
 
         self.flat_incoming_links.clear();
         self.regulated_nodes.clear();
-        for node_idx in (0..nodes.len()).rev() {
+        for &node_idx in execution_order.iter().rev() {
             if per_node_links[node_idx].is_empty() {
                 continue;
             }
@@ -267,22 +303,27 @@ impl SimpleNodewiseOrderingSystem {
         self.model_has_ordering = self.regulated_zone_counter > 0;
     }
 
+    /// The nodes that pass orders upstream in the ordering phase
+    pub fn ordered_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.regulated_nodes.iter().map(|entry| entry.node_idx)
+    }
+
     /// This function is to be run each day, before the flow phase, and it's job is to resolve
     /// orders and set today's intended operations (property values) in the nodes. The nodes can
     /// then follow these intended operations during the flow phase without further intervention
     /// from this struct during the flow phase.
     ///
     /// Unlike simple_ordering.rs which iterates links in reverse, this method iterates only
-    /// regulated nodes in reverse definition order, with incoming links stored in a flat
+    /// regulated nodes in reverse execution order, with incoming links stored in a flat
     /// contiguous vec for cache locality.
-    pub fn run_ordering_phase(&mut self, nodes: &mut Vec<NodeEnum>, data_cache: &mut DataCache) {
+    pub fn run_ordering_phase(&mut self, nodes: &mut [NodeEnum], links: &[Link], data_cache: &mut DataCache) {
 
         // Guard to save computation time if there is no ordering!
         if !self.model_has_ordering {
             return;
         }
 
-        // Iterate only regulated nodes (already in reverse definition order)
+        // Iterate only regulated nodes (already in reverse execution order)
         for entry in &self.regulated_nodes {
             let node_idx = entry.node_idx;
             let incoming = &self.flat_incoming_links[entry.links_start..entry.links_end];
@@ -357,7 +398,15 @@ impl SimpleNodewiseOrderingSystem {
                     node.run_order_phase(data_cache);
                     // Propagate orders upstream
                     for il in incoming {
-                        upstream_orders[n_orders] = (il.from_node, il.from_outlet, node.dsorders.iter().sum());
+                        upstream_orders[n_orders] = (il.from_node, il.from_outlet, node.usorders);
+                        n_orders += 1;
+                    }
+                }
+                NodeEnum::TransferNode(node) => {
+                    node.run_order_phase(data_cache);
+                    // Propagate orders upstream
+                    for il in incoming {
+                        upstream_orders[n_orders] = (il.from_node, il.from_outlet, node.usorders);
                         n_orders += 1;
                     }
                 }
@@ -381,7 +430,7 @@ impl SimpleNodewiseOrderingSystem {
                     node.run_order_phase(data_cache);
                     // Propagate orders upstream.
                     for il in incoming {
-                        upstream_orders[n_orders] = (il.from_node, il.from_outlet, node.dsorders[0]);
+                        upstream_orders[n_orders] = (il.from_node, il.from_outlet, node.usorders);
                         n_orders += 1;
                     }
                 }
@@ -403,9 +452,10 @@ impl SimpleNodewiseOrderingSystem {
                 }
                 NodeEnum::RoutingNode(node) => {
                     node.run_order_phase(data_cache);
-                    // Propagate orders upstream.
+                    // Propagate orders upstream. With a distributary split, the orders on both
+                    // outlets are summed, as for a splitter.
                     for il in incoming {
-                        upstream_orders[n_orders] = (il.from_node, il.from_outlet, node.dsorders[0]);
+                        upstream_orders[n_orders] = (il.from_node, il.from_outlet, node.dsorders[0] + node.dsorders[1]);
                         n_orders += 1;
                     }
                 }
@@ -419,12 +469,18 @@ impl SimpleNodewiseOrderingSystem {
                 }
             }
 
-            // Propagate computed orders to upstream nodes
+            // Propagate computed orders to upstream nodes, allowing for the links' losses and
+            // capacities. There is one order per incoming link, in the same order.
             for i in 0..n_orders {
                 let (from_node, from_outlet, order) = upstream_orders[i];
-                nodes[from_node].dsorders_mut()[from_outlet as usize] = order;
+                nodes[from_node].dsorders_mut()[from_outlet as usize] = links[incoming[i].link_idx].order_at_head(order);
             }
         }
+
+        // Now that the supply storages know what was ordered, share any shortfalls
+        for zone in &self.sharing_zones {
+            zone.share(nodes, data_cache);
+        }
     }
 }
 
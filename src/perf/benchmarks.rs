@@ -62,3 +62,140 @@ pub fn bench1() {
     // ------------ CONCLUSION ---------
     // Rust is faster, but not much faster.
 }
+
+/// A synthetic network of `n_reaches` reaches joining one after another, each an inflow, a
+/// routing reach, a loss, a gauge and the confluence with the reaches above it: 5 nodes per
+/// reach, with a daily period of `n_years`.
+pub fn network_model_ini(n_reaches: usize, n_years: usize) -> String {
+    let mut ini = format!("[kalix]\nstart = 1950-01-01\nend = {}-12-31\n\n", 1949 + n_years);
+    for i in 0..n_reaches {
+        let y = 10.0 * i as f64;
+        ini.push_str(&format!("\
+[node.in_{i}]\ntype = inflow\nloc = 0, {y}\ninflow = {a} + {a} * sin(sim.day * 0.0172 + {i})\nds_1 = rt_{i}\n\n\
+[node.rt_{i}]\ntype = routing\nloc = 1, {y}\nn_divs = 2\npwl = 0, 1, 1000, 1\nds_1 = ls_{i}\n\n\
+[node.ls_{i}]\ntype = loss\nloc = 2, {y}\ntable = 0, 0, 100, 10, 1000, 50\nds_1 = gg_{i}\n\n\
+[node.gg_{i}]\ntype = gauge\nloc = 3, {y}\nds_1 = cf_{i}\n\n\
+[node.cf_{i}]\ntype = confluence\nloc = 4, {y}\n", a = 10 + i % 7));
+        if i + 1 < n_reaches {
+            ini.push_str(&format!("ds_1 = cf_{}\n", i + 1));
+        }
+        ini.push('\n');
+    }
+    ini.push_str(&format!("[outputs]\nnode.cf_{}.dsflow\n", n_reaches - 1));
+    ini
+}
+
+/// Runs the 500-node network benchmark, reporting the fastest of `n_runs` runs. See
+/// benchmarks/002_node_network for what it measures.
+pub fn bench_network(n_runs: usize) -> std::time::Duration {
+    let ini = network_model_ini(100, 50);
+    let mut model = crate::io::ini_model_io::IniModelIO::new().read_model_string(&ini).expect("Error");
+    model.configure().expect("Error");
+    let mut fastest = std::time::Duration::MAX;
+    for _ in 0..n_runs {
+        let started = std::time::Instant::now();
+        model.configure().expect("Error");
+        model.run().expect("Error");
+        fastest = fastest.min(started.elapsed());
+    }
+    println!("500-node network, 50 years daily: {:?} (fastest of {} runs)", fastest, n_runs);
+    fastest
+}
+
+/// A synthetic chain of `n_sacramento` Sacramento catchments then `n_gr4j` GR4J catchments,
+/// each taking the flow of the one above, with a daily period of `n_years`. The Sacramento
+/// lag (3.5 days) and GR4J x4 give unit hydrographs of several ordinates.
+pub fn catchment_model_ini(n_sacramento: usize, n_gr4j: usize, n_years: usize) -> String {
+    let n = n_sacramento + n_gr4j;
+    let mut ini = format!("[kalix]\nstart = 1950-01-01\nend = {}-12-31\n\n", 1949 + n_years);
+    for i in 0..n {
+        let (kind, params) = if i < n_sacramento {
+            ("sacramento", "0.01, 40.0, 23.0, 0.009, 0.043, 130.0, 0.01, 0.063, 1.0, 0.01, 0.0, 0.0, 40.0, 0.245, 50.0, 40.0, 3.5")
+        } else {
+            ("gr4j", "350.0, 0.0, 90.0, 3.7")
+        };
+        ini.push_str(&format!("[node.c_{i}]\ntype = {kind}\nloc = 0, {y}\narea = 80\n\
+rain = 3 + 3 * sin(sim.day * 0.3 + {i})\nevap = 4\nparams = {params}\n", y = 10 * i));
+        if i + 1 < n {
+            ini.push_str(&format!("ds_1 = c_{}\n", i + 1));
+        }
+        ini.push('\n');
+    }
+    ini.push_str(&format!("[outputs]\nnode.c_{}.dsflow\n", n - 1));
+    ini
+}
+
+/// Runs the catchment benchmark (200 Sacramento and 100 GR4J nodes), reporting the fastest
+/// of `n_runs` runs.
+pub fn bench_catchments(n_runs: usize) -> std::time::Duration {
+    let ini = catchment_model_ini(200, 100, 50);
+    let mut model = crate::io::ini_model_io::IniModelIO::new().read_model_string(&ini).expect("Error");
+    model.configure().expect("Error");
+    let mut fastest = std::time::Duration::MAX;
+    for _ in 0..n_runs {
+        let started = std::time::Instant::now();
+        model.configure().expect("Error");
+        model.run().expect("Error");
+        fastest = fastest.min(started.elapsed());
+    }
+    println!("300 catchments, 50 years daily: {:?} (fastest of {} runs)", fastest, n_runs);
+    fastest
+}
+
+/// Times the unit hydrograph kernels alone: `n_steps` steps of an 8-ordinate UHPrealloc32
+/// and of GR4J-length (4 and 8 ordinate) UHDyns.
+pub fn bench_unit_hydrographs(n_steps: usize) -> std::time::Duration {
+    use crate::hydrology::routing::unit_hydrograph::{uh_dyn::UHDyn, uh_prealloc_32::UHPrealloc32};
+    let mut fixed = UHPrealloc32::new(8);
+    for i in 0..8 {
+        fixed.set_kernel(i, 0.125);
+    }
+    let mut short = UHDyn::from_kernel(vec![0.25; 4]);
+    let mut long = UHDyn::from_kernel(vec![0.125; 8]);
+    let started = std::time::Instant::now();
+    let mut total = 0.0;
+    for step in 0..n_steps {
+        let input = (step % 17) as f64;
+        total += fixed.run_step(input) + short.run_step(input) + long.run_step(input);
+    }
+    let elapsed = started.elapsed();
+    println!("Unit hydrographs, {} steps: {:?} (total flow {})", n_steps, elapsed, total);
+    elapsed
+}
+
+/// Times cloning a configured model, as optimisers do for each parallel worker: 20 inflow
+/// nodes each reading its own input of 100 years of daily data. Reports the mean of
+/// `n_clones` clones.
+pub fn bench_model_clone(n_clones: usize) -> std::time::Duration {
+    let n_inputs = 20;
+    let mut ini = "[kalix]\nstart = 1920-01-01\nend = 2019-12-31\n\n".to_string();
+    for i in 0..n_inputs {
+        ini.push_str(&format!("[node.in_{i}]\ntype = inflow\nloc = 0, {}\ninflow = data.clim_csv.by_name.c{i}\n", 10 * i));
+        if i + 1 < n_inputs {
+            ini.push_str(&format!("ds_1 = in_{}\n", i + 1));
+        }
+        ini.push('\n');
+    }
+    ini.push_str(&format!("[outputs]\nnode.in_{}.dsflow\n", n_inputs - 1));
+
+    let header: Vec<String> = (0..n_inputs).map(|i| format!("c{}", i)).collect();
+    let mut csv = format!("Date,{}\n", header.join(","));
+    let start = chrono::NaiveDate::from_ymd_opt(1920, 1, 1).unwrap();
+    for day in 0..36525u64 {
+        let date = start + chrono::Days::new(day);
+        let row: Vec<String> = (0..n_inputs).map(|i| format!("{}", (day as usize * 7 + i) % 13)).collect();
+        csv.push_str(&format!("{},{}\n", date, row.join(",")));
+    }
+
+    let mut model = crate::io::ini_model_io::IniModelIO::new().read_model_string(&ini).expect("Error");
+    model.load_input_csv_str("clim.csv", &csv, None).expect("Error");
+    model.configure().expect("Error");
+    model.run().expect("Error");
+    let started = std::time::Instant::now();
+    for _ in 0..n_clones {
+        std::hint::black_box(model.clone());
+    }
+    let mean = started.elapsed() / n_clones as u32;
+    println!("Model clone, 20 inputs of 100 years daily: {:?} (mean of {} clones)", mean, n_clones);
+    mean
+}
@@ -1 +1,2 @@
 pub mod benchmarks;
+pub mod profiler;
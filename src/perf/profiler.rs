@@ -0,0 +1,118 @@
+//! Per-node profiles of a run, for finding the nodes that make a model slow
+//!
+//! A run with a [`NodeTimer`] attached (`Model::node_timer`) leaves a [`Profile`] in
+//! `Model::last_profile`: each node's flow-phase time over the whole run, ranked slowest
+//! first. It can be printed as a table, or written as JSON in the nested `{name, value,
+//! children}` form that flame graph viewers (e.g. d3-flame-graph) read, grouped by node type.
+//! The time the run spent outside the nodes (functions, ordering, recording) is shown as
+//! `other`.
+
+use std::cmp::Reverse;
+use std::time::Duration;
+use crate::misc::node_timing::NodeTimer;
+use crate::model::Model;
+use crate::nodes::Node;
+
+/// One node's share of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeProfile {
+    pub name: String,
+    pub node_type: String,
+    /// Flow-phase time over the run
+    pub time: Duration,
+    /// Timesteps the node ran
+    pub calls: u64,
+}
+
+impl NodeProfile {
+    /// Mean flow-phase time per timestep, in microseconds
+    pub fn micros_per_step(&self) -> f64 {
+        if self.calls == 0 { 0.0 } else { self.time.as_secs_f64() * 1e6 / self.calls as f64 }
+    }
+}
+
+/// Where the time of a run went, node by node
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// Time of the whole run, including what is spent outside the nodes
+    pub run_time: Duration,
+    /// The nodes, slowest first
+    pub nodes: Vec<NodeProfile>,
+}
+
+impl Profile {
+    /// The profile of a model's run from the times its timer added up
+    pub fn new(model: &Model, timer: &NodeTimer, run_time: Duration) -> Profile {
+        let mut nodes: Vec<NodeProfile> = model.nodes.iter().enumerate()
+            .map(|(i, node)| NodeProfile {
+                name: node.get_name().to_string(),
+                node_type: node.get_type_as_string(),
+                time: timer.get(i),
+                calls: timer.calls(i),
+            })
+            .collect();
+        nodes.sort_by_key(|n| Reverse(n.time));
+        Profile { run_time, nodes }
+    }
+
+    /// Time over all nodes
+    pub fn node_time(&self) -> Duration {
+        self.nodes.iter().map(|n| n.time).sum()
+    }
+
+    /// A node's time as a percentage of the time over all nodes
+    pub fn share(&self, node: &NodeProfile) -> f64 {
+        let total = self.node_time().as_secs_f64();
+        if total > 0.0 { 100.0 * node.time.as_secs_f64() / total } else { 0.0 }
+    }
+
+    /// The ranked table of the `max_nodes` slowest nodes (all of them if None), with the time
+    /// over all nodes below it
+    pub fn to_table(&self, max_nodes: Option<usize>) -> String {
+        let name_width = self.nodes.iter().map(|n| n.name.len()).max().unwrap_or(0).max(4);
+        let type_width = self.nodes.iter().map(|n| n.node_type.len()).max().unwrap_or(0).max(4);
+        let mut table = format!("{:>4}  {:<name_width$}  {:<type_width$}  {:>12}  {:>10}  {:>6}\n",
+                                "Rank", "Node", "Type", "Time (ms)", "us/step", "Share");
+        for (rank, node) in self.nodes.iter().take(max_nodes.unwrap_or(usize::MAX)).enumerate() {
+            table.push_str(&format!("{:>4}  {:<name_width$}  {:<type_width$}  {:>12.3}  {:>10.3}  {:>5.1}%\n",
+                                    rank + 1, node.name, node.node_type, node.time.as_secs_f64() * 1000.0,
+                                    node.micros_per_step(), self.share(node)));
+        }
+        let run_ms = self.run_time.as_secs_f64() * 1000.0;
+        let node_ms = self.node_time().as_secs_f64() * 1000.0;
+        let percent = if run_ms > 0.0 { 100.0 * node_ms / run_ms } else { 0.0 };
+        table.push_str(&format!("Nodes took {:.3} ms of the {:.3} ms run ({:.1}%)\n", node_ms, run_ms, percent));
+        table
+    }
+
+    /// The profile as a flame graph: the run, then node types, then nodes, with values in
+    /// microseconds
+    pub fn to_flame_graph(&self) -> serde_json::Value {
+        let micros = |d: Duration| d.as_micros() as u64;
+        let mut types: Vec<(&str, Vec<&NodeProfile>)> = Vec::new();
+        for node in &self.nodes {
+            match types.iter_mut().find(|(t, _)| *t == node.node_type) {
+                Some((_, nodes)) => nodes.push(node),
+                None => types.push((&node.node_type, vec![node])),
+            }
+        }
+        let mut children: Vec<serde_json::Value> = types.iter()
+            .map(|(node_type, nodes)| serde_json::json!({
+                "name": node_type,
+                "value": nodes.iter().map(|n| micros(n.time)).sum::<u64>(),
+                "children": nodes.iter()
+                    .map(|n| serde_json::json!({"name": n.name, "value": micros(n.time)}))
+                    .collect::<Vec<_>>(),
+            }))
+            .collect();
+        children.push(serde_json::json!({
+            "name": "other",
+            "value": micros(self.run_time.saturating_sub(self.node_time())),
+        }));
+        serde_json::json!({
+            "name": "run",
+            "value": micros(self.run_time.max(self.node_time())),
+            "children": children,
+        })
+    }
+}
@@ -26,6 +26,11 @@ pub struct OptimisationOutcome {
     pub parameters: Vec<(String, f64)>,
     /// The optimised model serialised to an INI string (lossless round-trip).
     pub optimised_model_ini: String,
+    /// Best parameter set re-evaluated over the config's `calibration_period`.
+    /// `None` unless the config sets a calibration or validation period.
+    pub calibration_metrics: Option<crate::numerical::opt::PeriodMetrics>,
+    /// Best parameter set re-evaluated over the config's `validation_period`, if set.
+    pub validation_metrics: Option<crate::numerical::opt::PeriodMetrics>,
 }
 
 /// Load a model from an INI file, run it, and write optional outputs.
@@ -51,6 +56,33 @@ pub fn simulate_from_file(
     Ok(())
 }
 
+/// Load a model from an INI file, run it, and write an HTML report of the run.
+///
+/// The core of the `kalix report` CLI subcommand. With a `calibration_config`, the model is
+/// calibrated first (as by [`calibrate_model`]), and the report lists the calibrated
+/// parameters. The report is titled with the model file's name.
+pub fn report_from_file(
+    model_path: &str,
+    report_path: &str,
+    calibration_config: Option<&str>,
+) -> Result<(), String> {
+    let mut m = IniModelIO::new().read_model_file(model_path)?;
+    m.record_gauge_series();
+    let parameters = match calibration_config {
+        Some(config_path) => {
+            let config = crate::numerical::opt::OptimisationConfig::from_file(config_path)?;
+            Some(calibrate_model(&mut m, &config, None)?.parameters)
+        }
+        None => None,
+    };
+    m.configure()?;
+    m.run()?;
+    let title = std::path::Path::new(model_path).file_name()
+        .map_or(model_path.to_string(), |name| name.to_string_lossy().to_string());
+    std::fs::write(report_path, m.html_report(&title, parameters.as_deref()))
+        .map_err(|e| format!("Failed to write report to '{}': {}", report_path, e))
+}
+
 /// Load an optimisation config, run the calibration, and return the outcome.
 ///
 /// The non-interactive core of the `kalix optimise` CLI subcommand: the same
@@ -78,10 +110,7 @@ pub fn optimise_from_file(
     save_model_path: Option<&str>,
     progress_callback: Option<Box<dyn Fn(&crate::numerical::opt::optimizer_trait::OptimizationProgress) + Send + Sync>>,
 ) -> Result<OptimisationOutcome, String> {
-    use crate::numerical::opt::{OptimisationConfig, OptimisationProblem, Optimisable, create_optimizer_with_callback};
-    use crate::numerical::opt::optimisation::ComparisonPair;
-    use crate::io::optimisation_config_io::load_observed_for_term;
-    use crate::functions::parse_function;
+    use crate::numerical::opt::OptimisationConfig;
 
     // Load optimisation configuration.
     let config = OptimisationConfig::from_file(config_path)?;
@@ -95,50 +124,12 @@ pub fn optimise_from_file(
         })?,
     };
 
-    let model = IniModelIO::new().read_model_file(model_file_path)?;
-
-    // Build comparison pairs from terms (load each observed series).
-    let mut comparisons: Vec<ComparisonPair> = Vec::with_capacity(config.terms.len());
-    for term in &config.terms {
-        let observed = load_observed_for_term(&term.observed_file, &term.observed_series)
-            .map_err(|e| format!("Failed to load observed data for term '{}': {}", term.name, e))?;
-        comparisons.push(ComparisonPair {
-            name: term.name.clone(),
-            observed: observed.timeseries,
-            simulated_series_name: term.simulated_series.clone(),
-            statistic: term.statistic.clone(),
-        });
-    }
-
-    // Parse the composite objective expression.
-    let expression = parse_function(&config.objective_expression).map_err(|e| {
-        format!("Failed to parse objective_expression '{}': {}", config.objective_expression, e)
-    })?;
-
-    let mut problem = OptimisationProblem::new(
-        model,
-        config.parameter_config.clone(),
-        comparisons,
-        expression,
-    );
-
-    // Run the optimisation, wiring up the caller's progress callback (if any).
-    let optimiser = create_optimizer_with_callback(&config, progress_callback)
-        .map_err(|e| e.to_string())?;
-    let result = optimiser.optimize(&mut problem, None);
-
-    // Physical parameter values for the best genes.
-    let parameters = problem.config.evaluate(&result.best_params);
-
-    // Apply best parameters so the model is in its optimal state before saving.
-    problem.set_params(&result.best_params)
-        .map_err(|e| format!("Failed to apply best parameters: {}", e))?;
-
-    let optimised_model_ini = IniModelIO::new().model_to_string(&problem.model);
+    let mut model = IniModelIO::new().read_model_file(model_file_path)?;
+    let outcome = calibrate_model(&mut model, &config, progress_callback)?;
 
     // Optionally write the optimised model to disk.
     if let Some(path) = save_model_path {
-        std::fs::write(path, &optimised_model_ini)
+        std::fs::write(path, &outcome.optimised_model_ini)
             .map_err(|e| format!("Failed to write optimised model to '{}': {}", path, e))?;
     }
 
@@ -158,16 +149,98 @@ pub fn optimise_from_file(
         writeln!(&mut output, "Objective expression: {}", config.objective_expression).unwrap();
         writeln!(&mut output, "Algorithm: {}", config.algorithm.name()).unwrap();
         writeln!(&mut output, "Population size: {}", config.algorithm.population_size()).unwrap();
-        writeln!(&mut output, "Best objective value: {:.6}", result.best_objective).unwrap();
-        writeln!(&mut output, "Function evaluations: {}\n", result.n_evaluations).unwrap();
+        writeln!(&mut output, "Best objective value: {:.6}", outcome.best_objective).unwrap();
+        writeln!(&mut output, "Function evaluations: {}\n", outcome.n_evaluations).unwrap();
+        for (label, metrics) in [("Calibration", &outcome.calibration_metrics), ("Validation", &outcome.validation_metrics)] {
+            if let Some(metrics) = metrics {
+                writeln!(&mut output, "{} metrics:", label).unwrap();
+                writeln!(&mut output, "  objective = {:.6}", metrics.objective).unwrap();
+                for (term, value) in &metrics.terms {
+                    writeln!(&mut output, "  {} = {:.6}", term, value).unwrap();
+                }
+                writeln!(&mut output).unwrap();
+            }
+        }
         writeln!(&mut output, "Optimized Parameters:").unwrap();
-        for (target, value) in &parameters {
+        for (target, value) in &outcome.parameters {
             writeln!(&mut output, "  {} = {:.6}", target, value).unwrap();
         }
         std::fs::write(output_path, output)
             .map_err(|e| format!("Failed to write results to '{}': {}", output_path, e))?;
     }
 
+    Ok(outcome)
+}
+
+/// Calibrate a model that is already loaded, leaving it with the best parameters found.
+///
+/// The in-memory counterpart of [`optimise_from_file`], used by the Python `Model` class.
+/// The config's `model_file` and `output_file` are not used. Observed data files (and the
+/// `trace_file`, if any) are still resolved relative to the current working directory.
+pub fn calibrate_model(
+    model: &mut crate::model::Model,
+    config: &crate::numerical::opt::OptimisationConfig,
+    progress_callback: Option<crate::numerical::opt::ProgressCallback>,
+) -> Result<OptimisationOutcome, String> {
+    use crate::numerical::opt::{OptimisationProblem, Optimisable, create_optimizer_with_callback};
+    use crate::functions::parse_function;
+
+    if !config.catchments.is_empty() {
+        return Err("The config has [catchment.NAME] sections; run it with calibrate_region".to_string());
+    }
+
+    // Build comparison pairs from terms (load each observed series).
+    let comparisons = term_comparisons(&config.terms)?;
+
+    // Parse the composite objective expression.
+    let expression = parse_function(&config.objective_expression).map_err(|e| {
+        format!("Failed to parse objective_expression '{}': {}", config.objective_expression, e)
+    })?;
+
+    let objectives = config.parse_objective_expressions()?;
+
+    let mut problem = OptimisationProblem::new(
+        model.clone(),
+        config.parameter_config.clone(),
+        comparisons,
+        expression,
+    ).with_objectives(objectives).with_period(config.calibration_period);
+
+    // Optionally trace every evaluation to the file requested by the config.
+    if let Some(trace_file) = &config.trace_file {
+        problem.start_trace(trace_file)?;
+    }
+
+    // Run the optimisation, wiring up the caller's progress callback (if any).
+    let optimiser = create_optimizer_with_callback(config, problem.trace_progress(progress_callback))
+        .map_err(|e| e.to_string())?;
+    let result = optimiser.optimize(&mut problem, None);
+    problem.finish_trace()?;
+
+    // Re-evaluate the best parameter set over the calibration and validation periods.
+    let (calibration_metrics, validation_metrics) = if result.success
+        && (config.calibration_period.is_some() || config.validation_period.is_some()) {
+        let calibration = problem.period_metrics(&result.best_params, config.calibration_period)
+            .map_err(|e| format!("Failed to evaluate calibration period: {}", e))?;
+        let validation = config.validation_period
+            .map(|period| problem.period_metrics(&result.best_params, Some(period)))
+            .transpose()
+            .map_err(|e| format!("Failed to evaluate validation period: {}", e))?;
+        (Some(calibration), validation)
+    } else {
+        (None, None)
+    };
+
+    // Physical parameter values for the best genes.
+    let parameters = problem.config.evaluate(&result.best_params);
+
+    // Apply best parameters so the model is in its optimal state before saving.
+    problem.set_params(&result.best_params)
+        .map_err(|e| format!("Failed to apply best parameters: {}", e))?;
+
+    let optimised_model_ini = IniModelIO::new().model_to_string(&problem.model);
+    *model = problem.model;
+
     Ok(OptimisationOutcome {
         best_objective: result.best_objective,
         n_evaluations: result.n_evaluations,
@@ -175,5 +248,150 @@ pub fn optimise_from_file(
         message: result.message,
         parameters,
         optimised_model_ini,
+        calibration_metrics,
+        validation_metrics,
     })
 }
+
+/// Outcome of a regional calibration (see [`calibrate_region`]).
+pub struct RegionalOutcome {
+    /// Best objective value found (lower is better).
+    pub best_objective: f64,
+    /// Total number of function evaluations performed.
+    pub n_evaluations: usize,
+    /// Whether the optimiser terminated successfully.
+    pub success: bool,
+    /// Termination message from the optimiser.
+    pub message: String,
+    /// Calibrated parameters as (target, physical value) pairs, in config order. Targets for
+    /// one catchment keep their `catchment.<name>.` prefix.
+    pub parameters: Vec<(String, f64)>,
+    /// Each catchment's name and its model with the calibrated parameters written back
+    /// (see [`crate::model::Model::apply_calibration`]).
+    pub models: Vec<(String, crate::model::Model)>,
+    /// Best parameter set re-evaluated over the config's `calibration_period`.
+    pub calibration_metrics: Option<crate::numerical::opt::PeriodMetrics>,
+    /// Best parameter set re-evaluated over the config's `validation_period`, if set.
+    pub validation_metrics: Option<crate::numerical::opt::PeriodMetrics>,
+}
+
+/// Calibrate the catchment models of a config with `[catchment.NAME]` sections together.
+///
+/// Each catchment's model is loaded from its `model_file`, and each term is compared against
+/// the model of the catchment it names. Parameters are shared by every catchment unless
+/// their target is scoped to one as `catchment.NAME.<address>` (see
+/// [`crate::numerical::opt::regional`]).
+pub fn calibrate_region(
+    config: &crate::numerical::opt::OptimisationConfig,
+    progress_callback: Option<crate::numerical::opt::ProgressCallback>,
+) -> Result<RegionalOutcome, String> {
+    use crate::numerical::opt::{OptimisationProblem, RegionalCatchment, RegionalProblem,
+                                ParameterMappingConfig, create_optimizer_with_callback};
+    use crate::numerical::opt::regional::catchment_parameters;
+    use crate::functions::parse_function;
+
+    if config.catchments.is_empty() {
+        return Err("A regional calibration needs [catchment.NAME] sections".to_string());
+    }
+    if config.trace_file.is_some() {
+        return Err("'trace_file' is not supported for a regional calibration".to_string());
+    }
+
+    let expression = parse_function(&config.objective_expression).map_err(|e| {
+        format!("Failed to parse objective_expression '{}': {}", config.objective_expression, e)
+    })?;
+
+    let mut catchments = Vec::with_capacity(config.catchments.len());
+    for catchment in &config.catchments {
+        let model = IniModelIO::new().read_model_file(&catchment.model_file)
+            .map_err(|e| format!("Failed to load model for catchment '{}': {}", catchment.name, e))?;
+        let terms: Vec<_> = config.terms.iter()
+            .filter(|t| t.catchment.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(&catchment.name)))
+            .cloned()
+            .collect();
+        catchments.push(RegionalCatchment {
+            name: catchment.name.clone(),
+            problem: OptimisationProblem::new(model, ParameterMappingConfig::new(), term_comparisons(&terms)?, expression.clone()),
+        });
+    }
+    let mut problem = RegionalProblem::new(config.parameter_config.clone(), catchments, expression)?
+        .with_period(config.calibration_period);
+
+    let optimiser = create_optimizer_with_callback(config, progress_callback)
+        .map_err(|e| e.to_string())?;
+    let result = optimiser.optimize(&mut problem, None);
+
+    let (calibration_metrics, validation_metrics) = if result.success
+        && (config.calibration_period.is_some() || config.validation_period.is_some()) {
+        let calibration = problem.period_metrics(&result.best_params, config.calibration_period)
+            .map_err(|e| format!("Failed to evaluate calibration period: {}", e))?;
+        let validation = config.validation_period
+            .map(|period| problem.period_metrics(&result.best_params, Some(period)))
+            .transpose()
+            .map_err(|e| format!("Failed to evaluate validation period: {}", e))?;
+        (Some(calibration), validation)
+    } else {
+        (None, None)
+    };
+
+    let parameters = problem.config.evaluate(&result.best_params);
+    let mut models = Vec::with_capacity(problem.catchments.len());
+    for catchment in &problem.catchments {
+        let mut model = catchment.problem.model.clone();
+        model.apply_calibration(&catchment_parameters(&catchment.name, &parameters))
+            .map_err(|e| format!("Failed to apply best parameters to catchment '{}': {}", catchment.name, e))?;
+        models.push((catchment.name.clone(), model));
+    }
+
+    Ok(RegionalOutcome {
+        best_objective: result.best_objective,
+        n_evaluations: result.n_evaluations,
+        success: result.success,
+        message: result.message,
+        parameters,
+        models,
+        calibration_metrics,
+        validation_metrics,
+    })
+}
+
+/// Load the observed series of each term and pair it with the series it is compared against.
+fn term_comparisons(terms: &[crate::io::optimisation_config_io::Term])
+    -> Result<Vec<crate::numerical::opt::optimisation::ComparisonPair>, String> {
+    use crate::io::optimisation_config_io::load_observed_for_term;
+    use crate::numerical::opt::optimisation::ComparisonPair;
+
+    let mut comparisons = Vec::with_capacity(terms.len());
+    for term in terms {
+        let observed = load_observed_for_term(&term.observed_file, &term.observed_series)
+            .map_err(|e| format!("Failed to load observed data for term '{}': {}", term.name, e))?;
+        comparisons.push(ComparisonPair {
+            name: term.name.clone(),
+            observed: std::sync::Arc::unwrap_or_clone(observed.timeseries),
+            simulated_series_name: term.simulated_series.clone(),
+            statistic: term.statistic.clone(),
+            transforms: term.transforms.clone(),
+        });
+    }
+    Ok(comparisons)
+}
+
+/// Read every series from an output file and summarise it.
+///
+/// Backs the `kalix stats` CLI subcommand. Like [`crate::model::Model::write_outputs`], the
+/// format is chosen by extension: `.pxb`/`.pxt` are read as a Pixie pair, anything else as CSV.
+/// Statistics ignore missing (NaN) values.
+pub fn summarise_series_file(path: &str) -> Result<Vec<(String, crate::misc::batch_run::SeriesSummary)>, String> {
+    use crate::misc::batch_run::SeriesSummary;
+
+    let lower = path.to_ascii_lowercase();
+    let series = if lower.ends_with(".pxb") || lower.ends_with(".pxt") {
+        crate::io::pixie_io::read_all_series(&path[..path.len() - 4])
+            .map_err(|e| format!("Could not read file {}: {:?}", path, e))?
+    } else {
+        crate::io::csv_io::read_ts(path)?
+    };
+    Ok(series.iter()
+        .map(|ts| (ts.name.clone(), SeriesSummary::from_values(&ts.values)))
+        .collect())
+}
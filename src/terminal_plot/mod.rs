@@ -25,11 +25,16 @@
 //!
 //! println!("{}", plot.render());
 //! ```
+//!
+//! For a quick look at a flow series, e.g. while checking a calibration, its flow duration
+//! curve can be plotted directly with `TerminalPlot::flow_duration_curve(&series)`.
 
 pub mod optimisation_plot;
 
 use std::fmt;
 
+use crate::timeseries::Timeseries;
+
 /// Main plot structure containing configuration and plot elements
 pub struct TerminalPlot {
     config: PlotConfig,
@@ -50,6 +55,9 @@ pub struct PlotConfig {
     pub x_range: Option<(f64, f64)>,
     pub y_range: Option<(f64, f64)>,
     pub color_scheme: ColorScheme,
+    pub resolution: Resolution,
+    /// Whether the y-axis is logarithmic. Values that are zero or negative are not drawn.
+    pub log_y: bool,
 }
 
 /// Plot elements that can be rendered
@@ -66,6 +74,21 @@ pub struct Line {
     pub color: Option<Color>,
 }
 
+impl Line {
+    /// The flow duration curve of a series: each value against the percentage of time it is
+    /// equalled or exceeded, using Weibull plotting positions. Missing values are left out.
+    pub fn flow_duration_curve(series: &Timeseries) -> Line {
+        let mut values: Vec<f64> = series.values.iter().copied().filter(|v| v.is_finite()).collect();
+        values.sort_by(|a, b| b.total_cmp(a));
+        let n = values.len() as f64;
+        Line {
+            points: values.iter().enumerate().map(|(i, &v)| ((i + 1) as f64 / (n + 1.0) * 100.0, v)).collect(),
+            style: LineStyle::Solid,
+            color: None,
+        }
+    }
+}
+
 /// Individual scatter point
 #[derive(Clone)]
 pub struct ScatterPoint {
@@ -84,6 +107,18 @@ pub struct Marker {
     pub label: Option<String>,
 }
 
+/// How finely lines are drawn within each character cell of the plot area
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Resolution {
+    /// One point per cell, drawn with the line's style character
+    #[default]
+    Cell,
+    /// 2×2 points per cell, drawn with quadrant block characters ('▖', '▚', '▟', ...)
+    Quadrant,
+    /// 2×4 points per cell, drawn with Unicode braille patterns ('⡀', '⠢', '⣿', ...)
+    Braille,
+}
+
 /// Style for rendering lines
 #[derive(Clone, Copy)]
 pub enum LineStyle {
@@ -148,6 +183,8 @@ impl Default for PlotConfig {
             x_range: None,
             y_range: None,
             color_scheme: ColorScheme::default(),
+            resolution: Resolution::default(),
+            log_y: false,
         }
     }
 }
@@ -209,6 +246,33 @@ impl Color {
     }
 }
 
+impl Resolution {
+    /// Points per cell across and down
+    fn points_per_cell(&self) -> (usize, usize) {
+        match self {
+            Resolution::Cell => (1, 1),
+            Resolution::Quadrant => (2, 2),
+            Resolution::Braille => (2, 4),
+        }
+    }
+
+    /// The character for a cell whose points are set in `mask`, where bit `row * 2 + col`
+    /// is the point in that row and column of the cell
+    fn cell_char(&self, mask: u8) -> char {
+        const QUADRANTS: [char; 16] = [' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█'];
+        // Braille dots 1-8 by (row, col), in Unicode's bit order
+        const BRAILLE_BITS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+        match self {
+            Resolution::Cell => if mask == 0 { ' ' } else { '·' },
+            Resolution::Quadrant => QUADRANTS[(mask & 0x0f) as usize],
+            Resolution::Braille => {
+                let bits: u32 = (0..8).filter(|i| mask & (1 << i) != 0).map(|i| BRAILLE_BITS[i]).sum();
+                char::from_u32(0x2800 + bits).unwrap()
+            }
+        }
+    }
+}
+
 impl LineStyle {
     fn to_char(&self) -> char {
         match self {
@@ -228,6 +292,22 @@ impl TerminalPlot {
         }
     }
 
+    /// A plot of the flow duration curve of a series, with exceedance on the x-axis and flow on
+    /// a log y-axis. Other curves can be added for comparison, e.g. observed flows with
+    /// `add_line(Line::flow_duration_curve(&observed))`.
+    pub fn flow_duration_curve(series: &Timeseries) -> TerminalPlot {
+        let mut plot = TerminalPlot::builder()
+            .title(&series.name)
+            .x_label("% exceeded")
+            .y_label("Flow")
+            .x_range(0.0, 100.0)
+            .log_y(true)
+            .resolution(Resolution::Braille)
+            .build();
+        plot.add_line(Line::flow_duration_curve(series));
+        plot
+    }
+
     /// Add a line to the plot
     pub fn add_line(&mut self, line: Line) {
         self.elements.push(PlotElement::Line(line));
@@ -321,6 +401,23 @@ impl TerminalPlot {
     }
 
     /// Calculate actual x and y ranges based on data
+    /// A data point's position on the plot's axes, which is `log10(y)` up a log y-axis
+    fn to_plot_space(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        if !self.config.log_y {
+            (x, y)
+        } else if y > 0.0 {
+            (x, y.log10())
+        } else {
+            (x, f64::NAN)
+        }
+    }
+
+    /// The data value at a height up the y-axis, in plot space
+    fn data_y(&self, y: f64) -> f64 {
+        if self.config.log_y { 10f64.powf(y) } else { y }
+    }
+
+    /// The x and y ranges of the plot, with y in plot space (see `to_plot_space`)
     fn calculate_ranges(&self) -> ((f64, f64), (f64, f64)) {
         let mut x_min = f64::INFINITY;
         let mut x_max = f64::NEG_INFINITY;
@@ -331,7 +428,7 @@ impl TerminalPlot {
         for element in &self.elements {
             match element {
                 PlotElement::Line(line) => {
-                    for &(x, y) in &line.points {
+                    for (x, y) in line.points.iter().map(|&p| self.to_plot_space(p)).filter(|p| p.1.is_finite()) {
                         x_min = x_min.min(x);
                         x_max = x_max.max(x);
                         y_min = y_min.min(y);
@@ -339,18 +436,21 @@ impl TerminalPlot {
                     }
                 }
                 PlotElement::ScatterPoints(points) => {
-                    for point in points {
-                        x_min = x_min.min(point.x);
-                        x_max = x_max.max(point.x);
-                        y_min = y_min.min(point.y);
-                        y_max = y_max.max(point.y);
+                    for (x, y) in points.iter().map(|p| self.to_plot_space((p.x, p.y))).filter(|p| p.1.is_finite()) {
+                        x_min = x_min.min(x);
+                        x_max = x_max.max(x);
+                        y_min = y_min.min(y);
+                        y_max = y_max.max(y);
                     }
                 }
                 PlotElement::Marker(marker) => {
-                    x_min = x_min.min(marker.x);
-                    x_max = x_max.max(marker.x);
-                    y_min = y_min.min(marker.y);
-                    y_max = y_max.max(marker.y);
+                    let (x, y) = self.to_plot_space((marker.x, marker.y));
+                    if y.is_finite() {
+                        x_min = x_min.min(x);
+                        x_max = x_max.max(x);
+                        y_min = y_min.min(y);
+                        y_max = y_max.max(y);
+                    }
                 }
             }
         }
@@ -376,25 +476,37 @@ impl TerminalPlot {
         }
 
         let x_range = self.config.x_range.unwrap_or((x_min, x_max));
-        let y_range = self.config.y_range.unwrap_or((y_min, y_max));
+        let y_range = match self.config.y_range {
+            Some((min, max)) => (self.to_plot_space((0.0, min)).1, self.to_plot_space((0.0, max)).1),
+            None => (y_min, y_max),
+        };
 
         (x_range, y_range)
     }
 
     /// Transform data coordinates to screen coordinates
     fn data_to_screen(&self, x: f64, y: f64, x_range: (f64, f64), y_range: (f64, f64)) -> Option<(usize, usize)> {
+        self.data_to_points(x, y, x_range, y_range, (1, 1))
+    }
+
+    /// Transform data coordinates to a grid of `points_per_cell` points in each character
+    /// cell, counted from the top left
+    fn data_to_points(&self, x: f64, y: f64, x_range: (f64, f64), y_range: (f64, f64),
+                      points_per_cell: (usize, usize)) -> Option<(usize, usize)> {
         let (x_min, x_max) = x_range;
         let (y_min, y_max) = y_range;
 
-        if x < x_min || x > x_max || y < y_min || y > y_max {
+        if !(x_min..=x_max).contains(&x) || !(y_min..=y_max).contains(&y) {
             return None;
         }
 
-        let screen_x = ((x - x_min) / (x_max - x_min) * self.config.width as f64) as usize;
-        let screen_y = self.config.height - 1 -
-                       ((y - y_min) / (y_max - y_min) * self.config.height as f64) as usize;
+        let width = self.config.width * points_per_cell.0;
+        let height = self.config.height * points_per_cell.1;
+        let screen_x = ((x - x_min) / (x_max - x_min) * width as f64) as usize;
+        let screen_y = height - 1 -
+                       (((y - y_min) / (y_max - y_min) * height as f64) as usize).min(height - 1);
 
-        Some((screen_x.min(self.config.width - 1), screen_y.min(self.config.height - 1)))
+        Some((screen_x.min(width - 1), screen_y))
     }
 
     /// Internal method to render the plot without clearing
@@ -439,27 +551,12 @@ impl TerminalPlot {
         output.push_str(Color::Reset.to_ansi());
         output.push('\n');
 
-        // Create a 2D grid for the plot area
-        let mut grid: Vec<Vec<Option<(char, Color)>>> = vec![vec![None; self.config.width]; self.config.height];
-
-        // Render all elements onto the grid
-        for element in &self.elements {
-            match element {
-                PlotElement::Line(line) => {
-                    self.render_line_to_grid(&mut grid, line, x_range, y_range);
-                }
-                PlotElement::ScatterPoints(points) => {
-                    self.render_scatter_to_grid(&mut grid, points, x_range, y_range);
-                }
-                PlotElement::Marker(marker) => {
-                    self.render_marker_to_grid(&mut grid, marker, x_range, y_range);
-                }
-            }
-        }
+        let grid = self.build_grid(x_range, y_range);
 
         // Determine label formatting based on y-range
-        let y_span = (y_range.1 - y_range.0).abs();
-        let max_abs_y = y_range.0.abs().max(y_range.1.abs());
+        let y_range_labels = (self.data_y(y_range.0), self.data_y(y_range.1));
+        let y_span = (y_range_labels.1 - y_range_labels.0).abs();
+        let max_abs_y = y_range_labels.0.abs().max(y_range_labels.1.abs());
 
         // Use scientific notation for very small or very large values
         let use_scientific = y_span < 0.01 || max_abs_y > 10000.0;
@@ -480,9 +577,16 @@ impl TerminalPlot {
         for row in 0..self.config.height {
             // Y-axis label - only show every 2 rows and avoid duplicates
             if row % 2 == 0 {
-                let y_value = y_range.1 - (row as f64 / self.config.height as f64) * (y_range.1 - y_range.0);
-
-                let label = if use_scientific {
+                let y_value = self.data_y(y_range.1 - (row as f64 / self.config.height as f64) * (y_range.1 - y_range.0));
+
+                let label = if self.config.log_y {
+                    // Values across decades get the precision of their own magnitude
+                    if !(0.01..100000.0).contains(&y_value) {
+                        format!("{:.1e}", y_value)
+                    } else {
+                        format!("{:.*}", if y_value < 1.0 { 2 } else if y_value < 10.0 { 1 } else { 0 }, y_value)
+                    }
+                } else if use_scientific {
                     format!("{:.1e}", y_value)
                 } else {
                     format!("{:.*}", decimals, y_value)
@@ -601,19 +705,71 @@ impl TerminalPlot {
         output
     }
 
+    /// The characters and colours of the plot area, row by row from the top
+    fn build_grid(&self, x_range: (f64, f64), y_range: (f64, f64)) -> Vec<Vec<Option<(char, Color)>>> {
+        // Create a 2D grid for the plot area
+        let mut grid: Vec<Vec<Option<(char, Color)>>> = vec![vec![None; self.config.width]; self.config.height];
+
+        // Lines drawn finer than a cell are collected first, and go under the other elements
+        let mut fine_lines: Vec<Vec<(u8, Color)>> = vec![vec![(0, Color::Reset); self.config.width]; self.config.height];
+
+        // Render all elements onto the grid
+        for element in &self.elements {
+            match element {
+                PlotElement::Line(line) if self.config.resolution != Resolution::Cell => {
+                    self.render_line_to_points(&mut fine_lines, line, x_range, y_range);
+                }
+                PlotElement::Line(line) => {
+                    self.render_line_to_grid(&mut grid, line, x_range, y_range);
+                }
+                PlotElement::ScatterPoints(points) => {
+                    self.render_scatter_to_grid(&mut grid, points, x_range, y_range);
+                }
+                PlotElement::Marker(marker) => {
+                    self.render_marker_to_grid(&mut grid, marker, x_range, y_range);
+                }
+            }
+        }
+
+        for (grid_row, fine_row) in grid.iter_mut().zip(&fine_lines) {
+            for (cell, &(mask, color)) in grid_row.iter_mut().zip(fine_row) {
+                if cell.is_none() && mask != 0 {
+                    *cell = Some((self.config.resolution.cell_char(mask), color));
+                }
+            }
+        }
+
+        grid
+    }
+
+    /// Just the plot area, without the title, axes or borders: one string per row, with
+    /// colour codes. Used for small panels alongside a full plot.
+    pub fn render_plot_area(&self) -> Vec<String> {
+        let (x_range, y_range) = self.calculate_ranges();
+        self.build_grid(x_range, y_range).iter()
+            .map(|row| row.iter()
+                .map(|cell| match cell {
+                    Some((ch, color)) => format!("{}{}{}", color.to_ansi(), ch, Color::Reset.to_ansi()),
+                    None => " ".to_string(),
+                })
+                .collect())
+            .collect()
+    }
+
     fn render_line_to_grid(&self, grid: &mut Vec<Vec<Option<(char, Color)>>>, line: &Line, x_range: (f64, f64), y_range: (f64, f64)) {
         let ch = line.style.to_char();
         let color = line.color.unwrap_or(self.config.color_scheme.default);
+        let points: Vec<(f64, f64)> = line.points.iter().map(|&p| self.to_plot_space(p)).collect();
 
-        for i in 0..line.points.len() {
-            if let Some((sx, sy)) = self.data_to_screen(line.points[i].0, line.points[i].1, x_range, y_range) {
+        for i in 0..points.len() {
+            if let Some((sx, sy)) = self.data_to_screen(points[i].0, points[i].1, x_range, y_range) {
                 grid[sy][sx] = Some((ch, color));
             }
 
             // Interpolate between points for continuous line
             if i > 0 {
-                let (x0, y0) = line.points[i - 1];
-                let (x1, y1) = line.points[i];
+                let (x0, y0) = points[i - 1];
+                let (x1, y1) = points[i];
 
                 let steps = ((x1 - x0).abs().max((y1 - y0).abs()) * 10.0) as usize;
                 for step in 0..steps {
@@ -629,9 +785,43 @@ impl TerminalPlot {
         }
     }
 
+    /// Draws a line onto the points within each cell. A cell takes the colour of the last
+    /// line drawn through it.
+    fn render_line_to_points(&self, cells: &mut [Vec<(u8, Color)>], line: &Line, x_range: (f64, f64), y_range: (f64, f64)) {
+        let color = line.color.unwrap_or(self.config.color_scheme.default);
+        let (per_x, per_y) = self.config.resolution.points_per_cell();
+        let mut set_point = |x: f64, y: f64| {
+            if let Some((px, py)) = self.data_to_points(x, y, x_range, y_range, (per_x, per_y)) {
+                let cell = &mut cells[py / per_y][px / per_x];
+                cell.0 |= 1 << ((py % per_y) * 2 + px % per_x);
+                cell.1 = color;
+            }
+        };
+
+        let points: Vec<(f64, f64)> = line.points.iter().map(|&p| self.to_plot_space(p)).collect();
+        for i in 0..points.len() {
+            let (x1, y1) = points[i];
+            if i == 0 {
+                set_point(x1, y1);
+                continue;
+            }
+
+            // Step through every point between the two ends
+            let (x0, y0) = points[i - 1];
+            let across = (x1 - x0).abs() / (x_range.1 - x_range.0) * (self.config.width * per_x) as f64;
+            let down = (y1 - y0).abs() / (y_range.1 - y_range.0) * (self.config.height * per_y) as f64;
+            let steps = across.max(down).ceil().max(1.0) as usize;
+            for step in 1..=steps {
+                let t = step as f64 / steps as f64;
+                set_point(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+            }
+        }
+    }
+
     fn render_scatter_to_grid(&self, grid: &mut Vec<Vec<Option<(char, Color)>>>, points: &[ScatterPoint], x_range: (f64, f64), y_range: (f64, f64)) {
         for point in points {
-            if let Some((sx, sy)) = self.data_to_screen(point.x, point.y, x_range, y_range) {
+            let (x, y) = self.to_plot_space((point.x, point.y));
+            if let Some((sx, sy)) = self.data_to_screen(x, y, x_range, y_range) {
                 let color = point.color.unwrap_or(self.config.color_scheme.default);
                 grid[sy][sx] = Some((point.symbol, color));
             }
@@ -639,7 +829,8 @@ impl TerminalPlot {
     }
 
     fn render_marker_to_grid(&self, grid: &mut Vec<Vec<Option<(char, Color)>>>, marker: &Marker, x_range: (f64, f64), y_range: (f64, f64)) {
-        if let Some((sx, sy)) = self.data_to_screen(marker.x, marker.y, x_range, y_range) {
+        let (x, y) = self.to_plot_space((marker.x, marker.y));
+        if let Some((sx, sy)) = self.data_to_screen(x, y, x_range, y_range) {
             let color = marker.color.unwrap_or(self.config.color_scheme.default);
             grid[sy][sx] = Some((marker.symbol, color));
         }
@@ -687,6 +878,19 @@ impl PlotBuilder {
         self
     }
 
+    /// Draw lines with several points per character cell, so they render smoothly in the
+    /// same footprint. Scatter points and markers are still one per cell, drawn over the lines.
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.config.resolution = resolution;
+        self
+    }
+
+    /// Use a logarithmic y-axis. A `y_range` is still given in data values.
+    pub fn log_y(mut self, log_y: bool) -> Self {
+        self.config.log_y = log_y;
+        self
+    }
+
     pub fn build(self) -> TerminalPlot {
         TerminalPlot {
             config: self.config,
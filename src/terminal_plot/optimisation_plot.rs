@@ -4,6 +4,8 @@
 //! - Best objective function evolution over time
 //! - Current generation population scatter points
 //! - Progress tracking and timing information
+//! - Small panels of each parameter's spread across the population (min, median and max),
+//!   for optimisers that report their population, to show which parameters have converged
 
 use super::*;
 use crate::numerical::opt::OptimizationProgress;
@@ -14,6 +16,33 @@ pub struct OptimisationPlot {
     history: Vec<(f64, f64)>,  // (evaluation, objective) pairs
     all_scatter_points: Vec<ScatterPoint>,  // All generation scatter points
     termination_evaluations: usize,
+    width: usize,
+    parameter_names: Vec<String>,
+    parameter_bands: Vec<(f64, Vec<ParameterBand>)>,  // (evaluation, band of each parameter)
+}
+
+/// The spread of one parameter across the population, in normalised [0,1] values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParameterBand {
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+}
+
+impl ParameterBand {
+    /// The band of each parameter, from a population of normalised parameter vectors
+    pub fn from_population(population: &[Vec<f64>]) -> Vec<ParameterBand> {
+        let n_params = population.first().map_or(0, |p| p.len());
+        (0..n_params)
+            .map(|j| {
+                let mut values: Vec<f64> = population.iter().map(|p| p[j]).collect();
+                values.sort_by(|a, b| a.total_cmp(b));
+                let mid = values.len() / 2;
+                let median = if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] };
+                ParameterBand { min: values[0], median, max: values[values.len() - 1] }
+            })
+            .collect()
+    }
 }
 
 impl OptimisationPlot {
@@ -38,9 +67,18 @@ impl OptimisationPlot {
             history: Vec::new(),
             all_scatter_points: Vec::new(),
             termination_evaluations,
+            width,
+            parameter_names: Vec::new(),
+            parameter_bands: Vec::new(),
         }
     }
 
+    /// Names for the parameter panels, in parameter order (otherwise `g[0]`, `g[1]`, ...)
+    pub fn with_parameter_names(mut self, names: Vec<String>) -> Self {
+        self.parameter_names = names;
+        self
+    }
+
     /// Calculate x-range with minimum clamped to 0
     fn calculate_x_range(&self) -> (f64, f64) {
         let mut x_max = 0.0f64;
@@ -119,9 +157,17 @@ impl OptimisationPlot {
             label: Some(format!("← BEST: {:.6}", progress.best_objective)),
         });
 
+        // Record each parameter's spread across the population
+        if let Some(ref pop_params) = progress.population_params {
+            if !pop_params.is_empty() {
+                self.parameter_bands.push((progress.n_evaluations as f64, ParameterBand::from_population(pop_params)));
+            }
+        }
+
         // Add footer information (generic across all algorithms)
         self.plot.add_footer_line(format!("Best: {:.6}", progress.best_objective));
         self.plot.add_footer_line(format!("Time: {:.1}s", progress.elapsed.as_secs_f64()));
+        self.add_parameter_panels();
     }
 
     /// Render the final optimisation result
@@ -163,6 +209,55 @@ impl OptimisationPlot {
         // Add footer information
         self.plot.add_footer_line(format!("Best: {:.6}", best_objective));
         self.plot.add_footer_line(format!("Time: {:.1}s", elapsed.as_secs_f64()));
+        self.add_parameter_panels();
+    }
+
+    /// Adds a small panel for each parameter below the plot, two to a row, tracing the
+    /// population's min and max (cyan) and median (magenta) over the evaluations. The spread
+    /// in the latest population is shown beside each name.
+    fn add_parameter_panels(&mut self) {
+        let Some((_, latest)) = self.parameter_bands.last() else {
+            return;
+        };
+        let n_params = latest.len();
+        let panel_width = (self.width + 12) / 2 - 2;
+        let (x_min, x_max) = self.calculate_x_range();
+
+        let mut names = Vec::with_capacity(n_params);
+        let mut panels = Vec::with_capacity(n_params);
+        for j in 0..n_params {
+            let name = self.parameter_names.get(j).cloned().unwrap_or_else(|| format!("g[{}]", j));
+            let spread = format!(" {:.3}", latest[j].max - latest[j].min);
+            let name: String = name.chars().take(panel_width.saturating_sub(spread.len())).collect();
+            names.push(format!("{:<w$}{}", name, spread, w = panel_width - spread.len()));
+
+            let mut panel = TerminalPlot::builder()
+                .width(panel_width)
+                .height(3)
+                .x_range(x_min, x_max)
+                .y_range(0.0, 1.0)
+                .resolution(Resolution::Braille)
+                .build();
+            let trace = |value: fn(&ParameterBand) -> f64| -> Vec<(f64, f64)> {
+                self.parameter_bands.iter().map(|(x, bands)| (*x, value(&bands[j]))).collect()
+            };
+            for (points, color) in [(trace(|b| b.min), Color::Cyan), (trace(|b| b.max), Color::Cyan),
+                                    (trace(|b| b.median), Color::BrightMagenta)] {
+                panel.add_line(Line { points, style: LineStyle::Dots, color: Some(color) });
+            }
+            panels.push(panel.render_plot_area());
+        }
+
+        self.plot.add_footer_line("Parameter spread (normalised):");
+        for pair in (0..n_params).collect::<Vec<_>>().chunks(2) {
+            self.plot.add_footer_line(pair.iter().map(|&j| names[j].as_str()).collect::<Vec<_>>().join("  "));
+            let rows: Vec<String> = (0..3)
+                .map(|row| pair.iter().map(|&j| panels[j][row].as_str()).collect::<Vec<_>>().join("  "))
+                .collect();
+            for row in rows {
+                self.plot.add_footer_line(row);
+            }
+        }
     }
 
     /// Render the plot (automatically handles clearing/redrawing)
@@ -1,5 +1,5 @@
 #[cfg(test)]
-mod test_helpers;
+pub(crate) mod test_helpers;
 
 #[cfg(test)]
 mod test_table;
@@ -98,4 +98,124 @@ mod test_fifo_buffer;
 mod test_interpolation;
 
 #[cfg(test)]
-mod test_table_discontinuous;
\ No newline at end of file
+mod test_table_discontinuous;
+#[cfg(test)]
+mod test_model_validation;
+
+#[cfg(test)]
+mod test_execution_order;
+
+#[cfg(test)]
+mod test_batch_run;
+
+#[cfg(test)]
+mod test_glue;
+
+#[cfg(test)]
+mod test_calibration_trace;
+
+#[cfg(test)]
+mod test_model_quantity_optimisation;
+
+#[cfg(test)]
+mod test_constituents;
+
+#[cfg(test)]
+mod test_node_transfer;
+
+#[cfg(test)]
+mod test_shortfall_sharing;
+
+#[cfg(test)]
+mod test_off_allocation;
+
+#[cfg(test)]
+mod test_min_flow;
+
+#[cfg(test)]
+mod test_node_loss;
+
+#[cfg(test)]
+mod test_node_splitter;
+
+#[cfg(test)]
+mod test_node_unregulated_user;
+
+#[cfg(test)]
+mod test_node_gauge;
+
+#[cfg(test)]
+mod test_user_functions;
+
+#[cfg(test)]
+mod test_tables;
+
+#[cfg(test)]
+mod test_expression_errors;
+#[cfg(test)]
+mod test_circular_references;
+#[cfg(test)]
+mod test_compressed_series;
+#[cfg(test)]
+mod test_recording;
+#[cfg(test)]
+mod test_input_resampling;
+#[cfg(test)]
+mod test_calibrate_model;
+#[cfg(all(test, feature = "ffi"))]
+mod test_ffi;
+#[cfg(test)]
+mod test_graph_export;
+
+#[cfg(test)]
+mod test_terminal_plot;
+
+#[cfg(test)]
+mod test_run_report;
+
+#[cfg(test)]
+mod test_output_statistics;
+#[cfg(test)]
+mod test_provenance;
+#[cfg(test)]
+mod test_thread_reproducibility;
+#[cfg(test)]
+mod test_initial_conditions;
+#[cfg(test)]
+mod test_spin_up;
+#[cfg(test)]
+mod test_model_diff;
+#[cfg(test)]
+mod test_link_attributes;
+#[cfg(test)]
+mod test_templates;
+#[cfg(test)]
+mod test_parameter_files;
+#[cfg(test)]
+mod test_regional_calibration;
+#[cfg(test)]
+mod test_regionalisation;
+#[cfg(test)]
+mod test_forecast;
+#[cfg(test)]
+mod test_policies;
+#[cfg(test)]
+mod test_carryover;
+#[cfg(test)]
+mod test_resource_assessment;
+#[cfg(test)]
+mod test_residual;
+#[cfg(test)]
+mod test_input_extension;
+#[cfg(test)]
+mod test_input_coverage;
+#[cfg(test)]
+mod test_nan_policy;
+#[cfg(test)]
+mod test_negative_guards;
+#[cfg(test)]
+mod test_incremental_run;
+#[cfg(test)]
+mod test_source_res_io;
+#[cfg(test)]
+mod test_ini_formatter;
\ No newline at end of file
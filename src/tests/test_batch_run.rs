@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::batch_run::{BatchOptions, ParameterSet};
+use crate::model::Model;
+
+fn load_model() -> Model {
+    // The input path in this model is relative to the repo root
+    let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+    IniModelIO::new().read_model_string_with_working_directory(&ini, Some(PathBuf::from("."))).unwrap()
+}
+
+fn set(pairs: &[(&str, f64)]) -> ParameterSet {
+    pairs.iter().map(|&(t, v)| (t.to_string(), v)).collect()
+}
+
+/// Inflow added by node2 for given constants
+fn node2_inflow(a: f64, b: f64) -> f64 {
+    (a - 5.5).powi(2) + (b - 6.5).powi(2)
+}
+
+#[test]
+fn test_run_batch_matches_individual_runs() {
+    let param_sets = vec![
+        set(&[("c.a", 1.0), ("c.b", 1.0)]),
+        set(&[("c.a", 5.5), ("c.b", 6.5)]),
+        set(&[("c.a", 10.0)]),
+        set(&[("c.b", 0.0)]),
+    ];
+
+    // Reference: the node1 flow from a plain run
+    let mut reference = load_model();
+    reference.configure().unwrap();
+    reference.run().unwrap();
+    let idx = reference.data_cache.get_existing_series_idx("node.node1.ds_1").unwrap();
+    let node1 = reference.data_cache.series[idx].values.clone();
+    let node1_mean = node1.iter().sum::<f64>() / node1.len() as f64;
+
+    // One thread forces every set through the same model clone, so this also checks that
+    // parameters are restored between runs (sets 3 and 4 only touch one constant each).
+    for n_threads in [1, 3] {
+        let mut model = load_model();
+        let options = BatchOptions {
+            series: vec!["node.node2.ds_1".to_string()],
+            return_series: true,
+            n_threads,
+        };
+        let results = model.run_batch(&param_sets, &options).unwrap();
+        assert_eq!(results.len(), 4);
+
+        let expected = [node2_inflow(1.0, 1.0), 0.0, node2_inflow(10.0, 3.0), node2_inflow(2.0, 0.0)];
+        for (i, r) in results.iter().enumerate() {
+            assert_eq!(r.index, i);
+            assert!(r.error.is_none());
+            let s = &r.series[0];
+            assert_eq!(s.name, "node.node2.ds_1");
+            assert_eq!(s.summary.count, node1.len());
+            assert!((s.summary.mean - (node1_mean + expected[i])).abs() < 1e-9);
+            let values = s.values.as_ref().unwrap();
+            assert!((values[0] - (node1[0] + expected[i])).abs() < 1e-9);
+        }
+
+        // The base model is left with its original parameters
+        assert_eq!(model.get_parameter("c.a").unwrap(), 2.0);
+        assert_eq!(model.get_parameter("c.b").unwrap(), 3.0);
+    }
+}
+
+#[test]
+fn test_run_batch_reports_failed_sets_without_stopping() {
+    let mut model = load_model();
+    let param_sets = vec![
+        set(&[("c.a", 1.0)]),
+        set(&[("node.node1.not_a_param", 1.0)]),
+        set(&[("c.a", 3.0)]),
+    ];
+    let results = model.run_batch(&param_sets, &BatchOptions::default()).unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results[0].error.is_none());
+    assert!(results[1].error.is_some());
+    assert!(results[1].series.is_empty());
+    assert!(results[2].error.is_none());
+
+    // Defaults to the model's outputs, without the values
+    assert_eq!(results[0].series.len(), 2);
+    assert!(results[0].series[0].values.is_none());
+}
@@ -0,0 +1,60 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::numerical::opt::OptimisationConfig;
+use crate::run::calibrate_model;
+use crate::tests::test_helpers::TestDir;
+
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-10
+
+[constants]
+c.a = 1.0
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = c.a
+
+[outputs]
+node.a.ds_1
+";
+
+#[test]
+fn test_calibrate_model_in_place() {
+    let dir = TestDir::new("kalix_calibrate_model");
+    let lines: Vec<String> = (1..=10).map(|d| format!("2000-01-{:02},4.0", d)).collect();
+    let observed = dir.write("observed.csv", &format!("Datetime,obs\n{}\n", lines.join("\n")));
+    let config = OptimisationConfig::from_ini(&format!("\
+[optimisation]
+model_file = ./does_not_exist.ini
+objective_expression = term1
+algorithm = SCE
+complexes = 2
+termination_evaluations = 500
+n_threads = 1
+random_seed = 7
+
+[term.term1]
+simulated = node.a.ds_1
+observed_file = {}
+observed_series = 1
+statistic = MAE
+
+[parameters]
+c.a = lin_range(g(1), 0, 10)
+", observed.display())).unwrap();
+
+    // The config's model_file is not used, and the loaded model keeps the best parameters
+    let mut model = IniModelIO::new().read_model_string(MODEL).unwrap();
+    let outcome = calibrate_model(&mut model, &config, None).unwrap();
+    assert!(outcome.best_objective < 1e-3, "{}", outcome.best_objective);
+    assert_eq!(outcome.parameters[0].0, "c.a");
+    assert!((outcome.parameters[0].1 - 4.0).abs() < 1e-3);
+    assert_eq!(model.get_parameter("c.a").unwrap(), outcome.parameters[0].1);
+    assert_eq!(IniModelIO::new().model_to_string(&model), outcome.optimised_model_ini);
+
+    model.run().unwrap();
+    let idx = model.data_cache.get_existing_series_idx("node.a.ds_1").unwrap();
+    assert!(model.data_cache.series[idx].values.iter().all(|v| (v - 4.0).abs() < 1e-3));
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::numerical::opt::{DEConfig, DifferentialEvolution, ObjectiveFunction, Optimizer, OptimisationProblem, ParameterMappingConfig};
+use crate::numerical::opt::objectives::RmseObjective;
+use crate::tests::test_helpers::TestDir;
+
+fn load_model() -> Model {
+    // The input path in this model is relative to the repo root
+    let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+    IniModelIO::new().read_model_string_with_working_directory(&ini, Some(PathBuf::from("."))).unwrap()
+}
+
+/// Against node1's flow as "observed", node2's RMSE is exactly (a - 5.5)^2 + (b - 6.5)^2
+fn problem() -> OptimisationProblem {
+    let mut reference = load_model();
+    reference.configure().unwrap();
+    reference.run().unwrap();
+    let idx = reference.data_cache.get_existing_series_idx("node.node1.ds_1").unwrap();
+    let observed = reference.data_cache.series[idx].clone();
+
+    let par_map = ParameterMappingConfig::from_strings(vec![
+        "c.a = lin_range(g(1), 0.0, 10.0)",
+        "c.b = lin_range(g(2), 0.0, 10.0)",
+    ]).unwrap();
+    OptimisationProblem::single_comparison(
+        load_model(),
+        par_map,
+        observed,
+        "node.node2.ds_1".to_string(),
+        ObjectiveFunction::RMSE(RmseObjective::new()),
+    )
+}
+
+#[test]
+fn test_trace_records_every_evaluation() {
+    let dir = TestDir::new("kalix_trace");
+    let path = dir.join("trace.csv").to_str().unwrap().to_string();
+
+    let mut problem = problem();
+    problem.start_trace(&path).unwrap();
+    let config = DEConfig {
+        population_size: 8,
+        termination_evaluations: 40,
+        seed: Some(2),
+        n_threads: 2,
+        progress_callback: problem.trace_progress(None),
+        ..Default::default()
+    };
+    let result = DifferentialEvolution::new(config).optimize(&mut problem, None);
+    problem.finish_trace().unwrap();
+    assert!(problem.trace.is_none());
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let mut lines = content.lines();
+    assert_eq!(lines.next().unwrap(), "evaluation,generation,c.a,c.b,objective");
+
+    let rows: Vec<Vec<f64>> = lines
+        .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+        .collect();
+    assert_eq!(rows.len(), result.n_evaluations);
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!(row[0], (i + 1) as f64);
+        let expected = (row[2] - 5.5).powi(2) + (row[3] - 6.5).powi(2);
+        assert!((row[4] - expected).abs() < 1e-6, "row {:?}", row);
+    }
+
+    // Initial population is generation 0; later rows follow the DE generation counter
+    assert!(rows[..8].iter().all(|row| row[1] == 0.0));
+    assert_eq!(rows.last().unwrap()[1], 3.0);
+    let best = rows.iter().map(|row| row[4]).fold(f64::INFINITY, f64::min);
+    assert!((best - result.best_objective).abs() < 1e-9);
+}
+
+#[test]
+fn test_trace_rejects_parquet() {
+    let mut problem = problem();
+    let err = problem.start_trace("trace.parquet").unwrap_err();
+    assert!(err.contains("only CSV"), "got: {}", err);
+}
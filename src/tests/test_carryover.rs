@@ -0,0 +1,135 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series_rounded};
+
+// An irrigator orders 3 ML/d from a dam against an allocation of 100 ML a year, with the water
+// year starting in February. January leaves 7 ML unused, which is carried over.
+const IRRIGATION: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-02-10
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 900
+dimensions = 0,    0,    0, 0,
+             10,   1000, 0, 0,
+             10.1, 1001, 0, 1e8
+ds_1 = irrigator
+
+[node.irrigator]
+type = regulated_user
+loc = 0, 10
+order = 3
+
+[allocation]
+water_year = 2
+account.irrigator.storage = dam
+account.irrigator.allocation = 100
+account.irrigator.carryover_limit = 50
+
+[outputs]
+node.irrigator.diversion
+node.dam.volume
+acc.irrigator.allocation
+acc.irrigator.carryover
+acc.irrigator.balance
+acc.irrigator.evap_loss
+acc.irrigator.spill_loss
+";
+
+#[test]
+fn test_unused_allocation_is_carried_over() {
+    let m = run(IRRIGATION).unwrap();
+    let allocation = series_rounded(&m, "acc.irrigator.allocation");
+    let carryover = series_rounded(&m, "acc.irrigator.carryover");
+    assert_eq!((allocation[0], allocation[30], carryover[30]), (97.0, 7.0, 0.0));
+
+    // The carryover is used first in the new year: 3 ML on 1 Feb and 2 Feb, then 1 ML more
+    assert_eq!(carryover[31..34], [4.0, 1.0, 0.0]);
+    assert_eq!(allocation[31..34], [100.0, 100.0, 98.0]);
+    assert_eq!(*allocation.last().unwrap(), 77.0);
+    assert_eq!(series_rounded(&m, "acc.irrigator.balance")[33], 98.0);
+
+    // Nothing is carried over without a carryover limit
+    let m = run(&IRRIGATION.replace("account.irrigator.carryover_limit = 50\n", "")).unwrap();
+    assert!(series_rounded(&m, "acc.irrigator.carryover").iter().all(|&v| v == 0.0));
+    assert_eq!(*series_rounded(&m, "acc.irrigator.allocation").last().unwrap(), 70.0);
+
+    // The limit caps what is carried
+    let m = run(&IRRIGATION.replace("carryover_limit = 50", "carryover_limit = 5")).unwrap();
+    assert_eq!(series_rounded(&m, "acc.irrigator.carryover")[31], 2.0);
+}
+
+#[test]
+fn test_orders_are_limited_to_the_balance() {
+    // 4 ML/d uses the allocation up by 25 January, and the new allocation starts in February
+    let m = run(&IRRIGATION.replace("order = 3", "order = 4")).unwrap();
+    let diversion = series_rounded(&m, "node.irrigator.diversion");
+    assert_eq!(diversion[..25], [4.0; 25]);
+    assert_eq!(diversion[25..31], [0.0; 6]);
+    assert_eq!(diversion[31..], [4.0; 10]);
+
+    // A run starting part way through the water year can be given what is left
+    let m = run(&IRRIGATION.replace("order = 3", "order = 4")
+        .replace("carryover_limit = 50", "carryover_limit = 50\naccount.irrigator.initial_allocation = 8")).unwrap();
+    assert_eq!(series_rounded(&m, "node.irrigator.diversion")[..3], [4.0, 4.0, 0.0]);
+}
+
+#[test]
+fn test_carryover_bears_evaporation_and_spills_first() {
+    // A 1 km2 surface evaporating 2 mm/d loses 2 ML/d
+    let evaporating = IRRIGATION
+        .replace("0,    0,    0, 0,\n             10,   1000, 0, 0,\n             10.1, 1001, 0, 1e8",
+                 "0,    0,    0, 0,\n             0.1,  1,    1, 0,\n             10,   1000, 1, 0,\n             10.1, 1001, 1, 1e8")
+        .replace("ds_1 = irrigator", "evap = 2\nds_1 = irrigator");
+    let m = run(&evaporating).unwrap();
+    let volume = series_rounded(&m, "node.dam.volume");
+    let evap_loss = series_rounded(&m, "acc.irrigator.evap_loss");
+    assert_eq!(evap_loss[30], 0.0);
+    assert!((evap_loss[31] - 2.0 * 7.0 / volume[30]).abs() < 1e-6, "{:?}", &evap_loss[29..33]);
+    assert!((series_rounded(&m, "acc.irrigator.carryover")[31] - (4.0 - evap_loss[31])).abs() < 1e-6);
+
+    // A flood in February fills the dam, and the carried-over water is the first to spill
+    let flooding = IRRIGATION.replace("[node.irrigator]", "\
+[node.river]
+type = inflow
+loc = 0, -10
+inflow = if(sim.month == 2, 300, 0)
+ds_1 = dam
+
+[node.irrigator]");
+    let m = run(&flooding).unwrap();
+    let spill_loss = series_rounded(&m, "acc.irrigator.spill_loss");
+    assert_eq!((spill_loss[30], spill_loss[31]), (0.0, 7.0));
+    assert!(series_rounded(&m, "acc.irrigator.carryover")[31..].iter().all(|&v| v == 0.0));
+    assert_eq!(series_rounded(&m, "acc.irrigator.allocation")[31], 97.0);
+}
+
+#[test]
+fn test_allocation_round_trip_and_errors() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_string(IRRIGATION).unwrap();
+    let saved = ini_io.model_to_string(&ini_io.read_model_string(&ini_io.model_to_string(&m)).unwrap());
+    for line in ["water_year = 2", "account.irrigator.storage = dam", "account.irrigator.allocation = 100",
+                 "account.irrigator.carryover_limit = 50"] {
+        assert!(saved.contains(line), "missing '{}' in:\n{}", line, saved);
+    }
+    assert!(!saved.contains("initial_carryover"), "got:\n{}", saved);
+
+    let err = |ini: String| run(&ini).err().unwrap();
+    let e = err(IRRIGATION.replace("account.irrigator.storage = dam\n", ""));
+    assert!(e.contains("Allocation account 'irrigator' has no storage"), "{}", e);
+    let e = err(IRRIGATION.replace("storage = dam", "storage = irrigator"));
+    assert!(e.contains("is held in 'irrigator', which is not a storage node"), "{}", e);
+    let e = err(IRRIGATION.replace("account.irrigator.", "account.dam."));
+    assert!(e.contains("'dam' must be named after a regulated user node"), "{}", e);
+    let e = err(IRRIGATION.replace("account.irrigator.allocation = 100\n", ""));
+    assert!(e.contains("has no allocation"), "{}", e);
+    let e = err(IRRIGATION.replace("carryover_limit = 50", "carryover_limit = -5"));
+    assert!(e.contains("Invalid 'account.irrigator.carryover_limit' value '-5'"), "{}", e);
+    let e = err(IRRIGATION.replace("carryover_limit", "carryover_cap"));
+    assert!(e.contains("Unknown allocation account property 'carryover_cap'"), "{}", e);
+    let e = err(IRRIGATION.replace("water_year = 2", "water_year = 13"));
+    assert!(e.contains("Invalid water year start month 13"), "{}", e);
+}
@@ -0,0 +1,100 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::configuration::CircularReferences;
+use crate::model::Model;
+use crate::tests::test_helpers::series;
+
+// A pump diverts half of the flow at the gauge below it. Without an offset that is the flow
+// from the same timestep, which depends on the pump's own diversion.
+const PUMP_ABOVE_GAUGE: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = 10 * sim.day
+ds_1 = pump
+
+[node.pump]
+type = unregulated_user
+loc = 0, 10
+demand = 0.5 * node.gauge.dsflow
+ds_1 = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 20
+
+[outputs]
+node.pump.diversion
+node.gauge.dsflow
+";
+
+fn configure(ini: &str) -> Result<Model, String> {
+    let mut m = IniModelIO::new().read_model_string(ini)?;
+    m.configure()?;
+    Ok(m)
+}
+
+#[test]
+fn test_circular_reference_is_an_error_with_its_path() {
+    let err = configure(PUMP_ABOVE_GAUGE).err().unwrap();
+    assert!(err.starts_with("Circular reference between nodes: gauge → pump → gauge. \
+        'demand' on node 'pump' uses node.gauge.dsflow from the same timestep."), "{}", err);
+    assert!(err.contains("node.gauge.dsflow[-1, 0]"), "{}", err);
+
+    // Referring to the previous timestep explicitly is fine
+    assert!(configure(&PUMP_ABOVE_GAUGE.replace("node.gauge.dsflow", "node.gauge.dsflow[-1, 0]")).is_ok());
+}
+
+#[test]
+fn test_circular_reference_can_use_previous_value() {
+    let explicit = PUMP_ABOVE_GAUGE.replace("node.gauge.dsflow", "node.gauge.dsflow[-1, 0]");
+    let allowed = PUMP_ABOVE_GAUGE.replace("end = 2000-01-03", "end = 2000-01-03\ncircular_references = previous_value");
+
+    let mut m = configure(&allowed).unwrap();
+    assert_eq!(m.configuration.circular_references, CircularReferences::PreviousValue);
+    m.run().unwrap();
+    let mut expected = configure(&explicit).unwrap();
+    expected.run().unwrap();
+    assert_eq!(series(&m, "node.pump.diversion"), vec![0.0, 5.0, 7.5]);
+    assert_eq!(series(&m, "node.pump.diversion"), series(&expected, "node.pump.diversion"));
+
+    // The expression is saved as written, along with the option
+    let saved = IniModelIO::new().model_to_string(&m);
+    assert!(saved.contains("demand = 0.5 * node.gauge.dsflow\n"), "{}", saved);
+    assert!(saved.contains("circular_references = previous_value"), "{}", saved);
+
+    let err = configure(&PUMP_ABOVE_GAUGE.replace("end = 2000-01-03", "end = 2000-01-03\ncircular_references = lag")).err().unwrap();
+    assert!(err.starts_with("Error on line 4: Unknown circular_references option 'lag'"), "{}", err);
+}
+
+#[test]
+fn test_nodes_run_after_the_nodes_they_refer_to() {
+    // The pump is on its own branch, defined before the inflow whose flow it uses
+    let ini = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.pump]
+type = unregulated_user
+loc = 10, 0
+demand = 0.5 * node.a.dsflow
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = 10 * sim.day
+
+[outputs]
+node.pump.demand
+";
+    let mut m = configure(ini).unwrap();
+    m.run().unwrap();
+    let a = m.get_node_idx("a").unwrap();
+    let pump = m.get_node_idx("pump").unwrap();
+    assert_eq!(m.execution_order(), &[a, pump]);
+    assert_eq!(series(&m, "node.pump.demand"), vec![5.0, 10.0, 15.0]);
+}
@@ -0,0 +1,79 @@
+use crate::data_management::compressed_series::{CompressedSeries, BLOCK_SIZE};
+use crate::data_management::data_cache::Recording;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tests::test_helpers::run;
+
+// Four years of daily steps, so results span several compressed blocks. The pump reads the
+// inflow from 1500 days ago, which is in a block that has to be decompressed.
+const LONG_RUN: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2003-12-31
+compress_results = true
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = 10 * sim.day + sim.month
+ds_1 = pump
+
+[node.pump]
+type = unregulated_user
+loc = 0, 10
+demand = 0.1 * node.a.dsflow[-1500, 0] + 0.2 * node.a.dsflow[-1, 0]
+
+[outputs]
+node.a.dsflow
+node.pump.diversion
+node.pump.dsflow
+";
+
+#[test]
+fn test_compressed_series_round_trip() {
+    let values: Vec<f64> = (0..3 * BLOCK_SIZE + 10).map(|i| (i as f64 * 0.01).sin() * 100.0).collect();
+    let mut series = CompressedSeries::from_values(&values);
+    assert_eq!(series.len(), values.len());
+    assert_eq!(series.to_values(), values);
+    for step in [0, BLOCK_SIZE - 1, BLOCK_SIZE, 2 * BLOCK_SIZE + 5, 3 * BLOCK_SIZE + 9] {
+        assert_eq!(series.get(step), values[step]);
+    }
+
+    // Writing past the end fills the gap with NaN, and writing an earlier step starts again from there
+    series.set(values.len() + 2, 1.0);
+    assert!(series.get(values.len()).is_nan());
+    series.set(5, -1.0);
+    assert_eq!(series.len(), 6);
+    assert_eq!(series.to_values(), [&values[..5], &[-1.0]].concat());
+
+    // A constant series takes a small fraction of the memory of plain values
+    let constant = CompressedSeries::from_values(&vec![2.5; 20 * BLOCK_SIZE]);
+    assert!(constant.memory_bytes() * 4 < 20 * BLOCK_SIZE * size_of::<f64>(), "{}", constant.memory_bytes());
+}
+
+#[test]
+fn test_compressed_results_match_plain_results() {
+    let mut compressed = run(LONG_RUN).unwrap();
+    let plain = run(&LONG_RUN.replace("compress_results = true", "compress_results = false")).unwrap();
+
+    let idx = compressed.data_cache.get_existing_series_idx("node.pump.diversion").unwrap();
+    assert_eq!(compressed.data_cache.recording(idx), Recording::Compressed);
+    // Only the value being written is held uncompressed
+    assert_eq!(compressed.data_cache.series[idx].values.len(), 2);
+
+    let outputs = |m: &Model| m.collect_output_series().iter()
+        .map(|ts| (ts.values.clone(), ts.timestamps.clone()))
+        .collect::<Vec<_>>();
+    let expected = outputs(&plain);
+    assert_eq!(expected.len(), 3);
+    assert_eq!(expected[0].0.len(), 1461);
+    assert_eq!(outputs(&compressed), expected);
+
+    // A second run records over the first
+    compressed.run().unwrap();
+    assert_eq!(outputs(&compressed), expected);
+
+    // The option is saved with the model
+    let saved = IniModelIO::new().model_to_string(&compressed);
+    assert!(saved.contains("compress_results = true"), "{}", saved);
+}
@@ -0,0 +1,179 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+const SALINE_NETWORK: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-12-31
+
+[constituents]
+salt = conservative
+
+[node.saline]
+type = inflow
+loc = 0, 0
+inflow = 10
+conc_salt = 100
+ds_1 = fresh
+
+[node.fresh]
+type = inflow
+loc = 0, 10
+inflow = 30
+ds_1 = town
+
+[node.town]
+type = unregulated_user
+loc = 0, 20
+demand = 20
+ds_1 = reach
+
+[node.reach]
+type = routing
+loc = 0, 30
+lag = 2
+ds_1 = lake
+
+[node.lake]
+type = storage
+loc = 0, 40
+initial_volume = 100
+evap = 5
+dimensions = 90,   0, 0, 0,
+             91,   100, 1, 0,
+             91.1, 101, 1, 1e8,
+             92,   102, 1, 1e8
+
+[outputs]
+node.fresh.constituent.salt
+node.town.constituent.salt
+node.reach.constituent.salt
+node.lake.constituent.salt
+node.reach.volume
+node.lake.volume
+node.lake.dsflow
+";
+
+#[test]
+fn test_conservative_constituent_mixes_through_network() {
+    let m = run(SALINE_NETWORK).unwrap();
+
+    // 10 ML at 100 mixed with 30 ML of fresh water is 25. The user and the reach take
+    // water at that concentration without changing it.
+    for node in ["fresh", "town", "reach"] {
+        let values = series(&m, &format!("node.{}.constituent.salt", node));
+        assert!(values.iter().all(|c| (c - 25.0).abs() < 1e-9), "{}: {:?}", node, &values[..5]);
+    }
+
+    // The lake starts fresh and full. 20 ML/d flows in, 5 ML/d evaporates and 15 ML/d
+    // spills, so the salt concentrates towards 25 * 20 / 15.
+    let lake = series(&m, "node.lake.constituent.salt");
+    assert!(lake[0] < 25.0);
+    assert!((lake.last().unwrap() - 25.0 * 20.0 / 15.0).abs() < 1e-6, "lake: {}", lake.last().unwrap());
+
+    // Salt is conserved: what entered the reach either spilled from the lake or is still
+    // held in the reach or the lake
+    let salt_in = lake.len() as f64 * 20.0 * 25.0;
+    let lake_dsflow = series(&m, "node.lake.dsflow");
+    let salt_out: f64 = lake.iter().zip(&lake_dsflow).map(|(c, q)| c * q).sum();
+    let salt_stored = 25.0 * series(&m, "node.reach.volume").last().unwrap()
+        + lake.last().unwrap() * series(&m, "node.lake.volume").last().unwrap();
+    assert!((salt_in - salt_out - salt_stored).abs() < 1e-9 * salt_in,
+            "in {}, out {}, stored {}", salt_in, salt_out, salt_stored);
+}
+
+#[test]
+fn test_constituents_round_trip_and_are_checked() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_string(SALINE_NETWORK).unwrap();
+    let saved = ini_io.model_to_string(&m);
+    assert!(saved.contains("salt = conservative"), "got:\n{}", saved);
+    assert!(saved.contains("conc_salt = 100"), "got:\n{}", saved);
+
+    let undeclared = SALINE_NETWORK.replace("conc_salt", "conc_nitrogen");
+    let err = ini_io.read_model_string(&undeclared).err().unwrap();
+    assert!(err.contains("nitrogen"), "got: {}", err);
+    let unknown_kind = SALINE_NETWORK.replace("salt = conservative", "salt = radioactive");
+    assert!(ini_io.read_model_string(&unknown_kind).is_err());
+    let bad_option = SALINE_NETWORK.replace("salt = conservative", "salt = conservative, colour 3");
+    assert!(ini_io.read_model_string(&bad_option).is_err());
+
+    let options = SALINE_NETWORK.replace("salt = conservative", "salt = Decay 0.05, DWC 2, settling 0.3");
+    let m = ini_io.read_model_string(&options).unwrap();
+    assert_eq!(m.constituents.constituents[0].definition(), "decay 0.05, settling 0.3, dwc 2");
+    let reloaded = ini_io.read_model_string(&ini_io.model_to_string(&m)).unwrap();
+    assert_eq!(reloaded.constituents.constituents, m.constituents.constituents);
+
+    // Without constituents, nothing is recorded
+    let plain = SALINE_NETWORK.replace("[constituents]\nsalt = conservative\n", "").replace("conc_salt = 100\n", "");
+    let m = run(&plain).unwrap();
+    assert!(m.constituents.is_empty());
+    assert!(series(&m, "node.town.constituent.salt").is_empty());
+}
+
+#[test]
+fn test_decay_and_settling_in_storage() {
+    let ini = SALINE_NETWORK
+        .replace("salt = conservative", "salt = conservative\ntn = decay 0.1\ntss = conservative, settling 0.05")
+        .replace("conc_salt = 100", "conc_salt = 100\nconc_tn = 100\nconc_tss = 100")
+        .replace("node.lake.constituent.salt", "node.lake.constituent.salt\nnode.reach.constituent.tn\nnode.reach.constituent.tss\nnode.lake.constituent.tn\nnode.lake.constituent.tss");
+    let m = run(&ini).unwrap();
+
+    // Settling only happens in storages, so the reach passes tss on unchanged
+    let reach_tss = series(&m, "node.reach.constituent.tss");
+    assert!(reach_tss.iter().all(|c| (c - 25.0).abs() < 1e-9));
+
+    // At steady state the lake holds V and releases 15 ML/d of the 20 ML/d inflow, with a
+    // fraction r of the held mass retained each day: C * (V + 15) = C * V * r + 20 * C_in.
+    // The lake has an area of 1 km2, so its depth in metres is V / 1000.
+    let v = *series(&m, "node.lake.volume").last().unwrap();
+    let steady = |c_in: f64, r: f64| 20.0 * c_in / (v + 15.0 - v * r);
+    let reach_tn = *series(&m, "node.reach.constituent.tn").last().unwrap();
+    assert!(reach_tn < 25.0);
+    let lake_tn = *series(&m, "node.lake.constituent.tn").last().unwrap();
+    assert!((lake_tn - steady(reach_tn, (-0.1f64).exp())).abs() < 1e-6, "tn: {}", lake_tn);
+    let lake_tss = *series(&m, "node.lake.constituent.tss").last().unwrap();
+    assert!((lake_tss - steady(25.0, (-0.05 / (v / 1000.0)).exp())).abs() < 1e-6, "tss: {}", lake_tss);
+
+    // Salt is unaffected
+    let lake_salt = *series(&m, "node.lake.constituent.salt").last().unwrap();
+    assert!((lake_salt - steady(25.0, 1.0)).abs() < 1e-6);
+}
+
+#[test]
+fn test_runoff_carries_emc_and_dwc() {
+    let catchment = |definition: &str| format!("\
+[kalix]
+start = 2000-01-01
+end = 2000-06-30
+
+[constituents]
+tss = {}
+
+[node.catchment]
+type = gr4j
+loc = 0, 0
+rain = 5
+evap = 2
+area = 10
+params = 350, 0, 90, 1.7
+
+[outputs]
+node.catchment.dsflow
+node.catchment.constituent.tss
+", definition);
+
+    // With equal concentrations the split between quickflow and baseflow does not matter
+    let m = run(&catchment("conservative, emc 40, dwc 40")).unwrap();
+    let flow = series(&m, "node.catchment.dsflow");
+    let tss = series(&m, "node.catchment.constituent.tss");
+    for (q, c) in flow.iter().zip(&tss) {
+        assert!(*q <= 0.0 || (c - 40.0).abs() < 1e-9, "flow {} with tss {}", q, c);
+    }
+
+    // Otherwise the concentration lies between the two
+    let m = run(&catchment("conservative, emc 100, dwc 10")).unwrap();
+    let tss = series(&m, "node.catchment.constituent.tss");
+    let last = *tss.last().unwrap();
+    assert!(last > 10.0 && last < 100.0, "tss: {}", last);
+}
@@ -1,4 +1,5 @@
-use crate::io::csv_io::{read_ts, write_ts};
+use crate::io::csv_io::{read_ts, write_ts, write_ts_wide, CsvWriteOptions};
+use crate::tests::test_helpers::TestDir;
 use std::io::Write;
 
 
@@ -318,3 +319,87 @@ fn test_hourly_model_end_to_end_preserves_step_size() {
         "Output should preserve hour-of-day in every row. Got:\n{}", written
     );
 }
+
+#[test]
+fn test_summarise_series_file() {
+    let csv = "Time,a,b\n2000-01-01,1,\n2000-01-02,3,5\n";
+    let dir = TestDir::new("kalix_test_summarise_series_file");
+    let path = dir.write("series.csv", csv);
+
+    let summaries = crate::run::summarise_series_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(summaries.len(), 2);
+    let (name, a) = &summaries[0];
+    assert_eq!(name, "a");
+    assert_eq!((a.count, a.mean, a.min, a.max, a.sum), (2, 2.0, 1.0, 3.0, 4.0));
+    let (_, b) = &summaries[1];
+    assert_eq!(b.count, 1);
+}
+
+
+#[test]
+fn test_wide_csv_units_missing_token_and_yearly_files() {
+    use crate::timeseries::Timeseries;
+    use crate::tid::utils::date_string_to_u64_flexible;
+
+    let mut flow = Timeseries::new_daily();
+    flow.name = "node.gauge.ds_1".to_string();
+    let mut storage = Timeseries::new_daily();
+    storage.name = "node.dam.volume".to_string();
+    let start = date_string_to_u64_flexible("2019-12-30").unwrap().0;
+    for (i, (q, v)) in [(1.5, 100.0), (f64::NAN, 101.0), (2.0, 102.0), (2.5, f64::NAN)].iter().enumerate() {
+        flow.push(start + i as u64 * 86400, *q);
+        storage.push(start + i as u64 * 86400, *v);
+    }
+
+    let dir = TestDir::new("kalix_wide_csv");
+    let options = CsvWriteOptions {
+        date_header: "Date".to_string(),
+        units: Some(vec!["ML/d".to_string(), "ML".to_string()]),
+        missing_value: String::new(),
+        split_by_year: true,
+        ..Default::default()
+    };
+    let written = write_ts_wide(dir.join("outputs.csv").to_str().unwrap(), &[&flow, &storage], &options).unwrap();
+    assert_eq!(written, vec![
+        dir.join("outputs_2019.csv").to_string_lossy().to_string(),
+        dir.join("outputs_2020.csv").to_string_lossy().to_string(),
+    ]);
+
+    let first_year = std::fs::read_to_string(&written[0]).unwrap();
+    assert_eq!(first_year, "Date,node.gauge.ds_1,node.dam.volume\r\nUnits,ML/d,ML\r\n\
+                           2019-12-30,1.5,100\r\n2019-12-31,,101\r\n");
+
+    // The units row is skipped on reading, and blank values read as missing
+    let series = read_ts(&written[1]).unwrap();
+    assert_eq!(series[0].name, "node.gauge.ds_1");
+    assert_eq!(series[0].values, vec![2.0, 2.5]);
+    assert_eq!(series[1].values[0], 102.0);
+    assert!(series[1].values[1].is_nan());
+
+    // Units must be given for every series
+    let options = CsvWriteOptions { units: Some(vec!["ML/d".to_string()]), ..Default::default() };
+    assert!(write_ts_wide("unused.csv", &[&flow, &storage], &options).is_err());
+}
+
+
+#[test]
+fn test_tsv_and_ssv_layouts() {
+    use crate::timeseries::Timeseries;
+
+    let mut ts = Timeseries::new_daily();
+    ts.name = "node.gauge.ds_1".to_string();
+    let start = crate::tid::utils::date_string_to_u64_flexible("2020-01-01").unwrap().0;
+    ts.push(start, 1.25);
+    ts.push(start + 86400, f64::NAN);
+
+    let dir = TestDir::new("kalix_delimited");
+    let tsv = dir.join("outputs.tsv").to_string_lossy().to_string();
+    let ssv = dir.join("outputs.ssv").to_string_lossy().to_string();
+    write_ts_wide(&tsv, &[&ts], &CsvWriteOptions::for_path(&tsv)).unwrap();
+    write_ts_wide(&ssv, &[&ts], &CsvWriteOptions::for_path(&ssv)).unwrap();
+    let tsv = std::fs::read_to_string(&tsv).unwrap();
+    let ssv = std::fs::read_to_string(&ssv).unwrap();
+
+    assert_eq!(tsv, "Time\tnode.gauge.ds_1\r\n2020-01-01\t1.25\r\n2020-01-02\tNaN\r\n");
+    assert_eq!(ssv, "Time;node.gauge.ds_1\r\n2020-01-01;1,25\r\n2020-01-02;NaN\r\n");
+}
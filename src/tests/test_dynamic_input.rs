@@ -1087,6 +1087,274 @@ fn test_dynamic_input_forward_lookup_rejected_for_node() {
     assert!(result.unwrap_err().contains("Forward lookup not supported"));
 }
 
+// ============================================================================
+// Tests for compiled expressions
+// ============================================================================
+
+#[test]
+fn test_compiled_expression_argument_order() {
+    let mut data_cache = DataCache::new();
+    let start_timestamp: u64 = wrap_to_u64(1577836800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+
+    let idx = data_cache.get_or_add_new_series("data.x", true);
+    let mut ts = Timeseries::new_daily();
+    ts.start_timestamp = start_timestamp;
+    ts.push_value(3.0);
+    data_cache.series[idx] = ts;
+    data_cache.set_current_step(0);
+
+    // Operands and arguments must come off the stack in the order they were written
+    let cases = [
+        ("data.x - 1", 2.0),
+        ("10 / data.x - 10 / 5", 10.0 / 3.0 - 2.0),
+        ("pow(2, data.x)", 8.0),
+        ("atan2(data.x, 0) * 2", std::f64::consts::PI),
+        ("if(data.x > 2, min(data.x, 10, 4) - -1, 99)", 4.0),
+        ("-(data.x - 5) * -(2)", -4.0),
+        ("max(1, 2, 3, 4, 5, data.x * 2) + avg(data.x, 5)", 10.0),
+    ];
+    for (expression, expected) in cases {
+        let input = DynamicInput::from_string(expression, &mut data_cache, true, None)
+            .unwrap_or_else(|e| panic!("Failed to parse {}: {}", expression, e));
+        assert!((input.get_value(&data_cache) - expected).abs() < 1e-12, "{}", expression);
+    }
+}
+
+#[test]
+fn test_compiled_expression_deep_nesting() {
+    let mut data_cache = DataCache::new();
+    let start_timestamp: u64 = wrap_to_u64(1577836800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+
+    let idx = data_cache.get_or_add_new_series("data.x", true);
+    let mut ts = Timeseries::new_daily();
+    ts.start_timestamp = start_timestamp;
+    ts.push_value(1.0);
+    data_cache.series[idx] = ts;
+    data_cache.set_current_step(0);
+
+    // Right-nested sums keep every operand on the stack, deeper than the fixed-size stack
+    let expression = format!("{}data.x{}", "data.x + (".repeat(50), ")".repeat(50));
+    let input = DynamicInput::from_string(&expression, &mut data_cache, true, None).unwrap();
+    assert_eq!(input.get_value(&data_cache), 51.0);
+}
+
+#[test]
+fn test_compiled_expression_unknown_function() {
+    let mut data_cache = DataCache::new();
+    let result = DynamicInput::from_string("foo(data.x) + 1", &mut data_cache, true, None);
+    assert!(result.unwrap_err().contains("Unknown function 'foo'"));
+}
+
+// ============================================================================
+// Tests for precomputed expressions
+// ============================================================================
+
+#[test]
+fn test_precomputed_expressions() {
+    let mut data_cache = DataCache::new();
+    let start_timestamp: u64 = wrap_to_u64(1577836800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+
+    let idx = data_cache.get_or_add_new_series("data.x", true);
+    let mut ts = Timeseries::new_daily();
+    ts.start_timestamp = start_timestamp;
+    for v in [1.0, 2.0, 3.0, 4.0] {
+        ts.push_value(v);
+    }
+    data_cache.series[idx] = ts;
+
+    // Inputs, constants and sim.* can be worked out before the run; node results cannot
+    let input = DynamicInput::from_string("data.x * 2 + sim.day", &mut data_cache, true, None).unwrap();
+    let same = DynamicInput::from_string("data.x * 2 + sim.day", &mut data_cache, true, None).unwrap();
+    let stateful = DynamicInput::from_string("accumulate(data.x)", &mut data_cache, true, None).unwrap();
+    let node = DynamicInput::from_string("data.x + node.a.dsflow", &mut data_cache, true, None).unwrap();
+    assert!(input.is_precomputed());
+    assert!(same.is_precomputed());
+    assert!(stateful.is_precomputed());
+    assert!(!node.is_precomputed());
+    assert_eq!(data_cache.precomputed.len(), 2);
+
+    // Until the cache is filled the expression is evaluated directly
+    data_cache.set_current_step(1);
+    assert_eq!(input.get_value(&data_cache), 6.0);
+
+    data_cache.precompute_expressions(4);
+    assert_eq!(data_cache.current_step, 0);
+
+    // Changing the input afterwards shows the values come from the cache
+    data_cache.series[idx].values[2] = 100.0;
+    let mut results = vec![];
+    for step in 0..4 {
+        data_cache.set_current_step(step);
+        results.push((input.get_value(&data_cache), same.get_value(&data_cache), stateful.get_value(&data_cache)));
+    }
+    assert_eq!(results, vec![(3.0, 3.0, 1.0), (6.0, 6.0, 3.0), (9.0, 9.0, 6.0), (12.0, 12.0, 10.0)]);
+
+    // Parsing the expression again (e.g. when a node is re-initialised) discards the old values
+    DynamicInput::from_string("data.x * 2 + sim.day", &mut data_cache, true, None).unwrap();
+    data_cache.set_current_step(2);
+    assert_eq!(input.get_value(&data_cache), 203.0);
+}
+
+// ============================================================================
+// Tests for rolling-window functions
+// ============================================================================
+
+#[test]
+fn test_window_functions() {
+    let mut data_cache = DataCache::new();
+    let start_timestamp: u64 = wrap_to_u64(1577836800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+
+    let idx = data_cache.get_or_add_new_series("data.flow", true);
+    let mut ts = Timeseries::new_daily();
+    ts.start_timestamp = start_timestamp;
+    for v in [10.0, 40.0, f64::NAN, 20.0, 30.0] {
+        ts.push_value(v);
+    }
+    data_cache.series[idx] = ts;
+
+    // The window covers the previous timesteps only, skipping missing values
+    data_cache.set_current_step(4);
+    let cases = [
+        ("mavg_last(data.flow, 3)", 30.0),
+        ("sum_last(data.flow, 3)", 60.0),
+        ("min_last(data.flow, 3)", 20.0),
+        ("MAX_LAST(data.flow, 3)", 40.0),
+        ("mavg_last(data.flow, 1)", 20.0),
+        ("if(mavg_last(data.flow, 2) > 15, 1, 0) + data.flow", 31.0),
+    ];
+    for (expression, expected) in cases {
+        let input = DynamicInput::from_string(expression, &mut data_cache, true, None)
+            .unwrap_or_else(|e| panic!("Failed to parse {}: {}", expression, e));
+        assert_eq!(input.get_value(&data_cache), expected, "{}", expression);
+    }
+
+    // Timesteps before the start of the data are ignored, and an empty window is NaN
+    let input = DynamicInput::from_string("sum_last(data.flow, 7)", &mut data_cache, true, None).unwrap();
+    data_cache.set_current_step(2);
+    assert_eq!(input.get_value(&data_cache), 50.0);
+    data_cache.set_current_step(0);
+    assert!(input.get_value(&data_cache).is_nan());
+}
+
+#[test]
+fn test_window_functions_node_reference() {
+    let mut data_cache = DataCache::new();
+    let start_timestamp: u64 = wrap_to_u64(1577836800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+
+    let input = DynamicInput::from_string("mavg_last(node.dam.volume, 2)", &mut data_cache, true, None)
+        .expect("Failed to parse window function on node output");
+    let idx = data_cache.get_series_idx("node.dam.volume", false).unwrap();
+    assert!(!data_cache.get_critical_input_names().contains(&"node.dam.volume"));
+
+    for (step, volume) in [100.0, 200.0, 400.0].into_iter().enumerate() {
+        data_cache.set_current_step(step);
+        data_cache.add_value_at_index(idx, volume);
+    }
+    data_cache.set_current_step(3);
+    assert_eq!(input.get_value(&data_cache), 300.0);
+}
+
+#[test]
+fn test_window_functions_invalid_arguments() {
+    let mut data_cache = DataCache::new();
+    for expression in [
+        "mavg_last(data.flow)",
+        "mavg_last(data.flow, 0)",
+        "mavg_last(data.flow, 2.5)",
+        "mavg_last(data.flow, -3)",
+        "mavg_last(data.flow * 2, 3)",
+        "sum_last(c.limit, 3)",
+        "sum_last(sim.month, 3)",
+        "sum_last(data.flow[-1, 0], 3)",
+    ] {
+        assert!(DynamicInput::from_string(expression, &mut data_cache, true, None).is_err(), "{}", expression);
+    }
+}
+
+// ============================================================================
+// Tests for stateful functions
+// ============================================================================
+
+#[test]
+fn test_accumulate() {
+    let mut data_cache = DataCache::new();
+    let start_timestamp: u64 = wrap_to_u64(1577836800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+
+    let idx = data_cache.get_or_add_new_series("data.flow", true);
+    let mut ts = Timeseries::new_daily();
+    ts.start_timestamp = start_timestamp;
+    for v in [10.0, 20.0, f64::NAN, 30.0, 40.0] {
+        ts.push_value(v);
+    }
+    data_cache.series[idx] = ts;
+
+    // Restarts on the fourth timestep, and missing values add nothing
+    let input = DynamicInput::from_string("accumulate(data.flow, sim.step == 3)", &mut data_cache, true, None)
+        .expect("Failed to parse accumulate");
+    let mut results = vec![];
+    for step in 0..5 {
+        data_cache.set_current_step(step);
+        results.push(input.get_value(&data_cache));
+        // Evaluating again in the same timestep gives the same value
+        assert_eq!(input.get_value(&data_cache), results[step]);
+    }
+    assert_eq!(results, vec![10.0, 30.0, 30.0, 30.0, 70.0]);
+
+    // A new run starts from zero
+    data_cache.set_current_step(0);
+    assert_eq!(input.get_value(&data_cache), 10.0);
+}
+
+#[test]
+fn test_counter() {
+    let mut data_cache = DataCache::new();
+    let start_timestamp: u64 = wrap_to_u64(1577836800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+
+    let idx = data_cache.get_or_add_new_series("data.rain", true);
+    let mut ts = Timeseries::new_daily();
+    ts.start_timestamp = start_timestamp;
+    for v in [0.0, 15.0, 0.0, 2.0, 0.0, 12.0, 0.0] {
+        ts.push_value(v);
+    }
+    data_cache.series[idx] = ts;
+
+    // Days since the last rain over 10 mm
+    let since_rain = DynamicInput::from_string("counter(data.rain <= 10, data.rain > 10)", &mut data_cache, true, None)
+        .expect("Failed to parse counter");
+    // Counter with no variables and no reset counts timesteps
+    let steps = DynamicInput::from_string("counter(1)", &mut data_cache, true, None)
+        .expect("Failed to parse counter");
+    let mut results = vec![];
+    for step in 0..7 {
+        data_cache.set_current_step(step);
+        results.push(since_rain.get_value(&data_cache));
+        assert_eq!(steps.get_value(&data_cache), (step + 1) as f64);
+    }
+    assert_eq!(results, vec![1.0, 0.0, 1.0, 2.0, 3.0, 0.0, 1.0]);
+}
+
+#[test]
+fn test_stateful_functions_invalid_arguments() {
+    let mut data_cache = DataCache::new();
+    for expression in ["accumulate()", "counter(data.a, data.b, data.c)"] {
+        assert!(DynamicInput::from_string(expression, &mut data_cache, true, None).is_err(), "{}", expression);
+    }
+}
+
 // ============================================================================
 // Tests for sim.* namespace (simulation context variables)
 // ============================================================================
@@ -1301,6 +1569,58 @@ fn test_sim_with_data_reference() {
     assert_eq!(input.get_value(&data_cache), 100.0);
 }
 
+#[test]
+fn test_sim_days_in_month_and_leap_year() {
+    let mut data_cache = DataCache::new();
+    // 2020-02-10 00:00:00 UTC
+    let start_timestamp: u64 = wrap_to_u64(1581292800);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+    data_cache.set_current_step(0);
+
+    let days = DynamicInput::from_string("sim.days_in_month", &mut data_cache, true, None)
+        .expect("Failed to parse sim.days_in_month");
+    let leap = DynamicInput::from_string("sim.is_leap_year", &mut data_cache, true, None)
+        .expect("Failed to parse sim.is_leap_year");
+
+    assert_eq!(days.get_value(&data_cache), 29.0);
+    assert_eq!(leap.get_value(&data_cache), 1.0);
+}
+
+#[test]
+fn test_sim_time_functions() {
+    let mut data_cache = DataCache::new();
+    // 2020-06-15 12:00:00 UTC, then three days on to 2020-06-18
+    let start_timestamp: u64 = wrap_to_u64(1592222400);
+    data_cache.initialize(start_timestamp);
+    data_cache.set_start_and_stepsize(start_timestamp, 86400);
+    data_cache.set_current_step(3);
+
+    // Function-call forms are the same as the sim.* variables
+    let cases = [
+        ("year()", 2020.0),
+        ("Month()", 6.0),
+        ("day()", 18.0),
+        ("day_of_year()", 170.0),
+        ("days_in_month()", 30.0),
+        ("is_leap_year()", 1.0),
+        ("step()", 3.0),
+        ("if(month() >= 6 && month() <= 8, 0.5, 1.0)", 0.5),
+        ("2 * month() + day_of_year()", 182.0),
+    ];
+    for (expression, expected) in cases {
+        let input = DynamicInput::from_string(expression, &mut data_cache, true, None)
+            .unwrap_or_else(|e| panic!("Failed to parse {}: {}", expression, e));
+        assert_eq!(input.get_value(&data_cache), expected, "{}", expression);
+    }
+
+    // Time-dependent expressions must not be folded into a constant
+    let input = DynamicInput::from_string("if(month() <= 6, 1, 0)", &mut data_cache, true, None).unwrap();
+    assert_eq!(input.get_value(&data_cache), 1.0);
+    data_cache.set_current_step(30);
+    assert_eq!(input.get_value(&data_cache), 0.0);
+}
+
 // ============================================================================
 // Tests for "this." self-reference expansion
 // ============================================================================
@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::nodes::Link;
+use crate::ordering::execution_order::topological_sort;
+
+fn links(pairs: &[(usize, usize)]) -> Vec<Link> {
+    pairs.iter().map(|&(from, to)| Link::new(from, to, 0, 0)).collect()
+}
+
+#[test]
+fn test_topological_sort_keeps_definition_order_when_already_sorted() {
+    let order = topological_sort(4, &links(&[(0, 2), (1, 2), (2, 3)])).unwrap();
+    assert_eq!(order, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn test_topological_sort_reorders_downstream_first_definitions() {
+    // 3 -> 1 -> 0, and 2 -> 0
+    let order = topological_sort(4, &links(&[(3, 1), (1, 0), (2, 0)])).unwrap();
+    assert_eq!(order, vec![2, 3, 1, 0]);
+}
+
+#[test]
+fn test_topological_sort_reports_nodes_on_and_below_a_cycle() {
+    // 0 -> 1 -> 2 -> 1, and 2 -> 3
+    let err = topological_sort(4, &links(&[(0, 1), (1, 2), (2, 1), (2, 3)])).unwrap_err();
+    assert_eq!(err, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_cycle_is_an_error_at_run_time() {
+    let mut model = IniModelIO::new().read_model_string("\
+[kalix]
+start = 2022-08-09
+end = 2022-08-14
+
+[node.a]
+type = confluence
+loc = 0, 0
+ds_1 = b
+
+[node.b]
+type = confluence
+loc = 0, 1
+ds_1 = a
+").unwrap();
+    model.configure().unwrap();
+    let err = model.run().unwrap_err();
+    assert!(err.contains("cycle"), "Unexpected error: {}", err);
+    assert!(err.contains("a, b"), "Unexpected error: {}", err);
+}
+
+/// Writes the node sections of an INI file in reverse order, so that every node is defined
+/// after the nodes downstream of it.
+fn reverse_node_sections(ini: &str) -> String {
+    let mut head = String::new();
+    let mut nodes: Vec<String> = vec![];
+    let mut tail = String::new();
+    for line in ini.lines() {
+        if line.starts_with("[node.") {
+            nodes.push(String::new());
+        } else if line.starts_with('[') && !nodes.is_empty() {
+            tail.push_str("\n");
+        }
+        let target = if !tail.is_empty() {
+            &mut tail
+        } else if let Some(last) = nodes.last_mut() {
+            last
+        } else {
+            &mut head
+        };
+        target.push_str(line);
+        target.push('\n');
+    }
+    nodes.reverse();
+    format!("{}{}\n{}", head, nodes.join("\n"), tail)
+}
+
+fn run_and_collect(ini: &str) -> (Model, Vec<Vec<f64>>) {
+    let wd = PathBuf::from("./src/tests/example_models/6");
+    let mut model = IniModelIO::new().read_model_string_with_working_directory(ini, Some(wd)).unwrap();
+    model.configure().unwrap();
+    model.run().unwrap();
+    let results = model.collect_output_series().iter().map(|ts| ts.values.clone()).collect();
+    (model, results)
+}
+
+#[test]
+fn test_node_definition_order_does_not_change_results() {
+    let ini = std::fs::read_to_string("./src/tests/example_models/6/model_with_every_node_type.ini").unwrap();
+    let reversed = reverse_node_sections(&ini);
+    assert_ne!(ini, reversed);
+
+    let (model_a, results_a) = run_and_collect(&ini);
+    let (model_b, results_b) = run_and_collect(&reversed);
+
+    // The forward model runs in definition order; the reversed one must not.
+    let n = model_a.nodes.len();
+    assert_eq!(model_a.execution_order(), (0..n).collect::<Vec<_>>().as_slice());
+    assert_ne!(model_b.execution_order(), model_a.execution_order());
+
+    assert!(!results_a.is_empty());
+    assert_eq!(results_a.len(), results_b.len());
+    for (a, b) in results_a.iter().zip(results_b.iter()) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!(x == y || (x.is_nan() && y.is_nan()), "{} != {}", x, y);
+        }
+    }
+}
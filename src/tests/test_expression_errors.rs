@@ -0,0 +1,85 @@
+use crate::data_management::data_cache::DataCache;
+use crate::functions::{closest_match, parse_function, ParseError, Span};
+use crate::io::ini_model_io::IniModelIO;
+use crate::model_inputs::DynamicInput;
+
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[functions]
+f.demand = 2 * sim.day
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = 10 * sim.day +
+    INFLOW_TAIL
+ds_1 = pump
+
+[node.pump]
+type = unregulated_user
+loc = 5, 20
+demand = f.demand
+";
+
+#[test]
+fn test_parse_error_spans() {
+    let span = |expression: &str| parse_function(expression).err().unwrap().span().unwrap();
+
+    // The span covers the offending token, not the position the parser had read up to
+    assert_eq!(span("1 + * 2"), Span::new(4, 5));
+    assert_eq!(span("max(1, 2"), Span::new(8, 8));
+    assert_eq!(span("2 * (1 + 3"), Span::new(4, 5));
+    assert_eq!(span("1 + 2 3 4"), Span::new(6, 9));
+    assert_eq!(span("mavg_last(data.flow, 0.5) + 1"), Span::new(0, 25));
+
+    let rendered = parse_function("1 + * 2").err().unwrap().render("1 + * 2");
+    assert_eq!(rendered.lines().skip(1).collect::<Vec<_>>(), vec!["    1 + * 2", "        ^"]);
+}
+
+#[test]
+fn test_unknown_names_are_suggested() {
+    assert_eq!(closest_match("mx", ["max", "min", "sum"]), Some("max"));
+    assert_eq!(closest_match("SIM.MNTH", ["sim.month", "sim.day"]), Some("sim.month"));
+    assert_eq!(closest_match("foo", ["max", "min", "sum"]), None);
+
+    let mut data_cache = DataCache::new();
+    let err = DynamicInput::parse("1 + Mx(data.a, 2)", &mut data_cache, true, None).err().unwrap();
+    assert_eq!(err, ParseError::UnknownFunction {
+        name: "mx".to_string(),
+        span: Span::new(4, 6),
+        suggestion: Some("max".to_string()),
+    });
+
+    let err = DynamicInput::from_string("if(sim.mnth > 6, 1, 0)", &mut data_cache, true, None).err().unwrap();
+    assert!(err.starts_with("Unknown variable 'sim.mnth' at position 3. Did you mean 'sim.month'?"), "{}", err);
+    assert!(err.ends_with("\n    if(sim.mnth > 6, 1, 0)\n       ^^^^^^^^"), "{}", err);
+
+    // Time functions are suggested too
+    let err = DynamicInput::from_string("mnth() + 1", &mut data_cache, true, None).err().unwrap();
+    assert!(err.contains("Did you mean 'month'?"), "{}", err);
+}
+
+#[test]
+fn test_ini_expression_errors_report_line_and_column() {
+    let ini_io = IniModelIO::new();
+    assert!(ini_io.read_model_string(&MODEL.replace("INFLOW_TAIL", "max(1, 2)")).is_ok());
+
+    // Errors on a continuation line are reported where they are in the file
+    let err = ini_io.read_model_string(&MODEL.replace("INFLOW_TAIL", "mx(1, 2)")).err().unwrap();
+    assert!(err.starts_with("Error on line 12, column 5: Unknown function 'mx'"), "{}", err);
+    assert!(err.contains("Did you mean 'max'?"), "{}", err);
+
+    let err = ini_io.read_model_string(&MODEL.replace("INFLOW_TAIL", "(1 + 2")).err().unwrap();
+    assert!(err.starts_with("Error on line 12, column 5: Unmatched parentheses"), "{}", err);
+
+    let err = ini_io.read_model_string(&MODEL.replace("10 * sim.day +", "10 * * sim.day +")).err().unwrap();
+    assert!(err.starts_with("Error on line 11, column 15: Syntax error"), "{}", err);
+
+    // References to undefined functions suggest the closest defined one
+    let err = ini_io.read_model_string(&MODEL.replace("demand = f.demand", "demand = f.demnd")
+        .replace("INFLOW_TAIL", "1")).err().unwrap();
+    assert!(err.contains("'f.demnd' is used but not defined in [functions]. Did you mean 'f.demand'?"), "{}", err);
+}
@@ -0,0 +1,89 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use crate::apis::ffi::*;
+
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-05
+
+[constants]
+c.scale = 2.0
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = c.scale * sim.day
+
+[outputs]
+node.a.ds_1
+";
+
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(kalix_last_error()) }.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_ffi_load_run_and_get_series() {
+    let ini = CString::new(MODEL).unwrap();
+    let name = CString::new("node.a.ds_1").unwrap();
+    let target = CString::new("c.scale").unwrap();
+    let mut model: *mut KalixModel = ptr::null_mut();
+    unsafe {
+        assert_eq!(kalix_load_model_string(ini.as_ptr(), &mut model), KALIX_OK);
+        assert!(!model.is_null());
+        assert_eq!(kalix_run(model), KALIX_OK);
+
+        let mut info = KalixSeriesInfo::default();
+        assert_eq!(kalix_get_series_info(model, name.as_ptr(), &mut info), KALIX_OK);
+        assert_eq!(info, KalixSeriesInfo { start_timestamp: 946684800, step_size: 86400, len: 5 });
+
+        // Asking for the length with no buffer, then copying the values
+        let mut len = 0;
+        assert_eq!(kalix_get_series(model, name.as_ptr(), ptr::null_mut(), 0, &mut len), KALIX_ERR_BUFFER_TOO_SMALL);
+        assert_eq!(len, 5);
+        let mut values = vec![0.0; len];
+        assert_eq!(kalix_get_series(model, name.as_ptr(), values.as_mut_ptr(), values.len(), &mut len), KALIX_OK);
+        assert_eq!(values, vec![2.0, 4.0, 6.0, 8.0, 10.0]);
+        assert_eq!(last_error(), "");
+
+        // Changing a parameter and running again
+        assert_eq!(kalix_set_parameter(model, target.as_ptr(), 0.5), KALIX_OK);
+        let mut scale = 0.0;
+        assert_eq!(kalix_get_parameter(model, target.as_ptr(), &mut scale), KALIX_OK);
+        assert_eq!(scale, 0.5);
+        assert_eq!(kalix_run(model), KALIX_OK);
+        assert_eq!(kalix_get_series(model, name.as_ptr(), values.as_mut_ptr(), values.len(), ptr::null_mut()), KALIX_OK);
+        assert_eq!(values, vec![0.5, 1.0, 1.5, 2.0, 2.5]);
+
+        kalix_free(model);
+        kalix_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn test_ffi_errors() {
+    let missing = CString::new("./no/such/model.ini").unwrap();
+    let ini = CString::new(MODEL).unwrap();
+    let unknown = CString::new("node.nowhere.ds_1").unwrap();
+    let mut model: *mut KalixModel = ptr::null_mut();
+    unsafe {
+        assert_eq!(kalix_load_model(missing.as_ptr(), &mut model), KALIX_ERR_LOAD);
+        assert!(model.is_null());
+        assert!(!last_error().is_empty());
+        assert_eq!(kalix_load_model(ptr::null(), &mut model), KALIX_ERR_NULL_ARGUMENT);
+        assert_eq!(last_error(), "Argument 'path' is null");
+        assert_eq!(kalix_run(ptr::null_mut()), KALIX_ERR_NULL_ARGUMENT);
+
+        assert_eq!(kalix_load_model_string(ini.as_ptr(), &mut model), KALIX_OK);
+        let mut info = KalixSeriesInfo::default();
+        assert_eq!(kalix_get_series_info(model, unknown.as_ptr(), &mut info), KALIX_ERR_SERIES_NOT_FOUND);
+        assert_eq!(last_error(), "Series not found: node.nowhere.ds_1");
+        assert_eq!(kalix_set_parameter(model, unknown.as_ptr(), 1.0), KALIX_ERR_PARAMETER);
+        kalix_free(model);
+
+        let version = CStr::from_ptr(kalix_version()).to_str().unwrap();
+        assert_eq!(version, env!("KALIX_VERSION"));
+    }
+}
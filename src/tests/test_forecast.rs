@@ -0,0 +1,152 @@
+use crate::tests::test_helpers::TestDir;
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::forecast::{member_series_name, ForecastConfig, ForecastInput};
+use crate::model::Model;
+use crate::tid::utils::date_string_to_u64_flexible;
+
+const RAIN: &str = "data.climate_csv.by_index.1";
+
+fn rain(day: usize) -> f64 {
+    [0.0, 12.0, 3.0, 0.0, 25.0, 1.0, 0.0][day % 7]
+}
+
+fn timestamp(date: &str) -> u64 {
+    date_string_to_u64_flexible(date).unwrap().0
+}
+
+/// CSV with a date column for each day from 2000-01-01 and a column for each of `columns`
+fn csv(first_day: usize, n_days: usize, columns: &[&dyn Fn(usize) -> f64]) -> String {
+    let mut text = String::from("Date");
+    for i in 0..columns.len() {
+        text.push_str(&format!(",m{}", i + 1));
+    }
+    text.push('\n');
+    let start = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    for day in first_day..first_day + n_days {
+        text.push_str(&(start + chrono::Days::new(day as u64)).format("%Y-%m-%d").to_string());
+        for f in columns {
+            text.push_str(&format!(",{}", f(day)));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// A Gr4j catchment driven by 60 days of rain, from 2000-01-01 to 2000-02-29
+fn model(dir_name: &str) -> (Model, TestDir) {
+    let dir = TestDir::new(dir_name);
+    let rain_path = dir.write("climate.csv", &csv(0, 60, &[&rain]));
+    let ini = format!("\
+[inputs]
+{}
+
+[node.catchment]
+type = gr4j
+loc = 0, 0
+rain = {}
+evap = 3
+area = 100
+params = 350, 0, 40, 2.5
+
+[outputs]
+node.catchment.dsflow
+", rain_path.display(), RAIN);
+    (IniModelIO::new().read_model_string(&ini).unwrap(), dir)
+}
+
+fn dsflow(m: &Model) -> Vec<f64> {
+    m.data_cache.get_series(m.data_cache.get_existing_series_idx("node.catchment.dsflow").unwrap()).values.clone()
+}
+
+#[test]
+fn test_forecast_from_history_matches_a_full_run() {
+    let (m, _dir) = model("kalix_forecast_history");
+    let mut full = m.clone();
+    full.configure().unwrap();
+    full.run().unwrap();
+    let full = dsflow(&full);
+
+    // A single member with the observed rain continues the run exactly
+    let members = ForecastInput::from_csv(RAIN, &csv(30, 30, &[&rain])).unwrap();
+    let config = ForecastConfig {
+        forecast_start: timestamp("2000-01-31"),
+        inputs: vec![members],
+        ..Default::default()
+    };
+    let result = m.run_forecast(&config, || false, None).unwrap().unwrap();
+    assert_eq!((result.n_members, result.end_timestamp), (1, timestamp("2000-02-29")));
+    assert_eq!(result.series[0].members[0], full[30..]);
+    assert_eq!(result.initial_state[0].0, "catchment");
+
+    // The same again from the saved state, without a history run
+    let saved = ForecastConfig { initial_state: Some(result.initial_state.clone()), ..config };
+    let again = m.run_forecast(&saved, || false, None).unwrap().unwrap();
+    assert_eq!(again.series[0].members[0], full[30..]);
+}
+
+#[test]
+fn test_forecast_ensemble() {
+    let (mut m, _dir) = model("kalix_forecast_ensemble");
+
+    // Three members running past the end of the history, with more rain in each
+    let dry = |_: usize| 0.0;
+    let wetter = |d: usize| 2.0 * rain(d);
+    let members = ForecastInput::from_csv(RAIN, &csv(40, 30, &[&rain, &dry, &wetter])).unwrap();
+    let config = ForecastConfig {
+        forecast_start: timestamp("2000-02-10"),
+        forecast_end: Some(timestamp("2000-03-05")),
+        inputs: vec![members],
+        ..Default::default()
+    };
+    let result = m.run_forecast(&config, || false, None).unwrap().unwrap();
+    let s = &result.series[0];
+    assert_eq!(s.members.len(), 3);
+    assert_eq!(s.members[0].len(), 25);
+
+    // Quantiles are taken across the members at each step
+    let names: Vec<&str> = s.quantiles.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["node.catchment.dsflow_p10", "node.catchment.dsflow_p50", "node.catchment.dsflow_p90"]);
+    assert_eq!(s.quantiles[0].1, s.members[1]);
+    assert_eq!(s.quantiles[1].1, s.members[0]);
+    assert_eq!(s.quantiles[2].1, s.members[2]);
+    assert!(s.members[2].iter().sum::<f64>() > s.members[0].iter().sum::<f64>());
+
+    // Members and quantiles are written to the model's results
+    result.write_to_data_cache(&mut m.data_cache);
+    let idx = m.data_cache.get_existing_series_idx(&member_series_name("node.catchment.dsflow", 2)).unwrap();
+    let written = m.data_cache.get_series(idx);
+    assert_eq!((written.start_timestamp, written.values.clone()), (config.forecast_start, s.members[2].clone()));
+    assert!(m.data_cache.get_existing_series_idx("node.catchment.dsflow_p50").is_some());
+}
+
+#[test]
+fn test_forecast_errors() {
+    let (m, _dir) = model("kalix_forecast_errors");
+    let config = |start: &str, members: ForecastInput| ForecastConfig {
+        forecast_start: timestamp(start),
+        inputs: vec![members],
+        ..Default::default()
+    };
+    let run = |config: ForecastConfig| m.run_forecast(&config, || false, None).err().unwrap();
+
+    let err = run(config("2000-01-01", ForecastInput::from_csv(RAIN, &csv(0, 10, &[&rain])).unwrap()));
+    assert!(err.contains("no history to run. Give a saved state instead"), "{}", err);
+
+    let err = run(config("2000-01-31", ForecastInput::from_csv(RAIN, &csv(31, 10, &[&rain])).unwrap()));
+    assert!(err.contains("Member 1 of the forecast for 'data.climate_csv.by_index.1' starts after the forecast start"), "{}", err);
+
+    let err = run(config("2000-01-31", ForecastInput::from_csv("data.nowhere.by_index.1", &csv(30, 10, &[&rain])).unwrap()));
+    assert!(err.contains("Input series not found"), "{}", err);
+
+    let short = ForecastConfig {
+        forecast_end: Some(timestamp("2000-03-31")),
+        ..config("2000-01-31", ForecastInput::from_csv(RAIN, &csv(30, 10, &[&rain])).unwrap())
+    };
+    let err = run(short);
+    assert!(err.contains("The forecast members end on 2000-02-09"), "{}", err);
+
+    let mut two = config("2000-01-31", ForecastInput::from_csv(RAIN, &csv(30, 10, &[&rain, &rain])).unwrap());
+    two.inputs.push(ForecastInput::from_csv(RAIN, &csv(30, 10, &[&rain])).unwrap());
+    let err = run(two);
+    assert!(err.contains("has 1 members but forecast for"), "{}", err);
+}
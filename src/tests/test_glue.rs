@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::numerical::glue::{glue_analysis, GlueConfig};
+use crate::numerical::opt::{ObjectiveFunction, OptimisationProblem, ParameterMappingConfig};
+use crate::numerical::opt::objectives::RmseObjective;
+
+fn load_model() -> Model {
+    // The input path in this model is relative to the repo root
+    let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+    IniModelIO::new().read_model_string_with_working_directory(&ini, Some(PathBuf::from("."))).unwrap()
+}
+
+/// node2 adds a constant (a - 5.5)^2 + (b - 6.5)^2 to node1's flow, so against node1's flow as
+/// "observed", the RMSE of a parameter set is exactly that constant.
+fn problem() -> (OptimisationProblem, Vec<f64>) {
+    let mut reference = load_model();
+    reference.configure().unwrap();
+    reference.run().unwrap();
+    let idx = reference.data_cache.get_existing_series_idx("node.node1.ds_1").unwrap();
+    let observed = reference.data_cache.series[idx].clone();
+    let observed_values = observed.values.clone();
+
+    let par_map = ParameterMappingConfig::from_strings(vec![
+        "c.a = lin_range(g(1), 0.0, 10.0)",
+        "c.b = lin_range(g(2), 0.0, 10.0)",
+    ]).unwrap();
+    let problem = OptimisationProblem::single_comparison(
+        load_model(),
+        par_map,
+        observed,
+        "node.node2.ds_1".to_string(),
+        ObjectiveFunction::RMSE(RmseObjective::new()),
+    );
+    (problem, observed_values)
+}
+
+#[test]
+fn test_glue_bounds_contain_behavioural_runs() {
+    let (problem, observed) = problem();
+    let config = GlueConfig {
+        n_samples: 300,
+        threshold: 5.0,
+        series: vec!["node.node2.ds_1".to_string()],
+        seed: Some(3),
+        n_threads: 2,
+        ..Default::default()
+    };
+    let result = glue_analysis(&problem, &config, || false, None).unwrap().unwrap();
+
+    assert_eq!(result.n_samples, 300);
+    assert_eq!(result.n_failed, 0);
+    assert!(!result.behavioural.is_empty());
+    assert!(result.behavioural.iter().all(|s| s.objective < 5.0));
+    let total_weight: f64 = result.behavioural.iter().map(|s| s.weight).sum();
+    assert!((total_weight - 1.0).abs() < 1e-9);
+
+    // Every behavioural run lies between observed and observed + 5
+    let bounds = &result.bounds[0];
+    assert_eq!(bounds.lower_name, "node.node2.ds_1_p05");
+    assert_eq!(bounds.upper_name, "node.node2.ds_1_p95");
+    assert_eq!(bounds.lower.len(), observed.len());
+    for t in 0..observed.len() {
+        assert!(bounds.lower[t] >= observed[t] - 1e-9);
+        assert!(bounds.lower[t] <= bounds.upper[t]);
+        assert!(bounds.upper[t] < observed[t] + 5.0);
+    }
+
+    // Bounds are written to the data cache under their quantile names
+    let mut model = load_model();
+    model.configure().unwrap();
+    result.write_to_data_cache(&mut model.data_cache);
+    let idx = model.data_cache.get_existing_series_idx("node.node2.ds_1_p95").unwrap();
+    assert_eq!(model.data_cache.series[idx].values, bounds.upper);
+}
+
+#[test]
+fn test_glue_no_behavioural_sets() {
+    let (problem, _) = problem();
+    let config = GlueConfig { n_samples: 20, threshold: 0.0, seed: Some(1), ..Default::default() };
+    let err = glue_analysis(&problem, &config, || false, None).unwrap_err();
+    assert!(err.contains("No behavioural parameter sets"), "got: {}", err);
+}
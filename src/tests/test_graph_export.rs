@@ -0,0 +1,75 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::graph_export::GraphFormat;
+
+// An inflow reaches a splitter whose second outlet bypasses the main channel
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.river]
+type = inflow
+loc = 148.5, -35.25
+inflow = 100
+ds_1 = split
+
+[node.split]
+type = splitter
+loc = 148.5, -35.5
+ds_2_flow = 0.3 * this.usflow
+ds_1 = main
+ds_2 = bypass
+
+[node.main]
+type = gauge
+loc = 148.5, -35.75
+
+[node.bypass]
+type = gauge
+loc = 148.75, -35.75
+";
+
+#[test]
+fn test_export_graph_as_dot() {
+    let model = IniModelIO::new().read_model_string(MODEL).unwrap();
+    assert_eq!(model.export_graph(GraphFormat::Dot), "\
+digraph kalix {
+    \"river\" [label=\"river\\ninflow\"];
+    \"split\" [label=\"split\\nsplitter\"];
+    \"main\" [label=\"main\\ngauge\"];
+    \"bypass\" [label=\"bypass\\ngauge\"];
+    \"river\" -> \"split\";
+    \"split\" -> \"main\";
+    \"split\" -> \"bypass\" [label=\"ds_2\"];
+}
+");
+}
+
+#[test]
+fn test_export_graph_as_geojson() {
+    let model = IniModelIO::new().read_model_string(MODEL).unwrap();
+    let geojson: serde_json::Value = serde_json::from_str(&model.export_graph(GraphFormat::GeoJson)).unwrap();
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 7);
+    assert_eq!(features[0], serde_json::json!({
+        "type": "Feature",
+        "geometry": {"type": "Point", "coordinates": [148.5, -35.25]},
+        "properties": {"kind": "node", "name": "river", "node_type": "inflow"}
+    }));
+    assert_eq!(features[6], serde_json::json!({
+        "type": "Feature",
+        "geometry": {"type": "LineString", "coordinates": [[148.5, -35.5], [148.75, -35.75]]},
+        "properties": {"kind": "link", "from": "split", "to": "bypass", "outlet": "ds_2"}
+    }));
+}
+
+#[test]
+fn test_graph_format_names() {
+    assert_eq!(GraphFormat::from_name("GeoJSON").unwrap(), GraphFormat::GeoJson);
+    assert_eq!(GraphFormat::from_path("network.gv").unwrap(), GraphFormat::Dot);
+    assert_eq!(GraphFormat::from_path("./out/network.geojson").unwrap(), GraphFormat::GeoJson);
+    assert_eq!(GraphFormat::from_name("svg").unwrap_err(),
+               "Unknown graph format 'svg'. Expected one of: dot, geojson");
+    assert!(GraphFormat::from_path("network.svg").is_err());
+}
@@ -1,4 +1,7 @@
+use std::path::{Path, PathBuf};
 use approx::assert_relative_eq;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
 
 /// Print a diagnostic to stderr describing how two strings expected to be byte-identical
 /// differ: line-ending style mismatch, the first differing line, and overall line counts.
@@ -57,3 +60,60 @@ pub fn assert_stats_close(
     assert_relative_eq!(new.1, old.1, max_relative = 1e-15);
     assert_relative_eq!(new.2, old.2, max_relative = 1e-15);
 }
+
+/// A folder for the input files of a test, under the temp directory and named for the test
+/// and the process so that parallel runs don't collide. It is removed when dropped.
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new(name: &str) -> TestDir {
+        let path = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TestDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of a file in the folder
+    pub fn join(&self, file: &str) -> PathBuf {
+        self.path.join(file)
+    }
+
+    /// Writes a file into the folder (creating any subfolders), returning its path
+    pub fn write(&self, file: &str, content: &str) -> PathBuf {
+        let path = self.path.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Reads a model from an INI string, configures it and runs it.
+pub fn run(ini: &str) -> Result<Model, String> {
+    let mut m = IniModelIO::new().read_model_string(ini)?;
+    m.configure()?;
+    m.run()?;
+    Ok(m)
+}
+
+/// The values of a series in the model's data cache. Panics if there is no such series.
+pub fn series(m: &Model, name: &str) -> Vec<f64> {
+    m.data_cache.series[m.data_cache.get_existing_series_idx(name).unwrap()].values.clone()
+}
+
+/// As `series`, with each value rounded to 6 decimal places, for comparing against
+/// hand-worked results.
+pub fn series_rounded(m: &Model, name: &str) -> Vec<f64> {
+    series(m, name).iter().map(|v| (v * 1e6).round() / 1e6).collect()
+}
@@ -0,0 +1,233 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::nodes::Node;
+
+// Two branches joining at c, above a gauge. x reads c's result, so it depends on c without
+// a link. The town's loss_scale and b's input data are the edits.
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-03-31
+
+[constants]
+c.base = 20
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = if(sim.day < 10, 100, c.base)
+ds_1 = town
+
+[node.town]
+type = loss
+loc = 0, 10
+table = 0, 0, 100, 50
+ds_1 = c
+
+[node.b]
+type = inflow
+loc = 10, 0
+inflow = data.b_csv.by_name.b
+ds_1 = c
+
+[node.c]
+type = confluence
+loc = 0, 20
+ds_1 = g
+
+[node.g]
+type = gauge
+loc = 0, 30
+
+[node.x]
+type = inflow
+loc = 20, 20
+inflow = 0.1 * node.c.dsflow
+
+[outputs]
+node.a.dsflow
+node.town.dsflow
+node.g.dsflow
+node.x.dsflow
+";
+
+fn model(extra: &str) -> Model {
+    let mut m = IniModelIO::new().read_model_string(&format!("{}{}", MODEL, extra)).unwrap();
+    let start = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let csv: String = (0..91u64).map(|i| format!("{},{}\n", start + chrono::Days::new(i), i % 7)).collect();
+    m.load_input_csv_str("b.csv", &format!("Date,b\n{}", csv), None).unwrap();
+    m
+}
+
+fn run(m: &mut Model) {
+    m.configure().unwrap();
+    m.run().unwrap();
+}
+
+fn rerun_names(m: &Model) -> Option<Vec<&str>> {
+    m.rerun_nodes().map(|nodes| nodes.iter().map(|&i| m.nodes[i].get_name()).collect())
+}
+
+// Every output and mass balance of an incremental run matches a full run of the same model
+fn assert_same_results(incremental: &Model, full: &Model) {
+    for name in &full.outputs {
+        let series = |m: &Model| m.data_cache.get_series(m.data_cache.get_existing_series_idx(name).unwrap()).values.clone();
+        assert_eq!(series(incremental), series(full), "{}", name);
+    }
+    for (a, b) in incremental.nodes.iter().zip(&full.nodes) {
+        assert_eq!(a.get_mass_balance(), b.get_mass_balance(), "{}", a.get_name());
+    }
+}
+
+#[test]
+fn test_parameter_change_reruns_the_affected_nodes() {
+    let mut m = model("");
+    m.enable_incremental_runs(true);
+    run(&mut m);
+    assert_eq!(m.rerun_nodes(), None);
+
+    m.set_parameter("node.town.loss_scale", 0.5).unwrap();
+    run(&mut m);
+    let mut names = rerun_names(&m).unwrap();
+    names.sort();
+    assert_eq!(names, vec!["c", "g", "town", "x"]);
+    assert_eq!(m.run_log().count("incremental_run"), 1);
+
+    let mut full = model("");
+    full.set_parameter("node.town.loss_scale", 0.5).unwrap();
+    run(&mut full);
+    assert_same_results(&m, &full);
+
+    // With nothing changed, nothing is re-simulated and the results stand
+    run(&mut m);
+    assert_eq!(rerun_names(&m), Some(vec![]));
+    assert_same_results(&m, &full);
+}
+
+#[test]
+fn test_input_change_reruns_the_nodes_reading_it() {
+    let mut m = model("");
+    m.enable_incremental_runs(true);
+    run(&mut m);
+
+    let name = "data.b_csv.by_name.b";
+    let mut ts = m.get_input_series(name).unwrap().clone();
+    ts.values[40] = 500.0;
+    m.replace_input_series(name, ts.clone()).unwrap();
+    run(&mut m);
+    let mut names = rerun_names(&m).unwrap();
+    names.sort();
+    assert_eq!(names, vec!["b", "c", "g", "x"]);
+
+    let mut full = model("");
+    full.replace_input_series(name, ts).unwrap();
+    run(&mut full);
+    assert_same_results(&m, &full);
+}
+
+#[test]
+fn test_whole_model_rerun_where_a_change_can_reach_any_node() {
+    let mut m = model("");
+    m.enable_incremental_runs(true);
+    run(&mut m);
+    m.set_parameter("c.base", 30.0).unwrap();
+    run(&mut m);
+    assert_eq!(m.rerun_nodes(), None);
+    let entry = m.run_log().entries().iter().find(|e| e.code == "full_rerun").unwrap();
+    assert_eq!(entry.message, "The whole model was re-run, as a constant was changed, and constants may be read anywhere");
+
+    // A function of the gauge could carry the change to any node
+    let mut m = model("\n[functions]\nf.total = node.g.dsflow\n");
+    m.enable_incremental_runs(true);
+    run(&mut m);
+    m.set_parameter("node.town.loss_scale", 0.5).unwrap();
+    run(&mut m);
+    assert_eq!(m.rerun_nodes(), None);
+    assert!(m.run_log().entries().iter().any(|e| e.message.contains("function 'f.total' reads a result or input that changed")));
+
+    // Without incremental runs, every run is a full one
+    let mut m = model("");
+    run(&mut m);
+    m.set_parameter("node.town.loss_scale", 0.5).unwrap();
+    run(&mut m);
+    assert_eq!(m.rerun_nodes(), None);
+    assert_eq!(m.run_log().count("full_rerun"), 0);
+}
+
+// A dam releases to an irrigator two days down a reach, and orders are due two days after
+// they are placed. The loss on the separate branch is the edit, which affects no ordered node.
+const ORDERED: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-20
+
+[node.dam_inflow]
+type = inflow
+loc = 0, 0
+inflow = 5
+ds_1 = dam
+
+[node.dam]
+type = storage
+loc = 0, 10
+initial_volume = 1000
+dimensions = 0,    0,    0, 0,
+             10,   1000, 1, 0,
+             10.1, 1001, 1, 1e8
+ds_1 = reach
+
+[node.reach]
+type = routing
+loc = 0, 20
+lag = 2
+ds_1 = irrigator
+
+[node.irrigator]
+type = regulated_user
+loc = 0, 30
+order = if(sim.day < 10, 30, 2)
+ds_1 = end
+
+[node.end]
+type = gauge
+loc = 0, 40
+
+[node.side]
+type = inflow
+loc = 10, 0
+inflow = 50
+ds_1 = town
+
+[node.town]
+type = loss
+loc = 10, 10
+table = 0, 0, 100, 50
+ds_1 = side_gauge
+
+[node.side_gauge]
+type = gauge
+loc = 10, 20
+
+[outputs]
+node.irrigator.order_due
+node.irrigator.diversion
+node.dam.volume
+node.side_gauge.dsflow
+";
+
+#[test]
+fn test_unaffected_ordered_nodes_keep_their_orders() {
+    let mut m = IniModelIO::new().read_model_string(ORDERED).unwrap();
+    m.enable_incremental_runs(true);
+    run(&mut m);
+    m.set_parameter("node.town.loss_scale", 0.5).unwrap();
+    run(&mut m);
+    let mut names = rerun_names(&m).unwrap();
+    names.sort();
+    assert_eq!(names, vec!["side_gauge", "town"]);
+
+    let mut full = IniModelIO::new().read_model_string(ORDERED).unwrap();
+    full.set_parameter("node.town.loss_scale", 0.5).unwrap();
+    run(&mut full);
+    assert_same_results(&m, &full);
+}
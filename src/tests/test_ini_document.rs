@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 use crate::io::custom_ini_parser::IniDocument;
 use crate::io::ini_model_io::IniModelIO;
-use crate::tests::test_helpers::print_text_diff;
+use crate::tests::test_helpers::{print_text_diff, TestDir};
 
 #[test]
 fn test_line_continuation_integration() {
@@ -338,3 +338,71 @@ fn test_exact_round_trip_every_node_type_model() {
         "re-serialised model must match the original byte-for-byte (line endings normalised)"
     );
 }
+
+/// Writes files into a fresh folder under the temp directory
+fn write_include_files(name: &str, files: &[(&str, &str)]) -> TestDir {
+    let dir = TestDir::new(&format!("kalix_{}", name));
+    for (file, content) in files {
+        dir.write(file, content);
+    }
+    dir
+}
+
+#[test]
+fn test_include_combines_sections() {
+    let dir = write_include_files("include_combines", &[
+        ("shared/common.ini", "include = tables.ini\n\n[inputs]\nflows.csv\n\n[constants]\nc.x = 1\nc.y = 2\n"),
+        ("shared/tables.ini", "[tables]\nt.rating = 0, 0, 1, 10\n"),
+        ("scenario.ini", "# Dry scenario\ninclude = shared/common.ini\n\n[inputs]\nrain.csv\n\n[constants]\nc.y = 3\n-c.x\n"),
+    ]);
+    let path = dir.join("scenario.ini");
+    let doc = IniDocument::read_file(path.to_str().unwrap()).unwrap();
+
+    assert!(doc.includes.is_empty());
+    let names: Vec<&String> = doc.sections.keys().collect();
+    assert_eq!(names, vec!["tables", "inputs", "constants"]);
+    let inputs: Vec<&String> = doc.sections["inputs"].properties.keys().collect();
+    assert_eq!(inputs, vec!["flows.csv", "rain.csv"]);
+    assert_eq!(doc.get_property("constants", "c.x"), None);
+    assert_eq!(doc.get_property("constants", "c.y"), Some("3"));
+    assert_eq!(doc.get_property("tables", "t.rating"), Some("0, 0, 1, 10"));
+}
+
+#[test]
+fn test_include_errors_name_the_including_line() {
+    // A missing file
+    let dir = write_include_files("include_missing", &[
+        ("model.ini", "# header\ninclude = missing.ini\n[constants]\nc.x = 1\n"),
+    ]);
+    let err = IniDocument::read_file(dir.join("model.ini").to_str().unwrap()).err().unwrap();
+    assert!(err.contains("model.ini' on line 2") && err.contains("missing.ini"), "got: {}", err);
+
+    // A cycle through another file
+    let dir = write_include_files("include_cycle", &[
+        ("a.ini", "include = b.ini\n[constants]\nc.x = 1\n"),
+        ("b.ini", "\ninclude = a.ini\n"),
+    ]);
+    let err = IniDocument::read_file(dir.join("a.ini").to_str().unwrap()).err().unwrap();
+    assert!(err.contains("cycle") && err.contains("b.ini' on line 2"), "got: {}", err);
+
+    // An error in the included file
+    let dir = write_include_files("include_bad", &[
+        ("model.ini", "include = bad.ini\n"),
+        ("bad.ini", "c.x = 1\n"),
+    ]);
+    let err = IniDocument::read_file(dir.join("model.ini").to_str().unwrap()).err().unwrap();
+    assert!(err.contains("bad.ini") && err.contains("model.ini' on line 1") && err.contains("line 1"), "got: {}", err);
+
+    // Includes belong before the first section
+    let err = IniDocument::parse("[constants]\ninclude = other.ini\n").err().unwrap();
+    assert!(err.contains("line 2") && err.contains("before the first section"), "got: {}", err);
+}
+
+#[test]
+fn test_include_round_trip() {
+    let content = "# Shared inputs\ninclude = shared.ini   ; for every scenario\n\n[constants]\nc.x = 1\n";
+    let doc = IniDocument::parse(content).unwrap();
+    assert_eq!(doc.includes.len(), 1);
+    assert_eq!(doc.includes[0].path, "shared.ini");
+    assert_eq!(doc.to_string(), content);
+}
@@ -0,0 +1,106 @@
+use crate::io::ini_formatter::{format_ini, is_formatted};
+use crate::io::ini_model_io::IniModelIO;
+use std::path::PathBuf;
+
+const MESSY: &str = "\
+# Test model
+[kalix]
+#version = 0.0.1
+
+
+[node.dam]
+ds_1=reach   ; main outlet
+loc=0,0
+evap =  data.evap
+type=storage
+ds_1_capacity = 500
+dimensions = 90, 0, 0, 0,
+     91, 100, 1, 0,   # full supply
+     91.1, 101, 1, 1e8,
+meta_owner = state
+ds_2 = user
+
+[node.reach]
+type = routing
+loc = 0, 10
+pwl = 0, 3,
+      100, 2,
+
+[outputs]
+node.dam.volume
+
+
+# the end
+";
+
+#[test]
+fn test_format_ini_canonical_layout() {
+    let formatted = format_ini(MESSY).unwrap();
+    let expected = "\
+# Test model
+[kalix]
+#version = 0.0.1
+
+[node.dam]
+type = storage
+loc = 0,0
+evap = data.evap
+dimensions = 90,   0,   0, 0,
+             91,   100, 1, 0,    # full supply
+             91.1, 101, 1, 1e8,
+meta_owner = state
+ds_1 = reach  ; main outlet
+ds_1_capacity = 500
+ds_2 = user
+
+[node.reach]
+type = routing
+loc = 0, 10
+pwl = 0,   3,
+      100, 2,
+
+[outputs]
+node.dam.volume
+
+# the end
+";
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn test_format_ini_is_idempotent() {
+    let formatted = format_ini(MESSY).unwrap();
+    assert!(!is_formatted(MESSY).unwrap());
+    assert!(is_formatted(&formatted).unwrap());
+}
+
+#[test]
+fn test_format_ini_leaves_non_numeric_rows() {
+    let ini = "[constants]\nc.x = max(1,\n  2)  # spread over two lines\n";
+    let formatted = format_ini(ini).unwrap();
+    assert_eq!(formatted, "[constants]\nc.x = max(1,\n      2)      # spread over two lines\n");
+}
+
+#[test]
+fn test_format_ini_keeps_model_unchanged() {
+    let ini = std::fs::read_to_string("./src/tests/example_models/6/model_with_every_node_type.ini").unwrap();
+    let formatted = format_ini(&ini).unwrap();
+    assert_ne!(ini, formatted);
+    assert!(formatted.contains("# not the default parameters"));
+    assert!(formatted.contains(";does this appear?"));
+
+    let wd = PathBuf::from("./src/tests/example_models/6");
+    let io = IniModelIO::new();
+    let model_a = io.read_model_string_with_working_directory(&ini, Some(wd.clone())).unwrap();
+    let model_b = io.read_model_string_with_working_directory(&formatted, Some(wd)).unwrap();
+    // Only the spacing within values may differ
+    let squash = |s: String| -> String { s.chars().filter(|c| !c.is_whitespace()).collect() };
+    assert_eq!(squash(io.model_to_json(&model_a).to_string()), squash(io.model_to_json(&model_b).to_string()));
+}
+
+#[test]
+fn test_format_ini_keeps_includes() {
+    let ini = "# Shared inputs\ninclude =  shared.ini  ; every scenario\n[constants]\nc.x=1\n";
+    let formatted = format_ini(ini).unwrap();
+    assert_eq!(formatted, "# Shared inputs\ninclude = shared.ini  ; every scenario\n\n[constants]\nc.x = 1\n");
+}
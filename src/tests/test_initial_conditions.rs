@@ -0,0 +1,118 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tests::test_helpers::{run, series};
+
+// Catchments and a reach that start wet, with no rain or inflow to add water
+const WET_START: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-03-31
+
+[node.gr4j]
+type = gr4j
+loc = 0, 0
+rain = 0
+evap = 0
+area = 100
+params = 350, 0, 90, 1.7
+initial_stores = 200, 50
+ds_1 = reach
+
+[node.sacramento]
+type = sacramento
+loc = 10, 0
+rain = 0
+evap = 0
+area = 100
+params = 0.01, 40.0, 23.0, 0.009,
+         0.043, 130.0, 0.01, 0.063,
+         1.0, 0.01, 0.0, 0.0,
+         40.0, 0.245, 50.0, 40.0,
+         0.1
+initial_stores = 25, 10, 65, 12, 30
+
+[node.reach]
+type = routing
+loc = 0, 10
+n_divs = 2
+pwl = 0, 2, 1000, 2
+initial_volume = 500
+
+[outputs]
+node.gr4j.production_store
+node.gr4j.routing_store
+node.gr4j.dsflow
+node.sacramento.dsflow
+node.reach.dsflow
+node.reach.volume
+";
+
+#[test]
+fn test_initial_conditions() {
+    let m = run(WET_START).unwrap();
+
+    // The GR4J stores start near their initial levels and drain from there
+    let production = series(&m, "node.gr4j.production_store");
+    let routing = series(&m, "node.gr4j.routing_store");
+    assert!(production[0] > 190.0 && production[0] < 200.0, "{}", production[0]);
+    assert!(routing[0] > 0.0 && routing[0] < 50.0, "{}", routing[0]);
+    assert!(series(&m, "node.gr4j.dsflow")[0] > 0.0);
+
+    // The Sacramento catchment has baseflow without any rain
+    assert!(series(&m, "node.sacramento.dsflow").iter().all(|&q| q > 0.0));
+
+    // The reach releases its initial volume. Its mass balance only counts inflows and
+    // outflows, so the water it started with comes out of the reach's storage.
+    let reach_outflow: f64 = series(&m, "node.reach.dsflow").iter().sum();
+    let gr4j_outflow: f64 = series(&m, "node.gr4j.dsflow").iter().sum();
+    let final_volume = *series(&m, "node.reach.volume").last().unwrap();
+    assert!(series(&m, "node.reach.dsflow")[0] > 0.0);
+    assert!((reach_outflow + final_volume - gr4j_outflow - 500.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_initial_conditions_default_to_empty() {
+    // Without initial conditions there is no flow at all
+    let dry = WET_START.replace("initial_stores = 200, 50\n", "")
+        .replace("initial_stores = 25, 10, 65, 12, 30\n", "")
+        .replace("initial_volume = 500\n", "");
+    let m = run(&dry).unwrap();
+    for name in ["node.gr4j.dsflow", "node.sacramento.dsflow", "node.reach.dsflow"] {
+        assert!(series(&m, name).iter().all(|&q| q == 0.0), "{}", name);
+    }
+    let text = IniModelIO::new().model_to_string(&m);
+    assert!(!text.contains("initial_"));
+}
+
+#[test]
+fn test_initial_stores_limited_to_capacity() {
+    let full = WET_START.replace("initial_stores = 200, 50", "initial_stores = 1000, 1000");
+    let m = run(&full).unwrap();
+    assert!(series(&m, "node.gr4j.production_store")[0] <= 350.0);
+    assert!(series(&m, "node.gr4j.routing_store")[0] <= 90.0);
+}
+
+#[test]
+fn test_initial_conditions_round_trip() {
+    let io = IniModelIO::new();
+    let m: Model = io.read_model_string(WET_START).unwrap();
+    let text = io.model_to_string(&m);
+    assert!(text.contains("initial_stores = 200, 50"));
+    assert!(text.contains("initial_stores = 25, 10, 65, 12, 30"));
+    assert!(text.contains("initial_volume = 500"));
+    let m2: Model = io.read_model_string(&text).unwrap();
+    assert_eq!(io.model_to_string(&m2), text);
+}
+
+#[test]
+fn test_initial_conditions_errors() {
+    let wrong_count = WET_START.replace("initial_stores = 200, 50", "initial_stores = 200");
+    let err = IniModelIO::new().read_model_string(&wrong_count).err().unwrap();
+    assert!(err.contains("must have 2 values"), "{}", err);
+
+    // A lag-only reach has no storage to hold an initial volume
+    let lag_only = WET_START.replace("pwl = 0, 2, 1000, 2", "lag = 1");
+    let mut m: Model = IniModelIO::new().read_model_string(&lag_only).unwrap();
+    let err = m.configure().err().unwrap();
+    assert!(err.contains("needs storage routing"), "{}", err);
+}
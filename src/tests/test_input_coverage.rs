@@ -0,0 +1,108 @@
+use crate::tests::test_helpers::TestDir;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tid::utils::date_string_to_u64_flexible;
+
+fn timestamp(date: &str) -> u64 {
+    date_string_to_u64_flexible(date).unwrap().0
+}
+
+// Two inflows: a from 2000-01-01 to 01-10, and b from 01-03 to 01-12
+fn model(dir_name: &str, kalix: &str, coverage: &str) -> (Model, TestDir) {
+    let dir = TestDir::new(dir_name);
+    let write = |name: &str, first_day: usize, last_day: usize| {
+        let mut text = format!("Date,{}\n", name);
+        for day in first_day..=last_day {
+            text.push_str(&format!("2000-01-{:02},{}\n", day, day));
+        }
+        dir.write(&format!("{}.csv", name), &text);
+    };
+    write("a", 1, 10);
+    write("b", 3, 12);
+    let ini = format!("\
+[kalix]
+{}
+
+[inputs]
+{}
+{}
+{}
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = data.a_csv.by_name.a
+
+[node.b]
+type = inflow
+loc = 0, 10
+inflow = data.b_csv.by_name.b
+
+[outputs]
+node.b.dsflow
+", kalix, dir.join("a.csv").display(), dir.join("b.csv").display(), coverage);
+    (IniModelIO::new().read_model_string(&ini).unwrap(), dir)
+}
+
+fn configure(kalix: &str, coverage: &str) -> Result<Model, String> {
+    let (mut m, _dir) = model("kalix_coverage", kalix, coverage);
+    let result = m.configure();
+    result.map(|_| m)
+}
+
+#[test]
+fn test_clipped_inputs_set_the_period() {
+    let m = configure("", "").unwrap();
+    let w = &m.simulation_window;
+    assert_eq!((w.start, w.end), (timestamp("2000-01-03"), timestamp("2000-01-10")));
+    assert_eq!((w.start_set_by.clone(), w.end_set_by.clone()), (vec!["data.b_csv.by_name.b".to_string()], vec!["data.a_csv.by_name.a".to_string()]));
+    assert!(w.clipped.is_empty());
+
+    // A specified start before the data is clipped to it, and flagged
+    let mut m = configure("start = 2000-01-01\nend = 2000-01-05", "").unwrap();
+    let w = &m.simulation_window;
+    assert_eq!((w.start, w.end, m.configuration.sim_nsteps), (timestamp("2000-01-03"), timestamp("2000-01-05"), 3));
+    assert_eq!((w.end_set_by.clone(), w.clipped.clone()), (vec!["end".to_string()], vec!["data.b_csv.by_name.b".to_string()]));
+    m.run().unwrap();
+    let entry = m.run_log().entries().iter().find(|e| e.code == "simulation_window_clipped").unwrap();
+    assert!(entry.message.contains("The start is set by data.b_csv.by_name.b and the end by end. The period given in [kalix] was clipped to the data of data.b_csv.by_name.b."),
+            "{}", entry.message);
+}
+
+#[test]
+fn test_filled_inputs_do_not_limit_the_period() {
+    let (mut m, _dir) = model("kalix_coverage_fill", "", "coverage.b_csv = fill(0)");
+    m.configure().unwrap();
+    m.run().unwrap();
+    let w = &m.simulation_window;
+    assert_eq!((w.start, w.end), (timestamp("2000-01-01"), timestamp("2000-01-10")));
+    let b = &m.data_cache.series[m.data_cache.get_existing_series_idx("node.b.dsflow").unwrap()].values;
+    assert_eq!(b[..4], [0.0, 0.0, 3.0, 4.0]);
+
+    // Saved the way it was read
+    let ini = IniModelIO::new().model_to_string(&m);
+    assert!(ini.contains("coverage.b_csv = fill(0)"), "{}", ini);
+}
+
+#[test]
+fn test_inputs_that_must_cover_the_period() {
+    let err = configure("", "coverage.b_csv = error").err().unwrap();
+    assert_eq!(err, "Input 'data.b_csv.by_name.b' has no data on 2000-01-01, within the simulation period \
+                     2000-01-01 to 2000-01-10. Its file's coverage is 'error'");
+
+    // With nothing clipped, the period is where they all have data
+    let m = configure("", "coverage.a_csv = error\ncoverage.b_csv = error").unwrap();
+    assert_eq!((m.simulation_window.start, m.simulation_window.end), (timestamp("2000-01-03"), timestamp("2000-01-10")));
+    let err = configure("start = 2000-01-02\nend = 2000-01-05", "coverage.a_csv = error\ncoverage.b_csv = error").err().unwrap();
+    assert!(err.starts_with("Input 'data.b_csv.by_name.b' has no data on 2000-01-02"), "{}", err);
+}
+
+#[test]
+fn test_coverage_errors() {
+    let err = configure("", "coverage.c_csv = error").err().unwrap();
+    assert_eq!(err, "Input file 'c_csv' named by 'coverage.c_csv' was not found");
+    let err = IniModelIO::new().read_model_string("[inputs]\ncoverage.a_csv = sometimes").err().unwrap();
+    assert!(err.contains("Unknown coverage option 'sometimes'"), "{}", err);
+    let err = IniModelIO::new().read_model_string("[inputs]\ncoverage.a_csv = fill(x)").err().unwrap();
+    assert!(err.contains("Invalid fill value 'x'"), "{}", err);
+}
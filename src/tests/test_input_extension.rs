@@ -0,0 +1,115 @@
+use crate::tests::test_helpers::{series, TestDir};
+use crate::data_management::run_log::LogLevel;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tid::utils::{date_string_to_u64_flexible, u64_to_year_month_day_and_seconds};
+
+fn long_flow(day: usize) -> f64 {
+    (day % 17) as f64
+}
+
+// A long record for 2000-2001, and a short one observed in 2001 only: 10 times the month in the
+// first file, and 2 * long + 1 in a file of its own
+fn model(dir_name: &str, extension: &str) -> (Model, TestDir) {
+    let dir = TestDir::new(dir_name);
+    let start = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let mut flows = String::from("Date,long,monthly\n");
+    let mut spring = String::from("Date,spring\n");
+    for day in 0..731 {
+        let date = start + chrono::Days::new(day as u64);
+        let text = date.format("%Y-%m-%d").to_string();
+        let observed = text.starts_with("2001");
+        let monthly = if observed { format!("{}", 10 * chrono::Datelike::month(&date)) } else { String::new() };
+        flows.push_str(&format!("{},{},{}\n", text, long_flow(day), monthly));
+        if observed {
+            spring.push_str(&format!("{},{}\n", text, 2.0 * long_flow(day) + 1.0));
+        }
+    }
+    dir.write("flows.csv", &flows);
+    dir.write("spring.csv", &spring);
+    let ini = format!("\
+[inputs]
+{}
+{}
+
+[node.long]
+type = inflow
+loc = 0, 0
+inflow = data.flows_csv.by_name.long
+
+[node.monthly]
+type = inflow
+loc = 0, 10
+inflow = data.flows_csv.by_name.monthly
+
+[node.spring]
+type = inflow
+loc = 0, 20
+inflow = data.spring_csv.by_name.spring
+
+{}
+
+[outputs]
+node.monthly.dsflow
+node.spring.dsflow
+", dir.join("flows.csv").display(), dir.join("spring.csv").display(), extension);
+    (IniModelIO::new().read_model_string(&ini).unwrap(), dir)
+}
+
+fn run(m: &mut Model) -> Result<(), String> {
+    m.configure()?;
+    m.run()
+}
+
+#[test]
+fn test_short_inputs_truncate_the_run_unless_extended() {
+    let (mut m, _dir) = model("kalix_extension_none", "");
+    run(&mut m).unwrap();
+    assert_eq!(m.configuration.sim_start_timestamp, date_string_to_u64_flexible("2001-01-01").unwrap().0);
+
+    let extension = "\
+[extension]
+data.flows_csv.by_name.monthly = monthly_mean
+data.spring_csv.by_name.spring = regression data.flows_csv.by_name.long";
+    let (mut m, _dir) = model("kalix_extension", extension);
+    run(&mut m).unwrap();
+    assert_eq!(m.configuration.sim_start_timestamp, date_string_to_u64_flexible("2000-01-01").unwrap().0);
+    assert_eq!(m.configuration.sim_nsteps, 731);
+
+    // Monthly means in 2000, and the fit on the long record, which is exact here
+    let monthly = series(&m, "node.monthly.dsflow");
+    for (day, value) in monthly.iter().enumerate() {
+        let month = u64_to_year_month_day_and_seconds(m.configuration.sim_start_timestamp + day as u64 * 86400).1;
+        assert_eq!(*value, 10.0 * month as f64);
+    }
+    let spring = series(&m, "node.spring.dsflow");
+    for (day, value) in spring.iter().enumerate() {
+        assert!((value - (2.0 * long_flow(day) + 1.0)).abs() < 1e-9, "day {}: {}", day, value);
+    }
+
+    // The filled steps are flagged in the run log
+    let entries: Vec<_> = m.data_cache.log.entries().iter().filter(|e| e.code == "input_extended").collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[0].level, entries[0].source.as_str()), (LogLevel::Warning, "data.flows_csv.by_name.monthly"));
+    assert!(entries[0].message.contains("366 simulated steps before and 0 after are filled by monthly_mean"), "{}", entries[0].message);
+
+    // The section is saved the way it was read
+    let ini = IniModelIO::new().model_to_string(&m);
+    assert!(ini.contains("data.spring_csv.by_name.spring = regression data.flows_csv.by_name.long"), "{}", ini);
+}
+
+#[test]
+fn test_extension_errors() {
+    let error = |extension: &str| {
+        let (mut m, _dir) = model("kalix_extension_errors", extension);
+        let err = run(&mut m).err().unwrap();
+        err
+    };
+    let err = error("[extension]\ndata.flows_csv.by_name.monthy = monthly_mean");
+    assert!(err.contains("Extended series 'data.flows_csv.by_name.monthy' was not found in any input file. Did you mean"), "{}", err);
+    let err = error("[extension]\ndata.flows_csv.by_name.monthly = regression data.flows_csv.by_name.nothing");
+    assert_eq!(err, "Series 'data.flows_csv.by_name.nothing' was not found in any input file");
+
+    let err = IniModelIO::new().read_model_string("[extension]\ndata.a.by_name.b = median").err().unwrap();
+    assert!(err.contains("Invalid extension 'median' for 'data.a.by_name.b'"), "{}", err);
+}
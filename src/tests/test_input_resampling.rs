@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tests::test_helpers::{run, TestDir};
+
+// Daily flows and six-hourly rainfall over three days
+fn write_inputs(name: &str) -> (TestDir, PathBuf, PathBuf) {
+    let dir = TestDir::new(&format!("kalix_resample_{}", name));
+    let flow = dir.write("flow.csv", "date,flow\n2000-01-01,10\n2000-01-02,20\n2000-01-03,30\n");
+    let mut csv = "date,rain\n".to_string();
+    for i in 0..12 {
+        csv.push_str(&format!("2000-01-{:02}T{:02}:00:00,{}\n", 1 + i / 4, 6 * (i % 4), i + 1));
+    }
+    let rain = dir.write("rain.csv", &csv);
+    (dir, flow, rain)
+}
+
+fn model_ini(flow: &PathBuf, rain: &PathBuf, policies: &str) -> String {
+    format!("\
+[inputs]
+flow = {}
+rain = {}
+{}
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = data.flow.by_name.flow + data.rain.by_name.rain
+
+[outputs]
+node.a.dsflow
+", flow.display(), rain.display(), policies)
+}
+
+fn dsflow(m: &Model) -> Vec<f64> {
+    m.data_cache.series[m.data_cache.get_existing_series_idx("node.a.dsflow").unwrap()].values.clone()
+}
+
+#[test]
+fn test_finer_input_is_aggregated_to_the_model_step() {
+    let (_dir, flow, rain) = write_inputs("finer");
+
+    // The model runs daily, and the rainfall is totalled over each day
+    let m = run(&model_ini(&flow, &rain, "resample.rain = sum")).unwrap();
+    assert_eq!(m.configuration.sim_stepsize, 86400);
+    assert_eq!(dsflow(&m), vec![20.0, 46.0, 72.0]);
+
+    // By default it is averaged
+    let m = run(&model_ini(&flow, &rain, "")).unwrap();
+    assert_eq!(dsflow(&m), vec![12.5, 26.5, 40.5]);
+
+    let err = run(&model_ini(&flow, &rain, "resample.rain = total")).err().unwrap();
+    assert!(err.starts_with("Error on line 4: Unknown resample option 'total'. Expected one of: mean, sum, repeat, interpolate"), "{}", err);
+    let err = run(&model_ini(&flow, &rain, "resample.rainfall = sum")).err().unwrap();
+    assert_eq!(err, "Input file 'rainfall' named by 'resample.rainfall' was not found");
+}
+
+#[test]
+fn test_coarser_input_is_filled_in_by_its_policy() {
+    let (_dir, flow, rain) = write_inputs("coarser");
+
+    // Interpolated flows run the model at the rainfall's six-hourly step. There is nothing to
+    // interpolate to after the last daily value, so the run stops there.
+    let m = run(&model_ini(&flow, &rain, "resample.flow = interpolate")).unwrap();
+    assert_eq!(m.configuration.sim_stepsize, 21600);
+    assert_eq!(dsflow(&m), vec![11.0, 14.5, 18.0, 21.5, 25.0, 28.5, 32.0, 35.5, 39.0]);
+    let saved = IniModelIO::new().model_to_string(&m);
+    assert!(saved.contains("resample.flow = interpolate"), "{}", saved);
+
+    let m = run(&model_ini(&flow, &rain, "resample.flow = repeat")).unwrap();
+    assert_eq!(dsflow(&m)[..6], [11.0, 12.0, 13.0, 14.0, 25.0, 26.0]);
+    assert_eq!(dsflow(&m).len(), 12);
+
+    // Repeating and interpolating are only for coarser data
+    let ini = model_ini(&flow, &rain, "resample.rain = repeat");
+    let err = run(&ini).err().unwrap();
+    assert!(err.contains("'repeat' only applies to data coarser than the simulation step"), "{}", err);
+}
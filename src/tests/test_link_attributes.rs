@@ -0,0 +1,137 @@
+use crate::data_management::events::EventKind;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::tests::test_helpers::series;
+
+// 100 ML/d down a channel that carries at most 60 ML/d and delivers 90% of it
+const LOSSY_CHANNEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = 100
+ds_1 = gauge
+ds_1_capacity = 60
+ds_1_efficiency = 0.9
+
+[node.gauge]
+type = gauge
+loc = 0, 10
+
+[outputs]
+node.gauge.dsflow
+";
+
+// A dam supplying an irrigator ordering 40 ML/d through a channel that delivers 80%
+const REGULATED_CHANNEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 1000
+dimensions = 0,    0,    0, 0,
+             10,   1000, 1, 0,
+             10.1, 1001, 1, 1e8
+ds_1 = irrigator
+ds_1_efficiency = 0.8
+
+[node.irrigator]
+type = regulated_user
+loc = 0, 10
+order = 40
+
+[outputs]
+node.dam.dsflow
+node.irrigator.diversion
+";
+
+fn run(ini: &str) -> Model {
+    let mut m: Model = IniModelIO::new().read_model_string(ini).unwrap();
+    m.configure().unwrap();
+    m.data_cache.events.enabled = true;
+    m.run().unwrap();
+    m
+}
+
+#[test]
+fn test_link_capacity_and_efficiency() {
+    let m = run(LOSSY_CHANNEL);
+    for q in series(&m, "node.gauge.dsflow") {
+        assert!((q - 54.0).abs() < 1e-9, "{}", q);
+    }
+
+    // Each day 40 ML is beyond the capacity
+    let events = m.events();
+    let exceeded = events.filter(Some(EventKind::LinkCapacityExceeded), Some("river.ds_1"), None, None);
+    assert_eq!(exceeded.len(), 3);
+    assert!(exceeded.iter().all(|e| (e.magnitude - 40.0).abs() < 1e-9));
+
+    // The 46 ML/d lost shows in the mass balance
+    let report = m.generate_mass_balance_report();
+    assert!(report.contains("LINKS\n  river.ds_1, -46\n"), "{}", report);
+
+    // Without attributes the link passes everything, and reports no links
+    let plain = run(&LOSSY_CHANNEL.replace("ds_1_capacity = 60\nds_1_efficiency = 0.9\n", ""));
+    assert!(series(&plain, "node.gauge.dsflow").iter().all(|&q| q == 100.0));
+    assert!(plain.events().is_empty());
+    assert!(!plain.generate_mass_balance_report().contains("LINKS"));
+}
+
+#[test]
+fn test_link_losses_added_to_orders() {
+    // The dam releases enough for the irrigator's order to arrive in full
+    let m = run(REGULATED_CHANNEL);
+    for (release, diversion) in series(&m, "node.dam.dsflow").iter().zip(series(&m, "node.irrigator.diversion")) {
+        assert!((release - 50.0).abs() < 1e-9, "{}", release);
+        assert!((diversion - 40.0).abs() < 1e-9, "{}", diversion);
+    }
+
+    // Orders are limited to what the link can carry
+    let limited = run(&REGULATED_CHANNEL.replace("ds_1_efficiency = 0.8", "ds_1_efficiency = 0.8\nds_1_capacity = 45"));
+    for (release, diversion) in series(&limited, "node.dam.dsflow").iter().zip(series(&limited, "node.irrigator.diversion")) {
+        assert!((release - 45.0).abs() < 1e-9, "{}", release);
+        assert!((diversion - 36.0).abs() < 1e-9, "{}", diversion);
+    }
+    // The irrigator goes short, but the release never exceeds the capacity
+    let events = limited.events();
+    assert!(events.filter(Some(EventKind::LinkCapacityExceeded), None, None, None).is_empty());
+    assert_eq!(events.filter(Some(EventKind::DemandShortfall), Some("irrigator"), None, None).len(), 3);
+}
+
+#[test]
+fn test_link_attributes_round_trip() {
+    let io = IniModelIO::new();
+    let m: Model = io.read_model_string(LOSSY_CHANNEL).unwrap();
+    assert_eq!(m.links[0].capacity, Some(60.0));
+    assert_eq!(m.links[0].efficiency, Some(0.9));
+    let text = io.model_to_string(&m);
+    let m2: Model = io.read_model_string(&text).unwrap();
+    assert_eq!((m2.links[0].capacity, m2.links[0].efficiency), (Some(60.0), Some(0.9)));
+
+    // Attributes set on a model are written after their link
+    let mut m3: Model = io.read_model_string(&LOSSY_CHANNEL.replace("ds_1_capacity = 60\nds_1_efficiency = 0.9\n", "")).unwrap();
+    m3.links[0].efficiency = Some(0.75);
+    assert!(io.model_to_string(&m3).contains("ds_1 = gauge\nds_1_efficiency = 0.75"));
+}
+
+#[test]
+fn test_link_attribute_errors() {
+    let io = IniModelIO::new();
+    let no_link = LOSSY_CHANNEL.replace("ds_1_capacity = 60", "ds_2_capacity = 60");
+    let err = io.read_model_string(&no_link).err().unwrap();
+    assert!(err.contains("has 'ds_2_capacity' but no ds_2 link"), "{}", err);
+
+    let too_efficient = LOSSY_CHANNEL.replace("ds_1_efficiency = 0.9", "ds_1_efficiency = 1.5");
+    let err = io.read_model_string(&too_efficient).err().unwrap();
+    assert!(err.contains("must be greater than 0 and at most 1"), "{}", err);
+
+    let negative = LOSSY_CHANNEL.replace("ds_1_capacity = 60", "ds_1_capacity = -1");
+    let err = io.read_model_string(&negative).err().unwrap();
+    assert!(err.contains("must be zero or more"), "{}", err);
+}
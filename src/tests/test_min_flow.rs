@@ -0,0 +1,55 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+// A dam holding 1000 ML with no inflow, and a gauge downstream with a minimum flow of
+// 20 ML/d. Nothing else orders water from the dam.
+const DAM_AND_GAUGE: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-05
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 1000
+dimensions = 0,    0,    0, 0,
+             10,   1000, 1, 0,
+             10.1, 1001, 1, 1e8
+ds_1 = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 10
+min_flow = 20
+
+[outputs]
+node.gauge.dsflow
+node.gauge.min_flow
+node.gauge.min_flow_shortfall
+";
+
+#[test]
+fn test_min_flow_is_released_from_storage() {
+    let m = run(DAM_AND_GAUGE).unwrap();
+    assert_eq!(series(&m, "node.gauge.dsflow"), vec![20.0; 5]);
+    assert_eq!(series(&m, "node.gauge.min_flow"), vec![20.0; 5]);
+    assert_eq!(series(&m, "node.gauge.min_flow_shortfall"), vec![0.0; 5]);
+
+    // Without a requirement the dam holds its water
+    let m = run(&DAM_AND_GAUGE.replace("min_flow = 20\n", "")).unwrap();
+    assert_eq!(series(&m, "node.gauge.dsflow"), vec![0.0; 5]);
+
+    // An empty dam cannot meet the requirement, which shows up as a shortfall
+    let m = run(&DAM_AND_GAUGE.replace("initial_volume = 1000", "initial_volume = 0")).unwrap();
+    assert_eq!(series(&m, "node.gauge.min_flow_shortfall"), vec![20.0; 5]);
+}
+
+#[test]
+fn test_min_flow_round_trip() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_string(DAM_AND_GAUGE).unwrap();
+    let text = ini_io.model_to_string(&m);
+    assert!(text.contains("min_flow = 20"));
+    let reloaded = ini_io.read_model_string(&text).unwrap();
+    assert_eq!(ini_io.model_to_string(&reloaded), text);
+}
@@ -48,6 +48,7 @@ fn test_model_constant_optimisation() {
         seed: Some(42),
         n_threads: 1,
         progress_callback: None,
+        ..Default::default()
     };
     let optimiser = DifferentialEvolution::new(de_config);
 
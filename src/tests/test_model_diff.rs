@@ -0,0 +1,92 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+
+const BASE: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-12-31
+
+[node.catchment]
+type = gr4j
+loc = 0, 0
+rain = 3
+evap = 2
+area = 100
+params = 350, 0, 90, 1.7
+ds_1 = reach
+
+[node.reach]
+type = routing
+loc = 0, 10
+pwl = 0, 1, 1000, 1
+ds_1 = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 20
+
+[outputs]
+node.gauge.dsflow
+";
+
+fn read(ini: &str) -> Model {
+    IniModelIO::new().read_model_string(ini).unwrap()
+}
+
+#[test]
+fn test_diff_of_identical_models() {
+    // Formatting and comments make no difference
+    let reformatted = BASE.replace("params = 350, 0, 90, 1.7", "; Calibrated\nparams = 350.0,0,\n    90,1.70");
+    let diff = read(BASE).diff(&read(&reformatted));
+    assert!(diff.is_empty(), "{:?}", diff);
+    assert_eq!(diff.to_text(), "No differences.\n");
+}
+
+#[test]
+fn test_diff_of_scenario() {
+    // A weir added between the reach and the gauge, with a recalibrated catchment
+    let scenario = BASE
+        .replace("params = 350, 0, 90, 1.7", "params = 360, 0, 85.5, 1.7")
+        .replace("end = 2000-12-31", "end = 2001-12-31")
+        .replace("pwl = 0, 1, 1000, 1\nds_1 = gauge", "pwl = 0, 1, 1000, 1\nds_1 = weir")
+        .replace("[outputs]", "[node.weir]\ntype = gauge\nloc = 0, 15\nds_1 = gauge\n\n[outputs]\nnode.weir.dsflow");
+    let diff = read(BASE).diff(&read(&scenario));
+
+    assert_eq!(diff.added_nodes, vec![("weir".to_string(), "gauge".to_string())]);
+    assert!(diff.removed_nodes.is_empty());
+
+    let params = diff.changed_properties.iter().find(|c| c.key == "params").unwrap();
+    assert_eq!(params.section, "node.catchment");
+    assert_eq!(params.numeric_deltas().unwrap(), vec![10.0, 0.0, -4.5, 0.0]);
+    assert!(diff.changed_properties.iter().any(|c| c.section == "kalix" && c.key == "end"));
+    assert!(diff.changed_properties.iter().any(|c| c.section == "outputs" && c.key == "node.weir.dsflow"
+        && c.old_value.is_none()));
+    // The new node's own properties and links aren't listed
+    assert!(diff.changed_properties.iter().all(|c| c.section != "node.weir"));
+
+    assert_eq!(diff.rerouted_links.len(), 1);
+    let link = &diff.rerouted_links[0];
+    assert_eq!((link.from_node.as_str(), link.outlet), ("reach", 1));
+    assert_eq!((link.old_to_node.as_deref(), link.new_to_node.as_deref()), (Some("gauge"), Some("weir")));
+
+    let text = diff.to_text();
+    assert!(text.contains("  + weir (gauge)"), "{}", text);
+    assert!(text.contains("[node.catchment] params: 350, 0, 90, 1.7 -> 360, 0, 85.5, 1.7 (change +10, 0, -4.5, 0)"), "{}", text);
+    assert!(text.contains("[outputs] node.weir.dsflow: added"), "{}", text);
+    assert!(text.contains("reach ds_1: gauge -> weir"), "{}", text);
+
+    // And back again
+    let reverse = read(&scenario).diff(&read(BASE));
+    assert_eq!(reverse.removed_nodes, vec![("weir".to_string(), "gauge".to_string())]);
+    assert_eq!(reverse.rerouted_links[0].new_to_node.as_deref(), Some("gauge"));
+}
+
+#[test]
+fn test_diff_of_removed_link() {
+    let unlinked = BASE.replace("pwl = 0, 1, 1000, 1\nds_1 = gauge", "pwl = 0, 1, 1000, 1");
+    let diff = read(BASE).diff(&read(&unlinked));
+    assert!(diff.changed_properties.is_empty(), "{:?}", diff.changed_properties);
+    assert_eq!(diff.rerouted_links.len(), 1);
+    assert_eq!(diff.rerouted_links[0].new_to_node, None);
+    assert!(diff.to_text().contains("reach ds_1: gauge -> none"));
+}
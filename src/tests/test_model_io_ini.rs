@@ -1,7 +1,7 @@
 use crate::io::ini_model_io::IniModelIO;
 use crate::model::Model;
 use crate::nodes::Node;
-use crate::tests::test_helpers::print_text_diff;
+use crate::tests::test_helpers::{print_text_diff, TestDir};
 
 #[test]
 fn test_model_1_io_ini_read() {
@@ -49,8 +49,8 @@ fn test_model_1_io_ini_read() {
     println!("Printing the data cache");
     m.data_cache.print();
     println!(" ");
-    for temp in &m.data_cache.series {
-         temp.print();
+    for idx in 0..m.data_cache.series.len() {
+         m.data_cache.get_series(idx).print();
     }
 }
 
@@ -395,7 +395,7 @@ fn test_changed_unregulated_user_keeps_account() {
     for node in &mut model.nodes {
         if let crate::nodes::NodeEnum::UnregulatedUserNode(n) = node {
             n.annual_cap = Some(500.0);
-            n.annual_cap_reset_month = 6;
+            n.annual_cap_year = crate::tid::water_year::WaterYear::new(6).unwrap();
         }
     }
 
@@ -405,3 +405,145 @@ fn test_changed_unregulated_user_keeps_account() {
     assert!(saved.contains("account = myacc, general, 1000, 7"),
             "changed unregulated_user must keep its account, got:\n{}", saved);
 }
+
+#[test]
+fn test_changed_node_keeps_metadata() {
+    // meta_* keys are user tags rather than parameters. They must survive a
+    // canonical re-render. We change area to force it.
+    let ini = "[kalix]\n\
+               \n\
+               [node.g]\n\
+               type = gr4j\n\
+               loc = 10, 20\n\
+               META_Region = Upper Murray\n\
+               area = 30\n\
+               params = 350, 0, 90, 1.7\n\
+               meta_owner = Water NSW\n\
+               ds_1 = bh\n\
+               \n\
+               [node.bh]\n\
+               type = blackhole\n\
+               loc = 1, 2\n";
+
+    let ini_io = IniModelIO::new();
+    let mut model = ini_io.read_model_string(ini).expect("model should parse");
+    let metadata: Vec<(&str, &str)> = model.nodes[0].get_metadata().iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    assert_eq!(metadata, vec![("region", "Upper Murray"), ("owner", "Water NSW")]);
+    assert!(model.nodes[1].get_metadata().is_empty());
+
+    // Force the gr4j section to re-render canonically.
+    for node in &mut model.nodes {
+        if let crate::nodes::NodeEnum::Gr4jNode(n) = node {
+            n.area_km2 = 40.0;
+        }
+    }
+
+    let saved = ini_io.model_to_string(&model);
+
+    assert!(saved.contains("area = 40"), "expected changed section, got:\n{}", saved);
+    assert!(saved.contains("meta_region = Upper Murray") && saved.contains("meta_owner = Water NSW"),
+            "changed node must keep its metadata, got:\n{}", saved);
+    assert!(!saved.contains("META_Region"), "metadata keys are written lowercase, got:\n{}", saved);
+
+    // Metadata keys follow the same rules as other names
+    let bad = ini.replace("meta_owner", "meta_");
+    assert!(ini_io.read_model_string(&bad).is_err());
+}
+
+#[test]
+fn test_model_4_with_overlays() {
+    // Two overlays over model 4: the first halves node2's inflow, the second removes
+    // one of the outputs and tags node1. The expected node3 flow is 100 + 50.
+    let dir = TestDir::new("kalix_overlays");
+    let overlay_1 = dir.write("overlay_1.ini", "[node.node2]\ninflow = 50\n");
+    let overlay_2 = dir.write("overlay_2.ini", "[outputs]\n-node.node1.dsflow\n\n[node.node1]\nmeta_scenario = half\n");
+    let overlays = vec![overlay_1.to_string_lossy().to_string(), overlay_2.to_string_lossy().to_string()];
+
+    let ini_reader = IniModelIO::new();
+    let result = ini_reader.read_model_file_with_overlays("./src/tests/example_models/4/linked_model.ini", &overlays);
+
+    // A removal that does not match anything names the overlay
+    dir.write("overlay_2.ini", "[-node.node9]\n");
+    let err = ini_reader.read_model_file_with_overlays("./src/tests/example_models/4/linked_model.ini", &overlays)
+        .err().unwrap();
+    assert!(err.contains("overlay_2.ini") && err.contains("node.node9"), "got: {}", err);
+
+    let mut m = result.unwrap();
+    assert_eq!(m.outputs.len(), 13);
+    assert_eq!(m.nodes[0].get_metadata().get("scenario").map(|s| s.as_str()), Some("half"));
+    m.configure().unwrap();
+    m.run().unwrap();
+    let node3_dsflow = &m.data_cache.series[m.data_cache.get_existing_series_idx("node.node3.dsflow").unwrap()];
+    assert!((node3_dsflow.mean() - 150.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_model_4_with_included_inputs() {
+    // Model 4 with its [inputs] moved to a shared file that it includes. Input paths are
+    // still relative to the model's folder.
+    let ini = std::fs::read_to_string("./src/tests/example_models/4/linked_model.ini").unwrap();
+    let start = ini.find("[inputs]").unwrap();
+    let end = ini.find("[node.node1]").unwrap();
+    let dir = TestDir::new("kalix_included_inputs");
+    for csv in ["rex_mpot.csv", "rex_rain.csv", "inflow_100_200.csv"] {
+        std::fs::copy(format!("./src/tests/example_models/4/{}", csv), dir.join(csv)).unwrap();
+    }
+    dir.write("shared_inputs.ini", &ini[start..end]);
+    let model_file = dir.write("model.ini", &format!("include = shared_inputs.ini\n\n{}{}", &ini[..start], &ini[end..]));
+
+    let mut m = IniModelIO::new().read_model_file(model_file.to_str().unwrap()).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    let node3_dsflow = &m.data_cache.series[m.data_cache.get_existing_series_idx("node.node3.dsflow").unwrap()];
+    assert!((node3_dsflow.mean() - 300.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_model_4_with_input_scaling() {
+    // Doubling little_flow (100) and adding 10% to node2's inflow (200) takes node3
+    // from 300 to 420. Scaling is applied at configure time, so configuring twice
+    // must not compound it.
+    let ini = std::fs::read_to_string("./src/tests/example_models/4/linked_model.ini").unwrap()
+        .replace("[node.node1]", "[scaling]\n\
+                                  data.inflow_100_200_csv.by_name.little_flow = *2\n\
+                                  data.inflow_100_200_csv.by_index.2 = +10%\n\
+                                  \n\
+                                  [node.node1]");
+    let ini_io = IniModelIO::new();
+    let mut m = ini_io.read_model_string_with_working_directory(
+        &ini, Some(std::path::PathBuf::from("./src/tests/example_models/4"))).unwrap();
+    assert_eq!(m.input_scaling.len(), 2);
+    m.configure().unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    let node3_dsflow = &m.data_cache.series[m.data_cache.get_existing_series_idx("node.node3.dsflow").unwrap()];
+    assert!((node3_dsflow.mean() - 420.0).abs() < 1e-9);
+
+    // The section is written back
+    let saved = ini_io.model_to_string(&m);
+    assert!(saved.contains("data.inflow_100_200_csv.by_index.2 = +10%"), "got:\n{}", saved);
+
+    // Unknown series are reported at configure time; bad definitions at load time
+    let typo = ini.replace("little_flow = *2", "little_flew = *2");
+    let mut m = ini_io.read_model_string_with_working_directory(
+        &typo, Some(std::path::PathBuf::from("./src/tests/example_models/4"))).unwrap();
+    assert!(m.configure().unwrap_err().contains("little_flew"));
+    let bad = ini.replace("little_flow = *2", "little_flow = double");
+    assert!(ini_io.read_model_string_with_working_directory(
+        &bad, Some(std::path::PathBuf::from("./src/tests/example_models/4"))).is_err());
+}
+
+#[test]
+fn test_model_to_json() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_file("./src/tests/example_models/1/first_model.ini").unwrap();
+    let json = ini_io.model_to_json(&m);
+
+    assert_eq!(json["inputs"][0]["path"], "./rex_mpot.csv");
+    assert_eq!(json["inputs"].as_array().unwrap().len(), 2);
+    assert_eq!(json["nodes"]["my_gr4j_node"]["type"], "gr4j");
+    assert_eq!(json["nodes"]["my_gr4j_node"]["area"], "22.8");
+    assert_eq!(json["outputs"][0], "node.my_gr4j_node.dsflow");
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+
+const MODEL_INI: &str = "\
+[kalix]
+#version = 0.0.1
+
+[constants]
+c.demand = 5.0
+
+[inputs]
+./src/tests/example_models/5/data.csv
+
+[node.node1]
+type = inflow
+loc = 0, 0
+inflow = data.data_csv.by_index.3
+ds_1 = user
+
+[node.user]
+type = unregulated_user
+loc = 0, 50
+demand = c.demand
+ds_1 = loss
+
+[node.loss]
+type = loss
+loc = 0, 100
+table = 0, 0, 1e9, 1e8
+
+[outputs]
+node.user.diversion
+node.loss.usflow
+node.loss.loss
+";
+
+fn load_model(ini: &str) -> Model {
+    IniModelIO::new().read_model_string_with_working_directory(ini, Some(PathBuf::from("."))).unwrap()
+}
+
+fn run_and_get(model: &mut Model, series: &str) -> Vec<f64> {
+    model.configure().unwrap();
+    model.run().unwrap();
+    let idx = model.data_cache.get_existing_series_idx(series).unwrap();
+    model.data_cache.series[idx].values.clone()
+}
+
+#[test]
+fn test_demand_multiplier_matches_scaled_constant() {
+    let mut scaled_constant = load_model(MODEL_INI);
+    scaled_constant.set_parameter("c.demand", 10.0).unwrap();
+    let expected = run_and_get(&mut scaled_constant, "node.user.diversion");
+
+    let mut multiplied = load_model(MODEL_INI);
+    assert_eq!(multiplied.get_parameter("node.user.demand_multiplier").unwrap(), 1.0);
+    multiplied.set_parameter("node.user.demand_multiplier", 2.0).unwrap();
+    assert_eq!(multiplied.get_parameter("node.user.demand_multiplier").unwrap(), 2.0);
+    let diversion = run_and_get(&mut multiplied, "node.user.diversion");
+
+    assert_eq!(diversion, expected);
+    assert!(diversion.iter().any(|&d| d > 5.0));
+}
+
+#[test]
+fn test_loss_scale_scales_loss_table() {
+    let mut model = load_model(MODEL_INI);
+    model.set_parameter("node.loss.loss_scale", 0.5).unwrap();
+    let loss = run_and_get(&mut model, "node.loss.loss");
+    let usflow = run_and_get(&mut model, "node.loss.usflow");
+    for (l, q) in loss.iter().zip(usflow.iter()) {
+        assert!((l - 0.05 * q).abs() < 1e-9);
+    }
+
+    // A scale that makes the loss exceed the inflow is rejected when the model is configured
+    model.set_parameter("node.loss.loss_scale", 20.0).unwrap();
+    assert!(model.configure().is_err());
+}
+
+#[test]
+fn test_optimisable_quantities_round_trip_through_ini() {
+    let mut model = load_model(MODEL_INI);
+    model.set_parameter("node.user.demand_multiplier", 1.25).unwrap();
+    model.set_parameter("node.loss.loss_scale", 0.75).unwrap();
+
+    let ini = IniModelIO::new().model_to_string(&model);
+    assert!(ini.contains("demand_multiplier = 1.25"), "{}", ini);
+    assert!(ini.contains("loss_scale = 0.75"), "{}", ini);
+
+    let reloaded = load_model(&ini);
+    assert_eq!(reloaded.get_parameter("node.user.demand_multiplier").unwrap(), 1.25);
+    assert_eq!(reloaded.get_parameter("node.loss.loss_scale").unwrap(), 0.75);
+}
+
+#[test]
+fn test_unknown_quantities_are_rejected() {
+    let mut model = load_model(MODEL_INI);
+    assert!(model.set_parameter("node.user.pump_multiplier", 1.0).is_err());
+    assert!(model.set_parameter("node.node1.demand_multiplier", 1.0).is_err());
+    assert!(model.get_parameter("node.missing.loss_scale").is_err());
+}
@@ -0,0 +1,154 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::model_validation::{ValidationReport, ValidationSeverity};
+use crate::model::Model;
+
+fn load(ini: &str) -> Model {
+    IniModelIO::new()
+        .read_model_string_with_working_directory(ini, Some(PathBuf::from("./src/tests/example_data")))
+        .expect("Should be able to parse the model")
+}
+
+fn codes(report: &ValidationReport, severity: ValidationSeverity) -> Vec<String> {
+    report.issues.iter()
+        .filter(|i| i.severity == severity)
+        .map(|i| i.code.clone())
+        .collect()
+}
+
+#[test]
+fn test_valid_model_has_no_issues() {
+    let model = load("\
+[kalix]
+
+[inputs]
+./test.csv
+
+[node.inflow]
+type = inflow
+loc = 0, 0
+inflow = data.test_csv.by_name.value
+ds_1 = sink
+
+[node.sink]
+type = blackhole
+loc = 0, 1
+");
+    let report = model.validate();
+    assert!(report.is_valid());
+    assert!(report.issues.is_empty(), "Unexpected issues: {:?}", report.issues);
+}
+
+#[test]
+fn test_detects_cycle_and_disconnected_node() {
+    let model = load("\
+[kalix]
+start = 2022-08-09
+end = 2022-08-14
+
+[node.a]
+type = confluence
+loc = 0, 0
+ds_1 = b
+
+[node.b]
+type = confluence
+loc = 0, 1
+ds_1 = a
+
+[node.lonely]
+type = blackhole
+loc = 0, 2
+");
+    let report = model.validate();
+    assert!(!report.is_valid());
+    assert_eq!(codes(&report, ValidationSeverity::Error), vec!["cycle"]);
+    assert_eq!(codes(&report, ValidationSeverity::Warning), vec!["disconnected_node"]);
+
+    let disconnected = &report.warnings()[0];
+    assert_eq!(disconnected.section.as_deref(), Some("node.lonely"));
+    assert_eq!(disconnected.line_number, Some(15));
+}
+
+#[test]
+fn test_detects_missing_input_and_unassigned_constant() {
+    let model = load("\
+[kalix]
+
+[inputs]
+./test.csv
+
+[node.inflow]
+type = inflow
+loc = 0, 0
+inflow = data.test_csv.by_name.valuee * c.factor
+ds_1 = sink
+
+[node.sink]
+type = blackhole
+loc = 0, 1
+");
+    let report = model.validate();
+    let errors = report.errors();
+    assert_eq!(codes(&report, ValidationSeverity::Error), vec!["missing_input", "unassigned_constant"]);
+    for e in errors {
+        assert_eq!(e.section.as_deref(), Some("node.inflow"));
+        assert_eq!(e.line_number, Some(9));
+    }
+}
+
+#[test]
+fn test_detects_zero_area_and_bad_storage_table() {
+    let model = load("\
+[kalix]
+start = 2022-08-09
+end = 2022-08-14
+
+[node.catchment]
+type = gr4j
+loc = 0, 0
+area = 0
+params = 350, 0, 90, 1.7
+ds_1 = dam
+
+[node.dam]
+type = storage
+loc = 0, 1
+dimensions = 90, 0, 0, 0,
+             91, 100, 1, 0,
+             92, 100, 1, 0
+");
+    let report = model.validate();
+    assert_eq!(codes(&report, ValidationSeverity::Warning), vec!["zero_area"]);
+    assert_eq!(report.warnings()[0].line_number, Some(8));
+    assert_eq!(codes(&report, ValidationSeverity::Error), vec!["non_monotonic_table"]);
+    assert_eq!(report.errors()[0].line_number, Some(15));
+    assert!(report.errors()[0].message.contains("volumes"));
+}
+
+#[test]
+fn test_detects_specified_period_outside_input_data() {
+    let model = load("\
+[kalix]
+start = 2022-08-10
+end = 2022-09-01
+
+[inputs]
+./test.csv
+
+[node.inflow]
+type = inflow
+loc = 0, 0
+inflow = data.test_csv.by_name.value
+ds_1 = sink
+
+[node.sink]
+type = blackhole
+loc = 0, 1
+");
+    let report = model.validate();
+    assert_eq!(codes(&report, ValidationSeverity::Error), vec!["date_range"]);
+    let error = &report.errors()[0];
+    assert_eq!(error.section.as_deref(), Some("kalix"));
+    assert_eq!(error.line_number, Some(3));
+}
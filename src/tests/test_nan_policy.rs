@@ -0,0 +1,113 @@
+use crate::tests::test_helpers::TestDir;
+use crate::data_management::events::EventKind;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+
+// Two inflows into a confluence over 2000-01-01 to 01-05: b has every value, and a is missing
+// on 01-03 (its file is filled with NaN, so the gap does not clip the period)
+fn model(dir_name: &str, kalix: &str, inputs: &str) -> (Model, TestDir) {
+    let dir = TestDir::new(dir_name);
+    dir.write("a.csv", "Date,a\n2000-01-01,1\n2000-01-02,2\n2000-01-03,\n2000-01-04,4\n2000-01-05,5\n");
+    dir.write("b.csv", "Date,b\n2000-01-01,10\n2000-01-02,10\n2000-01-03,10\n2000-01-04,10\n2000-01-05,10\n");
+    let ini = format!("\
+[kalix]
+{}
+
+[inputs]
+{}
+{}
+coverage.a_csv = fill(nan)
+{}
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = data.a_csv.by_name.a
+ds_1 = b
+
+[node.b]
+type = inflow
+loc = 0, 10
+inflow = data.b_csv.by_name.b
+
+[outputs]
+node.b.dsflow
+", kalix, dir.join("a.csv").display(), dir.join("b.csv").display(), inputs);
+    (IniModelIO::new().read_model_string(&ini).unwrap(), dir)
+}
+
+fn run(dir_name: &str, kalix: &str, inputs: &str) -> Result<Model, String> {
+    let (mut m, _dir) = model(dir_name, kalix, inputs);
+    let result = m.configure().and_then(|_| m.run());
+    result.map(|_| m)
+}
+
+fn dsflow(m: &Model) -> Vec<f64> {
+    m.data_cache.series[m.data_cache.get_existing_series_idx("node.b.dsflow").unwrap()].values.clone()
+}
+
+#[test]
+fn test_missing_values_propagate_by_default() {
+    let m = run("kalix_nan_propagate", "", "").unwrap();
+    let flow = dsflow(&m);
+    assert!(flow[2].is_nan());
+    assert_eq!((flow[1], flow[3]), (12.0, 14.0));
+    assert_eq!((m.run_log().count("input_nan"), m.run_log().count("input_nan_substituted")), (1, 0));
+    let entry = m.run_log().entries().iter().find(|e| e.code == "input_nan").unwrap();
+    assert_eq!((entry.source.as_str(), entry.message.as_str()), ("a", "Inflow input is missing; downstream flows will be missing too"));
+}
+
+#[test]
+fn test_missing_values_pass_through_link_capacity() {
+    // The capacity of the link from a does not turn the missing value into a full link
+    let (mut m, _dir) = model("kalix_nan_link_capacity", "", "");
+    m.links[0].capacity = Some(100.0);
+    m.data_cache.events.enabled = true;
+    m.configure().unwrap();
+    m.run().unwrap();
+    let flow = dsflow(&m);
+    assert!(flow[2].is_nan());
+    assert_eq!((flow[1], flow[3]), (12.0, 14.0));
+    assert!(m.events().filter(Some(EventKind::LinkCapacityExceeded), None, None, None).is_empty());
+    assert_eq!(m.links[0].mbal, 0.0);
+}
+
+#[test]
+fn test_missing_values_substituted_by_zero() {
+    let m = run("kalix_nan_zero", "nan_policy = zero", "").unwrap();
+    assert_eq!(dsflow(&m), vec![11.0, 12.0, 10.0, 14.0, 15.0]);
+    assert_eq!((m.run_log().count("input_nan"), m.run_log().count("input_nan_substituted")), (0, 1));
+
+    // Set for the file, in place of the model's policy
+    let m = run("kalix_nan_zero_file", "", "nan_policy.a_csv = zero").unwrap();
+    assert_eq!(dsflow(&m)[2], 10.0);
+
+    // Saved the way they were read
+    let ini = IniModelIO::new().model_to_string(&m);
+    assert!(ini.contains("nan_policy.a_csv = zero"), "{}", ini);
+    let m = run("kalix_nan_zero_saved", "nan_policy = zero", "").unwrap();
+    let ini = IniModelIO::new().model_to_string(&m);
+    assert!(ini.contains("nan_policy = zero"), "{}", ini);
+}
+
+#[test]
+fn test_missing_values_stop_the_run() {
+    let err = run("kalix_nan_error", "nan_policy = zero", "nan_policy.a_csv = error").err().unwrap();
+    assert!(err.contains("2000-01-03"), "{}", err);
+    assert!(err.contains("Node: 'a'"), "{}", err);
+    assert!(err.contains("Inflow input 'data.a_csv.by_name.a' is missing and its nan_policy is 'error'"), "{}", err);
+
+    // Only the file that is set to error
+    let m = run("kalix_nan_error_other", "", "nan_policy.b_csv = error").unwrap();
+    assert!(dsflow(&m)[2].is_nan());
+}
+
+#[test]
+fn test_nan_policy_errors() {
+    let err = run("kalix_nan_policy_missing_file", "", "nan_policy.c_csv = zero").err().unwrap();
+    assert_eq!(err, "Input file 'c_csv' named by 'nan_policy.c_csv' was not found");
+    let err = IniModelIO::new().read_model_string("[kalix]\nnan_policy = ignore").err().unwrap();
+    assert!(err.contains("Unknown nan_policy option 'ignore'. Expected one of: propagate, zero, error"), "{}", err);
+    let err = IniModelIO::new().read_model_string("[inputs]\nnan_policy.a_csv = skip").err().unwrap();
+    assert!(err.contains("Unknown nan_policy option 'skip'"), "{}", err);
+}
@@ -0,0 +1,75 @@
+use crate::data_management::data_cache::DataCache;
+use crate::data_management::events::EventKind;
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+
+// An inflow whose data turns negative on 2000-01-03 (e.g. from a bad rating), above a confluence
+fn model(kalix: &str) -> Model {
+    let ini = format!("\
+[kalix]
+start = 2000-01-01
+end = 2000-01-05
+{}
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = if(sim.day == 3, -5, 10)
+ds_1 = b
+
+[node.b]
+type = confluence
+loc = 0, 10
+
+[outputs]
+node.b.dsflow
+", kalix);
+    IniModelIO::new().read_model_string(&ini).unwrap()
+}
+
+fn run(kalix: &str) -> Result<Model, String> {
+    let mut m = model(kalix);
+    m.data_cache.events.enabled = true;
+    m.configure()?;
+    m.run()?;
+    Ok(m)
+}
+
+#[test]
+fn test_negative_link_flow_is_clamped_and_reported() {
+    let m = run("").unwrap();
+    let b = &m.data_cache.series[m.data_cache.get_existing_series_idx("node.b.dsflow").unwrap()].values;
+    assert_eq!(*b, vec![10.0, 10.0, 0.0, 10.0, 10.0]);
+    let events = m.events().filter(Some(EventKind::NegativeFlowClamped), None, None, None);
+    assert_eq!(events.len(), 1);
+    assert_eq!((m.events().source_name(events[0]), events[0].step, events[0].magnitude), ("a.ds_1", 2, 5.0));
+    let entry = m.run_log().entries().iter().find(|e| e.code == "negative_flow_clamped").unwrap();
+    assert_eq!(entry.message, "Negative flow of -5 ML set to zero");
+}
+
+#[test]
+fn test_strict_mode_stops_the_run() {
+    let err = run("strict = true").err().unwrap();
+    assert!(err.starts_with("2000-01-03"), "{}", err);
+    assert!(err.contains("Negative flow of -5 ML at 'a.ds_1'. Without 'strict = true' in [kalix] it would be set to zero"), "{}", err);
+
+    // Within the tolerance, a negative flow is not guarded against
+    let m = run("strict = true\nnegative_tolerance = 5").unwrap();
+    assert!(m.events().is_empty());
+    let ini = IniModelIO::new().model_to_string(&m);
+    assert!(ini.contains("strict = true") && ini.contains("negative_tolerance = 5"), "{}", ini);
+
+    let err = IniModelIO::new().read_model_string("[kalix]\nnegative_tolerance = -1").err().unwrap();
+    assert!(err.contains("Invalid negative_tolerance '-1'"), "{}", err);
+}
+
+#[test]
+fn test_negative_storage_is_clamped() {
+    let mut data_cache = DataCache::new();
+    data_cache.events.enabled = true;
+    let dam = data_cache.add_event_source("dam");
+    assert_eq!(data_cache.guard_negative(EventKind::NegativeStorageClamped, dam, -1e-9), -1e-9);
+    assert_eq!(data_cache.guard_negative(EventKind::NegativeStorageClamped, dam, -2.0), 0.0);
+    assert_eq!(data_cache.events.len(), 1);
+    assert_eq!(data_cache.log.entries()[0].message, "Negative volume of -2 ML set to zero");
+}
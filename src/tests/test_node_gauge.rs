@@ -0,0 +1,54 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+// Observed levels are rated to flows (level 1.5 is 20 ML/d, and 5 is off the rating). The
+// quality code is the day of the month, and codes above 3 are excluded.
+const RATED_GAUGE: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-04
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = 25
+ds_1 = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 10
+reference_level = if(sim.day == 2, 5, 1.5)
+rating = 0, 0, 1, 10, 2, 30
+quality = sim.day
+quality_threshold = 3
+
+[outputs]
+node.gauge.reference_level
+node.gauge.reference_flow
+node.gauge.delta
+";
+
+fn nan_as(values: Vec<f64>, missing: f64) -> Vec<f64> {
+    values.iter().map(|v| if v.is_nan() { missing } else { *v }).collect()
+}
+
+#[test]
+fn test_gauge_rating_and_quality_filter() {
+    let m = run(RATED_GAUGE).unwrap();
+    assert_eq!(series(&m, "node.gauge.reference_level"), vec![1.5, 5.0, 1.5, 1.5]);
+    assert_eq!(nan_as(series(&m, "node.gauge.reference_flow"), -1.0), vec![20.0, -1.0, 20.0, -1.0]);
+    assert_eq!(nan_as(series(&m, "node.gauge.delta"), -1.0), vec![5.0, -1.0, 5.0, -1.0]);
+}
+
+#[test]
+fn test_gauge_rating_round_trip_and_checks() {
+    let ini_io = IniModelIO::new();
+    let text = ini_io.model_to_string(&ini_io.read_model_string(RATED_GAUGE).unwrap());
+    assert!(text.contains("quality_threshold = 3"));
+    assert_eq!(ini_io.model_to_string(&ini_io.read_model_string(&text).unwrap()), text);
+
+    let err = run(&RATED_GAUGE.replace("rating = 0, 0, 1, 10, 2, 30\n", "")).err().unwrap();
+    assert!(err.contains("rating table must be given together"));
+    let err = run(&RATED_GAUGE.replace("quality_threshold = 3\n", "")).err().unwrap();
+    assert!(err.contains("quality_threshold must be given together"));
+}
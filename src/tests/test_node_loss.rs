@@ -0,0 +1,82 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::nodes::Node;
+use crate::nodes::NodeEnum;
+use crate::tests::test_helpers::{run, series};
+
+// 100 ML/d flows through a loss node that loses half its inflow, over the end of January and
+// the start of February. Half of the loss returns to the outfall two days later.
+const EFFLUENT_RETURN: &str = "\
+[kalix]
+start = 2000-01-30
+end = 2000-02-03
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = 100
+ds_1 = town
+
+[node.town]
+type = loss
+loc = 0, 10
+table = 0, 0, 100, 50
+monthly_factors = 1, 0.4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+return_fraction = 0.5
+return_lag = 2
+ds_1 = reach
+ds_2 = outfall
+
+[node.reach]
+type = gauge
+loc = 0, 20
+ds_1 = outfall
+
+[node.outfall]
+type = gauge
+loc = 0, 30
+
+[outputs]
+node.town.loss
+node.town.ds_2
+node.outfall.dsflow
+";
+
+#[test]
+fn test_loss_monthly_factors_and_return_flow() {
+    let m = run(EFFLUENT_RETURN).unwrap();
+
+    // February losses are 0.4 of the table's
+    assert_eq!(series(&m, "node.town.loss"), vec![50.0, 50.0, 20.0, 20.0, 20.0]);
+
+    // Half the loss comes back two days later
+    assert_eq!(series(&m, "node.town.ds_2"), vec![0.0, 0.0, 25.0, 25.0, 10.0]);
+    assert_eq!(series(&m, "node.outfall.dsflow"), vec![50.0, 50.0, 105.0, 105.0, 90.0]);
+
+    // Returns still in transit at the end of the run are counted as lost
+    let town = m.nodes.iter().find_map(|n| match n { NodeEnum::LossNode(l) => Some(l), _ => None }).unwrap();
+    assert_eq!(town.get_mass_balance(), -(50.0 + 50.0 + 20.0 + 20.0 + 20.0) + 60.0);
+}
+
+#[test]
+fn test_loss_factor_expression() {
+    let ini = EFFLUENT_RETURN
+        .replace("monthly_factors = 1, 0.4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1\n", "loss_factor = if(sim.day == 31, 2, 1)\n");
+    let m = run(&ini).unwrap();
+
+    // A factor above one cannot lose more than the inflow
+    assert_eq!(series(&m, "node.town.loss"), vec![50.0, 100.0, 50.0, 50.0, 50.0]);
+}
+
+#[test]
+fn test_loss_round_trip_and_checks() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_string(EFFLUENT_RETURN).unwrap();
+    let text = ini_io.model_to_string(&m);
+    assert!(text.contains("monthly_factors = 1, 0.4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1"));
+    assert!(text.contains("return_lag = 2"));
+    assert_eq!(ini_io.model_to_string(&ini_io.read_model_string(&text).unwrap()), text);
+
+    assert!(ini_io.read_model_string(&EFFLUENT_RETURN.replace("1, 0.4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1", "1, 0.4")).is_err());
+    let err = run(&EFFLUENT_RETURN.replace("return_fraction = 0.5", "return_fraction = 1.5")).err().unwrap();
+    assert!(err.contains("return_fraction"));
+}
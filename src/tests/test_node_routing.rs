@@ -53,4 +53,76 @@ fn test_inflow_node_with_timeseries() {
     // //Check the results
     // assert_eq!(result_dsflow_ts.len(), 6);
     // assert_eq!(result_dsflow_ts.sum(), 38.1);
-}
\ No newline at end of file
+}
+
+// 1000 ML/d passes through a one-day lag, after which an anabranch takes part of the flow
+const DISTRIBUTARY: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-04
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = 1000
+ds_1 = reach
+
+[node.reach]
+type = routing
+loc = 0, 10
+lag = 1
+split_table = 0, 0, 500, 0, 2000, 600
+ds_1 = main
+ds_2 = anabranch
+
+[node.main]
+type = gauge
+loc = 0, 20
+
+[node.anabranch]
+type = gauge
+loc = 10, 20
+
+[outputs]
+node.reach.dsflow
+node.reach.ds_1
+node.reach.ds_2
+node.anabranch.dsflow
+";
+
+fn run_model(ini: &str) -> Result<Model, String> {
+    let mut m = crate::io::ini_model_io::IniModelIO::new().read_model_string(ini)?;
+    m.configure()?;
+    m.run()?;
+    Ok(m)
+}
+
+fn output(m: &Model, name: &str) -> Vec<f64> {
+    let idx = m.data_cache.get_existing_series_idx(name).unwrap();
+    m.data_cache.get_series(idx).values.clone()
+}
+
+#[test]
+fn test_routing_distributary_split() {
+    let m = run_model(DISTRIBUTARY).unwrap();
+    assert_eq!(output(&m, "node.reach.dsflow"), vec![0.0, 1000.0, 1000.0, 1000.0]);
+    assert_eq!(output(&m, "node.reach.ds_1"), vec![0.0, 800.0, 800.0, 800.0]);
+    assert_eq!(output(&m, "node.reach.ds_2"), vec![0.0, 200.0, 200.0, 200.0]);
+    assert_eq!(output(&m, "node.anabranch.dsflow"), output(&m, "node.reach.ds_2"));
+
+    // The reach holds one day of flow; the split itself loses nothing
+    if let NodeEnum::RoutingNode(reach) = &m.nodes[m.get_node_idx("reach").unwrap()] {
+        assert_eq!(crate::nodes::Node::get_mass_balance(reach), -1000.0);
+    }
+
+    // The table and second outlet survive a save
+    let saved = crate::io::ini_model_io::IniModelIO::new().model_to_string(&m);
+    assert_eq!(output(&run_model(&saved).unwrap(), "node.reach.ds_2"), vec![0.0, 200.0, 200.0, 200.0]);
+}
+
+#[test]
+fn test_routing_split_table_must_not_exceed_flow() {
+    let ini = DISTRIBUTARY.replace("split_table = 0, 0, 500, 0, 2000, 600", "split_table = 0, 0, 500, 600");
+    let err = run_model(&ini).err().unwrap();
+    assert!(err.contains("split table has ds_2 flow exceeding the routed flow"), "{}", err);
+}
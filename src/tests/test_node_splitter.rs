@@ -0,0 +1,113 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+// 100 ML/d reaches a splitter that sends part of it down a bypass channel
+const BYPASS: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = 100
+ds_1 = split
+
+[node.split]
+type = splitter
+loc = 0, 10
+ds_2_flow = 0.3 * this.usflow
+ds_1 = main
+ds_2 = bypass
+
+[node.main]
+type = gauge
+loc = 0, 20
+
+[node.bypass]
+type = gauge
+loc = 10, 20
+
+[outputs]
+node.split.ds_1
+node.split.ds_2
+";
+
+// A dam supplies an irrigator below a splitter whose bypass is filled first
+const PRIORITY_BYPASS: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 1000
+dimensions = 0,    0,    0, 0,
+             10,   1000, 1, 0,
+             10.1, 1001, 1, 1e8
+ds_1 = split
+
+[node.split]
+type = splitter
+loc = 0, 10
+ds_2_target = 40
+ds_1 = irrigator
+ds_2 = bypass
+
+[node.irrigator]
+type = regulated_user
+loc = 0, 20
+order = 10
+
+[node.bypass]
+type = gauge
+loc = 10, 20
+
+[outputs]
+node.split.usflow
+node.split.ds_2
+node.irrigator.diversion
+";
+
+#[test]
+fn test_splitter_expression_and_capacity() {
+    let m = run(BYPASS).unwrap();
+    assert_eq!(series(&m, "node.split.ds_2"), vec![30.0; 3]);
+    assert_eq!(series(&m, "node.split.ds_1"), vec![70.0; 3]);
+
+    // The bypass channel only takes 20 ML/d
+    let m = run(&BYPASS.replace("ds_1 = main", "ds_2_max = 20\nds_1 = main")).unwrap();
+    assert_eq!(series(&m, "node.split.ds_2"), vec![20.0; 3]);
+
+    // A minimum above the inflow takes all of it
+    let m = run(&BYPASS.replace("ds_1 = main", "ds_2_min = 150\nds_1 = main")).unwrap();
+    assert_eq!(series(&m, "node.split.ds_2"), vec![100.0; 3]);
+    assert_eq!(series(&m, "node.split.ds_1"), vec![0.0; 3]);
+}
+
+#[test]
+fn test_splitter_target_is_filled_first() {
+    let m = run(&BYPASS.replace("ds_2_flow = 0.3 * this.usflow", "ds_2_target = 40")).unwrap();
+    assert_eq!(series(&m, "node.split.ds_2"), vec![40.0; 3]);
+    assert_eq!(series(&m, "node.split.ds_1"), vec![60.0; 3]);
+
+    // Orders below the splitter are passed on with the target added
+    let m = run(PRIORITY_BYPASS).unwrap();
+    assert_eq!(series(&m, "node.split.usflow"), vec![50.0; 3]);
+    assert_eq!(series(&m, "node.split.ds_2"), vec![40.0; 3]);
+    assert_eq!(series(&m, "node.irrigator.diversion"), vec![10.0; 3]);
+}
+
+#[test]
+fn test_splitter_round_trip_and_checks() {
+    let ini_io = IniModelIO::new();
+    let ini = BYPASS.replace("ds_1 = main", "ds_2_max = 20\nds_1 = main");
+    let text = ini_io.model_to_string(&ini_io.read_model_string(&ini).unwrap());
+    assert!(text.contains("ds_2_max = 20"));
+    assert_eq!(ini_io.model_to_string(&ini_io.read_model_string(&text).unwrap()), text);
+
+    // The table cannot be given with an expression
+    let err = run(&BYPASS.replace("ds_1 = main", "table = 0, 0, 100, 50\nds_1 = main")).err().unwrap();
+    assert!(err.contains("Only one of"));
+}
@@ -1,3 +1,4 @@
+use crate::io::ini_model_io::IniModelIO;
 use crate::model::Model;
 use crate::nodes::inflow_node::InflowNode;
 use crate::nodes::{Node, NodeEnum};
@@ -19,3 +20,73 @@ fn test_create_storage_node() {
     println!("Name = {}", n.get_name());
 }
 
+
+
+// A reservoir surveyed twice, silting from 1000 ML to 800 ML capacity over ten days
+const SILTING_STORAGE: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-21
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 500
+dimensions_2000-01-11 = 0,    0,    0, 0,
+                        10,   800,  1, 0,
+                        10.1, 801,  1, 1e8
+dimensions_2000-01-01 = 0,    0,    0, 0,
+                        10,   1000, 1, 0,
+                        10.1, 1001, 1, 1e8
+
+[outputs]
+node.dam.capacity
+node.dam.level
+";
+
+#[test]
+fn test_storage_dated_dimensions() {
+    let mut m: Model = IniModelIO::new().read_model_string(SILTING_STORAGE).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    let series = |name: &str| m.data_cache.series[m.data_cache.get_existing_series_idx(name).unwrap()].values.clone();
+
+    // Interpolated linearly by date between the surveys, then held after the last
+    let capacity = series("node.dam.capacity");
+    assert!((capacity[0] - 1000.0).abs() < 1e-9);
+    assert!((capacity[5] - 900.0).abs() < 1e-9);
+    assert!((capacity[10] - 800.0).abs() < 1e-9);
+    assert!((capacity[20] - 800.0).abs() < 1e-9);
+
+    // The same 500 ML stands higher as the storage silts up
+    let level = series("node.dam.level");
+    assert!((level[0] - 5.0).abs() < 1e-9);
+    assert!((level[20] - 6.25).abs() < 1e-9);
+}
+
+#[test]
+fn test_storage_dated_dimensions_round_trip() {
+    let io = IniModelIO::new();
+    let m: Model = io.read_model_string(SILTING_STORAGE).unwrap();
+    let text = io.model_to_string(&m);
+    assert!(text.contains("dimensions_2000-01-01"));
+    assert!(text.contains("dimensions_2000-01-11"));
+    assert!(!text.contains("dimensions ="));
+    let m2: Model = io.read_model_string(&text).unwrap();
+    assert_eq!(io.model_to_string(&m2), text);
+}
+
+#[test]
+fn test_storage_dated_dimensions_errors() {
+    // Plain and dated dimensions can't be mixed
+    let both = SILTING_STORAGE.replace("initial_volume = 500",
+        "initial_volume = 500\ndimensions = 0, 0, 0, 0, 10, 1000, 1, 0");
+    let err = IniModelIO::new().read_model_string(&both).err().unwrap();
+    assert!(err.contains("both 'dimensions' and dated dimensions"), "{}", err);
+
+    // Surveys are interpolated row by row, so they must share their levels
+    let shifted = SILTING_STORAGE.replace("10,   800,  1, 0,", "9,    800,  1, 0,");
+    let mut m: Model = IniModelIO::new().read_model_string(&shifted).unwrap();
+    let err = m.configure().err().unwrap();
+    assert!(err.contains("same levels"), "{}", err);
+}
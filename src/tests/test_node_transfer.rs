@@ -0,0 +1,82 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::nodes::Node;
+use crate::tests::test_helpers::series;
+
+// A pipeline takes up to 30 ML/d from valley A and delivers it, less 10%, to a dam in
+// valley B until the dam holds 200 ML
+const TWO_VALLEYS: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-20
+
+[node.a_river]
+type = inflow
+loc = 0, 0
+inflow = 100
+ds_1 = pipeline
+
+[node.pipeline]
+type = transfer
+loc = 0, 10
+capacity = 30
+loss_fraction = 0.1
+trigger = if(node.b_dam.volume[-1, 0] < 200, 1, 0)
+ds_1 = a_outlet
+ds_2 = b_dam
+
+[node.a_outlet]
+type = gauge
+loc = 0, 20
+
+[node.b_dam]
+type = storage
+loc = 50, 20
+dimensions = 0,    0,    0, 0,
+             10,   1000, 1, 0,
+             10.1, 1001, 1, 1e8
+
+[outputs]
+node.pipeline.transfer_volume
+node.pipeline.transfer_loss
+node.pipeline.ds_1
+node.pipeline.ds_2
+node.a_outlet.dsflow
+node.b_dam.volume
+";
+
+#[test]
+fn test_transfer_runs_until_triggered_off() {
+    let mut m = IniModelIO::new().read_model_string(TWO_VALLEYS).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+
+    // 27 ML/d arrives at the dam, so it passes 200 ML on day 8 and the transfer stops
+    let transfer = series(&m, "node.pipeline.transfer_volume");
+    assert_eq!(transfer[..9], [30.0, 30.0, 30.0, 30.0, 30.0, 30.0, 30.0, 30.0, 0.0]);
+    assert!(transfer[9..].iter().all(|&v| v == 0.0));
+    let loss = series(&m, "node.pipeline.transfer_loss");
+    assert!((loss[0] - 3.0).abs() < 1e-12);
+    let outlet = series(&m, "node.a_outlet.dsflow");
+    assert_eq!((outlet[0], outlet[19]), (70.0, 100.0));
+    let volume = series(&m, "node.b_dam.volume");
+    assert!((volume[19] - 8.0 * 27.0).abs() < 1e-9, "volume: {}", volume[19]);
+
+    // The water lost in the pipeline is the only mass balance term
+    let pipeline = m.get_node_idx("pipeline").unwrap();
+    assert!((m.nodes[pipeline].get_mass_balance() + 8.0 * 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_transfer_round_trip_and_checks() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_string(TWO_VALLEYS).unwrap();
+    let saved = ini_io.model_to_string(&ini_io.read_model_string(&ini_io.model_to_string(&m)).unwrap());
+    assert!(saved.contains("type = transfer"), "got:\n{}", saved);
+    assert!(saved.contains("ds_2 = b_dam"), "got:\n{}", saved);
+    assert!(saved.contains("loss_fraction = 0.1"), "got:\n{}", saved);
+
+    let bad_loss = TWO_VALLEYS.replace("loss_fraction = 0.1", "loss_fraction = 1.5");
+    let mut m = ini_io.read_model_string(&bad_loss).unwrap();
+    let err = m.configure().and_then(|_| m.run()).err().unwrap();
+    assert!(err.contains("loss_fraction"), "got: {}", err);
+}
@@ -0,0 +1,55 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+// A pump that ceases below 50 ML/d and only commences again once the flow reaches 80 ML/d
+const PUMP_THRESHOLDS: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-07
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = if(sim.day <= 2, 100, if(sim.day <= 4, 30, if(sim.day <= 6, 60, 100)))
+ds_1 = pump
+
+[node.pump]
+type = unregulated_user
+loc = 0, 10
+demand = 10
+flow_threshold = 50
+commence_threshold = 80
+
+[outputs]
+node.pump.diversion
+";
+
+#[test]
+fn test_commence_and_cease_to_pump() {
+    let m = run(PUMP_THRESHOLDS).unwrap();
+    assert_eq!(series(&m, "node.pump.diversion"), vec![10.0, 10.0, 0.0, 0.0, 0.0, 0.0, 10.0]);
+
+    // Without a commence threshold, pumping restarts as soon as the flow is back over 50 ML/d
+    let m = run(&PUMP_THRESHOLDS.replace("commence_threshold = 80\n", "")).unwrap();
+    assert_eq!(series(&m, "node.pump.diversion"), vec![10.0, 10.0, 0.0, 0.0, 10.0, 10.0, 10.0]);
+}
+
+#[test]
+fn test_commence_threshold_round_trip() {
+    let ini_io = IniModelIO::new();
+    let text = ini_io.model_to_string(&ini_io.read_model_string(PUMP_THRESHOLDS).unwrap());
+    assert!(text.contains("commence_threshold = 80"));
+    assert_eq!(ini_io.model_to_string(&ini_io.read_model_string(&text).unwrap()), text);
+}
+
+#[test]
+fn test_annual_cap_resets_at_the_start_of_the_water_year() {
+    let ini = PUMP_THRESHOLDS
+        .replace("start = 2000-01-01\nend = 2000-01-07", "start = 2000-06-28\nend = 2000-07-03")
+        .replace("flow_threshold = 50\ncommence_threshold = 80", "annual_cap = 25, 7");
+    let m = run(&ini).unwrap();
+    assert_eq!(series(&m, "node.pump.diversion"), vec![10.0, 10.0, 5.0, 10.0, 10.0, 5.0]);
+
+    let err = run(&ini.replace("annual_cap = 25, 7", "annual_cap = 25, 13")).err().unwrap();
+    assert!(err.contains("Invalid 'annual_cap' reset month. Invalid water year start month 13"), "{}", err);
+}
@@ -0,0 +1,78 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+// A full dam spills its 100 ML/d inflow past an irrigator who orders 10 ML/d. Off-allocation
+// is announced while yesterday's flow at the gauge below the irrigator was over 50 ML/d.
+const SPILLING_DAM: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-05
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = 100
+ds_1 = dam
+
+[node.dam]
+type = storage
+loc = 0, 10
+initial_volume = 1000
+dimensions = 0,    0,    0, 0,
+             10,   1000, 1, 0,
+             10.1, 1001, 1, 1e8
+ds_1 = irrigator
+
+[node.irrigator]
+type = regulated_user
+loc = 0, 20
+order = 10
+off_allocation = if(node.gauge.dsflow[-1, 0] > 50, 1, 0)
+off_allocation_volume = 30
+ds_1 = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 30
+
+[outputs]
+node.irrigator.diversion
+node.irrigator.off_allocation_diversion
+node.gauge.dsflow
+";
+
+fn rounded(values: Vec<f64>) -> Vec<f64> {
+    values.iter().map(|v| (v * 1e3).round() / 1e3).collect()
+}
+
+#[test]
+fn test_off_allocation_is_taken_on_top_of_orders() {
+    let m = run(SPILLING_DAM).unwrap();
+
+    // Ordered and off-allocation extraction are recorded separately. There is no flow at
+    // the gauge before the first day, so off-allocation starts on the second.
+    assert_eq!(rounded(series(&m, "node.irrigator.diversion")), vec![10.0; 5]);
+    assert_eq!(rounded(series(&m, "node.irrigator.off_allocation_diversion")), vec![0.0, 30.0, 30.0, 30.0, 30.0]);
+    assert_eq!(rounded(series(&m, "node.gauge.dsflow")), vec![90.0, 60.0, 60.0, 60.0, 60.0]);
+
+    // The pump limits the total extraction
+    let m = run(&SPILLING_DAM.replace("order = 10", "order = 10\npump = 25")).unwrap();
+    assert_eq!(rounded(series(&m, "node.irrigator.off_allocation_diversion"))[1], 15.0);
+
+    // Nothing is taken once the threshold is above the flow
+    let m = run(&SPILLING_DAM.replace("> 50", "> 95")).unwrap();
+    assert!(series(&m, "node.irrigator.off_allocation_diversion").iter().all(|&v| v == 0.0));
+}
+
+#[test]
+fn test_off_allocation_round_trip_and_checks() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_string(SPILLING_DAM).unwrap();
+    let saved = ini_io.model_to_string(&ini_io.read_model_string(&ini_io.model_to_string(&m)).unwrap());
+    assert!(saved.contains("off_allocation_volume = 30"), "got:\n{}", saved);
+    assert!(saved.contains("off_allocation = if("), "got:\n{}", saved);
+
+    let no_volume = SPILLING_DAM.replace("off_allocation_volume = 30\n", "");
+    let err = run(&no_volume).err().unwrap();
+    assert!(err.contains("off_allocation_volume"), "got: {}", err);
+}
@@ -0,0 +1,53 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::output_statistics::{statistics_csv, OutputStatistics};
+use crate::timeseries::Timeseries;
+
+#[test]
+fn test_statistics_from_series() {
+    let mut ts = Timeseries::new_daily();
+    ts.name = "flow".to_string();
+    for v in [0.0, 2.0, f64::NAN, 0.0, 8.0] {
+        ts.push_value(v);
+    }
+    let stats = OutputStatistics::from_series(&ts);
+    assert_eq!(stats.mean, 2.5);
+    assert_eq!(stats.min, 0.0);
+    assert_eq!(stats.max, 8.0);
+    assert_eq!(stats.annual_mean, 2.5 * 365.25);
+    assert_eq!(stats.zero_percent, 50.0);
+    assert_eq!(stats.nan_count, 1);
+
+    let mut empty = Timeseries::new_daily();
+    empty.name = "a,b".to_string();
+    empty.push_value(f64::NAN);
+    let csv = statistics_csv(&[OutputStatistics::from_series(&empty)]);
+    assert_eq!(csv, "series,mean,min,max,annual_mean,zero_%,nan_count\n\"a,b\",,,,,,1\n");
+}
+
+#[test]
+fn test_model_output_statistics() {
+    let ini = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-10
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = max(0, sim.day - 5)
+
+[outputs]
+node.river.dsflow
+node.missing.dsflow
+";
+    let mut model = IniModelIO::new().read_model_string(ini).unwrap();
+    model.configure().unwrap();
+    model.run().unwrap();
+    let stats = model.output_statistics();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].name, "node.river.dsflow");
+    assert_eq!(stats[0].min, 0.0);
+    assert_eq!(stats[0].max, 5.0);
+    assert_eq!(stats[0].zero_percent, 50.0);
+    assert_eq!(stats[0].nan_count, 0);
+}
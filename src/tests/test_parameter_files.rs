@@ -0,0 +1,90 @@
+use crate::tests::test_helpers::TestDir;
+use crate::io::ini_model_io::IniModelIO;
+use crate::io::parameter_file_io::{parameters_to_csv, parse_parameter_csv, parse_parameter_ini, read_parameter_file};
+use crate::model::Model;
+
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-03-31
+
+[constants]
+c.rain_scale = 1.0
+
+[node.catchment]
+type = gr4j
+loc = 0, 0
+rain = 5 * c.rain_scale
+evap = 2
+area = 100
+params = 350, 0, 90, 1.7
+
+[outputs]
+node.catchment.dsflow
+";
+
+fn run(m: &mut Model) -> Vec<f64> {
+    m.configure().unwrap();
+    m.run().unwrap();
+    m.data_cache.series[m.data_cache.get_existing_series_idx("node.catchment.dsflow").unwrap()].values.clone()
+}
+
+#[test]
+fn test_parse_parameter_files() {
+    let csv = parse_parameter_csv("parameter,value\nnode.catchment.x1,200\n\nc.rain_scale, 2\n").unwrap();
+    assert_eq!(csv.len(), 2);
+    assert_eq!((csv[0].target.as_str(), csv[0].value, csv[0].line_number), ("node.catchment.x1", 200.0, 2));
+    assert_eq!((csv[1].target.as_str(), csv[1].value, csv[1].line_number), ("c.rain_scale", 2.0, 4));
+
+    // The header is optional
+    assert_eq!(parse_parameter_csv("node.catchment.x1,200\n").unwrap().len(), 1);
+
+    let ini = parse_parameter_ini("; Best set\n[parameters]\nnode.catchment.x1 = 200\nc.rain_scale = 2\n").unwrap();
+    assert_eq!(ini.iter().map(|p| p.value).collect::<Vec<_>>(), vec![200.0, 2.0]);
+
+    // Written values are read back exactly
+    let values = vec![("node.catchment.x1".to_string(), 1.0 / 3.0)];
+    assert_eq!(parse_parameter_csv(&parameters_to_csv(&values)).unwrap()[0].value, 1.0 / 3.0);
+
+    let err = parse_parameter_csv("parameter,value\nnode.catchment.x1,big\n").err().unwrap();
+    assert!(err.contains("line 2: Invalid value 'big'"), "{}", err);
+    let err = parse_parameter_ini("[node.catchment]\nx1 = 200\n").err().unwrap();
+    assert!(err.contains("Parameters go in a [parameters] section"), "{}", err);
+}
+
+#[test]
+fn test_apply_parameters() {
+    let io = IniModelIO::new();
+    let values = parse_parameter_csv("node.catchment.x1,200\nc.rain_scale,2\n").unwrap();
+
+    let mut m: Model = io.read_model_string(MODEL).unwrap();
+    m.apply_parameters(&values).unwrap();
+    assert_eq!(m.get_parameter("node.catchment.x1").unwrap(), 200.0);
+    assert_eq!(m.get_parameter("c.rain_scale").unwrap(), 2.0);
+    let applied = run(&mut m);
+
+    // The same as editing the model file
+    let edited = MODEL.replace("c.rain_scale = 1.0", "c.rain_scale = 2.0")
+        .replace("params = 350, 0, 90, 1.7", "params = 200, 0, 90, 1.7");
+    let mut m2: Model = io.read_model_string(&edited).unwrap();
+    assert_eq!(applied, run(&mut m2));
+    let mut m3: Model = io.read_model_string(MODEL).unwrap();
+    assert_ne!(applied, run(&mut m3));
+
+    // Unknown parameters are errors
+    let mut m: Model = io.read_model_string(MODEL).unwrap();
+    let typo = parse_parameter_csv("node.catchment.x1,200\nc.rain_scael,2\n").unwrap();
+    let err = m.apply_parameters(&typo).err().unwrap();
+    assert!(err.starts_with("Error on line 2:"), "{}", err);
+    let err = m.apply_parameters(&parse_parameter_csv("node.nowhere.x1,200\n").unwrap()).err().unwrap();
+    assert!(err.contains("Node not found: nowhere"), "{}", err);
+}
+
+#[test]
+fn test_read_parameter_file() {
+    let dir = TestDir::new("kalix_params");
+    let csv_path = dir.write("best.csv", "parameter,value\nnode.catchment.x1,200\n");
+    let ini_path = dir.write("best.ini", "[parameters]\nnode.catchment.x1 = 250\n");
+    assert_eq!(read_parameter_file(csv_path.to_str().unwrap()).unwrap()[0].value, 200.0);
+    assert_eq!(read_parameter_file(ini_path.to_str().unwrap()).unwrap()[0].value, 250.0);
+}
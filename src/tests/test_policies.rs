@@ -0,0 +1,104 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series_rounded};
+
+// A dam with no inflow is drawn down 100 ML/d by an irrigator, while a town takes 10 ML/d from
+// a creek nearby. Restrictions tighten in two stages as the dam falls.
+const DRAWDOWN: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-10
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 1000
+dimensions = 0,  0,     0, 0,
+             10, 10000, 0, 0
+ds_1 = irrigator
+
+[node.irrigator]
+type = regulated_user
+loc = 0, 10
+order = 100
+
+[node.creek]
+type = inflow
+loc = 10, 0
+inflow = 10
+ds_1 = town
+
+[node.town]
+type = unregulated_user
+loc = 10, 10
+demand = 10
+
+[policies]
+stage.1 = node.dam.volume < 800
+stage.1.demand_factor = 0.5
+stage.1.demand_factor.town = 0.2
+stage.2 = node.dam.volume < 500
+stage.2.release_cap.dam = 20
+
+[outputs]
+policy.stage
+node.dam.volume
+node.irrigator.diversion
+node.town.diversion
+";
+
+#[test]
+fn test_policy_stages_restrict_demands_and_releases() {
+    let m = run(DRAWDOWN).unwrap();
+
+    // Each stage is triggered by the previous day's volume, and only its own actions apply
+    assert_eq!(series_rounded(&m, "policy.stage"), vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0, 2.0]);
+    assert_eq!(series_rounded(&m, "node.irrigator.diversion"), vec![100.0, 100.0, 100.0, 50.0, 50.0, 50.0, 50.0, 50.0, 20.0, 20.0]);
+    assert_eq!(series_rounded(&m, "node.dam.volume"), vec![900.0, 800.0, 700.0, 650.0, 600.0, 550.0, 500.0, 450.0, 430.0, 410.0]);
+    assert_eq!(series_rounded(&m, "node.town.diversion"), vec![10.0, 10.0, 10.0, 2.0, 2.0, 2.0, 2.0, 2.0, 10.0, 10.0]);
+
+    // Without the policies the dam is drawn down unrestricted
+    let unrestricted = DRAWDOWN.split("[policies]").next().unwrap().to_string()
+        + "[outputs]\nnode.irrigator.diversion\n";
+    let m = run(&unrestricted).unwrap();
+    assert_eq!(series_rounded(&m, "node.irrigator.diversion"), vec![100.0; 10]);
+}
+
+#[test]
+fn test_policy_stage_in_expressions() {
+    // The stage can be read by other expressions, and action values are expressions too
+    let ini = DRAWDOWN
+        .replace("demand_factor.town = 0.2", "demand_factor.town = 1 - 0.1 * policy.stage[-1, 0]")
+        .replace("demand = 10", "demand = if(policy.stage > 0, 5, 10)");
+    let m = run(&ini).unwrap();
+    assert_eq!(series_rounded(&m, "node.town.diversion"), vec![10.0, 10.0, 10.0, 5.0, 4.5, 4.5, 4.5, 4.5, 5.0, 5.0]);
+}
+
+#[test]
+fn test_policies_round_trip() {
+    let ini_io = IniModelIO::new();
+    let m = ini_io.read_model_string(DRAWDOWN).unwrap();
+    let saved = ini_io.model_to_string(&ini_io.read_model_string(&ini_io.model_to_string(&m)).unwrap());
+    assert!(saved.contains("stage.2 = node.dam.volume < 500"), "got:\n{}", saved);
+    assert!(saved.contains("stage.1.demand_factor.town = 0.2"), "got:\n{}", saved);
+    assert!(saved.contains("stage.2.release_cap.dam = 20"), "got:\n{}", saved);
+}
+
+#[test]
+fn test_policy_errors() {
+    let err = |ini: String| run(&ini).err().unwrap();
+
+    let e = err(DRAWDOWN.replace("stage.2", "stage.3"));
+    assert!(e.contains("stage 2 is missing"), "{}", e);
+    let e = err(DRAWDOWN.replace("stage.1 = node.dam.volume < 800\n", ""));
+    assert!(e.contains("Policy stage 1 has no trigger"), "{}", e);
+    let e = err(DRAWDOWN.replace("release_cap.dam", "release_cap.town"));
+    assert!(e.contains("sets 'release_cap' on node 'town', which is not a storage"), "{}", e);
+    let e = err(DRAWDOWN.replace("demand_factor.town", "demand_factor.village"));
+    assert!(e.contains("refers to node 'village', which is not defined"), "{}", e);
+    let e = err(DRAWDOWN.replace("stage.2.release_cap.dam", "stage.2.release_limit.dam"));
+    assert!(e.contains("Unknown policy action 'release_limit'"), "{}", e);
+    let e = err(DRAWDOWN.replace("stage.2.release_cap.dam", "stage.2.release_cap"));
+    assert!(e.contains("needs a storage node"), "{}", e);
+    let e = err(DRAWDOWN.replace("stage.2 =", "level.2 ="));
+    assert!(e.contains("Invalid policy property 'level.2'"), "{}", e);
+}
@@ -0,0 +1,50 @@
+use crate::io::csv_io::read_ts;
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::provenance::{sha256_hex, Provenance};
+use crate::tests::test_helpers::TestDir;
+
+const MODEL: &str = "./src/tests/example_models/8/picnic_sacr_working.ini";
+
+#[test]
+fn test_sha256_hex() {
+    assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+}
+
+#[test]
+fn test_fingerprint() {
+    let mut model = IniModelIO::new().read_model_file(MODEL).unwrap();
+    let fingerprint = model.fingerprint().unwrap();
+    assert_eq!(fingerprint.len(), 64);
+
+    // The time and command line are not part of the fingerprint, but seeds and parameters are
+    let provenance = Provenance::new(&model).unwrap()
+        .with_model_file(MODEL).unwrap()
+        .with_command_line("kalix run model.ini");
+    assert_eq!(provenance.fingerprint(), fingerprint);
+    assert_eq!(provenance.input_files.len(), 3);
+    assert_ne!(provenance.clone().with_seed("sce", 42).fingerprint(), fingerprint);
+
+    model.set_parameter("node.my_sac.lzfpm", 50.0).unwrap();
+    assert_ne!(model.fingerprint().unwrap(), fingerprint);
+}
+
+#[test]
+fn test_outputs_with_provenance() {
+    let mut model = IniModelIO::new().read_model_file(MODEL).unwrap();
+    model.configure().unwrap();
+    model.run().unwrap();
+    let provenance = Provenance::new(&model).unwrap().with_seed("de", 7);
+
+    let dir = TestDir::new("kalix_test_outputs_with_provenance");
+    let path = dir.join("outputs.csv");
+    let path = path.to_str().unwrap();
+    model.write_outputs_with_provenance(path, &provenance).unwrap();
+    let text = std::fs::read_to_string(path).unwrap();
+    assert!(text.starts_with(&format!("# fingerprint: {}\r\n", provenance.fingerprint())));
+    assert!(text.contains("# seed: de 7\r\n"));
+
+    // The header is skipped when the outputs are read back
+    let series = read_ts(path).unwrap();
+    assert_eq!(series[0].name, "node.my_sac.ds_1");
+    assert_eq!(series[0].len(), model.configuration.sim_nsteps as usize);
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+use crate::data_management::data_cache::{DataCache, Recording};
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::numerical::series_comparison::{check_relative_tolerance, SINGLE_PRECISION_TOLERANCE};
+use crate::tid::utils::wrap_to_u64;
+
+// The pump's demand reads the inflow from a week ago, a 3-day average of it, and a function
+// of it from the day before. Only the pump's diversion is an output.
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-03-31
+
+[functions]
+f.wet = if(node.a.dsflow > 200, 1, 0)
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = 10 * sim.day
+ds_1 = pump
+
+[node.pump]
+type = unregulated_user
+loc = 0, 10
+demand = 0.1 * node.a.dsflow[-7, 0] + mavg_last(node.a.usflow, 3) + f.wet[-1, 0]
+
+[outputs]
+node.pump.diversion
+";
+
+fn run(model: &mut Model) -> Vec<f64> {
+    model.configure().unwrap();
+    model.run().unwrap();
+    let idx = model.data_cache.get_existing_series_idx("node.pump.diversion").unwrap();
+    model.data_cache.series[idx].values.clone()
+}
+
+#[test]
+fn test_recent_values_hold_only_the_lookback() {
+    let mut cache = DataCache::new();
+    let idx = cache.get_or_add_new_series("node.a.dsflow", false);
+    cache.set_start_and_stepsize(wrap_to_u64(946684800), 86400);
+    cache.set_recording(idx, Recording::Recent(2));
+    for step in 0..10 {
+        cache.set_current_step(step);
+        cache.add_value_at_index(idx, step as f64);
+    }
+    assert_eq!(cache.series_len(idx), 10);
+    assert_eq!((-2..=0).map(|offset| cache.get_value_with_offset(idx, offset)).collect::<Vec<_>>(), vec![7.0, 8.0, 9.0]);
+    let values = cache.get_series(idx).values.clone();
+    assert!(values[..7].iter().all(|v| v.is_nan()));
+    assert_eq!(&values[7..], &[7.0, 8.0, 9.0]);
+    assert!(cache.series[idx].values.len() < 10);
+
+    // Overwriting the current step, then starting again from the beginning
+    cache.add_value_at_index(idx, -9.0);
+    assert_eq!(cache.get_current_value(idx), -9.0);
+    cache.set_recording(idx, Recording::Recent(2));
+    cache.set_current_step(0);
+    assert!(cache.get_value_with_offset(idx, 0).is_nan());
+    cache.add_value_at_index(idx, 100.0);
+    assert_eq!(cache.series_len(idx), 1);
+    assert_eq!(cache.get_current_value(idx), 100.0);
+}
+
+#[test]
+fn test_results_are_only_recorded_as_far_back_as_they_are_read() {
+    let mut model = IniModelIO::new().read_model_string(MODEL).unwrap();
+    let diversion = run(&mut model);
+
+    let recording = |name: &str| {
+        let idx = model.data_cache.get_existing_series_idx(name).unwrap();
+        model.data_cache.recording(idx)
+    };
+    assert_eq!(recording("node.pump.diversion"), Recording::Full);
+    assert_eq!(recording("node.a.dsflow"), Recording::Recent(7));
+    assert_eq!(recording("node.a.usflow"), Recording::Recent(3));
+    assert_eq!(recording("f.wet"), Recording::Recent(1));
+
+    // Recording everything gives the same results
+    let mut model_in_full = IniModelIO::new().read_model_string(MODEL).unwrap();
+    model_in_full.record_series_in_full(&["node.a.dsflow".to_string(), "node.a.usflow".to_string(), "f.wet".to_string()]);
+    assert_eq!(run(&mut model_in_full), diversion);
+    let idx = model_in_full.data_cache.get_existing_series_idx("node.a.dsflow").unwrap();
+    assert_eq!(model_in_full.data_cache.recording(idx), Recording::Full);
+    assert_eq!(model_in_full.data_cache.series[idx].values.len(), 91);
+
+    // A series that is only held in part reads as missing before the values it holds
+    let idx = model.data_cache.get_existing_series_idx("node.a.dsflow").unwrap();
+    let partial = model.data_cache.get_series(idx);
+    assert_eq!(partial.values.len(), 91);
+    assert!(partial.values[..83].iter().all(|v| v.is_nan()));
+    assert_eq!(&partial.values[83..], &model_in_full.data_cache.series[idx].values[83..]);
+}
+
+#[test]
+fn test_single_precision_results_are_within_tolerance() {
+    let mut double = IniModelIO::new().read_model_string(MODEL).unwrap();
+    let expected = run(&mut double);
+
+    let ini = MODEL.replace("end = 2000-03-31", "end = 2000-03-31\nsingle_precision = true");
+    let mut single = IniModelIO::new().read_model_string(&ini).unwrap();
+    run(&mut single);
+    let idx = single.data_cache.get_existing_series_idx("node.pump.diversion").unwrap();
+    assert_eq!(single.data_cache.recording(idx), Recording::Single);
+    // Only the value being written is held in double precision
+    assert_eq!(single.data_cache.series[idx].values.len(), 2);
+
+    let outputs = single.collect_output_series();
+    check_relative_tolerance(&expected, &outputs[0].values, SINGLE_PRECISION_TOLERANCE).unwrap();
+    assert!(IniModelIO::new().model_to_string(&single).contains("single_precision = true"));
+}
+
+#[test]
+fn test_single_precision_outputs_read_back_in_double_precision() {
+    // The inflow is an output too, and the pump's demand reads it
+    let ini = MODEL.replace("inflow = 10 * sim.day", "inflow = 10.1 * sim.day")
+        .replace("[outputs]\n", "[outputs]\nnode.a.dsflow\n");
+    let mut double = IniModelIO::new().read_model_string(&ini).unwrap();
+    let expected = run(&mut double);
+
+    let ini = ini.replace("end = 2000-03-31", "end = 2000-03-31\nsingle_precision = true");
+    let mut single = IniModelIO::new().read_model_string(&ini).unwrap();
+    run(&mut single);
+    let idx = |name: &str| single.data_cache.get_existing_series_idx(name).unwrap();
+    assert_eq!(single.data_cache.recording(idx("node.a.dsflow")), Recording::Full);
+    assert_eq!(single.data_cache.recording(idx("node.pump.diversion")), Recording::Single);
+
+    // The demand reads the inflow as it was calculated, so the diversion only differs by its
+    // own rounding
+    let diversion = single.data_cache.get_series(idx("node.pump.diversion")).values.clone();
+    assert_eq!(diversion, expected.iter().map(|&v| v as f32 as f64).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_shared_values_are_copied_only_when_written() {
+    let values = Arc::new(vec![1.0, 2.0, 3.0]);
+    let mut cache = DataCache::new();
+    let idx = cache.get_or_add_new_series("data.x", false);
+    cache.set_start_and_stepsize(wrap_to_u64(946684800), 86400);
+    cache.set_shared_values(idx, Arc::clone(&values), 0, 86400);
+    assert_eq!(cache.recording(idx), Recording::Full);
+    assert_eq!(cache.series_len(idx), 3);
+
+    // A clone shares the values until it writes to them (they are copied when the written
+    // step is archived, as the step moves on)
+    let mut copy = cache.clone();
+    assert_eq!(Arc::strong_count(&values), 3);
+    copy.set_current_step(1);
+    assert_eq!(copy.get_current_value(idx), 2.0);
+    copy.add_value_at_index(idx, 5.0);
+    assert_eq!(copy.get_series(idx).values, vec![1.0, 5.0, 3.0]);
+    copy.increment_current_step();
+    assert_eq!(Arc::strong_count(&values), 2);
+    assert_eq!(copy.get_value_with_offset(idx, -1), 5.0);
+    assert_eq!(copy.get_series(idx).values, vec![1.0, 5.0, 3.0]);
+    assert_eq!(cache.get_series(idx).values, vec![1.0, 2.0, 3.0]);
+}
+
+#[test]
+fn test_model_clones_share_input_data() {
+    let ini = "[kalix]\nstart = 2000-01-01\nend = 2000-01-03\n\n\
+[node.a]\ntype = inflow\nloc = 0, 0\ninflow = data.q_csv.by_name.q\n\n[outputs]\nnode.a.dsflow\n";
+    let mut m = IniModelIO::new().read_model_string(ini).unwrap();
+    m.load_input_csv_str("q.csv", "Date,q\n2000-01-01,1\n2000-01-02,2\n2000-01-03,3\n", None).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+
+    // The clone shares the input data, and runs the same
+    let mut copy = m.clone();
+    assert!(Arc::ptr_eq(&m.inputs[0].timeseries, &copy.inputs[0].timeseries));
+    copy.run().unwrap();
+    let dsflow = |m: &Model| m.data_cache.get_series(m.data_cache.get_existing_series_idx("node.a.dsflow").unwrap()).values.clone();
+    assert_eq!(dsflow(&copy), vec![1.0, 2.0, 3.0]);
+    assert_eq!(dsflow(&m), dsflow(&copy));
+}
+
+#[test]
+fn test_input_data_is_read_ahead_and_back() {
+    let ini = "[kalix]\nstart = 2000-01-01\nend = 2000-01-10\n\n\
+[node.a]\ntype = inflow\nloc = 0, 0\ninflow = data.q_csv.by_name.q[3, 0] + 0.5 * data.q_csv.by_name.q[-2, 0]\n\n\
+[outputs]\nnode.a.dsflow\n";
+    let mut m = IniModelIO::new().read_model_string(ini).unwrap();
+    let csv: String = (1..=10).map(|day| format!("2000-01-{:02},{}\n", day, day)).collect();
+    m.load_input_csv_str("q.csv", &format!("Date,q\n{}", csv), None).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+
+    // Only the values read are held for the input, and they are brought in as the run goes
+    let q = m.data_cache.get_existing_series_idx("data.q_csv.by_name.q").unwrap();
+    assert_eq!(m.data_cache.recording(q), Recording::Full);
+    assert_eq!(m.data_cache.series[q].values.len(), 8);
+    let expected: Vec<f64> = (1..=10)
+        .map(|day| if day + 3 <= 10 { (day + 3) as f64 } else { 0.0 } + if day > 2 { 0.5 * (day - 2) as f64 } else { 0.0 })
+        .collect();
+    let dsflow = m.data_cache.get_existing_series_idx("node.a.dsflow").unwrap();
+    assert_eq!(m.data_cache.get_series(dsflow).values, expected);
+    assert_eq!(m.data_cache.get_series(q).values, (1..=10).map(|day| day as f64).collect::<Vec<_>>());
+}
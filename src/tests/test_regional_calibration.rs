@@ -0,0 +1,147 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::numerical::opt::OptimisationConfig;
+use crate::run::{calibrate_model, calibrate_region};
+use crate::tests::test_helpers::TestDir;
+
+// A catchment whose flow is its rainfall scaled by a regional factor plus a local baseflow
+fn catchment_model(rain_file: &str, stem: &str) -> String {
+    format!("\
+[kalix]
+start = 2000-01-01
+end = 2000-01-10
+
+[inputs]
+{}
+
+[constants]
+c.scale = 1.0
+c.baseflow = 0.0
+
+[node.runoff]
+type = inflow
+loc = 0, 0
+inflow = c.scale * data.{}_csv.by_index.1 + c.baseflow
+
+[outputs]
+node.runoff.ds_1
+", rain_file, stem)
+}
+
+/// Writes the rain, observed flow and model files of a catchment, returning the model and
+/// observed file paths
+fn write_catchment(dir: &TestDir, name: &str, rain: impl Fn(usize) -> f64, baseflow: f64) -> (String, String) {
+    let stem = format!("{}_rain", name);
+    let rows = |f: &dyn Fn(f64) -> f64| (1..=10)
+        .map(|d| format!("2000-01-{:02},{}", d, f(rain(d))))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let rain_path = dir.write(&format!("{}.csv", stem), &format!("Datetime,rain\n{}\n", rows(&|r| r)));
+    let observed_path = dir.write(&format!("{}_flow.csv", name), &format!("Datetime,flow\n{}\n", rows(&|r| 2.0 * r + baseflow)));
+    let model_path = dir.write(&format!("{}.ini", name), &catchment_model(rain_path.to_str().unwrap(), &stem));
+    (model_path.display().to_string(), observed_path.display().to_string())
+}
+
+fn regional_config(upper: &(String, String), lower: &(String, String), parameters: &str) -> String {
+    format!("\
+[optimisation]
+algorithm = SCE
+complexes = 2
+termination_evaluations = 3000
+random_seed = 3
+
+[catchment.upper]
+model_file = {}
+
+[catchment.lower]
+model_file = {}
+
+[term.upper_gauge]
+catchment = upper
+simulated = node.runoff.ds_1
+observed_file = {}
+observed_series = 1
+statistic = MAE
+
+[term.lower_gauge]
+catchment = lower
+simulated = node.runoff.ds_1
+observed_file = {}
+observed_series = 1
+statistic = MAE
+
+[parameters]
+{}", upper.0, lower.0, upper.1, lower.1, parameters)
+}
+
+const PARAMETERS: &str = "\
+c.scale = lin_range(g(1), 0, 5)
+catchment.upper.c.baseflow = lin_range(g(2), 0, 10)
+catchment.lower.c.baseflow = lin_range(g(3), 0, 10)
+";
+
+#[test]
+fn test_regional_calibration_shares_and_varies_parameters() {
+    let dir = TestDir::new("kalix_regional");
+    let upper = write_catchment(&dir, "upper", |d| d as f64, 1.0);
+    let lower = write_catchment(&dir, "lower", |d| (d * d) as f64 / 4.0, 5.0);
+    let config = OptimisationConfig::from_ini(&regional_config(&upper, &lower, PARAMETERS)).unwrap();
+    assert_eq!(config.catchments.len(), 2);
+    assert_eq!(config.terms[1].catchment.as_deref(), Some("lower"));
+
+    let outcome = calibrate_region(&config, None).unwrap();
+    assert!(outcome.best_objective < 1e-2, "{}", outcome.best_objective);
+
+    // One scale for the region, and a baseflow for each catchment
+    let value = |target: &str| outcome.parameters.iter().find(|(t, _)| t == target).unwrap().1;
+    assert!((value("c.scale") - 2.0).abs() < 1e-2);
+    assert!((value("catchment.upper.c.baseflow") - 1.0).abs() < 1e-2);
+    assert!((value("catchment.lower.c.baseflow") - 5.0).abs() < 1e-2);
+
+    // Each catchment's model holds its own calibrated values, in its INI document too
+    let (name, model) = &outcome.models[1];
+    assert_eq!(name, "lower");
+    assert_eq!(model.get_parameter("c.scale").unwrap(), value("c.scale"));
+    assert_eq!(model.get_parameter("c.baseflow").unwrap(), value("catchment.lower.c.baseflow"));
+    let reloaded = IniModelIO::new().read_model_string(&model.get_ini_string().unwrap()).unwrap();
+    assert_eq!(reloaded.get_parameter("c.baseflow").unwrap(), value("catchment.lower.c.baseflow"));
+}
+
+#[test]
+fn test_regional_calibration_errors() {
+    let upper = ("upper.ini".to_string(), "upper_flow.csv".to_string());
+    let lower = ("lower.ini".to_string(), "lower_flow.csv".to_string());
+    let config = regional_config(&upper, &lower, PARAMETERS);
+
+    let err = OptimisationConfig::from_ini(&config.replace("catchment = lower\n", "")).err().unwrap();
+    assert!(err.contains("Missing 'catchment' in [term.lower_gauge]"), "{}", err);
+
+    let err = OptimisationConfig::from_ini(&config.replace("catchment = lower\n", "catchment = lowr\n")).err().unwrap();
+    assert!(err.contains("Unknown catchment 'lowr' in [term.lower_gauge]"), "{}", err);
+
+    let err = OptimisationConfig::from_ini(&config.replace("catchment.lower.c.baseflow", "catchment.middle.c.baseflow")).err().unwrap();
+    assert!(err.contains("is for catchment 'middle', which has no [catchment.middle] section"), "{}", err);
+
+    // Without catchments, terms cannot name one and a single model is calibrated
+    let single = config.replace("[catchment.upper]\nmodel_file = upper.ini\n\n[catchment.lower]\nmodel_file = lower.ini\n\n", "");
+    let err = OptimisationConfig::from_ini(&single).err().unwrap();
+    assert!(err.contains("'catchment' in [term.upper_gauge] needs a matching [catchment.NAME] section"), "{}", err);
+
+    // A regional config is not calibrated as one model
+    let config = OptimisationConfig::from_ini(&config).unwrap();
+    let err = calibrate_model(&mut Model::new(), &config, None).err().unwrap();
+    assert!(err.contains("calibrate_region"), "{}", err);
+}
+
+#[test]
+fn test_regional_parameters_must_exist_in_each_catchment() {
+    let dir = TestDir::new("kalix_regional_missing");
+    let upper = write_catchment(&dir, "upper", |d| d as f64, 1.0);
+    let lower = write_catchment(&dir, "lower", |d| d as f64, 5.0);
+    let model = std::fs::read_to_string(&lower.0).unwrap().replace("c.scale = 1.0\n", "").replace("c.scale *", "2 *");
+    std::fs::write(&lower.0, model).unwrap();
+
+    let config = OptimisationConfig::from_ini(&regional_config(&upper, &lower, PARAMETERS)).unwrap();
+    let err = calibrate_region(&config, None).err().unwrap();
+    assert!(err.starts_with("In catchment 'lower':"), "{}", err);
+}
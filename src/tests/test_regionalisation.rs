@@ -0,0 +1,128 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::regionalisation::{CatchmentTable, TransferMethod};
+use crate::model::Model;
+
+fn gr4j(name: &str, params: &str) -> String {
+    format!("\
+[node.{}]
+type = gr4j
+loc = 0, 0
+rain = 5
+evap = 2
+area = 100
+params = {}
+
+", name, params)
+}
+
+fn model() -> Model {
+    let ini = format!("[kalix]\nstart = 2000-01-01\nend = 2000-01-31\n\n{}{}{}{}{}",
+                      gr4j("wet", "300, 0, 90, 1.5"),
+                      gr4j("dry", "600, 0, 90, 2.5"),
+                      gr4j("middling", "450, 0, 90, 2.0"),
+                      gr4j("ungauged_wet", "100, 0, 90, 1.0"),
+                      gr4j("ungauged_dry", "100, 0, 90, 1.0"));
+    IniModelIO::new().read_model_string(&ini).unwrap()
+}
+
+const ATTRIBUTES: &str = "\
+catchment,mean_rain,forest
+wet,1200,0.8
+dry,500,0.2
+middling,850,0.5
+ungauged_wet,1100,0.7
+ungauged_dry,600,0.3
+";
+
+const DONORS: &str = "\
+catchment,x1,x4
+wet,300,1.5
+dry,600,2.5
+middling,450,2.0
+";
+
+#[test]
+fn test_parse_catchment_table() {
+    let table = CatchmentTable::parse(ATTRIBUTES).unwrap();
+    assert_eq!(table.columns, vec!["mean_rain", "forest"]);
+    assert_eq!(table.row("DRY"), Some([500.0, 0.2].as_slice()));
+    assert_eq!(table.row("nowhere"), None);
+
+    let err = CatchmentTable::parse("catchment,x1\nwet,big\n").err().unwrap();
+    assert!(err.contains("line 2: Invalid value 'big'"), "{}", err);
+    let err = CatchmentTable::parse("catchment,x1,x4\nwet,300\n").err().unwrap();
+    assert!(err.contains("Expected 2 values but found 1"), "{}", err);
+    let err = CatchmentTable::parse("catchment,x1\nwet,300\nWet,200\n").err().unwrap();
+    assert!(err.contains("Catchment 'Wet' is listed twice"), "{}", err);
+    assert_eq!(TransferMethod::from_name("Regression"), Ok(TransferMethod::Regression));
+    assert!(TransferMethod::from_name("kriging").is_err());
+}
+
+#[test]
+fn test_regionalise_nearest_neighbour() {
+    let attributes = CatchmentTable::parse(ATTRIBUTES).unwrap();
+    let donors = CatchmentTable::parse(DONORS).unwrap();
+
+    let mut m = model();
+    let transfers = m.regionalise(&attributes, &donors, TransferMethod::NearestNeighbour, 1).unwrap();
+    assert_eq!(transfers.len(), 2);
+    assert_eq!((transfers[0].node.as_str(), transfers[0].donors.clone()), ("ungauged_wet", vec!["wet".to_string()]));
+    assert_eq!(transfers[1].donors, vec!["dry"]);
+    assert_eq!(m.get_parameter("node.ungauged_wet.x1").unwrap(), 300.0);
+    assert_eq!(m.get_parameter("node.ungauged_dry.x4").unwrap(), 2.5);
+
+    // Parameters not in the donor table, and the donors themselves, are left alone
+    assert_eq!(m.get_parameter("node.ungauged_wet.x3").unwrap(), 90.0);
+    assert_eq!(m.get_parameter("node.middling.x1").unwrap(), 450.0);
+
+    // The assignments are written into the INI document
+    let reloaded = IniModelIO::new().read_model_string(&m.get_ini_string().unwrap()).unwrap();
+    assert_eq!(reloaded.get_parameter("node.ungauged_dry.x1").unwrap(), 600.0);
+
+    // The mean of the two nearest donors
+    let mut m = model();
+    let transfers = m.regionalise(&attributes, &donors, TransferMethod::NearestNeighbour, 2).unwrap();
+    assert_eq!(transfers[0].donors, vec!["wet", "middling"]);
+    assert_eq!(transfers[0].parameters, vec![("x1".to_string(), 375.0), ("x4".to_string(), 1.75)]);
+}
+
+#[test]
+fn test_regionalise_regression() {
+    // x1 is linear in rainfall, so the regression recovers it exactly
+    let attributes = CatchmentTable::parse("catchment,mean_rain\nwet,1200\ndry,500\nmiddling,850\nungauged_wet,1100\nungauged_dry,100\n").unwrap();
+    let donors = CatchmentTable::parse(DONORS).unwrap();
+    let mut m = model();
+    let transfers = m.regionalise(&attributes, &donors, TransferMethod::Regression, 1).unwrap();
+    assert!(transfers[0].donors.is_empty());
+    let x1 = m.get_parameter("node.ungauged_wet.x1").unwrap();
+    assert!((x1 - (600.0 - 300.0 * 600.0 / 700.0)).abs() < 1e-9, "{}", x1);
+
+    // Predictions outside the donors' range are kept within it
+    assert_eq!(m.get_parameter("node.ungauged_dry.x1").unwrap(), 600.0);
+}
+
+#[test]
+fn test_regionalise_errors() {
+    let attributes = CatchmentTable::parse(ATTRIBUTES).unwrap();
+    let donors = CatchmentTable::parse(DONORS).unwrap();
+
+    let missing = CatchmentTable::parse(&ATTRIBUTES.replace("ungauged_dry,600,0.3\n", "")).unwrap();
+    let err = model().regionalise(&missing, &donors, TransferMethod::NearestNeighbour, 1).err().unwrap();
+    assert!(err.contains("Ungauged node 'ungauged_dry' has no row in the attribute table"), "{}", err);
+
+    let missing = CatchmentTable::parse(&ATTRIBUTES.replace("wet,1200,0.8\n", "")).unwrap();
+    let err = model().regionalise(&missing, &donors, TransferMethod::NearestNeighbour, 1).err().unwrap();
+    assert!(err.contains("Donor 'wet' has no row"), "{}", err);
+
+    let err = model().regionalise(&attributes, &donors, TransferMethod::NearestNeighbour, 4).err().unwrap();
+    assert!(err.contains("between 1 and the number of donors (3)"), "{}", err);
+
+    let unknown = CatchmentTable::parse("catchment,uztwm\nwet,50\n").unwrap();
+    let err = model().regionalise(&attributes, &unknown, TransferMethod::NearestNeighbour, 1).err().unwrap();
+    assert!(err.contains("No ungauged node has the parameters uztwm"), "{}", err);
+
+    // Two attributes and an intercept cannot be fitted to two donors
+    let two = CatchmentTable::parse("catchment,x1\nwet,300\ndry,600\n").unwrap();
+    let err = model().regionalise(&attributes, &two, TransferMethod::Regression, 1).err().unwrap();
+    assert!(err.contains("needs at least 3 donors, but there are 2"), "{}", err);
+}
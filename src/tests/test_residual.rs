@@ -0,0 +1,107 @@
+use crate::tests::test_helpers::{series, TestDir};
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::nodes::Node;
+
+// A reach from gauge_a to gauge_b with a modelled tributary between them. The model puts 10 ML/d
+// through gauge_a but 12 ML/d was observed there, and with the tributary's 5 ML/d, 20 ML/d was
+// observed at gauge_b, so 3 ML/d is unattributed. gauge_b has no observation on day 4.
+fn model(dir_name: &str) -> (Model, TestDir) {
+    let dir = TestDir::new(dir_name);
+    let mut flows = String::from("Date,top,trib,obs_a,obs_b\n");
+    for day in 1..=10 {
+        let obs_b = if day == 4 { String::new() } else { "20".to_string() };
+        flows.push_str(&format!("2000-01-{:02},10,5,12,{}\n", day, obs_b));
+    }
+    let flows_path = dir.write("flows.csv", &flows);
+    let ini = format!("\
+[inputs]
+{}
+
+[node.top]
+type = inflow
+loc = 0, 0
+inflow = data.flows_csv.by_name.top
+ds_1 = gauge_a
+
+[node.gauge_a]
+type = gauge
+loc = 0, 10
+reference_flow = data.flows_csv.by_name.obs_a
+ds_1 = junction
+
+[node.trib]
+type = inflow
+loc = 10, 10
+inflow = data.flows_csv.by_name.trib
+ds_1 = junction
+
+[node.junction]
+type = confluence
+loc = 0, 20
+ds_1 = gauge_b
+
+[node.gauge_b]
+type = gauge
+loc = 0, 30
+reference_flow = data.flows_csv.by_name.obs_b
+
+[outputs]
+node.gauge_b.dsflow
+node.gauge_b.residual
+", flows_path.display());
+    (IniModelIO::new().read_model_string(&ini).unwrap(), dir)
+}
+
+#[test]
+fn test_residual_is_estimated_and_injected() {
+    let (mut m, dir) = model("kalix_residual");
+    let estimate = m.estimate_residual("gauge_a", "gauge_b").unwrap();
+    assert_eq!(estimate.series.values, vec![3.0, 3.0, 3.0, 0.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0]);
+    assert_eq!((estimate.n_missing, estimate.series.name.as_str()), (1, "gauge_b"));
+
+    // Injected, the residual is added to the modelled flow at gauge_b (gauge_a is not forced)
+    let csv_path = dir.join("residual.csv").display().to_string();
+    m.apply_residual(&estimate, &csv_path).unwrap();
+    let mut run = m.clone();
+    run.configure().unwrap();
+    run.run().unwrap();
+    assert_eq!(series(&run, "node.gauge_b.residual"), estimate.series.values);
+    assert_eq!(series(&run, "node.gauge_b.dsflow")[0], 18.0);
+
+    // The residual file is listed in [inputs] and read again with the saved model
+    let ini = m.get_ini_string().unwrap();
+    assert!(ini.contains(&csv_path), "{}", ini);
+    assert!(ini.contains("residual = data.residual_csv.by_name.gauge_b"), "{}", ini);
+    let mut reread = IniModelIO::new().read_model_string(&ini).unwrap();
+    reread.configure().unwrap();
+    reread.run().unwrap();
+    assert_eq!(series(&reread, "node.gauge_b.residual"), estimate.series.values);
+
+    // Estimating again with the residual in place gives the same residual
+    let again = m.estimate_residual("gauge_a", "gauge_b").unwrap();
+    assert_eq!(again.series.values, estimate.series.values);
+    m.apply_residual(&again, &csv_path).unwrap();
+    assert_eq!(m.input_file_paths.iter().filter(|p| **p == csv_path).count(), 1);
+}
+
+#[test]
+fn test_residual_loss_takes_at_most_the_flow() {
+    let (m, _dir) = model("kalix_residual_loss");
+    let ini = m.get_ini_string().unwrap()
+        .replace("reference_flow = data.flows_csv.by_name.obs_b", "residual = -40");
+    let mut m = IniModelIO::new().read_model_string(&ini).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    assert_eq!((series(&m, "node.gauge_b.residual")[0], series(&m, "node.gauge_b.dsflow")[0]), (-15.0, 0.0));
+    assert_eq!(m.nodes[m.get_node_idx("gauge_b").unwrap()].get_mass_balance(), -150.0);
+}
+
+#[test]
+fn test_residual_errors() {
+    let (m, _dir) = model("kalix_residual_errors");
+    let err = |us: &str, ds: &str| m.estimate_residual(us, ds).err().unwrap();
+    assert_eq!(err("gauge_b", "gauge_a"), "Gauge 'gauge_a' is not downstream of gauge 'gauge_b'");
+    assert_eq!(err("gauge_a", "junction"), "The downstream node 'junction' is not a gauge");
+    assert_eq!(err("gauge_a", "nowhere"), "The downstream gauge 'nowhere' is not a node of the model");
+}
@@ -0,0 +1,151 @@
+use crate::tests::test_helpers::{series_rounded, TestDir};
+use crate::hydrology::accounts::resource_assessment::{InflowForecast, InflowForecastMethod};
+use crate::io::ini_model_io::IniModelIO;
+use crate::tid::utils::date_string_to_u64_flexible;
+use crate::tid::water_year::WaterYear;
+use crate::timeseries::Timeseries;
+
+/// Daily inflows from `start` for `n_days`, at 1, 3 and 2 ML/d in the water years from July
+/// 2000, 2001 and 2002
+fn inflows(start: &str, n_days: u64) -> Vec<(String, f64)> {
+    let start = chrono::NaiveDate::parse_from_str(start, "%Y-%m-%d").unwrap();
+    (0..n_days).map(|i| {
+        let date = start + chrono::Days::new(i);
+        let rate = match WaterYear::JULY.year_of_month(chrono::Datelike::year(&date), chrono::Datelike::month(&date)) {
+            2000 => 1.0,
+            2001 => 3.0,
+            _ => 2.0,
+        };
+        (date.format("%Y-%m-%d").to_string(), rate)
+    }).collect()
+}
+
+fn timeseries(values: &[(String, f64)]) -> Timeseries {
+    let mut ts = Timeseries::new_daily();
+    for (date, value) in values {
+        ts.push(date_string_to_u64_flexible(date).unwrap().0, *value);
+    }
+    ts
+}
+
+#[test]
+fn test_inflow_forecast_from_history() {
+    // Three complete water years, and part of another before them that is left out
+    let history = timeseries(&inflows("2000-03-01", 122 + 3 * 365 + 1));
+    let mut data_cache = Default::default();
+    let mut forecast = InflowForecast::new("inflow", &mut data_cache);
+    forecast.series = "data.inflow".to_string();
+    forecast.assess_history(&history, WaterYear::JULY).unwrap();
+
+    // The driest year, from the first of the month to 30 June
+    assert_eq!(forecast.for_month(7), 365.0);
+    assert_eq!(forecast.for_month(9), 303.0);
+    assert_eq!(forecast.for_month(1), 181.0);
+
+    // The sum exceeded in a given percentage of years
+    forecast.method = InflowForecastMethod::Exceedance;
+    forecast.exceedance = 50.0;
+    forecast.assess_history(&history, WaterYear::JULY).unwrap();
+    assert_eq!(forecast.for_month(7), 730.0);
+    forecast.exceedance = 95.0;
+    forecast.assess_history(&history, WaterYear::JULY).unwrap();
+    assert!((forecast.for_month(7) - 1.1 * 365.0).abs() < 1e-9);
+
+    // Years with missing values are left out
+    let mut gappy = history.clone();
+    gappy.values[200] = f64::NAN;
+    forecast.method = InflowForecastMethod::Minimum;
+    forecast.assess_history(&gappy, WaterYear::JULY).unwrap();
+    assert_eq!(forecast.for_month(7), 730.0);
+    assert_eq!(forecast.for_month(1), 181.0);
+
+    let short = timeseries(&inflows("2000-07-02", 365));
+    let err = forecast.assess_history(&short, WaterYear::JULY).err().unwrap();
+    assert!(err.contains("'data.inflow' does not cover a complete water year"), "{}", err);
+}
+
+fn model(dir_name: &str) -> (String, TestDir) {
+    let dir = TestDir::new(dir_name);
+    let csv: String = inflows("2000-07-01", 3 * 365 + 1).iter().map(|(d, v)| format!("{},{}\n", d, v)).collect();
+    let path = dir.write("inflows.csv", &format!("Date,inflow\n{}", csv));
+    let ini = format!("\
+[inputs]
+{}
+
+[kalix]
+start = 2002-07-01
+end = 2002-10-31
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 1000
+dimensions = 0,  0,     0, 0,
+             10, 10000, 0, 0
+ds_1 = irrigator
+
+[node.irrigator]
+type = regulated_user
+loc = 0, 10
+order = 1
+
+[allocation]
+assessment_months = 9
+forecast.inflow.series = data.inflows_csv.by_name.inflow
+account.irrigator.storage = dam
+account.irrigator.allocation = 0.1 * node.dam.volume + forecast.inflow
+
+[outputs]
+forecast.inflow
+acc.irrigator.announced
+acc.irrigator.allocation
+", path.display());
+    (ini, dir)
+}
+
+#[test]
+fn test_allocation_is_reassessed_with_forecast_inflows() {
+    let (ini, _dir) = model("kalix_resource_assessment");
+    let mut m = IniModelIO::new().read_model_string(&ini).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+
+    // The forecast holds between assessments: July's until September
+    let forecast = series_rounded(&m, "forecast.inflow");
+    assert_eq!((forecast[0], forecast[61], forecast[62], forecast[122]), (365.0, 365.0, 303.0, 303.0));
+
+    // There is no volume before the first day, so only the forecast is allocated. By September
+    // the dam holds 938 ML, and the allocation goes up.
+    let announced = series_rounded(&m, "acc.irrigator.announced");
+    assert_eq!((announced[0], announced[61], announced[62]), (365.0, 365.0, 396.8));
+    assert_eq!(series_rounded(&m, "acc.irrigator.allocation")[62], 365.0 - 62.0 + 31.8 - 1.0);
+
+    // A later assessment that would reduce the allocation leaves it as it was
+    let mut m = IniModelIO::new().read_model_string(&ini.replace("0.1 * node.dam.volume + ", "")).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    assert!(series_rounded(&m, "acc.irrigator.announced").iter().all(|&v| v == 365.0));
+
+    // Round trip
+    let ini_io = IniModelIO::new();
+    let saved = ini_io.model_to_string(&ini_io.read_model_string(&ini.replace("forecast.inflow.series",
+        "forecast.inflow.method = exceedance\nforecast.inflow.exceedance = 90\nforecast.inflow.series")).unwrap());
+    for line in ["assessment_months = 9", "forecast.inflow.series = data.inflows_csv.by_name.inflow",
+                 "forecast.inflow.method = exceedance", "forecast.inflow.exceedance = 90"] {
+        assert!(saved.contains(line), "missing '{}' in:\n{}", line, saved);
+    }
+
+    // Errors
+    let err = |ini: String| {
+        let mut m = IniModelIO::new().read_model_string(&ini)?;
+        m.configure()
+    };
+    let e = err(ini.replace("by_name.inflow", "by_name.inflw")).err().unwrap();
+    assert!(e.contains("Inflow forecast 'inflow' uses 'data.inflows_csv.by_name.inflw', which is not an input series. Did you mean"), "{}", e);
+    let e = err(ini.replace("forecast.inflow.series = data.inflows_csv.by_name.inflow\n", "forecast.inflow.method = minimum\n")).err().unwrap();
+    assert!(e.contains("Inflow forecast 'inflow' has no series"), "{}", e);
+    let e = err(ini.replace("assessment_months = 9", "assessment_months = 9, 13")).err().unwrap();
+    assert!(e.contains("Invalid assessment month '13'"), "{}", e);
+    let e = err(ini.replace("forecast.inflow.series", "forecast.inflow.method = wettest\nforecast.inflow.series")).err().unwrap();
+    assert!(e.contains("Unknown inflow forecast method 'wettest'"), "{}", e);
+}
@@ -0,0 +1,76 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+
+// The gauge's observed flow is the inflow plus 10% on even days, so the simulated flow is a
+// little low overall
+const MODEL: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-03-31
+
+[constants]
+c.scale = 1.0
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = c.scale * (10 + sim.day)
+ds_1 = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 10
+reference_flow = (10 + sim.day) * if(sim.day % 2 == 0, 1.1, 1.0)
+
+[outputs]
+node.river.dsflow
+";
+
+fn run(record_gauges: bool) -> Model {
+    let mut model = IniModelIO::new().read_model_string(MODEL).unwrap();
+    if record_gauges {
+        model.record_gauge_series();
+    }
+    model.configure().unwrap();
+    model.run().unwrap();
+    model
+}
+
+#[test]
+fn test_gauge_statistics() {
+    let model = run(true);
+    let stats = model.gauge_statistics();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].gauge, "gauge");
+    assert_eq!(stats[0].n_compared, 91);
+    assert!(stats[0].nse > 0.9 && stats[0].nse < 1.0, "{}", stats[0].nse);
+    assert!(stats[0].kge > 0.9 && stats[0].kge < 1.0, "{}", stats[0].kge);
+    assert!(stats[0].pbias < -4.0 && stats[0].pbias > -5.0, "{}", stats[0].pbias);
+
+    // Without recording, the gauge's results are not kept
+    assert!(run(false).gauge_statistics().is_empty());
+}
+
+#[test]
+fn test_html_report() {
+    let model = run(true);
+    let html = model.html_report("Test <report>", None);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<h1>Test &lt;report&gt;</h1>"));
+    for section in ["<h2>Model</h2>", "<h2>Mass balance</h2>", "<h2>Outputs</h2>", "<h2>Gauges</h2>", "<h2>Parameters</h2>"] {
+        assert!(html.contains(section), "{}", section);
+    }
+    assert!(html.contains("<td>2000-01-01</td>"));
+    assert!(html.contains("<td>node.river.dsflow</td>"));
+    assert_eq!(html.matches("<svg").count(), 2);
+    assert!(html.contains("Flow duration curve"));
+    assert!(html.contains("<td>c.scale</td><td>1</td>"));
+
+    // Given parameters replace the model's own
+    let html = model.html_report("Calibrated", Some(&[("c.scale".to_string(), 1.25)]));
+    assert!(html.contains("<td>c.scale</td><td>1.25</td>"));
+
+    let html = run(false).html_report("No gauges", None);
+    assert!(html.contains("No gauges with recorded results"));
+    assert!(!html.contains("<svg"));
+}
@@ -0,0 +1,103 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::ordering::shortfall_sharing::{allocate, SharingRule};
+
+// A dam holding 100 ML supplies two irrigators who each order 80 ML/d. The upstream
+// irrigator would take its full order if nothing were shared.
+const SHORT_SUPPLY: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.dam]
+type = storage
+loc = 0, 0
+initial_volume = 100
+dimensions = 0,    0,    0, 0,
+             10,   1000, 1, 0,
+             10.1, 1001, 1, 1e8
+ds_1 = upper
+
+[node.upper]
+type = regulated_user
+loc = 0, 10
+order = 80
+priority = 2
+entitlement = 300
+ds_1 = lower
+
+[node.lower]
+type = regulated_user
+loc = 0, 20
+order = 80
+entitlement = 100
+
+[outputs]
+node.upper.diversion
+node.lower.diversion
+node.upper.allocation
+";
+
+fn first_day_diversions(ini: &str) -> (f64, f64) {
+    let mut m: Model = IniModelIO::new().read_model_string(ini).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    let value = |name: &str| m.data_cache.series[m.data_cache.get_existing_series_idx(name).unwrap()].values[0];
+    (value("node.upper.diversion"), value("node.lower.diversion"))
+}
+
+fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+    assert!((actual.0 - expected.0).abs() < 1e-9 && (actual.1 - expected.1).abs() < 1e-9,
+            "expected {:?}, got {:?}", expected, actual);
+}
+
+#[test]
+fn test_allocate() {
+    let orders = [50.0, 30.0, 20.0];
+    let priorities = [2, 1, 2];
+    let entitlements = [100.0, 300.0, 0.0];
+
+    // Enough for everyone
+    assert_eq!(allocate(SharingRule::Priority, 200.0, &orders, &priorities, &entitlements), orders.to_vec());
+
+    assert_eq!(allocate(SharingRule::ProRataOrder, 50.0, &orders, &priorities, &entitlements), vec![25.0, 15.0, 10.0]);
+
+    // Priority 1 is met in full, then priority 2 shares the remaining 35 pro-rata
+    assert_eq!(allocate(SharingRule::Priority, 65.0, &orders, &priorities, &entitlements), vec![25.0, 30.0, 10.0]);
+
+    // By entitlement 60 splits 15/45, but the second user only ordered 30, so its surplus
+    // goes to the first. The user without an entitlement gets nothing.
+    assert_eq!(allocate(SharingRule::ProRataEntitlement, 60.0, &orders, &priorities, &entitlements), vec![30.0, 30.0, 0.0]);
+    // Once the entitled users are satisfied, the rest goes to the others
+    assert_eq!(allocate(SharingRule::ProRataEntitlement, 90.0, &orders, &priorities, &entitlements), vec![50.0, 30.0, 10.0]);
+
+    assert_eq!(SharingRule::from_name("Pro_Rata_Order"), Some(SharingRule::ProRataOrder));
+    assert_eq!(SharingRule::from_name("lottery"), None);
+}
+
+#[test]
+fn test_shortfall_sharing_rules() {
+    // Without a rule the upstream irrigator is served first
+    assert_close(first_day_diversions(SHORT_SUPPLY), (80.0, 20.0));
+
+    let with_rule = |rule: &str| SHORT_SUPPLY.replace("initial_volume = 100", &format!("initial_volume = 100\nshortfall_sharing = {}", rule));
+    assert_close(first_day_diversions(&with_rule("pro_rata_order")), (50.0, 50.0));
+    assert_close(first_day_diversions(&with_rule("priority")), (20.0, 80.0));
+    assert_close(first_day_diversions(&with_rule("pro_rata_entitlement")), (75.0, 25.0));
+}
+
+#[test]
+fn test_shortfall_sharing_round_trip() {
+    let ini_io = IniModelIO::new();
+    let ini = SHORT_SUPPLY.replace("initial_volume = 100", "initial_volume = 100\nshortfall_sharing = priority");
+    let m = ini_io.read_model_string(&ini).unwrap();
+    let reloaded = ini_io.read_model_string(&ini_io.model_to_string(&m)).unwrap();
+    let saved = ini_io.model_to_string(&reloaded);
+    assert!(saved.contains("shortfall_sharing = priority"), "got:\n{}", saved);
+    assert!(saved.contains("priority = 2"), "got:\n{}", saved);
+    assert!(saved.contains("entitlement = 300"), "got:\n{}", saved);
+
+    let unknown = SHORT_SUPPLY.replace("initial_volume = 100", "initial_volume = 100\nshortfall_sharing = lottery");
+    let err = ini_io.read_model_string(&unknown).err().unwrap();
+    assert!(err.contains("pro_rata_order"), "got: {}", err);
+}
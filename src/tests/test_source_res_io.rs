@@ -0,0 +1,81 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::io::source_res_io::{write_res_csv, ResCsvOptions};
+use crate::tests::test_helpers::TestDir;
+use crate::timeseries::Timeseries;
+use crate::tid::utils::date_string_to_u64_flexible;
+
+const SOURCE_RESULTS: &str = "./src/hydrology/routing/storage_routing/source_model_results/source_model_results.res.csv";
+
+/// Lines of a results file that hold its structure rather than the run's details
+fn structure(text: &str) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let eoc = lines.iter().position(|l| *l == "EOC").unwrap();
+    let eoh = lines.iter().position(|l| *l == "EOH").unwrap();
+    let mut answer: Vec<String> = lines[..3].iter().map(|l| l.to_string()).collect();
+    answer.push(lines[eoc - 1].to_string());
+    answer.push("EOC".to_string());
+    // Column table rows have the fields of the header and a trailing comma
+    let n_fields = lines[eoc - 1].split(',').count();
+    for row in &lines[eoc + 2..eoh - 1] {
+        assert_eq!(row.split(',').count(), n_fields + 1, "{}", row);
+    }
+    answer.push("EOH".to_string());
+    answer
+}
+
+#[test]
+fn test_res_csv_matches_source_layout() {
+    let mut flow = Timeseries::new_daily();
+    flow.name = "node.outflow1.ds_1".to_string();
+    let mut volume = Timeseries::new_daily();
+    volume.name = "node.dam.volume".to_string();
+    let start = date_string_to_u64_flexible("2010-01-02").unwrap().0;
+    for (i, (q, v)) in [(2.5, 10.0), (f64::NAN, 11.0)].iter().enumerate() {
+        flow.push(start + i as u64 * 86400, *q);
+        volume.push(start + i as u64 * 86400, *v);
+    }
+
+    let dir = TestDir::new("kalix_results");
+    let path = dir.join("results.res.csv");
+    let options = ResCsvOptions {
+        project_name: "kalix_test".to_string(),
+        units: Some(vec!["ML.day^-1".to_string(), "ML".to_string()]),
+        feature_types: Some(vec!["gauge".to_string(), "storage".to_string()]),
+    };
+    write_res_csv(path.to_str().unwrap(), &[&flow, &volume], &options).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+
+    let source = std::fs::read_to_string(SOURCE_RESULTS).unwrap();
+    assert_eq!(structure(&written), structure(&source));
+
+    let lines: Vec<&str> = written.lines().collect();
+    let eoc = lines.iter().position(|l| *l == "EOC").unwrap();
+    assert_eq!(lines[eoc + 1], "2");
+    assert_eq!(lines[eoc + 2], "1,ML.day^-1,Latest Run,Kalix,Default Input Set,gauge: outflow1: ds_1,outflow1,ds_1,gauge,Node,ds_1,,");
+    assert_eq!(lines[eoc + 4], "Date,1>outflow1>ds_1,2>dam>volume");
+    assert_eq!(&lines[eoc + 5..], &["EOH", "2010-01-02,2.5,10", "2010-01-03,-9999,11"]);
+    assert!(written.contains("\nSimulation time,2010-01-02 - 2010-01-03\n"));
+}
+
+#[test]
+fn test_outputs_are_written_in_the_layout_of_their_extension() {
+    let ini = std::fs::read_to_string("./src/tests/example_models/5/model.ini").unwrap();
+    let mut model = IniModelIO::new().read_model_string(&ini).unwrap();
+    model.configure().unwrap();
+    model.run().unwrap();
+
+    let dir = TestDir::new("kalix_output_layouts");
+    let read = |name: &str| {
+        let path = dir.join(name);
+        model.write_outputs(path.to_str().unwrap()).unwrap();
+        std::fs::read_to_string(path).unwrap()
+    };
+    let res_csv = read("results.res.csv");
+    let tsv = read("results.tsv");
+    let csv = read("results.csv");
+
+    assert!(res_csv.starts_with("File version,3\nMissing data value,-9999\nEOM\nProject name,results\n"));
+    assert!(res_csv.contains("\n1,,Latest Run,Kalix,Default Input Set,inflow: node1: ds_1,node1,ds_1,inflow,Node,ds_1,,\n"));
+    assert!(tsv.starts_with("Time\tnode.node1.ds_1\tnode.node2.ds_1\r\n1925-11-15\t"));
+    assert!(csv.starts_with("Time,node.node1.ds_1,node.node2.ds_1\r\n1925-11-15,"));
+}
@@ -0,0 +1,65 @@
+use crate::data_management::run_log::LogLevel;
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+// A catchment under steady climate, starting empty, so its stores fill over the first years
+const STEADY_CLIMATE: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2002-12-31
+spin_up_years = 1
+
+[node.catchment]
+type = gr4j
+loc = 0, 0
+rain = 3
+evap = 2
+area = 100
+params = 350, 0, 90, 1.7
+ds_1 = reach
+
+[node.reach]
+type = routing
+loc = 0, 10
+pwl = 0, 1, 1000, 1
+
+[outputs]
+node.catchment.production_store
+node.reach.dsflow
+";
+
+#[test]
+fn test_spin_up_settles_states() {
+    let m = run(STEADY_CLIMATE).unwrap();
+
+    // The run starts where it would otherwise have ended up
+    let store = series(&m, "node.catchment.production_store");
+    let flow = series(&m, "node.reach.dsflow");
+    assert!((store[0] - store[store.len() - 1]).abs() < 1.0, "{} vs {}", store[0], store[store.len() - 1]);
+    assert!((flow[0] - flow[flow.len() - 1]).abs() / flow[flow.len() - 1] < 0.01);
+
+    let entry = m.run_log().entries().iter().find(|e| e.code == "spin_up").unwrap();
+    assert_eq!(entry.level, LogLevel::Info);
+    assert!(entry.message.starts_with("Spin-up settled after"), "{}", entry.message);
+
+    // Without a spin-up the catchment starts empty
+    let cold = run(&STEADY_CLIMATE.replace("spin_up_years = 1\n", "")).unwrap();
+    assert_eq!(series(&cold, "node.catchment.production_store")[0] < 1.0, true);
+    assert!(cold.run_log().entries().iter().all(|e| e.code != "spin_up"));
+}
+
+#[test]
+fn test_spin_up_loop_limit() {
+    let ini = STEADY_CLIMATE.replace("spin_up_years = 1", "spin_up_years = 1\nspin_up_max_loops = 1\nspin_up_tolerance = 1e-9");
+    let m = run(&ini).unwrap();
+    let entry = m.run_log().entries().iter().find(|e| e.code == "spin_up").unwrap();
+    assert_eq!(entry.level, LogLevel::Warning);
+    assert!(entry.message.contains("did not settle in 1 loops"), "{}", entry.message);
+
+    // Settings round-trip
+    let io = IniModelIO::new();
+    let text = io.model_to_string(&io.read_model_string(&ini).unwrap());
+    assert!(text.contains("spin_up_years = 1"));
+    assert!(text.contains("spin_up_max_loops = 1"));
+    assert!(text.contains("spin_up_tolerance = 1e-9"));
+}
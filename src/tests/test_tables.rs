@@ -0,0 +1,70 @@
+use crate::tests::test_helpers::{run, series, TestDir};
+use crate::io::ini_model_io::IniModelIO;
+
+// Inflow grows by a day each timestep, and the pump demand is a piecewise-linear function
+// of it, held at the table's end values outside its range
+const PUMP_CURVE: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-05
+
+[tables]
+tables.pump_curve = 10, 0, 30, 20, 40, 20
+
+[node.river]
+type = inflow
+loc = 0, 0
+inflow = 10 * sim.day
+ds_1 = pump
+
+[node.pump]
+type = unregulated_user
+loc = 0, 10
+demand = lookup(\"tables.pump_curve\", node.river.dsflow[-1, 0])
+
+[outputs]
+node.pump.demand
+";
+
+#[test]
+fn test_table_lookup_in_expression() {
+    // Yesterday's inflow was 0 (default), 10, 20, 30 and 40
+    let m = run(PUMP_CURVE).unwrap();
+    assert_eq!(series(&m, "node.pump.demand"), vec![0.0, 0.0, 10.0, 20.0, 20.0]);
+
+    // The quotes around the table name are optional
+    let m = run(&PUMP_CURVE.replace("\"tables.pump_curve\"", "tables.pump_curve")).unwrap();
+    assert_eq!(series(&m, "node.pump.demand"), vec![0.0, 0.0, 10.0, 20.0, 20.0]);
+}
+
+#[test]
+fn test_table_from_csv_file() {
+    let dir = TestDir::new("kalix_test_tables");
+    dir.write("pump_curve.csv", "flow,demand\n10,0\n30,20\n40,20\n");
+
+    let ini = PUMP_CURVE.replace("10, 0, 30, 20, 40, 20", "./pump_curve.csv");
+    let mut m = IniModelIO::new().read_model_string_with_working_directory(&ini, Some(dir.path().to_path_buf())).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    assert_eq!(series(&m, "node.pump.demand"), vec![0.0, 0.0, 10.0, 20.0, 20.0]);
+
+    // The file path is written back as it was given
+    let text = IniModelIO::new().model_to_string(&m);
+    assert!(text.contains("tables.pump_curve = ./pump_curve.csv"), "{}", text);
+}
+
+#[test]
+fn test_table_errors_and_round_trip() {
+    let ini_io = IniModelIO::new();
+    let text = ini_io.model_to_string(&ini_io.read_model_string(PUMP_CURVE).unwrap());
+    assert!(text.contains("tables.pump_curve = 10, 0, 30, 20, 40, 20"), "{}", text);
+    assert_eq!(ini_io.model_to_string(&ini_io.read_model_string(&text).unwrap()), text);
+
+    // Tables must be defined, named with the prefix, given as pairs with x increasing
+    let err = ini_io.read_model_string(&PUMP_CURVE.replace("\"tables.pump_curve\"", "\"tables.other\"")).err().unwrap();
+    assert!(err.contains("'tables.other' is used but not defined"), "{}", err);
+    assert!(ini_io.read_model_string(&PUMP_CURVE.replace("tables.pump_curve =", "pump_curve =")).is_err());
+    assert!(ini_io.read_model_string(&PUMP_CURVE.replace("40, 20\n", "40\n")).is_err());
+    assert!(ini_io.read_model_string(&PUMP_CURVE.replace("30, 20, 40", "30, 20, 20")).is_err());
+    assert!(ini_io.read_model_string(&PUMP_CURVE.replace("lookup(\"tables.pump_curve\",", "lookup(\"pump_curve\",")).is_err());
+}
@@ -0,0 +1,163 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::model::Model;
+use crate::nodes::NodeEnum;
+use crate::tests::test_helpers::{run, series};
+
+// Two catchments built from one template, joining at a gauge
+const TEMPLATED: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-03-31
+
+[template.catchment]
+; A catchment with a reach down to its outlet
+runoff.type = gr4j
+runoff.loc = 0, 0
+runoff.rain = {rain}
+runoff.evap = 2
+runoff.area = {area}
+runoff.params = {x1}, 0, 90, 1.7
+runoff.ds_1 = reach
+reach.type = routing
+reach.loc = 0, 10
+reach.pwl = 0, {k}, 1000, {k}
+reach.ds_1 = {outlet}
+
+[instance.upper]
+template = catchment
+loc = 100, 200
+rain = 5
+area = 100
+x1 = 350
+k = 1
+outlet = gauge
+
+[instance.lower]
+template = catchment
+rain = 4
+area = 50
+x1 = 300
+k = 2
+outlet = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 50
+
+[outputs]
+node.upper_reach.dsflow
+node.lower_reach.dsflow
+node.gauge.dsflow
+";
+
+// The same network written out node by node
+const FLAT: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-03-31
+
+[node.upper_runoff]
+type = gr4j
+loc = 100, 200
+rain = 5
+evap = 2
+area = 100
+params = 350, 0, 90, 1.7
+ds_1 = upper_reach
+
+[node.upper_reach]
+type = routing
+loc = 100, 210
+pwl = 0, 1, 1000, 1
+ds_1 = gauge
+
+[node.lower_runoff]
+type = gr4j
+loc = 0, 0
+rain = 4
+evap = 2
+area = 50
+params = 300, 0, 90, 1.7
+ds_1 = lower_reach
+
+[node.lower_reach]
+type = routing
+loc = 0, 10
+pwl = 0, 2, 1000, 2
+ds_1 = gauge
+
+[node.gauge]
+type = gauge
+loc = 0, 50
+
+[outputs]
+node.upper_reach.dsflow
+node.lower_reach.dsflow
+node.gauge.dsflow
+";
+
+#[test]
+fn test_template_instances_match_flat_model() {
+    let templated = run(TEMPLATED).unwrap();
+    let flat = run(FLAT).unwrap();
+    for name in ["node.upper_reach.dsflow", "node.lower_reach.dsflow", "node.gauge.dsflow"] {
+        assert_eq!(series(&templated, name), series(&flat, name), "{}", name);
+    }
+    assert!(series(&templated, "node.gauge.dsflow").iter().any(|&q| q > 0.0));
+
+    // Node locations are offset by the instance's location
+    let reach = templated.get_node("upper_reach").unwrap();
+    assert_eq!((reach.get_location().x(), reach.get_location().y()), (100.0, 210.0));
+    assert!(templated.diff(&flat).is_empty(), "{}", templated.diff(&flat).to_text());
+}
+
+#[test]
+fn test_templates_kept_when_saved() {
+    let io = IniModelIO::new();
+    let m: Model = io.read_model_string(TEMPLATED).unwrap();
+    let text = io.model_to_string(&m);
+    assert!(text.contains("[template.catchment]\n; A catchment with a reach down to its outlet\nrunoff.type = gr4j"), "{}", text);
+    assert!(text.contains("[instance.upper]"));
+    assert!(!text.contains("[node.upper_runoff]"), "{}", text);
+    assert_eq!(text, TEMPLATED);
+
+    // An edited node is saved as a section of its own, which replaces the instance's node
+    let mut edited: Model = io.read_model_string(TEMPLATED).unwrap();
+    let idx = edited.get_node_idx("upper_runoff").unwrap();
+    if let NodeEnum::Gr4jNode(n) = &mut edited.nodes[idx] {
+        n.area_km2 = 120.0;
+    }
+    let text = io.model_to_string(&edited);
+    assert!(text.contains("[instance.upper]"));
+    assert!(text.contains("[node.upper_runoff]"), "{}", text);
+    assert!(!text.contains("[node.lower_runoff]"), "{}", text);
+
+    let reloaded: Model = io.read_model_string(&text).unwrap();
+    let area = |m: &Model, name: &str| match m.get_node(name).unwrap() {
+        NodeEnum::Gr4jNode(n) => n.area_km2,
+        _ => panic!("not gr4j"),
+    };
+    assert_eq!(area(&reloaded, "upper_runoff"), 120.0);
+    assert_eq!(area(&reloaded, "lower_runoff"), 50.0);
+    assert_eq!(reloaded.nodes.len(), 5);
+}
+
+#[test]
+fn test_template_errors() {
+    let io = IniModelIO::new();
+    let missing = TEMPLATED.replace("x1 = 300\n", "");
+    let err = io.read_model_string(&missing).err().unwrap();
+    assert!(err.contains("Instance 'lower' gives no value for '{x1}'"), "{}", err);
+
+    let typo = TEMPLATED.replace("x1 = 300", "x1 = 300\nxl = 300");
+    let err = io.read_model_string(&typo).err().unwrap();
+    assert!(err.contains("Template 'catchment' has no placeholder '{xl}'"), "{}", err);
+
+    let unknown = TEMPLATED.replace("[instance.lower]\ntemplate = catchment", "[instance.lower]\ntemplate = catchmnet");
+    let err = io.read_model_string(&unknown).err().unwrap();
+    assert!(err.contains("Template 'catchmnet' is not defined"), "{}", err);
+
+    let bad_key = TEMPLATED.replace("runoff.evap = 2", "evap = 2");
+    let err = io.read_model_string(&bad_key).err().unwrap();
+    assert!(err.contains("keys must be NODE.PROPERTY"), "{}", err);
+}
@@ -0,0 +1,158 @@
+use crate::terminal_plot::{Color, ColorScheme, Line, LineStyle, Resolution, ScatterPoint, TerminalPlot};
+use crate::timeseries::Timeseries;
+use crate::numerical::opt::OptimizationProgress;
+use crate::terminal_plot::optimisation_plot::{OptimisationPlot, ParameterBand};
+use std::time::Duration;
+
+fn plot(resolution: Resolution) -> TerminalPlot {
+    TerminalPlot::builder()
+        .width(10)
+        .height(4)
+        .x_range(0.0, 10.0)
+        .y_range(0.0, 4.0)
+        .color_scheme(ColorScheme::monochrome())
+        .resolution(resolution)
+        .build()
+}
+
+fn rising_line() -> Line {
+    Line { points: vec![(0.0, 0.0), (10.0, 4.0)], style: LineStyle::Solid, color: Some(Color::Cyan) }
+}
+
+/// Characters drawn in the plot area, without colour codes or the axis and borders
+fn plot_area(plot: &mut TerminalPlot) -> Vec<String> {
+    let mut text = String::new();
+    let mut in_escape = false;
+    for c in plot.render().chars() {
+        match c {
+            '\x1b' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if !in_escape => text.push(c),
+            _ => {}
+        }
+    }
+    text.lines()
+        .filter_map(|line| line.split_once('┊').map(|(_, rest)| rest.chars().take(10).collect()))
+        .take(4)
+        .collect()
+}
+
+#[test]
+fn test_cell_resolution_draws_the_line_style() {
+    let mut plot = plot(Resolution::Cell);
+    plot.add_line(rising_line());
+    let area = plot_area(&mut plot);
+    assert_eq!(area.len(), 4);
+    assert!(area.iter().all(|row| row.chars().all(|c| c == ' ' || c == '━')));
+    assert!(area[3].starts_with('━'));
+}
+
+#[test]
+fn test_braille_resolution_draws_sub_cell_points() {
+    let mut plot = plot(Resolution::Braille);
+    plot.add_line(rising_line());
+    let area = plot_area(&mut plot);
+    // The line rises 4 points every 5 across, so it starts along the bottom dots of the first cell
+    assert_eq!(area[3], "⣀⠔⠁       ");
+    assert_eq!(area[0], "       ⢀⡠⠊");
+    assert!(area.iter().flat_map(|row| row.chars()).all(|c| c == ' ' || ('\u{2800}'..='\u{28ff}').contains(&c)));
+    // Every row is crossed by the line
+    assert!(area.iter().all(|row| row.chars().any(|c| c != ' ')));
+}
+
+#[test]
+fn test_quadrant_resolution_draws_block_characters() {
+    let mut plot = plot(Resolution::Quadrant);
+    plot.add_line(Line { points: vec![(0.0, 0.1), (10.0, 0.1)], style: LineStyle::Solid, color: None });
+    let area = plot_area(&mut plot);
+    // A flat line along the bottom half of the lowest row
+    assert_eq!(area[3], "▄".repeat(10));
+    assert!(area[..3].iter().all(|row| row.trim().is_empty()));
+}
+
+#[test]
+fn test_scatter_points_are_drawn_over_sub_cell_lines() {
+    let mut plot = plot(Resolution::Braille);
+    plot.add_line(rising_line());
+    plot.add_scatter_points(vec![ScatterPoint { x: 0.1, y: 0.1, color: None, symbol: '∘' }]);
+    assert!(plot_area(&mut plot)[3].starts_with('∘'));
+}
+
+#[test]
+fn test_flow_duration_curve_points() {
+    let mut series = Timeseries::new_daily();
+    for v in [5.0, f64::NAN, 1.0, 100.0, 0.0] {
+        series.push_value(v);
+    }
+    let line = Line::flow_duration_curve(&series);
+    assert_eq!(line.points, vec![(20.0, 100.0), (40.0, 5.0), (60.0, 1.0), (80.0, 0.0)]);
+}
+
+#[test]
+fn test_log_y_axis_spaces_decades_evenly() {
+    let mut plot = TerminalPlot::builder()
+        .width(10)
+        .height(4)
+        .x_range(0.0, 10.0)
+        .y_range(1.0, 10000.0)
+        .log_y(true)
+        .build();
+    // One point in each decade, and a zero that can't be drawn
+    plot.add_scatter_points([(0.5, 1.5), (3.0, 15.0), (5.5, 150.0), (8.0, 1500.0), (9.0, 0.0)].iter()
+        .map(|&(x, y)| ScatterPoint { x, y, color: None, symbol: 'o' })
+        .collect());
+    let area = plot_area(&mut plot);
+    assert_eq!(area, vec!["        o ", "     o    ", "   o      ", "o         "]);
+    let text = plot.to_string();
+    assert!(text.contains("10000"));
+    assert!(text.contains(" 100 "));
+}
+
+#[test]
+fn test_flow_duration_curve_plot() {
+    let mut series = Timeseries::new_daily();
+    series.name = "node.gauge.dsflow".to_string();
+    for i in 0..365 {
+        series.push_value(if i % 10 == 0 { 0.0 } else { (i as f64 / 20.0).exp() });
+    }
+    let text = TerminalPlot::flow_duration_curve(&series).to_string();
+    assert!(text.contains("node.gauge.dsflow"));
+    assert!(text.contains("% exceeded"));
+    assert!(text.chars().any(|c| ('\u{2801}'..='\u{28ff}').contains(&c)));
+}
+
+#[test]
+fn test_parameter_bands_from_population() {
+    let population = vec![vec![0.1, 0.5], vec![0.4, 0.5], vec![0.2, 0.6], vec![0.9, 0.5]];
+    let bands = ParameterBand::from_population(&population);
+    assert_eq!(bands.len(), 2);
+    assert_eq!(bands[0], ParameterBand { min: 0.1, median: 0.30000000000000004, max: 0.9 });
+    assert_eq!(bands[1], ParameterBand { min: 0.5, median: 0.5, max: 0.6 });
+}
+
+#[test]
+fn test_optimisation_plot_parameter_panels() {
+    let mut plot = OptimisationPlot::new("KALIX//OPTIMISER", 1000, 50, 12)
+        .with_parameter_names(vec!["g(1)".to_string(), "g(2)".to_string(), "g(3)".to_string()]);
+    // No panels until the optimiser reports its population
+    plot.update_from_progress(&OptimizationProgress::new(10, 2.0, Duration::from_secs(1)));
+    assert!(!plot.render().contains("Parameter spread"));
+
+    for generation in 1..=10 {
+        let shrink = 1.0 / generation as f64;
+        let population: Vec<Vec<f64>> = (0..8)
+            .map(|i| {
+                let offset = (i as f64 / 7.0 - 0.5) * shrink;
+                vec![0.5 + offset, 0.3 + offset * 0.5, 0.8]
+            })
+            .collect();
+        let progress = OptimizationProgress::new(generation * 100, 1.0 / generation as f64, Duration::from_secs(1))
+            .with_population_params(population);
+        plot.update_from_progress(&progress);
+    }
+    let text = plot.render();
+    assert!(text.contains("Parameter spread"));
+    assert!(text.contains("g(1)") && text.contains("0.100"));
+    assert!(text.contains("g(2)") && text.contains("0.050"));
+    assert!(text.contains("g(3)") && text.contains("0.000"));
+}
@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+use crate::io::ini_model_io::IniModelIO;
+use crate::misc::batch_run::{BatchOptions, ParameterSet};
+use crate::model::Model;
+use crate::numerical::opt::OptimisationConfig;
+use crate::run::{calibrate_model, OptimisationOutcome};
+use crate::tests::test_helpers::TestDir;
+
+// With a fixed seed, calibration and batch results must be bit-for-bit the same whatever the
+// number of threads. The GR4J node carries state from step to step, so these also check that
+// reused worker models do not leak anything from one evaluation into the next.
+
+fn load_model() -> Model {
+    let ini = std::fs::read_to_string("./src/tests/example_models/1/first_model.ini").unwrap()
+        .replace("#version = 0.0.1", "start = 2000-01-01\nend = 2002-12-31");
+    IniModelIO::new().read_model_string_with_working_directory(
+        &ini, Some(PathBuf::from("./src/tests/example_models/1"))).unwrap()
+}
+
+fn calibrate(algorithm_lines: &str, n_threads: usize, observed: &str) -> OptimisationOutcome {
+    let config = OptimisationConfig::from_ini(&format!("\
+[optimisation]
+objective_expression = term1
+{}
+termination_evaluations = 150
+n_threads = {}
+random_seed = 11
+
+[term.term1]
+simulated = node.my_gr4j_node.dsflow
+observed_file = {}
+observed_series = 1
+statistic = SDEB
+
+[parameters]
+node.my_gr4j_node.x1 = lin_range(g(1), 10, 2000)
+node.my_gr4j_node.x2 = lin_range(g(2), -8, 6)
+node.my_gr4j_node.x3 = lin_range(g(3), 10, 500)
+node.my_gr4j_node.x4 = lin_range(g(4), 0.5, 4.0)
+", algorithm_lines, n_threads, observed)).unwrap();
+    calibrate_model(&mut load_model(), &config, None).unwrap()
+}
+
+#[test]
+fn test_calibration_is_independent_of_thread_count() {
+    // Observed flows from the model as given
+    let dir = TestDir::new("kalix_thread_reproducibility");
+    let observed = dir.join("observed.csv");
+    let observed = observed.to_str().unwrap();
+    let mut model = load_model();
+    model.configure().unwrap();
+    model.run().unwrap();
+    model.write_outputs(observed).unwrap();
+
+    for algorithm_lines in ["algorithm = DE\npopulation_size = 12", "algorithm = SCE\ncomplexes = 3"] {
+        let single = calibrate(algorithm_lines, 1, observed);
+        for n_threads in [2, 4] {
+            let parallel = calibrate(algorithm_lines, n_threads, observed);
+            assert_eq!(parallel.best_objective.to_bits(), single.best_objective.to_bits(), "{}", algorithm_lines);
+            assert_eq!(parallel.n_evaluations, single.n_evaluations);
+            for (p, s) in parallel.parameters.iter().zip(&single.parameters) {
+                assert_eq!(p.1.to_bits(), s.1.to_bits(), "{} {}", algorithm_lines, p.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_batch_is_independent_of_thread_count() {
+    let param_sets: Vec<ParameterSet> = (0..9)
+        .map(|i| vec![
+            ("node.my_gr4j_node.x1".to_string(), 100.0 + 200.0 * i as f64),
+            ("node.my_gr4j_node.x4".to_string(), 0.5 + 0.3 * i as f64),
+        ])
+        .collect();
+    let options = |n_threads| BatchOptions {
+        series: vec!["node.my_gr4j_node.dsflow".to_string()],
+        return_series: true,
+        n_threads,
+    };
+    let single = load_model().run_batch(&param_sets, &options(1)).unwrap();
+    for n_threads in [2, 4] {
+        let parallel = load_model().run_batch(&param_sets, &options(n_threads)).unwrap();
+        for (p, s) in parallel.iter().zip(&single) {
+            assert_eq!(p.index, s.index);
+            let bits = |values: &Option<Vec<f64>>| values.as_ref().unwrap().iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&p.series[0].values), bits(&s.series[0].values), "set {}", p.index);
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use crate::tid::utils::{date_string_to_u64, u64_to_date_string};
+use crate::io::ini_model_io::IniModelIO;
+use crate::tid::utils::{date_string_to_u64, date_string_to_u64_flexible, seconds_between, u64_to_date_string,
+                        u64_to_iso_datetime_string, u64_to_unix_seconds, u64_to_year_month_day_and_seconds, unix_seconds_to_u64};
+use crate::tid::water_year::WaterYear;
+use crate::tests::test_helpers::TestDir;
 
 #[test]
 fn test_date_string_to_u64() {
@@ -18,4 +22,86 @@ fn test_u64_to_date_string() {
     let ans_str = u64_to_date_string(ans_u64);
     println!("ans_str: {}", ans_str);
     assert_eq!(ans_str, "9999-01-01");
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn test_water_year() {
+    let july = WaterYear::default();
+    assert_eq!(july, WaterYear::JULY);
+    assert_eq!(july.year_of(date_string_to_u64("2000-06-30").unwrap()), 1999);
+    assert_eq!(july.year_of(date_string_to_u64("2000-07-01").unwrap()), 2000);
+    assert_eq!(july.year_of(date_string_to_u64("2001-06-30").unwrap()), 2000);
+    assert_eq!(u64_to_date_string(july.start_timestamp(2000)), "2000-07-01");
+    assert_eq!((july.month_of_year(7), july.month_of_year(12), july.month_of_year(6)), (1, 6, 12));
+
+    assert!(july.is_start_of_year(date_string_to_u64("2000-07-01").unwrap()));
+    assert!(!july.is_start_of_year(date_string_to_u64("2000-07-02").unwrap()));
+    assert!(!july.is_start_of_year(date_string_to_u64("2000-07-01").unwrap() + 3600));
+    assert!(WaterYear::CALENDAR.is_start_of_year(date_string_to_u64("2000-01-01").unwrap()));
+
+    let october = WaterYear::new(10).unwrap();
+    assert_eq!(october.year_of(date_string_to_u64("2000-09-30").unwrap()), 1999);
+    assert_eq!(WaterYear::new(13).err().unwrap(), "Invalid water year start month 13: must be 1 to 12");
+    assert!(WaterYear::new(0).is_err());
+}
+
+
+#[test]
+fn test_dates_either_side_of_1970_round_trip() {
+    let dates = ["1890-01-01", "1900-02-28", "1969-12-31", "1970-01-01", "2000-02-29", "2100-12-31"];
+    let timestamps: Vec<u64> = dates.iter().map(|d| date_string_to_u64(d).unwrap()).collect();
+    for (date, &t) in dates.iter().zip(&timestamps) {
+        assert_eq!(u64_to_date_string(t), *date);
+        assert_eq!(unix_seconds_to_u64(u64_to_unix_seconds(t)), t);
+    }
+    assert!(timestamps.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(u64_to_unix_seconds(timestamps[2]), -86400);
+    assert_eq!(u64_to_unix_seconds(timestamps[3]), 0);
+    assert_eq!(seconds_between(timestamps[2], timestamps[3]), -86400);
+    assert_eq!(seconds_between(timestamps[3], timestamps[2]), 86400);
+    assert_eq!(u64_to_year_month_day_and_seconds(timestamps[0]), (1890, 1, 1, 0));
+
+    let t = date_string_to_u64_flexible("1969-12-31T18:30:00").unwrap().0;
+    assert_eq!(u64_to_iso_datetime_string(t), "1969-12-31T18:30:00.000Z");
+    assert_eq!(u64_to_year_month_day_and_seconds(t), (1969, 12, 31, 66600));
+}
+
+
+#[test]
+fn test_century_scale_run_round_trips_through_csv_and_pixie() {
+    let ini = "\
+[kalix]
+start = 1890-01-01
+end = 2100-12-31
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = sim.year + sim.month / 100
+
+[outputs]
+node.a.dsflow
+";
+    let mut m = IniModelIO::new().read_model_string(ini).unwrap();
+    m.configure().unwrap();
+    m.run().unwrap();
+    let outputs = m.collect_output_series();
+    let ts = &outputs[0];
+    assert_eq!(ts.len(), 77066);
+    assert_eq!((ts.values[0], ts.values[ts.len() - 1]), (1890.01, 2100.12));
+    assert!(IniModelIO::new().model_to_string(&m).contains("start = 1890-01-01"));
+
+    let dir = TestDir::new("kalix_century");
+    let csv_path = dir.join("century.csv");
+    crate::io::csv_io::write_ts(csv_path.to_str().unwrap(), vec![ts]).unwrap();
+    let csv = crate::io::csv_io::read_ts(csv_path.to_str().unwrap()).unwrap();
+    assert_eq!(csv[0].timestamps, ts.timestamps);
+    assert_eq!(csv[0].values, ts.values);
+
+    let pixie_path = dir.join("century");
+    crate::io::pixie_io::write_series_with_precision(pixie_path.to_str().unwrap(), &[ts], true).unwrap();
+    let pixie = crate::io::pixie_io::read_all_series(pixie_path.to_str().unwrap()).unwrap();
+    assert_eq!(pixie[0].timestamps, ts.timestamps);
+    assert_eq!(pixie[0].values, ts.values);
+}
@@ -56,3 +56,77 @@ fn test_read_csv_values() {
     assert_eq!(what[0].count_nonzero(), 4);
     assert_eq!(what[0].sum(), 38.1);
 }
+
+
+fn daily(start_day: u64, values: &[f64]) -> Timeseries {
+    let mut ts = Timeseries::new_daily();
+    ts.start_timestamp = start_day * 86400;
+    for &v in values {
+        ts.push_value(v);
+    }
+    ts
+}
+
+fn same(a: &[f64], b: &[f64]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x == y || (x.is_nan() && y.is_nan()))
+}
+
+
+/*
+Align two series with different start dates over the steps they share, and over all steps.
+ */
+#[test]
+fn test_timeseries_align() {
+    use crate::timeseries::Alignment;
+    let a = daily(10, &[1.0, 2.0, 3.0, 4.0]);
+    let b = daily(12, &[10.0, 20.0, 30.0]);
+
+    let (x, y) = a.align(&b, Alignment::Intersection).unwrap();
+    assert_eq!(x.start_timestamp, 12 * 86400);
+    assert_eq!(x.timestamps, vec![12 * 86400, 13 * 86400]);
+    assert_eq!(x.values, vec![3.0, 4.0]);
+    assert_eq!(y.values, vec![10.0, 20.0]);
+
+    let (x, y) = a.align(&b, Alignment::Union).unwrap();
+    assert_eq!(x.start_timestamp, 10 * 86400);
+    assert!(same(&x.values, &[1.0, 2.0, 3.0, 4.0, f64::NAN]));
+    assert!(same(&y.values, &[f64::NAN, f64::NAN, 10.0, 20.0, 30.0]));
+
+    // No shared steps, different step sizes, and timestamps off the grid
+    let (x, _) = a.align(&daily(20, &[1.0]), Alignment::Intersection).unwrap();
+    assert_eq!(x.len(), 0);
+    let mut hourly = Timeseries::new(3600);
+    hourly.push_value(1.0);
+    assert!(a.align(&hourly, Alignment::Union).is_err());
+    let mut offset = daily(10, &[1.0]);
+    offset.start_timestamp += 3600;
+    assert!(a.align(&offset, Alignment::Union).is_err());
+}
+
+
+/*
+Elementwise arithmetic, shifting, clipping and running totals.
+ */
+#[test]
+fn test_timeseries_algebra() {
+    let a = daily(10, &[1.0, 2.0, 3.0, 4.0]);
+    let b = daily(12, &[10.0, 20.0, 30.0]);
+
+    assert_eq!((&a + &b).values, vec![13.0, 24.0]);
+    assert_eq!((&a + &b).start_timestamp, 12 * 86400);
+    assert_eq!((&b - &a).values, vec![7.0, 16.0]);
+    assert_eq!((&a * &b).values, vec![30.0, 80.0]);
+    assert_eq!((&a * 2.0).values, vec![2.0, 4.0, 6.0, 8.0]);
+    assert_eq!((&a - 1.0).values, vec![0.0, 1.0, 2.0, 3.0]);
+    assert!(a.zip_with(&Timeseries::new(3600), |x, y| x + y).is_err());
+
+    assert!(same(&a.shift(1).values, &[f64::NAN, 1.0, 2.0, 3.0]));
+    assert!(same(&a.shift(-2).values, &[3.0, 4.0, f64::NAN, f64::NAN]));
+    assert_eq!(a.shift(1).timestamps, a.timestamps);
+
+    let c = daily(0, &[-5.0, 0.5, f64::NAN, 7.0]);
+    assert!(same(&c.clip(0.0, 1.0).values, &[0.0, 0.5, f64::NAN, 1.0]));
+
+    assert_eq!(a.cumulative_sum().values, vec![1.0, 3.0, 6.0, 10.0]);
+    assert!(same(&c.cumulative_sum().values, &[-5.0, -4.5, f64::NAN, f64::NAN]));
+}
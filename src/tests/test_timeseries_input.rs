@@ -132,3 +132,35 @@ fn test_model_with_aliased_data() {
     assert_eq!(ans.len(), 6);
     assert_eq!(ans.sum(), 38.1);
 }
+
+#[test]
+fn test_input_data_from_csv_text() {
+    use crate::io::ini_model_io::IniModelIO;
+
+    // Data supplied as text (as in a WebAssembly build) is addressed like a file of that name
+    let ini = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = 2 * data.rain_csv.by_name.rain + data.climate.by_index.2
+
+[outputs]
+node.a.ds_1
+";
+    let csv = "Date,Rain,Evap\n2000-01-01,1.0,10.0\n2000-01-02,2.0,20.0\n2000-01-03,3.0,30.0\n";
+    let mut model = IniModelIO::new().read_model_string(ini).unwrap();
+    assert!(model.configure().is_err());
+    assert_eq!(model.load_input_csv_str("rain.csv", csv, Some("climate")).unwrap(), 2);
+    model.configure().unwrap();
+    model.run().unwrap();
+    let idx = model.data_cache.get_existing_series_idx("node.a.ds_1").unwrap();
+    assert_eq!(model.data_cache.series[idx].values, vec![12.0, 24.0, 36.0]);
+
+    // It is not saved as an input file
+    assert!(!IniModelIO::new().model_to_string(&model).contains("rain.csv"));
+    assert!(model.load_input_csv_str("bad.csv", "Date,x\nnot a date,1\n", None).is_err());
+}
@@ -73,4 +73,35 @@ fn test_uh_prealloc32_2() {
     assert_eq!(v4, 0.1);
     assert_eq!(v5, 0.0);
     //println!("{v1} {v2} {v3} {v4} {v5}");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_uh_ring_buffers_match_shifting() {
+    // The circular buffers give exactly what shifting the storages each step does
+    let kernel = [0.05, 0.3, 0.25, 0.2, 0.1, 0.07, 0.03];
+    let mut uhp = uh_prealloc_32::UHPrealloc32::new(kernel.len());
+    for (i, &k) in kernel.iter().enumerate() {
+        uhp.set_kernel(i, k);
+    }
+    uhp.reset();
+    let mut uhd = uh_dyn::UHDyn::from_kernel(kernel.to_vec());
+    let mut shifting = vec![0.0; kernel.len()];
+    for step in 0..50 {
+        let input = ((step * 7) % 11) as f64;
+        for i in 0..kernel.len() {
+            shifting[i] += input * kernel[i];
+        }
+        let expected = shifting.remove(0);
+        shifting.push(0.0);
+        assert_eq!(uhp.run_step(input), expected);
+        assert_eq!(uhd.run_step(input), expected);
+        assert_eq!(uhd.get_state(), shifting);
+    }
+
+    // The state restores into a fresh buffer, whatever its head
+    let mut restored = uh_dyn::UHDyn::from_kernel(kernel.to_vec());
+    restored.set_state(&uhd.get_state());
+    for _ in 0..10 {
+        assert_eq!(restored.run_step(1.0), uhd.run_step(1.0));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::io::ini_model_io::IniModelIO;
+use crate::tests::test_helpers::{run, series};
+
+// Two tributaries join, and a pump below the junction diverts a tenth of yesterday's total
+// inflow. Demand is a function of the day only, so it is evaluated before the nodes run.
+const TRIBUTARIES: &str = "\
+[kalix]
+start = 2000-01-01
+end = 2000-01-03
+
+[functions]
+f.total_inflow = node.a.dsflow + node.b.dsflow
+f.share = 0.1 * f.total_inflow
+f.demand = 2 * sim.day
+
+[node.a]
+type = inflow
+loc = 0, 0
+inflow = 10 * sim.day
+ds_1 = junction
+
+[node.b]
+type = inflow
+loc = 10, 0
+inflow = 30
+ds_1 = junction
+
+[node.junction]
+type = confluence
+loc = 5, 10
+ds_1 = pump
+
+[node.pump]
+type = unregulated_user
+loc = 5, 20
+demand = f.share[-1, 0] + f.demand
+
+[outputs]
+f.total_inflow
+f.share
+node.pump.diversion
+";
+
+#[test]
+fn test_functions_are_evaluated_and_referenced() {
+    let m = run(TRIBUTARIES).unwrap();
+    assert_eq!(series(&m, "f.total_inflow"), vec![40.0, 50.0, 60.0]);
+    assert_eq!(series(&m, "f.share"), vec![4.0, 5.0, 6.0]);
+
+    // Node results are only known after the flow phase, so the pump uses yesterday's share
+    assert_eq!(series(&m, "node.pump.diversion"), vec![2.0, 8.0, 11.0]);
+    assert!(m.functions.functions[1].after_flow);
+    assert!(!m.functions.functions[2].after_flow);
+}
+
+#[test]
+fn test_function_errors_and_round_trip() {
+    let ini_io = IniModelIO::new();
+    let text = ini_io.model_to_string(&ini_io.read_model_string(TRIBUTARIES).unwrap());
+    assert!(text.contains("f.share = 0.1 * f.total_inflow"));
+    assert_eq!(ini_io.model_to_string(&ini_io.read_model_string(&text).unwrap()), text);
+
+    // Functions may only use functions defined above them
+    let err = ini_io.read_model_string(&TRIBUTARIES.replace("0.1 * f.total_inflow", "0.1 * f.demand")).err().unwrap();
+    assert!(err.contains("not defined above it"), "{}", err);
+
+    // Names need the prefix, and references must be defined
+    assert!(ini_io.read_model_string(&TRIBUTARIES.replace("f.demand =", "demand =")).is_err());
+    let err = ini_io.read_model_string(&TRIBUTARIES.replace("+ f.demand", "+ f.unknown")).err().unwrap();
+    assert!(err.contains("'f.unknown' is used but not defined"), "{}", err);
+}
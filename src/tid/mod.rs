@@ -1,2 +1,3 @@
 pub mod utils;
 
+pub mod water_year;
@@ -18,7 +18,7 @@ use chrono::{DateTime, ParseResult, NaiveDate, NaiveDateTime, Timelike, Datelike
 pub fn date_string_to_u64(date_str: &str) -> ParseResult<u64> {
     let formatter = "%Y-%m-%d"; //"%Y-%m-%d %H:%M:%S"
     match NaiveDate::parse_and_remainder(date_str,formatter) {
-        Ok((dt,_)) => Ok(wrap_to_u64(dt.and_hms_opt(0,0,0).unwrap().and_utc().timestamp())),
+        Ok((dt,_)) => Ok(naive_datetime_to_u64(dt.and_hms_opt(0,0,0).unwrap())),
         Err(e) => Err(e),
     }
 }
@@ -58,8 +58,7 @@ pub fn date_string_to_u64_flexible(date_str: &str) -> Result<(u64, &'static str)
 
     for format in formats {
         if let Ok(dt) = try_parse_datetime(date_str, format) {
-            let timestamp = dt.and_utc().timestamp();
-            return Ok((wrap_to_u64(timestamp), format));
+            return Ok((naive_datetime_to_u64(dt), format));
         }
     }
 
@@ -98,7 +97,7 @@ fn try_parse_datetime(date_str: &str, format: &str) -> ParseResult<NaiveDateTime
 /// A u64 timestamp, or an error if parsing fails.
 pub fn date_string_to_u64_with_format(date_str: &str, format: &str) -> Result<u64, String> {
     try_parse_datetime(date_str, format)
-        .map(|dt| wrap_to_u64(dt.and_utc().timestamp()))
+        .map(naive_datetime_to_u64)
         .map_err(|e| format!("Failed to parse '{}' with format '{}': {}", date_str, format, e))
 }
 
@@ -120,7 +119,7 @@ pub fn date_string_to_u64_with_format(date_str: &str, format: &str) -> Result<u6
 /// A date in "%Y-%m-%d" format. Partial days are truncated to fit in the "%Y-%m-%d" format.
 pub fn u64_to_date_string(value: u64) -> String {
     let formatter = "%Y-%m-%d"; //"%Y-%m-%d %H:%M:%S"
-    match DateTime::from_timestamp(wrap_to_i64(value), 0).
+    match u64_to_naive_datetime(value).
         map(|dt| format!("{}", dt.format(formatter))) {
         Some(s) => s,
         None => value.to_string(),
@@ -143,7 +142,7 @@ pub fn u64_to_date_string(value: u64) -> String {
 /// A date in "%Y-%m-%dT%H:%M:%S%.3fZ" format.
 pub fn u64_to_iso_datetime_string(value: u64) -> String {
     let formatter = "%Y-%m-%dT%H:%M:%S%.3fZ";
-    match DateTime::from_timestamp(wrap_to_i64(value), 0).
+    match u64_to_naive_datetime(value).
         map(|dt| format!("{}", dt.format(formatter))) {
         Some(s) => s,
         None => value.to_string(),
@@ -161,7 +160,7 @@ pub fn u64_to_iso_datetime_string(value: u64) -> String {
 /// * `YYYY-MM-DD` if the time is exactly midnight (00:00:00)
 /// * `YYYY-MM-DDTHH:MM:SS` if there is any partial-day information
 pub fn u64_to_auto_datetime_string(value: u64) -> String {
-    match DateTime::from_timestamp(wrap_to_i64(value), 0) {
+    match u64_to_naive_datetime(value) {
         Some(dt) => {
             // Check if it's a whole day (midnight)
             if dt.hour() == 0 && dt.minute() == 0 && dt.second() == 0 {
@@ -198,7 +197,7 @@ pub fn u64_to_date_string_for_step_size(value: u64, step_size: u64) -> String {
     } else {
         "%Y-%m-%dT%H:%M:%S"
     };
-    match DateTime::from_timestamp(wrap_to_i64(value), 0) {
+    match u64_to_naive_datetime(value) {
         Some(dt) => dt.format(format).to_string(),
         None => value.to_string(),
     }
@@ -206,7 +205,7 @@ pub fn u64_to_date_string_for_step_size(value: u64, step_size: u64) -> String {
 
 
 pub fn u64_to_year_month_day_and_seconds(value: u64) -> (i32, u32, u32, u32) {
-    match DateTime::from_timestamp(wrap_to_i64(value), 0) {
+    match u64_to_naive_datetime(value) {
         Some(dt) => {
             let y = dt.year();
             let m = dt.month();
@@ -219,10 +218,40 @@ pub fn u64_to_year_month_day_and_seconds(value: u64) -> (i32, u32, u32, u32) {
 }
 
 
+/// Converts a u64 timestamp into the signed number of seconds since 1970-01-01 (negative before
+/// then). The u64 representation is the signed value offset by 2^63, so timestamps keep their
+/// order either side of 1970 and differences between them are exact.
+pub fn u64_to_unix_seconds(value: u64) -> i64 {
+    value.wrapping_sub(1 << 63) as i64
+}
+
+/// Converts signed seconds since 1970-01-01 into a u64 timestamp (see `u64_to_unix_seconds`)
+pub fn unix_seconds_to_u64(seconds: i64) -> u64 {
+    (seconds as u64).wrapping_add(1 << 63)
+}
+
+/// Converts a u64 timestamp into a chrono datetime, or None if it is out of chrono's range
+pub fn u64_to_naive_datetime(value: u64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(u64_to_unix_seconds(value), 0).map(|dt| dt.naive_utc())
+}
+
+/// Converts a chrono datetime into a u64 timestamp
+pub fn naive_datetime_to_u64(dt: NaiveDateTime) -> u64 {
+    unix_seconds_to_u64(dt.and_utc().timestamp())
+}
+
+/// Signed number of seconds from timestamp `b` to timestamp `a`. Unlike `a - b` this does not
+/// overflow when `a` is earlier than `b`.
+pub fn seconds_between(a: u64, b: u64) -> i64 {
+    a.wrapping_sub(b) as i64
+}
+
+/// Same as `unix_seconds_to_u64`, kept for existing call sites
 pub fn wrap_to_u64(x: i64) -> u64 {
-    (x as u64).wrapping_add(u64::MAX/2 + 1)
+    unix_seconds_to_u64(x)
 }
 
+/// Same as `u64_to_unix_seconds`, kept for existing call sites
 pub fn wrap_to_i64(x: u64) -> i64 {
-    x.wrapping_sub(u64::MAX/2 + 1) as i64
+    u64_to_unix_seconds(x)
 }
@@ -0,0 +1,73 @@
+//! Water years, i.e. years that start on the first day of a month other than January
+//!
+//! Annual caps, account allocations and annual statistics are usually reckoned over a water
+//! year. In Australia that starts on 1 July. A water year is numbered by the calendar year it
+//! starts in, so the year from 2000-07-01 to 2001-06-30 is water year 2000.
+
+use chrono::NaiveDate;
+use crate::tid::utils::{u64_to_year_month_day_and_seconds, wrap_to_u64};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WaterYear {
+    start_month: u8,
+}
+
+impl Default for WaterYear {
+    fn default() -> Self {
+        WaterYear::JULY
+    }
+}
+
+impl WaterYear {
+    /// The calendar year, starting in January
+    pub const CALENDAR: WaterYear = WaterYear { start_month: 1 };
+    /// The water year starting in July
+    pub const JULY: WaterYear = WaterYear { start_month: 7 };
+
+    /// A water year starting on the first day of `start_month` (Jan=1)
+    pub fn new(start_month: u8) -> Result<WaterYear, String> {
+        if (1..=12).contains(&start_month) {
+            Ok(WaterYear { start_month })
+        } else {
+            Err(format!("Invalid water year start month {}: must be 1 to 12", start_month))
+        }
+    }
+
+    pub fn start_month(&self) -> u8 {
+        self.start_month
+    }
+
+    /// The water year that a calendar year and month (Jan=1) fall in
+    pub fn year_of_month(&self, year: i32, month: u32) -> i32 {
+        if month >= self.start_month as u32 { year } else { year - 1 }
+    }
+
+    /// The water year that a timestamp falls in
+    pub fn year_of(&self, timestamp: u64) -> i32 {
+        let (year, month, _, _) = u64_to_year_month_day_and_seconds(timestamp);
+        self.year_of_month(year, month)
+    }
+
+    /// The month's position in the water year, from 1 for the start month to 12
+    pub fn month_of_year(&self, month: u32) -> u32 {
+        (month + 12 - self.start_month as u32) % 12 + 1
+    }
+
+    /// Whether a date and time is the very start of a water year (midnight on its first day)
+    pub fn is_start(&self, month: u32, day: u32, seconds: u32) -> bool {
+        day == 1 && month == self.start_month as u32 && seconds == 0
+    }
+
+    /// Whether a timestamp is the very start of a water year
+    pub fn is_start_of_year(&self, timestamp: u64) -> bool {
+        let (_, month, day, seconds) = u64_to_year_month_day_and_seconds(timestamp);
+        self.is_start(month, day, seconds)
+    }
+
+    /// The timestamp at the start of the given water year
+    pub fn start_timestamp(&self, water_year: i32) -> u64 {
+        let date = NaiveDate::from_ymd_opt(water_year, self.start_month as u32, 1)
+            .expect("Water year start date out of range");
+        wrap_to_u64(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp())
+    }
+}
@@ -4,7 +4,17 @@
 // we copy the next value into a cache property), and then all the nodes using the value can get it
 // from there (maybe using immutable refs).
 
-use crate::numerical::mathfn::u64_subtraction;
+use std::ops::{Add, Mul, Sub};
+use crate::tid::utils::seconds_between;
+
+/// Which timesteps to keep when aligning two series with different spans
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    /// Only the timesteps both series have
+    Intersection,
+    /// Every timestep either series has, with NaN where one of them has no value
+    Union,
+}
 
 #[derive(Clone)]
 #[derive(Default)]
@@ -149,8 +159,8 @@ impl Timeseries {
             self.set_all_values_to(f64::NAN);
         } else {
             //Get the index offset. I.e. how many steps is self[0] ahead of mask[0]?
-            let mask_offset = u64_subtraction(self.start_timestamp / self.step_size,
-                                              mask.start_timestamp / self.step_size);
+            let mask_offset = seconds_between(self.start_timestamp, mask.start_timestamp)
+                .div_euclid(self.step_size as i64);
 
             //Now for each element of self, set self.value=NAN if the mask is NAN.
             for i_self in 0..self.len() {
@@ -244,6 +254,163 @@ impl Timeseries {
     }
 }
 
+impl Timeseries {
+    /*
+    Returns a new series with this series' name and step_size, starting at start_timestamp.
+     */
+    fn with_values(&self, start_timestamp: u64, values: Vec<f64>) -> Timeseries {
+        let timestamps = (0..values.len() as u64)
+            .map(|i| start_timestamp + i * self.step_size)
+            .collect();
+        Timeseries {
+            name: self.name.clone(),
+            start_timestamp,
+            step_size: self.step_size,
+            values,
+            timestamps,
+            next_played_index: 0,
+            current_played_value: f64::NAN,
+        }
+    }
+
+    /*
+    Returns the values from start_timestamp for n steps, with NaN for steps outside this series.
+    The start_timestamp must be on the same grid as this series.
+     */
+    fn values_over(&self, start_timestamp: u64, n: usize) -> Vec<f64> {
+        let step = self.step_size.max(1);
+        (0..n as u64)
+            .map(|i| {
+                let t = start_timestamp + i * step;
+                if t < self.start_timestamp {
+                    return f64::NAN;
+                }
+                let idx = ((t - self.start_timestamp) / step) as usize;
+                self.values.get(idx).copied().unwrap_or(f64::NAN)
+            })
+            .collect()
+    }
+
+    /*
+    Returns both series over a common span of timesteps. With Alignment::Intersection that is
+    the timesteps they share (possibly none), and with Alignment::Union it is every timestep
+    either has, filling the gaps with NaN. The series must have the same step_size, and their
+    timestamps must fall on the same grid.
+     */
+    pub fn align(&self, other: &Timeseries, alignment: Alignment) -> Result<(Timeseries, Timeseries), String> {
+        if self.step_size != other.step_size {
+            return Err(format!(
+                "Series have different step sizes ({} and {})", self.step_size, other.step_size
+            ));
+        }
+        let step = self.step_size.max(1);
+        if !self.start_timestamp.abs_diff(other.start_timestamp).is_multiple_of(step) {
+            return Err(format!(
+                "Series timestamps are not aligned ({} and {} with step size {})",
+                self.start_timestamp, other.start_timestamp, self.step_size
+            ));
+        }
+
+        // Spans are [start, end) in timestamps, ignoring empty series for a union
+        let end = |ts: &Timeseries| ts.start_timestamp + ts.len() as u64 * step;
+        let (start, stop) = match alignment {
+            Alignment::Intersection => (
+                self.start_timestamp.max(other.start_timestamp),
+                end(self).min(end(other)),
+            ),
+            Alignment::Union => match (self.len(), other.len()) {
+                (0, 0) => (self.start_timestamp, self.start_timestamp),
+                (0, _) => (other.start_timestamp, end(other)),
+                (_, 0) => (self.start_timestamp, end(self)),
+                _ => (self.start_timestamp.min(other.start_timestamp), end(self).max(end(other))),
+            },
+        };
+        let n = if stop > start { ((stop - start) / step) as usize } else { 0 };
+        Ok((
+            self.with_values(start, self.values_over(start, n)),
+            other.with_values(start, other.values_over(start, n)),
+        ))
+    }
+
+    /*
+    Combines two series value by value over the timesteps they share.
+     */
+    pub fn zip_with(&self, other: &Timeseries, f: impl Fn(f64, f64) -> f64) -> Result<Timeseries, String> {
+        let (a, b) = self.align(other, Alignment::Intersection)?;
+        let values = a.values.iter().zip(&b.values).map(|(&x, &y)| f(x, y)).collect();
+        Ok(a.with_values(a.start_timestamp, values))
+    }
+
+    /*
+    Applies a function to every value.
+     */
+    pub fn map(&self, mut f: impl FnMut(f64) -> f64) -> Timeseries {
+        self.with_values(self.start_timestamp, self.values.iter().map(|&v| f(v)).collect())
+    }
+
+    /*
+    Returns the series with its values moved later by the given number of steps (earlier if
+    negative), keeping the same timestamps. Steps with no value are NaN.
+     */
+    pub fn shift(&self, steps: i64) -> Timeseries {
+        let n = self.len() as i64;
+        let values = (0..n)
+            .map(|i| {
+                let source = i - steps;
+                if source >= 0 && source < n { self.values[source as usize] } else { f64::NAN }
+            })
+            .collect();
+        self.with_values(self.start_timestamp, values)
+    }
+
+    /*
+    Limits values to the range [min, max]. Missing values stay missing.
+     */
+    pub fn clip(&self, min: f64, max: f64) -> Timeseries {
+        self.map(|v| v.clamp(min, max))
+    }
+
+    /*
+    Returns the running total of the values, including any non-finite values (so a NaN makes
+    the rest of the total NaN, as for sum()).
+     */
+    pub fn cumulative_sum(&self) -> Timeseries {
+        let mut total = 0.0;
+        self.map(|v| {
+            total += v;
+            total
+        })
+    }
+}
+
+/*
+Elementwise arithmetic over the timesteps both series share. These panic if the series have
+different step sizes or unaligned timestamps; use zip_with() to handle that as an error.
+ */
+macro_rules! impl_elementwise_op {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait<&Timeseries> for &Timeseries {
+            type Output = Timeseries;
+
+            fn $method(self, other: &Timeseries) -> Timeseries {
+                self.zip_with(other, |x, y| x $op y).unwrap_or_else(|e| panic!("{}", e))
+            }
+        }
+
+        impl $trait<f64> for &Timeseries {
+            type Output = Timeseries;
+
+            fn $method(self, other: f64) -> Timeseries {
+                self.map(|x| x $op other)
+            }
+        }
+    };
+}
+
+impl_elementwise_op!(Add, add, +);
+impl_elementwise_op!(Sub, sub, -);
+impl_elementwise_op!(Mul, mul, *);
+
 /// Create a new vector of a given value
 /// TODO: there is probably a better way than this
 pub fn new_vector(value: f64, length: i32) -> Vec<f64> {
@@ -1,6 +1,7 @@
 use crate::timeseries::Timeseries;
 use crate::misc::misc_functions::sanitize_name;
 use std::path::Path;
+use std::sync::Arc;
 
 #[derive(Clone)]
 #[derive(Default)]
@@ -14,7 +15,7 @@ pub struct TimeseriesInput {
     pub full_colname_path: String,  //This is the full name of the series within the model, using the column name, e.g. "data.flow_data.GS123456_flow"
     pub alias_colindex_path: Option<String>, //Alias-based reference using index, e.g. "data.climate.by_index.1"
     pub alias_colname_path: Option<String>,  //Alias-based reference using column name, e.g. "data.climate.by_name.rainfall"
-    pub timeseries: Timeseries,     //The data
+    pub timeseries: Arc<Timeseries>, //The data, shared with clones of the model
     pub reload_on_run: bool,        //Whether we want to reload the data for this series into the data_cache between runs
 }
 
@@ -33,52 +34,61 @@ impl TimeseriesInput {
     /// * `alias` - Optional user-provided alias for this file (e.g., "climate" instead of "climate_data_2020_csv")
     pub fn load(file_path: &str, alias: Option<&str>) -> Result<Vec<TimeseriesInput>, String> {
         match crate::io::csv_io::read_ts(file_path) {
-            Ok(vts) => {
-                let mut vinputts: Vec<TimeseriesInput> = vec![];
+            Ok(vts) => Ok(Self::from_timeseries(vts, file_path, alias)),
+            Err(s) => {
+                Err(format!("Error reading {}: {}", file_path, s))
+            }
+        }
+    }
+
+    /// Like [`TimeseriesInput::load`], but for CSV text that is already in memory (e.g. in a
+    /// WebAssembly build, which has no file system). `file_name` stands in for the file path,
+    /// so the series are addressed as `data.<file_name>.*` as if the file had been loaded.
+    pub fn load_from_str(csv_text: &str, file_name: &str, alias: Option<&str>) -> Result<Vec<TimeseriesInput>, String> {
+        let vts = crate::io::csv_io::read_ts_from_str(csv_text, file_name)
+            .map_err(|s| format!("Error reading {}: {}", file_name, s))?;
+        Ok(Self::from_timeseries(vts, file_name, alias))
+    }
 
-                // Create an object for each and add it
-                for i in 0..vts.len() {
-                    let mut inputts = TimeseriesInput::new();
-                    let col_name = vts[i].name.clone();
-                    let col_index = i + 1;
-                    inputts.source_path = file_path.to_string();
-                    let path = Path::new(file_path);
+    fn from_timeseries(vts: Vec<Timeseries>, file_path: &str, alias: Option<&str>) -> Vec<TimeseriesInput> {
+        let mut vinputts: Vec<TimeseriesInput> = vec![];
 
-                    // Sanitize the source name (filename)
-                    let source_name_raw = path.file_name().unwrap().to_str().unwrap().to_owned();
-                    let source_name = sanitize_name(&source_name_raw);
+        // Create an object for each and add it
+        for (i, ts) in vts.into_iter().enumerate() {
+            let mut inputts = TimeseriesInput::new();
+            let col_name = ts.name.clone();
+            let col_index = i + 1;
+            inputts.source_path = file_path.to_string();
+            let path = Path::new(file_path);
 
-                    // Sanitize the column name
-                    let col_name_sanitized = sanitize_name(&col_name);
+            // Sanitize the source name (filename)
+            let source_name_raw = path.file_name().unwrap().to_str().unwrap().to_owned();
+            let source_name = sanitize_name(&source_name_raw);
 
-                    inputts.source_name = source_name.clone();
-                    inputts.col_index = col_index;
-                    inputts.col_name = col_name.clone();
+            // Sanitize the column name
+            let col_name_sanitized = sanitize_name(&col_name);
 
-                    inputts.full_colname_path = format!("data.{}.by_name.{}", source_name, col_name_sanitized);
-                    inputts.full_colindex_path = format!("data.{}.by_index.{}", source_name, col_index);
+            inputts.source_name = source_name.clone();
+            inputts.col_index = col_index;
+            inputts.col_name = col_name.clone();
 
-                    if let Some(alias_str) = alias {
-                        let alias_sanitized = sanitize_name(alias_str);
-                        inputts.alias = Some(alias_sanitized.clone());
-                        inputts.alias_colname_path = Some(format!("data.{}.by_name.{}", alias_sanitized, col_name_sanitized));
-                        inputts.alias_colindex_path = Some(format!("data.{}.by_index.{}", alias_sanitized, col_index));
-                    }
+            inputts.full_colname_path = format!("data.{}.by_name.{}", source_name, col_name_sanitized);
+            inputts.full_colindex_path = format!("data.{}.by_index.{}", source_name, col_index);
 
-                    inputts.timeseries = vts[i].clone();
-                    inputts.reload_on_run = false;
-                    vinputts.push(inputts);
-                }
-                Ok(vinputts)
-            }
-            Err(s) => {
-                Err(format!("Error reading {}: {}", file_path, s))
+            if let Some(alias_str) = alias {
+                let alias_sanitized = sanitize_name(alias_str);
+                inputts.alias = Some(alias_sanitized.clone());
+                inputts.alias_colname_path = Some(format!("data.{}.by_name.{}", alias_sanitized, col_name_sanitized));
+                inputts.alias_colindex_path = Some(format!("data.{}.by_index.{}", alias_sanitized, col_index));
             }
+
+            inputts.timeseries = Arc::new(ts);
+            inputts.reload_on_run = false;
+            vinputts.push(inputts);
         }
+        vinputts
     }
 
-
-    /// Returns the length of the contained timeseries.
     pub fn len(&self) -> usize {
         self.timeseries.len()
     }